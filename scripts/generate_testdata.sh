@@ -4,6 +4,19 @@ set -e
 # Script to generate gensort testdata of specified size in GiB
 # Usage: ./scripts/generate_testdata.sh <size_in_gib> <output_file>
 # Example: ./scripts/generate_testdata.sh 5 testdata/test_gensort_5gb.dat
+# Set SEED=<u64> to get the same file on every run and machine,
+# and THREADS=<n> to generate in parallel (the file is the same either way).
+# DISTRIBUTION=zipf (with optional ZIPF_S=<s>) generates skewed keys, and
+# DISTINCT_KEYS=<n> or DUPLICATE_RATIO=<0..1> controls how often keys repeat,
+# and HEAVY_HITTERS=<k> with HEAVY_FRACTION=<0..1> gives k keys that fraction.
+# ASCII=1 writes printable records like `gensort -a`, and CHECKSUM=1 writes
+# the data's checksum to a .sum file next to it. PAYLOAD_ENTROPY=low|medium|high
+# controls how well payloads compress. KEY_SIZE=<bytes> and PAYLOAD_SIZE=<bytes>
+# change the record layout (load it with the same --key-size/--payload-size).
+# OUTPUT_FORMAT=parquet (with optional ROW_GROUP_SIZE=<rows>) writes a Parquet
+# file instead; the generator must be built with the util-parquet feature.
+# OUTPUT_FORMAT=csv (with optional CSV_ENCODING=hex|base64) writes CSV lines.
+# SCHEMA=wide adds typed columns to each payload (load with --schema wide).
 
 if [ "$#" -ne 2 ]; then
     echo "Usage: $0 <size_in_gib> <output_file>"
@@ -14,8 +27,10 @@ fi
 SIZE_GIB=$1
 OUTPUT_FILE=$2
 
-# Each gensort record is 100 bytes (10-byte key + 90-byte payload)
-RECORD_SIZE=100
+# Each gensort record is 100 bytes (10-byte key + 90-byte payload) by default
+KEY_SIZE=${KEY_SIZE:-10}
+PAYLOAD_SIZE=${PAYLOAD_SIZE:-90}
+RECORD_SIZE=$((KEY_SIZE + PAYLOAD_SIZE))
 
 # Calculate number of records needed
 # 1 GiB = 1024^3 bytes = 1,073,741,824 bytes
@@ -26,6 +41,9 @@ NUM_RECORDS=$(echo "$TOTAL_BYTES / $RECORD_SIZE" | bc)
 echo "Generating ${SIZE_GIB} GiB of gensort testdata..."
 echo "Records to generate: ${NUM_RECORDS}"
 echo "Output file: ${OUTPUT_FILE}"
+if [ -n "$SEED" ]; then
+    echo "Seed: ${SEED}"
+fi
 echo ""
 
 # Create testdata directory if it doesn't exist
@@ -39,7 +57,24 @@ cargo build --release --bin generate-gensort
 echo "Generating data..."
 ./target/release/generate-gensort \
     --output "$OUTPUT_FILE" \
-    --num-records "$NUM_RECORDS"
+    --num-records "$NUM_RECORDS" \
+    --key-size "$KEY_SIZE" \
+    --payload-size "$PAYLOAD_SIZE" \
+    ${SEED:+--seed "$SEED"} \
+    ${THREADS:+--threads "$THREADS"} \
+    ${DISTRIBUTION:+--distribution "$DISTRIBUTION"} \
+    ${ZIPF_S:+--zipf-s "$ZIPF_S"} \
+    ${DISTINCT_KEYS:+--distinct-keys "$DISTINCT_KEYS"} \
+    ${DUPLICATE_RATIO:+--duplicate-ratio "$DUPLICATE_RATIO"} \
+    ${HEAVY_HITTERS:+--heavy-hitters "$HEAVY_HITTERS"} \
+    ${HEAVY_FRACTION:+--heavy-fraction "$HEAVY_FRACTION"} \
+    ${ASCII:+--ascii} \
+    ${CHECKSUM:+--checksum} \
+    ${PAYLOAD_ENTROPY:+--payload-entropy "$PAYLOAD_ENTROPY"} \
+    ${OUTPUT_FORMAT:+--output-format "$OUTPUT_FORMAT"} \
+    ${ROW_GROUP_SIZE:+--row-group-size "$ROW_GROUP_SIZE"} \
+    ${CSV_ENCODING:+--csv-encoding "$CSV_ENCODING"} \
+    ${SCHEMA:+--schema "$SCHEMA"}
 
 echo ""
 echo "Generation complete!"
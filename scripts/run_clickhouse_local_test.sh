@@ -12,6 +12,8 @@ CLICKHOUSE_PID="${SCRIPT_DIR}/clickhouse.pid"
 
 HTTP_PORT="${HTTP_PORT:-8123}"
 TCP_PORT="${TCP_PORT:-9000}"
+# Optional hard memory cap for the server (cgroup v2, asks for sudo), e.g. 8GB
+CGROUP_MEMORY_LIMIT="${CGROUP_MEMORY_LIMIT:-}"
 
 # 1. Download ClickHouse if not present
 if [[ ! -x "${CLICKHOUSE_BIN_DIR}/clickhouse" ]]; then
@@ -94,9 +96,17 @@ if curl -s "http://localhost:${HTTP_PORT}/ping" > /dev/null; then
 else
   echo "Starting ClickHouse server..."
   
+  LAUNCH=()
+  if [[ -n "${CGROUP_MEMORY_LIMIT}" ]]; then
+    (cd "${SCRIPT_DIR}/.." && cargo build --release --bin run-in-cgroup)
+    echo "Capping ClickHouse memory at ${CGROUP_MEMORY_LIMIT} via cgroup v2"
+    LAUNCH=(sudo "${SCRIPT_DIR}/../target/release/run-in-cgroup"
+      --cgroup-memory-limit "${CGROUP_MEMORY_LIMIT}" --name clickhouse --)
+  fi
+
   # Note: we use --daemon to run in background.
   # Configuration parameters are passed as XML-style overrides after --
-  "${CLICKHOUSE_BIN_DIR}/clickhouse" server \
+  ${LAUNCH[@]+"${LAUNCH[@]}"} "${CLICKHOUSE_BIN_DIR}/clickhouse" server \
     --daemon \
     --pid-file="${CLICKHOUSE_PID}" \
     --log-file="${CLICKHOUSE_LOG}" \
@@ -23,6 +23,8 @@ POSTGRES_PORT="${POSTGRES_PORT:-5433}"
 POSTGRES_USER="${POSTGRES_USER:-postgres}"
 POSTGRES_DB="${POSTGRES_DB:-es_duck_test}"
 POSTGRES_HOST="${POSTGRES_HOST:-localhost}"
+# Optional hard memory cap for the server (cgroup v2, asks for sudo), e.g. 8GB
+CGROUP_MEMORY_LIMIT="${CGROUP_MEMORY_LIMIT:-}"
 
 # Download PostgreSQL if not already present
 if [[ ! -x "${POSTGRES_DIR}/bin/postgres" ]]; then
@@ -150,7 +152,14 @@ if pg_isready -h "${POSTGRES_HOST}" -p "${POSTGRES_PORT}" >/dev/null 2>&1; then
   echo "PostgreSQL is already running on ${POSTGRES_HOST}:${POSTGRES_PORT}"
 else
   echo "Starting PostgreSQL server on port ${POSTGRES_PORT}..."
-  pg_ctl -D "${POSTGRES_DATA_DIR}" -l "${POSTGRES_LOG}" \
+  LAUNCH=()
+  if [[ -n "${CGROUP_MEMORY_LIMIT}" ]]; then
+    (cd "${SCRIPT_DIR}/.." && cargo build --release --bin run-in-cgroup)
+    echo "Capping PostgreSQL memory at ${CGROUP_MEMORY_LIMIT} via cgroup v2"
+    LAUNCH=(sudo "${SCRIPT_DIR}/../target/release/run-in-cgroup"
+      --cgroup-memory-limit "${CGROUP_MEMORY_LIMIT}" --name postgres --)
+  fi
+  ${LAUNCH[@]+"${LAUNCH[@]}"} "$(command -v pg_ctl)" -D "${POSTGRES_DATA_DIR}" -l "${POSTGRES_LOG}" \
     -o "-p ${POSTGRES_PORT} -k ${SCRIPT_DIR} -c max_worker_processes=128 -c max_parallel_workers=128 -c max_connections=200" \
     start
 
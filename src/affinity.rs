@@ -0,0 +1,152 @@
+//! CPU pinning and NUMA placement.
+//!
+//! On multi-socket machines the scheduler moves threads across sockets and
+//! memory gets allocated on whichever node touched it first, which makes
+//! run-to-run numbers noisy. `--cpus` restricts threads to a fixed set of
+//! cores; `--numa-node` uses that node's cores and binds memory to it as well.
+
+use std::error::Error;
+use std::fs;
+use std::sync::OnceLock;
+
+/// Parses a Linux CPU list such as "0-15,32-47" or "3"
+pub fn parse_cpu_list(list: &str) -> Result<Vec<usize>, Box<dyn Error + Send + Sync>> {
+    let mut cpus = Vec::new();
+    for part in list.trim().split(',').filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let first: usize = first.trim().parse()?;
+                let last: usize = last.trim().parse()?;
+                if first > last {
+                    return Err(format!("Invalid CPU range '{}'", part).into());
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(part.trim().parse()?),
+        }
+    }
+    if cpus.is_empty() {
+        return Err(format!("Empty CPU list '{}'", list).into());
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+    Ok(cpus)
+}
+
+/// CPUs that belong to a NUMA node, from sysfs
+pub fn numa_node_cpus(node: usize) -> Result<Vec<usize>, Box<dyn Error + Send + Sync>> {
+    let path = format!("/sys/devices/system/node/node{}/cpulist", node);
+    let list = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read {} (no such NUMA node?): {}", path, e))?;
+    parse_cpu_list(&list)
+}
+
+/// Restricts the calling thread to `cpus`. Threads spawned afterwards inherit the mask.
+pub fn pin_current_thread(cpus: &[usize]) -> Result<(), Box<dyn Error + Send + Sync>> {
+    // SAFETY: cpu_set_t is plain data, and CPU_ZERO/CPU_SET only touch the set we own
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for &cpu in cpus {
+            if cpu >= libc::CPU_SETSIZE as usize {
+                return Err(format!("CPU {} is out of range", cpu).into());
+            }
+            libc::CPU_SET(cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(format!(
+                "sched_setaffinity failed: {}",
+                std::io::Error::last_os_error()
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Binds future memory allocations of the calling thread (and threads it spawns) to `node`
+pub fn bind_memory_to_node(node: usize) -> Result<(), Box<dyn Error + Send + Sync>> {
+    const MPOL_BIND: libc::c_int = 2;
+    const MASK_BITS: usize = 1024;
+
+    if node >= MASK_BITS {
+        return Err(format!("NUMA node {} is out of range", node).into());
+    }
+    let mut mask = [0 as libc::c_ulong; MASK_BITS / libc::c_ulong::BITS as usize];
+    let bits = libc::c_ulong::BITS as usize;
+    mask[node / bits] |= 1 << (node % bits);
+
+    // SAFETY: the mask outlives the call and maxnode matches its size in bits
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_set_mempolicy,
+            MPOL_BIND,
+            mask.as_ptr(),
+            MASK_BITS as libc::c_ulong,
+        )
+    };
+    if ret != 0 {
+        return Err(format!("set_mempolicy failed: {}", std::io::Error::last_os_error()).into());
+    }
+    Ok(())
+}
+
+/// Resolves `--cpus` / `--numa-node` into the CPU set to pin to, if any
+fn resolve_cpus(
+    cpus: Option<&str>,
+    numa_node: Option<usize>,
+) -> Result<Option<Vec<usize>>, Box<dyn Error + Send + Sync>> {
+    match (cpus, numa_node) {
+        (Some(list), _) => Ok(Some(parse_cpu_list(list)?)),
+        (None, Some(node)) => Ok(Some(numa_node_cpus(node)?)),
+        (None, None) => Ok(None),
+    }
+}
+
+struct Placement {
+    cpus: Option<Vec<usize>>,
+    numa_node: Option<usize>,
+}
+
+static PLACEMENT: OnceLock<Placement> = OnceLock::new();
+
+/// Applies `--cpus` / `--numa-node` to the calling thread. Call it early in `main`,
+/// before spawning threads, so they all inherit the placement. `--cpus` takes
+/// precedence over the node's CPU list; memory is bound whenever a node is given.
+pub fn apply(
+    cpus: Option<&str>,
+    numa_node: Option<usize>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let placement = Placement {
+        cpus: resolve_cpus(cpus, numa_node)?,
+        numa_node,
+    };
+    place_current_thread(&placement)?;
+    if let Some(ref set) = placement.cpus {
+        println!("Pinned to {} CPUs: {:?}", set.len(), set);
+    }
+    if let Some(node) = numa_node {
+        println!("Bound memory allocations to NUMA node {}", node);
+    }
+    let _ = PLACEMENT.set(placement);
+    Ok(())
+}
+
+/// Applies the placement chosen by `apply` to a thread that did not inherit it
+/// from the main thread (e.g. tokio's blocking pool). Does nothing otherwise.
+pub fn pin_worker() -> Result<(), Box<dyn Error + Send + Sync>> {
+    match PLACEMENT.get() {
+        Some(placement) => place_current_thread(placement),
+        None => Ok(()),
+    }
+}
+
+fn place_current_thread(placement: &Placement) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(ref set) = placement.cpus {
+        pin_current_thread(set)?;
+    }
+    if let Some(node) = placement.numa_node {
+        bind_memory_to_node(node)?;
+    }
+    Ok(())
+}
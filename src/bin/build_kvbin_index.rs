@@ -0,0 +1,61 @@
+use clap::Parser;
+use es_duck::cgroup::parse_memory_to_bytes;
+use es_duck::kvbin_index::{self, Spacing};
+use std::error::Error;
+use std::fs::File;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser)]
+#[command(name = "build-kvbin-index")]
+#[command(about = "Write the .idx offset file the parallel kvbin loaders use")]
+struct Args {
+    /// kvbin file to index
+    #[arg(long)]
+    input: PathBuf,
+
+    /// Index file to write (default: <input>.idx, where the loaders look for it)
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Add an entry every this many records
+    #[arg(long, conflicts_with = "every_bytes")]
+    every_records: Option<u64>,
+
+    /// Add an entry at the first record after every this many bytes (e.g. "64MB")
+    #[arg(long, value_parser = |s: &str| parse_memory_to_bytes(s).map_err(|e| e.to_string()))]
+    every_bytes: Option<u64>,
+}
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let args = Args::parse();
+
+    let spacing = match (args.every_records, args.every_bytes) {
+        (_, Some(0)) | (Some(0), _) => return Err("Index spacing must be at least 1".into()),
+        (Some(n), _) => Spacing::Records(n),
+        (None, Some(n)) => Spacing::Bytes(n),
+        // Same default as generate-kvbin --index-interval
+        (None, None) => Spacing::Records(100_000),
+    };
+    let output = args
+        .output
+        .unwrap_or_else(|| kvbin_index::path_for(&args.input));
+
+    println!("Scanning {:?} ({:?})...", args.input, spacing);
+    let start = Instant::now();
+    let file =
+        File::open(&args.input).map_err(|e| format!("Failed to open {:?}: {}", args.input, e))?;
+    let index = kvbin_index::scan(file, spacing)
+        .map_err(|e| format!("Failed to scan {:?}: {}", args.input, e))?;
+    kvbin_index::write(&output, &index.offsets)
+        .map_err(|e| format!("Failed to write {:?}: {}", output, e))?;
+
+    println!(
+        "Indexed {} records ({:.2} MB) in {:.2} seconds",
+        index.records,
+        index.bytes as f64 / (1024.0 * 1024.0),
+        start.elapsed().as_secs_f64()
+    );
+    println!("Wrote {} offsets to {:?}", index.offsets.len(), output);
+    Ok(())
+}
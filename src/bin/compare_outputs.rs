@@ -0,0 +1,445 @@
+use clap::Parser;
+use duckdb::Connection;
+use es_duck::checksum::Checksum;
+use es_duck::export::{self, OutputFormat, RowWithId};
+use es_duck::schema;
+use es_duck::telemetry;
+use es_duck::verify::{Verifier, hex};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
+use std::thread::{self, JoinHandle};
+use tracing::{Span, info_span};
+
+type RecordBatch = Vec<RowWithId>;
+/// A row of an equal-key run: its row id, if the file carries them, and payload
+type RunRow = (Option<u64>, Vec<u8>);
+
+const BATCH_SIZE: usize = 10_000;
+
+#[derive(Parser)]
+#[command(name = "compare-outputs")]
+#[command(about = "Check that the sorted outputs of different engines contain identical rows")]
+struct Args {
+    /// Sorted output of sort-duckdb
+    #[arg(long)]
+    duckdb: Option<PathBuf>,
+
+    /// Sorted output of sort-postgres
+    #[arg(long)]
+    postgres: Option<PathBuf>,
+
+    /// Sorted output of sort-clickhouse
+    #[arg(long)]
+    clickhouse: Option<PathBuf>,
+
+    /// Format of every file (see the sorters' --output-format). Without it each
+    /// file's extension says, and else the format its sorter writes by default
+    /// (DuckDB Parquet, PostgreSQL binary COPY, ClickHouse Native).
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Fail (instead of warn) when rows with equal keys appear in a different order
+    #[arg(long)]
+    strict_ties: bool,
+
+    /// The `.sum` file of the sorted gensort input (generate-gensort
+    /// --checksum): every output's key and payload records must add up to it,
+    /// which catches a row lost from every output alike, or made up for by a
+    /// doubled one
+    #[arg(long, value_name = "SUM_FILE")]
+    checksum: Option<PathBuf>,
+}
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let args = Args::parse();
+    let _telemetry = telemetry::init("compare-outputs");
+    let _span = info_span!("compare").entered();
+
+    let inputs: [(&'static str, Option<PathBuf>, OutputFormat); 3] = [
+        ("duckdb", args.duckdb, OutputFormat::Parquet),
+        ("postgres", args.postgres, OutputFormat::Pgbinary),
+        ("clickhouse", args.clickhouse, OutputFormat::Native),
+    ];
+
+    let expected_sum = args.checksum.as_deref().map(Checksum::read).transpose()?;
+    let mut streams: Vec<OutputStream> = inputs
+        .into_iter()
+        .filter_map(|(engine, path, default)| {
+            path.map(|p| {
+                let format = OutputFormat::resolve(args.format, &p, default);
+                OutputStream::spawn(engine, p, format, expected_sum.is_some())
+            })
+        })
+        .collect();
+
+    if streams.len() < 2 {
+        eprintln!("Error: at least two of --duckdb, --postgres, --clickhouse are required.");
+        std::process::exit(1);
+    }
+
+    let reference = streams[0].engine;
+    println!(
+        "Comparing {} against {}...",
+        streams[1..]
+            .iter()
+            .map(|s| s.engine)
+            .collect::<Vec<_>>()
+            .join(", "),
+        reference
+    );
+
+    while let Some((key, run)) = streams[0].next_run() {
+        for stream in streams[1..].iter_mut().filter(|s| s.failure.is_none()) {
+            let offset = stream.rows;
+            let Some((other_key, other_run)) = stream.next_run() else {
+                stream.fail(format!(
+                    "output ends at row {}, {} still has key {}",
+                    offset,
+                    reference,
+                    hex(&key)
+                ));
+                continue;
+            };
+
+            // Runs of row ids can tell which row went missing
+            let ids = run[0].0.is_some() && other_run[0].0.is_some();
+            if other_key != key || (other_run.len() != run.len() && !ids) {
+                stream.fail(format!(
+                    "key sequence differs at row {}: {} has {} x{}, found {} x{}",
+                    offset,
+                    reference,
+                    hex(&key),
+                    run.len(),
+                    hex(&other_key),
+                    other_run.len()
+                ));
+            } else if other_run != run {
+                // Same keys but different row order: either a tie was broken
+                // differently, or rows were lost or corrupted
+                let mut expected = run.clone();
+                let mut actual = other_run;
+                expected.sort_unstable();
+                actual.sort_unstable();
+                if expected == actual {
+                    stream.reordered_runs += 1;
+                } else {
+                    stream.fail(run_difference(&key, offset, reference, &expected, &actual));
+                }
+            }
+        }
+
+        if streams[0].failure.is_some() {
+            break;
+        }
+    }
+
+    if streams[0].failure.is_none() {
+        for stream in streams[1..].iter_mut().filter(|s| s.failure.is_none()) {
+            let offset = stream.rows;
+            if let Some((key, _)) = stream.next_run() {
+                stream.fail(format!(
+                    "extra rows after row {}, starting with key {}",
+                    offset,
+                    hex(&key)
+                ));
+            }
+        }
+    }
+
+    // Drain everything so row counts are complete and reader threads can finish
+    for stream in streams.iter_mut() {
+        while stream.next_run().is_some() {}
+        if let (Some(expected), Some(sum)) = (expected_sum, stream.checksum) {
+            if sum.records != expected.records {
+                stream.fail(format!(
+                    "{} records, the input checksum has {}",
+                    sum.records, expected.records
+                ));
+            } else if sum.sum != expected.sum {
+                stream.fail(format!(
+                    "checksum {:x} differs from the input's {:x}: records were lost \
+                     or corrupted",
+                    sum.sum, expected.sum
+                ));
+            }
+        }
+    }
+
+    let mut failed = false;
+    println!("\n===== OUTPUT EQUIVALENCE =====");
+    for stream in streams {
+        let engine = stream.engine;
+        let rows = stream.rows;
+        let reordered_runs = stream.reordered_runs;
+        let ids = stream.verifier.row_ids();
+        let unstable_ties = stream.verifier.unstable_ties();
+        let failure = stream.failure;
+
+        match stream.handle.join() {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => return Err(format!("Failed to read {} output: {}", engine, e).into()),
+            Err(_) => return Err(format!("Reader thread for {} panicked", engine).into()),
+        }
+
+        if let Some(failure) = failure {
+            println!("{}: MISMATCH ({} rows) - {}", engine, rows, failure);
+            failed = true;
+        } else if reordered_runs > 0 {
+            println!(
+                "{}: {} rows, {} equal-key runs in a different order than {}",
+                engine, rows, reordered_runs, reference
+            );
+            failed |= args.strict_ties;
+        } else if expected_sum.is_some() {
+            println!("{}: {} rows, OK (checksum matches)", engine, rows);
+        } else {
+            println!("{}: {} rows, OK", engine, rows);
+        }
+        match (ids, unstable_ties) {
+            (None, _) => {}
+            (Some(_), 0) => println!("{}: equal keys in row id order (stable)", engine),
+            (Some(_), n) => println!(
+                "{}: {} equal keys out of row id order (unstable)",
+                engine, n
+            ),
+        }
+    }
+    println!("==============================\n");
+
+    if failed {
+        return Err("Sorted outputs are not equivalent".into());
+    }
+
+    println!("All outputs are equivalent.");
+    Ok(())
+}
+
+/// Sorted rows from one engine, read on a background thread
+struct OutputStream {
+    engine: &'static str,
+    rx: Receiver<RecordBatch>,
+    handle: JoinHandle<Result<u64, Box<dyn Error + Send + Sync>>>,
+    batch: std::vec::IntoIter<RowWithId>,
+    pending: Option<RowWithId>,
+    rows: u64,
+    reordered_runs: u64,
+    /// Checks the file's own order and counts its row ids and unstable ties
+    verifier: Verifier,
+    /// The records' checksum, when there is one to compare against
+    checksum: Option<Checksum>,
+    failure: Option<String>,
+}
+
+impl OutputStream {
+    fn spawn(engine: &'static str, path: PathBuf, format: OutputFormat, checksum: bool) -> Self {
+        let (tx, rx) = sync_channel::<RecordBatch>(4);
+        let span = info_span!(parent: Span::current(), "file_read", engine, format = format.name(), path = %path.display());
+        let handle = thread::spawn(move || {
+            let _span = span.entered();
+            match format {
+                OutputFormat::Parquet => read_parquet(&path, &tx),
+                _ => read_rows(&path, format, &tx),
+            }
+        });
+
+        Self {
+            engine,
+            rx,
+            handle,
+            batch: Vec::new().into_iter(),
+            pending: None,
+            rows: 0,
+            reordered_runs: 0,
+            verifier: Verifier::new(true),
+            checksum: checksum.then(Checksum::new),
+            failure: None,
+        }
+    }
+
+    fn next_record(&mut self) -> Option<RowWithId> {
+        if let Some(record) = self.pending.take() {
+            return Some(record);
+        }
+        loop {
+            if let Some(record) = self.batch.next() {
+                return Some(record);
+            }
+            self.batch = self.rx.recv().ok()?.into_iter();
+        }
+    }
+
+    /// Returns the next key together with the row ids and payloads of all
+    /// rows sharing it
+    fn next_run(&mut self) -> Option<(Vec<u8>, Vec<RunRow>)> {
+        let ((key, payload), id) = self.next_record()?;
+        let mut run = vec![(id, payload)];
+
+        while let Some(((next_key, next_payload), next_id)) = self.next_record() {
+            if next_key != key {
+                self.pending = Some(((next_key, next_payload), next_id));
+                break;
+            }
+            run.push((next_id, next_payload));
+        }
+
+        for (id, payload) in &run {
+            if let Err(e) = self.verifier.add_row(&key, *id) {
+                self.fail(e);
+            }
+            if let Some(ref mut checksum) = self.checksum {
+                checksum.add(&[key.as_slice(), payload].concat());
+            }
+        }
+        self.rows += run.len() as u64;
+        Some((key, run))
+    }
+
+    fn fail(&mut self, reason: String) {
+        if self.failure.is_none() {
+            self.failure = Some(reason);
+        }
+    }
+}
+
+/// What differs between two runs of `key`, both sorted: the first row id one
+/// has and the other lacks, or the first differing payload
+fn run_difference(
+    key: &[u8],
+    offset: u64,
+    reference: &str,
+    expected: &[RunRow],
+    actual: &[RunRow],
+) -> String {
+    let mut expected = expected.iter();
+    let mut actual = actual.iter();
+    loop {
+        match (expected.next(), actual.next()) {
+            (Some((Some(a), _)), Some((Some(b), _))) if a < b => {
+                return format!(
+                    "row id {} under key {} at row {} is missing, {} has it",
+                    a,
+                    hex(key),
+                    offset,
+                    reference
+                );
+            }
+            (Some((Some(a), _)), Some((Some(b), _))) if a > b => {
+                return format!(
+                    "row id {} under key {} at row {} is extra, {} lacks it",
+                    b,
+                    hex(key),
+                    offset,
+                    reference
+                );
+            }
+            (Some((Some(id), _)), None) => {
+                return format!(
+                    "row id {} under key {} at row {} is missing, {} has it",
+                    id,
+                    hex(key),
+                    offset,
+                    reference
+                );
+            }
+            (None, Some((Some(id), _))) => {
+                return format!(
+                    "row id {} under key {} at row {} is extra, {} lacks it",
+                    id,
+                    hex(key),
+                    offset,
+                    reference
+                );
+            }
+            (Some(a), Some(b)) if a == b => continue,
+            _ => {
+                return format!(
+                    "payloads for key {} at row {} differ from {}",
+                    hex(key),
+                    offset,
+                    reference
+                );
+            }
+        }
+    }
+}
+
+/// Reads a Parquet file, preserving file order, with its row ids if it has them
+fn read_parquet(
+    path: &Path,
+    tx: &SyncSender<RecordBatch>,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let conn = Connection::open_in_memory()?;
+    conn.execute("SET preserve_insertion_order = true;", [])?;
+
+    let path = path.display().to_string().replace('\'', "''");
+    let row_ids: bool = conn.query_row(
+        &format!(
+            "SELECT count(*) > 0 FROM parquet_schema('{}') WHERE name = '{}'",
+            path,
+            schema::ROW_ID_COLUMN
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    let columns = match row_ids {
+        // BIGINT from DuckDB, UInt64 from ClickHouse
+        true => format!("sort_key, payload, {}::UBIGINT", schema::ROW_ID_COLUMN),
+        false => "sort_key, payload".to_string(),
+    };
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM read_parquet('{}')", columns, path))?;
+    let mut rows = stmt.query([])?;
+
+    let mut count = 0u64;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    while let Some(row) = rows.next()? {
+        let id = match row_ids {
+            true => Some(row.get::<_, u64>(2)?),
+            false => None,
+        };
+        batch.push(((row.get(0)?, row.get(1)?), id));
+        count += 1;
+
+        if batch.len() >= BATCH_SIZE {
+            tx.send(batch)
+                .map_err(|_| "Failed to send batch to channel")?;
+            batch = Vec::with_capacity(BATCH_SIZE);
+        }
+    }
+
+    if !batch.is_empty() {
+        tx.send(batch)
+            .map_err(|_| "Failed to send batch to channel")?;
+    }
+
+    Ok(count)
+}
+
+/// Reads an output file in any other format, row by row
+fn read_rows(
+    path: &Path,
+    format: OutputFormat,
+    tx: &SyncSender<RecordBatch>,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let mut reader = export::Reader::open(path, format)?;
+
+    let mut count = 0u64;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    while let Some(row) = reader.next_row_with_id()? {
+        batch.push(row);
+        count += 1;
+
+        if batch.len() >= BATCH_SIZE {
+            tx.send(batch)
+                .map_err(|_| "Failed to send batch to channel")?;
+            batch = Vec::with_capacity(BATCH_SIZE);
+        }
+    }
+
+    if !batch.is_empty() {
+        tx.send(batch)
+            .map_err(|_| "Failed to send batch to channel")?;
+    }
+
+    Ok(count)
+}
@@ -0,0 +1,99 @@
+use clap::Parser;
+use es_duck::results::{self, Comparison, Verdict};
+use std::error::Error;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "compare-results")]
+#[command(about = "Fail when any engine/configuration got slower than in a baseline harness run")]
+struct Args {
+    /// Baseline results.json (or results.csv) written by bench_harness.sh
+    baseline: PathBuf,
+
+    /// Results of the run to check
+    current: PathBuf,
+
+    /// Allowed slowdown of the sort time (e.g. "5%")
+    #[arg(long, default_value = "5%")]
+    threshold: String,
+}
+
+fn format_seconds(result: Option<&results::RunResult>) -> String {
+    match result {
+        Some(r) => match r.seconds {
+            Some(secs) if r.status == "ok" => format!("{:.2}s", secs),
+            _ => r.status.to_uppercase(),
+        },
+        None => "-".to_string(),
+    }
+}
+
+fn describe(comparison: &Comparison) -> String {
+    match (comparison.verdict, comparison.change_pct) {
+        (Verdict::Ok, Some(change)) => format!("{:+.1}%", change),
+        (Verdict::Regression, Some(change)) => format!("{:+.1}% REGRESSION", change),
+        (Verdict::Regression, None) => "REGRESSION (run did not complete)".to_string(),
+        (Verdict::NoBaseline, _) => "skipped (baseline did not complete)".to_string(),
+        (Verdict::Missing, _) => "missing from current results".to_string(),
+        (Verdict::Ok, None) => "OK".to_string(),
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let args = Args::parse();
+    let threshold = results::parse_threshold(&args.threshold)?;
+    let baseline = results::load(&args.baseline)?;
+    let current = results::load(&args.current)?;
+
+    println!(
+        "Comparing {:?} against baseline {:?} (threshold {}%)",
+        args.current, args.baseline, threshold
+    );
+
+    let comparisons = results::compare(&baseline, &current, threshold);
+    println!("\n===== SORT TIME VS BASELINE =====");
+    println!(
+        "{:<12} {:>12} {:>8} {:>12} {:>12}  result",
+        "engine", "memory_limit", "threads", "baseline", "current"
+    );
+    for c in &comparisons {
+        println!(
+            "{:<12} {:>12} {:>8} {:>12} {:>12}  {}",
+            c.baseline.engine,
+            c.baseline.memory_limit,
+            c.baseline.threads,
+            format_seconds(Some(&c.baseline)),
+            format_seconds(c.current.as_ref()),
+            describe(c)
+        );
+    }
+    println!("==================================\n");
+
+    let missing = comparisons
+        .iter()
+        .filter(|c| c.verdict == Verdict::Missing)
+        .count();
+    if missing > 0 {
+        println!(
+            "Warning: {} baseline configurations are missing from the current results",
+            missing
+        );
+    }
+
+    let regressions = comparisons
+        .iter()
+        .filter(|c| c.verdict == Verdict::Regression)
+        .count();
+    if regressions > 0 {
+        return Err(format!(
+            "{} of {} configurations regressed by more than {}%",
+            regressions,
+            comparisons.len(),
+            threshold
+        )
+        .into());
+    }
+
+    println!("No regressions beyond {}%.", threshold);
+    Ok(())
+}
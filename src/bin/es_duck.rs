@@ -0,0 +1,265 @@
+use clap::{Args, Parser, Subcommand};
+use es_duck::inspect;
+use es_duck::loader::{self, IfExists, LoaderConfig};
+use es_duck::metrics;
+use es_duck::progress;
+use es_duck::progress_bar;
+use es_duck::records::{self, FixedLayout, InputFormat, RecordReader, Window};
+use es_duck::rss;
+use es_duck::schema::{ColumnNames, ExtraColumn, Schema};
+use es_duck::sorter::{self, SortOptions};
+use es_duck::telemetry;
+use std::error::Error;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tracing::info_span;
+
+#[derive(Parser)]
+#[command(name = "es-duck")]
+#[command(about = "Backend-agnostic entry point for the es-duck benchmarks")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Load an input file into any registered backend
+    Load(LoadArgs),
+    /// Sort a loaded table with any registered backend
+    Sort(SortArgs),
+}
+
+#[derive(Args)]
+struct MetricsArgs {
+    /// Expose Prometheus metrics on this port while running
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Print a JSON progress event to stderr every this many seconds
+    #[arg(long)]
+    progress_interval: Option<u64>,
+
+    /// Write per-second throughput samples to this CSV file (render with plot-throughput)
+    #[arg(long)]
+    throughput_log: Option<PathBuf>,
+}
+
+impl MetricsArgs {
+    fn start(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(port) = self.metrics_port {
+            metrics::serve(port)?;
+            println!("Serving Prometheus metrics on port {}", port);
+        }
+        if let Some(secs) = self.progress_interval {
+            progress::start(Duration::from_secs(secs));
+        }
+        if let Some(ref path) = self.throughput_log {
+            metrics::record_throughput(path)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+struct LoadArgs {
+    /// Backend to load into (duckdb, postgres, clickhouse; depends on the enabled features)
+    #[arg(long)]
+    backend: String,
+
+    #[arg(long, value_enum)]
+    format: InputFormat,
+
+    #[arg(long)]
+    input: PathBuf,
+
+    #[command(flatten)]
+    layout: FixedLayout,
+
+    /// Table columns; wide splits each payload into typed columns
+    /// (see generate-gensort --schema wide)
+    #[arg(long, value_enum, default_value_t = Schema::KeyPayload)]
+    schema: Schema,
+
+    #[command(flatten)]
+    columns: ColumnNames,
+
+    /// Add a column the load leaves empty, e.g. --extra-column "tenant_id INTEGER" (repeatable)
+    #[arg(long = "extra-column", value_name = "NAME TYPE")]
+    extra_columns: Vec<ExtraColumn>,
+
+    /// Leave out this many records at the start of the input
+    #[arg(long, default_value_t = 0)]
+    skip: u64,
+
+    /// Load at most this many records
+    #[arg(long)]
+    limit: Option<u64>,
+
+    /// Read and check the input without connecting to the database: print the
+    /// record count, bytes, smallest and largest key and any malformed records
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Database file (DuckDB), connection string (PostgreSQL) or HTTP URL (ClickHouse)
+    #[arg(long)]
+    connection: String,
+
+    #[arg(long, default_value = "bench_data")]
+    table: String,
+
+    /// What to do when the table already exists
+    #[arg(long, value_enum, default_value_t = IfExists::Fail)]
+    if_exists: IfExists,
+
+    /// ClickHouse database
+    #[arg(long, default_value = "default")]
+    database: String,
+
+    /// Rows per batch handed to the backend
+    #[arg(long, default_value_t = 50_000)]
+    batch_size: usize,
+
+    /// Don't draw a progress bar on stderr
+    #[arg(long)]
+    no_progress: bool,
+
+    #[command(flatten)]
+    metrics: MetricsArgs,
+}
+
+#[derive(Parser)]
+struct SortArgs {
+    /// Backend to sort with (duckdb, postgres, clickhouse; depends on the enabled features)
+    #[arg(long)]
+    backend: String,
+
+    /// Database file (DuckDB), connection string (PostgreSQL) or HTTP URL (ClickHouse)
+    #[arg(long)]
+    connection: String,
+
+    #[arg(long, default_value = "bench_data")]
+    table: String,
+
+    #[command(flatten)]
+    columns: ColumnNames,
+
+    /// ClickHouse database
+    #[arg(long, default_value = "default")]
+    database: String,
+
+    /// Memory budget for the whole sort (e.g., "2GB", "512MB")
+    #[arg(long, default_value = "2GB")]
+    memory_limit: String,
+
+    /// Engine threads (PostgreSQL: parallel workers)
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Write the sorted rows here (Parquet, binary COPY or Native, by backend).
+    /// Without it the sort runs without producing output.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Spill directory (DuckDB)
+    #[arg(long)]
+    temp_dir: Option<PathBuf>,
+
+    /// Engine setting passed through as is, e.g. --set preserve_insertion_order=false (repeatable)
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = sorter::parse_setting)]
+    settings: Vec<(String, String)>,
+
+    #[command(flatten)]
+    metrics: MetricsArgs,
+}
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let cli = Cli::parse();
+    let _telemetry = telemetry::init("es-duck");
+
+    match cli.command {
+        Command::Load(args) => load(args),
+        Command::Sort(args) => sort(args),
+    }
+}
+
+fn load(args: LoadArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let _span = info_span!("load", engine = %args.backend, input = %args.input.display()).entered();
+
+    args.metrics.start()?;
+
+    let config = LoaderConfig {
+        connection: args.connection,
+        table: args.table,
+        database: args.database,
+        schema: args.schema,
+        columns: args.columns,
+        extra_columns: args.extra_columns,
+        if_exists: args.if_exists,
+    };
+    let layout = args.layout;
+    layout.check()?;
+    if args.dry_run {
+        inspect::inspect(args.format, layout, &args.input, args.skip, args.limit)?.report()?;
+        return Ok(());
+    }
+    let mut backend = loader::Registry::builtin().create(&args.backend, &config)?;
+    let range = records::select(args.format, layout, &args.input, args.skip, args.limit)?;
+    let window = Window::open(&args.input, range.clone())?;
+    let mut reader = RecordReader::new(
+        args.format,
+        BufReader::with_capacity(8 * 1024 * 1024, window),
+    )
+    .with_layout(layout);
+
+    println!(
+        "Starting load from {:?} into {} (batch_size={})...",
+        args.input, args.backend, args.batch_size
+    );
+    let bar = progress_bar::load(args.format, layout, &range, !args.no_progress);
+    let start = Instant::now();
+    let rows = loader::run(backend.as_mut(), &mut reader, args.batch_size)?;
+    let duration = start.elapsed();
+    bar.finish();
+
+    println!(
+        "Successfully loaded {} rows to {} in {:.2} seconds.",
+        rows,
+        args.backend,
+        duration.as_secs_f64()
+    );
+    Ok(())
+}
+
+fn sort(args: SortArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let _span = info_span!("sort", engine = %args.backend, table = %args.table).entered();
+    args.metrics.start()?;
+
+    let options = SortOptions {
+        connection: args.connection,
+        database: args.database,
+        table: args.table,
+        columns: args.columns,
+        memory_limit: args.memory_limit,
+        threads: args.threads,
+        output: args.output,
+        temp_dir: args.temp_dir,
+        settings: args.settings,
+    };
+    let mut backend = sorter::Registry::builtin().create(&args.backend, &options)?;
+
+    println!(
+        "Running {} sort with memory_limit={}, threads={}...",
+        args.backend,
+        options.memory_limit,
+        options
+            .threads
+            .map_or("default".to_string(), |t| t.to_string())
+    );
+    let result = sorter::run(backend.as_mut())?;
+
+    println!("TIMING: {:.2} seconds", result.seconds);
+    rss::report(result.peak_rss_bytes);
+    Ok(())
+}
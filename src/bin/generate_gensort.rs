@@ -1,75 +1,959 @@
-use clap::Parser;
-use rand::RngCore;
+use clap::{Parser, ValueEnum};
+use es_duck::checksum::{self, Checksum};
+use es_duck::csv_file::{self, Encoding};
+use es_duck::loader::{self, IfExists, LoaderConfig};
+use es_duck::progress_bar::Bar;
+use es_duck::records::{self, FixedLayout, InputFormat, RecordReader};
+use es_duck::schema::{self, ColumnNames, ExtraColumn, Schema};
+use es_duck::telemetry;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Zipf};
 use std::error::Error;
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::path::PathBuf;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::info_span;
 
 #[derive(Parser)]
 #[command(name = "generate-gensort")]
+#[command(about = "Generate gensort records, as binary, Parquet or CSV")]
 struct Args {
-    /// Output file path
-    #[arg(long)]
-    output: PathBuf,
+    /// Output file path; a FIFO (or /dev/stdout) gets the records in order as
+    /// they are generated, for a loader reading the other end
+    #[arg(long, required_unless_present = "load")]
+    output: Option<PathBuf>,
+
+    /// Layout of the output file
+    #[arg(long, value_enum, default_value_t = OutputFormat::Binary)]
+    output_format: OutputFormat,
+
+    /// Rows per row group with --output-format parquet
+    #[arg(long, default_value_t = 1_048_576)]
+    row_group_size: usize,
+
+    /// How --output-format csv writes keys and payloads
+    #[arg(long, value_enum, default_value_t = Encoding::Hex)]
+    csv_encoding: Encoding,
 
     /// Number of records to generate
     #[arg(long)]
     num_records: u64,
+
+    /// Index of the first record to generate (like gensort -b). Slices
+    /// generated separately concatenate to the file a single run would write.
+    #[arg(long, default_value_t = 0)]
+    start_record: u64,
+
+    /// Size of the whole dataset when generating a slice of it; sets the key
+    /// universe of --distribution zipf, --distinct-keys and --duplicate-ratio
+    /// so all slices agree (default: start-record + num-records)
+    #[arg(long)]
+    total_records: Option<u64>,
+
+    /// Key bytes per record (gensort: 10)
+    #[arg(long, default_value_t = 10)]
+    key_size: usize,
+
+    /// Payload bytes per record (gensort: 90)
+    #[arg(long, default_value_t = 90)]
+    payload_size: usize,
+
+    /// Record contents. wide stores an int, a bigint, a double and a short
+    /// string at the start of each payload, for multi-column ORDER BY
+    /// benchmarks; load them with `es-duck load --schema wide`.
+    #[arg(long, value_enum, default_value_t = Schema::KeyPayload)]
+    schema: Schema,
+
+    /// RNG seed; the same seed and record count always produce the same file.
+    /// Without it a random seed is picked and printed.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Generator threads, each writing its own range of the file.
+    /// The output does not depend on the thread count.
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+
+    /// How sort keys are drawn
+    #[arg(long, value_enum, default_value_t = KeyDistribution::Uniform)]
+    distribution: KeyDistribution,
+
+    /// Zipf exponent for --distribution zipf; larger means more skew
+    #[arg(long, default_value_t = 0.99)]
+    zipf_s: f64,
+
+    /// Draw keys from exactly this many distinct values. With the uniform
+    /// distribution every value appears total_records / N times (give or take one).
+    #[arg(long, conflicts_with = "duplicate_ratio")]
+    distinct_keys: Option<u64>,
+
+    /// Fraction of records (0 to 1) whose key also appears on an earlier record;
+    /// shorthand for --distinct-keys total_records * (1 - ratio)
+    #[arg(long)]
+    duplicate_ratio: Option<f64>,
+
+    /// Number of heavy-hitter keys: a few keys that together take
+    /// --heavy-fraction of all records, on top of --distribution
+    #[arg(long, requires = "heavy_fraction")]
+    heavy_hitters: Option<u64>,
+
+    /// Fraction of records (0 to 1] whose key is one of the --heavy-hitters keys,
+    /// each of them equally likely
+    #[arg(long, requires = "heavy_hitters")]
+    heavy_fraction: Option<f64>,
+
+    /// Printable records in the layout of `gensort -a`: 10 printable key
+    /// characters, the record number in hex, hex filler and CRLF.
+    /// Only for the default 10/90 record layout.
+    #[arg(long)]
+    ascii: bool,
+
+    /// How compressible payloads are; keys are unaffected
+    #[arg(long, value_enum, default_value_t = PayloadEntropy::High, conflicts_with = "ascii")]
+    payload_entropy: PayloadEntropy,
+
+    /// Also write the valsort-style checksum of the data to <output>.sum
+    /// (with --load it is only printed)
+    #[arg(long)]
+    checksum: bool,
+
+    /// Stream the records straight into this loader backend (duckdb, postgres,
+    /// clickhouse; depends on the enabled features) instead of writing a file
+    #[arg(long, conflicts_with = "output", requires = "connection")]
+    load: Option<String>,
+
+    /// Database file (DuckDB), connection string (PostgreSQL) or HTTP URL (ClickHouse) for --load
+    #[arg(long)]
+    connection: Option<String>,
+
+    /// Table for --load
+    #[arg(long, default_value = "bench_data")]
+    table: String,
+
+    /// What to do when the --load table already exists
+    #[arg(long, value_enum, default_value_t = IfExists::Fail)]
+    if_exists: IfExists,
+
+    #[command(flatten)]
+    columns: ColumnNames,
+
+    /// Add a column the --load leaves empty, e.g. --extra-column "tenant_id INTEGER" (repeatable)
+    #[arg(long = "extra-column", value_name = "NAME TYPE")]
+    extra_columns: Vec<ExtraColumn>,
+
+    /// ClickHouse database for --load
+    #[arg(long, default_value = "default")]
+    database: String,
+
+    /// Rows per batch handed to the loader
+    #[arg(long, default_value_t = 50_000)]
+    batch_size: usize,
+
+    /// Don't draw a progress bar on stderr
+    #[arg(long)]
+    no_progress: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Records back to back, as gensort writes them
+    Binary,
+    /// A `sort_key` and a `payload` BLOB column (needs the util-parquet feature)
+    Parquet,
+    /// One `sort_key,payload` line per record, fields encoded per --csv-encoding
+    Csv,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum KeyDistribution {
+    /// Independent random keys, practically all distinct
+    Uniform,
+    /// Keys drawn from total_records (or --distinct-keys) values with
+    /// Zipf-distributed frequencies, so a few keys repeat very often
+    Zipf,
+}
+
+/// What every generator thread needs to produce its records
+struct Generator {
+    seed: u64,
+    layout: FixedLayout,
+    payload_entropy: PayloadEntropy,
+    /// Index of the record at offset 0 of the output
+    first_record: u64,
+    keys: Keys,
+    heavy: Option<HeavyHitters>,
+    /// Put the wide schema's columns at the start of each payload
+    wide: bool,
+    ascii: bool,
+    /// Encode records as CSV lines before writing them
+    csv: Option<Encoding>,
+    checksum: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum PayloadEntropy {
+    /// One random byte repeated over the whole payload; compresses to almost nothing
+    Low,
+    /// Each byte one of 16 letters (4 bits of entropy); compresses to about half
+    Medium,
+    /// Uniformly random bytes; incompressible
+    High,
+}
+
+impl PayloadEntropy {
+    /// Reshapes a random payload in place
+    fn apply(self, payload: &mut [u8]) {
+        match self {
+            PayloadEntropy::Low => {
+                if let Some(&byte) = payload.first() {
+                    payload.fill(byte);
+                }
+            }
+            PayloadEntropy::Medium => {
+                for b in payload.iter_mut() {
+                    *b = b'a' + (*b & 0x0F);
+                }
+            }
+            PayloadEntropy::High => {}
+        }
+    }
+}
+
+/// Where generated chunks of whole records go
+#[derive(Clone)]
+enum Sink<'a> {
+    /// Written at the records' offset in the output file
+    File(&'a File),
+    /// Handed to a consumer thread (the loader or the Parquet writer)
+    Channel(SyncSender<Vec<u8>>),
+}
+
+impl Sink<'_> {
+    fn write(&self, chunk: &[u8], offset: u64) -> io::Result<()> {
+        match self {
+            Sink::File(file) => file.write_all_at(chunk, offset),
+            Sink::Channel(tx) => tx.send(chunk.to_vec()).map_err(|_| {
+                io::Error::new(io::ErrorKind::BrokenPipe, "the consumer stopped reading")
+            }),
+        }
+    }
+}
+
+/// `Read` over the chunks sent to a `Sink::Channel`; ends once every sender is gone
+struct ChunkReader {
+    rx: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Where record keys come from
+enum Keys {
+    /// The record's own random bytes
+    Random,
+    /// Rank permutation(index) % distinct, so every rank appears equally often
+    Distinct {
+        distinct: u64,
+        permutation: Permutation,
+    },
+    Zipf(Zipf<f64>),
+}
+
+impl Keys {
+    /// Overwrites the key of record `index`, unless keys are plain random bytes;
+    /// returns whether it did. Depends only on (seed, index), so records do not
+    /// depend on how the file is split across threads.
+    fn fill(&self, seed: u64, index: u64, key: &mut [u8]) -> bool {
+        let rank = match self {
+            Keys::Random => return false,
+            Keys::Distinct {
+                distinct,
+                permutation,
+            } => permutation.apply(index) % distinct,
+            Keys::Zipf(zipf) => {
+                let mut rng = SplitMix64(mix64(seed ^ mix64(index)));
+                zipf.sample(&mut rng) as u64
+            }
+        };
+        rank_key(seed, rank, key);
+        true
+    }
+}
+
+/// `keys` keys that together take `fraction` of the records
+struct HeavyHitters {
+    keys: u64,
+    fraction: f64,
+}
+
+impl HeavyHitters {
+    /// Draws from their own stream and key space, so they do not change which
+    /// records the base distribution would give which key
+    const SALT: u64 = 0x6865_6176_7968_6974;
+
+    /// Gives record `index` a heavy key with probability `fraction`; returns whether it did
+    fn fill(&self, seed: u64, index: u64, key: &mut [u8]) -> bool {
+        let mut rng = SplitMix64(mix64(seed ^ Self::SALT ^ mix64(index)));
+        let draw = (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        if draw >= self.fraction {
+            return false;
+        }
+        rank_key(seed ^ Self::SALT, rng.next_u64() % self.keys, key);
+        true
+    }
+}
+
+/// Bytes generated and written per pwrite, rounded down to whole records
+const CHUNK_BYTES: usize = 16_000_000;
+
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
+    let _telemetry = telemetry::init("generate-gensort");
+    let _span = info_span!(
+        "generate",
+        num_records = args.num_records,
+        start_record = args.start_record,
+        seed = args.seed,
+        threads = args.threads,
+        zipf = (args.distribution == KeyDistribution::Zipf).then_some(args.zipf_s)
+    )
+    .entered();
 
-    const KEY_SIZE: usize = 10;
-    const PAYLOAD_SIZE: usize = 90;
-    const RECORD_SIZE: usize = KEY_SIZE + PAYLOAD_SIZE;
+    if args.threads == 0 {
+        return Err("--threads must be at least 1".into());
+    }
+    if args.key_size == 0 {
+        return Err("--key-size must be at least 1".into());
+    }
+    let layout = FixedLayout {
+        key_size: args.key_size,
+        payload_size: args.payload_size,
+    };
+    if args.ascii && layout != FixedLayout::GENSORT {
+        return Err("--ascii only supports 10-byte keys and 90-byte payloads".into());
+    }
+    if args.output_format != OutputFormat::Binary && (args.ascii || args.load.is_some()) {
+        return Err("--ascii and --load only work with --output-format binary".into());
+    }
+    let wide = args.schema == Schema::Wide;
+    if wide && args.payload_size < schema::WIDE_COLUMNS_SIZE {
+        return Err(format!(
+            "--schema wide needs --payload-size of at least {}",
+            schema::WIDE_COLUMNS_SIZE
+        )
+        .into());
+    }
+    if wide && (args.ascii || args.output_format != OutputFormat::Binary) {
+        return Err("--schema wide only works with --output-format binary".into());
+    }
+    let parquet = args.output_format == OutputFormat::Parquet;
+    let csv = (args.output_format == OutputFormat::Csv).then_some(args.csv_encoding);
+    // A FIFO or pipe can't be written at offsets, so like Parquet output it
+    // takes the generators' chunks in order
+    let stream = match args.output {
+        Some(ref path) if path.exists() => records::is_stream(path)?,
+        _ => false,
+    };
+    if stream && args.load.is_some() {
+        return Err("--load needs a regular --output file, not a stream".into());
+    }
+    let ordered = parquet || stream;
 
-    let file = File::create(&args.output)?;
-    let mut writer = BufWriter::with_capacity(16 * 1024 * 1024, file); // 16MB buffer
+    let end_record = args
+        .start_record
+        .checked_add(args.num_records)
+        .ok_or("--start-record + --num-records overflows")?;
+    let total_records = args.total_records.unwrap_or(end_record);
+    if total_records < end_record {
+        return Err(format!(
+            "Records {}..{} lie outside --total-records {}",
+            args.start_record, end_record, total_records
+        )
+        .into());
+    }
 
-    let mut record = vec![0u8; RECORD_SIZE];
-    let mut rng = rand::rng();
+    let distinct = match (args.distinct_keys, args.duplicate_ratio) {
+        (Some(0), _) => return Err("--distinct-keys must be at least 1".into()),
+        (Some(n), _) => Some(n),
+        (None, Some(ratio)) if !(0.0..1.0).contains(&ratio) => {
+            return Err(format!("--duplicate-ratio must be in [0, 1), got {}", ratio).into());
+        }
+        (None, Some(ratio)) => Some(((total_records as f64 * (1.0 - ratio)).round() as u64).max(1)),
+        (None, None) => None,
+    };
 
-    let start = std::time::Instant::now();
-    let mut last_report = start;
+    // ChaCha8's output is fixed by its spec, so a seed means the same data on every machine
+    let seed = args.seed.unwrap_or_else(rand::random);
+    eprintln!("Seed: {}", seed);
 
-    for i in 0..args.num_records {
-        // Generate random 10-byte key
-        rng.fill_bytes(&mut record[..KEY_SIZE]);
+    let keys = match (args.distribution, distinct) {
+        (KeyDistribution::Uniform, None) => Keys::Random,
+        (KeyDistribution::Uniform, Some(distinct)) => Keys::Distinct {
+            distinct,
+            permutation: Permutation::new(total_records, seed),
+        },
+        (KeyDistribution::Zipf, distinct) => Keys::Zipf(
+            Zipf::new(distinct.unwrap_or(total_records).max(1) as f64, args.zipf_s)
+                .map_err(|e| format!("Invalid --zipf-s {}: {}", args.zipf_s, e))?,
+        ),
+    };
 
-        // Generate random 90-byte payload
-        rng.fill_bytes(&mut record[KEY_SIZE..]);
+    let heavy = match (args.heavy_hitters, args.heavy_fraction) {
+        (Some(0), _) => return Err("--heavy-hitters must be at least 1".into()),
+        (_, Some(fraction)) if !(fraction > 0.0 && fraction <= 1.0) => {
+            return Err(format!("--heavy-fraction must be in (0, 1], got {}", fraction).into());
+        }
+        (Some(keys), Some(fraction)) => Some(HeavyHitters { keys, fraction }),
+        _ => None,
+    };
 
-        writer.write_all(&record)?;
+    let generator = Generator {
+        seed,
+        layout,
+        payload_entropy: args.payload_entropy,
+        first_record: args.start_record,
+        keys,
+        heavy,
+        wide,
+        ascii: args.ascii,
+        csv,
+        checksum: args.checksum,
+    };
+    // Bytes each record takes in the output
+    let output_size = generator.output_size() as u64;
+
+    let file = match args.output {
+        Some(_) if ordered => None,
+        Some(ref path) => {
+            let file = File::create(path)?;
+            file.set_len(args.num_records * output_size)?;
+            Some(file)
+        }
+        None => None,
+    };
+    // Chunks in flight to the loader. The Parquet and stream writers must see
+    // them in file order, so each generator gets its own channel and the writer
+    // takes turns.
+    let (tx, rx) = sync_channel::<Vec<u8>>(args.threads * 2);
+    let mut rx = Some(rx);
+    let (ordered_txs, ordered_rxs): (Vec<_>, Vec<_>) = (0..args.threads)
+        .filter(|_| ordered)
+        .map(|_| sync_channel::<Vec<u8>>(2))
+        .unzip();
+    let mut ordered_rxs = Some(ordered_rxs);
 
-        // Progress reporting every 1 million records
-        if i > 0 && i % 1_000_000 == 0 {
-            let elapsed = last_report.elapsed().as_secs_f64();
-            let records_per_sec = 1_000_000.0 / elapsed;
-            let mb_written = (i * RECORD_SIZE as u64) as f64 / (1024.0 * 1024.0);
-            let progress = (i as f64 / args.num_records as f64) * 100.0;
+    let start = Instant::now();
+    let generated = AtomicU64::new(0);
+    let records_per_thread = args.num_records.div_ceil(args.threads as u64);
 
-            eprintln!(
-                "Progress: {:.1}% ({} / {} records, {:.2} MB, {:.0} rec/s)",
-                progress, i, args.num_records, mb_written, records_per_sec
-            );
+    let (sum, loaded) = thread::scope(|s| -> Result<(Checksum, Option<u64>), Box<dyn Error>> {
+        let handles: Vec<_> = (0..args.threads as u64)
+            .map(|thread_id| {
+                let (generator, generated) = (&generator, &generated);
+                if ordered {
+                    let sink = Sink::Channel(ordered_txs[thread_id as usize].clone());
+                    let stride = args.threads as u64;
+                    return s.spawn(move || {
+                        generator
+                            .generate_interleaved(&sink, thread_id, stride, end_record, generated)
+                    });
+                }
+                let first = (args.start_record + thread_id * records_per_thread).min(end_record);
+                let end = (first + records_per_thread).min(end_record);
+                let sink = match file {
+                    Some(ref file) => Sink::File(file),
+                    None => Sink::Channel(tx.clone()),
+                };
+                s.spawn(move || generator.generate_range(&sink, first, end, generated))
+            })
+            .collect();
+        // The consumers see the end of the input once the generators drop their senders
+        drop(tx);
+        drop(ordered_txs);
 
-            last_report = std::time::Instant::now();
+        let writer = args.output.as_deref().filter(|_| ordered).map(|path| {
+            let rxs = ordered_rxs.take().expect("one writer");
+            let row_group_size = args.row_group_size;
+            s.spawn(move || {
+                if parquet {
+                    write_parquet(path, layout, row_group_size, rxs)
+                } else {
+                    write_stream(path, rxs)
+                }
+            })
+        });
+
+        let loader = args.load.as_ref().map(|backend| {
+            let config = LoaderConfig {
+                connection: args.connection.clone().unwrap_or_default(),
+                table: args.table.clone(),
+                database: args.database.clone(),
+                schema: args.schema,
+                columns: args.columns.clone(),
+                extra_columns: args.extra_columns.clone(),
+                if_exists: args.if_exists,
+            };
+            let rx = rx.take().expect("one loader");
+            let batch_size = args.batch_size;
+            s.spawn(move || -> Result<u64, Box<dyn Error + Send + Sync>> {
+                let mut backend = loader::Registry::builtin().create(backend, &config)?;
+                let input = ChunkReader {
+                    rx,
+                    chunk: Vec::new(),
+                    pos: 0,
+                };
+                let mut reader = RecordReader::new(InputFormat::Gensort, input).with_layout(layout);
+                loader::run(backend.as_mut(), &mut reader, batch_size)
+            })
+        });
+
+        let bar = Bar::new(Some(args.num_records), !args.no_progress);
+        while !handles.iter().all(|h| h.is_finished())
+            || loader.as_ref().is_some_and(|h| !h.is_finished())
+            || writer.as_ref().is_some_and(|h| !h.is_finished())
+        {
+            thread::sleep(Duration::from_millis(100));
+            let count = generated.load(Ordering::Relaxed);
+            bar.set(count, count * output_size);
         }
-    }
+        let count = generated.load(Ordering::Relaxed);
+        bar.set(count, count * output_size);
+        bar.finish();
 
-    writer.flush()?;
+        // A failed loader makes the generators fail too; report the cause
+        let loaded = match loader {
+            Some(handle) => Some(
+                handle
+                    .join()
+                    .map_err(|_| "Loader thread panicked")?
+                    .map_err(|e| format!("Load failed: {}", e))?,
+            ),
+            None => None,
+        };
+        if let Some(handle) = writer {
+            handle
+                .join()
+                .map_err(|_| "Writer thread panicked")?
+                .map_err(|e| {
+                    format!(
+                        "Writing {} failed: {}",
+                        args.output.as_ref().unwrap().display(),
+                        e
+                    )
+                })?;
+        }
+
+        // Sums commute, so merging per-range checksums gives the whole file's
+        let mut sum = Checksum::new();
+        for handle in handles {
+            sum.merge(&handle.join().map_err(|_| "Generator thread panicked")??);
+        }
+        Ok((sum, loaded))
+    })?;
+
+    if let Some(ref file) = file {
+        file.sync_all()?;
+    }
+    if let Some(rows) = loaded
+        && rows != args.num_records
+    {
+        return Err(format!(
+            "Generated {} records, but the loader read {}",
+            args.num_records, rows
+        )
+        .into());
+    }
+    if args.checksum {
+        match args.output {
+            Some(ref output) => {
+                let path = checksum::sum_path(output);
+                sum.write(&path).map_err(|e| e.to_string())?;
+                eprintln!("Checksum: {:x} (written to {})", sum.sum, path.display());
+            }
+            None => eprintln!("Checksum: {:x}", sum.sum),
+        }
+    }
 
     let total_elapsed = start.elapsed().as_secs_f64();
-    let total_mb = (args.num_records * RECORD_SIZE as u64) as f64 / (1024.0 * 1024.0);
+    let total_mb = (args.num_records * output_size) as f64 / (1024.0 * 1024.0);
     let total_gb = total_mb / 1024.0;
 
     eprintln!("\n=== Generation Complete ===");
     eprintln!("Records: {}", args.num_records);
-    eprintln!("File size: {:.2} GB ({:.2} MB)", total_gb, total_mb);
+    if args.start_record > 0 || total_records != end_record {
+        eprintln!(
+            "Slice: records {}..{} of {}",
+            args.start_record, end_record, total_records
+        );
+    }
+    eprintln!("Seed: {}", seed);
+    eprintln!("Threads: {}", args.threads);
+    if let Keys::Zipf(_) = generator.keys {
+        eprintln!("Keys: zipf (s = {})", args.zipf_s);
+    }
+    if let Some(distinct) = distinct {
+        eprintln!("Distinct keys: {}", distinct);
+    }
+    if let Some(ref heavy) = generator.heavy {
+        eprintln!(
+            "Heavy hitters: {} keys in {:.1}% of records",
+            heavy.keys,
+            heavy.fraction * 100.0
+        );
+    }
+    if layout != FixedLayout::GENSORT {
+        eprintln!(
+            "Record layout: {}-byte key, {}-byte payload",
+            layout.key_size, layout.payload_size
+        );
+    }
+    if args.ascii {
+        eprintln!("Format: ascii (gensort -a)");
+    }
+    if parquet {
+        eprintln!(
+            "Format: parquet (row groups of {} rows)",
+            args.row_group_size
+        );
+    }
+    if let Some(encoding) = csv {
+        eprintln!("Format: csv ({:?} fields)", encoding);
+    }
+    if wide {
+        eprintln!("Schema: wide");
+    }
+    if args.payload_entropy != PayloadEntropy::High {
+        eprintln!("Payload entropy: {:?}", args.payload_entropy);
+    }
+    if parquet {
+        eprintln!("Data size: {:.2} GB ({:.2} MB)", total_gb, total_mb);
+    } else {
+        eprintln!("File size: {:.2} GB ({:.2} MB)", total_gb, total_mb);
+    }
     eprintln!("Time: {:.2} seconds", total_elapsed);
     eprintln!("Speed: {:.2} MB/s", total_mb / total_elapsed);
-    eprintln!("Output: {}", args.output.display());
+    match (&args.output, &args.load) {
+        (Some(output), _) => eprintln!("Output: {}", output.display()),
+        (None, Some(backend)) => eprintln!("Loaded into: {} table {}", backend, args.table),
+        (None, None) => {}
+    }
 
     Ok(())
 }
+
+impl Generator {
+    /// Generates records `first..end` and writes them to `sink`.
+    /// Record i is always bytes R*i..R*(i+1) of the seed's ChaCha8 stream, R the
+    /// record size (key, then payload; words 25*i.. for gensort's 100 bytes),
+    /// so how the range is split does not change the bytes.
+    /// `keys` may then replace the key, `wide` turn the start of the payload into
+    /// typed columns and `payload_entropy` reshape the rest of the payload, and
+    /// `ascii` rewrites the record as text. `csv` encodes the records after
+    /// checksumming them, so the checksum is always that of the binary data.
+    /// Returns the range's checksum (empty unless `checksum` is set).
+    fn generate_range(
+        &self,
+        sink: &Sink,
+        first: u64,
+        end: u64,
+        generated: &AtomicU64,
+    ) -> io::Result<Checksum> {
+        let record_size = self.layout.record_size();
+        let key_size = self.layout.key_size;
+        let chunk_records = self.chunk_records();
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+
+        let mut sum = Checksum::new();
+        let chunk_records = chunk_records.min(end.saturating_sub(first));
+        // Up to 3 extra bytes for a chunk starting mid-word
+        let mut buffer = vec![0u8; chunk_records as usize * record_size + 3];
+        let mut text = Vec::new();
+        let mut next = first;
+        while next < end {
+            let records = chunk_records.min(end - next);
+            let len = records as usize * record_size;
+            // ChaCha8 seeks by 32-bit word; records need not be a whole number of words
+            let byte_pos = next as u128 * record_size as u128;
+            let skip = (byte_pos % 4) as usize;
+            rng.set_word_pos(byte_pos / 4);
+            rng.fill_bytes(&mut buffer[..skip + len]);
+            let chunk = &mut buffer[skip..skip + len];
+            let random_keys = matches!(self.keys, Keys::Random) && self.heavy.is_none();
+            let random_payloads = self.payload_entropy == PayloadEntropy::High;
+            if !random_keys || !random_payloads || self.wide || self.ascii {
+                // Wide columns come first in the payload and are not reshaped
+                let columns = if self.wide {
+                    schema::WIDE_COLUMNS_SIZE
+                } else {
+                    0
+                };
+                let blob_start = key_size + columns;
+                for (i, record) in (next..).zip(chunk.chunks_exact_mut(record_size)) {
+                    let ranked = self.fill_key(i, &mut record[..key_size]);
+                    if self.wide {
+                        schema::fill_wide(&mut record[key_size..]);
+                    }
+                    self.payload_entropy.apply(&mut record[blob_start..]);
+                    if self.ascii {
+                        to_ascii(record, i, !ranked);
+                    }
+                }
+            }
+            if self.checksum {
+                sum.add_records(chunk, record_size);
+            }
+            let offset = (next - self.first_record) * self.output_size() as u64;
+            match self.csv {
+                Some(encoding) => {
+                    text.clear();
+                    csv_file::encode_records(chunk, self.layout, encoding, &mut text);
+                    sink.write(&text, offset)?;
+                }
+                None => sink.write(chunk, offset)?,
+            }
+            next += records;
+            generated.fetch_add(records, Ordering::Relaxed);
+        }
+        Ok(sum)
+    }
+
+    /// Sets the key of record `index`, a heavy hitter's or one from `keys`;
+    /// returns false if it kept its random bytes
+    fn fill_key(&self, index: u64, key: &mut [u8]) -> bool {
+        if let Some(ref heavy) = self.heavy
+            && heavy.fill(self.seed, index, key)
+        {
+            return true;
+        }
+        self.keys.fill(self.seed, index, key)
+    }
+
+    /// Bytes each record takes in the output
+    fn output_size(&self) -> usize {
+        match self.csv {
+            Some(encoding) => csv_file::row_size(self.layout, encoding),
+            None => self.layout.record_size(),
+        }
+    }
+
+    /// Records per chunk written to the sink
+    fn chunk_records(&self) -> u64 {
+        (CHUNK_BYTES / self.layout.record_size()).max(1) as u64
+    }
+
+    /// Generates chunk `first_chunk` of the output and every `stride`-th chunk
+    /// after it, in order, up to record `end`. With one channel per thread, a
+    /// consumer that takes the threads' chunks in turn sees the records in order.
+    fn generate_interleaved(
+        &self,
+        sink: &Sink,
+        first_chunk: u64,
+        stride: u64,
+        end: u64,
+        generated: &AtomicU64,
+    ) -> io::Result<Checksum> {
+        let chunk_records = self.chunk_records();
+        let mut sum = Checksum::new();
+        let mut first = self.first_record + first_chunk * chunk_records;
+        while first < end {
+            let chunk_end = (first + chunk_records).min(end);
+            sum.merge(&self.generate_range(sink, first, chunk_end, generated)?);
+            first += stride * chunk_records;
+        }
+        Ok(sum)
+    }
+}
+
+/// Writes chunks to a Parquet file at `path`. Chunk k comes from generator
+/// k % threads, so taking the channels in turn keeps the records in order; the
+/// first closed channel means every chunk has been written.
+#[cfg(feature = "util-parquet")]
+fn write_parquet(
+    path: &Path,
+    layout: FixedLayout,
+    row_group_size: usize,
+    rxs: Vec<Receiver<Vec<u8>>>,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let mut writer = es_duck::parquet_file::RecordWriter::create(path, layout, row_group_size)?;
+    for rx in rxs.iter().cycle() {
+        match rx.recv() {
+            Ok(chunk) => writer.write(&chunk)?,
+            Err(_) => break,
+        }
+    }
+    writer.finish()
+}
+
+/// Writes chunks to a FIFO or pipe at `path`, in order the way `write_parquet`
+/// takes them. Returns the bytes written.
+fn write_stream(
+    path: &Path,
+    rxs: Vec<Receiver<Vec<u8>>>,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    // Blocks until a reader opens the other end
+    let mut out = OpenOptions::new().write(true).open(path)?;
+    let mut bytes = 0u64;
+    for rx in rxs.iter().cycle() {
+        match rx.recv() {
+            Ok(chunk) => {
+                out.write_all(&chunk)?;
+                bytes += chunk.len() as u64;
+            }
+            Err(_) => break,
+        }
+    }
+    out.flush()?;
+    Ok(bytes)
+}
+
+#[cfg(not(feature = "util-parquet"))]
+fn write_parquet(
+    _path: &Path,
+    _layout: FixedLayout,
+    _row_group_size: usize,
+    _rxs: Vec<Receiver<Vec<u8>>>,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    Err("--output-format parquet requires building with --features util-parquet".into())
+}
+
+/// Writes the key for `rank`. The mapping is a bijective mix, so distinct ranks
+/// give distinct keys (of 8 bytes or more) and the most frequent ranks are
+/// scattered over the key space. Bytes past the first 8 repeat the mix.
+fn rank_key(seed: u64, rank: u64, key: &mut [u8]) {
+    let mut hashed = mix64(rank ^ seed);
+    for part in key.chunks_mut(8) {
+        part.copy_from_slice(&hashed.to_be_bytes()[..part.len()]);
+        hashed = mix64(hashed);
+    }
+}
+
+/// Rewrites a binary record in the `gensort -a` layout:
+///
+/// ```text
+/// 0..10   key, characters ' '..='~'
+/// 10..12  two spaces
+/// 12..44  record number, 32 uppercase hex digits
+/// 44..46  two spaces
+/// 46..98  13 hex digits, each repeated 4 times
+/// 98..100 "\r\n"
+/// ```
+///
+/// Random keys take 8 characters from bytes 0..8 and 2 from bytes 8..10, as
+/// gensort does with its two random words. Keys from ranks encode bytes 0..8 in
+/// all 10 characters instead, which keeps distinct ranks distinct.
+fn to_ascii(record: &mut [u8], index: u64, random_key: bool) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+    let hi = u64::from_be_bytes(record[..8].try_into().unwrap());
+    let lo = u64::from_be_bytes(record[8..16].try_into().unwrap());
+
+    let mut value = hi;
+    for (i, c) in record[..10].iter_mut().enumerate() {
+        if random_key && i == 8 {
+            value = lo >> 48;
+        }
+        *c = b' ' + (value % 95) as u8;
+        value /= 95;
+    }
+
+    record[10..12].copy_from_slice(b"  ");
+    for (i, c) in record[12..44].iter_mut().enumerate() {
+        let shift = 4 * (31 - i);
+        *c = HEX[((index as u128 >> shift) & 0xF) as usize];
+    }
+    record[44..46].copy_from_slice(b"  ");
+    for (i, group) in record[46..98].chunks_exact_mut(4).enumerate() {
+        group.fill(HEX[((lo >> (48 - 4 * i)) & 0xF) as usize]);
+    }
+    record[98..100].copy_from_slice(b"\r\n");
+}
+
+/// SplitMix64 finalizer: a bijection on u64 with good avalanche
+fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// A seeded random permutation of 0..n: a 4-round Feistel network over the
+/// next even power of two, cycle-walking until the result falls below n
+struct Permutation {
+    n: u64,
+    half_bits: u32,
+    seed: u64,
+}
+
+impl Permutation {
+    fn new(n: u64, seed: u64) -> Self {
+        let bits = (64 - n.saturating_sub(1).leading_zeros()).max(2);
+        Self {
+            n,
+            half_bits: bits.div_ceil(2),
+            seed,
+        }
+    }
+
+    fn apply(&self, index: u64) -> u64 {
+        let mut x = index;
+        loop {
+            x = self.feistel(x);
+            if x < self.n {
+                return x;
+            }
+        }
+    }
+
+    fn feistel(&self, x: u64) -> u64 {
+        let mask = (1u64 << self.half_bits) - 1;
+        let (mut left, mut right) = (x >> self.half_bits, x & mask);
+        for round in 0..4 {
+            let f = mix64(right ^ mix64(self.seed.wrapping_add(round))) & mask;
+            (left, right) = (right, left ^ f);
+        }
+        (left << self.half_bits) | right
+    }
+}
+
+/// Tiny generator for the few draws a Zipf sample needs; much cheaper to seed than ChaCha8
+struct SplitMix64(u64);
+
+impl RngCore for SplitMix64 {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        mix64(self.0)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
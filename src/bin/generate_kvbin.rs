@@ -0,0 +1,268 @@
+use clap::{ArgGroup, Parser};
+use es_duck::kvbin_index::{self, Spacing};
+use es_duck::progress_bar::Bar;
+use es_duck::telemetry;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, LogNormal};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Instant;
+use tracing::info_span;
+
+#[derive(Parser)]
+#[command(name = "generate-kvbin")]
+#[command(group(ArgGroup::new("size").required(true).args(["num_records", "target_bytes"])))]
+struct Args {
+    /// Output file path
+    #[arg(long)]
+    output: PathBuf,
+
+    /// Number of records to generate
+    #[arg(long)]
+    num_records: Option<u64>,
+
+    /// Generate records until the file reaches this many bytes (e.g. "10GB", "512MB")
+    #[arg(long, value_parser = parse_size)]
+    target_bytes: Option<u64>,
+
+    /// Key lengths: N, fixed:N, uniform:MIN:MAX or lognormal:MEAN:CV
+    #[arg(long, default_value = "fixed:10")]
+    key_len: LengthDistribution,
+
+    /// Value lengths, same forms as --key-len
+    #[arg(long, default_value = "fixed:90")]
+    value_len: LengthDistribution,
+
+    /// RNG seed; the same seed and options always produce the same file.
+    /// Without it a random seed is picked and printed.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Also write <output>.idx with the offset of every --index-interval-th
+    /// record, which the loaders use to split the file across threads
+    #[arg(long)]
+    index: bool,
+
+    /// Records between index entries
+    #[arg(long, default_value_t = 100_000)]
+    index_interval: u64,
+
+    /// Don't draw a progress bar on stderr
+    #[arg(long)]
+    no_progress: bool,
+}
+
+/// Longest key or value a lognormal draw is clamped to
+const MAX_LEN: usize = 1 << 20;
+
+/// How key or value lengths are drawn
+#[derive(Clone, Debug)]
+enum LengthDistribution {
+    Fixed(usize),
+    /// Inclusive range
+    Uniform(usize, usize),
+    /// Rounded and clamped to 1..=MAX_LEN
+    LogNormal {
+        mean: f64,
+        cv: f64,
+        dist: LogNormal<f64>,
+    },
+}
+
+impl LengthDistribution {
+    fn sample(&self, rng: &mut ChaCha8Rng) -> usize {
+        match self {
+            LengthDistribution::Fixed(len) => *len,
+            LengthDistribution::Uniform(min, max) => rng.random_range(*min..=*max),
+            LengthDistribution::LogNormal { dist, .. } => {
+                (dist.sample(rng).round() as usize).clamp(1, MAX_LEN)
+            }
+        }
+    }
+
+    /// Largest length this can produce
+    fn max(&self) -> usize {
+        match self {
+            LengthDistribution::Fixed(len) | LengthDistribution::Uniform(_, len) => *len,
+            LengthDistribution::LogNormal { .. } => MAX_LEN,
+        }
+    }
+}
+
+impl FromStr for LengthDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        let int = |p: &str| {
+            p.trim()
+                .parse::<usize>()
+                .map_err(|e| format!("Invalid length '{}' in '{}': {}", p, s, e))
+        };
+        let float = |p: &str| {
+            p.trim()
+                .parse::<f64>()
+                .map_err(|e| format!("Invalid number '{}' in '{}': {}", p, s, e))
+        };
+        match parts.as_slice() {
+            [len] | ["fixed", len] => Ok(LengthDistribution::Fixed(int(len)?)),
+            ["uniform", min, max] => {
+                let (min, max) = (int(min)?, int(max)?);
+                if min > max {
+                    return Err(format!("Empty range in '{}'", s));
+                }
+                Ok(LengthDistribution::Uniform(min, max))
+            }
+            ["lognormal", mean, cv] => {
+                let (mean, cv) = (float(mean)?, float(cv)?);
+                let dist = LogNormal::from_mean_cv(mean, cv)
+                    .map_err(|e| format!("Invalid lognormal parameters in '{}': {}", s, e))?;
+                Ok(LengthDistribution::LogNormal { mean, cv, dist })
+            }
+            _ => Err(format!(
+                "Expected N, fixed:N, uniform:MIN:MAX or lognormal:MEAN:CV, got '{}'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for LengthDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LengthDistribution::Fixed(len) => write!(f, "fixed:{}", len),
+            LengthDistribution::Uniform(min, max) => write!(f, "uniform:{}:{}", min, max),
+            LengthDistribution::LogNormal { mean, cv, .. } => {
+                write!(f, "lognormal:{}:{}", mean, cv)
+            }
+        }
+    }
+}
+
+/// Parses a byte size such as "512MB", "10GB" or a plain number of bytes
+fn parse_size(s: &str) -> Result<u64, String> {
+    let upper = s.trim().to_ascii_uppercase();
+    let (number, unit) = match upper.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(i) => upper.split_at(i),
+        None => (upper.as_str(), ""),
+    };
+    let multiplier: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "KB" | "KIB" => 1 << 10,
+        "MB" | "MIB" => 1 << 20,
+        "GB" | "GIB" => 1 << 30,
+        "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("Unknown size unit in '{}'", s)),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|e| format!("Invalid size '{}': {}", s, e))?;
+    Ok((number * multiplier as f64) as u64)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    let _telemetry = telemetry::init("generate-kvbin");
+    let _span = info_span!(
+        "generate",
+        num_records = args.num_records,
+        target_bytes = args.target_bytes,
+        seed = args.seed
+    )
+    .entered();
+
+    if args.index && args.index_interval == 0 {
+        return Err("--index-interval must be at least 1".into());
+    }
+    for dist in [&args.key_len, &args.value_len] {
+        if dist.max() > u32::MAX as usize {
+            return Err(format!("Length {} does not fit the u32 length prefix", dist.max()).into());
+        }
+    }
+
+    // ChaCha8's output is fixed by its spec, so a seed means the same data on every machine
+    let seed = args.seed.unwrap_or_else(rand::random);
+    eprintln!("Seed: {}", seed);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let file = File::create(&args.output)?;
+    let mut writer = BufWriter::with_capacity(16 * 1024 * 1024, file); // 16MB buffer
+
+    let mut index = kvbin_index::Builder::new(Spacing::Records(args.index_interval));
+    let mut buffer = Vec::new();
+    let mut records = 0u64;
+    let mut bytes = 0u64;
+
+    let start = Instant::now();
+    let bar = Bar::new(args.num_records, !args.no_progress);
+
+    loop {
+        let done = match (args.num_records, args.target_bytes) {
+            (Some(n), _) => records >= n,
+            (None, Some(target)) => bytes >= target,
+            (None, None) => unreachable!("clap requires one of them"),
+        };
+        if done {
+            break;
+        }
+
+        let key_len = args.key_len.sample(&mut rng);
+        let value_len = args.value_len.sample(&mut rng);
+
+        // u32 LE key length, key, u32 LE value length, value
+        buffer.clear();
+        buffer.extend_from_slice(&(key_len as u32).to_le_bytes());
+        buffer.resize(4 + key_len, 0);
+        rng.fill_bytes(&mut buffer[4..]);
+        buffer.extend_from_slice(&(value_len as u32).to_le_bytes());
+        let value_start = buffer.len();
+        buffer.resize(value_start + value_len, 0);
+        rng.fill_bytes(&mut buffer[value_start..]);
+
+        writer.write_all(&buffer)?;
+        index.push(buffer.len() as u64);
+        records += 1;
+        bytes += buffer.len() as u64;
+
+        if records.is_multiple_of(10_000) {
+            bar.set(records, bytes);
+        }
+    }
+
+    writer.flush()?;
+    bar.set(records, bytes);
+    bar.finish();
+
+    let offsets = index.finish().offsets;
+    let index_path = args.index.then(|| kvbin_index::path_for(&args.output));
+    if let Some(ref path) = index_path {
+        kvbin_index::write(path, &offsets)?;
+    }
+
+    let total_elapsed = start.elapsed().as_secs_f64();
+    let total_mb = bytes as f64 / (1024.0 * 1024.0);
+
+    eprintln!("\n=== Generation Complete ===");
+    eprintln!("Records: {}", records);
+    eprintln!("Seed: {}", seed);
+    eprintln!("Key lengths: {}", args.key_len);
+    eprintln!("Value lengths: {}", args.value_len);
+    eprintln!(
+        "File size: {:.2} GB ({:.2} MB)",
+        total_mb / 1024.0,
+        total_mb
+    );
+    eprintln!("Time: {:.2} seconds", total_elapsed);
+    eprintln!("Speed: {:.2} MB/s", total_mb / total_elapsed);
+    eprintln!("Output: {}", args.output.display());
+    if let Some(path) = index_path {
+        eprintln!("Index: {} ({} entries)", path.display(), offsets.len());
+    }
+
+    Ok(())
+}
@@ -1,16 +1,37 @@
 use clap::{Parser, ValueEnum};
-use clickhouse::Client;
+use clickhouse::{Client, Compression, Row, RowOwned, RowWrite};
+use es_duck::affinity;
+use es_duck::buffer_pool::BufferPool;
+use es_duck::cgroup::parse_memory_to_bytes;
+use es_duck::checkpoint::{self, Checkpoint};
+use es_duck::input_files::{self, InputFiles};
+use es_duck::inspect;
+use es_duck::kvbin_index;
+use es_duck::latency;
+use es_duck::loader::IfExists;
+use es_duck::metrics::{self, Phase};
+use es_duck::profile;
+use es_duck::progress;
+use es_duck::progress_bar;
+use es_duck::records::{self, FixedLayout, FixedRecords, IoOptions, Window};
+use es_duck::rowbinary::{ColumnType, Encoder, Value};
+use es_duck::scale::ScaleOptions;
+use es_duck::schema::{self, ColumnNames, ExtraColumn, Schema};
+use es_duck::shards::{self, Router, ShardBy};
+use es_duck::stream::{BudgetPermit, ByteBudget, ChannelReader, UploadProgress};
+use es_duck::telemetry;
+use serde::{Serialize, Serializer};
 use std::error::Error;
 use std::fs::File;
-use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
-use std::pin::Pin;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::task::{Context, Poll};
-use tokio::io::{AsyncRead, ReadBuf};
-use tokio::sync::mpsc::{Sender, channel};
-use tokio::task;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+use tokio::task::{self, JoinHandle};
+use tracing::{Instrument, info_span};
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
 enum InputFormat {
@@ -18,14 +39,138 @@ enum InputFormat {
     Kvbin,
 }
 
+impl From<InputFormat> for records::InputFormat {
+    fn from(format: InputFormat) -> Self {
+        match format {
+            InputFormat::Gensort => records::InputFormat::Gensort,
+            InputFormat::Kvbin => records::InputFormat::Kvbin,
+        }
+    }
+}
+
+/// When an async INSERT is acknowledged
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum AsyncInsert {
+    /// Once the server has flushed its buffer into the table
+    Wait,
+    /// As soon as the server has buffered the data
+    NoWait,
+}
+
+/// How the rows reach the server
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Via {
+    /// RowBinary encoded here and streamed in the bodies of HTTP INSERTs
+    Http,
+    /// The clickhouse crate's typed `insert()`, which checks the rows against
+    /// the table's columns and sends them LZ4-compressed
+    ClientInsert,
+}
+
+/// ClickHouse type of the key column
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum KeyType {
+    String,
+    /// UInt64 of the first 8 key bytes, big-endian
+    Uint64,
+}
+
+impl KeyType {
+    fn sql(self) -> &'static str {
+        match self {
+            KeyType::String => "String",
+            KeyType::Uint64 => "UInt64",
+        }
+    }
+
+    /// RowBinary type of the key column
+    fn column(self) -> ColumnType {
+        match self {
+            KeyType::String => ColumnType::String,
+            KeyType::Uint64 => ColumnType::UInt64,
+        }
+    }
+
+    fn value(self, key: &[u8]) -> Value<'_> {
+        match self {
+            KeyType::String => Value::Bytes(key),
+            KeyType::Uint64 => Value::UInt64(schema::key_u64(key)),
+        }
+    }
+}
+
+/// How the readers encode each record: the key column's type, how many
+/// copies of the record go into the table, and whether rows carry their id
+#[derive(Copy, Clone, Debug)]
+struct Encoding {
+    key_type: KeyType,
+    scale: ScaleOptions,
+    row_id: bool,
+}
+
+impl Encoding {
+    fn new(args: &Args) -> Self {
+        Self {
+            key_type: args.key_type,
+            scale: args.scale,
+            row_id: args.with_row_id,
+        }
+    }
+
+    /// RowBinary types of the columns, in `insert_columns` order
+    fn columns(self) -> Vec<ColumnType> {
+        let mut columns = vec![self.key_type.column(), ColumnType::String];
+        if self.row_id {
+            columns.push(ColumnType::UInt64);
+        }
+        columns
+    }
+
+    /// Encodes one record, once per --scale-factor copy; `id` is the record's
+    /// id in the input (`schema::ROW_ID_COLUMN`)
+    fn encode(
+        self,
+        encoder: &mut Encoder,
+        id: u64,
+        key: &[u8],
+        payload: &[u8],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.scale.each_copy(key, payload, |copy, key, payload| {
+            let key = self.key_type.value(key);
+            match self.row_id {
+                true => {
+                    let row_id = Value::UInt64(self.scale.row_id(id, copy)?);
+                    encoder.encode_row(&[key, Value::Bytes(payload), row_id])
+                }
+                false => encoder.encode_row(&[key, Value::Bytes(payload)]),
+            }
+        })
+    }
+}
+
+/// Column list of the INSERTs
+fn insert_columns(args: &Args) -> String {
+    let columns = args.column_names.list(Schema::KeyPayload);
+    match args.with_row_id {
+        true => format!("{}, {}", columns, schema::ROW_ID_COLUMN),
+        false => columns,
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "es-duck-clickhouse")]
+#[command(about = "Load gensort or kvbin records into a ClickHouse table")]
 struct Args {
     #[arg(long, value_enum)]
     format: InputFormat,
 
-    #[arg(long)]
-    input: PathBuf,
+    #[command(flatten)]
+    layout: FixedLayout,
+
+    /// Input file; several (e.g. --input parts/*.gensort) load together, their
+    /// parts shared out between the threads
+    #[arg(long, num_args = 1.., required = true)]
+    input: Vec<PathBuf>,
 
     #[arg(long, default_value = "http://localhost:8123")]
     url: String,
@@ -33,107 +178,496 @@ struct Args {
     #[arg(long, default_value = "default")]
     database: String,
 
+    /// ClickHouse user
+    #[arg(long, env = "CLICKHOUSE_USER", default_value = "default")]
+    user: String,
+
+    /// Password for --user
+    #[arg(long, env = "CLICKHOUSE_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+
+    /// PEM file of CA certificates to trust in addition to the system roots,
+    /// for an https:// --url with a private CA
+    #[arg(long, env = "CLICKHOUSE_CA_CERT")]
+    ca_cert: Option<PathBuf>,
+
     #[arg(long, default_value = "bench_data")]
     table: String,
 
+    /// What to do when the table already exists
+    #[arg(long, value_enum, default_value_t = IfExists::Fail)]
+    if_exists: IfExists,
+
+    #[command(flatten)]
+    column_names: ColumnNames,
+
+    /// Column type of the key
+    #[arg(long, value_enum, default_value = "string")]
+    key_type: KeyType,
+
+    /// Add a column the load leaves empty, e.g. --extra-column "tenant_id Nullable(Int32)"
+    /// (repeatable); rows get the type's default
+    #[arg(long = "extra-column", value_name = "NAME TYPE")]
+    extra_columns: Vec<ExtraColumn>,
+
+    /// Table engine, e.g. "ReplacingMergeTree()" or "Log". Like the options
+    /// below, it only applies when the table does not exist yet.
+    #[arg(long, default_value = "MergeTree()")]
+    engine: String,
+
+    /// ORDER BY of a MergeTree-family table; "tuple()" (unsorted) by default
+    #[arg(long)]
+    order_by: Option<String>,
+
+    /// PARTITION BY of a MergeTree-family table
+    #[arg(long)]
+    partition_by: Option<String>,
+
+    /// Table SETTINGS, e.g. "index_granularity = 1024, min_bytes_for_wide_part = 0"
+    #[arg(long)]
+    table_settings: Option<String>,
+
     #[arg(long, default_value_t = 1)]
     threads: usize,
 
+    #[command(flatten)]
+    io: IoOptions,
+
+    #[command(flatten)]
+    scale: ScaleOptions,
+
+    /// Leave out this many records at the start of the input
+    #[arg(long, default_value_t = 0)]
+    skip: u64,
+
+    /// Load at most this many records
+    #[arg(long)]
+    limit: Option<u64>,
+
+    /// Read and check the input without connecting to the database: print the
+    /// record count, bytes, smallest and largest key and any malformed records
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Add a UInt64 row_id column numbering the rows in input order: the
+    /// record number of gensort input, the byte offset of kvbin input
+    #[arg(long)]
+    with_row_id: bool,
+
     /// Number of records to batch before sending (higher = more memory, less overhead)
     #[arg(long, default_value_t = 100_000)]
     batch_size: usize,
+
+    /// How the rows reach the server. `client-insert` gives each reader
+    /// thread an INSERT of its own through the clickhouse crate, as a
+    /// comparison point for the hand-encoded `http` path.
+    #[arg(long, value_enum, default_value_t = Via::Http)]
+    via: Via,
+
+    /// Concurrent INSERT requests, each fed by its own share of the reader threads
+    #[arg(long, default_value_t = 1)]
+    upload_streams: usize,
+
+    /// Bound the encoded RowBinary waiting for the upload streams to this
+    /// many bytes (e.g. "512MB"), summed over all reader threads, instead of
+    /// to 4 batches per thread whatever their size
+    #[arg(long, conflicts_with = "checkpoint_file",
+          value_parser = |s: &str| parse_memory_to_bytes(s).map_err(|e| e.to_string()))]
+    max_buffer_bytes: Option<u64>,
+
+    /// Split each upload stream into INSERTs of about this much RowBinary data
+    /// (e.g. "256MB"), retried on failure. Without it each stream is one
+    /// streaming INSERT, and any failure aborts the load.
+    #[arg(long, value_parser = |s: &str| parse_memory_to_bytes(s).map_err(|e| e.to_string()))]
+    insert_bytes: Option<u64>,
+
+    /// Send INSERTs with async_insert=1, so the server batches many small
+    /// INSERTs into parts itself, as under high-concurrency streaming
+    /// ingestion. Uses INSERTs of --insert-bytes, 1MB by default.
+    #[arg(long, value_enum)]
+    async_insert: Option<AsyncInsert>,
+
+    /// Retries of a failed INSERT (with --insert-bytes or --async-insert)
+    #[arg(long, default_value_t = 5)]
+    insert_retries: u32,
+
+    /// Wait before the first retry in milliseconds, doubled for each further one
+    #[arg(long, default_value_t = 500)]
+    retry_backoff_ms: u64,
+
+    /// Load into this many tables, <table>_shard0 .. <table>_shard<N-1>,
+    /// instead of one (see --shard-by). Each shard gets an upload stream of
+    /// its own.
+    #[arg(long, conflicts_with_all = ["checkpoint_file", "upload_streams"])]
+    shards: Option<usize>,
+
+    /// How --shards spreads the rows between the tables
+    #[arg(long, value_enum, default_value_t = ShardBy::RoundRobin, requires = "shards")]
+    shard_by: ShardBy,
+
+    /// Record finished units of the input in this file, and when it exists
+    /// skip the units an earlier run loaded. Each unit is one retried INSERT
+    /// with a deduplication token derived from the unit, so no row is lost or
+    /// loaded twice. The file is deleted once the load completes.
+    #[arg(long, conflicts_with_all = ["insert_bytes", "async_insert", "upload_streams"])]
+    checkpoint_file: Option<PathBuf>,
+
+    /// Input bytes per checkpointed unit (e.g. "64MB")
+    #[arg(long, default_value = "256MB", requires = "checkpoint_file",
+          value_parser = |s: &str| parse_memory_to_bytes(s).map_err(|e| e.to_string()))]
+    checkpoint_unit: u64,
+
+    /// Without a kvbin index, read the input once more to check that the record
+    /// boundaries found by scanning are real (no record skipped or loaded twice)
+    #[arg(long)]
+    validate_boundaries: bool,
+
+    /// After loading, check with SELECT count() that the table holds at least the uploaded rows
+    #[arg(long)]
+    verify_count: bool,
+
+    /// Expose Prometheus metrics on this port while loading
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Print a JSON progress event to stderr every this many seconds
+    #[arg(long)]
+    progress_interval: Option<u64>,
+
+    /// Don't draw a progress bar on stderr
+    #[arg(long)]
+    no_progress: bool,
+
+    /// Write per-second throughput samples to this CSV file (render with plot-throughput)
+    #[arg(long)]
+    throughput_log: Option<PathBuf>,
+
+    /// Write a CPU flamegraph of this run to this SVG file (needs the util-profile feature)
+    #[arg(long)]
+    profile: Option<PathBuf>,
+
+    /// Pin threads to these CPUs (e.g. "0-15,32-47")
+    #[arg(long)]
+    cpus: Option<String>,
+
+    /// Run on the CPUs of this NUMA node and allocate memory from it
+    #[arg(long)]
+    numa_node: Option<usize>,
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
     let args = Args::parse();
+    // Before the runtime starts its worker threads, so they inherit the placement
+    affinity::apply(args.cpus.as_deref(), args.numa_node)?;
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(load(args))
+}
 
-    // Initialize ClickHouse connection for table setup
-    let client = Client::default()
-        .with_url(&args.url)
-        .with_database(&args.database);
+async fn load(mut args: Args) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let _telemetry = telemetry::init("load-clickhouse");
+    let span = info_span!("load", engine = "clickhouse", input = %args.input[0].display());
+    let _profile = profile::start(args.profile.as_deref())?;
 
-    // Create table (unsorted for benchmarking)
-    println!("Creating table if not exists...");
-    client
-        .query(&format!(
-            "CREATE TABLE IF NOT EXISTS {} (
-                sort_key String,
-                payload String
-            ) ENGINE = MergeTree()
-            ORDER BY tuple()",
-            args.table
-        ))
-        .execute()
-        .await?;
+    if let Some(port) = args.metrics_port {
+        metrics::serve(port)?;
+        println!("Serving Prometheus metrics on port {}", port);
+    }
+    if let Some(secs) = args.progress_interval {
+        progress::start(Duration::from_secs(secs));
+    }
+    if let Some(ref path) = args.throughput_log {
+        metrics::record_throughput(path)?;
+    }
+    metrics::set_phase(Phase::Setup);
 
-    println!(
-        "Starting load from {:?} with {} threads (batch_size={})...",
-        args.input, args.threads, args.batch_size
-    );
+    if args.upload_streams == 0 {
+        return Err("--upload-streams must be at least 1".into());
+    }
+    if args.insert_bytes == Some(0) {
+        return Err("--insert-bytes must be at least 1".into());
+    }
+    // A stream has no size to split, so one thread reads it as it arrives
+    let stream = records::is_stream(&args.input[0])?;
+    args.io.check(args.format.into(), stream)?;
+    if stream {
+        if args.checkpoint_file.is_some() {
+            return Err("--checkpoint-file needs a regular input file, not a stream".into());
+        }
+        args.threads = 1;
+    }
+    if let Some(shards) = args.shards {
+        shards::check(shards, args.shard_by)?;
+    }
+    args.scale.check()?;
+    if args.scale.is_scaled() && args.checkpoint_file.is_some() {
+        return Err("--scale-factor does not support --checkpoint-file".into());
+    }
+    if args.input.len() > 1 {
+        input_files::check(&args.input, args.skip, args.limit)?;
+        if args.checkpoint_file.is_some()
+            || args.shards.is_some()
+            || args.with_row_id
+            || args.max_buffer_bytes.is_some()
+        {
+            return Err(
+                "Several --input files do not support --checkpoint-file, --shards, \
+                        --with-row-id or --max-buffer-bytes"
+                    .into(),
+            );
+        }
+    }
+    if args.via == Via::ClientInsert
+        && (args.input.len() > 1
+            || args.checkpoint_file.is_some()
+            || args.shards.is_some()
+            || args.insert_bytes.is_some()
+            || args.async_insert.is_some()
+            || args.max_buffer_bytes.is_some()
+            || args.upload_streams > 1
+            || args.ca_cert.is_some()
+            || args.key_type != KeyType::String
+            || args.column_names != ColumnNames::default())
+    {
+        // The typed rows name the default columns, and each reader thread
+        // sends one INSERT of its own
+        return Err(
+            "--via client-insert does not support several --input files, \
+                    --checkpoint-file, --shards, --insert-bytes, --async-insert, \
+                    --max-buffer-bytes, --upload-streams, --ca-cert, --key-type uint64, \
+                    --key-column or --payload-column"
+                .into(),
+        );
+    }
+    args.layout.check()?;
+    if args.dry_run {
+        for input in &args.input {
+            if args.input.len() > 1 {
+                println!("{}:", input.display());
+            }
+            inspect::inspect(
+                args.format.into(),
+                args.layout,
+                input,
+                args.skip,
+                args.limit,
+            )?
+            .report()?;
+        }
+        return Ok(());
+    }
+    let files = match args.input.len() {
+        1 => None,
+        _ => Some(Arc::new(InputFiles::new(
+            args.format.into(),
+            args.layout,
+            &args.input,
+            args.threads,
+            input_files::UNIT_BYTES,
+        )?)),
+    };
+    let range = match files {
+        Some(ref files) => 0..files.bytes(),
+        None => records::select(
+            args.format.into(),
+            args.layout,
+            &args.input[0],
+            args.skip,
+            args.limit,
+        )?,
+    };
+    if args.skip > 0 || args.limit.is_some() {
+        println!("Selected input bytes {}..{}", range.start, range.end);
+    }
+
+    // Table setup and uploads share one client, so they connect the same way
+    let conn = Connection::new(&args)?;
+    let tables = match args.shards {
+        Some(shards) => shards::table_names(&args.table, shards),
+        None => vec![args.table.clone()],
+    };
+
+    // A checkpointed load resumes into the table it started, whatever --if-exists says
+    let resuming = args
+        .checkpoint_file
+        .as_ref()
+        .is_some_and(|path| path.exists());
+    for table in &tables {
+        let exists = conn.execute(&format!("EXISTS TABLE {}", table)).await?;
+        if !resuming && args.if_exists.must_drop(table, exists.trim() == "1")? {
+            println!("Dropping table {}...", table);
+            conn.execute(&format!("DROP TABLE {} SYNC", table)).await?;
+        }
+    }
+
+    // Create table (unsorted for benchmarking unless --order-by says otherwise)
+    println!("Creating table if not exists...");
+    for table in &tables {
+        conn.execute(&create_table_sql(&args, table)?).await?;
+        if deduplicates_inserts(&args) {
+            ensure_deduplication_window(&conn, table).await?;
+        }
+    }
+    let target = Target {
+        conn,
+        table: args.table.clone(),
+        columns: insert_columns(&args),
+        encoding: Encoding::new(&args),
+        streams: args.upload_streams,
+        buffer: args.max_buffer_bytes.map(ByteBudget::new),
+        progress: UploadProgress::new((range != records::STREAM).then(|| range.end - range.start)),
+        // Enough for the chunks the channels hold and one being encoded per thread
+        pool: BufferPool::new(args.threads * 5 + args.upload_streams),
+        retried_inserts: insert_bytes(&args).map(|max_bytes| RetriedInserts {
+            max_bytes: max_bytes as usize,
+            retries: args.insert_retries,
+            backoff: Duration::from_millis(args.retry_backoff_ms),
+            async_insert: args.async_insert,
+        }),
+    };
+    metrics::set_phase(Phase::Load);
+
+    match files {
+        Some(_) => println!(
+            "Starting load from {} files with {} threads...",
+            args.input.len(),
+            args.threads
+        ),
+        None => println!(
+            "Starting load from {:?} with {} threads, {} upload streams (batch_size={})...",
+            args.input[0], args.threads, args.upload_streams, args.batch_size
+        ),
+    }
+    let bar = progress_bar::load(args.format.into(), args.layout, &range, !args.no_progress);
 
     let rows = match args.format {
+        _ if args.checkpoint_file.is_some() => {
+            load_checkpointed(&args, range, &target.conn)
+                .instrument(span)
+                .await?
+        }
+        _ if files.is_some() => {
+            load_files(&args, files.unwrap(), &target.conn)
+                .instrument(span)
+                .await?
+        }
+        _ if args.shards.is_some() => {
+            load_shards(&args, range, &target, &tables)
+                .instrument(span)
+                .await?
+        }
+        _ if args.via == Via::ClientInsert => {
+            load_client_insert(&args, range).instrument(span).await?
+        }
         InputFormat::Gensort => {
             load_gensort_streaming(
-                &args.input,
-                &args.url,
-                &args.table,
+                &args.input[0],
+                args.layout,
+                range.start / args.layout.record_size() as u64
+                    ..range.end / args.layout.record_size() as u64,
+                &target,
                 args.threads,
                 args.batch_size,
+                args.io,
             )
+            .instrument(span)
             .await?
         }
         InputFormat::Kvbin => {
             load_kvbin_streaming(
-                &args.input,
-                &args.url,
-                &args.table,
+                &args.input[0],
+                range,
+                args.io,
+                &target,
                 args.threads,
                 args.batch_size,
+                args.validate_boundaries,
             )
+            .instrument(span)
             .await?
         }
     };
+    bar.finish();
+    if target.progress.sent().0 > 0 {
+        println!("{}", target.progress.line());
+        let pool = target.pool.stats();
+        println!(
+            "Chunk buffers: {} allocated, {} reused",
+            pool.created, pool.reused
+        );
+    }
+    if let Some(ref buffer) = target.buffer {
+        println!(
+            "Buffered at most {:.1} MB of RowBinary",
+            buffer.peak() as f64 / 1_000_000.0
+        );
+    }
+
+    if let Some(AsyncInsert::NoWait) = args.async_insert {
+        // Acknowledged rows may still sit in the server's buffer
+        println!("Flushing the async insert queue...");
+        target
+            .conn
+            .execute("SYSTEM FLUSH ASYNC INSERT QUEUE")
+            .await?;
+    }
+
+    for table in &tables {
+        for column in &args.extra_columns {
+            target.conn.execute(&column.add_sql(table)).await?;
+        }
+    }
+
+    if args.verify_count {
+        let mut table_rows = 0u64;
+        for table in &tables {
+            let count = target
+                .conn
+                .execute(&format!("SELECT count() FROM {}", table))
+                .await?;
+            table_rows += count
+                .trim()
+                .parse::<u64>()
+                .map_err(|e| format!("Unexpected count() result '{}': {}", count.trim(), e))?;
+        }
+        let name = match args.shards {
+            Some(shards) => format!("the {} shards of {}", shards, args.table),
+            None => args.table.clone(),
+        };
+        // The table may already have held rows (CREATE TABLE IF NOT EXISTS)
+        if table_rows < rows {
+            return Err(format!(
+                "Uploaded {} rows, but only {} are in {}",
+                rows, table_rows, name
+            )
+            .into());
+        }
+        println!("Verified: {} rows in {}", table_rows, name);
+    }
 
+    metrics::set_phase(Phase::Done);
     println!("Successfully loaded {} rows to ClickHouse.", rows);
+    latency::report("HTTP chunk");
     Ok(())
 }
 
-/// Optimized Gensort loader using direct RowBinary streaming
+/// Optimized Gensort loader using direct RowBinary streaming; loads records
+/// `records` (record numbers)
 async fn load_gensort_streaming(
-    input: &PathBuf,
-    url: &str,
-    table: &str,
+    input: &Path,
+    layout: FixedLayout,
+    records: Range<u64>,
+    target: &Target,
     num_threads: usize,
     batch_size: usize,
+    io: IoOptions,
 ) -> Result<u64, Box<dyn Error + Send + Sync>> {
-    const KEY_SIZE: usize = 10;
-    const PAYLOAD_SIZE: usize = 90;
-    const RECORD_SIZE: usize = KEY_SIZE + PAYLOAD_SIZE;
-
-    let file = File::open(input)?;
-    let file_size = file.metadata()?.len();
-    let total_records = file_size / RECORD_SIZE as u64;
-    drop(file);
-
-    // Use bounded channel to prevent OOM (buffer up to threads*4 batches)
-    let (tx, rx) = channel::<Vec<u8>>(num_threads * 4);
-
-    // Spawn HTTP uploader task
-    let upload_url = format!("{}/?query=INSERT+INTO+{}+FORMAT+RowBinary", url, table);
-    let total_rows = Arc::new(AtomicU64::new(0));
-    let total_rows_clone = total_rows.clone();
-
-    let uploader = tokio::spawn(async move {
-        let client = reqwest::Client::new();
-        let reader = ChannelReader::new(rx, total_rows_clone);
-        let stream = tokio_util::io::ReaderStream::new(reader);
-        client
-            .post(&upload_url)
-            .body(reqwest::Body::wrap_stream(stream))
-            .send()
-            .await
-    });
+    let total_records = records.end - records.start;
+
+    // Bounded channels prevent OOM (buffer up to threads*4 batches in all,
+    // or --max-buffer-bytes)
+    let (senders, uploads) = Uploads::start(target, num_threads * 4);
 
     // Spawn reader/formatter threads
     let records_per_thread = (total_records + num_threads as u64 - 1) / num_threads as u64;
@@ -146,89 +680,1125 @@ async fn load_gensort_streaming(
         if start_record >= total_records {
             break;
         }
+        let (start_record, end_record) = (records.start + start_record, records.start + end_record);
 
-        let input = input.clone();
-        let tx = tx.clone();
+        let input = input.to_path_buf();
+        let encoding = target.encoding;
+        // Reader threads take turns between the streams
+        let tx = senders[thread_id % senders.len()].clone();
+        // Reader threads read the file and encode it as RowBinary in the same loop
+        let span = info_span!("encode", start_record, end_record);
 
         let handle = task::spawn_blocking(move || -> Result<u64, Box<dyn Error + Send + Sync>> {
-            format_gensort_to_rowbinary(&input, start_record, end_record, tx, batch_size)
+            // Blocking-pool threads may be spawned by runtime workers that predate `apply`
+            affinity::pin_worker()?;
+            let _span = span.entered();
+            format_gensort_to_rowbinary(
+                &input,
+                layout,
+                start_record..end_record,
+                io,
+                tx,
+                batch_size,
+                encoding,
+            )
         });
 
         handles.push(handle);
     }
 
-    // Drop original sender so channel closes when all threads finish
-    drop(tx);
+    // Drop original senders so the channels close when all threads finish
+    drop(senders);
 
-    // Wait for all reader threads
-    for (i, handle) in handles.into_iter().enumerate() {
-        match handle.await {
-            Ok(result) => match result {
-                Ok(rows) => println!("Thread {} formatted {} records", i, rows),
-                Err(e) => return Err(format!("Thread {} failed: {}", i, e).into()),
-            },
+    let formatted = join_readers(handles).await;
+    // A failed upload closes its channel, so its error explains a failed reader
+    let uploaded = uploads.finish().await?;
+    check_uploaded(target.encoding.scale.rows(formatted?), uploaded)
+}
+
+/// Loads the input in checkpointed units, spread round-robin over the threads,
+/// as one INSERT per unit. A unit's deduplication token depends only on the
+/// load and the unit, so the server drops a unit that an earlier run inserted
+/// but did not get to mark done.
+async fn load_checkpointed(
+    args: &Args,
+    range: Range<u64>,
+    conn: &Connection,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let path = args.checkpoint_file.as_deref().unwrap();
+    let format = args.format.into();
+    let description = checkpoint::describe(
+        format,
+        args.layout,
+        &args.input[0],
+        &range,
+        &args.table,
+        args.checkpoint_unit,
+    )?;
+    let units = checkpoint::units(
+        format,
+        args.layout,
+        &args.input[0],
+        range,
+        args.checkpoint_unit,
+    )?;
+    let checkpoint = Arc::new(Checkpoint::open(path, &description)?);
+    println!(
+        "Loading {} units ({} already loaded)",
+        units.len(),
+        checkpoint.done_units()
+    );
+
+    let token_prefix = format!("{:x}", crc32fast::hash(description.as_bytes()));
+    let insert = format!(
+        "INSERT INTO {} ({}) FORMAT RowBinary",
+        args.table,
+        insert_columns(args)
+    );
+    let threads = args.threads.max(1);
+    let units = Arc::new(units);
+    let mut tasks = Vec::with_capacity(threads);
+    for t in 0..threads {
+        let (units, checkpoint) = (units.clone(), checkpoint.clone());
+        let (conn, insert, token_prefix) = (conn.clone(), insert.clone(), token_prefix.clone());
+        let input = args.input[0].clone();
+        let (format, layout, io) = (args.format, args.layout, args.io);
+        let encoding = Encoding::new(args);
+        let retries = args.insert_retries;
+        let backoff = Duration::from_millis(args.retry_backoff_ms);
+        let span = info_span!("db_ingest", thread = t);
+        tasks.push(tokio::spawn(
+            async move {
+                let mut rows = 0u64;
+                for unit in units.iter().skip(t).step_by(threads) {
+                    if checkpoint.is_done(unit) {
+                        continue;
+                    }
+                    let (input, range) = (input.clone(), unit.clone());
+                    let (data, unit_rows) = task::spawn_blocking(move || {
+                        affinity::pin_worker()?;
+                        encode_unit(format, layout, io, &input, range, encoding)
+                    })
+                    .await??;
+                    let bytes = data.len() as u64;
+                    let token = format!("{}-{}-{}", token_prefix, unit.start, unit.end);
+                    let what = format!("INSERT {} (unit {}..{})", token, unit.start, unit.end);
+                    send_insert(
+                        &conn,
+                        &insert,
+                        &[("insert_deduplication_token", token.as_str())],
+                        &data.into(),
+                        retries,
+                        backoff,
+                        &what,
+                    )
+                    .await?;
+                    checkpoint.mark_done(unit)?;
+                    rows += unit_rows;
+                    metrics::add_rows_loaded(unit_rows);
+                    metrics::add_bytes_uploaded(bytes);
+                }
+                Ok::<u64, Box<dyn Error + Send + Sync>>(rows)
+            }
+            .instrument(span),
+        ));
+    }
+
+    let mut total_rows = 0u64;
+    for (i, task) in tasks.into_iter().enumerate() {
+        match task.await {
+            Ok(Ok(rows)) => total_rows += rows,
+            Ok(Err(e)) => return Err(format!("Thread {} failed: {}", i, e).into()),
             Err(e) => return Err(format!("Thread {} panicked: {}", i, e).into()),
         }
     }
+    match Arc::try_unwrap(checkpoint) {
+        Ok(checkpoint) => checkpoint.remove()?,
+        Err(_) => unreachable!("all tasks have finished"),
+    }
+    Ok(total_rows)
+}
 
-    // Wait for uploader
-    let resp = uploader
-        .await
-        .map_err(|e| format!("Uploader task failed: {}", e))??;
-    if !resp.status().is_success() {
-        let error_text = resp
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("ClickHouse error: {}", error_text).into());
+/// Loads several input files, one INSERT per unit. A unit's INSERT carries a
+/// deduplication token, so retrying it cannot load its rows twice.
+async fn load_files(
+    args: &Args,
+    files: Arc<InputFiles>,
+    conn: &Connection,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let insert = format!(
+        "INSERT INTO {} ({}) FORMAT RowBinary",
+        args.table,
+        insert_columns(args)
+    );
+    // Deduplication tokens must not repeat across loads of the same data
+    let run_id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let mut tasks = Vec::with_capacity(args.threads.max(1));
+    for t in 0..args.threads.max(1) {
+        let (files, conn, insert) = (files.clone(), conn.clone(), insert.clone());
+        let (format, layout, io) = (args.format, args.layout, args.io);
+        let encoding = Encoding::new(args);
+        let retries = args.insert_retries;
+        let backoff = Duration::from_millis(args.retry_backoff_ms);
+        let span = info_span!("db_ingest", thread = t);
+        tasks.push(tokio::spawn(
+            async move {
+                let mut rows = 0u64;
+                while let Some(unit) = files.next(t) {
+                    let (input, range) = (files.path(&unit).to_path_buf(), unit.range.clone());
+                    let (data, unit_rows) = task::spawn_blocking(move || {
+                        affinity::pin_worker()?;
+                        encode_unit(format, layout, io, &input, range, encoding)
+                    })
+                    .await??;
+                    let bytes = data.len() as u64;
+                    let (start, end) = (unit.range.start, unit.range.end);
+                    let token = format!("{:x}-{}-{}-{}", run_id, unit.file, start, end);
+                    let what = format!(
+                        "INSERT {} ({} {}..{})",
+                        token,
+                        files.path(&unit).display(),
+                        start,
+                        end
+                    );
+                    send_insert(
+                        &conn,
+                        &insert,
+                        &[("insert_deduplication_token", token.as_str())],
+                        &data.into(),
+                        retries,
+                        backoff,
+                        &what,
+                    )
+                    .await?;
+                    files.finish(&unit, unit_rows);
+                    rows += unit_rows;
+                    metrics::add_rows_loaded(unit_rows);
+                    metrics::add_bytes_uploaded(bytes);
+                }
+                Ok::<u64, Box<dyn Error + Send + Sync>>(rows)
+            }
+            .instrument(span),
+        ));
     }
 
-    Ok(total_rows.load(Ordering::Relaxed))
+    let mut total_rows = 0u64;
+    for (i, task) in tasks.into_iter().enumerate() {
+        match task.await {
+            Ok(Ok(rows)) => total_rows += rows,
+            Ok(Err(e)) => return Err(format!("Thread {} failed: {}", i, e).into()),
+            Err(e) => return Err(format!("Thread {} panicked: {}", i, e).into()),
+        }
+    }
+    Ok(total_rows)
 }
 
-/// Formats Gensort records into ClickHouse RowBinary format
-/// RowBinary for (String, String): [varint_len][bytes][varint_len][bytes]
-fn format_gensort_to_rowbinary(
-    input: &PathBuf,
-    start_record: u64,
-    end_record: u64,
-    tx: Sender<Vec<u8>>,
+/// Loads into the --shards `tables`: reader threads encode each row for the
+/// shard it is routed to, and every shard has an upload stream of its own
+async fn load_shards(
+    args: &Args,
+    range: Range<u64>,
+    target: &Target,
+    tables: &[String],
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let mut senders = Vec::with_capacity(tables.len());
+    let mut uploads = Vec::with_capacity(tables.len());
+    for table in tables {
+        let shard = Target {
+            conn: target.conn.clone(),
+            table: table.clone(),
+            columns: target.columns.clone(),
+            encoding: target.encoding,
+            streams: 1,
+            buffer: target.buffer.clone(),
+            progress: target.progress.clone(),
+            pool: target.pool.clone(),
+            retried_inserts: target.retried_inserts.clone(),
+        };
+        let (mut shard_senders, shard_uploads) = Uploads::start(&shard, args.threads * 4);
+        senders.push(shard_senders.remove(0));
+        uploads.push(shard_uploads);
+    }
+
+    let mut handles = vec![];
+    let ranges = shards::reader_ranges(args.format.into(), args.layout, range, args.threads);
+    for (reader, bytes) in ranges.into_iter().enumerate() {
+        let (input, senders) = (args.input[0].clone(), senders.clone());
+        let (format, layout, io) = (args.format, args.layout, args.io);
+        let (batch_size, encoding) = (args.batch_size, target.encoding);
+        let router = Router::new(args.shard_by, tables.len(), reader);
+        let span = info_span!("encode", start = bytes.start, end = bytes.end);
+        handles.push(task::spawn_blocking(
+            move || -> Result<u64, Box<dyn Error + Send + Sync>> {
+                affinity::pin_worker()?;
+                let _span = span.entered();
+                encode_shards(
+                    &input, format, layout, io, bytes, router, &senders, batch_size, encoding,
+                )
+            },
+        ));
+    }
+    // Drop original senders so the channels close when all threads finish
+    drop(senders);
+
+    let formatted = join_readers(handles).await;
+    let mut uploaded = 0u64;
+    let mut errors = Vec::new();
+    for (i, shard) in uploads.into_iter().enumerate() {
+        match shard.finish().await {
+            Ok(rows) => {
+                println!("Shard {} loaded {} rows", i, rows);
+                uploaded += rows;
+            }
+            Err(e) => errors.push(format!("Shard {}: {}", i, e)),
+        }
+    }
+    // A failed upload closes its channel, so its error explains a failed reader
+    if !errors.is_empty() {
+        return Err(errors.join("\n").into());
+    }
+    check_uploaded(target.encoding.scale.rows(formatted?), uploaded)
+}
+
+/// Encodes the records in bytes `range` as RowBinary for the shards `router`
+/// picks, and sends each shard's rows to its upload stream in chunks
+#[allow(clippy::too_many_arguments)]
+fn encode_shards(
+    input: &Path,
+    format: InputFormat,
+    layout: FixedLayout,
+    io: IoOptions,
+    range: Range<u64>,
+    mut router: Router,
+    senders: &[ChunkSender],
     batch_size: usize,
+    encoding: Encoding,
 ) -> Result<u64, Box<dyn Error + Send + Sync>> {
-    const RECORD_SIZE: usize = 100;
-    const KEY_SIZE: usize = 10;
-    const PAYLOAD_SIZE: usize = 90;
+    // About a gensort record's RowBinary: both fields and their length bytes
+    let batch_bytes = batch_size * (layout.record_size() + 2);
+    let mut encoders: Vec<Encoder> = senders
+        .iter()
+        .map(|_| Encoder::with_capacity(encoding.columns(), batch_bytes))
+        .collect();
+    // Input position, where each shard's pending rows were read from, and the
+    // bytes of those rows: the other shards' rows lie in between
+    let mut pos = range.start;
+    let mut chunk_starts = vec![range.start; senders.len()];
+    let mut chunk_bytes = vec![0u64; senders.len()];
+    let rows = shards::route(
+        input,
+        format.into(),
+        layout,
+        io,
+        range,
+        &mut router,
+        |shard, id, key, payload| {
+            let record = match format {
+                InputFormat::Gensort => (key.len() + payload.len()) as u64,
+                InputFormat::Kvbin => (8 + key.len() + payload.len()) as u64,
+            };
+            pos += record;
+            chunk_bytes[shard] += record;
+            let encoder = &mut encoders[shard];
+            encoding.encode(encoder, id, key, payload)?;
+            if encoder.len() >= batch_bytes {
+                let input = chunk_starts[shard]..pos;
+                send_chunk_of(&senders[shard], encoder, input, chunk_bytes[shard])?;
+                chunk_starts[shard] = pos;
+                chunk_bytes[shard] = 0;
+            }
+            Ok(())
+        },
+    )?;
+    for (shard, encoder) in encoders.iter_mut().enumerate() {
+        if !encoder.is_empty() {
+            let input = chunk_starts[shard]..pos;
+            send_chunk_of(&senders[shard], encoder, input, chunk_bytes[shard])?;
+        }
+    }
+    Ok(rows)
+}
 
-    let mut file = File::open(input)?;
-    file.seek(SeekFrom::Start(start_record * RECORD_SIZE as u64))?;
-    let mut reader = BufReader::with_capacity(4 * 1024 * 1024, file);
+/// Bytes sent as a RowBinary String; a `Vec<u8>` would go in as Array(UInt8)
+struct StringBytes(Vec<u8>);
 
-    // Pre-allocate output buffer: each record = 1 byte + 10 bytes + 1 byte + 90 bytes = 102 bytes
-    let mut output_buffer = Vec::with_capacity(batch_size * 102);
-    let mut raw_record = [0u8; RECORD_SIZE];
-    let num_records = end_record - start_record;
+impl Serialize for StringBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
 
-    for _ in 0..num_records {
-        reader.read_exact(&mut raw_record)?;
+/// A row of `--via client-insert`; the crate takes the column names from the
+/// field names, so they are the default ones
+#[derive(Row, Serialize)]
+struct KeyPayloadRow {
+    sort_key: StringBytes,
+    payload: StringBytes,
+}
 
-        // Key: varint length (10 fits in 1 byte) + data
-        output_buffer.push(KEY_SIZE as u8);
-        output_buffer.extend_from_slice(&raw_record[..KEY_SIZE]);
+/// A `KeyPayloadRow` with `--with-row-id`
+#[derive(Row, Serialize)]
+struct KeyPayloadIdRow {
+    sort_key: StringBytes,
+    payload: StringBytes,
+    row_id: u64,
+}
 
-        // Payload: varint length (90 fits in 1 byte) + data
-        output_buffer.push(PAYLOAD_SIZE as u8);
-        output_buffer.extend_from_slice(&raw_record[KEY_SIZE..]);
+/// Loads through the clickhouse crate's typed inserter (--via client-insert)
+async fn load_client_insert(
+    args: &Args,
+    range: Range<u64>,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    match args.with_row_id {
+        false => {
+            client_insert(args, range, |_, key, payload| KeyPayloadRow {
+                sort_key: StringBytes(key.to_vec()),
+                payload: StringBytes(payload.to_vec()),
+            })
+            .await
+        }
+        true => {
+            client_insert(args, range, |row_id, key, payload| KeyPayloadIdRow {
+                sort_key: StringBytes(key.to_vec()),
+                payload: StringBytes(payload.to_vec()),
+                row_id,
+            })
+            .await
+        }
+    }
+}
+
+/// Reads bytes `range` of the input on `--threads` threads, turns every
+/// record into `row(row_id, key, payload)` and writes each thread's rows into
+/// an INSERT of its own, in batches of `--batch-size`. The rows own their
+/// bytes, so `Insert::write` takes them as they are.
+async fn client_insert<R: RowOwned + RowWrite + Send + Sync>(
+    args: &Args,
+    range: Range<u64>,
+    row: fn(u64, &[u8], &[u8]) -> R,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let mut client = Client::default()
+        .with_url(&args.url)
+        .with_database(&args.database)
+        .with_user(&args.user)
+        .with_compression(Compression::Lz4);
+    if let Some(ref password) = args.password {
+        client = client.with_password(password);
+    }
+
+    let mut readers = vec![];
+    let mut inserts = vec![];
+    let ranges = shards::reader_ranges(args.format.into(), args.layout, range, args.threads);
+    for (reader, bytes) in ranges.into_iter().enumerate() {
+        // Batches of rows and the key and payload bytes in them
+        let (tx, mut rx) = channel::<(Vec<R>, u64)>(4);
+        let input = args.input[0].clone();
+        let (format, layout, io) = (args.format, args.layout, args.io);
+        let (scale, batch_size) = (args.scale, args.batch_size);
+        let span = info_span!("encode", start = bytes.start, end = bytes.end);
+        readers.push(task::spawn_blocking(
+            move || -> Result<u64, Box<dyn Error + Send + Sync>> {
+                affinity::pin_worker()?;
+                let _span = span.entered();
+                let mut router = Router::new(ShardBy::RoundRobin, 1, reader);
+                let (mut batch, mut batch_bytes) = (Vec::with_capacity(batch_size), 0u64);
+                let read = shards::route(
+                    &input,
+                    format.into(),
+                    layout,
+                    io,
+                    bytes,
+                    &mut router,
+                    |_, id, key, payload| {
+                        scale.each_copy(key, payload, |copy, key, payload| {
+                            batch.push(row(scale.row_id(id, copy)?, key, payload));
+                            batch_bytes += (key.len() + payload.len()) as u64;
+                            Ok::<(), Box<dyn Error + Send + Sync>>(())
+                        })?;
+                        if batch.len() >= batch_size {
+                            let full =
+                                std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+                            tx.blocking_send((full, std::mem::take(&mut batch_bytes)))
+                                .map_err(|_| "The INSERT stopped taking rows")?;
+                        }
+                        Ok(())
+                    },
+                )?;
+                if !batch.is_empty() {
+                    tx.blocking_send((batch, batch_bytes))
+                        .map_err(|_| "The INSERT stopped taking rows")?;
+                }
+                Ok(read)
+            },
+        ));
+
+        let (client, table) = (client.clone(), args.table.clone());
+        let span = info_span!("db_ingest", reader);
+        inserts.push(tokio::spawn(
+            async move {
+                let mut insert = client.insert::<R>(&table).await?;
+                let mut rows = 0u64;
+                while let Some((batch, bytes)) = rx.recv().await {
+                    for row in &batch {
+                        insert.write(row).await?;
+                    }
+                    rows += batch.len() as u64;
+                    metrics::add_rows_loaded(batch.len() as u64);
+                    metrics::add_bytes_uploaded(bytes);
+                }
+                insert.end().await?;
+                Ok::<u64, clickhouse::error::Error>(rows)
+            }
+            .instrument(span),
+        ));
+    }
+
+    let formatted = join_readers(readers).await;
+    let mut uploaded = 0u64;
+    let mut errors = Vec::new();
+    for (i, insert) in inserts.into_iter().enumerate() {
+        match insert.await {
+            Ok(Ok(rows)) => uploaded += rows,
+            Ok(Err(e)) => errors.push(format!("INSERT {}: {}", i, e)),
+            Err(e) => errors.push(format!("INSERT {} panicked: {}", i, e)),
+        }
+    }
+    // A failed INSERT closes its channel, so its error explains a failed reader
+    if !errors.is_empty() {
+        return Err(errors.join("\n").into());
+    }
+    check_uploaded(args.scale.rows(formatted?), uploaded)
+}
+
+/// RowBinary of one checkpointed unit: records `unit` of a gensort input of
+/// `layout`, or the kvbin records in bytes `unit`. Returns the data and its rows.
+fn encode_unit(
+    format: InputFormat,
+    layout: FixedLayout,
+    io: IoOptions,
+    input: &Path,
+    unit: Range<u64>,
+    encoding: Encoding,
+) -> Result<(Vec<u8>, u64), Box<dyn Error + Send + Sync>> {
+    let len = match format {
+        InputFormat::Gensort => (unit.end - unit.start) * layout.record_size() as u64,
+        InputFormat::Kvbin => unit.end - unit.start,
+    };
+    // Length prefixes take about as much room as the kvbin ones they replace
+    let mut encoder = Encoder::with_capacity(encoding.columns(), len as usize);
+
+    match format {
+        InputFormat::Gensort => {
+            let mut id = unit.start;
+            let mut reader = FixedRecords::open(io, input, layout, unit, 4 * 1024 * 1024)?;
+            while let Some(record) = reader.next_record()? {
+                let (key, payload) = record.split_at(layout.key_size);
+                encoding.encode(&mut encoder, id, key, payload)?;
+                id += 1;
+            }
+        }
+        InputFormat::Kvbin => {
+            let reader = Window::open(input, unit.clone())?;
+            let mut records = io.kvbin_reader(reader, input, unit)?;
+            while let Some((id, key, value)) = records.next_record_at()? {
+                encoding.encode(&mut encoder, id, key, value)?;
+            }
+        }
+    }
+    let rows = encoder.rows();
+    Ok((encoder.take(), rows))
+}
+
+/// Size of the bounded INSERTs, if the load uses them instead of one streaming
+/// INSERT per stream
+fn insert_bytes(args: &Args) -> Option<u64> {
+    const ASYNC_INSERT_BYTES: u64 = 1 << 20;
+
+    args.insert_bytes
+        .or(args.async_insert.map(|_| ASYNC_INSERT_BYTES))
+}
+
+/// Plain MergeTree tables only honour insert_deduplication_token with a window
+const DEDUPLICATION_WINDOW: &str = "non_replicated_deduplication_window = 1000";
+
+/// Whether the load tags its INSERTs with deduplication tokens, so that a
+/// retried one is not loaded twice
+fn deduplicates_inserts(args: &Args) -> bool {
+    insert_bytes(args).is_some() || args.checkpoint_file.is_some() || args.input.len() > 1
+}
+
+/// Gives a MergeTree table that CREATE TABLE IF NOT EXISTS left alone the
+/// deduplication window a new one gets, unless it has one of its own
+async fn ensure_deduplication_window(
+    conn: &Connection,
+    table: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let engine = conn
+        .execute(&format!(
+            "SELECT engine_full FROM system.tables WHERE database = currentDatabase() AND name = '{}'",
+            table.replace('\\', "\\\\").replace('\'', "\\'")
+        ))
+        .await?;
+    if engine.contains("MergeTree") && !engine.contains("non_replicated_deduplication_window") {
+        println!("Setting {} on {}...", DEDUPLICATION_WINDOW, table);
+        conn.execute(&format!(
+            "ALTER TABLE {} MODIFY SETTING {}",
+            table, DEDUPLICATION_WINDOW
+        ))
+        .await?;
+    }
+    Ok(())
+}
+
+/// CREATE TABLE IF NOT EXISTS for `table`, as described by the command line
+fn create_table_sql(args: &Args, table: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let row_id = match args.with_row_id {
+        true => format!(",\n            {} UInt64", schema::ROW_ID_COLUMN),
+        false => String::new(),
+    };
+    let mut sql = format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            {} {},
+            {} String{}
+        ) ENGINE = {}",
+        table,
+        args.column_names.key,
+        args.key_type.sql(),
+        args.column_names.payload,
+        row_id,
+        args.engine
+    );
+    let merge_tree = args.engine.contains("MergeTree");
+    if merge_tree {
+        if let Some(ref partition_by) = args.partition_by {
+            sql.push_str(&format!("\n        PARTITION BY {}", partition_by));
+        }
+        let order_by = args.order_by.as_deref().unwrap_or("tuple()");
+        sql.push_str(&format!("\n        ORDER BY {}", order_by));
+    } else if args.order_by.is_some() || args.partition_by.is_some() {
+        return Err(format!(
+            "--order-by and --partition-by need a MergeTree-family engine, not {}",
+            args.engine
+        )
+        .into());
+    }
+
+    let mut settings: Vec<&str> = args.table_settings.iter().map(String::as_str).collect();
+    if deduplicates_inserts(args) {
+        if !merge_tree {
+            println!(
+                "Warning: {} tables do not deduplicate, so a retried INSERT may load rows twice",
+                args.engine
+            );
+        } else if !settings
+            .iter()
+            .any(|s| s.contains("non_replicated_deduplication_window"))
+        {
+            settings.push(DEDUPLICATION_WINDOW);
+        }
+    }
+    if !settings.is_empty() {
+        sql.push_str(&format!("\n        SETTINGS {}", settings.join(", ")));
+    }
+    Ok(sql)
+}
+
+/// ClickHouse's HTTP interface, with the credentials and TLS settings from the
+/// command line
+#[derive(Clone)]
+struct Connection {
+    url: String,
+    database: String,
+    user: String,
+    password: Option<String>,
+    http: reqwest::Client,
+}
+
+impl Connection {
+    fn new(args: &Args) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(ref path) = args.ca_cert {
+            let pem = std::fs::read(path)
+                .map_err(|e| format!("Failed to read CA certificate {:?}: {}", path, e))?;
+            for cert in reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|e| format!("Invalid CA certificate {:?}: {}", path, e))?
+            {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        Ok(Self {
+            url: args.url.clone(),
+            database: args.database.clone(),
+            user: args.user.clone(),
+            password: args.password.clone(),
+            http: builder.build()?,
+        })
+    }
+
+    /// A POST running `query`; a body sent with it is the query's input data
+    fn request(&self, query: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .http
+            .post(&self.url)
+            .query(&[("query", query), ("database", &self.database)])
+            .header("X-ClickHouse-User", &self.user);
+        match self.password {
+            Some(ref password) => request.header("X-ClickHouse-Key", password),
+            None => request,
+        }
+    }
+
+    /// Runs a statement that takes no input; returns its output
+    async fn execute(&self, query: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let resp = self.request(query).send().await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        if !status.is_success() {
+            return Err(format!("ClickHouse error: {}", text).into());
+        }
+        Ok(text)
+    }
+}
+
+/// Encoded RowBinary bytes, the number of rows in them and the input bytes
+/// they were read from
+struct Chunk {
+    data: Vec<u8>,
+    rows: u64,
+    input: Range<u64>,
+    /// The bytes of `input` holding the chunk's records, all of them unless
+    /// the records of other shards lie in between
+    input_bytes: u64,
+    /// The data's share of --max-buffer-bytes, given back once an upload
+    /// stream takes the chunk
+    _permit: Option<BudgetPermit>,
+}
+
+impl From<Chunk> for (Vec<u8>, u64) {
+    fn from(chunk: Chunk) -> Self {
+        (chunk.data, chunk.rows)
+    }
+}
+
+/// The sending end of one upload stream's channel
+#[derive(Clone)]
+struct ChunkSender {
+    tx: Sender<Chunk>,
+    buffer: Option<Arc<ByteBudget>>,
+    pool: Arc<BufferPool<u8>>,
+}
+
+/// Hands the encoder's rows, read from `input`, to the uploader, first
+/// waiting for room in --max-buffer-bytes
+fn send_chunk(
+    sender: &ChunkSender,
+    encoder: &mut Encoder,
+    input: Range<u64>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let input_bytes = input.end - input.start;
+    send_chunk_of(sender, encoder, input, input_bytes)
+}
+
+/// `send_chunk` for rows that are only `input_bytes` of `input`
+fn send_chunk_of(
+    sender: &ChunkSender,
+    encoder: &mut Encoder,
+    input: Range<u64>,
+    input_bytes: u64,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let rows = encoder.rows();
+    let data = encoder.take_into(sender.pool.take(0));
+    let permit = sender
+        .buffer
+        .as_ref()
+        .map(|buffer| buffer.acquire(data.len() as u64));
+    let chunk = Chunk {
+        data,
+        rows,
+        input,
+        input_bytes,
+        _permit: permit,
+    };
+    sender
+        .tx
+        .blocking_send(chunk)
+        .map_err(|_| "Upload stream closed")?;
+    Ok(())
+}
+
+/// The uploader counts the rows it streamed; they must match what the readers
+/// encoded, every record read once per --scale-factor copy
+fn check_uploaded(formatted: u64, uploaded: u64) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    if formatted != uploaded {
+        return Err(format!(
+            "Encoded {} rows, but only {} were uploaded",
+            formatted, uploaded
+        )
+        .into());
+    }
+    Ok(uploaded)
+}
+
+/// Where and how the loaders upload
+struct Target {
+    conn: Connection,
+    table: String,
+    /// Column list of the INSERTs
+    columns: String,
+    encoding: Encoding,
+    streams: usize,
+    /// Shared bound on the bytes queued for all streams (--max-buffer-bytes)
+    buffer: Option<Arc<ByteBudget>>,
+    /// RowBinary sent by all streams, and the input it covers
+    progress: Arc<UploadProgress>,
+    /// Chunk buffers, given back by the streams once sent
+    pool: Arc<BufferPool<u8>>,
+    /// Bounded, retried INSERTs instead of one streaming INSERT per stream
+    retried_inserts: Option<RetriedInserts>,
+}
+
+#[derive(Clone)]
+struct RetriedInserts {
+    max_bytes: usize,
+    retries: u32,
+    backoff: Duration,
+    async_insert: Option<AsyncInsert>,
+}
+
+/// Chunks a channel may hold under --max-buffer-bytes; only there to keep
+/// the channel bounded, the byte budget runs out long before
+const BUDGETED_CHUNKS: usize = 1 << 16;
+
+/// Concurrent INSERT streams, one per channel
+struct Uploads {
+    /// Each stream's input byte ranges that needed a retry
+    tasks: Vec<JoinHandle<Result<Vec<Range<u64>>, String>>>,
+    total_rows: Arc<AtomicU64>,
+}
+
+impl Uploads {
+    /// Starts `target.streams` upload streams, splitting `capacity` buffered
+    /// chunks between their channels, or bounded by `target.buffer` instead
+    /// when it is set; returns a sender for each stream
+    fn start(target: &Target, capacity: usize) -> (Vec<ChunkSender>, Self) {
+        let insert = format!(
+            "INSERT INTO {} ({}) FORMAT RowBinary",
+            target.table, target.columns
+        );
+        let total_rows = Arc::new(AtomicU64::new(0));
+        // Deduplication tokens must not repeat across loads of the same data
+        let run_id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let mut senders = Vec::with_capacity(target.streams);
+        let mut tasks = Vec::with_capacity(target.streams);
+
+        for stream_id in 0..target.streams {
+            let chunks = match target.buffer {
+                Some(_) => BUDGETED_CHUNKS,
+                None => capacity.div_ceil(target.streams).max(1),
+            };
+            let (tx, rx) = channel::<Chunk>(chunks);
+            let span = info_span!("db_ingest", stream_id);
+            let task = match target.retried_inserts {
+                None => {
+                    let reader = ChannelReader::new(rx, total_rows.clone())
+                        .with_progress(target.progress.clone(), |chunk: &Chunk| chunk.input_bytes)
+                        .with_pool(target.pool.clone());
+                    let request = target
+                        .conn
+                        .request(&insert)
+                        .body(reqwest::Body::wrap_stream(
+                            tokio_util::io::ReaderStream::new(reader),
+                        ));
+                    tokio::spawn(
+                        async move {
+                            let resp = request.send().await.map_err(|e| e.to_string())?;
+                            check_response(resp).await?;
+                            Ok(Vec::new())
+                        }
+                        .instrument(span),
+                    )
+                }
+                Some(ref limits) => tokio::spawn(
+                    insert_with_retries(
+                        target.conn.clone(),
+                        insert.clone(),
+                        rx,
+                        limits.clone(),
+                        format!("{:x}-{}", run_id, stream_id),
+                        total_rows.clone(),
+                        target.progress.clone(),
+                        target.pool.clone(),
+                    )
+                    .instrument(span),
+                ),
+            };
+            senders.push(ChunkSender {
+                tx,
+                buffer: target.buffer.clone(),
+                pool: target.pool.clone(),
+            });
+            tasks.push(task);
+        }
+        (senders, Self { tasks, total_rows })
+    }
+
+    /// Waits for every stream; returns the rows they uploaded between them,
+    /// or the errors of all the streams that failed
+    async fn finish(self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let mut errors = Vec::new();
+        let mut retried = Vec::new();
+        for (i, task) in self.tasks.into_iter().enumerate() {
+            match task.await {
+                Ok(Ok(ranges)) => retried.extend(ranges),
+                Ok(Err(e)) => errors.push(format!("Upload stream {}: {}", i, e)),
+                Err(e) => errors.push(format!("Upload stream {}: task failed: {}", i, e)),
+            }
+        }
+        if !retried.is_empty() {
+            retried.sort_by_key(|r| r.start);
+            println!(
+                "Retried inserts covered input bytes {}",
+                format_ranges(&retried)
+            );
+        }
+        if !errors.is_empty() {
+            return Err(errors.join("\n").into());
+        }
+        Ok(self.total_rows.load(Ordering::Relaxed))
+    }
+}
+
+/// Turns a non-2xx response into its error text
+async fn check_response(resp: reqwest::Response) -> Result<(), String> {
+    if resp.status().is_success() {
+        return Ok(());
+    }
+    let text = resp
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    Err(format!("ClickHouse error: {}", text))
+}
+
+/// Longest wait between two attempts of an INSERT
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Uploads one stream's chunks as INSERTs of about `limits.max_bytes`, each
+/// tagged with a deduplication token so that a retry of an INSERT the server
+/// did apply is not loaded twice. Returns the input byte ranges that were
+/// retried.
+#[allow(clippy::too_many_arguments)]
+async fn insert_with_retries(
+    conn: Connection,
+    insert: String,
+    mut rx: Receiver<Chunk>,
+    limits: RetriedInserts,
+    token_prefix: String,
+    total_rows: Arc<AtomicU64>,
+    progress: Arc<UploadProgress>,
+    pool: Arc<BufferPool<u8>>,
+) -> Result<Vec<Range<u64>>, String> {
+    let mut retried = Vec::new();
+    for seq in 0.. {
+        let mut data = Vec::new();
+        let mut rows = 0;
+        let mut input: Vec<Range<u64>> = Vec::new();
+        let mut input_bytes = 0;
+        let mut merged = false;
+        while data.len() < limits.max_bytes {
+            let Some(chunk) = rx.recv().await else {
+                break;
+            };
+            if data.is_empty() {
+                data = chunk.data;
+            } else {
+                data.extend_from_slice(&chunk.data);
+                pool.give(chunk.data);
+                merged = true;
+            }
+            rows += chunk.rows;
+            input_bytes += chunk.input_bytes;
+            // Chunks of one reader arrive in order; merge them
+            match input.last_mut() {
+                Some(last) if last.end == chunk.input.start => last.end = chunk.input.end,
+                _ => input.push(chunk.input),
+            }
+        }
+        if data.is_empty() {
+            break;
+        }
+
+        let token = format!("{}-{}", token_prefix, seq);
+        let mut settings = vec![("insert_deduplication_token", token.as_str())];
+        if let Some(mode) = limits.async_insert {
+            let wait = match mode {
+                AsyncInsert::Wait => "1",
+                AsyncInsert::NoWait => "0",
+            };
+            settings.extend([
+                ("async_insert", "1"),
+                ("async_insert_deduplicate", "1"),
+                ("wait_for_async_insert", wait),
+            ]);
+        }
+        let body = bytes::Bytes::from(data);
+        let bytes = body.len() as u64;
+        let what = format!("INSERT {} (input bytes {})", token, format_ranges(&input));
+        let retries = send_insert(
+            &conn,
+            &insert,
+            &settings,
+            &body,
+            limits.retries,
+            limits.backoff,
+            &what,
+        )
+        .await?;
+        // A one-chunk body is that chunk's buffer, free again unless still
+        // shared; a merged one has grown to --insert-bytes, too big to keep
+        if let (false, Ok(buf)) = (merged, body.try_into_mut()) {
+            pool.give(buf.into());
+        }
+        if retries > 0 {
+            retried.extend(input);
+        }
+        total_rows.fetch_add(rows, Ordering::Relaxed);
+        metrics::add_rows_loaded(rows);
+        metrics::add_bytes_uploaded(bytes);
+        progress.add(bytes, input_bytes);
+    }
+    Ok(retried)
+}
+
+/// Sends one INSERT with `body` as its data, retrying failures that may be
+/// transient up to `retries` times with exponential backoff. `what` names the
+/// INSERT in messages. Returns the retries it took.
+async fn send_insert(
+    conn: &Connection,
+    insert: &str,
+    settings: &[(&str, &str)],
+    body: &bytes::Bytes,
+    retries: u32,
+    backoff: Duration,
+    what: &str,
+) -> Result<u32, String> {
+    let mut attempt = 0;
+    loop {
+        let started = Instant::now();
+        // The error, and whether trying again may help: bad credentials or
+        // a bad query fail the same way every time
+        let result = match conn
+            .request(insert)
+            .query(settings)
+            .body(body.clone())
+            .send()
+            .await
+        {
+            Ok(resp) => {
+                let transient = !resp.status().is_client_error();
+                check_response(resp).await.map_err(|e| (e, transient))
+            }
+            Err(e) => Err((e.to_string(), true)),
+        };
+        match result {
+            Ok(()) => {
+                latency::record(started.elapsed());
+                return Ok(attempt);
+            }
+            Err((e, true)) if attempt < retries => {
+                let wait = backoff
+                    .checked_mul(2u32.saturating_pow(attempt))
+                    .map_or(MAX_BACKOFF, |wait| wait.min(MAX_BACKOFF));
+                attempt += 1;
+                println!(
+                    "{} failed, retry {} of {} in {:?}: {}",
+                    what, attempt, retries, wait, e
+                );
+                tokio::time::sleep(wait).await;
+            }
+            Err((e, _)) => {
+                return Err(format!(
+                    "{} failed after {} attempts: {}",
+                    what,
+                    attempt + 1,
+                    e
+                ));
+            }
+        }
+    }
+}
+
+fn format_ranges(ranges: &[Range<u64>]) -> String {
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|r| format!("{}..{}", r.start, r.end))
+        .collect();
+    ranges.join(", ")
+}
+
+/// Waits for every reader thread; returns the rows they encoded
+async fn join_readers(
+    handles: Vec<JoinHandle<Result<u64, Box<dyn Error + Send + Sync>>>>,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let mut formatted = 0u64;
+    for (i, handle) in handles.into_iter().enumerate() {
+        match handle.await {
+            Ok(result) => match result {
+                Ok(rows) => {
+                    println!("Thread {} formatted {} records", i, rows);
+                    formatted += rows;
+                }
+                Err(e) => return Err(format!("Thread {} failed: {}", i, e).into()),
+            },
+            Err(e) => return Err(format!("Thread {} panicked: {}", i, e).into()),
+        }
+    }
+    Ok(formatted)
+}
+
+/// Formats Gensort records into ClickHouse RowBinary format
+/// RowBinary for (String, String): [varint_len][bytes][varint_len][bytes]
+fn format_gensort_to_rowbinary(
+    input: &Path,
+    layout: FixedLayout,
+    records: Range<u64>,
+    io: IoOptions,
+    tx: ChunkSender,
+    batch_size: usize,
+    encoding: Encoding,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let record_size = layout.record_size() as u64;
+    let first = records.start;
+    let mut num_records = 0u64;
+    let mut chunk_start = records.start * record_size;
+    let mut pos = chunk_start;
+    let mut reader = FixedRecords::open(io, input, layout, records, 4 * 1024 * 1024)?;
+
+    // Pre-allocate output buffer: each record is its key and payload with
+    // about a length byte each (102 bytes for 10/90 records)
+    let batch_bytes = batch_size * (layout.record_size() + 2);
+    let mut encoder = Encoder::with_capacity(encoding.columns(), batch_bytes);
+
+    while let Some(record) = reader.next_record()? {
+        num_records += 1;
+        pos += record_size;
+        let (key, payload) = record.split_at(layout.key_size);
+        encoding.encode(&mut encoder, first + num_records - 1, key, payload)?;
 
         // Send batch when full
-        if output_buffer.len() >= batch_size * 102 {
-            tx.blocking_send(std::mem::take(&mut output_buffer))?;
-            output_buffer = Vec::with_capacity(batch_size * 102);
+        if encoder.len() >= batch_bytes {
+            send_chunk(&tx, &mut encoder, chunk_start..pos)?;
+            chunk_start = pos;
         }
     }
 
     // Send remaining records
-    if !output_buffer.is_empty() {
-        tx.blocking_send(output_buffer)?;
+    if !encoder.is_empty() {
+        send_chunk(&tx, &mut encoder, chunk_start..pos)?;
     }
 
     Ok(num_records)
@@ -236,55 +1806,45 @@ fn format_gensort_to_rowbinary(
 
 /// Optimized Kvbin loader using direct RowBinary streaming
 async fn load_kvbin_streaming(
-    input: &PathBuf,
-    url: &str,
-    table: &str,
+    input: &Path,
+    range: Range<u64>,
+    io: IoOptions,
+    target: &Target,
     num_threads: usize,
     batch_size: usize,
+    validate_boundaries: bool,
 ) -> Result<u64, Box<dyn Error + Send + Sync>> {
-    let file_size = File::open(input)?.metadata()?.len();
-
     // Check for index file
     let mut index_path = input.as_os_str().to_owned();
     index_path.push(".idx");
     let index_path = PathBuf::from(index_path);
 
-    if !index_path.exists() || num_threads == 1 {
-        if !index_path.exists() {
-            println!("No index file found, using sequential loading");
-        }
-        return load_kvbin_sequential(input, url, table).await;
+    if num_threads == 1 {
+        return load_kvbin_sequential(input, range, io, target).await;
     }
 
-    // Parallel loading using index
-    println!("Loading index from {:?}...", index_path);
-    let offsets = load_index(&index_path, file_size)
-        .map_err(|e| -> Box<dyn Error + Send + Sync> { e.into() })?;
+    // Parallel loading using the index, or boundaries found by scanning
+    let offsets = if index_path.exists() {
+        println!("Loading index from {:?}...", index_path);
+        let offsets = load_index(&index_path, &range)
+            .map_err(|e| -> Box<dyn Error + Send + Sync> { e.into() })?;
+
+        println!(
+            "Index loaded: {} offset points, using {} threads",
+            offsets.len(),
+            num_threads
+        );
+        offsets
+    } else {
+        let input = input.to_path_buf();
+        task::spawn_blocking(move || {
+            kvbin_index::scan_boundaries(&input, &range, num_threads, validate_boundaries)
+        })
+        .await??
+    };
 
-    println!(
-        "Index loaded: {} offset points, using {} threads",
-        offsets.len(),
-        num_threads
-    );
-
-    // Use bounded channel to prevent OOM
-    let (tx, rx) = channel::<Vec<u8>>(num_threads * 4);
-
-    // Spawn HTTP uploader task
-    let upload_url = format!("{}/?query=INSERT+INTO+{}+FORMAT+RowBinary", url, table);
-    let total_rows = Arc::new(AtomicU64::new(0));
-    let total_rows_clone = total_rows.clone();
-
-    let uploader = tokio::spawn(async move {
-        let client = reqwest::Client::new();
-        let reader = ChannelReader::new(rx, total_rows_clone);
-        let stream = tokio_util::io::ReaderStream::new(reader);
-        client
-            .post(&upload_url)
-            .body(reqwest::Body::wrap_stream(stream))
-            .send()
-            .await
-    });
+    // Bounded channels prevent OOM
+    let (senders, uploads) = Uploads::start(target, num_threads * 4);
 
     // Divide work among threads
     let offsets = Arc::new(offsets);
@@ -302,225 +1862,146 @@ async fn load_kvbin_streaming(
         let start_offset = offsets[start_partition];
         let end_offset = offsets[end_partition.min(offsets.len() - 1)];
 
-        let input = input.clone();
-        let tx = tx.clone();
+        let input = input.to_path_buf();
+        let encoding = target.encoding;
+        // Reader threads take turns between the streams
+        let tx = senders[thread_id % senders.len()].clone();
+        let span = info_span!("encode", start_offset, end_offset);
 
         let handle = task::spawn_blocking(move || -> Result<u64, Box<dyn Error + Send + Sync>> {
-            format_kvbin_to_rowbinary(&input, start_offset, end_offset, tx, batch_size)
+            affinity::pin_worker()?;
+            let _span = span.entered();
+            format_kvbin_to_rowbinary(
+                &input,
+                start_offset..end_offset,
+                io,
+                tx,
+                batch_size,
+                encoding,
+            )
         });
 
         handles.push(handle);
     }
 
-    drop(tx);
+    drop(senders);
 
-    // Wait for all reader threads
-    for (i, handle) in handles.into_iter().enumerate() {
-        match handle.await {
-            Ok(result) => match result {
-                Ok(rows) => println!("Thread {} formatted {} records", i, rows),
-                Err(e) => return Err(format!("Thread {} failed: {}", i, e).into()),
-            },
-            Err(e) => return Err(format!("Thread {} panicked: {}", i, e).into()),
-        }
-    }
-
-    // Wait for uploader
-    let resp = uploader
-        .await
-        .map_err(|e| format!("Uploader task failed: {}", e))??;
-    if !resp.status().is_success() {
-        let error_text = resp
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("ClickHouse error: {}", error_text).into());
-    }
-
-    Ok(total_rows.load(Ordering::Relaxed))
+    let formatted = join_readers(handles).await;
+    // A failed upload closes its channel, so its error explains a failed reader
+    let uploaded = uploads.finish().await?;
+    check_uploaded(target.encoding.scale.rows(formatted?), uploaded)
 }
 
 /// Sequential kvbin loading for single-threaded or non-indexed cases
 async fn load_kvbin_sequential(
-    input: &PathBuf,
-    url: &str,
-    table: &str,
+    input: &Path,
+    range: Range<u64>,
+    io: IoOptions,
+    target: &Target,
 ) -> Result<u64, Box<dyn Error + Send + Sync>> {
-    let (tx, rx) = channel::<Vec<u8>>(4);
-
-    let upload_url = format!("{}/?query=INSERT+INTO+{}+FORMAT+RowBinary", url, table);
-    let total_rows = Arc::new(AtomicU64::new(0));
-    let total_rows_clone = total_rows.clone();
-
-    let uploader = tokio::spawn(async move {
-        let client = reqwest::Client::new();
-        let reader = ChannelReader::new(rx, total_rows_clone);
-        let stream = tokio_util::io::ReaderStream::new(reader);
-        client
-            .post(&upload_url)
-            .body(reqwest::Body::wrap_stream(stream))
-            .send()
-            .await
-    });
+    let (senders, uploads) = Uploads::start(target, 4 * target.streams);
 
-    let input = input.clone();
+    let input = input.to_path_buf();
+    let encoding = target.encoding;
+    let span = info_span!("encode");
     let reader_task = task::spawn_blocking(move || -> Result<u64, Box<dyn Error + Send + Sync>> {
-        format_kvbin_sequential_to_rowbinary(&input, tx, 50_000)
+        affinity::pin_worker()?;
+        let _span = span.entered();
+        format_kvbin_sequential_to_rowbinary(&input, range, io, senders, 50_000, encoding)
     });
 
-    let rows = reader_task.await??;
-
-    let resp = uploader
-        .await
-        .map_err(|e| format!("Uploader task failed: {}", e))??;
-    if !resp.status().is_success() {
-        let error_text = resp
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("ClickHouse error: {}", error_text).into());
-    }
-
-    Ok(rows)
+    let formatted = reader_task.await?;
+    let uploaded = uploads.finish().await?;
+    check_uploaded(target.encoding.scale.rows(formatted?), uploaded)
 }
 
-/// Formats kvbin records into RowBinary format
+/// Formats the kvbin records starting in bytes `range` into RowBinary format
 fn format_kvbin_to_rowbinary(
-    input: &PathBuf,
-    start_offset: u64,
-    end_offset: u64,
-    tx: Sender<Vec<u8>>,
+    input: &Path,
+    range: Range<u64>,
+    io: IoOptions,
+    tx: ChunkSender,
     batch_size: usize,
+    encoding: Encoding,
 ) -> Result<u64, Box<dyn Error + Send + Sync>> {
     let mut file = File::open(input)?;
-    file.seek(SeekFrom::Start(start_offset))?;
-    let mut reader = BufReader::with_capacity(4 * 1024 * 1024, file);
+    file.seek(SeekFrom::Start(range.start))?;
+    let mut chunk_start = range.start;
+    let mut records = io.kvbin_reader(file, input, range)?;
 
-    let mut output_buffer = Vec::with_capacity(batch_size * 128); // Estimate
+    let mut encoder = Encoder::with_capacity(encoding.columns(), batch_size * 128); // Estimate
     let mut rows = 0u64;
-    let mut len_buf = [0u8; 4];
-    let mut key_buf = Vec::new();
-    let mut val_buf = Vec::new();
-    let mut current_pos = start_offset;
-
-    while current_pos < end_offset {
-        // Read key length
-        if let Err(e) = reader.read_exact(&mut len_buf) {
-            if e.kind() == io::ErrorKind::UnexpectedEof {
-                break;
-            }
-            return Err(e.into());
-        }
-        let klen = u32::from_le_bytes(len_buf) as usize;
-        key_buf.resize(klen, 0);
-        reader.read_exact(&mut key_buf)?;
-
-        // Read value length
-        reader.read_exact(&mut len_buf)?;
-        let vlen = u32::from_le_bytes(len_buf) as usize;
-        val_buf.resize(vlen, 0);
-        reader.read_exact(&mut val_buf)?;
-
-        current_pos += 8 + klen as u64 + vlen as u64;
 
+    while let Some((id, key, value)) = records.next_record_at()? {
         // Write to RowBinary: varint key_len + key + varint val_len + val
-        write_varint(&mut output_buffer, klen as u64);
-        output_buffer.extend_from_slice(&key_buf);
-        write_varint(&mut output_buffer, vlen as u64);
-        output_buffer.extend_from_slice(&val_buf);
+        encoding.encode(&mut encoder, id, key, value)?;
 
         rows += 1;
 
         // Send batch when large enough
-        if output_buffer.len() >= batch_size * 128 {
-            tx.blocking_send(std::mem::take(&mut output_buffer))?;
-            output_buffer = Vec::with_capacity(batch_size * 128);
-        }
-
-        if current_pos >= end_offset {
-            break;
+        if encoder.len() >= batch_size * 128 {
+            send_chunk(&tx, &mut encoder, chunk_start..records.offset())?;
+            chunk_start = records.offset();
         }
     }
 
     // Send remaining data
-    if !output_buffer.is_empty() {
-        tx.blocking_send(output_buffer)?;
+    if !encoder.is_empty() {
+        send_chunk(&tx, &mut encoder, chunk_start..records.offset())?;
     }
 
     Ok(rows)
 }
 
+/// Like `format_kvbin_to_rowbinary` for all of bytes `range`, dealing chunks
+/// out to the upload streams in turn
 fn format_kvbin_sequential_to_rowbinary(
-    input: &PathBuf,
-    tx: Sender<Vec<u8>>,
+    input: &Path,
+    range: Range<u64>,
+    io: IoOptions,
+    senders: Vec<ChunkSender>,
     batch_size: usize,
+    encoding: Encoding,
 ) -> Result<u64, Box<dyn Error + Send + Sync>> {
-    let file = File::open(input)?;
-    let mut reader = BufReader::with_capacity(8 * 1024 * 1024, file);
+    let mut chunk_start = range.start;
+    let reader = Window::open(input, range.clone())?;
+    let mut records = io.kvbin_reader(reader, input, range)?;
 
-    let mut output_buffer = Vec::with_capacity(batch_size * 128);
+    let mut encoder = Encoder::with_capacity(encoding.columns(), batch_size * 128);
     let mut rows = 0u64;
-    let mut len_buf = [0u8; 4];
-    let mut key_buf = Vec::new();
-    let mut val_buf = Vec::new();
-
-    loop {
-        // Read key length
-        if let Err(e) = reader.read_exact(&mut len_buf) {
-            if e.kind() == io::ErrorKind::UnexpectedEof {
-                break;
-            }
-            return Err(e.into());
-        }
-        let klen = u32::from_le_bytes(len_buf) as usize;
-        key_buf.resize(klen, 0);
-        reader.read_exact(&mut key_buf)?;
-
-        // Read value length
-        reader.read_exact(&mut len_buf)?;
-        let vlen = u32::from_le_bytes(len_buf) as usize;
-        val_buf.resize(vlen, 0);
-        reader.read_exact(&mut val_buf)?;
+    let mut streams = senders.iter().cycle();
 
+    while let Some((id, key, value)) = records.next_record_at()? {
         // Write to RowBinary
-        write_varint(&mut output_buffer, klen as u64);
-        output_buffer.extend_from_slice(&key_buf);
-        write_varint(&mut output_buffer, vlen as u64);
-        output_buffer.extend_from_slice(&val_buf);
+        encoding.encode(&mut encoder, id, key, value)?;
 
         rows += 1;
 
         // Send batch
-        if output_buffer.len() >= batch_size * 128 {
-            tx.blocking_send(std::mem::take(&mut output_buffer))?;
-            output_buffer = Vec::with_capacity(batch_size * 128);
+        if encoder.len() >= batch_size * 128 {
+            send_chunk(
+                streams.next().unwrap(),
+                &mut encoder,
+                chunk_start..records.offset(),
+            )?;
+            chunk_start = records.offset();
         }
     }
 
-    if !output_buffer.is_empty() {
-        tx.blocking_send(output_buffer)?;
+    if !encoder.is_empty() {
+        send_chunk(
+            streams.next().unwrap(),
+            &mut encoder,
+            chunk_start..records.offset(),
+        )?;
     }
 
     Ok(rows)
 }
 
-/// Write variable-length integer (LEB128 encoding used by ClickHouse)
-fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
-    loop {
-        let mut byte = (value & 0x7F) as u8;
-        value >>= 7;
-        if value != 0 {
-            byte |= 0x80;
-        }
-        buf.push(byte);
-        if value == 0 {
-            break;
-        }
-    }
-}
-
-fn load_index(index_file: impl AsRef<Path>, file_size: u64) -> Result<Vec<u64>, String> {
-    let mut index_points = vec![0];
+/// Index offsets inside bytes `range` of the input, with its ends
+fn load_index(index_file: impl AsRef<Path>, range: &Range<u64>) -> Result<Vec<u64>, String> {
+    let mut index_points = vec![range.start];
 
     let mut index_file =
         File::open(index_file).map_err(|e| format!("Failed to open index file: {e}"))?;
@@ -540,92 +2021,11 @@ fn load_index(index_file: impl AsRef<Path>, file_size: u64) -> Result<Vec<u64>,
     index_points.extend(
         buf.chunks_exact(8)
             .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
-            .filter(|&off| off > 0 && off < file_size),
+            .filter(|&off| off > range.start && off < range.end),
     );
 
-    index_points.push(file_size);
+    index_points.push(range.end);
     index_points.sort_unstable();
     index_points.dedup();
     Ok(index_points)
 }
-
-/// Reader that pulls data from channel and tracks row count
-struct ChannelReader {
-    rx: tokio::sync::mpsc::Receiver<Vec<u8>>,
-    current_chunk: Option<Vec<u8>>,
-    pos: usize,
-    total_rows: Arc<AtomicU64>,
-    last_million_printed: u64,
-}
-
-impl ChannelReader {
-    fn new(rx: tokio::sync::mpsc::Receiver<Vec<u8>>, total_rows: Arc<AtomicU64>) -> Self {
-        Self {
-            rx,
-            current_chunk: None,
-            pos: 0,
-            total_rows,
-            last_million_printed: 0,
-        }
-    }
-}
-
-impl AsyncRead for ChannelReader {
-    fn poll_read(
-        mut self: Pin<&mut Self>,
-        cx: &mut Context<'_>,
-        buf: &mut ReadBuf<'_>,
-    ) -> Poll<io::Result<()>> {
-        loop {
-            // Try to read from current chunk
-            if let Some(ref chunk) = self.current_chunk {
-                let pos = self.pos;
-                let chunk_len = chunk.len();
-                if pos < chunk_len {
-                    let remaining = chunk_len - pos;
-                    let to_copy = remaining.min(buf.remaining());
-                    buf.put_slice(&chunk[pos..pos + to_copy]);
-                    self.pos += to_copy;
-
-                    // Clear chunk if fully consumed
-                    if self.pos >= chunk_len {
-                        self.current_chunk = None;
-                        self.pos = 0;
-                    }
-
-                    return Poll::Ready(Ok(()));
-                }
-            }
-
-            // Need new chunk from channel
-            match self.rx.try_recv() {
-                Ok(chunk) => {
-                    // Estimate rows (for gensort: 102 bytes/row, for kvbin: varies)
-                    let estimated_rows = chunk.len() / 102;
-                    let new_total = self
-                        .total_rows
-                        .fetch_add(estimated_rows as u64, Ordering::Relaxed)
-                        + estimated_rows as u64;
-
-                    let current_million = new_total / 1_000_000;
-                    if current_million > self.last_million_printed {
-                        println!("Uploaded ~{} million records...", current_million);
-                        self.last_million_printed = current_million;
-                    }
-
-                    self.current_chunk = Some(chunk);
-                    self.pos = 0;
-                }
-                Err(tokio::sync::mpsc::error::TryRecvError::Empty) => {
-                    // No data available, register waker and return Pending
-                    cx.waker().wake_by_ref();
-                    return Poll::Pending;
-                }
-                Err(tokio::sync::mpsc::error::TryRecvError::Disconnected) => {
-                    // Channel closed, EOF
-                    return Poll::Ready(Ok(()));
-                }
-            }
-        }
-    }
-}
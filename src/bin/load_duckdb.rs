@@ -1,11 +1,37 @@
 use clap::{Parser, ValueEnum};
-use duckdb::{Connection, params};
+use duckdb::arrow::array::{ArrayRef, BinaryArray};
+use duckdb::arrow::record_batch::RecordBatch;
+use duckdb::{Appender, Connection, ToSql, params};
+use es_duck::affinity;
+use es_duck::buffer_pool::BufferPool;
+use es_duck::cgroup::parse_memory_to_bytes;
+use es_duck::checkpoint::{self, Checkpoint};
+use es_duck::collation::Collation;
+use es_duck::csv_file::{self, Encoding};
+use es_duck::input_files::{self, InputFiles};
+use es_duck::inspect;
+use es_duck::kvbin_index;
+use es_duck::latency;
+use es_duck::loader::IfExists;
+use es_duck::metrics::{self, Phase};
+use es_duck::profile;
+use es_duck::progress;
+use es_duck::progress_bar;
+use es_duck::records::{self, FixedLayout, FixedRecords, IoOptions, Window};
+use es_duck::scale::ScaleOptions;
+use es_duck::schema::{self, ColumnNames, ExtraColumn, Schema};
+use es_duck::shards::{self, Router, ShardBy};
+use es_duck::telemetry;
 use std::error::Error;
 use std::fs::File;
-use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc::{SyncSender, sync_channel};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{Span, info_span};
 
 #[derive(Copy, Clone, Debug, ValueEnum)]
 enum InputFormat {
@@ -13,14 +39,155 @@ enum InputFormat {
     Kvbin,
 }
 
+impl From<InputFormat> for records::InputFormat {
+    fn from(format: InputFormat) -> Self {
+        match format {
+            InputFormat::Gensort => records::InputFormat::Gensort,
+            InputFormat::Kvbin => records::InputFormat::Kvbin,
+        }
+    }
+}
+
+/// DuckDB type of the sort_key or payload column
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum ColumnType {
+    Blob,
+    /// Text; the input bytes must be valid UTF-8 (e.g. from generate-gensort --ascii)
+    Varchar,
+    /// UBIGINT of the first 8 key bytes, big-endian; for the key column only
+    Uint64,
+}
+
+impl ColumnType {
+    fn sql(self) -> &'static str {
+        match self {
+            ColumnType::Blob => "BLOB",
+            ColumnType::Varchar => "VARCHAR",
+            ColumnType::Uint64 => "UBIGINT",
+        }
+    }
+
+    /// Wraps a SQL expression of type BLOB so it yields this type; `--via
+    /// copy` refuses UBIGINT keys, so they never get here
+    fn wrap_blob(self, expr: &str) -> String {
+        match self {
+            ColumnType::Blob => expr.to_string(),
+            ColumnType::Varchar => format!("decode({})", expr),
+            ColumnType::Uint64 => unreachable!("--via copy does not load UBIGINT keys"),
+        }
+    }
+}
+
+/// Types of the two table columns
+#[derive(Copy, Clone, Debug)]
+struct Columns {
+    key: ColumnType,
+    payload: ColumnType,
+    /// Copies of each record to append
+    scale: ScaleOptions,
+    /// Whether the table has a row_id column
+    row_id: bool,
+}
+
+impl Columns {
+    /// Appends one record, once per --scale-factor copy; `id` is the record's
+    /// id in the input (`schema::ROW_ID_COLUMN`)
+    fn append(
+        self,
+        appender: &mut Appender,
+        id: u64,
+        key: &[u8],
+        payload: &[u8],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.scale.each_copy(key, payload, |copy, key, payload| {
+            let row_id = match self.row_id {
+                true => Some(self.scale.row_id(id, copy)?),
+                false => None,
+            };
+            self.append_row(appender, key, payload, row_id)
+        })
+    }
+
+    /// Appends one row, binding fields of VARCHAR columns as text and UBIGINT
+    /// keys as integers
+    fn append_row(
+        self,
+        appender: &mut Appender,
+        key: &[u8],
+        payload: &[u8],
+        row_id: Option<u64>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (key_text, key_int, payload_text);
+        let key: &dyn ToSql = match self.key {
+            ColumnType::Blob => &key,
+            ColumnType::Varchar => {
+                key_text = text(key, "sort_key")?;
+                &key_text
+            }
+            ColumnType::Uint64 => {
+                key_int = schema::key_u64(key);
+                &key_int
+            }
+        };
+        let payload: &dyn ToSql = match self.payload {
+            ColumnType::Blob => &payload,
+            ColumnType::Varchar => {
+                payload_text = text(payload, "payload")?;
+                &payload_text
+            }
+            ColumnType::Uint64 => return Err("Only the key column can be UBIGINT".into()),
+        };
+        match row_id {
+            Some(id) => appender.append_row(params![key, payload, id as i64])?,
+            None => appender.append_row(params![key, payload])?,
+        }
+        Ok(())
+    }
+}
+
+fn text<'a>(bytes: &'a [u8], column: &str) -> Result<&'a str, Box<dyn Error + Send + Sync>> {
+    std::str::from_utf8(bytes).map_err(|e| {
+        format!(
+            "A {} value is not valid UTF-8 ({}); load binary data into a BLOB column",
+            column, e
+        )
+        .into()
+    })
+}
+
+/// How rows get into DuckDB
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Via {
+    /// The appender API, fed by the reader threads
+    Appender,
+    /// Convert the input to a temporary file and let DuckDB's own parallel
+    /// reader load it
+    Copy,
+}
+
+/// Temporary file format for --via copy
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum CopyFormat {
+    /// Hex-encoded CSV, read with read_csv and unhex
+    Csv,
+    /// Parquet with binary columns, loaded with COPY (needs util-parquet)
+    Parquet,
+}
+
 #[derive(Parser)]
 #[command(name = "es-duck-duckdb")]
+#[command(about = "Load gensort or kvbin records into a DuckDB table")]
 struct Args {
     #[arg(long, value_enum)]
     format: InputFormat,
 
-    #[arg(long)]
-    input: PathBuf,
+    #[command(flatten)]
+    layout: FixedLayout,
+
+    /// Input file; several (e.g. --input parts/*.gensort) load together, their
+    /// parts shared out between the threads
+    #[arg(long, num_args = 1.., required = true)]
+    input: Vec<PathBuf>,
 
     #[arg(long)]
     db: PathBuf,
@@ -28,116 +195,723 @@ struct Args {
     #[arg(long, default_value = "bench_data")]
     table: String,
 
+    /// What to do when the table already exists
+    #[arg(long, value_enum, default_value_t = IfExists::Fail)]
+    if_exists: IfExists,
+
+    #[command(flatten)]
+    column_names: ColumnNames,
+
+    /// Add a column the load leaves empty, e.g. --extra-column "tenant_id INTEGER"
+    /// (repeatable). The appender fills columns by position, so a table that
+    /// already has extra columns can only be appended to with --via copy.
+    #[arg(long = "extra-column", value_name = "NAME TYPE")]
+    extra_columns: Vec<ExtraColumn>,
+
     #[arg(long, default_value_t = 1)]
     threads: usize,
+
+    /// Leave out this many records at the start of the input
+    #[arg(long, default_value_t = 0)]
+    skip: u64,
+
+    /// Load at most this many records
+    #[arg(long)]
+    limit: Option<u64>,
+
+    /// Read and check the input without connecting to the database: print the
+    /// record count, bytes, smallest and largest key and any malformed records
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Add a BIGINT row_id column numbering the rows in input order: the
+    /// record number of gensort input, the byte offset of kvbin input
+    #[arg(long)]
+    with_row_id: bool,
+
+    /// Column type of the key; DuckDB sorts VARCHAR and BLOB keys differently,
+    /// and uint64 compares integer keys against both
+    #[arg(long, value_enum, default_value = "blob")]
+    key_type: ColumnType,
+
+    /// Collation of a VARCHAR key column, which its ORDER BYs use: binary, or
+    /// icu:<locale> (e.g. icu:de-DE) from the icu extension
+    #[arg(long, value_name = "binary|icu:<locale>")]
+    collation: Option<Collation>,
+
+    /// Column type of the payload
+    #[arg(long, value_enum, default_value = "blob")]
+    payload_type: ColumnType,
+
+    #[arg(long, value_enum, default_value = "appender")]
+    via: Via,
+
+    #[command(flatten)]
+    io: IoOptions,
+
+    #[command(flatten)]
+    scale: ScaleOptions,
+
+    /// Format of the temporary file with --via copy (Parquet needs gensort input)
+    #[arg(long, value_enum, default_value = "csv")]
+    copy_format: CopyFormat,
+
+    /// Directory for the temporary file of --via copy (default: the system temp directory)
+    #[arg(long)]
+    copy_dir: Option<PathBuf>,
+
+    /// With more than one thread, have each thread append to its own staging
+    /// table over its own connection instead of funnelling every row through
+    /// one appender, then copy the staging tables into the table at the end
+    #[arg(long)]
+    staging_tables: bool,
+
+    /// Append gensort records as Arrow record batches instead of binding them
+    /// row by row (BLOB columns only)
+    #[arg(long)]
+    arrow: bool,
+
+    /// Load into this many tables, <table>_shard0 .. <table>_shard<N-1>,
+    /// instead of one (see --shard-by). Each reader thread appends to every
+    /// shard over its own connection.
+    #[arg(long, conflicts_with_all = ["checkpoint_file", "staging_tables", "arrow"])]
+    shards: Option<usize>,
+
+    /// How --shards spreads the rows between the tables
+    #[arg(long, value_enum, default_value_t = ShardBy::RoundRobin, requires = "shards")]
+    shard_by: ShardBy,
+
+    /// Record finished units of the input in this file, and when it exists
+    /// skip the units an earlier run loaded. Each unit goes through a staging
+    /// table of its own, so no row is lost or loaded twice. The file is
+    /// deleted once the load completes.
+    #[arg(long)]
+    checkpoint_file: Option<PathBuf>,
+
+    /// Input bytes per checkpointed unit (e.g. "64MB")
+    #[arg(long, default_value = "256MB", requires = "checkpoint_file",
+          value_parser = |s: &str| parse_memory_to_bytes(s).map_err(|e| e.to_string()))]
+    checkpoint_unit: u64,
+
+    /// Without a kvbin index, read the input once more to check that the record
+    /// boundaries found by scanning are real (no record skipped or loaded twice)
+    #[arg(long)]
+    validate_boundaries: bool,
+
+    /// Expose Prometheus metrics on this port while loading
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Print a JSON progress event to stderr every this many seconds
+    #[arg(long)]
+    progress_interval: Option<u64>,
+
+    /// Don't draw a progress bar on stderr
+    #[arg(long)]
+    no_progress: bool,
+
+    /// Write per-second throughput samples to this CSV file (render with plot-throughput)
+    #[arg(long)]
+    throughput_log: Option<PathBuf>,
+
+    /// Write a CPU flamegraph of this run to this SVG file (needs the util-profile feature)
+    #[arg(long)]
+    profile: Option<PathBuf>,
+
+    /// Pin threads to these CPUs (e.g. "0-15,32-47")
+    #[arg(long)]
+    cpus: Option<String>,
+
+    /// Run on the CPUs of this NUMA node and allocate memory from it
+    #[arg(long)]
+    numa_node: Option<usize>,
 }
 
 fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    let _telemetry = telemetry::init("load-duckdb");
+    let _span = info_span!("load", engine = "duckdb", input = %args.input[0].display()).entered();
+    let _profile = profile::start(args.profile.as_deref())?;
 
-    // Check if destination file already exists
-    if args.db.exists() {
-        eprintln!("Error: Destination file {:?} already exists.", args.db);
-        std::process::exit(1);
+    if args.arrow && matches!(args.format, InputFormat::Kvbin) {
+        return Err("--arrow supports --format gensort only".into());
+    }
+    args.layout.check()?;
+    let columns = Columns {
+        key: args.key_type,
+        payload: args.payload_type,
+        scale: args.scale,
+        row_id: args.with_row_id,
+    };
+    if args.arrow && (columns.key, columns.payload) != (ColumnType::Blob, ColumnType::Blob) {
+        return Err("--arrow needs BLOB key and payload columns".into());
+    }
+    if columns.payload == ColumnType::Uint64 {
+        return Err("--payload-type uint64 is not supported; only the key can be numeric".into());
+    }
+    if columns.key == ColumnType::Uint64 && matches!(args.via, Via::Copy) {
+        return Err("--key-type uint64 only applies to --via appender".into());
+    }
+    if args.collation.is_some() && columns.key != ColumnType::Varchar {
+        return Err("--collation needs --key-type varchar".into());
+    }
+    // A stream has no size to split, so one thread reads it as it arrives
+    let stream = records::is_stream(&args.input[0])?;
+    args.io.check(args.format.into(), stream)?;
+    if stream {
+        if args.checkpoint_file.is_some() {
+            return Err("--checkpoint-file needs a regular input file, not a stream".into());
+        }
+        args.threads = 1;
+    }
+    if matches!(args.via, Via::Copy) && !args.io.is_plain() {
+        return Err("--io mmap, --io uring and --direct-io only apply to --via appender".into());
+    }
+    if matches!(args.via, Via::Copy) && (args.arrow || args.staging_tables) {
+        return Err("--arrow and --staging-tables only apply to --via appender".into());
+    }
+    if args.checkpoint_file.is_some()
+        && (matches!(args.via, Via::Copy) || args.arrow || args.staging_tables)
+    {
+        return Err(
+            "--checkpoint-file does not support --via copy, --arrow or --staging-tables".into(),
+        );
+    }
+    args.scale.check()?;
+    if args.scale.is_scaled()
+        && (matches!(args.via, Via::Copy) || args.arrow || args.checkpoint_file.is_some())
+    {
+        return Err(
+            "--scale-factor does not support --via copy, --arrow or --checkpoint-file".into(),
+        );
+    }
+    if args.with_row_id && (matches!(args.via, Via::Copy) || args.arrow) {
+        return Err("--with-row-id does not support --via copy or --arrow".into());
+    }
+    if let Some(shards) = args.shards {
+        shards::check(shards, args.shard_by)?;
+        if matches!(args.via, Via::Copy) {
+            return Err("--shards only applies to --via appender".into());
+        }
+    }
+    if args.input.len() > 1 {
+        input_files::check(&args.input, args.skip, args.limit)?;
+        if args.checkpoint_file.is_some()
+            || matches!(args.via, Via::Copy)
+            || args.arrow
+            || args.staging_tables
+            || args.shards.is_some()
+            || args.with_row_id
+        {
+            return Err("Several --input files do not support --checkpoint-file, \
+                        --via copy, --arrow, --staging-tables, --shards or --with-row-id"
+                .into());
+        }
+    }
+
+    // A checkpointed load resumes into the table it started, whatever --if-exists says
+    let resuming = args
+        .checkpoint_file
+        .as_ref()
+        .is_some_and(|path| path.exists());
+
+    if args.dry_run {
+        for input in &args.input {
+            if args.input.len() > 1 {
+                println!("{}:", input.display());
+            }
+            inspect::inspect(
+                args.format.into(),
+                args.layout,
+                input,
+                args.skip,
+                args.limit,
+            )?
+            .report()?;
+        }
+        return Ok(());
+    }
+    let files = match args.input.len() {
+        1 => None,
+        _ => Some(Arc::new(InputFiles::new(
+            args.format.into(),
+            args.layout,
+            &args.input,
+            args.threads,
+            input_files::UNIT_BYTES,
+        )?)),
+    };
+    let range = match files {
+        Some(ref files) => 0..files.bytes(),
+        None => records::select(
+            args.format.into(),
+            args.layout,
+            &args.input[0],
+            args.skip,
+            args.limit,
+        )?,
+    };
+    if args.skip > 0 || args.limit.is_some() {
+        println!("Selected input bytes {}..{}", range.start, range.end);
     }
 
+    // Before any threads are spawned, so they inherit the placement
+    affinity::apply(args.cpus.as_deref(), args.numa_node)?;
+
+    if let Some(port) = args.metrics_port {
+        metrics::serve(port)?;
+        println!("Serving Prometheus metrics on port {}", port);
+    }
+    if let Some(secs) = args.progress_interval {
+        progress::start(Duration::from_secs(secs));
+    }
+    if let Some(ref path) = args.throughput_log {
+        metrics::record_throughput(path)?;
+    }
+    metrics::set_phase(Phase::Setup);
+
     // 1. Initialize DuckDB connection and create table
     let conn = Connection::open(&args.db)?;
-    conn.execute(
-        &format!(
-            "CREATE TABLE IF NOT EXISTS {} (sort_key BLOB, payload BLOB);",
-            args.table
-        ),
-        [],
-    )?;
+    let key_sql = match args.collation {
+        Some(ref collation) => {
+            conn.execute_batch(collation.duckdb_setup())?;
+            format!("{}{}", columns.key.sql(), collation.duckdb())
+        }
+        None => columns.key.sql().to_string(),
+    };
+    for table in &tables(&args) {
+        if !resuming
+            && args
+                .if_exists
+                .must_drop(table, table_exists(&conn, table)?)?
+        {
+            conn.execute_batch(&format!("DROP TABLE {};", table))?;
+        }
+        let row_id = match args.with_row_id {
+            true => format!(", {} BIGINT", schema::ROW_ID_COLUMN),
+            false => String::new(),
+        };
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} ({} {}, {} {}{});",
+                table,
+                args.column_names.key,
+                key_sql,
+                args.column_names.payload,
+                columns.payload.sql(),
+                row_id
+            ),
+            [],
+        )?;
+    }
     drop(conn);
+    metrics::set_phase(Phase::Load);
+    let bar = progress_bar::load(args.format.into(), args.layout, &range, !args.no_progress);
+
+    let start = Instant::now();
+    if let Some(ref path) = args.checkpoint_file {
+        let rows = load_checkpointed(&args, path, range, columns)?;
+        add_extra_columns(&args)?;
+        metrics::set_phase(Phase::Done);
+        bar.finish();
+        println!(
+            "Successfully appended {} rows to DuckDB in {:.2}s.",
+            rows,
+            start.elapsed().as_secs_f64()
+        );
+        latency::report("appender flush");
+        return Ok(());
+    }
+    if let Via::Copy = args.via {
+        let rows = load_via_copy(&args, range, columns)?;
+        add_extra_columns(&args)?;
+        metrics::set_phase(Phase::Done);
+        bar.finish();
+        println!(
+            "Successfully copied {} rows to DuckDB in {:.2}s.",
+            rows,
+            start.elapsed().as_secs_f64()
+        );
+        return Ok(());
+    }
+
+    match files {
+        Some(_) => println!(
+            "Starting load from {} files with {} threads...",
+            args.input.len(),
+            args.threads
+        ),
+        None => println!(
+            "Starting load from {:?} with {} threads...",
+            args.input[0], args.threads
+        ),
+    }
+
+    let rows = match (files, args.format) {
+        (Some(files), _) => load_files(&args, files, columns)?,
+        _ if args.shards.is_some() => load_shards(&args, range, columns)?,
+        (None, InputFormat::Gensort) => load_gensort_parallel(
+            &args.input[0],
+            args.layout,
+            range.start / args.layout.record_size() as u64
+                ..range.end / args.layout.record_size() as u64,
+            &args.db,
+            &args.table,
+            args.threads,
+            args.staging_tables,
+            args.arrow,
+            columns,
+            args.io,
+        )?,
+        (None, InputFormat::Kvbin) => load_kvbin_parallel(
+            &args.input[0],
+            range,
+            args.io,
+            &args.db,
+            &args.table,
+            args.threads,
+            args.staging_tables,
+            args.validate_boundaries,
+            columns,
+        )?,
+    };
 
+    add_extra_columns(&args)?;
+    metrics::set_phase(Phase::Done);
+    bar.finish();
+    if args.scale.is_scaled() {
+        println!("Read {} records", rows);
+    }
     println!(
-        "Starting load from {:?} with {} threads...",
-        args.input, args.threads
+        "Successfully appended {} rows to DuckDB in {:.2}s.",
+        args.scale.rows(rows),
+        start.elapsed().as_secs_f64()
     );
+    latency::report("appender flush");
+    Ok(())
+}
 
-    let rows = match args.format {
+/// Adds the --extra-column columns once the rows are in
+fn add_extra_columns(args: &Args) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if args.extra_columns.is_empty() {
+        return Ok(());
+    }
+    let conn = Connection::open(&args.db)?;
+    for table in &tables(args) {
+        for column in &args.extra_columns {
+            conn.execute_batch(&column.add_sql(table))?;
+        }
+    }
+    Ok(())
+}
+
+/// The tables the load writes: the shards with --shards, else --table
+fn tables(args: &Args) -> Vec<String> {
+    match args.shards {
+        Some(shards) => shards::table_names(&args.table, shards),
+        None => vec![args.table.clone()],
+    }
+}
+
+/// Writes the input to a temporary CSV or Parquet file and loads that with
+/// DuckDB's own reader, timing the two steps separately
+fn load_via_copy(
+    args: &Args,
+    range: Range<u64>,
+    columns: Columns,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let extension = match args.copy_format {
+        CopyFormat::Csv => "csv",
+        CopyFormat::Parquet => "parquet",
+    };
+    let dir = args.copy_dir.clone().unwrap_or_else(std::env::temp_dir);
+    let path = dir.join(format!("es-duck-copy-{}.{}", std::process::id(), extension));
+
+    let result = convert_and_copy(args, range, columns, &path);
+    if let Err(e) = std::fs::remove_file(&path)
+        && e.kind() != io::ErrorKind::NotFound
+    {
+        eprintln!("Warning: failed to remove {:?}: {}", path, e);
+    }
+    result
+}
+
+fn convert_and_copy(
+    args: &Args,
+    range: Range<u64>,
+    columns: Columns,
+    path: &Path,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    println!("Converting {:?} to {:?}...", args.input[0], path);
+    let start = Instant::now();
+    let rows = {
+        let _span = info_span!("convert", path = %path.display()).entered();
+        let input = Window::open(&args.input[0], range.clone())?;
+        match args.copy_format {
+            CopyFormat::Csv => write_copy_csv(
+                input,
+                &args.input[0],
+                range,
+                args.io,
+                args.format,
+                args.layout,
+                path,
+            )?,
+            CopyFormat::Parquet => match args.format {
+                InputFormat::Gensort => write_copy_parquet(input, args.layout, path)?,
+                InputFormat::Kvbin => {
+                    return Err("--copy-format parquet supports gensort input only".into());
+                }
+            },
+        }
+    };
+    let converted = start.elapsed();
+
+    println!("Loading {} rows with DuckDB's reader...", rows);
+    let start = Instant::now();
+    // The temporary file's columns are always sort_key and payload
+    let names = args.column_names.list(Schema::KeyPayload);
+    let sql = match args.copy_format {
+        // The fields are hex text, so decode them on the way in
+        CopyFormat::Csv => format!(
+            "INSERT INTO {} ({}) SELECT {}, {} FROM read_csv('{}', \
+             header = false, columns = {{'sort_key': 'VARCHAR', 'payload': 'VARCHAR'}})",
+            args.table,
+            names,
+            columns.key.wrap_blob("unhex(sort_key)"),
+            columns.payload.wrap_blob("unhex(payload)"),
+            path.display()
+        ),
+        CopyFormat::Parquet => match (columns.key, columns.payload) {
+            (ColumnType::Blob, ColumnType::Blob) => format!(
+                "COPY {} ({}) FROM '{}' (FORMAT parquet)",
+                args.table,
+                names,
+                path.display()
+            ),
+            _ => format!(
+                "INSERT INTO {} ({}) SELECT {}, {} FROM read_parquet('{}')",
+                args.table,
+                names,
+                columns.key.wrap_blob("sort_key"),
+                columns.payload.wrap_blob("payload"),
+                path.display()
+            ),
+        },
+    };
+    let copied = {
+        let _span = info_span!("db_ingest").entered();
+        let conn = Connection::open(&args.db)?;
+        // --threads sizes DuckDB's own reader here
+        conn.execute_batch(&format!("SET threads = {};", args.threads))?;
+        conn.execute(&sql, [])? as u64
+    };
+    let loaded = start.elapsed();
+    metrics::add_rows_loaded(copied);
+
+    println!(
+        "Convert: {:.2}s, DuckDB load: {:.2}s",
+        converted.as_secs_f64(),
+        loaded.as_secs_f64()
+    );
+    if copied != rows {
+        return Err(format!("Converted {} rows, but DuckDB loaded {}", rows, copied).into());
+    }
+    Ok(rows)
+}
+
+/// Writes `input`, bytes `range` of the file at `input_path`, as
+/// `sort_key,payload` lines of hex; returns the rows written
+fn write_copy_csv(
+    input: impl Read + Seek,
+    input_path: &Path,
+    range: Range<u64>,
+    io: IoOptions,
+    format: InputFormat,
+    layout: FixedLayout,
+    path: &Path,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    const BATCH_RECORDS: usize = 50_000;
+
+    let mut reader = BufReader::with_capacity(16 * 1024 * 1024, input);
+    let mut writer = BufWriter::with_capacity(16 * 1024 * 1024, File::create(path)?);
+    let mut out = Vec::new();
+    let mut rows = 0u64;
+
+    match format {
         InputFormat::Gensort => {
-            load_gensort_parallel(&args.input, &args.db, &args.table, args.threads)?
+            let mut buf = vec![0u8; BATCH_RECORDS * layout.record_size()];
+            loop {
+                let len = read_full(&mut reader, &mut buf)?;
+                if len % layout.record_size() != 0 {
+                    return Err("Input ends inside a gensort record".into());
+                }
+                if len == 0 {
+                    break;
+                }
+                out.clear();
+                csv_file::encode_records(&buf[..len], layout, Encoding::Hex, &mut out);
+                writer.write_all(&out)?;
+                metrics::add_bytes_uploaded(len as u64);
+                rows += (len / layout.record_size()) as u64;
+            }
         }
         InputFormat::Kvbin => {
-            load_kvbin_parallel(&args.input, &args.db, &args.table, args.threads)?
+            let mut records = io.kvbin_reader(reader, input_path, range)?;
+            while let Some((key, value)) = records.next_record()? {
+                out.clear();
+                Encoding::Hex.encode(key, &mut out);
+                out.push(b',');
+                Encoding::Hex.encode(value, &mut out);
+                out.push(b'\n');
+                writer.write_all(&out)?;
+                metrics::add_bytes_uploaded((key.len() + value.len()) as u64);
+                rows += 1;
+            }
         }
-    };
+    }
+    writer.flush()?;
+    Ok(rows)
+}
 
-    println!("Successfully appended {} rows to DuckDB.", rows);
-    Ok(())
+/// Reads until `buf` is full or the input ends; returns the bytes read
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(feature = "util-parquet")]
+fn write_copy_parquet(
+    input: impl Read,
+    layout: FixedLayout,
+    path: &Path,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    const BATCH_RECORDS: usize = 50_000;
+    const ROW_GROUP_SIZE: usize = 1_048_576;
+
+    let mut reader = BufReader::with_capacity(16 * 1024 * 1024, input);
+    let mut writer = es_duck::parquet_file::RecordWriter::create(path, layout, ROW_GROUP_SIZE)?;
+    let mut buf = vec![0u8; BATCH_RECORDS * layout.record_size()];
+    loop {
+        let len = read_full(&mut reader, &mut buf)?;
+        if len == 0 {
+            break;
+        }
+        writer.write(&buf[..len])?;
+        metrics::add_bytes_uploaded(len as u64);
+    }
+    writer.finish()
+}
+
+#[cfg(not(feature = "util-parquet"))]
+fn write_copy_parquet(
+    _input: impl Read,
+    _layout: FixedLayout,
+    _path: &Path,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    Err("--copy-format parquet requires building with --features util-parquet".into())
 }
 
+/// Loads gensort records `records` (record numbers) of `layout`
+#[allow(clippy::too_many_arguments)]
 fn load_gensort_parallel(
-    input: &PathBuf,
+    input: &Path,
+    layout: FixedLayout,
+    records: Range<u64>,
     db: &PathBuf,
     table: &str,
     num_threads: usize,
+    staging_tables: bool,
+    arrow: bool,
+    columns: Columns,
+    io: IoOptions,
 ) -> Result<u64, Box<dyn Error + Send + Sync>> {
-    const KEY_SIZE: usize = 10;
-    const PAYLOAD_SIZE: usize = 90;
-    const RECORD_SIZE: usize = KEY_SIZE + PAYLOAD_SIZE;
+    const READ_BUFFER: usize = 16 * 1024 * 1024;
     const BATCH_SIZE: usize = 50_000; // Process 50k records per batch
     const FLUSH_INTERVAL: usize = 10; // Flush every 10 batches (500k records)
 
-    let file = File::open(input)?;
-    let file_size = file.metadata()?.len();
-    let total_records = file_size / RECORD_SIZE as u64;
-    drop(file);
+    let total_records = records.end - records.start;
 
     if num_threads == 1 {
         // Single-threaded path: read and append directly with batching
+        let _span = info_span!("db_ingest", records = total_records).entered();
         let conn = Connection::open(db)?;
         let mut appender = conn.appender(table)?;
 
-        let file = File::open(input)?;
-        let mut reader = BufReader::with_capacity(16 * 1024 * 1024, file);
-        let mut buf = vec![0u8; RECORD_SIZE];
-        let mut last_million_printed = 0u64;
-
-        for i in 0..total_records {
-            reader.read_exact(&mut buf)?;
-            let key = &buf[..KEY_SIZE];
-            let payload = &buf[KEY_SIZE..];
-            appender.append_row(params![key, payload])?;
-
-            if (i + 1) % (BATCH_SIZE as u64 * FLUSH_INTERVAL as u64) == 0 {
-                appender.flush()?;
-                let current_million = (i + 1) / 1_000_000;
-                if current_million > last_million_printed {
-                    println!("Loaded {} million records...", current_million);
-                    last_million_printed = current_million;
-                }
+        let first_record = records.start;
+        let mut reader = FixedRecords::open(io, input, layout, records, READ_BUFFER)?;
+        if arrow {
+            let rows = append_gensort_arrow(&mut reader, layout, &mut appender)?;
+            latency::time(|| appender.flush())?;
+            return Ok(rows);
+        }
+
+        // Counted as read: a stream input ends wherever its writer stops
+        let mut rows = 0u64;
+        while let Some(record) = reader.next_record()? {
+            let id = first_record + rows;
+            let (key, payload) = record.split_at(layout.key_size);
+            columns.append(&mut appender, id, key, payload)?;
+            metrics::add_rows_loaded(1);
+            metrics::add_bytes_uploaded(record.len() as u64);
+            rows += 1;
+
+            if rows.is_multiple_of(BATCH_SIZE as u64 * FLUSH_INTERVAL as u64) {
+                latency::time(|| appender.flush())?;
             }
         }
 
-        appender.flush()?;
-        return Ok(total_records);
+        latency::time(|| appender.flush())?;
+        return Ok(rows);
     }
 
-    // Channel with batched records - send Vec of fixed-size byte arrays
-    type RecordBatch = Vec<[u8; RECORD_SIZE]>;
-    let (tx, rx) = sync_channel::<RecordBatch>(num_threads * 2);
-
-    // Multi-threaded path: spawn reader threads
+    // Multi-threaded path: one range of records per reader thread
     let records_per_thread = (total_records + num_threads as u64 - 1) / num_threads as u64;
-    let mut handles = vec![];
+    let ranges: Vec<(u64, u64)> = (0..num_threads as u64)
+        .map(|t| {
+            (
+                t * records_per_thread,
+                ((t + 1) * records_per_thread).min(total_records),
+            )
+        })
+        .take_while(|&(start, _)| start < total_records)
+        .map(|(start, end)| (records.start + start, records.start + end))
+        .collect();
+
+    if staging_tables {
+        let append = if arrow {
+            append_gensort_range_arrow
+        } else {
+            append_gensort_range
+        };
+        return load_via_staging_tables(input, db, table, ranges, layout, columns, io, append);
+    }
 
-    for thread_id in 0..num_threads {
-        let start_record = thread_id as u64 * records_per_thread;
-        let end_record = ((thread_id + 1) as u64 * records_per_thread).min(total_records);
+    // Channel with batched records - send whole records back to back, with
+    // the record number of the first
+    type RecordBatch = (u64, Vec<u8>);
+    let (tx, rx) = sync_channel::<RecordBatch>(num_threads * 2);
+    // Appended batches go back to the readers: the channel's plus one per reader
+    let pool = BufferPool::new(num_threads * 3);
 
-        if start_record >= total_records {
-            break;
-        }
+    let mut handles = vec![];
+    let parent = Span::current();
 
-        let input = input.clone();
-        let tx = tx.clone();
+    for (start_record, end_record) in ranges {
+        let input = input.to_path_buf();
+        let (tx, pool) = (tx.clone(), pool.clone());
+        let span = info_span!(parent: &parent, "file_read", start_record, end_record);
 
         let handle = thread::spawn(move || -> Result<u64, Box<dyn Error + Send + Sync>> {
-            send_gensort_chunk_batched(&input, start_record, end_record, tx, BATCH_SIZE)
+            let _span = span.entered();
+            let records = start_record..end_record;
+            send_gensort_chunk_batched(&input, layout, records, io, tx, &pool, BATCH_SIZE)
         });
 
         handles.push(handle);
@@ -147,32 +921,41 @@ fn load_gensort_parallel(
     drop(tx);
 
     // Main thread: consume from channel and append to DB
+    let ingest_span = info_span!("db_ingest").entered();
     let conn = Connection::open(db)?;
     let mut appender = conn.appender(table)?;
     let mut total_rows = 0u64;
     let mut batch_count = 0usize;
-    let mut last_million_printed = 0u64;
 
-    for batch in rx {
-        for record in &batch {
-            let key = &record[..KEY_SIZE];
-            let payload = &record[KEY_SIZE..];
-            appender.append_row(params![key, payload])?;
+    for (first, batch) in rx {
+        let rows = (batch.len() / layout.record_size()) as u64;
+        if arrow {
+            appender.append_record_batch(gensort_record_batch(&batch, layout)?)?;
+        } else {
+            for (id, record) in (first..).zip(batch.chunks_exact(layout.record_size())) {
+                let (key, payload) = record.split_at(layout.key_size);
+                columns.append(&mut appender, id, key, payload)?;
+            }
         }
-        total_rows += batch.len() as u64;
+        total_rows += rows;
         batch_count += 1;
+        metrics::add_rows_loaded(rows);
+        metrics::add_bytes_uploaded(batch.len() as u64);
+
+        pool.give(batch);
 
         if batch_count % FLUSH_INTERVAL == 0 {
-            appender.flush()?;
-            let current_million = total_rows / 1_000_000;
-            if current_million > last_million_printed {
-                println!("Loaded {} million records...", current_million);
-                last_million_printed = current_million;
-            }
+            latency::time(|| appender.flush())?;
         }
     }
 
-    appender.flush()?;
+    latency::time(|| appender.flush())?;
+    ingest_span.exit();
+    let stats = pool.stats();
+    println!(
+        "Batch buffers: {} allocated, {} reused",
+        stats.created, stats.reused
+    );
 
     // Wait for all threads and check for errors
     for (i, handle) in handles.into_iter().enumerate() {
@@ -188,48 +971,514 @@ fn load_gensort_parallel(
     Ok(total_rows)
 }
 
+/// Sends gensort records `records` of `layout` in batches of `batch_size`
+/// records, back to back
 fn send_gensort_chunk_batched(
-    input: &PathBuf,
-    start_record: u64,
-    end_record: u64,
-    tx: SyncSender<Vec<[u8; 100]>>,
+    input: &Path,
+    layout: FixedLayout,
+    records: Range<u64>,
+    io: IoOptions,
+    tx: SyncSender<(u64, Vec<u8>)>,
+    pool: &BufferPool<u8>,
     batch_size: usize,
 ) -> Result<u64, Box<dyn Error + Send + Sync>> {
-    const RECORD_SIZE: usize = 100;
+    let num_records = records.end - records.start;
+    let mut first = records.start;
+    let mut reader = FixedRecords::open(io, input, layout, records, 16 * 1024 * 1024)?;
 
-    let mut file = File::open(input)?;
-    file.seek(SeekFrom::Start(start_record * RECORD_SIZE as u64))?;
+    loop {
+        let records = reader.next_records(batch_size)?;
+        if records.is_empty() {
+            break;
+        }
+        // Batch buffers come back from the appender once appended
+        let mut batch = pool.take(records.len());
+        batch.extend_from_slice(records);
+        tx.send((first, batch))
+            .map_err(|_| "Failed to send batch to channel")?;
+        first += (records.len() / layout.record_size()) as u64;
+    }
 
-    let mut reader = BufReader::with_capacity(16 * 1024 * 1024, file);
-    let num_records = end_record - start_record;
+    Ok(num_records)
+}
 
-    // Allocate batch buffer once and reuse it
-    let mut batch = Vec::with_capacity(batch_size);
+/// Appender signature shared by the staging-table readers: appends the input
+/// between two positions (gensort records of the layout) and returns the rows
+/// appended
+type AppendRange = fn(
+    &Path,
+    u64,
+    u64,
+    FixedLayout,
+    Columns,
+    IoOptions,
+    &mut Appender,
+) -> Result<u64, Box<dyn Error + Send + Sync>>;
+
+/// Loads each of `ranges` on its own thread, which appends to its own staging
+/// table over its own connection, so DuckDB ingests in parallel rather than
+/// through one appender. The staging tables are then copied into `table` in
+/// range order, keeping the input order, and dropped.
+#[allow(clippy::too_many_arguments)]
+fn load_via_staging_tables(
+    input: &Path,
+    db: &Path,
+    table: &str,
+    ranges: Vec<(u64, u64)>,
+    layout: FixedLayout,
+    columns: Columns,
+    io: IoOptions,
+    append: AppendRange,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let conn = Connection::open(db)?;
+    let staging: Vec<String> = (0..ranges.len())
+        .map(|i| format!("{}_staging_{}", table, i))
+        .collect();
+    // Created up front so the threads do not race on the catalog
+    for name in &staging {
+        conn.execute(
+            &format!("CREATE TABLE {} AS SELECT * FROM {} LIMIT 0", name, table),
+            [],
+        )?;
+    }
+    println!("Appending to {} staging tables...", staging.len());
 
-    for _ in 0..num_records {
-        let mut record = [0u8; RECORD_SIZE];
-        reader.read_exact(&mut record)?;
-        batch.push(record);
+    let parent = Span::current();
+    let mut handles = vec![];
+    for (name, (start, end)) in staging.iter().zip(ranges) {
+        let thread_conn = conn.try_clone()?;
+        let input = input.to_path_buf();
+        let name = name.clone();
+        let span = info_span!(parent: &parent, "db_ingest", staging_table = %name, start, end);
+        handles.push(thread::spawn(
+            move || -> Result<u64, Box<dyn Error + Send + Sync>> {
+                let _span = span.entered();
+                let mut appender = thread_conn.appender(&name)?;
+                let rows = append(&input, start, end, layout, columns, io, &mut appender)?;
+                latency::time(|| appender.flush())?;
+                Ok(rows)
+            },
+        ));
+    }
 
-        // Send full batches
-        if batch.len() >= batch_size {
-            tx.send(batch)
-                .map_err(|_| "Failed to send batch to channel")?;
-            batch = Vec::with_capacity(batch_size);
+    let mut total_rows = 0u64;
+    for (i, handle) in handles.into_iter().enumerate() {
+        match handle.join() {
+            Ok(result) => match result {
+                Ok(rows) => {
+                    println!("Thread {} appended {} rows to {}", i, rows, staging[i]);
+                    total_rows += rows;
+                }
+                Err(e) => return Err(format!("Thread {} failed: {}", i, e).into()),
+            },
+            Err(_) => return Err(format!("Thread {} panicked", i).into()),
         }
     }
 
-    // Send remaining records
-    if !batch.is_empty() {
-        tx.send(batch)
-            .map_err(|_| "Failed to send batch to channel")?;
+    let _span = info_span!("merge_staging", tables = staging.len()).entered();
+    println!("Copying staging tables into {}...", table);
+    let mut sql = String::from("BEGIN TRANSACTION;\n");
+    for name in &staging {
+        sql.push_str(&format!("INSERT INTO {} SELECT * FROM {};\n", table, name));
+    }
+    for name in &staging {
+        sql.push_str(&format!("DROP TABLE {};\n", name));
+    }
+    sql.push_str("COMMIT;");
+    conn.execute_batch(&sql)?;
+    Ok(total_rows)
+}
+
+/// Loads the input in checkpointed units, spread round-robin over the threads.
+/// Each unit is appended to a staging table, which is committed and marked
+/// staged, then moved into the table and dropped in one transaction and marked
+/// done. A unit staged by an earlier run is moved if its staging table is still
+/// there, and was already moved otherwise. Returns the rows appended by this run.
+fn load_checkpointed(
+    args: &Args,
+    path: &Path,
+    range: Range<u64>,
+    columns: Columns,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let format = args.format.into();
+    let description = checkpoint::describe(
+        format,
+        args.layout,
+        &args.input[0],
+        &range,
+        &args.table,
+        args.checkpoint_unit,
+    )?;
+    let units = checkpoint::units(
+        format,
+        args.layout,
+        &args.input[0],
+        range,
+        args.checkpoint_unit,
+    )?;
+    let checkpoint = Arc::new(Checkpoint::open(path, &description)?);
+    println!(
+        "Loading {} units with {} threads ({} already loaded)...",
+        units.len(),
+        args.threads,
+        checkpoint.done_units()
+    );
+
+    let append: AppendRange = match args.format {
+        InputFormat::Gensort => append_gensort_range,
+        InputFormat::Kvbin => append_kvbin_range,
+    };
+    let units = Arc::new(units);
+    // Staging tables are created and moved one at a time, so the threads do not
+    // race on the catalog; DuckDB parallelizes each move itself
+    let catalog = Arc::new(Mutex::new(()));
+    let conn = Connection::open(&args.db)?;
+    let parent = Span::current();
+    let mut handles = vec![];
+    for t in 0..args.threads.max(1) {
+        let thread_conn = conn.try_clone()?;
+        let input = args.input[0].clone();
+        let table = args.table.clone();
+        let threads = args.threads.max(1);
+        let (io, layout) = (args.io, args.layout);
+        let (units, checkpoint, catalog) = (units.clone(), checkpoint.clone(), catalog.clone());
+        let span = info_span!(parent: &parent, "db_ingest", thread = t);
+        handles.push(thread::spawn(
+            move || -> Result<u64, Box<dyn Error + Send + Sync>> {
+                let _span = span.entered();
+                let mut rows = 0u64;
+                for unit in units.iter().skip(t).step_by(threads) {
+                    if checkpoint.is_done(unit) {
+                        continue;
+                    }
+                    let staging = format!("{}_unit_{}", table, unit.start);
+                    if !checkpoint.is_staged(unit) {
+                        {
+                            let _catalog = catalog.lock().unwrap();
+                            // A crashed run may have left part of the unit behind
+                            thread_conn.execute_batch(&format!(
+                                "DROP TABLE IF EXISTS {}; CREATE TABLE {} AS SELECT * FROM {} LIMIT 0;",
+                                staging, staging, table
+                            ))?;
+                        }
+                        {
+                            let mut appender = thread_conn.appender(&staging)?;
+                            rows += append(
+                                &input,
+                                unit.start,
+                                unit.end,
+                                layout,
+                                columns,
+                                io,
+                                &mut appender,
+                            )?;
+                            latency::time(|| appender.flush())?;
+                        }
+                        checkpoint.mark_staged(unit)?;
+                    }
+                    {
+                        let _catalog = catalog.lock().unwrap();
+                        publish_unit(&thread_conn, &table, &staging)?;
+                    }
+                    checkpoint.mark_done(unit)?;
+                }
+                Ok(rows)
+            },
+        ));
     }
 
-    Ok(num_records)
+    let mut total_rows = 0u64;
+    for (i, handle) in handles.into_iter().enumerate() {
+        match handle.join() {
+            Ok(Ok(rows)) => total_rows += rows,
+            Ok(Err(e)) => return Err(format!("Thread {} failed: {}", i, e).into()),
+            Err(_) => return Err(format!("Thread {} panicked", i).into()),
+        }
+    }
+    match Arc::try_unwrap(checkpoint) {
+        Ok(checkpoint) => checkpoint.remove()?,
+        Err(_) => unreachable!("all threads have finished"),
+    }
+    Ok(total_rows)
 }
 
-fn load_index(index_file: impl AsRef<Path>, file_size: u64) -> Result<Vec<u64>, String> {
-    let mut index_points = vec![0];
+/// Loads several --input files, their units shared out between the threads
+/// (see `input_files`). Each thread appends through its own connection and
+/// flushes after every unit, so a file is reported once its rows are in.
+fn load_files(
+    args: &Args,
+    files: Arc<InputFiles>,
+    columns: Columns,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let append: AppendRange = match args.format {
+        InputFormat::Gensort => append_gensort_range,
+        InputFormat::Kvbin => append_kvbin_range,
+    };
+    let conn = Connection::open(&args.db)?;
+    let parent = Span::current();
+    let mut handles = vec![];
+    for t in 0..args.threads.max(1) {
+        let thread_conn = conn.try_clone()?;
+        let (files, table, io, layout) = (files.clone(), args.table.clone(), args.io, args.layout);
+        let span = info_span!(parent: &parent, "db_ingest", thread = t);
+        handles.push(thread::spawn(
+            move || -> Result<u64, Box<dyn Error + Send + Sync>> {
+                let _span = span.entered();
+                let mut appender = thread_conn.appender(&table)?;
+                let mut rows = 0u64;
+                while let Some(unit) = files.next(t) {
+                    let (start, end) = (unit.range.start, unit.range.end);
+                    let path = files.path(&unit);
+                    let unit_rows = append(path, start, end, layout, columns, io, &mut appender)?;
+                    latency::time(|| appender.flush())?;
+                    files.finish(&unit, columns.scale.rows(unit_rows));
+                    rows += unit_rows;
+                }
+                Ok(rows)
+            },
+        ));
+    }
+
+    let mut total_rows = 0u64;
+    for (i, handle) in handles.into_iter().enumerate() {
+        match handle.join() {
+            Ok(Ok(rows)) => total_rows += rows,
+            Ok(Err(e)) => return Err(format!("Thread {} failed: {}", i, e).into()),
+            Err(_) => return Err(format!("Thread {} panicked", i).into()),
+        }
+    }
+    Ok(total_rows)
+}
+
+/// Loads into the --shards tables: every reader thread routes its rows to an
+/// appender per shard on a connection of its own
+fn load_shards(
+    args: &Args,
+    range: Range<u64>,
+    columns: Columns,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    const FLUSH_ROWS: u64 = 500_000;
+
+    let tables = tables(args);
+    let conn = Connection::open(&args.db)?;
+    let parent = Span::current();
+    let mut handles = vec![];
+    let ranges = shards::reader_ranges(args.format.into(), args.layout, range, args.threads);
+    for (reader, bytes) in ranges.into_iter().enumerate() {
+        let thread_conn = conn.try_clone()?;
+        let (input, tables) = (args.input[0].clone(), tables.clone());
+        let (format, layout, io) = (args.format, args.layout, args.io);
+        let mut router = Router::new(args.shard_by, tables.len(), reader);
+        let span = info_span!(parent: &parent, "db_ingest", start = bytes.start, end = bytes.end);
+        handles.push(thread::spawn(
+            move || -> Result<Vec<u64>, Box<dyn Error + Send + Sync>> {
+                let _span = span.entered();
+                let mut appenders = tables
+                    .iter()
+                    .map(|table| thread_conn.appender(table))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut loaded = vec![0u64; tables.len()];
+                shards::route(
+                    &input,
+                    format.into(),
+                    layout,
+                    io,
+                    bytes,
+                    &mut router,
+                    |shard, id, key, payload| {
+                        columns.append(&mut appenders[shard], id, key, payload)?;
+                        metrics::add_rows_loaded(1);
+                        metrics::add_bytes_uploaded((key.len() + payload.len()) as u64);
+                        loaded[shard] += 1;
+                        if loaded[shard].is_multiple_of(FLUSH_ROWS) {
+                            latency::time(|| appenders[shard].flush())?;
+                        }
+                        Ok(())
+                    },
+                )?;
+                for appender in &mut appenders {
+                    latency::time(|| appender.flush())?;
+                }
+                Ok(loaded)
+            },
+        ));
+    }
+
+    let mut shard_rows = vec![0u64; tables.len()];
+    for (i, handle) in handles.into_iter().enumerate() {
+        match handle.join() {
+            Ok(Ok(loaded)) => {
+                for (total, rows) in shard_rows.iter_mut().zip(loaded) {
+                    *total += rows;
+                }
+            }
+            Ok(Err(e)) => return Err(format!("Thread {} failed: {}", i, e).into()),
+            Err(_) => return Err(format!("Thread {} panicked", i).into()),
+        }
+    }
+    for (i, rows) in shard_rows.iter().enumerate() {
+        println!("Shard {} loaded {} rows", i, columns.scale.rows(*rows));
+    }
+    Ok(shard_rows.iter().sum())
+}
+
+/// Moves a staged unit into `table` and drops its staging table, unless an
+/// earlier run already did
+fn publish_unit(
+    conn: &Connection,
+    table: &str,
+    staging: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if !table_exists(conn, staging)? {
+        return Ok(());
+    }
+    conn.execute_batch(&format!(
+        "BEGIN TRANSACTION; INSERT INTO {} SELECT * FROM {}; DROP TABLE {}; COMMIT;",
+        table, staging, staging
+    ))?;
+    Ok(())
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, Box<dyn Error + Send + Sync>> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM information_schema.tables WHERE table_name = ?",
+        params![table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Appends gensort records `start_record..end_record` of `layout`
+fn append_gensort_range(
+    input: &Path,
+    start_record: u64,
+    end_record: u64,
+    layout: FixedLayout,
+    columns: Columns,
+    io: IoOptions,
+    appender: &mut Appender,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    const FLUSH_ROWS: u64 = 500_000;
+
+    let mut reader = FixedRecords::open(
+        io,
+        input,
+        layout,
+        start_record..end_record,
+        16 * 1024 * 1024,
+    )?;
+    let mut i = 0u64;
+    while let Some(record) = reader.next_record()? {
+        let id = start_record + i;
+        let (key, payload) = record.split_at(layout.key_size);
+        columns.append(appender, id, key, payload)?;
+        metrics::add_rows_loaded(1);
+        metrics::add_bytes_uploaded(record.len() as u64);
+        i += 1;
+        if i.is_multiple_of(FLUSH_ROWS) {
+            latency::time(|| appender.flush())?;
+        }
+    }
+    Ok(i)
+}
+
+/// Like `append_gensort_range`, in Arrow record batches (BLOB columns only)
+fn append_gensort_range_arrow(
+    input: &Path,
+    start_record: u64,
+    end_record: u64,
+    layout: FixedLayout,
+    _columns: Columns,
+    io: IoOptions,
+    appender: &mut Appender,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let mut reader = FixedRecords::open(
+        io,
+        input,
+        layout,
+        start_record..end_record,
+        16 * 1024 * 1024,
+    )?;
+    append_gensort_arrow(&mut reader, layout, appender)
+}
+
+/// Appends the rest of `reader`'s records, of `layout`, in Arrow record
+/// batches, flushing about as often as the row-by-row paths. Returns the rows
+/// appended.
+fn append_gensort_arrow(
+    reader: &mut FixedRecords,
+    layout: FixedLayout,
+    appender: &mut Appender,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    const BATCH_SIZE: usize = 50_000;
+    const FLUSH_INTERVAL: u64 = 10;
+
+    let (mut batches, mut rows) = (0u64, 0u64);
+    loop {
+        let records = reader.next_records(BATCH_SIZE)?;
+        if records.is_empty() {
+            break;
+        }
+        appender.append_record_batch(gensort_record_batch(records, layout)?)?;
+        batches += 1;
+        rows += (records.len() / layout.record_size()) as u64;
+        metrics::add_rows_loaded((records.len() / layout.record_size()) as u64);
+        metrics::add_bytes_uploaded(records.len() as u64);
+        if batches.is_multiple_of(FLUSH_INTERVAL) {
+            latency::time(|| appender.flush())?;
+        }
+    }
+    Ok(rows)
+}
+
+/// Whole gensort records of `layout` as a (sort_key, payload) batch of binary
+/// columns, which DuckDB appends as BLOBs
+fn gensort_record_batch(
+    data: &[u8],
+    layout: FixedLayout,
+) -> Result<RecordBatch, Box<dyn Error + Send + Sync>> {
+    let records = data.chunks_exact(layout.record_size());
+    let keys = BinaryArray::from_iter_values(records.clone().map(|r| &r[..layout.key_size]));
+    let payloads = BinaryArray::from_iter_values(records.map(|r| &r[layout.key_size..]));
+    Ok(RecordBatch::try_from_iter([
+        ("sort_key", Arc::new(keys) as ArrayRef),
+        ("payload", Arc::new(payloads) as ArrayRef),
+    ])?)
+}
+
+/// Appends the kvbin records starting in `start_offset..end_offset`
+fn append_kvbin_range(
+    input: &Path,
+    start_offset: u64,
+    end_offset: u64,
+    _layout: FixedLayout,
+    columns: Columns,
+    io: IoOptions,
+    appender: &mut Appender,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    const FLUSH_ROWS: u64 = 500_000;
+
+    let mut file = File::open(input)?;
+    file.seek(SeekFrom::Start(start_offset))?;
+    let mut records = io.kvbin_reader(file, input, start_offset..end_offset)?;
+
+    let mut rows = 0u64;
+
+    while let Some((id, key, value)) = records.next_record_at()? {
+        columns.append(appender, id, key, value)?;
+        rows += 1;
+        metrics::add_rows_loaded(1);
+        metrics::add_bytes_uploaded((key.len() + value.len()) as u64);
+        if rows.is_multiple_of(FLUSH_ROWS) {
+            latency::time(|| appender.flush())?;
+        }
+    }
+    Ok(rows)
+}
+
+/// Index offsets inside bytes `range` of the input, with its ends
+fn load_index(index_file: impl AsRef<Path>, range: &Range<u64>) -> Result<Vec<u64>, String> {
+    let mut index_points = vec![range.start];
 
     let mut index_file =
         File::open(index_file).map_err(|e| format!("Failed to open index file: {e}"))?;
@@ -249,111 +1498,109 @@ fn load_index(index_file: impl AsRef<Path>, file_size: u64) -> Result<Vec<u64>,
     index_points.extend(
         buf.chunks_exact(8)
             .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
-            .filter(|&off| off > 0 && off < file_size),
+            .filter(|&off| off > range.start && off < range.end),
     );
 
-    index_points.push(file_size);
+    index_points.push(range.end);
     index_points.sort_unstable();
     index_points.dedup();
     Ok(index_points)
 }
 
 fn send_kvbin_chunk_indexed(
-    input: &PathBuf,
-    start_offset: u64,
-    end_offset: u64,
-    tx: SyncSender<(Vec<u8>, Vec<u8>)>,
+    input: &Path,
+    range: Range<u64>,
+    io: IoOptions,
+    tx: SyncSender<(u64, Vec<u8>, Vec<u8>)>,
 ) -> Result<u64, Box<dyn Error + Send + Sync>> {
     let mut file = File::open(input)?;
-    file.seek(SeekFrom::Start(start_offset))?;
-    let mut reader = BufReader::with_capacity(4 * 1024 * 1024, file);
+    file.seek(SeekFrom::Start(range.start))?;
+    let mut records = io.kvbin_reader(file, input, range)?;
 
     let mut rows = 0u64;
-    let mut len_buf = [0u8; 4];
-    let mut key_buf = Vec::new();
-    let mut val_buf = Vec::new();
-    let mut current_pos = start_offset;
-
-    // Read records until we reach end_offset
-    while current_pos < end_offset {
-        // Read key length
-        if let Err(e) = reader.read_exact(&mut len_buf) {
-            if e.kind() == io::ErrorKind::UnexpectedEof {
-                break;
-            }
-            return Err(e.into());
-        }
-        let klen = u32::from_le_bytes(len_buf) as usize;
-        key_buf.resize(klen, 0);
-        reader.read_exact(&mut key_buf)?;
-
-        // Read value length
-        reader.read_exact(&mut len_buf)?;
-        let vlen = u32::from_le_bytes(len_buf) as usize;
-        val_buf.resize(vlen, 0);
-        reader.read_exact(&mut val_buf)?;
-
-        current_pos += 8 + klen as u64 + vlen as u64; // 4 bytes klen + 4 bytes vlen + data
 
-        tx.send((key_buf.clone(), val_buf.clone()))
+    // Read the records starting before the range's end
+    while let Some((offset, key, value)) = records.next_record_at()? {
+        tx.send((offset, key.to_vec(), value.to_vec()))
             .map_err(|_| "Failed to send record to channel")?;
         rows += 1;
-
-        // Stop if we've crossed into the next partition
-        if current_pos >= end_offset {
-            break;
-        }
     }
 
     Ok(rows)
 }
 
+/// Loads the kvbin records in bytes `range`
+#[allow(clippy::too_many_arguments)]
 fn load_kvbin_parallel(
-    input: &PathBuf,
+    input: &Path,
+    range: Range<u64>,
+    io: IoOptions,
     db: &PathBuf,
     table: &str,
     num_threads: usize,
+    staging_tables: bool,
+    validate_boundaries: bool,
+    columns: Columns,
 ) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    // Same flush cadence as the gensort paths (10 batches of 50k records)
+    const FLUSH_ROWS: u64 = 500_000;
+
     // Check for index file (original filename + .idx)
     let mut index_path = input.as_os_str().to_owned();
     index_path.push(".idx");
     let index_path = PathBuf::from(index_path);
-    let file_size = File::open(input)?.metadata()?.len();
-
-    if index_path.exists() && num_threads > 1 {
-        // Parallel loading using index
-        println!("Loading index from {:?}...", index_path);
-        let offsets = load_index(&index_path, file_size)
-            .map_err(|e| -> Box<dyn Error + Send + Sync> { e.into() })?;
-
-        println!(
-            "Index loaded: {} offset points, using {} threads",
-            offsets.len(),
-            num_threads
-        );
 
-        let (tx, rx) = sync_channel::<(Vec<u8>, Vec<u8>)>(100_000);
+    if num_threads > 1 {
+        // Parallel loading using the index, or boundaries found by scanning
+        let offsets = if index_path.exists() {
+            println!("Loading index from {:?}...", index_path);
+            let offsets = load_index(&index_path, &range)
+                .map_err(|e| -> Box<dyn Error + Send + Sync> { e.into() })?;
+
+            println!(
+                "Index loaded: {} offset points, using {} threads",
+                offsets.len(),
+                num_threads
+            );
+            offsets
+        } else {
+            kvbin_index::scan_boundaries(input, &range, num_threads, validate_boundaries)?
+        };
 
         // Divide the file into N partitions based on offsets
         let partitions_per_thread = (offsets.len() + num_threads - 1) / num_threads;
-        let mut handles = vec![];
-
-        for thread_id in 0..num_threads {
-            let start_partition = thread_id * partitions_per_thread;
-            let end_partition = ((thread_id + 1) * partitions_per_thread).min(offsets.len());
-
-            if start_partition >= offsets.len() - 1 {
-                break;
-            }
+        let ranges: Vec<(u64, u64)> = (0..num_threads)
+            .map(|t| (t * partitions_per_thread, (t + 1) * partitions_per_thread))
+            .take_while(|&(start, _)| start < offsets.len() - 1)
+            .map(|(start, end)| (offsets[start], offsets[end.min(offsets.len() - 1)]))
+            .collect();
+
+        if staging_tables {
+            // kvbin records have no layout
+            return load_via_staging_tables(
+                input,
+                db,
+                table,
+                ranges,
+                FixedLayout::default(),
+                columns,
+                io,
+                append_kvbin_range,
+            );
+        }
 
-            let start_offset = offsets[start_partition];
-            let end_offset = offsets[end_partition.min(offsets.len() - 1)];
+        let (tx, rx) = sync_channel::<(u64, Vec<u8>, Vec<u8>)>(100_000);
+        let mut handles = vec![];
+        let parent = Span::current();
 
-            let input = input.clone();
+        for (start_offset, end_offset) in ranges {
+            let input = input.to_path_buf();
             let tx = tx.clone();
+            let span = info_span!(parent: &parent, "file_read", start_offset, end_offset);
 
             let handle = thread::spawn(move || -> Result<u64, Box<dyn Error + Send + Sync>> {
-                send_kvbin_chunk_indexed(&input, start_offset, end_offset, tx)
+                let _span = span.entered();
+                send_kvbin_chunk_indexed(&input, start_offset..end_offset, io, tx)
             });
 
             handles.push(handle);
@@ -362,14 +1609,23 @@ fn load_kvbin_parallel(
         drop(tx);
 
         // Main thread: append to DB
+        let ingest_span = info_span!("db_ingest").entered();
         let conn = Connection::open(db)?;
         let mut appender = conn.appender(table)?;
         let mut total_rows = 0u64;
 
-        for (key, val) in rx {
-            appender.append_row(params![key.as_slice(), val.as_slice()])?;
+        for (id, key, val) in rx {
+            columns.append(&mut appender, id, &key, &val)?;
             total_rows += 1;
+            metrics::add_rows_loaded(1);
+            metrics::add_bytes_uploaded((key.len() + val.len()) as u64);
+
+            if total_rows.is_multiple_of(FLUSH_ROWS) {
+                latency::time(|| appender.flush())?;
+            }
         }
+        latency::time(|| appender.flush())?;
+        ingest_span.exit();
 
         // Wait for all threads
         for (i, handle) in handles.into_iter().enumerate() {
@@ -384,44 +1640,28 @@ fn load_kvbin_parallel(
 
         Ok(total_rows)
     } else {
-        // Sequential loading (no index or single thread)
-        if !index_path.exists() {
-            println!("No index file found, using sequential loading");
-        }
-
-        let file = File::open(input)?;
-        let mut reader = BufReader::with_capacity(32 * 1024 * 1024, file);
+        // Sequential loading (single thread)
+        let _span = info_span!("db_ingest").entered();
+        let reader = Window::open(input, range.clone())?;
+        let mut records = io.kvbin_reader(reader, input, range)?;
 
         let conn = Connection::open(db)?;
         let mut appender = conn.appender(table)?;
 
         let mut rows = 0u64;
-        let mut len_buf = [0u8; 4];
-        let mut key_buf = Vec::new();
-        let mut val_buf = Vec::new();
-
-        loop {
-            // Read key length
-            if let Err(e) = reader.read_exact(&mut len_buf) {
-                if e.kind() == io::ErrorKind::UnexpectedEof {
-                    break;
-                }
-                return Err(e.into());
-            }
-            let klen = u32::from_le_bytes(len_buf) as usize;
-            key_buf.resize(klen, 0);
-            reader.read_exact(&mut key_buf)?;
-
-            // Read value length
-            reader.read_exact(&mut len_buf)?;
-            let vlen = u32::from_le_bytes(len_buf) as usize;
-            val_buf.resize(vlen, 0);
-            reader.read_exact(&mut val_buf)?;
 
-            appender.append_row(params![key_buf.as_slice(), val_buf.as_slice()])?;
+        while let Some((id, key, value)) = records.next_record_at()? {
+            columns.append(&mut appender, id, key, value)?;
             rows += 1;
+            metrics::add_rows_loaded(1);
+            metrics::add_bytes_uploaded((key.len() + value.len()) as u64);
+
+            if rows.is_multiple_of(FLUSH_ROWS) {
+                latency::time(|| appender.flush())?;
+            }
         }
 
+        latency::time(|| appender.flush())?;
         Ok(rows)
     }
 }
@@ -0,0 +1,47 @@
+use clap::Parser;
+use es_duck::plot;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "plot-throughput")]
+#[command(about = "Render the samples recorded with --throughput-log as an SVG plot")]
+struct Args {
+    /// Throughput CSV written by a loader or sorter with --throughput-log
+    #[arg(long)]
+    input: PathBuf,
+
+    /// SVG file to write (defaults to the input path with an .svg extension)
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Plot title (defaults to the input file name)
+    #[arg(long)]
+    title: Option<String>,
+}
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let args = Args::parse();
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input.with_extension("svg"));
+    let title = args.title.clone().unwrap_or_else(|| {
+        args.input
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    });
+
+    let samples = plot::parse_samples(&fs::read_to_string(&args.input)?)?;
+    let svg = plot::render_svg(&samples, &title)?;
+    fs::write(&output, svg)?;
+    println!(
+        "Plotted {} samples from {:?} to {:?}",
+        samples.elapsed_secs.len(),
+        args.input,
+        output
+    );
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use clap::Parser;
+use es_duck::cgroup;
+use std::error::Error;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+#[derive(Parser)]
+#[command(name = "run-in-cgroup")]
+#[command(about = "Run a command (e.g. a local database server) under a cgroup v2 memory cap")]
+struct Args {
+    /// Hard memory cap enforced by the kernel (e.g., "8GB", "512MB")
+    #[arg(long)]
+    cgroup_memory_limit: String,
+
+    /// Name used for the cgroup directory
+    #[arg(long, default_value = "server")]
+    name: String,
+
+    /// Command to run, after `--`
+    #[arg(required = true, last = true)]
+    command: Vec<String>,
+}
+
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let args = Args::parse();
+
+    let limit = cgroup::parse_memory_to_bytes(&args.cgroup_memory_limit)?;
+    let path = cgroup::enter_memory_limited(&args.name, limit)?;
+    println!(
+        "Running {:?} in {:?} (memory.max = {} bytes)",
+        args.command, path, limit
+    );
+
+    // Creating the cgroup needs root, but PostgreSQL refuses to run as root. When
+    // started through sudo, run the command as the invoking user again.
+    let mut command = Command::new(&args.command[0]);
+    command.args(&args.command[1..]);
+    if let (Ok(uid), Ok(gid)) = (std::env::var("SUDO_UID"), std::env::var("SUDO_GID")) {
+        command.gid(gid.parse()?).uid(uid.parse()?);
+    }
+
+    // Daemons started by the command (pg_ctl, clickhouse --daemon) inherit the cgroup
+    let err = command.exec();
+    Err(format!("Failed to run {:?}: {}", args.command[0], err).into())
+}
@@ -1,23 +1,116 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use clickhouse::Client;
+use es_duck::cgroup;
+use es_duck::ch_query_log::{self, QueryLogStats};
+use es_duck::collation::Collation;
+use es_duck::csv_file::Encoding;
+use es_duck::export::{KeyType, OutputFormat};
+use es_duck::file_scan::{self, ScanFormat};
+use es_duck::metrics::{self, Phase};
+use es_duck::phases::PhaseTimes;
+use es_duck::progress;
+use es_duck::records::FixedLayout;
+use es_duck::rss::{self, PeakSampler};
+use es_duck::runs::{self, Runs};
+use es_duck::schema::{self, ColumnNames, OrderBy, SortMode};
+use es_duck::shards::{self, ShardSort};
+use es_duck::sorter::session;
+use es_duck::telemetry;
+use es_duck::timeout::{self, Timeout};
+use es_duck::verify::{self, RowIds};
 use std::error::Error;
 use std::path::PathBuf;
-use std::time::Instant;
-
-/// Parses strings like "1GB", "512MB" into a numeric byte value
-fn parse_memory_to_bytes(mem_str: &str) -> Result<u64, Box<dyn Error>> {
-    let s = mem_str.to_uppercase();
-    if s.ends_with("GB") || s.ends_with("G") {
-        let val: f64 = s.trim_end_matches('G').trim_end_matches("GB").parse()?;
-        Ok((val * 1024.0 * 1024.0 * 1024.0) as u64)
-    } else if s.ends_with("MB") || s.ends_with("M") {
-        let val: f64 = s.trim_end_matches('M').trim_end_matches("MB").parse()?;
-        Ok((val * 1024.0 * 1024.0) as u64)
-    } else {
-        Err("Unsupported memory format. Use GB or MB (e.g., '1GB', '512MB')".into())
+use std::time::{Duration, Instant};
+use tracing::{Instrument, info_span};
+
+/// Output formats, all of them written by the server
+const OUTPUT_FORMATS: &[OutputFormat] = &[
+    OutputFormat::Native,
+    OutputFormat::Parquet,
+    OutputFormat::Csv,
+    OutputFormat::Kvbin,
+    OutputFormat::Gensort,
+];
+
+/// The ClickHouse format that writes `format`'s bytes from `output_select`
+fn clickhouse_format(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Parquet => "Parquet",
+        OutputFormat::Csv => "TabSeparatedRaw",
+        OutputFormat::Kvbin | OutputFormat::Gensort => "RawBLOB",
+        OutputFormat::Native => "Native",
+        OutputFormat::Pgbinary => unreachable!("pgbinary isn't in OUTPUT_FORMATS"),
+    }
+}
+
+/// The SELECT list for an output of `format`: the key and payload columns for
+/// Native and Parquet, else one column holding each row's encoding, with a key
+/// of `key_type` as its bytes. With `row_ids` the formats that have room for it
+/// carry the row id.
+fn output_select(
+    format: OutputFormat,
+    names: &ColumnNames,
+    key_type: KeyType,
+    keys_only: bool,
+    row_ids: bool,
+) -> String {
+    let key = &key_type.clickhouse_bytes(&names.key);
+    let payload = &names.payload;
+    match (format, keys_only) {
+        (OutputFormat::Csv, false) if row_ids => format!(
+            "concat(lower(hex({})), ',', lower(hex({})), ',', leftPad(lower(hex({})), 16, '0'))",
+            key,
+            payload,
+            schema::ROW_ID_COLUMN
+        ),
+        (OutputFormat::Csv, false) => {
+            format!("concat(lower(hex({})), ',', lower(hex({})))", key, payload)
+        }
+        (OutputFormat::Csv, true) => format!("lower(hex({}))", key),
+        (OutputFormat::Kvbin, _) => {
+            // u32 LE lengths; reinterpretAsString drops trailing zero bytes
+            let len = |column: &str| {
+                format!(
+                    "toFixedString(reinterpretAsString(toUInt32(length({}))), 4)",
+                    column
+                )
+            };
+            let value = match keys_only {
+                true => "toFixedString('', 4)".to_string(),
+                false => format!("{}, {}", len(payload), payload),
+            };
+            format!("concat({}, {}, {})", len(key), key, value)
+        }
+        (OutputFormat::Gensort, _) => {
+            let layout = FixedLayout::GENSORT;
+            format!(
+                "concat(toFixedString({}, {}), toFixedString({}, {}))",
+                key, layout.key_size, payload, layout.payload_size
+            )
+        }
+        (_, true) => names.select_key(),
+        (_, false) => select(names, row_ids),
+    }
+}
+
+/// The key and payload columns, then the row id with `row_ids`
+fn select(names: &ColumnNames, row_ids: bool) -> String {
+    match row_ids {
+        true => format!("{}, {}", names.select(), schema::ROW_ID_COLUMN),
+        false => names.select(),
     }
 }
 
+/// Whether ClickHouse may merge the sorted parts of a table whose sorting key
+/// starts with the ORDER BY instead of sorting every row (optimize_read_in_order)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum ReadInOrder {
+    /// Read in order, and fail unless the plan does
+    On,
+    /// Sort every row even when the table is stored sorted
+    Off,
+}
+
 #[derive(Parser)]
 #[command(name = "sort-clickhouse")]
 #[command(about = "Run external sorting on a ClickHouse table")]
@@ -34,6 +127,26 @@ struct Args {
     #[arg(long, default_value = "bench_data")]
     table: String,
 
+    /// Sort the files matching this glob through the file() table function
+    /// instead of a table, so no load comes first. The path is relative to the
+    /// server's user_files_path; an s3:// or http(s):// URL goes through s3().
+    #[arg(long, value_name = "GLOB", conflicts_with = "shards")]
+    input_files: Option<String>,
+
+    /// Format of --input-files. Defaults to what the glob's extension names.
+    #[arg(long, value_enum, requires = "input_files")]
+    input_format: Option<ScanFormat>,
+
+    /// How the fields of CSV --input-files are encoded
+    #[arg(long, value_enum, default_value_t = Encoding::Hex)]
+    csv_encoding: Encoding,
+
+    #[command(flatten)]
+    column_names: ColumnNames,
+
+    #[command(flatten)]
+    order_by: OrderBy,
+
     /// Memory limit for external sorting (e.g., "1GB", "512MB")
     #[arg(long, default_value = "1GB")]
     memory_limit: String,
@@ -41,79 +154,543 @@ struct Args {
     #[arg(long)]
     threads: Option<usize>,
 
-    /// Output path for sorted data (CSV format). If not provided, runs query without output.
+    /// Output path for sorted data. If not provided, runs query without output.
     #[arg(long)]
     output: Option<PathBuf>,
+
+    /// Format of --output, written by the server (INTO OUTFILE) in every case:
+    /// CSV, kvbin and gensort rows are encoded by the query. Defaults to what
+    /// the file's extension names, else Native.
+    #[arg(long, value_enum, requires = "output")]
+    output_format: Option<OutputFormat>,
+
+    /// INSERT the sorted rows into this MergeTree table, ORDER BY sort_key,
+    /// instead of exporting them: the re-sort into a sorted table. It is
+    /// recreated before every sort with the sorted columns' types; a per-shard
+    /// sort creates <name>_shard<i>.
+    #[arg(long, conflicts_with = "output")]
+    output_table: Option<String>,
+
+    /// Sort the tables <table>_shard0 .. <table>_shard<N-1> of a load with
+    /// --shards instead of the table itself
+    #[arg(long)]
+    shards: Option<usize>,
+
+    /// How to sort --shards: one ORDER BY over their UNION ALL, or one ORDER BY
+    /// per shard, each writing <output stem>.shard<i>.<ext>
+    #[arg(long, value_enum, default_value_t = ShardSort::UnionAll, requires = "shards")]
+    shard_sort: ShardSort,
+
+    /// Keep only the first K rows of the sorted order (ORDER BY ... LIMIT K), to
+    /// benchmark the engine's top-K path against a full sort. Applies to each
+    /// query, so a per-shard sort keeps K rows of every shard.
+    #[arg(long)]
+    limit: Option<u64>,
+
+    /// Sort only the rows matching this SQL predicate (e.g. "c_int < 100"), to
+    /// measure filter + sort pipelines. With --shards it may only use the
+    /// columns the shards' UNION ALL carries.
+    #[arg(long = "where", value_name = "PREDICATE")]
+    filter: Option<String>,
+
+    /// Set optimize_read_in_order. On a table loaded with --order-by sort_key
+    /// `on` measures merging its sorted parts, and fails before the runs
+    /// unless the plan reads them in order; `off` makes it sort them in full.
+    /// Without it the server's setting applies.
+    #[arg(long, value_enum, conflicts_with = "input_files")]
+    read_in_order: Option<ReadInOrder>,
+
+    /// Select only the key, leaving the payload out of the sort, to separate
+    /// the cost of comparing keys from that of moving payload bytes. Output
+    /// files then hold just sort_key.
+    #[arg(long)]
+    keys_only: bool,
+
+    /// Sort every row, or keep each key once (SELECT DISTINCT, implies
+    /// --keys-only) to time dedup during the sort
+    #[arg(long, value_enum, default_value_t = SortMode::Sort)]
+    mode: SortMode,
+
+    /// Order a String key by ICU's rules for a locale (icu:<locale>, e.g.
+    /// icu:de-DE; ORDER BY ... COLLATE) instead of by its bytes (binary)
+    #[arg(long, value_name = "binary|icu:<locale>")]
+    collation: Option<Collation>,
+
+    #[command(flatten)]
+    runs: Runs,
+
+    #[command(flatten)]
+    timeout: Timeout,
+
+    /// Read --output back after the last run, untimed, and fail unless its keys
+    /// are sorted and it holds every row the sort should have written. The file
+    /// the server wrote has to be readable here too, and can't be Parquet.
+    #[arg(long, requires = "output")]
+    verify: bool,
+
+    /// Print the plan and the query every sort would run, settings included,
+    /// then exit without running them (nor writing --output or --output-table)
+    #[arg(long)]
+    explain_only: bool,
+
+    /// Expose Prometheus metrics on this port while the sort runs
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Print a JSON progress event to stderr every this many seconds
+    #[arg(long)]
+    progress_interval: Option<u64>,
+
+    /// Write per-second throughput samples to this CSV file (render with plot-throughput)
+    #[arg(long)]
+    throughput_log: Option<PathBuf>,
+}
+
+/// Polls system.processes for the running sort so metrics and progress events can
+/// report how many rows it has read and how much it has spilled
+fn watch_sort_progress(client: Client, query_id: String) {
+    tokio::spawn(async move {
+        loop {
+            let progress = client
+                .query(
+                    "SELECT read_rows, ProfileEvents['ExternalSortCompressedBytes'] \
+                     FROM system.processes WHERE query_id = ?",
+                )
+                .bind(&query_id)
+                .fetch_all::<(u64, u64)>()
+                .await;
+            match progress {
+                Ok(rows) => {
+                    for (read_rows, spill_bytes) in rows {
+                        metrics::set_rows_sorted(read_rows);
+                        metrics::set_spill_bytes(spill_bytes);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Warning: sort progress metrics disabled: {}", e);
+                    return;
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    });
+}
+
+/// The query_log entry of a finished query. The log is written in the background,
+/// so it is flushed first; None (with a warning) if that or the lookup fails, e.g.
+/// without the SYSTEM FLUSH LOGS grant or with query_log turned off.
+async fn query_log_stats(client: &Client, query_id: &str) -> Option<QueryLogStats> {
+    let stats = async {
+        client.query("SYSTEM FLUSH LOGS").execute().await?;
+        client
+            .query(ch_query_log::QUERY)
+            .bind(query_id)
+            .fetch_one::<(u64, u64, u64, u64, u64, u64)>()
+            .await
+    }
+    .await;
+    match stats {
+        Ok(row) => Some(QueryLogStats::from_row(row)),
+        Err(e) => {
+            eprintln!("Warning: no query_log entry for {}: {}", query_id, e);
+            None
+        }
+    }
+}
+
+/// The execution and export phases of a finished query from the logs
+/// `query_log_stats` flushed; None (with a warning) if the lookup fails
+async fn query_phases(client: &Client, query_id: &str) -> Option<PhaseTimes> {
+    let phases = client
+        .query(ch_query_log::PHASES_QUERY)
+        .bind(query_id)
+        .bind(query_id)
+        .fetch_one::<(u64, u64)>()
+        .await;
+    match phases {
+        Ok((duration_us, export_us)) => Some(PhaseTimes {
+            plan: None,
+            execute: Some(Duration::from_micros(duration_us.saturating_sub(export_us))),
+            export: Some(Duration::from_micros(export_us)),
+        }),
+        Err(e) => {
+            eprintln!("Warning: no phase times for {}: {}", query_id, e);
+            None
+        }
+    }
+}
+
+/// Caches `--cold` drops before every run. The OS page cache stays.
+const DROP_CACHES: &[&str] = &[
+    "SYSTEM DROP MARK CACHE",
+    "SYSTEM DROP UNCOMPRESSED CACHE",
+    "SYSTEM DROP QUERY CACHE",
+];
+
+/// Drops the server's caches, warning about any the server refuses or lacks
+async fn drop_caches(client: &Client) {
+    for statement in DROP_CACHES {
+        if let Err(e) = client.query(statement).execute().await {
+            eprintln!("Warning: {} failed: {}", statement, e);
+        }
+    }
+}
+
+/// Kills the sort `query_id` on the server; dropping its HTTP request alone
+/// doesn't stop a query that isn't readonly, like an INSERT or INTO OUTFILE
+async fn kill_query(client: &Client, query_id: &str) {
+    let kill = client
+        .query("KILL QUERY WHERE query_id = ? SYNC")
+        .bind(query_id)
+        .execute()
+        .await;
+    if let Err(e) = kill {
+        eprintln!("Warning: could not kill {}: {}", query_id, e);
+    }
+}
+
+/// Fails unless the plan of `select_query` reads the tables in the order of
+/// their sorting key, printing the keys, so --read-in-order on can't time a
+/// full sort by mistake
+async fn check_read_in_order(
+    client: &Client,
+    args: &Args,
+    tables: &[String],
+    select_query: &str,
+) -> Result<(), Box<dyn Error>> {
+    for table in tables {
+        let key = client
+            .query("SELECT sorting_key FROM system.tables WHERE database = ? AND name = ?")
+            .bind(&args.database)
+            .bind(table)
+            .fetch_one::<String>()
+            .await?;
+        println!("Sorting key of {}: {}", table, key);
+    }
+    let explained = client
+        .query(&format!("EXPLAIN actions = 1 {}", select_query))
+        .fetch_all::<String>()
+        .await?;
+    // ReadFromMergeTree reads a table in order, or in reverse for DESC
+    let in_order = explained.iter().any(|line| {
+        line.contains("ReadType: InOrder") || line.contains("ReadType: InReverseOrder")
+    });
+    if !in_order {
+        return Err(format!(
+            "--read-in-order on: the plan doesn't read {} in order; its sorting key has to \
+             start with ORDER BY {} (load it with --order-by)",
+            args.table,
+            args.order_by.clause(&args.column_names)
+        )
+        .into());
+    }
+    println!("Read in order: the sort merges the sorted parts");
+    Ok(())
+}
+
+/// Samples the server's resident memory. MemoryResident covers the whole server
+/// (caches included) and is refreshed about once a second, so short spikes can be missed.
+fn watch_server_rss(client: Client) -> PeakSampler {
+    let runtime = tokio::runtime::Handle::current();
+    PeakSampler::start(Duration::from_millis(500), move || {
+        let query = client
+            .query("SELECT value FROM system.asynchronous_metrics WHERE metric = 'MemoryResident'");
+        let bytes = runtime.block_on(query.fetch_one::<f64>()).ok()?;
+        Some(bytes as u64)
+    })
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    let _telemetry = telemetry::init("sort-clickhouse");
+    let root = info_span!("sort", engine = "clickhouse", table = %args.table);
+    if args.shards == Some(0) {
+        return Err("--shards must be at least 1".into());
+    }
+    args.order_by.check()?;
+    args.mode.check(&args.order_by)?;
+    if args.collation.is_some() && !args.order_by.columns.is_empty() {
+        return Err("--collation applies to the key: drop --order-by".into());
+    }
+    args.keys_only |= args.mode == SortMode::Distinct;
+    args.runs.check()?;
+    args.timeout.check()?;
+    let output_format = args
+        .output
+        .as_deref()
+        .map(|output| OutputFormat::resolve(args.output_format, output, OutputFormat::Native));
+    if let Some(format) = output_format {
+        format.check("sort-clickhouse", OUTPUT_FORMATS, args.keys_only)?;
+        if args.verify && format == OutputFormat::Parquet {
+            return Err("--verify can't read parquet output; pick another --output-format".into());
+        }
+    }
+    let input_format = match args.input_files {
+        Some(ref pattern) => Some(ScanFormat::resolve(args.input_format, pattern)?),
+        None => None,
+    };
+
+    if let Some(port) = args.metrics_port {
+        metrics::serve(port)?;
+        println!("Serving Prometheus metrics on port {}", port);
+    }
+    if let Some(secs) = args.progress_interval {
+        progress::start(Duration::from_secs(secs));
+    }
+    if let Some(ref path) = args.throughput_log {
+        metrics::record_throughput(path)?;
+    }
+    metrics::set_phase(Phase::Setup);
 
     // Initialize ClickHouse connection
     let client = Client::default()
         .with_url(&args.url)
         .with_database(&args.database);
 
+    let tables = match args.shards {
+        Some(shards) => shards::table_names(&args.table, shards),
+        None => vec![args.table.clone()],
+    };
+    // A --with-row-id table's ids go along with the rows, for --verify
+    let row_ids = !args.keys_only
+        && args.input_files.is_none()
+        && client
+            .query(
+                "SELECT count() FROM system.columns WHERE database = ? AND table = ? AND name = ?",
+            )
+            .bind(&args.database)
+            .bind(&tables[0])
+            .bind(schema::ROW_ID_COLUMN)
+            .fetch_one::<u64>()
+            .await?
+            > 0;
+    // An integer key goes out as its bytes; the files' keys are read as Strings
+    let key_type = match args.input_files {
+        Some(_) => KeyType::Bytes,
+        None => KeyType::from_sql(
+            &client
+                .query(
+                    "SELECT any(type) FROM system.columns \
+                     WHERE database = ? AND table = ? AND name = ?",
+                )
+                .bind(&args.database)
+                .bind(&tables[0])
+                .bind(&args.column_names.key)
+                .fetch_one::<String>()
+                .await?,
+        ),
+    };
+    if let Some(format) = output_format {
+        key_type.check(format)?;
+    }
+    // What the sort reads: the table, the UNION ALL of its shards, or the files
+    let source = match (&args.input_files, input_format, args.shards) {
+        (Some(pattern), Some(format), _) => {
+            file_scan::clickhouse_scan(pattern, format, args.csv_encoding, &args.column_names)
+        }
+        (_, _, Some(shards)) => shards::union_all(
+            &args.table,
+            shards,
+            &args.order_by.union_columns(&args.column_names, row_ids),
+        ),
+        _ => args.table.clone(),
+    };
+
     // Get table statistics
     println!("Gathering table statistics...");
-    let row_count: u64 = client
-        .query(&format!("SELECT COUNT(*) FROM {}", args.table))
-        .fetch_one::<u64>()
-        .await?;
+    // --explain-only leaves the counts' scans out; the plan has the estimates
+    let row_count: u64 = match args.explain_only {
+        true => 0,
+        false => {
+            client
+                .query(&format!("SELECT COUNT(*) FROM {}", source))
+                .fetch_one::<u64>()
+                .await?
+        }
+    };
+    let where_clause = args
+        .filter
+        .as_ref()
+        .map(|predicate| format!(" WHERE {}", predicate))
+        .unwrap_or_default();
+    // Rows the sort sees: all of them, or those matching --where
+    let sorted_rows: u64 = match args.filter {
+        Some(_) if !args.explain_only => {
+            client
+                .query(&format!("SELECT COUNT(*) FROM {}{}", source, where_clause))
+                .fetch_one::<u64>()
+                .await?
+        }
+        _ => row_count,
+    };
+    // The ids of those rows, which a --verify of all of them must find again
+    let expected_ids = match args.verify && row_ids && args.limit.is_none() {
+        true => {
+            let sum = client
+                .query(&RowIds::sum_query_clickhouse(&source, &where_clause))
+                .fetch_one::<String>()
+                .await?;
+            Some(RowIds {
+                rows: sorted_rows,
+                sum: sum.parse()?,
+            })
+        }
+        false => None,
+    };
 
-    // Get approximate table size
-    let table_size_bytes: u64 = client
-        .query(&format!(
-            "SELECT sum(bytes_on_disk) FROM system.parts WHERE database = '{}' AND table = '{}'",
-            args.database, args.table
-        ))
-        .fetch_one::<u64>()
-        .await
-        .unwrap_or(0);
-
-    println!("Table: {}", args.table);
-    println!("Row count: {}", row_count);
+    // Get approximate table size, or the files' from their virtual columns
+    let table_list: Vec<String> = tables.iter().map(|t| format!("'{}'", t)).collect();
+    let (input_file_count, table_size_bytes): (u64, u64) = match args.input_files {
+        Some(ref pattern) => client
+            .query(&file_scan::clickhouse_file_sizes(pattern))
+            .fetch_one::<(u64, u64)>()
+            .await
+            .unwrap_or((0, 0)),
+        None => {
+            let bytes = client
+                .query(&format!(
+                    "SELECT sum(bytes_on_disk) FROM system.parts \
+                     WHERE database = '{}' AND table IN ({})",
+                    args.database,
+                    table_list.join(", ")
+                ))
+                .fetch_one::<u64>()
+                .await
+                .unwrap_or(0);
+            (0, bytes)
+        }
+    };
+
+    match (&args.input_files, input_format) {
+        (Some(pattern), Some(format)) => println!(
+            "Input files: {} ({} files, {})",
+            pattern,
+            input_file_count,
+            format.name()
+        ),
+        _ => println!("Table: {}", args.table),
+    }
+    if let Some(shards) = args.shards {
+        println!("Shards: {}", shards);
+    }
+    match args.explain_only {
+        true => println!("Row count: not counted (--explain-only)"),
+        false => println!("Row count: {}", row_count),
+    }
+    // The key under --collation, if given
+    let order_clause = match args.collation {
+        Some(ref collation) => format!("{}{}", args.column_names.key, collation.clickhouse()),
+        None => args.order_by.clause(&args.column_names),
+    };
+    println!("Order by: {}", order_clause);
+    match args.filter {
+        Some(ref predicate) if args.explain_only => println!("Where: {}", predicate),
+        Some(ref predicate) => println!("Where: {} ({} rows match)", predicate, sorted_rows),
+        None => {}
+    }
+    if let Some(limit) = args.limit {
+        println!("Limit: {} rows (top-K)", limit);
+    }
+    if args.keys_only {
+        println!("Keys only: the payload is left out");
+    }
+    if args.mode == SortMode::Distinct {
+        println!("Mode: distinct (each key once)");
+    }
+    if let Some(format) = output_format {
+        println!("Output format: {}", format.name());
+    }
     println!(
-        "Table size: {} bytes ({:.2} GB)",
+        "{} size: {} bytes ({:.2} GB)",
+        match args.input_files {
+            Some(_) => "Input",
+            None => "Table",
+        },
         table_size_bytes,
         table_size_bytes as f64 / 1_073_741_824.0
     );
 
     // Parse memory limit
-    let max_bytes = parse_memory_to_bytes(&args.memory_limit)?;
+    let max_bytes = cgroup::parse_memory_to_bytes(&args.memory_limit).map_err(|e| e.to_string())?;
     println!("Parsed memory limit: {} bytes", max_bytes);
 
     // Build settings
-    let mut settings = Vec::new();
     if let Some(threads) = args.threads {
         println!("Setting max_threads to {}", threads);
-        settings.push(format!("max_threads = {}", threads));
     }
-    settings.push(format!("max_bytes_before_external_sort = {}", max_bytes));
-    settings.push(format!("max_bytes_ratio_before_external_sort = 0"));
+    let mut settings = session::clickhouse(max_bytes, args.threads, &[]);
+    if let Some(read_in_order) = args.read_in_order {
+        let on = read_in_order == ReadInOrder::On;
+        println!("Setting optimize_read_in_order to {}", on as u8);
+        settings.push(format!("optimize_read_in_order = {}", on as u8));
+    }
     println!(
         "Setting max_bytes_before_external_sort to {} bytes",
         max_bytes
     );
+    // Lets query_phases find the output format's time
+    settings.push("log_processors_profiles = 1".to_string());
+    if output_format == Some(OutputFormat::Parquet) {
+        // Binary columns, as the other engines write them, not UTF-8 strings
+        settings.push("output_format_parquet_string_as_string = 0".to_string());
+    }
     // settings.push(format!("max_memory_usage = {}", max_bytes));
     // println!("Setting max_memory_usage to {} bytes", max_bytes);
 
-    let settings_clause = if settings.is_empty() {
-        String::new()
-    } else {
-        format!("SETTINGS {}", settings.join(", "))
-    };
+    let settings_clause = format!("SETTINGS {}", settings.join(", "));
 
-    // Build the query
-    let select_query = format!(
-        "SELECT sort_key, payload FROM {} ORDER BY sort_key {}",
-        args.table, settings_clause
-    );
+    // The sorts to run, in order: what each reads and the file it writes
+    let sorts: Vec<(String, Option<PathBuf>)> = match (args.shards, args.shard_sort) {
+        (Some(_), ShardSort::PerShard) => tables
+            .iter()
+            .enumerate()
+            .map(|(i, table)| {
+                let output = args.output.as_deref().map(|o| shards::output_path(o, i));
+                (table.clone(), output)
+            })
+            .collect(),
+        _ => vec![(source, args.output.clone())],
+    };
+    // LIMIT goes before SETTINGS
+    let limit_clause = args
+        .limit
+        .map(|k| format!(" LIMIT {}", k))
+        .unwrap_or_default();
+    // The key and payload, or just the key with --keys-only, then any row id
+    let select_list = match args.keys_only {
+        true => args.column_names.select_key(),
+        false => select(&args.column_names, row_ids),
+    };
+    // What the query returns, already encoded for the row-per-value formats
+    let output_list = match output_format {
+        Some(format) => output_select(
+            format,
+            &args.column_names,
+            key_type,
+            args.keys_only,
+            row_ids,
+        ),
+        None => select_list.clone(),
+    };
+    // Plain DISTINCT: ClickHouse may order by a column it doesn't select, and
+    // runs DISTINCT ON as LIMIT 1 BY instead
+    let distinct = match args.mode {
+        SortMode::Sort => "",
+        SortMode::Distinct => "DISTINCT ",
+    };
+    let select_query = |from: &str| {
+        format!(
+            "SELECT {}{} FROM {}{} ORDER BY {}{} {}",
+            distinct, output_list, from, where_clause, order_clause, limit_clause, settings_clause
+        )
+    };
 
-    // Execute EXPLAIN to show the query plan
-    {
-        let explain_query = format!("EXPLAIN {}", select_query);
+    // Execute EXPLAIN to show the query plan; it plans without running the
+    // sort, so its time stands in for the planning ClickHouse doesn't log
+    let explain_start = Instant::now();
+    async {
+        let explain_query = format!("EXPLAIN {}", select_query(&sorts[0].0));
         println!("\n===== QUERY PLAN =====");
 
         let mut cursor = client.query(&explain_query).fetch::<String>()?;
@@ -121,29 +698,214 @@ async fn main() -> Result<(), Box<dyn Error>> {
             println!("{}", line);
         }
         println!("======================\n");
+        Ok::<(), clickhouse::error::Error>(())
     }
+    .instrument(info_span!(parent: &root, "explain"))
+    .await?;
+    let plan_time = explain_start.elapsed();
 
-    // Determine the mode
-    let (query, mode_description) = if let Some(output_path) = &args.output {
-        // Export mode: write sorted data to file
-        let path = output_path.display();
-        let query = format!("{} INTO OUTFILE '{}' FORMAT Native", select_query, path,);
-        (query, format!("writing to '{}' in Native format", path))
-    } else {
-        // Query mode: use FORMAT Null to execute without returning data
-        let query = format!("{} FORMAT Null", select_query);
-        (query, "query mode (no output)".to_string())
+    if args.read_in_order == Some(ReadInOrder::On) {
+        check_read_in_order(&client, &args, &tables, &select_query(&sorts[0].0)).await?;
+    }
+
+    // The table --output-table fills with sort i
+    let output_table = |i: usize| {
+        args.output_table.as_ref().map(|table| match sorts.len() {
+            1 => table.clone(),
+            _ => shards::table_name(table, i),
+        })
+    };
+    // The query that runs sort i in run `run`, and what it does
+    let sort_statement = |i: usize, run: usize| {
+        let (from, output) = &sorts[i];
+        let select_query = select_query(from);
+        if let Some(table) = output_table(i) {
+            let query = format!("INSERT INTO {} {}", table, select_query);
+            (query, format!("inserting into MergeTree table '{}'", table))
+        } else if let (Some(output_path), Some(format)) = (output, output_format) {
+            // Export mode: write sorted data to file; later runs replace it
+            let path = output_path.display();
+            let truncate = if run > 0 { " TRUNCATE" } else { "" };
+            let query = format!(
+                "{} INTO OUTFILE '{}'{} FORMAT {}",
+                select_query,
+                path,
+                truncate,
+                clickhouse_format(format)
+            );
+            (query, format!("writing to '{}' as {}", path, format.name()))
+        } else {
+            // Query mode: use FORMAT Null to execute without returning data
+            let query = format!("{} FORMAT Null", select_query);
+            (query, "query mode (no output)".to_string())
+        }
     };
 
-    println!("Running external sort ({})...", mode_description);
+    if args.explain_only {
+        println!("===== SQL =====");
+        for i in 0..sorts.len() {
+            let (query, mode_description) = sort_statement(i, 0);
+            println!("-- {}\n{};", mode_description, query);
+        }
+        println!("===============");
+        println!("Explain only: nothing was run");
+        return Ok(());
+    }
+
+    let watch_progress = args.metrics_port.is_some()
+        || args.progress_interval.is_some()
+        || args.throughput_log.is_some();
+    metrics::set_phase(Phase::Sort);
+    let sampler = watch_server_rss(client.clone());
+    // The query_log statistics are those of the last run
+    let mut times = Vec::with_capacity(args.runs.count);
+    let mut query_log: Option<QueryLogStats> = None;
+    let mut phases = PhaseTimes::default();
 
-    let start = Instant::now();
+    let mut budget = args.timeout.start();
+    for run in 0..args.runs.count {
+        if args.runs.cold {
+            drop_caches(&client).await;
+        }
+        if args.runs.count > 1 {
+            println!(
+                "Run {} of {} ({})",
+                run + 1,
+                args.runs.count,
+                args.runs.label(run)
+            );
+        }
+        query_log = None;
+        phases = PhaseTimes {
+            plan: Some(plan_time),
+            ..PhaseTimes::default()
+        };
+        let mut duration = Duration::ZERO;
+        for (i, (from, output)) in sorts.iter().enumerate() {
+            let output_table = output_table(i);
+            if let Some(ref table) = output_table {
+                // An empty table with the sorted columns, made before the clock starts
+                client
+                    .query(&format!("DROP TABLE IF EXISTS {}", table))
+                    .execute()
+                    .await?;
+                client
+                    .query(&format!(
+                        "CREATE TABLE {} ENGINE = MergeTree ORDER BY sort_key \
+                         AS SELECT {} FROM {} LIMIT 0",
+                        table, select_list, from
+                    ))
+                    .execute()
+                    .await?;
+            }
+            let (query, mode_description) = sort_statement(i, run);
 
-    // Execute the query (both modes use execute() now)
-    client.query(&query).execute().await?;
+            println!("Running external sort ({})...", mode_description);
 
-    let duration = start.elapsed();
-    println!("\nTIMING: {:.2} seconds", duration.as_secs_f64());
+            // A known query_id lets the progress poller find the sort in system.processes
+            let query_id = format!("es-duck-sort-{}-{}-{}", std::process::id(), run, i);
+            if watch_progress {
+                watch_sort_progress(client.clone(), query_id.clone());
+            }
+
+            // Sorting and writing the output file or table happen in the same query
+            let exec_span = match (output, &output_table) {
+                (_, Some(table)) => info_span!(parent: &root, "sort_insert", table = %table),
+                (Some(path), None) => {
+                    info_span!(parent: &root, "sort_export", output = %path.display())
+                }
+                (None, None) => info_span!(parent: &root, "sort_execution"),
+            };
+            let start = Instant::now();
+
+            // Execute the query (both modes use execute() now)
+            let sort = client
+                .clone()
+                .with_option("query_id", &query_id)
+                .query(&query)
+                .execute()
+                .instrument(exec_span);
+            let result = match budget.remaining() {
+                Some(remaining) => match tokio::time::timeout(remaining, sort).await {
+                    Ok(result) => result,
+                    Err(_) => {
+                        kill_query(&client, &query_id).await;
+                        eprintln!(
+                            "Sort killed after --timeout {} seconds",
+                            args.timeout.secs.unwrap_or_default()
+                        );
+                        println!("{}", args.timeout.report());
+                        std::process::exit(timeout::EXIT_CODE);
+                    }
+                },
+                None => sort.await,
+            };
+            result?;
+
+            let elapsed = start.elapsed();
+            budget.charge(elapsed);
+            if sorts.len() > 1 {
+                println!("Shard {}: {:.2}s", i, elapsed.as_secs_f64());
+            }
+            duration += elapsed;
+
+            if let Some(stats) = query_log_stats(&client, &query_id).await {
+                match query_log.as_mut() {
+                    Some(total) => total.merge(&stats),
+                    None => query_log = Some(stats),
+                }
+            }
+            if let Some(query) = query_phases(&client, &query_id).await {
+                phases.merge(query);
+            }
+        }
+        times.push(duration);
+    }
+
+    let peak_rss = sampler.finish();
+
+    if args.verify
+        && let Some(format) = output_format
+    {
+        let check = verify::Check {
+            // A String sorts by its bytes unless --collation says otherwise
+            check_order: verify::Check::checks_order(&args.order_by, args.collation.as_ref(), true),
+            mode: args.mode,
+            sorted_rows,
+            limit: args.limit,
+            ids: expected_ids,
+        };
+        println!("Verifying the output...");
+        let files: Vec<&PathBuf> = sorts
+            .iter()
+            .filter_map(|(_, output)| output.as_ref())
+            .collect();
+        let lines = check
+            .run(&files, format, |path, verifier| {
+                verify::verify_file_with(path, format, verifier)
+            })
+            .map_err(|e| e.to_string())?;
+        for line in lines {
+            println!("{}", line);
+        }
+    }
+    println!(
+        "\nTIMING: {:.2} seconds",
+        runs::median(&times).as_secs_f64()
+    );
+    if let Some(report) = args.runs.report(&times) {
+        println!("{}", report);
+    }
+    rss::report(peak_rss);
+    println!("{}", phases.report());
+    if let Some(stats) = query_log {
+        println!("{}", stats.report());
+    }
+    if let Some(ref predicate) = args.filter {
+        println!("WHERE: {}\nROWS_MATCHED: {}", predicate, sorted_rows);
+    }
+    metrics::set_rows_sorted(sorted_rows);
+    metrics::set_phase(Phase::Done);
 
     Ok(())
 }
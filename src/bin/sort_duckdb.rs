@@ -1,21 +1,67 @@
 use clap::Parser;
-use duckdb::Connection;
+use duckdb::{AccessMode, Config, Connection, params};
+use es_duck::affinity;
+use es_duck::cgroup;
+use es_duck::collation::Collation;
+use es_duck::csv_file::Encoding;
+use es_duck::duckdb_profile::{self, Profile};
+use es_duck::export::{self, KeyType, OutputFormat};
+use es_duck::file_scan::{self, ScanFormat};
+use es_duck::metrics::{self, Phase};
+use es_duck::phases::PhaseTimes;
+use es_duck::profile;
+use es_duck::progress;
+use es_duck::rss;
+use es_duck::runs::{self, Runs};
+use es_duck::schema::{self, ColumnNames, OrderBy, SortMode};
+use es_duck::shards::{self, ShardSort};
+use es_duck::sorter::{self, session};
+use es_duck::spill::{self, DirUsage, SpillWatcher};
+use es_duck::telemetry;
+use es_duck::timeout::{self, Timeout, Watchdog};
+use es_duck::verify::{self, RowIds, Verifier};
 use std::error::Error;
-use std::path::PathBuf;
-use std::time::Instant;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::info_span;
 
 #[derive(Parser)]
 #[command(name = "sort-duckdb")]
 #[command(about = "Run external sorting on a DuckDB database")]
 struct Args {
     /// Path to the DuckDB database file
-    #[arg(long)]
-    db: PathBuf,
+    #[arg(long, required_unless_present = "input_files")]
+    db: Option<PathBuf>,
 
     /// Table name to sort
     #[arg(long, default_value = "bench_data")]
     table: String,
 
+    /// Sort the Parquet or CSV files matching this glob (e.g. 'data/*.parquet')
+    /// in place, through read_parquet() or read_csv() in an in-memory database,
+    /// instead of a table of --db
+    #[arg(
+        long,
+        value_name = "GLOB",
+        conflicts_with_all = ["db", "shards", "output_table", "read_only"]
+    )]
+    input_files: Option<String>,
+
+    /// Format of --input-files. Defaults to what the glob's extension names.
+    #[arg(long, value_enum, requires = "input_files")]
+    input_format: Option<ScanFormat>,
+
+    /// How the fields of CSV --input-files are encoded
+    #[arg(long, value_enum, default_value_t = Encoding::Hex)]
+    csv_encoding: Encoding,
+
+    #[command(flatten)]
+    column_names: ColumnNames,
+
+    #[command(flatten)]
+    order_by: OrderBy,
+
     /// Temporary directory for DuckDB spilling (should be on fast SSD)
     #[arg(long)]
     temp_dir: Option<PathBuf>,
@@ -28,83 +74,639 @@ struct Args {
     #[arg(long)]
     threads: Option<usize>,
 
-    /// Output path for sorted data (parquet format). If not provided, runs analyze mode instead.
+    /// Output path for sorted data. If not provided, runs analyze mode instead.
     #[arg(long)]
     output: Option<PathBuf>,
+
+    /// Format of --output: Parquet and CSV come from DuckDB's COPY, the others
+    /// are written from the sorted rows as they arrive. Defaults to what the
+    /// file's extension names, else Parquet.
+    #[arg(long, value_enum, requires = "output")]
+    output_format: Option<OutputFormat>,
+
+    /// Materialize the sorted rows as this table in the same database (CREATE
+    /// TABLE ... AS SELECT ... ORDER BY), timing the sort without an export
+    /// format. An existing table of that name is replaced; a per-shard sort
+    /// creates <name>_shard<i>.
+    #[arg(long, conflicts_with_all = ["output", "read_only"])]
+    output_table: Option<String>,
+
+    /// Sort the tables <table>_shard0 .. <table>_shard<N-1> of a load with
+    /// --shards instead of the table itself
+    #[arg(long)]
+    shards: Option<usize>,
+
+    /// How to sort --shards: one ORDER BY over their UNION ALL, or one ORDER BY
+    /// per shard, each writing <output stem>.shard<i>.<ext>
+    #[arg(long, value_enum, default_value_t = ShardSort::UnionAll, requires = "shards")]
+    shard_sort: ShardSort,
+
+    /// Keep only the first K rows of the sorted order (ORDER BY ... LIMIT K), to
+    /// benchmark the engine's top-K path against a full sort. Applies to each
+    /// query, so a per-shard sort keeps K rows of every shard.
+    #[arg(long)]
+    limit: Option<u64>,
+
+    /// Sort only the rows matching this SQL predicate (e.g. "c_int < 100"), to
+    /// measure filter + sort pipelines. With --shards it may only use the
+    /// columns the shards' UNION ALL carries.
+    #[arg(long = "where", value_name = "PREDICATE")]
+    filter: Option<String>,
+
+    /// Select only the key, leaving the payload out of the sort, to separate
+    /// the cost of comparing keys from that of moving payload bytes. Output
+    /// files then hold just sort_key.
+    #[arg(long)]
+    keys_only: bool,
+
+    /// Sort every row, or keep each key once (SELECT DISTINCT, implies
+    /// --keys-only) to time dedup during the sort
+    #[arg(long, value_enum, default_value_t = SortMode::Sort)]
+    mode: SortMode,
+
+    /// Order a VARCHAR key by this collation instead of the column's own:
+    /// binary, or icu:<locale> (e.g. icu:de-DE) from the icu extension
+    #[arg(long, value_name = "binary|icu:<locale>")]
+    collation: Option<Collation>,
+
+    #[command(flatten)]
+    runs: Runs,
+
+    #[command(flatten)]
+    timeout: Timeout,
+
+    /// Read --output back after the last run, untimed, and fail unless its keys
+    /// are sorted and it holds every row the sort should have written
+    #[arg(long, requires = "output")]
+    verify: bool,
+
+    /// Print the plan, the settings and the SQL every sort would run, then exit
+    /// without running them (nor writing --output or --output-table)
+    #[arg(long)]
+    explain_only: bool,
+
+    /// Run SET key = value before the sort, after the memory, thread and temp
+    /// directory settings; e.g. --set preserve_insertion_order=false or --set
+    /// external_threads=4 (repeatable, value quoted as a string)
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = sorter::parse_setting)]
+    settings: Vec<(String, String)>,
+
+    /// Open the database read-only, so several sorts can run against it at once
+    #[arg(long)]
+    read_only: bool,
+
+    /// Hard memory cap enforced by a cgroup v2 (e.g., "4GB"). Requires root.
+    #[arg(long)]
+    cgroup_memory_limit: Option<String>,
+
+    /// Expose Prometheus metrics on this port while the sort runs
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Print a JSON progress event to stderr every this many seconds
+    #[arg(long)]
+    progress_interval: Option<u64>,
+
+    /// Write per-second throughput samples to this CSV file (render with plot-throughput)
+    #[arg(long)]
+    throughput_log: Option<PathBuf>,
+
+    /// Write a CPU flamegraph of this run to this SVG file (needs the util-profile feature)
+    #[arg(long)]
+    profile: Option<PathBuf>,
+
+    /// Pin threads to these CPUs (e.g. "0-15,32-47")
+    #[arg(long)]
+    cpus: Option<String>,
+
+    /// Run on the CPUs of this NUMA node and allocate memory from it
+    #[arg(long)]
+    numa_node: Option<usize>,
+}
+
+/// Opens the database, or an in-memory one for --input-files, and applies the
+/// memory, thread and temp directory settings, then --set
+fn open_database(args: &Args) -> Result<Connection, Box<dyn Error>> {
+    let conn = match args.db {
+        Some(ref db) if args.read_only => {
+            Connection::open_with_flags(db, Config::default().access_mode(AccessMode::ReadOnly)?)?
+        }
+        Some(ref db) => Connection::open(db)?,
+        None => Connection::open_in_memory()?,
+    };
+    if let Some(ref collation) = args.collation {
+        conn.execute_batch(collation.duckdb_setup())?;
+    }
+
+    if let Some(threads) = args.threads {
+        println!("Setting threads to {}", threads);
+    }
+    if let Some(ref temp_dir) = args.temp_dir {
+        println!("Setting temp_directory to {:?}", temp_dir);
+    }
+    println!("Setting memory_limit to {}", args.memory_limit);
+    for (key, value) in &args.settings {
+        println!("Setting {} = {}", key, value);
+    }
+    for statement in session::duckdb(
+        &args.memory_limit,
+        args.threads,
+        args.temp_dir.as_deref(),
+        &args.settings,
+    ) {
+        conn.execute(&statement, [])?;
+    }
+    Ok(conn)
+}
+
+/// Profiles the following queries as JSON, written to `path` after each one
+fn enable_profiling(conn: &Connection, path: &Path) -> duckdb::Result<()> {
+    conn.execute_batch(&format!(
+        "PRAGMA enable_profiling = 'json'; PRAGMA profiling_output = '{}'; \
+         PRAGMA custom_profiling_settings = '{}';",
+        path.display().to_string().replace('\'', "''"),
+        duckdb_profile::SETTINGS
+    ))
+}
+
+/// Every output format: the ones COPY can't write go through `export::Writer`
+const OUTPUT_FORMATS: &[OutputFormat] = &[
+    OutputFormat::Parquet,
+    OutputFormat::Csv,
+    OutputFormat::Native,
+    OutputFormat::Pgbinary,
+    OutputFormat::Kvbin,
+    OutputFormat::Gensort,
+];
+
+/// Operators that write the sorted rows out: COPY, in the batch variants that
+/// keep the order, and the insert of CREATE TABLE AS
+const WRITE_OPERATORS: &[&str] = &[
+    "COPY_TO_FILE",
+    "BATCH_COPY_TO_FILE",
+    "FIXED_BATCH_COPY_TO_FILE",
+    "INSERT",
+    "BATCH_INSERT",
+    "CREATE_TABLE_AS",
+];
+
+/// Runs one sort query: a COPY or CREATE TABLE AS, the SELECT whose rows go to
+/// `export`, or with `explain_lines` an EXPLAIN ANALYZE collecting its lines.
+/// Returns the time spent writing the rows that came back to `export`.
+fn execute_sort(
+    conn: &Connection,
+    query: &str,
+    export: Option<(&Path, OutputFormat)>,
+    output_columns: &[&str],
+    key_type: KeyType,
+    explain_lines: Option<&mut Vec<String>>,
+) -> Result<Option<Duration>, Box<dyn Error>> {
+    if let Some((path, format)) = export {
+        let mut writer =
+            export::Writer::create(path, format, output_columns).map_err(|e| e.to_string())?;
+        let mut stmt = conn.prepare(query)?;
+        let mut rows = stmt.query([])?;
+        let mut writing = Duration::ZERO;
+        while let Some(row) = rows.next()? {
+            // The row id comes as a BIGINT and an integer key as a UBIGINT,
+            // written as 8 big-endian bytes
+            let values = (0..output_columns.len())
+                .map(|i| match i {
+                    0 => key_bytes(row, key_type),
+                    2 => Ok(row.get::<_, i64>(i)?.to_be_bytes().to_vec()),
+                    _ => row.get::<_, Vec<u8>>(i),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let fields: Vec<&[u8]> = values.iter().map(Vec::as_slice).collect();
+            let write = Instant::now();
+            writer.write_row(&fields).map_err(|e| e.to_string())?;
+            writing += write.elapsed();
+        }
+        let write = Instant::now();
+        writer.finish().map_err(|e| e.to_string())?;
+        return Ok(Some(writing + write.elapsed()));
+    } else if let Some(explain_lines) = explain_lines {
+        // Analyze mode: execute EXPLAIN ANALYZE and collect results (don’t print during timing)
+        let mut stmt = conn.prepare(query)?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let line: String = row.get(1)?; // if 1-col output, use get(0)
+            explain_lines.push(line);
+        }
+    } else {
+        // COPY or table mode: execute the COPY or CREATE TABLE statement
+        conn.execute(query, [])?;
+    }
+    Ok(None)
+}
+
+/// The bytes of the key in the first column of `row`, a column of `key_type`
+fn key_bytes(row: &duckdb::Row, key_type: KeyType) -> duckdb::Result<Vec<u8>> {
+    match key_type {
+        KeyType::Bytes => row.get(0),
+        KeyType::Text => Ok(row.get::<_, String>(0)?.into_bytes()),
+        KeyType::Uint64 => Ok(row.get::<_, u64>(0)?.to_be_bytes().to_vec()),
+    }
+}
+
+/// Reads the keys (and with `row_ids` the row ids) of the Parquet output
+/// `path` back through DuckDB for --verify, from a connection of its own so
+/// --set can't change the order they come in
+fn verify_parquet(
+    path: &Path,
+    mut verifier: Verifier,
+    key_type: KeyType,
+    row_ids: bool,
+) -> Result<Verifier, Box<dyn Error + Send + Sync>> {
+    let conn = Connection::open_in_memory()?;
+    conn.execute_batch("SET preserve_insertion_order = true;")?;
+    let columns = match row_ids {
+        true => format!("sort_key, {}", schema::ROW_ID_COLUMN),
+        false => "sort_key".to_string(),
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM read_parquet('{}')",
+        columns,
+        path.display().to_string().replace('\'', "''")
+    ))?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let id = match row_ids {
+            true => Some(row.get::<_, i64>(1)? as u64),
+            false => None,
+        };
+        verifier
+            .add_row(&key_bytes(row, key_type)?, id)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(verifier)
+}
+
+/// Whether `table` has the `--with-row-id` column
+fn has_row_id(conn: &Connection, table: &str) -> duckdb::Result<bool> {
+    conn.query_row(
+        "SELECT count(*) > 0 FROM information_schema.columns \
+         WHERE table_name = ? AND column_name = ?",
+        params![table, schema::ROW_ID_COLUMN],
+        |row| row.get(0),
+    )
+}
+
+/// The type of `table`'s key `column`
+fn key_type(conn: &Connection, table: &str, column: &str) -> duckdb::Result<KeyType> {
+    let data_type: String = conn.query_row(
+        "SELECT coalesce(max(data_type), '') FROM information_schema.columns \
+         WHERE table_name = ? AND column_name = ?",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    Ok(KeyType::from_sql(&data_type))
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    let _telemetry = telemetry::init("sort-duckdb");
+    let _span = info_span!("sort", engine = "duckdb", table = %args.table).entered();
+    let _profile = profile::start(args.profile.as_deref()).map_err(|e| e.to_string())?;
+
+    if args.shards == Some(0) {
+        return Err("--shards must be at least 1".into());
+    }
+    args.order_by.check()?;
+    args.mode.check(&args.order_by)?;
+    if args.collation.is_some() && !args.order_by.columns.is_empty() {
+        return Err("--collation applies to the key: drop --order-by".into());
+    }
+    args.keys_only |= args.mode == SortMode::Distinct;
+    args.runs.check()?;
+    args.timeout.check()?;
+    let output_format = args
+        .output
+        .as_deref()
+        .map(|output| OutputFormat::resolve(args.output_format, output, OutputFormat::Parquet));
+    if let Some(format) = output_format {
+        format.check("sort-duckdb", OUTPUT_FORMATS, args.keys_only)?;
+    }
+    let input_format = match args.input_files {
+        Some(ref pattern) => {
+            let format = ScanFormat::resolve(args.input_format, pattern)?;
+            format.check("sort-duckdb", file_scan::DUCKDB_FORMATS)?;
+            Some(format)
+        }
+        None => None,
+    };
 
     // Check if database exists
-    if !args.db.exists() {
-        eprintln!("Error: Database file {:?} does not exist.", args.db);
+    if let Some(ref db) = args.db
+        && !db.exists()
+    {
+        eprintln!("Error: Database file {:?} does not exist.", db);
         std::process::exit(1);
     }
 
-    // Open the database
-    let conn = Connection::open(&args.db)?;
-
-    // Set threads if provided
-    if let Some(threads) = args.threads {
-        println!("Setting threads to {}", threads);
-        conn.execute(&format!("SET threads = {};", threads), [])?;
+    // Enter the cgroup before DuckDB allocates anything
+    if let Some(ref limit) = args.cgroup_memory_limit {
+        let bytes = cgroup::parse_memory_to_bytes(limit).map_err(|e| e.to_string())?;
+        let path = cgroup::enter_memory_limited("sort-duckdb", bytes).map_err(|e| e.to_string())?;
+        println!(
+            "Running in cgroup {:?} with memory.max = {} bytes",
+            path, bytes
+        );
     }
 
-    // Set temp directory if provided
-    if let Some(ref temp_dir) = args.temp_dir {
-        println!("Setting temp_directory to {:?}", temp_dir);
-        conn.execute(
-            &format!("SET temp_directory = '{}';", temp_dir.display()),
-            [],
-        )?;
+    // Before any threads are spawned, so they inherit the placement
+    affinity::apply(args.cpus.as_deref(), args.numa_node).map_err(|e| e.to_string())?;
+
+    if let Some(port) = args.metrics_port {
+        metrics::serve(port)?;
+        println!("Serving Prometheus metrics on port {}", port);
     }
+    if let Some(secs) = args.progress_interval {
+        progress::start(Duration::from_secs(secs));
+    }
+    if let Some(ref path) = args.throughput_log {
+        metrics::record_throughput(path)?;
+    }
+    metrics::set_phase(Phase::Setup);
 
-    // Set memory limit
-    println!("Setting memory_limit to {}", args.memory_limit);
-    conn.execute(&format!("SET memory_limit = '{}';", args.memory_limit), [])?;
+    let mut conn = open_database(&args)?;
+
+    // Quote table names as identifiers: "foo""bar"
+    let quote = |table: &str| format!("\"{}\"", table.replace('"', "\"\""));
+    // A --with-row-id table's ids go along with the rows, for --verify
+    let row_ids = !args.keys_only
+        && args.input_files.is_none()
+        && match args.shards {
+            Some(_) => has_row_id(&conn, &shards::table_name(&args.table, 0))?,
+            None => has_row_id(&conn, &args.table)?,
+        };
+    // What the sort reads, and the first table for the column types
+    let (source, first_table) = match (&args.input_files, input_format, args.shards) {
+        (Some(pattern), Some(format), _) => (
+            file_scan::duckdb_scan(pattern, format, args.csv_encoding, &args.column_names)?,
+            None,
+        ),
+        (_, _, Some(shards)) => (
+            shards::union_all(
+                &args.table,
+                shards,
+                &args.order_by.union_columns(&args.column_names, row_ids),
+            ),
+            Some(shards::table_name(&args.table, 0)),
+        ),
+        _ => (quote(&args.table), Some(args.table.clone())),
+    };
+    // An integer key goes out as its bytes; the files' keys are read as blobs
+    let key_type = match first_table {
+        Some(ref table) => key_type(&conn, table, &args.column_names.key)?,
+        None => KeyType::Bytes,
+    };
+    if let Some(format) = output_format {
+        key_type.check(format)?;
+    }
+    // The files --input-files matches, in DuckDB's reading of the glob
+    let input_files: Vec<PathBuf> = match args.input_files {
+        Some(ref pattern) => {
+            let files = conn
+                .prepare(&format!(
+                    "SELECT file FROM glob('{}') ORDER BY file",
+                    pattern.replace('\'', "''")
+                ))?
+                .query_map([], |row| row.get::<_, String>(0))?
+                .map(|file| file.map(PathBuf::from))
+                .collect::<Result<Vec<_>, _>>()?;
+            if files.is_empty() {
+                return Err(format!("--input-files '{}' matches no files", pattern).into());
+            }
+            files
+        }
+        None => Vec::new(),
+    };
 
     // Get table statistics
     println!("Gathering table statistics...");
-    let row_count: i64 =
-        conn.query_row(&format!("SELECT COUNT(*) FROM {}", args.table), [], |row| {
+    // --explain-only leaves the counts' scans out; the plan has the estimates
+    let row_count: i64 = match args.explain_only {
+        true => 0,
+        false => conn.query_row(&format!("SELECT COUNT(*) FROM {}", source), [], |row| {
             row.get(0)
-        })?;
+        })?,
+    };
+    let where_clause = args
+        .filter
+        .as_ref()
+        .map(|predicate| format!(" WHERE {}", predicate))
+        .unwrap_or_default();
+    // Rows the sort sees: all of them, or those matching --where
+    let sorted_rows: i64 = match args.filter {
+        Some(_) if !args.explain_only => conn.query_row(
+            &format!("SELECT COUNT(*) FROM {}{}", source, where_clause),
+            [],
+            |row| row.get(0),
+        )?,
+        _ => row_count,
+    };
+    // The ids of those rows, which a --verify of all of them must find again
+    let expected_ids = match args.verify && row_ids && args.limit.is_none() {
+        true => {
+            let sum: String =
+                conn.query_row(&RowIds::sum_query(&source, &where_clause), [], |row| {
+                    row.get(0)
+                })?;
+            Some(RowIds {
+                rows: sorted_rows as u64,
+                sum: sum.parse()?,
+            })
+        }
+        false => None,
+    };
 
-    // Get database file size directly from filesystem
-    let db_metadata = std::fs::metadata(&args.db)?;
-    let table_size_bytes = db_metadata.len();
+    // Get database file size directly from filesystem, or that of the input files
+    let table_size_bytes = match args.db {
+        Some(ref db) => std::fs::metadata(db)?.len(),
+        None => input_files
+            .iter()
+            .map(|file| Ok(std::fs::metadata(file)?.len()))
+            .sum::<Result<u64, std::io::Error>>()?,
+    };
+
+    // VARCHAR and BLOB keys take different sort paths in DuckDB
+    let columns = match first_table {
+        Some(ref table) => conn
+            .prepare(
+                "SELECT column_name || ' ' || data_type FROM information_schema.columns \
+                 WHERE table_name = ? ORDER BY ordinal_position",
+            )?
+            .query_map(params![table], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?,
+        // The types the scan gives the files' columns
+        None => conn
+            .prepare(&format!("DESCRIBE SELECT * FROM {}", source))?
+            .query_map([], |row| {
+                Ok(format!(
+                    "{} {}",
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?,
+    };
 
-    println!("Table: {}", args.table);
-    println!("Row count: {}", row_count);
+    match (&args.input_files, input_format) {
+        (Some(pattern), Some(format)) => println!(
+            "Input files: {} ({} files, {})",
+            pattern,
+            input_files.len(),
+            format.name()
+        ),
+        _ => println!("Table: {}", args.table),
+    }
+    if let Some(shards) = args.shards {
+        println!("Shards: {}", shards);
+    }
+    println!("Columns: {}", columns.join(", "));
+    match args.explain_only {
+        true => println!("Row count: not counted (--explain-only)"),
+        false => println!("Row count: {}", row_count),
+    }
+    // The key under --collation, if given
+    let order_clause = match args.collation {
+        Some(ref collation) => format!("{}{}", args.column_names.key, collation.duckdb()),
+        None => args.order_by.clause(&args.column_names),
+    };
+    println!("Order by: {}", order_clause);
+    match args.filter {
+        Some(ref predicate) if args.explain_only => println!("Where: {}", predicate),
+        Some(ref predicate) => println!("Where: {} ({} rows match)", predicate, sorted_rows),
+        None => {}
+    }
+    if let Some(limit) = args.limit {
+        println!("Limit: {} rows (top-K)", limit);
+    }
+    if args.keys_only {
+        println!("Keys only: the payload is left out");
+    }
+    if args.mode == SortMode::Distinct {
+        println!("Mode: distinct (each key once)");
+    }
+    if let Some(format) = output_format {
+        println!("Output format: {}", format.name());
+    }
     println!(
-        "Database size: {} bytes ({:.2} GB)",
+        "{} size: {} bytes ({:.2} GB)",
+        match args.db {
+            Some(_) => "Database",
+            None => "Input",
+        },
         table_size_bytes,
         table_size_bytes as f64 / 1_073_741_824.0
     );
-    // Quote table name as an identifier: "foo""bar"
-    let table = format!("\"{}\"", args.table.replace('"', "\"\""));
-    let select_query = format!("SELECT sort_key, payload FROM {} ORDER BY sort_key", table);
-
-    // Build the actual query that will be executed based on mode
-    let (query, mode_description) = if let Some(output_path) = &args.output {
-        let path = output_path.display().to_string().replace('\'', "''");
-        let copy_query = format!(
-            "COPY ({}) TO '{}' (FORMAT PARQUET, PRESERVE_ORDER true)",
-            select_query, path
-        );
-        (
-            copy_query,
-            format!("writing to '{}'", output_path.display()),
+
+    // The sorts to run, in order: a name, what each reads and the file it writes
+    let sorts: Vec<(String, String, Option<PathBuf>)> = match (args.shards, args.shard_sort) {
+        (Some(shards), ShardSort::PerShard) => (0..shards)
+            .map(|i| {
+                let name = shards::table_name(&args.table, i);
+                let output = args.output.as_deref().map(|o| shards::output_path(o, i));
+                (name.clone(), quote(&name), output)
+            })
+            .collect(),
+        (Some(shards), ShardSort::UnionAll) => vec![(
+            format!("the {} shards of {}", shards, args.table),
+            source,
+            args.output.clone(),
+        )],
+        (None, _) => {
+            let name = args.input_files.as_ref().unwrap_or(&args.table).clone();
+            vec![(name, source, args.output.clone())]
+        }
+    };
+    let limit_clause = args
+        .limit
+        .map(|k| format!(" LIMIT {}", k))
+        .unwrap_or_default();
+    // The key and payload, or just the key with --keys-only, then any row id;
+    // as hex text for CSV
+    let select_list = match (args.keys_only, output_format) {
+        (keys_only, Some(OutputFormat::Csv)) => {
+            let mut columns = vec![key_type.duckdb_hex(&args.column_names.key)];
+            if !keys_only {
+                columns.push(format!("lower(hex({}))", args.column_names.payload));
+            }
+            if row_ids {
+                columns.push(format!(
+                    "lpad(lower(hex({})), 16, '0')",
+                    schema::ROW_ID_COLUMN
+                ));
+            }
+            columns.join(", ")
+        }
+        (true, _) => args.column_names.select_key(),
+        (false, _) if row_ids => {
+            format!("{}, {}", args.column_names.select(), schema::ROW_ID_COLUMN)
+        }
+        (false, _) => args.column_names.select(),
+    };
+    // The columns `export::Writer` gets for the formats COPY can't write
+    let output_columns = export::columns(args.keys_only, row_ids);
+    let select_query = |from: &str| {
+        format!(
+            "SELECT {}{} FROM {}{} ORDER BY {}{}",
+            args.mode.select(&order_clause),
+            select_list,
+            from,
+            where_clause,
+            order_clause,
+            limit_clause
         )
-    } else {
-        let analyze_query = format!("EXPLAIN ANALYZE {}", select_query);
-        (analyze_query, format!("analyze mode on '{}'", args.table))
+    };
+
+    // The table --output-table materializes sort i into
+    let output_table = |i: usize| {
+        args.output_table.as_ref().map(|table| match sorts.len() {
+            1 => table.clone(),
+            _ => shards::table_name(table, i),
+        })
+    };
+    // The statement that runs sort i, and what it does
+    let sort_statement = |i: usize| {
+        let (name, from, output) = &sorts[i];
+        let select_query = select_query(from);
+        if let Some(table) = output_table(i) {
+            let create_query = format!("CREATE TABLE {} AS {}", quote(&table), select_query);
+            (create_query, format!("materializing table '{}'", table))
+        } else if let (Some(output_path), Some(format)) = (output, output_format) {
+            let path = output_path.display().to_string().replace('\'', "''");
+            let query = match format {
+                OutputFormat::Parquet => format!(
+                    "COPY ({}) TO '{}' (FORMAT PARQUET, PRESERVE_ORDER true)",
+                    select_query, path
+                ),
+                OutputFormat::Csv => format!(
+                    "COPY ({}) TO '{}' (FORMAT CSV, HEADER false, PRESERVE_ORDER true)",
+                    select_query, path
+                ),
+                // Read back and written by export::Writer
+                _ => select_query,
+            };
+            (
+                query,
+                format!(
+                    "writing to '{}' as {}",
+                    output_path.display(),
+                    format.name()
+                ),
+            )
+        } else {
+            let analyze_query = format!("EXPLAIN ANALYZE {}", select_query);
+            (analyze_query, format!("analyze mode on '{}'", name))
+        }
     };
 
     // Always print the sort-only plan (useful for both modes)
     {
-        let explain_sort = format!("EXPLAIN {}", select_query);
+        let _span = info_span!("explain").entered();
+        let explain_sort = format!("EXPLAIN {}", select_query(&sorts[0].1));
         let mut stmt = conn.prepare(&explain_sort)?;
         let mut rows = stmt.query([])?;
 
@@ -118,40 +720,254 @@ fn main() -> Result<(), Box<dyn Error>> {
         println!("=================================\n");
     }
 
-    // Execute the query
-    println!("Running external sort ({})...", mode_description);
+    if args.explain_only {
+        // What open_database set, as DuckDB took it
+        let mut names = vec!["memory_limit", "threads", "temp_directory"];
+        names.extend(args.settings.iter().map(|(key, _)| key.as_str()));
+        let names: Vec<String> = names
+            .iter()
+            .map(|name| format!("'{}'", name.replace('\'', "''")))
+            .collect();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT name, value FROM duckdb_settings() WHERE name IN ({}) ORDER BY name",
+            names.join(", ")
+        ))?;
+        let mut rows = stmt.query([])?;
+        println!("===== SETTINGS =====");
+        while let Some(row) = rows.next()? {
+            let (name, value): (String, Option<String>) = (row.get(0)?, row.get(1)?);
+            println!("{} = {}", name, value.unwrap_or_default());
+        }
+        println!("====================\n");
+
+        println!("===== SQL =====");
+        for i in 0..sorts.len() {
+            let (query, mode_description) = sort_statement(i);
+            println!("-- {}\n{};", mode_description, query);
+        }
+        println!("===============");
+        println!("Explain only: nothing was run");
+        return Ok(());
+    }
 
-    let start = Instant::now();
+    // DuckDB spills next to the database file unless a temp directory is given,
+    // and an in-memory database to .tmp
+    let spill_dir = match (&args.temp_dir, &args.db) {
+        (Some(dir), _) => dir.clone(),
+        (None, Some(db)) => PathBuf::from(format!("{}.tmp", db.display())),
+        (None, None) => PathBuf::from(".tmp"),
+    };
+    metrics::watch_spill_dir(&spill_dir);
 
-    if args.output.is_some() {
-        // Parquet mode: execute the COPY statement
-        conn.execute(&query, [])?;
-    } else {
-        // Analyze mode: execute EXPLAIN ANALYZE and collect results (don’t print during timing)
-        let mut stmt = conn.prepare(&query)?;
-        let mut rows = stmt.query([])?;
+    // Profile the sorts as JSON, into a file read back after each one
+    let profile_path =
+        std::env::temp_dir().join(format!("sort-duckdb-{}.profile.json", std::process::id()));
+    enable_profiling(&conn, &profile_path)?;
+    let spill_watcher = SpillWatcher::start(spill_dir.clone(), Duration::from_millis(100));
+    metrics::set_phase(Phase::Sort);
 
-        let mut explain_lines: Vec<String> = Vec::new();
-        while let Some(row) = rows.next()? {
-            let line: String = row.get(1)?; // if 1-col output, use get(0)
-            explain_lines.push(line);
+    // The statistics and EXPLAIN ANALYZE output are those of the last run
+    let mut times = Vec::with_capacity(args.runs.count);
+    let mut explain_lines: Vec<String> = Vec::new();
+    let mut profile: Option<Profile> = None;
+    let mut phases = PhaseTimes::default();
+    let mut budget = args.timeout.start();
+    for run in 0..args.runs.count {
+        if args.runs.cold {
+            // Closing the only connection frees the buffer pool; the file lock
+            // has to go before the database can be opened again
+            conn.close().map_err(|(_, e)| e)?;
+            conn = open_database(&args)?;
+            enable_profiling(&conn, &profile_path)?;
         }
+        if args.runs.count > 1 {
+            println!(
+                "Run {} of {} ({})",
+                run + 1,
+                args.runs.count,
+                args.runs.label(run)
+            );
+        }
+        explain_lines.clear();
+        profile = None;
+        phases = PhaseTimes::default();
+        let mut duration = Duration::ZERO;
+        for (i, (name, _, output)) in sorts.iter().enumerate() {
+            let explained = explain_lines.len();
+            let output_table = output_table(i);
+            if let Some(ref table) = output_table {
+                // Dropped before the clock starts, so only the CTAS is timed
+                conn.execute_batch(&format!("DROP TABLE IF EXISTS {};", quote(table)))?;
+            }
+            // Build the actual query that will be executed based on mode
+            let (query, mode_description) = sort_statement(i);
+
+            // Execute the query
+            println!("Running external sort ({})...", mode_description);
 
-        let duration = start.elapsed();
-        println!("TIMING: {:.2}", duration.as_secs_f64());
+            // Sorting and writing the output happen in the same COPY statement, or
+            // while the sorted rows are read back
+            let _exec_span = match (output, &output_table) {
+                (_, Some(table)) => info_span!("sort_materialize", table = %table).entered(),
+                (Some(path), None) => info_span!(
+                    "sort_export",
+                    output = %path.display(),
+                    format = output_format.map(OutputFormat::name)
+                )
+                .entered(),
+                (None, None) => info_span!("sort_execution").entered(),
+            };
+            // The formats COPY can't write come back as rows for export::Writer
+            let export = match (output, output_format) {
+                (Some(path), Some(format))
+                    if !matches!(format, OutputFormat::Parquet | OutputFormat::Csv) =>
+                {
+                    Some((path.as_path(), format))
+                }
+                _ => None,
+            };
+            let analyze = output.is_none() && output_table.is_none();
+            let interrupt = conn.interrupt_handle();
+            let watchdog = Watchdog::arm(budget, move || interrupt.interrupt());
+            let start = Instant::now();
 
-        // Print after timing to avoid stdout overhead in the measurement
+            let result = execute_sort(
+                &conn,
+                &query,
+                export,
+                output_columns,
+                key_type,
+                analyze.then_some(&mut explain_lines),
+            );
+            let elapsed = start.elapsed();
+            if watchdog.disarm() {
+                let _ = fs::remove_file(&profile_path);
+                eprintln!(
+                    "Sort of {} interrupted after --timeout {} seconds",
+                    name,
+                    args.timeout.secs.unwrap_or_default()
+                );
+                println!("{}", args.timeout.report());
+                std::process::exit(timeout::EXIT_CODE);
+            }
+            let written = result?;
+            budget.charge(elapsed);
+
+            if sorts.len() > 1 {
+                println!("Shard {}: {:.2}s", i, elapsed.as_secs_f64());
+            }
+            duration += elapsed;
+
+            // EXPLAIN ANALYZE may hand the JSON profile back instead of writing it
+            let json = fs::read_to_string(&profile_path)
+                .ok()
+                .or_else(|| Some(explain_lines[explained..].join("\n")))
+                .filter(|json| json.trim_start().starts_with('{'));
+            let _ = fs::remove_file(&profile_path);
+            let sort = match json.map(|json| duckdb_profile::parse(&json)) {
+                Some(Ok(sort)) => Some(sort),
+                Some(Err(e)) => {
+                    eprintln!("Warning: ignoring the profile of {}: {}", name, e);
+                    None
+                }
+                None => {
+                    eprintln!("Warning: DuckDB wrote no profile for {}", name);
+                    None
+                }
+            };
+            let seconds = |secs: f64| Duration::try_from_secs_f64(secs).ok();
+            let plan = sort.as_ref().and_then(|sort| seconds(sort.planning));
+            // Rows written here, or the write operators' share of the execution
+            let export = written.or_else(|| {
+                let share = sort.as_ref()?.cpu_share(WRITE_OPERATORS)?;
+                let execute = elapsed.saturating_sub(plan.unwrap_or_default());
+                seconds(execute.as_secs_f64() * share)
+            });
+            phases.merge(PhaseTimes::split(elapsed, plan, export));
+            if let Some(sort) = sort {
+                profile.get_or_insert_with(Profile::default).merge(sort);
+            }
+        }
+        times.push(duration);
+    }
+    conn.execute_batch("PRAGMA disable_profiling;")?;
+    let spilled = spill_watcher.finish();
+    // DuckDB may keep its temporary files around for the next query
+    let (files, bytes): (i64, i64) = conn.query_row(
+        "SELECT count(*), coalesce(sum(size), 0)::BIGINT FROM duckdb_temporary_files()",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let held = DirUsage {
+        bytes: bytes as u64,
+        files: files as u64,
+    };
+
+    if args.verify
+        && let Some(format) = output_format
+    {
+        let check = verify::Check {
+            // A VARCHAR key may have a collation of its own, which the
+            // catalog doesn't show
+            check_order: verify::Check::checks_order(
+                &args.order_by,
+                args.collation.as_ref(),
+                key_type != KeyType::Text,
+            ),
+            mode: args.mode,
+            sorted_rows: sorted_rows as u64,
+            limit: args.limit,
+            ids: expected_ids,
+        };
+        if key_type == KeyType::Text && args.collation.is_none() {
+            println!("A VARCHAR key may have a collation: --verify won't check the order");
+        }
+        println!("Verifying the output...");
+        let files: Vec<&PathBuf> = sorts
+            .iter()
+            .filter_map(|(_, _, output)| output.as_ref())
+            .collect();
+        let lines = check
+            .run(&files, format, |path, verifier| match format {
+                OutputFormat::Parquet => verify_parquet(path, verifier, key_type, row_ids),
+                _ => verify::verify_file_with(path, format, verifier),
+            })
+            .map_err(|e| e.to_string())?;
+        for line in lines {
+            println!("{}", line);
+        }
+    }
+
+    println!("TIMING: {:.2}", runs::median(&times).as_secs_f64());
+    if let Some(report) = args.runs.report(&times) {
+        println!("{}", report);
+    }
+    rss::report(rss::self_peak_rss());
+    println!("{}", phases.report());
+    if let Some(ref profile) = profile {
+        println!("{}", profile.report());
+    }
+    println!("{}", spill::report(spilled, held));
+    // A spill shorter than a sampling interval only shows in the profile
+    if spilled.bytes == 0 && profile.as_ref().is_none_or(|p| p.peak_temp_dir_size == 0) {
+        println!(
+            "No spill files seen in {:?}: the sort fit in memory_limit = {}",
+            spill_dir, args.memory_limit
+        );
+    }
+    if let Some(ref predicate) = args.filter {
+        println!("WHERE: {}\nROWS_MATCHED: {}", predicate, sorted_rows);
+    }
+    metrics::set_rows_sorted(sorted_rows as u64);
+    metrics::set_phase(Phase::Done);
+
+    // Print after timing to avoid stdout overhead in the measurement
+    if args.output.is_none() && args.output_table.is_none() {
         println!("\n===== EXPLAIN ANALYZE RESULTS =====");
         for line in explain_lines {
             println!("{}", line);
         }
         println!("====================================\n");
-
-        // early return so we don’t print TIMING twice
-        return Ok(());
     }
-
-    let duration = start.elapsed();
-    println!("TIMING: {:.2}", duration.as_secs_f64());
     Ok(())
 }
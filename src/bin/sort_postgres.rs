@@ -1,11 +1,34 @@
-use clap::Parser;
-use postgres::{Client, NoTls};
+use clap::{Parser, ValueEnum};
+use es_duck::cgroup;
+use es_duck::collation::Collation;
+use es_duck::csv_file::Encoding;
+use es_duck::export::{self, KeyType, OutputFormat};
+use es_duck::file_scan::{self, ScanFormat};
+use es_duck::key_ranges;
+use es_duck::metrics::{self, Phase};
+use es_duck::pg_plan::{self, PlanStats};
+use es_duck::pgcopy;
+use es_duck::phases::PhaseTimes;
+use es_duck::progress;
+use es_duck::rss::{self, PeakSampler};
+use es_duck::runs::{self, Runs};
+use es_duck::schema::{self, ColumnNames, OrderBy, SortMode};
+use es_duck::shards::{self, ShardSort};
+use es_duck::sorter::{self, session};
+use es_duck::telemetry;
+use es_duck::timeout::{self, Budget, Timeout, Watchdog};
+use es_duck::verify::{self, RowIds};
+use postgres::{Client, NoTls, SimpleQueryMessage};
 use std::error::Error;
-use std::path::PathBuf;
-use std::time::Instant;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::info_span;
 
 #[derive(Parser)]
 #[command(name = "sort-postgres")]
+#[command(about = "Run external sorting on a PostgreSQL table")]
 struct Args {
     #[arg(long)]
     db: String,
@@ -13,6 +36,30 @@ struct Args {
     #[arg(long, default_value = "bench_data")]
     table: String,
 
+    /// Sort the CSV file at this path on the server through a file_fdw foreign
+    /// table, made before the clock starts, instead of a table. A glob (e.g.
+    /// '/data/*.csv') is read through `cat` in the server's shell.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["shards", "cluster", "export_streams"]
+    )]
+    input_files: Option<String>,
+
+    /// Format of --input-files. Defaults to what the path's extension names.
+    #[arg(long, value_enum, requires = "input_files")]
+    input_format: Option<ScanFormat>,
+
+    /// How the fields of CSV --input-files are encoded
+    #[arg(long, value_enum, default_value_t = Encoding::Hex)]
+    csv_encoding: Encoding,
+
+    #[command(flatten)]
+    column_names: ColumnNames,
+
+    #[command(flatten)]
+    order_by: OrderBy,
+
     /// TOTAL memory budget for the entire sort (e.g., "2GB", "4GB")
     #[arg(long, default_value = "2GB")]
     total_memory: String,
@@ -21,41 +68,426 @@ struct Args {
     #[arg(long, default_value = "7")]
     parallel_workers: i32,
 
-    /// Output path for sorted data (binary format). If not provided, runs count mode instead.
+    /// Output path for sorted data. If not provided, runs count mode instead.
     #[arg(long)]
     output: Option<String>,
+
+    /// Format of --output: binary COPY and CSV are written by the server (or
+    /// with --client-side from a binary COPY stream), Native, kvbin and gensort
+    /// always client-side. Defaults to what the file's extension names, else
+    /// pgbinary.
+    #[arg(long, value_enum, requires = "output")]
+    output_format: Option<OutputFormat>,
+
+    /// Materialize the sorted rows as this UNLOGGED table (CREATE TABLE AS,
+    /// run under EXPLAIN ANALYZE) instead of writing a file, so no server file
+    /// access is needed. An existing table of that name is replaced; a
+    /// per-shard sort creates <name>_shard<i>.
+    #[arg(long, conflicts_with = "output")]
+    output_table: Option<String>,
+
+    /// Sort the table in place with CLUSTER, on an index over the ORDER BY
+    /// columns built before the clock starts. CLUSTER sorts in one process
+    /// within maintenance_work_mem, which gets the whole --total-memory.
+    #[arg(long, conflicts_with_all = ["output", "output_table", "filter", "limit", "keys_only"])]
+    cluster: bool,
+
+    /// How the rows get into order: an external sort, or building an index on
+    /// the ORDER BY columns and scanning it in order, both timed. The index
+    /// build gets the whole --total-memory as maintenance_work_mem and is
+    /// dropped after every run.
+    #[arg(long, value_enum, default_value_t = Strategy::Sort)]
+    strategy: Strategy,
+
+    /// Sort the tables <table>_shard0 .. <table>_shard<N-1> of a load with
+    /// --shards instead of the table itself
+    #[arg(long)]
+    shards: Option<usize>,
+
+    /// How to sort --shards: one ORDER BY over their UNION ALL, or one ORDER BY
+    /// per shard, each writing <output stem>.shard<i>.<ext>
+    #[arg(long, value_enum, default_value_t = ShardSort::UnionAll, requires = "shards")]
+    shard_sort: ShardSort,
+
+    /// Keep only the first K rows of the sorted order (ORDER BY ... LIMIT K), to
+    /// benchmark the engine's top-K path against a full sort. Applies to each
+    /// query, so a per-shard sort keeps K rows of every shard.
+    #[arg(long)]
+    limit: Option<u64>,
+
+    /// Sort only the rows matching this SQL predicate (e.g. "c_int < 100"), to
+    /// measure filter + sort pipelines. With --shards it may only use the
+    /// columns the shards' UNION ALL carries.
+    #[arg(long = "where", value_name = "PREDICATE")]
+    filter: Option<String>,
+
+    /// Select only the key, leaving the payload out of the sort, to separate
+    /// the cost of comparing keys from that of moving payload bytes. Output
+    /// files then hold just sort_key.
+    #[arg(long)]
+    keys_only: bool,
+
+    /// Sort every row, or keep each key once (SELECT DISTINCT, implies
+    /// --keys-only) to time dedup during the sort
+    #[arg(long, value_enum, default_value_t = SortMode::Sort, conflicts_with = "cluster")]
+    mode: SortMode,
+
+    /// Order a text key by this collation instead of the column's own: binary
+    /// ("C"), or icu:<locale> (e.g. icu:de-DE) for the server's ICU collations
+    #[arg(
+        long,
+        value_name = "binary|icu:<locale>",
+        conflicts_with = "export_streams"
+    )]
+    collation: Option<Collation>,
+
+    #[command(flatten)]
+    runs: Runs,
+
+    #[command(flatten)]
+    timeout: Timeout,
+
+    /// Read --output back after the last run, untimed, and fail unless its keys
+    /// are sorted and it holds every row the sort should have written. A file
+    /// the server wrote has to be readable here too.
+    #[arg(long, requires = "output")]
+    verify: bool,
+
+    /// Print the settings, the plan and the statement every sort would run,
+    /// then exit without running them. The rows are not counted either, which
+    /// takes a scan; the plan has the planner's estimate.
+    #[arg(long)]
+    explain_only: bool,
+
+    /// Stream the sorted rows to this machine (COPY TO STDOUT) and write --output
+    /// locally, instead of having the server write the file
+    #[arg(long)]
+    client_side: bool,
+
+    /// Export with this many COPY streams at once, each sorting one key range
+    /// into <output stem>.shard<i>.<ext>. The ranges split the keys at
+    /// quantiles sampled inside the timing; each stream gets its share of
+    /// --total-memory and --parallel-workers.
+    #[arg(
+        long,
+        requires = "output",
+        conflicts_with_all = ["output_table", "cluster", "shards", "limit"]
+    )]
+    export_streams: Option<usize>,
+
+    /// Run SET LOCAL name = value before the sort, after the sorter's own
+    /// settings, so it can override work_mem and the like too; e.g. --set
+    /// enable_incremental_sort=off or --set temp_tablespaces=fast
+    /// (repeatable, value quoted as a string)
+    #[arg(long = "set", value_name = "NAME=VALUE", value_parser = sorter::parse_setting)]
+    settings: Vec<(String, String)>,
+
+    /// Expose Prometheus metrics on this port while the sort runs
+    #[arg(long)]
+    metrics_port: Option<u16>,
+
+    /// Print a JSON progress event to stderr every this many seconds
+    #[arg(long)]
+    progress_interval: Option<u64>,
+
+    /// Write per-second throughput samples to this CSV file (render with plot-throughput)
+    #[arg(long)]
+    throughput_log: Option<PathBuf>,
+
+    /// Whether the sorted rows carry the table's `--with-row-id` column, found
+    /// out once connected
+    #[arg(skip)]
+    row_ids: bool,
+
+    /// The file_fdw table `--input-files` are read through, once created
+    #[arg(skip)]
+    foreign_table: Option<String>,
+
+    /// Type of the key column (`load-postgres --key-type`), found out once
+    /// connected
+    #[arg(skip)]
+    key_type: KeyType,
 }
 
-/// Parses strings like "2GB", "512MB" into a numeric byte value
-fn parse_memory_to_kb(mem_str: &str) -> Result<i64, Box<dyn Error>> {
-    let s = mem_str.to_uppercase();
-    if s.ends_with("GB") || s.ends_with("G") {
-        let val: f64 = s.trim_end_matches('G').trim_end_matches("GB").parse()?;
-        Ok((val * 1024.0 * 1024.0) as i64)
-    } else if s.ends_with("MB") || s.ends_with("M") {
-        let val: f64 = s.trim_end_matches('M').trim_end_matches("MB").parse()?;
-        Ok((val * 1024.0) as i64)
-    } else {
-        Err("Unsupported memory format. Use GB or MB (e.g., '2GB')".into())
+/// How sort-postgres orders the rows
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum Strategy {
+    /// ORDER BY, sorted by the executor
+    Sort,
+    /// CREATE INDEX on the ORDER BY columns, then an index-ordered scan
+    Index,
+}
+
+/// Polls the server's temp file usage for metrics and progress on a separate
+/// connection. pg_ls_tmpdir() needs superuser or pg_monitor; without it the
+/// spill metric simply stays at zero.
+fn watch_temp_files(db: String) {
+    thread::spawn(move || {
+        let mut client = match Client::connect(&db, NoTls) {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("Warning: spill metrics disabled: {}", e);
+                return;
+            }
+        };
+        loop {
+            match client.query_one(
+                "SELECT COALESCE(SUM(size), 0)::bigint FROM pg_ls_tmpdir()",
+                &[],
+            ) {
+                Ok(row) => metrics::set_spill_bytes(row.get::<_, i64>(0) as u64),
+                Err(e) => {
+                    eprintln!("Warning: spill metrics disabled: {}", e);
+                    return;
+                }
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+    });
+}
+
+/// Samples the combined private memory of the sorting backend and its parallel
+/// workers. Only works when the server runs on this host (or in a container
+/// sharing its PID namespace); otherwise the peak is reported as unavailable.
+fn watch_backend_rss(db: &str, backend_pid: i32) -> Option<PeakSampler> {
+    let mut client = match Client::connect(db, NoTls) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Warning: peak RSS measurement disabled: {}", e);
+            return None;
+        }
+    };
+    Some(PeakSampler::start(Duration::from_millis(100), move || {
+        let workers = client
+            .query(
+                "SELECT pid FROM pg_stat_activity WHERE leader_pid = $1 AND pid <> $1",
+                &[&backend_pid],
+            )
+            .ok()?;
+        let leader = rss::private_rss(backend_pid as u32, "postgres")?;
+        let workers: u64 = workers
+            .iter()
+            .filter_map(|row| rss::private_rss(row.get::<_, i32>(0) as u32, "postgres"))
+            .sum();
+        Some(leader + workers)
+    }))
+}
+
+/// Arms a watchdog that cancels the sorting backend's query with
+/// pg_cancel_backend, from a connection of its own, once it has used up what
+/// is left of `budget`
+fn cancel_on_timeout(db: &str, backend_pid: i32, budget: Budget) -> Watchdog {
+    let db = db.to_string();
+    Watchdog::arm(budget, move || {
+        let cancelled = Client::connect(&db, NoTls).and_then(|mut client| {
+            client.query_one("SELECT pg_cancel_backend($1)", &[&backend_pid])
+        });
+        if let Err(e) = cancelled {
+            eprintln!("Warning: could not cancel the sort: {}", e);
+        }
+    })
+}
+
+/// Reports a sort cancelled by `cancel_on_timeout` and exits. Exiting skips
+/// `ForeignTable`'s drop, so the foreign table goes here, on `client` once its
+/// transaction is rolled back: another connection would wait on its lock.
+fn exit_timed_out(args: &Args, client: &mut Client) -> ! {
+    eprintln!(
+        "Sort cancelled after --timeout {} seconds",
+        args.timeout.secs.unwrap_or_default()
+    );
+    if let Some(ref table) = args.foreign_table {
+        let dropped =
+            client.batch_execute(&format!("ROLLBACK; DROP FOREIGN TABLE IF EXISTS {}", table));
+        if let Err(e) = dropped {
+            eprintln!("Warning: could not drop the foreign table {}: {}", table, e);
+        }
+    }
+    println!("{}", args.timeout.report());
+    std::process::exit(timeout::EXIT_CODE)
+}
+
+/// Drops the `--input-files` foreign table when the sort is over, however it
+/// ends, on a connection of its own. Declared before the sort's client, so
+/// that client and the locks of its transaction are gone by then.
+struct ForeignTable {
+    db: String,
+    table: String,
+}
+
+impl Drop for ForeignTable {
+    fn drop(&mut self) {
+        let dropped = Client::connect(&self.db, NoTls).and_then(|mut client| {
+            client.batch_execute(&format!("DROP FOREIGN TABLE IF EXISTS {}", self.table))
+        });
+        if let Err(e) = dropped {
+            eprintln!(
+                "Warning: could not drop the foreign table {}: {}",
+                self.table, e
+            );
+        }
     }
 }
 
+/// Output formats; all but binary COPY and CSV are written client-side
+const OUTPUT_FORMATS: &[OutputFormat] = &[
+    OutputFormat::Pgbinary,
+    OutputFormat::Csv,
+    OutputFormat::Native,
+    OutputFormat::Kvbin,
+    OutputFormat::Gensort,
+];
+
+/// Streams the sorted rows through this process into a local file of `format`,
+/// counting them on the way so sort progress shows up in the metrics. Rows
+/// have the `export::columns` of the sort. Returns the rows and the time spent
+/// writing them.
+fn export_client_side(
+    client: &mut Client,
+    select_query: &str,
+    output: &Path,
+    format: OutputFormat,
+    columns: &[&str],
+) -> Result<(u64, Duration), Box<dyn Error>> {
+    let stream = client.copy_out(&format!(
+        "COPY ({}) TO STDOUT (FORMAT BINARY)",
+        select_query
+    ))?;
+    let mut reader = pgcopy::Reader::new(BufReader::with_capacity(8 * 1024 * 1024, stream))?;
+    let mut writer = export::Writer::create(output, format, columns).map_err(|e| e.to_string())?;
+
+    let mut fields = Vec::new();
+    let mut rows = 0u64;
+    let mut writing = Duration::ZERO;
+    while reader.read_row(&mut fields)? {
+        let row: Vec<&[u8]> = fields.iter().map(Vec::as_slice).collect();
+        let write = Instant::now();
+        writer.write_row(&row).map_err(|e| e.to_string())?;
+        writing += write.elapsed();
+        rows += 1;
+        if rows.is_multiple_of(100_000) {
+            metrics::set_rows_sorted(rows);
+        }
+    }
+    let write = Instant::now();
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok((rows, writing + write.elapsed()))
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    let _telemetry = telemetry::init("sort-postgres");
+    let _span = info_span!("sort", engine = "postgres", table = %args.table).entered();
+    if args.shards == Some(0) {
+        return Err("--shards must be at least 1".into());
+    }
+    args.order_by.check()?;
+    args.mode.check(&args.order_by)?;
+    if args.collation.is_some() && !args.order_by.columns.is_empty() {
+        return Err("--collation applies to the key: drop --order-by".into());
+    }
+    args.keys_only |= args.mode == SortMode::Distinct;
+    args.runs.check()?;
+    args.timeout.check()?;
+    // Resolved once, so the sorts below needn't look at the file name again
+    args.output_format = args.output.as_deref().map(|output| {
+        OutputFormat::resolve(
+            args.output_format,
+            Path::new(output),
+            OutputFormat::Pgbinary,
+        )
+    });
+    if let Some(format) = args.output_format {
+        format.check("sort-postgres", OUTPUT_FORMATS, args.keys_only)?;
+    }
+    if args.export_streams == Some(0) {
+        return Err("--export-streams must be at least 1".into());
+    }
+    if args.export_streams.is_some() && !args.order_by.columns.is_empty() {
+        return Err("--export-streams splits the key column's ranges: drop --order-by".into());
+    }
+    if let Some(ref pattern) = args.input_files {
+        ScanFormat::resolve(args.input_format, pattern)?
+            .check("sort-postgres", file_scan::POSTGRES_FORMATS)?;
+    }
+    if args.strategy == Strategy::Index {
+        if args.cluster || args.input_files.is_some() || args.export_streams.is_some() {
+            return Err(
+                "--strategy index scans a table's own index: drop --cluster, --input-files \
+                 and --export-streams"
+                    .into(),
+            );
+        }
+        if args.shards.is_some() && args.shard_sort == ShardSort::UnionAll {
+            return Err(
+                "--strategy index indexes tables, not their UNION ALL: use --shard-sort per-shard"
+                    .into(),
+            );
+        }
+    }
+    if args.cluster && args.shards.is_some() && args.shard_sort == ShardSort::UnionAll {
+        return Err(
+            "--cluster sorts tables, not their UNION ALL: use --shard-sort per-shard".into(),
+        );
+    }
+    if args.runs.cold {
+        // Nothing a client can run empties shared_buffers, so the runs stay warm
+        eprintln!(
+            "Warning: --cold cannot drop PostgreSQL's shared buffers from a client. \
+             For cold runs, restart the server (and drop the OS page cache) before \
+             each sort-postgres invocation; these runs keep the caches."
+        );
+        args.runs.cold = false;
+    }
+
+    if let Some(port) = args.metrics_port {
+        metrics::serve(port)?;
+        println!("Serving Prometheus metrics on port {}", port);
+    }
+    if let Some(secs) = args.progress_interval {
+        progress::start(Duration::from_secs(secs));
+    }
+    if let Some(ref path) = args.throughput_log {
+        metrics::record_throughput(path)?;
+    }
+    if args.metrics_port.is_some()
+        || args.progress_interval.is_some()
+        || args.throughput_log.is_some()
+    {
+        watch_temp_files(args.db.clone());
+    }
+    metrics::set_phase(Phase::Setup);
 
     // 1. CALCULATE WORK_MEM PER WORKER
     // NOTE: PostgreSQL parallel query uses N workers + 1 leader process
     // For example, --parallel-workers=40 creates 41 total processes (40 workers + 1 leader)
     // We divide total memory budget by N (the parallel_workers parameter) to get work_mem
     let total_procs = args.parallel_workers + 1;
-    let total_kb = parse_memory_to_kb(&args.total_memory)?;
-    let work_mem_kb = total_kb / args.parallel_workers as i64;
+    let total_kb =
+        cgroup::parse_memory_to_bytes(&args.total_memory).map_err(|e| e.to_string())? / 1024;
+    let work_mem_kb = total_kb / args.parallel_workers as u64;
     let work_mem_setting = format!("{}kB", work_mem_kb);
 
+    // Committed before the sort's read-only transaction, and dropped after it
+    args.foreign_table = args
+        .input_files
+        .as_ref()
+        .map(|_| format!("es_duck_input_files_{}", std::process::id()));
+    let _drop_foreign_table = args.foreign_table.clone().map(|table| ForeignTable {
+        db: args.db.clone(),
+        table,
+    });
     let mut client = Client::connect(&args.db, NoTls)?;
+    if let (Some(table), Some(pattern)) = (&args.foreign_table, &args.input_files) {
+        client.batch_execute(&file_scan::postgres_foreign_table(table, pattern))?;
+    }
+    let foreign_table = args.foreign_table.clone();
 
     client.batch_execute("BEGIN")?;
-    client.batch_execute("SET LOCAL transaction_read_only = on")?;
+    // Only the materializing modes, and the index build, write
+    if args.output_table.is_none() && !args.cluster && args.strategy == Strategy::Sort {
+        client.batch_execute("SET LOCAL transaction_read_only = on")?;
+    }
 
     // 2. APPLY CALCULATED SETTINGS
     println!(
@@ -64,120 +496,990 @@ fn main() -> Result<(), Box<dyn Error>> {
     );
     println!("Calculated work_mem per worker: {}", work_mem_setting);
 
-    client.batch_execute(&format!("SET LOCAL work_mem = '{}'", work_mem_setting))?;
-    client.batch_execute(&format!(
-        "SET LOCAL max_parallel_workers_per_gather = {}",
-        args.parallel_workers
-    ))?;
-
-    // Nudge Optimizer to ensure it actually uses the workers
-    client.batch_execute("SET LOCAL parallel_tuple_cost = 0")?;
-    client.batch_execute("SET LOCAL parallel_setup_cost = 0")?;
-    client.batch_execute("SET LOCAL min_parallel_table_scan_size = '0'")?;
-    client.batch_execute("SET LOCAL enable_parallel_append = on")?;
-    client.batch_execute("SET LOCAL temp_file_limit = -1")?;
+    if args.cluster {
+        println!("Setting maintenance_work_mem to {}kB for CLUSTER", total_kb);
+        client.batch_execute(&format!(
+            "SET LOCAL maintenance_work_mem = '{}kB'",
+            total_kb
+        ))?;
+    }
+    if args.strategy == Strategy::Index {
+        println!(
+            "Setting maintenance_work_mem to {}kB and max_parallel_maintenance_workers to {} \
+             for CREATE INDEX",
+            total_kb, args.parallel_workers
+        );
+        client.batch_execute(&format!(
+            "SET LOCAL maintenance_work_mem = '{}kB'",
+            total_kb
+        ))?;
+        client.batch_execute(&format!(
+            "SET LOCAL max_parallel_maintenance_workers = {}",
+            args.parallel_workers
+        ))?;
+        // The ordered scan has to come from the index, not a Sort node
+        client.batch_execute("SET LOCAL enable_sort = off")?;
+    }
+    for (name, value) in &args.settings {
+        println!("Setting {} = {}", name, value);
+    }
+    // --set comes last, so it can override any of the above
+    for statement in session::postgres(work_mem_kb, args.parallel_workers as usize, &args.settings)
+    {
+        client.batch_execute(&statement)?;
+    }
 
     // --- Gather and print table statistics ---
     println!("\nGathering table statistics...");
 
-    let row_count: i64 = client
-        .query_one(&format!("SELECT COUNT(*) FROM {}", args.table), &[])?
-        .get(0);
+    let tables = match args.shards {
+        Some(shards) => shards::table_names(&args.table, shards),
+        None => vec![args.table.clone()],
+    };
+    // A --with-row-id table's ids go along with the rows, for --verify
+    args.row_ids = !args.keys_only
+        && foreign_table.is_none()
+        && client
+            .query_one(
+                "SELECT EXISTS (SELECT 1 FROM pg_attribute WHERE attrelid = to_regclass($1) \
+                 AND attname = $2 AND NOT attisdropped)",
+                &[&tables[0], &schema::ROW_ID_COLUMN],
+            )?
+            .get::<_, bool>(0);
+    // A text key is hex-encoded and split into ranges as text, in its own
+    // order, and an integer one goes out as its bytes
+    if foreign_table.is_none() {
+        let key_type: String = client
+            .query_one(
+                "SELECT coalesce((SELECT atttypid::regtype::text FROM pg_attribute \
+                 WHERE attrelid = to_regclass($1) AND attname = $2 AND NOT attisdropped), '')",
+                &[&tables[0], &args.column_names.key],
+            )?
+            .get(0);
+        args.key_type = KeyType::from_sql(&key_type);
+    }
+    if let Some(format) = args.output_format {
+        args.key_type.check(format)?;
+    }
+    // A text key sorts in its column's collation, or else the database's,
+    // which --verify can check only if it is byte order: one that puts 'B'
+    // after 'a' isn't
+    let key_in_byte_order = match (args.verify, args.key_type) {
+        (true, KeyType::Text) => {
+            let collation: String = client
+                .query_one(
+                    "SELECT quote_ident(n.nspname) || '.' || quote_ident(c.collname) \
+                     FROM pg_attribute a JOIN pg_collation c ON c.oid = a.attcollation \
+                     JOIN pg_namespace n ON n.oid = c.collnamespace \
+                     WHERE a.attrelid = to_regclass($1) AND a.attname = $2",
+                    &[&tables[0], &args.column_names.key],
+                )?
+                .get(0);
+            client
+                .query_one(&format!("SELECT 'B' < ('a' COLLATE {})", collation), &[])?
+                .get(0)
+        }
+        _ => true,
+    };
+    if !key_in_byte_order && args.collation.is_none() {
+        println!("The key's collation is not byte order: --verify won't check the order");
+    }
+    // What the sort reads: the table, the UNION ALL of its shards, or the files
+    let source = match (&foreign_table, args.shards) {
+        (Some(table), _) => file_scan::postgres_scan(table, args.csv_encoding, &args.column_names),
+        (None, Some(shards)) => shards::union_all(
+            &args.table,
+            shards,
+            &args
+                .order_by
+                .union_columns(&args.column_names, args.row_ids),
+        ),
+        (None, None) => args.table.clone(),
+    };
 
-    let table_size: i64 = client
-        .query_one(
-            &format!("SELECT pg_total_relation_size('{}')", args.table),
-            &[],
-        )?
-        .get(0);
+    let row_count: i64 = match args.explain_only {
+        true => 0,
+        false => client
+            .query_one(&format!("SELECT COUNT(*) FROM {}", source), &[])?
+            .get(0),
+    };
+    // Rows the sort sees: all of them, or those matching --where
+    let sorted_rows: i64 = match args.filter {
+        Some(_) if !args.explain_only => client
+            .query_one(
+                &format!("SELECT COUNT(*) FROM {}{}", source, where_clause(&args)),
+                &[],
+            )?
+            .get(0),
+        _ => row_count,
+    };
+    // The ids of those rows, which a --verify of all of them must find again
+    let expected_ids = match args.verify && args.row_ids && args.limit.is_none() {
+        true => {
+            let sum: String = client
+                .query_one(&RowIds::sum_query(&source, &where_clause(&args)), &[])?
+                .get(0);
+            Some(RowIds {
+                rows: sorted_rows as u64,
+                sum: sum.parse()?,
+            })
+        }
+        false => None,
+    };
+
+    // The tables' size; a glob's files can't be listed, so theirs is unknown
+    let mut table_size = 0i64;
+    match args.input_files {
+        Some(ref pattern) if !file_scan::is_glob(pattern) => {
+            table_size = client
+                .query_one("SELECT (pg_stat_file($1)).size", &[pattern])?
+                .get(0);
+        }
+        Some(_) => {}
+        None => {
+            for table in &tables {
+                let size: i64 = client
+                    .query_one(&format!("SELECT pg_total_relation_size('{}')", table), &[])?
+                    .get(0);
+                table_size += size;
+            }
+        }
+    }
 
     let size_gb = table_size as f64 / (1024.0 * 1024.0 * 1024.0);
 
-    println!("Table: {}", args.table);
-    println!("Row count: {}", row_count);
-    println!("Size: {:.2} GB", size_gb);
+    match args.input_files {
+        Some(ref pattern) => println!("Input files: {} (file_fdw)", pattern),
+        None => println!("Table: {}", args.table),
+    }
+    if let Some(shards) = args.shards {
+        println!("Shards: {}", shards);
+    }
+    match args.explain_only {
+        true => println!("Row count: not counted (--explain-only)"),
+        false => println!("Row count: {}", row_count),
+    }
+    println!("Order by: {}", order_clause(&args));
+    match args.filter {
+        Some(ref predicate) if args.explain_only => println!("Where: {}", predicate),
+        Some(ref predicate) => println!("Where: {} ({} rows match)", predicate, sorted_rows),
+        None => {}
+    }
+    if let Some(limit) = args.limit {
+        println!("Limit: {} rows (top-K)", limit);
+    }
+    if args.keys_only {
+        println!("Keys only: the payload is left out");
+    }
+    if args.mode == SortMode::Distinct {
+        println!("Mode: distinct (each key once)");
+    }
+    if let Some(format) = args.output_format {
+        println!("Output format: {}", format.name());
+    }
+    match args.input_files {
+        Some(ref pattern) if file_scan::is_glob(pattern) => println!("Size: unknown"),
+        _ => println!("Size: {:.2} GB", size_gb),
+    }
     println!();
 
-    // Build the actual query based on mode
-    if let Some(ref output_path) = args.output {
-        // Binary output mode: Write sorted results to file
-        // Convert to absolute path (PostgreSQL requires absolute paths for COPY TO FILE)
-        let absolute_path = if PathBuf::from(output_path).is_absolute() {
-            output_path.to_string()
-        } else {
-            std::env::current_dir()?
-                .join(output_path)
-                .to_str()
-                .ok_or("Invalid path")?
-                .to_string()
-        };
+    let backend_pid: i32 = client.query_one("SELECT pg_backend_pid()", &[])?.get(0);
 
-        let select_query = format!(
-            "SELECT sort_key, payload FROM {} ORDER BY sort_key",
-            args.table
-        );
-        let query = format!(
-            "COPY ({}) TO '{}' (FORMAT BINARY)",
-            select_query, absolute_path
-        );
+    // The sorts to run, in order: what each reads and the file it writes
+    let sorts: Vec<(String, Option<String>)> = match (args.shards, args.shard_sort) {
+        (Some(_), ShardSort::PerShard) => tables
+            .iter()
+            .enumerate()
+            .map(|(i, table)| {
+                let output = args.output.as_ref().map(|o| {
+                    shards::output_path(Path::new(o), i)
+                        .to_string_lossy()
+                        .into_owned()
+                });
+                (table.clone(), output)
+            })
+            .collect(),
+        _ => vec![(source, args.output.clone())],
+    };
 
-        // --- Run EXPLAIN on the SELECT query (COPY cannot be EXPLAINed) ---
-        println!("\nRunning EXPLAIN on the SELECT query...");
-        let explain_query = format!("EXPLAIN (BUFFERS, VERBOSE) {}", select_query);
+    if args.explain_only {
+        explain_only(&args, &mut client, &sorts)?;
+        client.batch_execute("ROLLBACK")?;
+        println!("Explain only: nothing was run");
+        return Ok(());
+    }
 
-        let explain_rows = client.query(&explain_query, &[])?;
+    // The plan statistics are those of the last run
+    let mut budget = args.timeout.start();
+    let mut times = Vec::with_capacity(args.runs.count);
+    let mut peak_rss = None;
+    let mut plan_stats: Option<PlanStats> = None;
+    let mut phases = PhaseTimes::default();
+    for run in 0..args.runs.count {
+        if args.runs.count > 1 {
+            println!(
+                "Run {} of {} ({})",
+                run + 1,
+                args.runs.count,
+                args.runs.label(run)
+            );
+        }
+        plan_stats = None;
+        phases = PhaseTimes::default();
+        let mut duration = Duration::ZERO;
+        for (i, (from, output)) in sorts.iter().enumerate() {
+            let output_table = args.output_table.as_ref().map(|table| match sorts.len() {
+                1 => table.clone(),
+                _ => shards::table_name(table, i),
+            });
+            let index = match args.strategy {
+                Strategy::Index => {
+                    let (index, build) =
+                        build_index(&args, &mut client, backend_pid, budget, from)?;
+                    budget.charge(build.duration);
+                    Some((index, build))
+                }
+                Strategy::Sort => None,
+            };
+            let sort = match (output, output_table) {
+                _ if args.cluster => sort_cluster(&args, &mut client, backend_pid, budget, from)?,
+                (_, Some(table)) => {
+                    sort_analyze(&args, &mut client, backend_pid, budget, from, Some(&table))?
+                }
+                (Some(output_path), None) if args.export_streams.is_some() => sort_ranges(
+                    &args,
+                    &mut client,
+                    budget,
+                    from,
+                    row_count as u64,
+                    output_path,
+                )?,
+                (Some(output_path), None) => {
+                    sort_to_file(&args, &mut client, backend_pid, budget, from, output_path)?
+                }
+                (None, None) => sort_analyze(&args, &mut client, backend_pid, budget, from, None)?,
+            };
+            budget.charge(sort.duration);
+            let sort = match index {
+                Some((index, build)) => {
+                    // Dropped after the run, so the next one builds it again
+                    client.batch_execute(&format!("DROP INDEX {}", index))?;
+                    println!(
+                        "Index build: {:.2}s, ordered scan: {:.2}s",
+                        build.duration.as_secs_f64(),
+                        sort.duration.as_secs_f64()
+                    );
+                    build.then(sort)
+                }
+                None => sort,
+            };
+            if sorts.len() > 1 {
+                println!("Shard {}: {:.2}s", i, sort.duration.as_secs_f64());
+            }
+            duration += sort.duration;
+            phases.merge(sort.phases);
+            peak_rss = peak_rss.max(sort.peak_rss);
+            if let Some(plan) = sort.plan {
+                plan_stats
+                    .get_or_insert_with(PlanStats::default)
+                    .merge(plan);
+            }
+        }
+        times.push(duration);
+    }
+    client.batch_execute("COMMIT")?;
 
-        println!("\n===== QUERY PLAN =====");
-        for row in explain_rows {
-            let line: String = row.get(0);
+    if args.verify
+        && let Some(format) = args.output_format
+    {
+        let check = verify::Check {
+            check_order: verify::Check::checks_order(
+                &args.order_by,
+                args.collation.as_ref(),
+                key_in_byte_order,
+            ),
+            mode: args.mode,
+            sorted_rows: sorted_rows as u64,
+            limit: args.limit,
+            ids: expected_ids,
+        };
+        println!("Verifying the output...");
+        let files: Vec<String> = sorts
+            .iter()
+            .filter_map(|(_, output)| output.as_deref())
+            .flat_map(|output| output_files(&args, output))
+            .collect();
+        let lines = check
+            .run(&files, format, |path, verifier| {
+                verify::verify_file_with(path, format, verifier)
+            })
+            .map_err(|e| e.to_string())?;
+        for line in lines {
             println!("{}", line);
         }
-        println!("======================\n");
+    }
+    metrics::set_rows_sorted(sorted_rows as u64);
+    metrics::set_phase(Phase::Done);
 
-        // --- Final Execution ---
-        println!(
-            "\nRunning external sort (writing to '{}')...",
-            absolute_path
-        );
-        let start = Instant::now();
+    println!("TIMING: {:.2} seconds", runs::median(&times).as_secs_f64());
+    if let Some(report) = args.runs.report(&times) {
+        println!("{}", report);
+    }
+    rss::report(peak_rss);
+    println!("{}", phases.report());
+    if let Some(ref stats) = plan_stats {
+        println!("{}", stats.report());
+    }
+    if let Some(ref predicate) = args.filter {
+        println!("WHERE: {}\nROWS_MATCHED: {}", predicate, sorted_rows);
+    }
+    Ok(())
+}
 
-        client.batch_execute(&query)?;
-        client.batch_execute("COMMIT")?;
-        let duration = start.elapsed();
+/// What one sort measured
+struct SortRun {
+    duration: Duration,
+    phases: PhaseTimes,
+    /// The backend's peak memory, with its parallel workers
+    peak_rss: Option<u64>,
+    /// From EXPLAIN ANALYZE; a sort writing a file is not analyzed
+    plan: Option<PlanStats>,
+}
 
-        println!(
-            "\nExternal sorting completed and written to binary file in {:.2} seconds.",
-            duration.as_secs_f64()
-        );
-        println!("TIMING: {:.2} seconds", duration.as_secs_f64());
+impl SortRun {
+    /// This run followed by `next`, e.g. an index build and the scan of it
+    fn then(self, next: SortRun) -> SortRun {
+        let mut phases = self.phases;
+        phases.merge(next.phases);
+        SortRun {
+            duration: self.duration + next.duration,
+            phases,
+            peak_rss: self.peak_rss.max(next.peak_rss),
+            plan: next.plan,
+        }
+    }
+}
+
+/// ` WHERE <predicate>` for --where, or nothing
+fn where_clause(args: &Args) -> String {
+    args.filter
+        .as_ref()
+        .map(|predicate| format!(" WHERE {}", predicate))
+        .unwrap_or_default()
+}
+
+/// The ORDER BY columns: the key under --collation, if given
+fn order_clause(args: &Args) -> String {
+    match args.collation {
+        Some(ref collation) => format!("{}{}", args.column_names.key, collation.postgres()),
+        None => args.order_by.clause(&args.column_names),
+    }
+}
+
+/// Whether the server writes --output itself, rather than this process from
+/// a COPY TO STDOUT stream
+fn server_side(args: &Args) -> bool {
+    !args.client_side
+        && matches!(
+            args.output_format,
+            Some(OutputFormat::Pgbinary | OutputFormat::Csv)
+        )
+}
+
+/// The sort itself: the rows of `from` matching --where in key order, the
+/// first --limit of them, without the payload for --keys-only, each key once
+/// for --mode distinct
+fn select_query(args: &Args, from: &str) -> String {
+    // The key and payload, or just the key with --keys-only, then any row id;
+    // as hex text for a CSV file the server writes, and an integer key as its
+    // bytes in any file
+    let hex = args.output_format == Some(OutputFormat::Csv) && server_side(args);
+    let select_list = match (args.keys_only, args.output_format) {
+        (keys_only, Some(_)) if hex || args.key_type == KeyType::Uint64 => {
+            let encode = |column: String| match hex {
+                true => format!("encode({}, 'hex')", column),
+                false => column,
+            };
+            let mut columns = vec![encode(args.key_type.postgres_bytes(&args.column_names.key))];
+            if !keys_only {
+                columns.push(encode(args.column_names.payload.clone()));
+            }
+            if args.row_ids {
+                columns.push(match hex {
+                    true => encode(format!("int8send({})", schema::ROW_ID_COLUMN)),
+                    false => schema::ROW_ID_COLUMN.to_string(),
+                });
+            }
+            columns.join(", ")
+        }
+        (true, _) => args.column_names.select_key(),
+        (false, _) if args.row_ids => {
+            format!("{}, {}", args.column_names.select(), schema::ROW_ID_COLUMN)
+        }
+        (false, _) => args.column_names.select(),
+    };
+    let limit = args
+        .limit
+        .map(|k| format!(" LIMIT {}", k))
+        .unwrap_or_default();
+    format!(
+        "SELECT {}{} FROM {}{} ORDER BY {}{}",
+        args.mode.select(&order_clause(args)),
+        select_list,
+        from,
+        where_clause(args),
+        order_clause(args),
+        limit
+    )
+}
+
+/// The files a sort writing `output` makes: `output`, or with --export-streams
+/// one per key range
+fn output_files(args: &Args, output: &str) -> Vec<String> {
+    match args.export_streams {
+        Some(streams) => (0..streams)
+            .map(|i| {
+                shards::output_path(Path::new(output), i)
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect(),
+        None => vec![output.to_string()],
+    }
+}
+
+/// Where the COPY writes `output_path`: absolute for the server, which
+/// requires that, as given for a file written here
+fn copy_target(args: &Args, output_path: &str) -> Result<String, Box<dyn Error>> {
+    if !server_side(args) || PathBuf::from(output_path).is_absolute() {
+        return Ok(output_path.to_string());
+    }
+    Ok(std::env::current_dir()?
+        .join(output_path)
+        .to_str()
+        .ok_or("Invalid path")?
+        .to_string())
+}
+
+/// The server-side COPY of `select_query` to the file `target`
+fn copy_query(args: &Args, select_query: &str, target: &str) -> String {
+    let copy_format = match args.output_format {
+        Some(OutputFormat::Csv) => "CSV",
+        _ => "BINARY",
+    };
+    format!(
+        "COPY ({}) TO '{}' (FORMAT {})",
+        select_query, target, copy_format
+    )
+}
+
+/// Sorts the rows of `from` into the file `output_path`, in --output-format
+fn sort_to_file(
+    args: &Args,
+    client: &mut Client,
+    backend_pid: i32,
+    budget: Budget,
+    from: &str,
+    output_path: &str,
+) -> Result<SortRun, Box<dyn Error>> {
+    let format = args.output_format.unwrap_or(OutputFormat::Pgbinary);
+    let absolute_path = copy_target(args, output_path)?;
+    let select_query = select_query(args, from);
+    let query = copy_query(args, &select_query, &absolute_path);
+
+    // --- Run EXPLAIN on the SELECT query (COPY cannot be EXPLAINed) ---
+    // SUMMARY adds its Planning Time, which stands in for the COPY's
+    println!("\nRunning EXPLAIN on the SELECT query...");
+    let explain_query = format!("EXPLAIN (BUFFERS, VERBOSE, SUMMARY) {}", select_query);
+
+    let explain_rows = {
+        let _span = info_span!("explain").entered();
+        client.query(&explain_query, &[])?
+    };
+
+    let explained: Vec<String> = explain_rows.iter().map(|row| row.get(0)).collect();
+    let plan_time = pg_plan::planning_time(&explained.join("\n"));
+    println!("\n===== QUERY PLAN =====");
+    for line in &explained {
+        println!("{}", line);
+    }
+    println!("======================\n");
+
+    // --- Final Execution ---
+    println!(
+        "\nRunning external sort (writing to '{}' as {})...",
+        absolute_path,
+        format.name()
+    );
+    metrics::set_phase(Phase::Sort);
+    let exec_span =
+        info_span!("sort_export", output = %absolute_path, format = format.name()).entered();
+    let sampler = watch_backend_rss(&args.db, backend_pid);
+    let watchdog = cancel_on_timeout(&args.db, backend_pid, budget);
+    let start = Instant::now();
+
+    let rows_written = if !server_side(args) {
+        export_client_side(
+            client,
+            &select_query,
+            Path::new(&absolute_path),
+            format,
+            export::columns(args.keys_only, args.row_ids),
+        )
+        .map(Some)
     } else {
-        // Analyze mode: Run EXPLAIN ANALYZE to execute sort without writing
-        let query = format!(
-            "SELECT sort_key, payload FROM {} ORDER BY sort_key",
-            args.table
-        );
-        let explain_analyze_query = format!("EXPLAIN ANALYZE {}", query);
+        client
+            .batch_execute(&query)
+            .map(|_| None)
+            .map_err(Into::into)
+    };
+    let duration = start.elapsed();
+    if watchdog.disarm() {
+        exit_timed_out(args, client);
+    }
+    let (rows_written, written) = rows_written?.unzip();
+    let peak_rss = sampler.and_then(PeakSampler::finish);
+    exec_span.exit();
 
-        println!("\nRunning EXPLAIN ANALYZE (sort without writing)...");
-        let start = Instant::now();
+    println!(
+        "\nExternal sorting completed and written as {} in {:.2} seconds.",
+        format.name(),
+        duration.as_secs_f64()
+    );
+    if let Some(rows) = rows_written {
+        println!("Rows received: {}", rows);
+    }
+    Ok(SortRun {
+        duration,
+        // A server-side COPY writes as it sorts, so that is all execution
+        phases: PhaseTimes::split(duration, plan_time, written),
+        peak_rss,
+        plan: None,
+    })
+}
 
-        let explain_rows = client.query(&explain_analyze_query, &[])?;
-        client.batch_execute("COMMIT")?;
-        let duration = start.elapsed();
+/// Prints what the sorts `sorts` would run, for --explain-only: the settings
+/// the transaction holds, then every sort's plan and statement
+fn explain_only(
+    args: &Args,
+    client: &mut Client,
+    sorts: &[(String, Option<String>)],
+) -> Result<(), Box<dyn Error>> {
+    // SET LOCAL shows up as a session setting
+    println!("===== SETTINGS =====");
+    for row in client.query(
+        "SELECT name, setting, unit FROM pg_settings WHERE source = 'session' ORDER BY name",
+        &[],
+    )? {
+        let (name, setting, unit): (String, String, Option<String>) =
+            (row.get(0), row.get(1), row.get(2));
+        println!("{} = {}{}", name, setting, unit.unwrap_or_default());
+    }
+    println!("====================\n");
 
-        println!("\n===== EXPLAIN ANALYZE RESULTS =====");
-        for row in explain_rows {
-            let line: String = row.get(0);
-            println!("{}", line);
+    for (i, (from, output)) in sorts.iter().enumerate() {
+        let select_query = select_query(args, from);
+        let output_table = args.output_table.as_ref().map(|table| match sorts.len() {
+            1 => table.clone(),
+            _ => shards::table_name(table, i),
+        });
+        // CLUSTER runs no SELECT, so it has no plan to show either
+        let statement = match (output, output_table) {
+            _ if args.cluster => format!(
+                "-- CLUSTER {} on an index over ({}), built first",
+                from,
+                order_clause(args)
+            ),
+            (_, Some(table)) => format!("CREATE UNLOGGED TABLE {} AS {};", table, select_query),
+            (Some(output_path), None) if args.export_streams.is_some() => format!(
+                "-- one COPY per key range, sampled at run time, into {}, each of:\n{};",
+                output_files(args, output_path).join(", "),
+                select_query
+            ),
+            (Some(_), None) if !server_side(args) => {
+                format!("COPY ({}) TO STDOUT (FORMAT BINARY);", select_query)
+            }
+            (Some(output_path), None) => format!(
+                "{};",
+                copy_query(args, &select_query, &copy_target(args, output_path)?)
+            ),
+            (None, None) => format!("EXPLAIN (ANALYZE, BUFFERS, FORMAT JSON) {};", select_query),
+        };
+        let statement = match args.strategy {
+            Strategy::Index => format!(
+                "-- after CREATE INDEX on {} ({}), scanned in order\n{}",
+                from,
+                order_clause(args),
+                statement
+            ),
+            Strategy::Sort => statement,
+        };
+
+        if !args.cluster {
+            let plan: Vec<String> = client
+                .query(
+                    &format!("EXPLAIN (VERBOSE, SETTINGS) {}", select_query),
+                    &[],
+                )?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            println!("===== QUERY PLAN =====");
+            println!("{}", plan.join("\n"));
+            println!("======================\n");
         }
-        println!("====================================\n");
+        println!("===== SQL =====");
+        println!("{}", statement);
+        println!("===============\n");
+    }
+    Ok(())
+}
+
+/// Sorts the rows of `table` into --export-streams files at once, one key range
+/// each, after sampling the ranges' boundaries from its `rows` rows
+fn sort_ranges(
+    args: &Args,
+    client: &mut Client,
+    mut budget: Budget,
+    table: &str,
+    rows: u64,
+    output_path: &str,
+) -> Result<SortRun, Box<dyn Error>> {
+    let streams = args.export_streams.unwrap_or(1);
+    let key = &args.column_names.key;
+    let format = args.output_format.unwrap_or(OutputFormat::Pgbinary);
+
+    // The streams share the memory budget and the workers
+    let workers = args.parallel_workers / streams as i32;
+    let work_mem_kb = cgroup::parse_memory_to_bytes(&args.total_memory)
+        .map_err(|e| e.to_string())?
+        / 1024
+        / (streams as u64 * workers.max(1) as u64);
+    let settings = session::postgres(work_mem_kb, workers as usize, &args.settings);
+
+    println!(
+        "\nRunning external sort ({} streams writing '{}' as {}, each with work_mem {}kB \
+         and {} parallel workers)...",
+        streams,
+        output_files(args, output_path).join("', '"),
+        format.name(),
+        work_mem_kb,
+        workers
+    );
+    metrics::set_phase(Phase::Sort);
+    let exec_span =
+        info_span!("sort_export", output = %output_path, format = format.name(), streams).entered();
+    let start = Instant::now();
+
+    // The sample is this mode's planning, so it is timed too
+    let boundaries: Vec<String> = match streams {
+        1 => Vec::new(),
+        _ => {
+            let sample =
+                key_ranges::sample_query(table, key, args.filter.as_deref(), streams, rows);
+            client
+                .query_one(&sample, &[])?
+                .try_get::<_, Option<Vec<String>>>(0)?
+                .unwrap_or_default()
+        }
+    };
+    let sampled = start.elapsed();
+    budget.charge(sampled);
+    println!(
+        "Sampled {} range boundaries in {:.2}s",
+        boundaries.len(),
+        sampled.as_secs_f64()
+    );
+
+    let results: Vec<Result<Option<(u64, Duration)>, String>> = thread::scope(|scope| {
+        let handles: Vec<_> = output_files(args, output_path)
+            .into_iter()
+            .enumerate()
+            .map(|(i, output)| {
+                let from = format!(
+                    "(SELECT * FROM {} WHERE {}) AS range{}",
+                    table,
+                    key_ranges::range_predicate(key, &boundaries, i),
+                    i
+                );
+                let settings = &settings;
+                scope.spawn(move || {
+                    export_range(args, settings, budget, &from, &output).map_err(|e| e.to_string())
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err("the stream panicked".to_string()))
+            })
+            .collect()
+    });
+    let duration = start.elapsed();
+    exec_span.exit();
+    // The streams run at once on what is left of the budget, so it cancels
+    // all of them
+    if results.iter().any(|result| matches!(result, Ok(None))) {
+        exit_timed_out(args, client);
+    }
 
+    let mut total_rows = 0;
+    for (i, result) in results.into_iter().enumerate() {
+        let (rows, elapsed) = result
+            .map_err(|e| format!("Export stream {}: {}", i, e))?
+            .unwrap_or_default();
         println!(
-            "\nExternal sorting completed in {:.2} seconds.",
-            duration.as_secs_f64()
+            "Stream {}: {} rows in {:.2}s",
+            i,
+            rows,
+            elapsed.as_secs_f64()
         );
-        println!("TIMING: {:.2} seconds", duration.as_secs_f64());
+        total_rows += rows;
     }
+    println!(
+        "\nExternal sorting completed and written as {} by {} streams in {:.2} seconds.",
+        format.name(),
+        streams,
+        duration.as_secs_f64()
+    );
+    println!("Rows written: {}", total_rows);
+    Ok(SortRun {
+        duration,
+        phases: PhaseTimes::split(duration, Some(sampled), None),
+        peak_rss: None,
+        plan: None,
+    })
+}
 
-    Ok(())
+/// One stream of `sort_ranges`: sorts the rows of `from` into `output_path` on
+/// a connection of its own, returning the rows written and how long it took,
+/// or None when --timeout cancelled it
+fn export_range(
+    args: &Args,
+    settings: &[String],
+    budget: Budget,
+    from: &str,
+    output_path: &str,
+) -> Result<Option<(u64, Duration)>, Box<dyn Error>> {
+    let mut client = Client::connect(&args.db, NoTls)?;
+    client.batch_execute("BEGIN READ ONLY")?;
+    for statement in settings {
+        client.batch_execute(statement)?;
+    }
+    let backend_pid: i32 = client.query_one("SELECT pg_backend_pid()", &[])?.get(0);
+    let select_query = select_query(args, from);
+    let target = copy_target(args, output_path)?;
+
+    let watchdog = cancel_on_timeout(&args.db, backend_pid, budget);
+    let start = Instant::now();
+    let rows = if server_side(args) {
+        // COPY reports the rows it wrote
+        client
+            .execute(&copy_query(args, &select_query, &target), &[])
+            .map_err(Into::into)
+    } else {
+        export_client_side(
+            &mut client,
+            &select_query,
+            Path::new(&target),
+            args.output_format.unwrap_or(OutputFormat::Pgbinary),
+            export::columns(args.keys_only, args.row_ids),
+        )
+        .map(|(rows, _)| rows)
+    };
+    let elapsed = start.elapsed();
+    if watchdog.disarm() {
+        return Ok(None);
+    }
+    let rows = rows?;
+    client.batch_execute("COMMIT")?;
+    Ok(Some((rows, elapsed)))
+}
+
+/// Builds an index on the ORDER BY columns of `table` for --strategy index,
+/// returning its name and the build, which the scan of it then follows
+fn build_index(
+    args: &Args,
+    client: &mut Client,
+    backend_pid: i32,
+    budget: Budget,
+    table: &str,
+) -> Result<(String, SortRun), Box<dyn Error>> {
+    let index = format!("{}_es_duck_order_idx", table);
+    // Left over from an interrupted run
+    client.batch_execute(&format!("DROP INDEX IF EXISTS {}", index))?;
+
+    println!(
+        "\nBuilding index {} on {} ({})...",
+        index,
+        table,
+        order_clause(args)
+    );
+    metrics::set_phase(Phase::Sort);
+    let exec_span = info_span!("sort_index", table = %table).entered();
+    let sampler = watch_backend_rss(&args.db, backend_pid);
+    let watchdog = cancel_on_timeout(&args.db, backend_pid, budget);
+    let start = Instant::now();
+
+    let built = client.batch_execute(&format!(
+        "CREATE INDEX {} ON {} ({})",
+        index,
+        table,
+        order_clause(args)
+    ));
+    let duration = start.elapsed();
+    if watchdog.disarm() {
+        exit_timed_out(args, client);
+    }
+    built?;
+    let peak_rss = sampler.and_then(PeakSampler::finish);
+    exec_span.exit();
+
+    println!("\nIndex built in {:.2} seconds.", duration.as_secs_f64());
+    // The build sorts the keys, so it is all execution
+    Ok((
+        index,
+        SortRun {
+            duration,
+            phases: PhaseTimes::split(duration, None, None),
+            peak_rss,
+            plan: None,
+        },
+    ))
+}
+
+/// Sorts the rows of `from` with EXPLAIN ANALYZE, without writing them, or
+/// into the new unlogged table `into`
+fn sort_analyze(
+    args: &Args,
+    client: &mut Client,
+    backend_pid: i32,
+    budget: Budget,
+    from: &str,
+    into: Option<&str>,
+) -> Result<SortRun, Box<dyn Error>> {
+    // Analyze mode: Run EXPLAIN ANALYZE to execute sort without writing
+    let mut query = select_query(args, from);
+    if let Some(table) = into {
+        // Dropped before the clock starts, so only the CREATE TABLE AS is timed
+        client.batch_execute(&format!("DROP TABLE IF EXISTS {}", table))?;
+        query = format!("CREATE UNLOGGED TABLE {} AS {}", table, query);
+    }
+    let explain_analyze_query = format!("EXPLAIN (ANALYZE, BUFFERS, FORMAT JSON) {}", query);
+
+    match into {
+        Some(table) => println!("\nRunning EXPLAIN ANALYZE (sort into table {})...", table),
+        None => println!("\nRunning EXPLAIN ANALYZE (sort without writing)..."),
+    }
+    metrics::set_phase(Phase::Sort);
+    let exec_span = match into {
+        Some(table) => info_span!("sort_materialize", table = %table).entered(),
+        None => info_span!("sort_execution").entered(),
+    };
+    let sampler = watch_backend_rss(&args.db, backend_pid);
+    let watchdog = cancel_on_timeout(&args.db, backend_pid, budget);
+    let start = Instant::now();
+
+    // The plan is a json value, which the extended protocol won't hand over as
+    // a String; the simple one sends every value as text
+    let explain_rows = client.simple_query(&explain_analyze_query);
+    let duration = start.elapsed();
+    if watchdog.disarm() {
+        exit_timed_out(args, client);
+    }
+    let explain_rows = explain_rows?;
+    let peak_rss = sampler.and_then(PeakSampler::finish);
+    exec_span.exit();
+
+    // FORMAT JSON gives the whole plan in one row
+    let plan: String = explain_rows
+        .iter()
+        .filter_map(|message| match message {
+            SimpleQueryMessage::Row(row) => row.get(0),
+            _ => None,
+        })
+        .collect();
+    println!("\n===== EXPLAIN ANALYZE RESULTS =====");
+    println!("{}", plan);
+    println!("====================================\n");
+
+    let stats = match pg_plan::parse(&plan) {
+        Ok(stats) => Some(stats),
+        Err(e) => {
+            eprintln!("Warning: could not read the plan: {}", e);
+            None
+        }
+    };
+    if let Some(ref stats) = stats
+        && stats.workers_launched < args.parallel_workers.max(0) as u64
+    {
+        eprintln!(
+            "Warning: only {} of the {} requested parallel workers launched ({} planned); \
+             check max_worker_processes and max_parallel_workers",
+            stats.workers_launched, args.parallel_workers, stats.workers_planned
+        );
+    }
+
+    println!(
+        "\nExternal sorting completed in {:.2} seconds.",
+        duration.as_secs_f64()
+    );
+    // Nothing is written without a table; filling one is part of execution
+    let export = into.is_none().then_some(Duration::ZERO);
+    Ok(SortRun {
+        duration,
+        phases: PhaseTimes::split(duration, pg_plan::planning_time(&plan), export),
+        peak_rss,
+        plan: stats,
+    })
+}
+
+/// Sorts the table `table` in place with CLUSTER on an index over the ORDER BY
+/// columns. Building the index is a sort of its own, so it happens first,
+/// untimed, and is kept for later runs.
+fn sort_cluster(
+    args: &Args,
+    client: &mut Client,
+    backend_pid: i32,
+    budget: Budget,
+    table: &str,
+) -> Result<SortRun, Box<dyn Error>> {
+    let index = format!("{}_es_duck_sort_idx", table);
+    println!("\nIndexing {} on ({})...", table, order_clause(args));
+    client.batch_execute(&format!(
+        "CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+        index,
+        table,
+        order_clause(args)
+    ))?;
+
+    println!("Running CLUSTER {} USING {}...", table, index);
+    metrics::set_phase(Phase::Sort);
+    let exec_span = info_span!("sort_cluster", table = %table).entered();
+    let sampler = watch_backend_rss(&args.db, backend_pid);
+    let watchdog = cancel_on_timeout(&args.db, backend_pid, budget);
+    let start = Instant::now();
+
+    let clustered = client.batch_execute(&format!("CLUSTER {} USING {}", table, index));
+    let duration = start.elapsed();
+    if watchdog.disarm() {
+        exit_timed_out(args, client);
+    }
+    clustered?;
+    let peak_rss = sampler.and_then(PeakSampler::finish);
+    exec_span.exit();
+
+    println!(
+        "\nCLUSTER completed in {:.2} seconds.",
+        duration.as_secs_f64()
+    );
+    // CLUSTER has no plan to time, and rewrites the table as it sorts
+    Ok(SortRun {
+        duration,
+        phases: PhaseTimes::split(duration, None, None),
+        peak_rss,
+        plan: None,
+    })
 }
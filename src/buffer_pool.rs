@@ -0,0 +1,73 @@
+//! Recycled batch buffers.
+//!
+//! A loader's reader threads fill a batch buffer and send it over a channel,
+//! and the thread on the other end drops it once the batch is loaded, so
+//! every batch costs a large allocation and a free. A `BufferPool` shared by
+//! both ends keeps the emptied buffers instead: the receiver gives each one
+//! back, and the readers take their next buffer from the pool, so a load in
+//! its steady state allocates no batch buffers at all.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Emptied `Vec<T>` buffers waiting to be filled again
+#[derive(Debug)]
+pub struct BufferPool<T> {
+    free: Mutex<Vec<Vec<T>>>,
+    /// Buffers kept at most; more are freed, so a burst does not stay allocated
+    max_free: usize,
+    created: AtomicU64,
+    reused: AtomicU64,
+}
+
+/// How often `take` had to create a buffer, and how often it reused one
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub created: u64,
+    pub reused: u64,
+}
+
+impl<T> BufferPool<T> {
+    /// A pool keeping up to `max_free` buffers; as many as can be in flight
+    /// at once (e.g. channel capacity plus one per thread) is enough
+    pub fn new(max_free: usize) -> Arc<Self> {
+        Arc::new(Self {
+            free: Mutex::new(Vec::with_capacity(max_free)),
+            max_free,
+            created: AtomicU64::new(0),
+            reused: AtomicU64::new(0),
+        })
+    }
+
+    /// An empty buffer with room for at least `capacity` items, recycled if
+    /// the pool holds one
+    pub fn take(&self, capacity: usize) -> Vec<T> {
+        match self.free.lock().unwrap().pop() {
+            Some(mut buf) => {
+                self.reused.fetch_add(1, Ordering::Relaxed);
+                buf.reserve(capacity);
+                buf
+            }
+            None => {
+                self.created.fetch_add(1, Ordering::Relaxed);
+                Vec::with_capacity(capacity)
+            }
+        }
+    }
+
+    /// Empties `buf` and keeps it for a later `take`, unless the pool is full
+    pub fn give(&self, mut buf: Vec<T>) {
+        buf.clear();
+        let mut free = self.free.lock().unwrap();
+        if free.len() < self.max_free {
+            free.push(buf);
+        }
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            created: self.created.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Hard memory caps through cgroup v2.
+//!
+//! Engine settings like DuckDB's `memory_limit` are targets, not guarantees.
+//! Moving the process into its own cgroup with `memory.max` set makes the kernel
+//! enforce the budget: the process is OOM-killed instead of silently using more.
+//!
+//! Creating cgroups needs root (or a delegated subtree). Every process gets a
+//! leaf under `/sys/fs/cgroup/es-duck/`; leaves left over from earlier runs are
+//! removed on the next call once they are empty.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const PARENT: &str = "es-duck";
+
+/// Parses strings like "4GB", "512MB", "64KB" or a plain byte count into bytes
+pub fn parse_memory_to_bytes(mem_str: &str) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let s = mem_str.to_uppercase();
+    if let Ok(bytes) = s.parse::<u64>() {
+        Ok(bytes)
+    } else if s.ends_with("GB") || s.ends_with("G") {
+        let val: f64 = s.trim_end_matches("GB").trim_end_matches('G').parse()?;
+        Ok((val * 1024.0 * 1024.0 * 1024.0) as u64)
+    } else if s.ends_with("MB") || s.ends_with("M") {
+        let val: f64 = s.trim_end_matches("MB").trim_end_matches('M').parse()?;
+        Ok((val * 1024.0 * 1024.0) as u64)
+    } else if s.ends_with("KB") || s.ends_with("K") {
+        let val: f64 = s.trim_end_matches("KB").trim_end_matches('K').parse()?;
+        Ok((val * 1024.0) as u64)
+    } else {
+        Err(
+            "Unsupported memory format. Use GB, MB, KB or bytes (e.g., '4GB', '512MB', '64KB', '4096')"
+                .into(),
+        )
+    }
+}
+
+/// Moves the current process (all of its threads, and any children it spawns
+/// later) into a new cgroup capped at `limit_bytes` with swap disabled.
+/// Returns the path of the new cgroup.
+pub fn enter_memory_limited(
+    name: &str,
+    limit_bytes: u64,
+) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    let root = Path::new(CGROUP_ROOT);
+    if !root.join("cgroup.controllers").exists() {
+        return Err(format!("cgroup v2 is not mounted at {}", CGROUP_ROOT).into());
+    }
+
+    let parent = root.join(PARENT);
+    enable_memory_controller(root)?;
+    fs::create_dir_all(&parent)
+        .map_err(|e| format!("Failed to create {:?} (are you root?): {}", parent, e))?;
+    enable_memory_controller(&parent)?;
+    remove_empty_leaves(&parent);
+
+    let leaf = parent.join(format!("{}-{}", name, std::process::id()));
+    fs::create_dir_all(&leaf).map_err(|e| format!("Failed to create {:?}: {}", leaf, e))?;
+    write_control(&leaf, "memory.max", &limit_bytes.to_string())?;
+    // Without this the kernel swaps instead of enforcing the cap; absent when swap accounting is off
+    let _ = write_control(&leaf, "memory.swap.max", "0");
+    // Writing 0 moves the writing process
+    write_control(&leaf, "cgroup.procs", "0")?;
+
+    Ok(leaf)
+}
+
+fn enable_memory_controller(dir: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let enabled = fs::read_to_string(dir.join("cgroup.subtree_control")).unwrap_or_default();
+    if enabled.split_whitespace().any(|c| c == "memory") {
+        return Ok(());
+    }
+    write_control(dir, "cgroup.subtree_control", "+memory")
+}
+
+fn write_control(dir: &Path, file: &str, value: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let path = dir.join(file);
+    fs::write(&path, value)
+        .map_err(|e| format!("Failed to write '{}' to {:?}: {}", value, path, e).into())
+}
+
+/// rmdir only succeeds on cgroups without processes, so live runs are left alone
+fn remove_empty_leaves(parent: &Path) {
+    if let Ok(entries) = fs::read_dir(parent) {
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                let _ = fs::remove_dir(entry.path());
+            }
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! ClickHouse's `system.query_log` entry of a finished sort, boiled down to
+//! what a sort run reports.
+//!
+//! The sorter runs each ORDER BY under a known `query_id`, flushes the logs
+//! once it is done and reads the `QueryFinish` row back with `QUERY`. The
+//! spill counters are the `ExternalSort*` profile events, which stay zero
+//! when the sort fit under `max_bytes_before_external_sort`.
+//!
+//! `PHASES_QUERY` reads the same query's duration, and how long its output
+//! format or table sink ran from `system.processors_profile_log`, which the
+//! sort logs with `log_processors_profiles = 1`.
+
+/// Selects the fields of `QueryLogStats`, in order, for the query_id bound to `?`
+pub const QUERY: &str = "SELECT memory_usage, read_rows, read_bytes, \
+     ProfileEvents['ExternalSortWritePart'], \
+     ProfileEvents['ExternalSortCompressedBytes'], \
+     ProfileEvents['ExternalSortUncompressedBytes'] \
+     FROM system.query_log \
+     WHERE query_id = ? AND type = 'QueryFinish' \
+     ORDER BY event_time_microseconds DESC LIMIT 1";
+
+/// Selects the duration in microseconds and the microseconds spent writing the
+/// output, for the query_id bound to both `?`
+pub const PHASES_QUERY: &str = "SELECT \
+     toUInt64(ifNull((SELECT query_duration_ms FROM system.query_log \
+         WHERE query_id = ? AND type = 'QueryFinish' \
+         ORDER BY event_time_microseconds DESC LIMIT 1), 0) * 1000), \
+     toUInt64(ifNull((SELECT sum(elapsed_us) FROM system.processors_profile_log \
+         WHERE query_id = ? AND (name LIKE '%OutputFormat' OR name LIKE '%Sink')), 0))";
+
+/// The metrics of one or more finished sort queries
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryLogStats {
+    /// Peak memory of the largest query in bytes
+    pub memory_usage: u64,
+    pub read_rows: u64,
+    pub read_bytes: u64,
+    /// Sorted blocks written to temporary files
+    pub spill_parts: u64,
+    pub spill_compressed_bytes: u64,
+    pub spill_uncompressed_bytes: u64,
+}
+
+impl QueryLogStats {
+    /// The stats of one row of `QUERY`
+    pub fn from_row(row: (u64, u64, u64, u64, u64, u64)) -> Self {
+        let (memory_usage, read_rows, read_bytes, parts, compressed, uncompressed) = row;
+        Self {
+            memory_usage,
+            read_rows,
+            read_bytes,
+            spill_parts: parts,
+            spill_compressed_bytes: compressed,
+            spill_uncompressed_bytes: uncompressed,
+        }
+    }
+
+    /// Adds the stats of another query, e.g. the next shard of a per-shard sort.
+    /// Memory is the larger peak, since the queries run one after another.
+    pub fn merge(&mut self, other: &QueryLogStats) {
+        self.memory_usage = self.memory_usage.max(other.memory_usage);
+        self.read_rows += other.read_rows;
+        self.read_bytes += other.read_bytes;
+        self.spill_parts += other.spill_parts;
+        self.spill_compressed_bytes += other.spill_compressed_bytes;
+        self.spill_uncompressed_bytes += other.spill_uncompressed_bytes;
+    }
+
+    /// `KEY: value` lines that go next to TIMING
+    pub fn report(&self) -> String {
+        let mb = |bytes: u64| bytes as f64 / 1_048_576.0;
+        format!(
+            "QUERY_PEAK_MEMORY_MB: {:.1}\nQUERY_READ_ROWS: {}\nQUERY_READ_MB: {:.1}\n\
+             SPILL_PARTS: {}\nSPILL_COMPRESSED_MB: {:.1}\nSPILL_UNCOMPRESSED_MB: {:.1}",
+            mb(self.memory_usage),
+            self.read_rows,
+            mb(self.read_bytes),
+            self.spill_parts,
+            mb(self.spill_compressed_bytes),
+            mb(self.spill_uncompressed_bytes)
+        )
+    }
+}
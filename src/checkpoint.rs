@@ -0,0 +1,212 @@
+//! Checkpoint files for resumable loads.
+//!
+//! A checkpointed load splits its input into fixed units up front (record
+//! ranges for gensort, byte ranges between record starts for kvbin), so a
+//! restarted load sees the same units and can skip the finished ones. The file
+//! starts with a line describing the load (input, size, format, table, unit
+//! size); a checkpoint written for a different load is refused. Each later
+//! line marks a unit:
+//!
+//! ```text
+//! staged <start> <end>   the unit's rows are committed to its staging table
+//! done <start> <end>     the unit's rows are in the target table
+//! ```
+//!
+//! Lines are appended and synced one at a time, so a crash loses at most the
+//! line being written; a torn last line is ignored. A crash can still fall
+//! between committing a unit and marking it, so the loaders make that step
+//! safe to repeat: DuckDB and PostgreSQL move a staged unit into the table and
+//! drop its staging table in one transaction (a staged unit whose staging
+//! table is gone is done), and ClickHouse inserts each unit with a
+//! deduplication token derived from the unit.
+
+use crate::kvbin_index::{self, Spacing};
+use crate::records::{FixedLayout, InputFormat, Window};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Splits bytes `range` of `input` (whole records, see `records::select`)
+/// into load units of about `unit_bytes` each: ranges of record numbers for
+/// gensort records of `layout`, and for kvbin byte ranges starting at the
+/// first record at or after every `unit_bytes` from the start of `range`
+pub fn units(
+    format: InputFormat,
+    layout: FixedLayout,
+    input: &Path,
+    range: Range<u64>,
+    unit_bytes: u64,
+) -> io::Result<Vec<Range<u64>>> {
+    let unit = unit_bytes.max(1);
+    let starts = match format {
+        InputFormat::Gensort => {
+            let record_size = layout.record_size() as u64;
+            let records = range.start / record_size..range.end / record_size;
+            let per_unit = (unit / record_size).max(1);
+            let mut starts: Vec<u64> = records.clone().step_by(per_unit as usize).collect();
+            starts.push(records.end);
+            starts
+        }
+        InputFormat::Kvbin => {
+            let window = Window::open(input, range.clone())?;
+            let index = kvbin_index::scan(BufReader::new(window), Spacing::Bytes(unit))?;
+            let mut starts = vec![range.start];
+            starts.extend(index.offsets.iter().map(|offset| range.start + offset));
+            starts.push(range.end);
+            starts.dedup();
+            starts
+        }
+    };
+    Ok(starts.windows(2).map(|w| w[0]..w[1]).collect())
+}
+
+/// The header line of a checkpoint for loading bytes `range` of `input` into
+/// `table`. Gensort records of another layout than the standard one name it,
+/// since their units are record numbers.
+pub fn describe(
+    format: InputFormat,
+    layout: FixedLayout,
+    input: &Path,
+    range: &Range<u64>,
+    table: &str,
+    unit_bytes: u64,
+) -> io::Result<String> {
+    let format = match format {
+        InputFormat::Gensort if layout == FixedLayout::GENSORT => "gensort".to_string(),
+        InputFormat::Gensort => format!("gensort/{}+{}", layout.key_size, layout.payload_size),
+        InputFormat::Kvbin => "kvbin".to_string(),
+    };
+    Ok(format!(
+        "input={} size={} bytes={}..{} format={} table={} unit={}",
+        input.canonicalize()?.display(),
+        fs::metadata(input)?.len(),
+        range.start,
+        range.end,
+        format,
+        table,
+        unit_bytes
+    ))
+}
+
+/// The state of a checkpointed load, shared by its threads
+pub struct Checkpoint {
+    path: PathBuf,
+    file: Mutex<File>,
+    staged: HashSet<(u64, u64)>,
+    done: HashSet<(u64, u64)>,
+}
+
+impl Checkpoint {
+    /// Opens the checkpoint at `path`, or starts one there. `description`
+    /// identifies the load and must not contain a newline.
+    pub fn open(path: &Path, description: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut staged = HashSet::new();
+        let mut done = HashSet::new();
+
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                let mut lines = text.split_inclusive('\n');
+                let header = lines.next().unwrap_or("").trim_end_matches('\n');
+                if header != description {
+                    return Err(format!(
+                        "Checkpoint {:?} is for a different load:\n  it has:    {}\n  this load: {}",
+                        path, header, description
+                    )
+                    .into());
+                }
+                for line in lines {
+                    match parse_line(line) {
+                        Some(("staged", unit)) => {
+                            staged.insert(unit);
+                        }
+                        Some(("done", unit)) => {
+                            done.insert(unit);
+                        }
+                        // The write of the last line was cut short
+                        _ if !line.ends_with('\n') => break,
+                        _ => {
+                            return Err(format!(
+                                "Invalid line in checkpoint {:?}: {:?}",
+                                path, line
+                            )
+                            .into());
+                        }
+                    }
+                }
+                // Later lines start on a line of their own
+                if !text.is_empty() && !text.ends_with('\n') {
+                    let keep = text.rfind('\n').map_or(0, |i| i + 1);
+                    OpenOptions::new()
+                        .write(true)
+                        .open(path)?
+                        .set_len(keep as u64)?;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let mut file = File::create(path)?;
+                writeln!(file, "{}", description)?;
+                file.sync_all()?;
+            }
+            Err(e) => return Err(format!("Failed to read checkpoint {:?}: {}", path, e).into()),
+        }
+
+        let file = OpenOptions::new().append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+            staged,
+            done,
+        })
+    }
+
+    /// Whether an earlier run finished this unit
+    pub fn is_done(&self, unit: &Range<u64>) -> bool {
+        self.done.contains(&(unit.start, unit.end))
+    }
+
+    /// Whether an earlier run committed this unit to its staging table but may
+    /// not have moved it into the table
+    pub fn is_staged(&self, unit: &Range<u64>) -> bool {
+        let key = (unit.start, unit.end);
+        self.staged.contains(&key) && !self.done.contains(&key)
+    }
+
+    /// Units an earlier run finished
+    pub fn done_units(&self) -> usize {
+        self.done.len()
+    }
+
+    pub fn mark_staged(&self, unit: &Range<u64>) -> io::Result<()> {
+        self.append("staged", unit)
+    }
+
+    pub fn mark_done(&self, unit: &Range<u64>) -> io::Result<()> {
+        self.append("done", unit)
+    }
+
+    /// Deletes the checkpoint once the whole load has finished
+    pub fn remove(self) -> io::Result<()> {
+        fs::remove_file(&self.path)
+    }
+
+    fn append(&self, state: &str, unit: &Range<u64>) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        file.write_all(format!("{} {} {}\n", state, unit.start, unit.end).as_bytes())?;
+        file.sync_data()
+    }
+}
+
+fn parse_line(line: &str) -> Option<(&str, (u64, u64))> {
+    let mut fields = line.strip_suffix('\n')?.split(' ');
+    let state = fields.next()?;
+    let start = fields.next()?.parse().ok()?;
+    let end = fields.next()?.parse().ok()?;
+    if fields.next().is_some() || !matches!(state, "staged" | "done") {
+        return None;
+    }
+    Some((state, (start, end)))
+}
@@ -0,0 +1,94 @@
+//! gensort/valsort-style data checksums.
+//!
+//! valsort sums the CRC-32 of every 100-byte record into a 128-bit total. The
+//! sum does not depend on record order, so the checksum of a generated file
+//! must equal the checksum of any correct sort of it. `generate-gensort
+//! --checksum` stores it in a `.sum` file next to the data:
+//!
+//! ```text
+//! Records: 1000
+//! Checksum: 1f3a3b1c7e44
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Running record count and CRC-32 sum
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Checksum {
+    pub records: u64,
+    pub sum: u128,
+}
+
+impl Checksum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds one record
+    pub fn add(&mut self, record: &[u8]) {
+        self.records += 1;
+        self.sum = self.sum.wrapping_add(crc32fast::hash(record) as u128);
+    }
+
+    /// Adds every `record_size`-byte record in `data`
+    pub fn add_records(&mut self, data: &[u8], record_size: usize) {
+        for record in data.chunks_exact(record_size) {
+            self.add(record);
+        }
+    }
+
+    /// Combines the checksums of two disjoint parts of the data
+    pub fn merge(&mut self, other: &Checksum) {
+        self.records += other.records;
+        self.sum = self.sum.wrapping_add(other.sum);
+    }
+
+    /// Writes the `.sum` file format shown in the module docs
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+        fs::write(path, self.to_string())
+            .map_err(|e| format!("Failed to write {:?}: {}", path, e).into())
+    }
+
+    /// Reads a `.sum` file
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        parse(&text).map_err(|e| format!("Malformed sum file {:?}: {}", path, e).into())
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Records: {}", self.records)?;
+        writeln!(f, "Checksum: {:x}", self.sum)
+    }
+}
+
+/// Parses the `.sum` text format
+pub fn parse(text: &str) -> Result<Checksum, String> {
+    let mut records = None;
+    let mut sum = None;
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        match line.split_once(':') {
+            Some(("Records", value)) => {
+                records = Some(value.trim().parse().map_err(|e| format!("{}", e))?);
+            }
+            Some(("Checksum", value)) => {
+                sum = Some(u128::from_str_radix(value.trim(), 16).map_err(|e| format!("{}", e))?);
+            }
+            _ => return Err(format!("unexpected line '{}'", line)),
+        }
+    }
+    match (records, sum) {
+        (Some(records), Some(sum)) => Ok(Checksum { records, sum }),
+        _ => Err("expected Records and Checksum lines".to_string()),
+    }
+}
+
+/// Where the checksum of a data file goes: the data path with a `.sum` extension
+pub fn sum_path(data: &Path) -> PathBuf {
+    data.with_extension("sum")
+}
@@ -0,0 +1,103 @@
+//! Collations for string keys (`--collation`).
+//!
+//! A key loaded as text (load-duckdb `--key-type varchar`, load-postgres
+//! `--key-type text`, or ClickHouse's String) can sort by its bytes or by the
+//! rules of a locale, which costs a lot more per comparison. `--collation
+//! binary` or `--collation icu:<locale>` picks one, so the two can be timed
+//! against each other on the same data:
+//!
+//! - the loaders of DuckDB and PostgreSQL give the key column that collation,
+//!   which every ORDER BY on it then uses
+//! - the sorters add `COLLATE` to their ORDER BY, overriding the column's
+//!   collation (ClickHouse has no column collations, so it only collates here)
+//!
+//! The locale is a BCP 47 tag such as `de` or `en-US`, named the way each
+//! engine names its ICU collations: `de_de` in DuckDB (from its icu
+//! extension), `"de-DE-x-icu"` in PostgreSQL (built with ICU), as is in
+//! ClickHouse. ICU orders text by other rules than bytes, so a sort under
+//! one is not checked for byte order, and the engines needn't agree on it.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// How string keys compare
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Collation {
+    /// Byte by byte, like BLOB and bytea keys
+    Binary,
+    /// By the ICU rules of a locale
+    Icu(String),
+}
+
+impl Collation {
+    /// Whether keys in this order are in byte order too, as `--verify` checks
+    pub fn is_binary(&self) -> bool {
+        *self == Collation::Binary
+    }
+
+    /// The DuckDB `COLLATE` clause, to follow a column or its type
+    pub fn duckdb(&self) -> String {
+        match self {
+            Collation::Binary => " COLLATE binary".to_string(),
+            Collation::Icu(locale) => {
+                format!(" COLLATE {}", locale.to_ascii_lowercase().replace('-', "_"))
+            }
+        }
+    }
+
+    /// What DuckDB runs before it knows the collation: the ICU ones come from
+    /// its icu extension
+    pub fn duckdb_setup(&self) -> &'static str {
+        match self {
+            Collation::Binary => "",
+            Collation::Icu(_) => "INSTALL icu; LOAD icu;",
+        }
+    }
+
+    /// The PostgreSQL `COLLATE` clause, to follow a column or its type
+    pub fn postgres(&self) -> String {
+        match self {
+            Collation::Binary => " COLLATE \"C\"".to_string(),
+            Collation::Icu(locale) => format!(" COLLATE \"{}-x-icu\"", locale.replace('_', "-")),
+        }
+    }
+
+    /// The ClickHouse `COLLATE` clause, to follow an ORDER BY column; none for
+    /// bytes, how String compares anyway
+    pub fn clickhouse(&self) -> String {
+        match self {
+            Collation::Binary => String::new(),
+            Collation::Icu(locale) => format!(" COLLATE '{}'", locale),
+        }
+    }
+}
+
+impl FromStr for Collation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let locale_ok = |locale: &str| {
+            !locale.is_empty()
+                && locale
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        };
+        match s.split_once(':') {
+            None if s == "binary" => Ok(Collation::Binary),
+            Some(("icu", locale)) if locale_ok(locale) => Ok(Collation::Icu(locale.to_string())),
+            _ => Err(format!(
+                "Expected 'binary' or 'icu:<locale>' (e.g. icu:de-DE), got '{}'",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Collation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Collation::Binary => write!(f, "binary"),
+            Collation::Icu(locale) => write!(f, "icu:{}", locale),
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! CSV encoding of fixed-width records.
+//!
+//! Each record becomes one `sort_key,payload\n` line with both fields encoded
+//! as text (hex or standard padded base64), the form bulk CSV paths such as
+//! MySQL's `LOAD DATA` or StarRocks/Doris Stream Load take without a
+//! conversion step. There is no header line. Encoded fields have a fixed
+//! length, so every line of a file is equally long and line i starts at byte
+//! `i * row_size`.
+
+use crate::records::FixedLayout;
+use clap::ValueEnum;
+
+/// How binary fields are written as text
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    /// Lowercase hex, two characters per byte
+    Hex,
+    /// Standard base64 with padding
+    Base64,
+}
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl Encoding {
+    /// Encoded length of `len` bytes
+    pub fn encoded_len(self, len: usize) -> usize {
+        match self {
+            Encoding::Hex => 2 * len,
+            Encoding::Base64 => len.div_ceil(3) * 4,
+        }
+    }
+
+    /// Appends the encoding of `data` to `out`
+    pub fn encode(self, data: &[u8], out: &mut Vec<u8>) {
+        match self {
+            Encoding::Hex => {
+                for &b in data {
+                    out.extend_from_slice(&[HEX[(b >> 4) as usize], HEX[(b & 0xF) as usize]]);
+                }
+            }
+            Encoding::Base64 => {
+                for group in data.chunks(3) {
+                    let n = group
+                        .iter()
+                        .enumerate()
+                        .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+                    for i in 0..4 {
+                        out.push(if i <= group.len() {
+                            BASE64[(n >> (18 - 6 * i) & 0x3F) as usize]
+                        } else {
+                            b'='
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Decodes one field
+    pub fn decode(self, text: &[u8]) -> Result<Vec<u8>, String> {
+        let invalid = || {
+            format!(
+                "Invalid {:?} field '{}'",
+                self,
+                String::from_utf8_lossy(text)
+            )
+        };
+        match self {
+            Encoding::Hex => {
+                if !text.len().is_multiple_of(2) {
+                    return Err(invalid());
+                }
+                let digit = |c: u8| (c as char).to_digit(16).ok_or_else(invalid);
+                text.chunks_exact(2)
+                    .map(|pair| Ok((digit(pair[0])? << 4 | digit(pair[1])?) as u8))
+                    .collect()
+            }
+            Encoding::Base64 => {
+                if !text.len().is_multiple_of(4) {
+                    return Err(invalid());
+                }
+                let mut out = Vec::with_capacity(text.len() / 4 * 3);
+                for group in text.chunks_exact(4) {
+                    let padding = group.iter().rev().take_while(|&&c| c == b'=').count();
+                    if padding > 2 {
+                        return Err(invalid());
+                    }
+                    let mut n = 0u32;
+                    for &c in &group[..4 - padding] {
+                        let value = BASE64.iter().position(|&d| d == c).ok_or_else(invalid)?;
+                        n = n << 6 | value as u32;
+                    }
+                    n <<= 6 * padding;
+                    out.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// Length of each CSV line, newline included
+pub fn row_size(layout: FixedLayout, encoding: Encoding) -> usize {
+    encoding.encoded_len(layout.key_size) + 1 + encoding.encoded_len(layout.payload_size) + 1
+}
+
+/// Appends one line per record in `data`, which must hold whole records
+pub fn encode_records(data: &[u8], layout: FixedLayout, encoding: Encoding, out: &mut Vec<u8>) {
+    for record in data.chunks_exact(layout.record_size()) {
+        let (key, payload) = record.split_at(layout.key_size);
+        encoding.encode(key, out);
+        out.push(b',');
+        encoding.encode(payload, out);
+        out.push(b'\n');
+    }
+}
+
+/// Splits one line (with or without its newline) back into key and payload
+pub fn decode_row(line: &[u8], encoding: Encoding) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let comma = line
+        .iter()
+        .position(|&c| c == b',')
+        .ok_or_else(|| format!("No ',' in line '{}'", String::from_utf8_lossy(line)))?;
+    Ok((
+        encoding.decode(&line[..comma])?,
+        encoding.decode(&line[comma + 1..])?,
+    ))
+}
@@ -0,0 +1,132 @@
+//! Input reads that bypass the page cache.
+//!
+//! A freshly generated input is usually still in the page cache, which makes
+//! the first load look faster than one from a cold device. `--direct-io` reads
+//! it with `O_DIRECT` instead: reads start at block-aligned offsets into an
+//! aligned buffer, and the bytes before the range's start are skipped.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+/// Alignment of O_DIRECT offsets, lengths and buffers; the logical block size
+/// of every common device is a divisor of it
+pub const ALIGN: usize = 4096;
+
+/// Sequential reader of bytes `range` of a file, with O_DIRECT reads
+pub struct DirectReader {
+    file: File,
+    /// `ALIGN` bytes more than the aligned buffer, which starts at `shift`
+    buf: Vec<u8>,
+    shift: usize,
+    capacity: usize,
+    /// Bytes of the buffer read so far, and the next one to hand out
+    filled: usize,
+    pos: usize,
+    /// Leading bytes of the first read that come before the range
+    skip: usize,
+    next_offset: u64,
+    left: u64,
+    /// A read came back short: the file ends there
+    at_end: bool,
+}
+
+impl DirectReader {
+    /// Opens bytes `range` of the file at `path`, reading `buffer_bytes` (rounded
+    /// up to `ALIGN`) at a time
+    pub fn open(path: &Path, range: Range<u64>, buffer_bytes: usize) -> io::Result<Self> {
+        let file = open_direct(path)?;
+        let capacity = buffer_bytes.max(1).next_multiple_of(ALIGN);
+        let buf = vec![0u8; capacity + ALIGN];
+        let shift = buf.as_ptr().align_offset(ALIGN);
+        let start = range.start - range.start % ALIGN as u64;
+        Ok(Self {
+            file,
+            buf,
+            shift,
+            capacity,
+            filled: 0,
+            pos: 0,
+            skip: (range.start - start) as usize,
+            next_offset: start,
+            left: range.end.saturating_sub(range.start),
+            at_end: false,
+        })
+    }
+
+    /// Reads the next aligned block; false at the end of the file
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.at_end {
+            return Ok(false);
+        }
+        let buf = &mut self.buf[self.shift..self.shift + self.capacity];
+        let n = loop {
+            match self.file.read_at(buf, self.next_offset) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        };
+        // Only the file's last block comes back short, after which the next
+        // offset would no longer be aligned
+        self.at_end = n < self.capacity;
+        self.next_offset += n as u64;
+        self.filled = n;
+        self.pos = self.skip.min(n);
+        self.skip -= self.pos;
+        Ok(n > 0)
+    }
+}
+
+impl Read for DirectReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.filled {
+            if self.left == 0 || !self.fill()? {
+                return Ok(0);
+            }
+        }
+        let n = out
+            .len()
+            .min(self.filled - self.pos)
+            .min(self.left.min(usize::MAX as u64) as usize);
+        let start = self.shift + self.pos;
+        out[..n].copy_from_slice(&self.buf[start..start + n]);
+        self.pos += n;
+        self.left -= n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn open_direct(path: &Path) -> io::Result<File> {
+    use std::fs::OpenOptions;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_DIRECT)
+        .open(path)
+        .map_err(|e| {
+            if e.raw_os_error() == Some(libc::EINVAL) {
+                io::Error::new(
+                    e.kind(),
+                    format!(
+                        "the filesystem of {} does not support O_DIRECT (--direct-io)",
+                        path.display()
+                    ),
+                )
+            } else {
+                e
+            }
+        })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_direct(_path: &Path) -> io::Result<File> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--direct-io is only supported on Linux",
+    ))
+}
@@ -0,0 +1,138 @@
+//! DuckDB's JSON query profile, boiled down to what a sort run reports.
+//!
+//! `sort-duckdb` turns on `PRAGMA enable_profiling = 'json'` for the sort
+//! statements, with `SETTINGS` picking the metrics and the profile going to
+//! a temporary file. `parse` reads that file into a `Profile`: the query's
+//! latency, planning and CPU time, the peak buffer memory and temp directory
+//! size, and the time and output rows of every operator. DuckDB does not count the rows
+//! it spills, so the temp directory peak is what tells an external sort from
+//! an in-memory one.
+
+use serde_json::Value;
+use std::error::Error;
+
+/// `custom_profiling_settings` of a sort: the metrics `parse` reads
+pub const SETTINGS: &str = r#"{"LATENCY": "true", "CPU_TIME": "true", "PLANNER": "true", "ALL_OPTIMIZERS": "true", "PHYSICAL_PLANNER": "true", "SYSTEM_PEAK_BUFFER_MEMORY": "true", "SYSTEM_PEAK_TEMP_DIR_SIZE": "true", "OPERATOR_TYPE": "true", "OPERATOR_TIMING": "true", "OPERATOR_CARDINALITY": "true"}"#;
+
+/// Time and output rows of one kind of operator, summed over its instances
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperatorTiming {
+    pub operator: String,
+    pub seconds: f64,
+    pub rows: u64,
+}
+
+/// The metrics of one or more profiled queries
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub latency: f64,
+    /// Binding and planning, optimizing and physical planning, in seconds
+    pub planning: f64,
+    pub cpu_time: f64,
+    /// Most buffer memory in use at once, in bytes
+    pub peak_buffer_memory: u64,
+    /// Largest size of the temp directory, in bytes: 0 if nothing spilled
+    pub peak_temp_dir_size: u64,
+    /// In the order the operators first appear in the plan, root first
+    pub operators: Vec<OperatorTiming>,
+}
+
+/// Parses a profile DuckDB wrote with `enable_profiling = 'json'`. Metrics
+/// left out of the profile count as 0.
+pub fn parse(json: &str) -> Result<Profile, Box<dyn Error + Send + Sync>> {
+    let root: Value =
+        serde_json::from_str(json).map_err(|e| format!("Invalid DuckDB profile: {}", e))?;
+    let mut profile = Profile {
+        latency: root["latency"].as_f64().unwrap_or(0.0),
+        planning: ["planner", "all_optimizers", "physical_planner"]
+            .iter()
+            .map(|metric| root[*metric].as_f64().unwrap_or(0.0))
+            .sum(),
+        cpu_time: root["cpu_time"].as_f64().unwrap_or(0.0),
+        peak_buffer_memory: root["system_peak_buffer_memory"].as_u64().unwrap_or(0),
+        peak_temp_dir_size: root["system_peak_temp_dir_size"].as_u64().unwrap_or(0),
+        operators: Vec::new(),
+    };
+    add_operators(&root, &mut profile.operators);
+    Ok(profile)
+}
+
+/// Adds the operators below `node` to `operators`, depth first
+fn add_operators(node: &Value, operators: &mut Vec<OperatorTiming>) {
+    for child in node["children"].as_array().into_iter().flatten() {
+        if let Some(operator) = child["operator_type"].as_str() {
+            add_operator(
+                operators,
+                OperatorTiming {
+                    operator: operator.to_string(),
+                    seconds: child["operator_timing"].as_f64().unwrap_or(0.0),
+                    rows: child["operator_cardinality"].as_u64().unwrap_or(0),
+                },
+            );
+        }
+        add_operators(child, operators);
+    }
+}
+
+fn add_operator(operators: &mut Vec<OperatorTiming>, timing: OperatorTiming) {
+    match operators.iter_mut().find(|o| o.operator == timing.operator) {
+        Some(known) => {
+            known.seconds += timing.seconds;
+            known.rows += timing.rows;
+        }
+        None => operators.push(timing),
+    }
+}
+
+impl Profile {
+    /// Adds the profile of a further query, e.g. the next shard's sort: times
+    /// and rows add up, peaks keep the higher one
+    pub fn merge(&mut self, other: Profile) {
+        self.latency += other.latency;
+        self.planning += other.planning;
+        self.cpu_time += other.cpu_time;
+        self.peak_buffer_memory = self.peak_buffer_memory.max(other.peak_buffer_memory);
+        self.peak_temp_dir_size = self.peak_temp_dir_size.max(other.peak_temp_dir_size);
+        for timing in other.operators {
+            add_operator(&mut self.operators, timing);
+        }
+    }
+
+    /// Seconds `operator` took, summed over its threads and instances
+    pub fn operator_seconds(&self, operator: &str) -> Option<f64> {
+        self.operators
+            .iter()
+            .find(|o| o.operator == operator)
+            .map(|o| o.seconds)
+    }
+
+    /// The part of the query's CPU time that `operators` took. Operator times
+    /// add up over the threads, so this share, not their seconds, says how
+    /// much of the wall time went to them. None without a CPU time.
+    pub fn cpu_share(&self, operators: &[&str]) -> Option<f64> {
+        let seconds: f64 = operators
+            .iter()
+            .filter_map(|operator| self.operator_seconds(operator))
+            .sum();
+        (self.cpu_time > 0.0).then(|| (seconds / self.cpu_time).min(1.0))
+    }
+
+    /// `KEY: value` lines next to TIMING, one `OPERATOR:` line per operator
+    pub fn report(&self) -> String {
+        let mut out = format!(
+            "PROFILE_LATENCY: {:.3}\nPROFILE_CPU_TIME: {:.3}\n\
+             PROFILE_PEAK_BUFFER_MB: {:.1}\nPROFILE_PEAK_TEMP_MB: {:.1}",
+            self.latency,
+            self.cpu_time,
+            self.peak_buffer_memory as f64 / (1024.0 * 1024.0),
+            self.peak_temp_dir_size as f64 / (1024.0 * 1024.0)
+        );
+        for o in &self.operators {
+            out.push_str(&format!(
+                "\nOPERATOR: {} {:.3}s {} rows",
+                o.operator, o.seconds, o.rows
+            ));
+        }
+        out
+    }
+}
@@ -0,0 +1,659 @@
+//! Sorted output files (`--output-format`).
+//!
+//! The sorters share these six formats, so a harness can ask them all for one
+//! format and a verifier can read any of them the same way. Each sorter lists
+//! the ones it writes in its `OUTPUT_FORMATS`: PostgreSQL has no `parquet`
+//! and ClickHouse no `pgbinary`.
+//!
+//! - `parquet`: `sort_key` and `payload` BYTE_ARRAY columns
+//! - `csv`: one `sort_key,payload` line per row, both lowercase hex, no header
+//!   (the `csv_file::Encoding::Hex` lines the loaders take)
+//! - `native`: ClickHouse Native blocks of `String` columns
+//! - `pgbinary`: a PostgreSQL binary COPY file of `bytea` fields
+//! - `kvbin`: u32 LE key length, key, u32 LE value length, value
+//! - `gensort`: 100-byte records, a 10-byte key and a 90-byte payload
+//!
+//! A sorter writes a format server-side when its engine can (DuckDB's COPY,
+//! PostgreSQL's COPY, ClickHouse's INTO OUTFILE) and otherwise reads the
+//! sorted rows back and writes them with `Writer`, the client-side time then
+//! counting towards the sort. With `--keys-only` the rows have no payload:
+//! csv lines and Native blocks have one column, kvbin values are empty and
+//! gensort, whose records have a fixed size, is refused.
+//!
+//! Sorting a `--with-row-id` table carries its `row_id` along as a third
+//! column, so `verify` can tell a lost row and an unstable sort: a BIGINT
+//! Parquet column, 16 hex digits in csv, a UInt64 Native column and an int8
+//! binary COPY field. kvbin and gensort have no room for it and leave it out.
+//!
+//! A `--key-type uint64` key (`KeyType::Uint64`) goes out as 8 big-endian
+//! bytes, so its bytes sort as the integers do: PostgreSQL's BIGINT has its
+//! top bit flipped back, and csv holds 16 hex digits. Where a format names its
+//! columns (a DuckDB Parquet file, ClickHouse Native and Parquet) it stays an
+//! integer column, which `Reader` turns into those bytes. gensort's 10-byte
+//! keys don't fit it.
+//!
+//! Without `--output-format` the format follows the output file's extension
+//! (`OutputFormat::from_path`), and else the sorter's own default.
+
+use crate::csv_file::Encoding;
+use crate::kvbin_index;
+use crate::pgcopy;
+use crate::records::FixedLayout;
+use crate::rowbinary;
+use crate::schema::ROW_ID_COLUMN;
+use clap::ValueEnum;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// A row read back: the key and the payload, empty for a keys-only sort
+pub type Row = (Vec<u8>, Vec<u8>);
+
+/// A row and its row id, for files that carry them
+pub type RowWithId = (Row, Option<u64>);
+
+/// Format of a sorted output file
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// sort_key and payload BYTE_ARRAY columns
+    Parquet,
+    /// Lowercase hex `sort_key,payload` lines without a header
+    Csv,
+    /// ClickHouse Native blocks
+    Native,
+    /// PostgreSQL binary COPY
+    Pgbinary,
+    /// Length-prefixed key/value records
+    Kvbin,
+    /// 100-byte records with 10-byte keys
+    Gensort,
+}
+
+/// Rows in each block of a Native file `Writer` writes
+const NATIVE_BLOCK_ROWS: usize = 65_536;
+
+impl OutputFormat {
+    /// The format's name as `--output-format` takes it
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Native => "native",
+            OutputFormat::Pgbinary => "pgbinary",
+            OutputFormat::Kvbin => "kvbin",
+            OutputFormat::Gensort => "gensort",
+        }
+    }
+
+    /// The format an output file's extension names. `.bin` (sort-postgres'
+    /// usual extension) is binary COPY and `.dat` (gensort's) gensort.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "parquet" => Some(OutputFormat::Parquet),
+            "csv" => Some(OutputFormat::Csv),
+            "native" => Some(OutputFormat::Native),
+            "pgbinary" | "bin" => Some(OutputFormat::Pgbinary),
+            "kvbin" => Some(OutputFormat::Kvbin),
+            "gensort" | "dat" => Some(OutputFormat::Gensort),
+            _ => None,
+        }
+    }
+
+    /// The `--output-format` given, or the one `output`'s extension names, or
+    /// the sorter's `default`
+    pub fn resolve(format: Option<Self>, output: &Path, default: Self) -> Self {
+        format
+            .or_else(|| Self::from_path(output))
+            .unwrap_or(default)
+    }
+
+    /// Whether the format keeps the `row_id` column of a sort
+    pub fn carries_row_ids(self) -> bool {
+        !matches!(self, OutputFormat::Kvbin | OutputFormat::Gensort)
+    }
+
+    /// Refuses a format `sorter` can't write, or one rows without a payload
+    /// don't fit
+    pub fn check(self, sorter: &str, supported: &[Self], keys_only: bool) -> Result<(), String> {
+        if !supported.contains(&self) {
+            let names: Vec<&str> = supported.iter().map(|f| f.name()).collect();
+            return Err(format!(
+                "{} can't write {} output (it writes {})",
+                sorter,
+                self.name(),
+                names.join(", ")
+            ));
+        }
+        if keys_only && self == OutputFormat::Gensort {
+            return Err("gensort records need a payload: --keys-only can't write gensort".into());
+        }
+        Ok(())
+    }
+}
+
+/// The columns of sorted rows, as `Writer` takes them: the key, the payload
+/// unless `keys_only`, then with `row_ids` the row id
+pub fn columns(keys_only: bool, row_ids: bool) -> &'static [&'static str] {
+    match (keys_only, row_ids) {
+        (true, _) => &["sort_key"],
+        (false, false) => &["sort_key", "payload"],
+        (false, true) => &["sort_key", "payload", ROW_ID_COLUMN],
+    }
+}
+
+/// Type of the key column a sorter reads, which decides how the output files
+/// encode the key
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum KeyType {
+    /// bytea, BLOB, String or FixedString: the key's bytes
+    #[default]
+    Bytes,
+    /// PostgreSQL text or DuckDB VARCHAR: the key's UTF-8 bytes
+    Text,
+    /// PostgreSQL BIGINT, DuckDB UBIGINT or ClickHouse UInt64 of a
+    /// `--key-type uint64` table: 8 big-endian bytes
+    Uint64,
+}
+
+impl KeyType {
+    /// The key type of a column of type `name`, as the engine's catalog spells
+    /// it (`bigint`, `VARCHAR`, `UInt64`, ...)
+    pub fn from_sql(name: &str) -> Self {
+        match name.to_ascii_lowercase().as_str() {
+            "text" | "varchar" => KeyType::Text,
+            "bigint" | "ubigint" | "uint64" => KeyType::Uint64,
+            _ => KeyType::Bytes,
+        }
+    }
+
+    /// Refuses a format the key doesn't fit
+    pub fn check(self, format: OutputFormat) -> Result<(), String> {
+        match (self, format) {
+            (KeyType::Uint64, OutputFormat::Gensort) => {
+                Err("gensort records have 10-byte keys: a uint64 key can't write gensort".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The PostgreSQL key `column` as bytea. The expression is not named after
+    /// the column, so an ORDER BY of it still sorts the column itself.
+    pub fn postgres_bytes(self, column: &str) -> String {
+        match self {
+            KeyType::Bytes => column.to_string(),
+            KeyType::Text => format!("convert_to({}, 'UTF8')", column),
+            KeyType::Uint64 => format!("int8send({} # (1::bigint << 63))", column),
+        }
+    }
+
+    /// The DuckDB key `column` as lowercase hex, 16 digits for an integer
+    pub fn duckdb_hex(self, column: &str) -> String {
+        match self {
+            KeyType::Uint64 => format!("lpad(lower(hex({})), 16, '0')", column),
+            _ => format!("lower(hex({}))", column),
+        }
+    }
+
+    /// The ClickHouse key `column` as a String of its bytes, likewise not
+    /// named after it
+    pub fn clickhouse_bytes(self, column: &str) -> String {
+        match self {
+            KeyType::Uint64 => format!("unhex(leftPad(hex({}), 16, '0'))", column),
+            _ => column.to_string(),
+        }
+    }
+}
+
+/// Writes sorted rows of one to three fields (the key, then the payload unless
+/// the sort left it out, then the 8-byte big-endian row id if it carries one)
+/// in any format but Parquet
+pub struct Writer<W: Write> {
+    format: OutputFormat,
+    inner: Option<W>,
+    pg: Option<pgcopy::Writer<W>>,
+    columns: Vec<String>,
+    /// Native: the values of the current block, column by column
+    block: Vec<Vec<u8>>,
+    block_rows: usize,
+    buf: Vec<u8>,
+    rows: u64,
+}
+
+impl Writer<BufWriter<File>> {
+    /// Creates `path` for rows of the given columns
+    pub fn create(
+        path: &Path,
+        format: OutputFormat,
+        columns: &[&str],
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let file = File::create(path).map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+        Self::new(
+            BufWriter::with_capacity(8 * 1024 * 1024, file),
+            format,
+            columns,
+        )
+    }
+}
+
+impl<W: Write> Writer<W> {
+    /// A writer of rows of `columns`, the key column first and any
+    /// `ROW_ID_COLUMN` last; the other names only end up in Native blocks
+    pub fn new(
+        inner: W,
+        format: OutputFormat,
+        columns: &[&str],
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if format == OutputFormat::Parquet {
+            return Err("Parquet output is written by the engines, not row by row".into());
+        }
+        if !(1..=3).contains(&columns.len()) {
+            return Err(format!("Expected 1 to 3 columns, found {}", columns.len()).into());
+        }
+        if columns.len() == 3 && columns[2] != ROW_ID_COLUMN {
+            return Err(format!("Expected a {} third column", ROW_ID_COLUMN).into());
+        }
+        let (inner, pg) = match format {
+            OutputFormat::Pgbinary => (None, Some(pgcopy::Writer::new(inner)?)),
+            _ => (Some(inner), None),
+        };
+        Ok(Self {
+            format,
+            inner,
+            pg,
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            block: vec![Vec::new(); columns.len()],
+            block_rows: 0,
+            buf: Vec::new(),
+            rows: 0,
+        })
+    }
+
+    /// Appends one row, a value per column
+    pub fn write_row(&mut self, fields: &[&[u8]]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if fields.len() != self.columns.len() {
+            return Err(format!(
+                "Expected {} fields per row, found {}",
+                self.columns.len(),
+                fields.len()
+            )
+            .into());
+        }
+        self.buf.clear();
+        match self.format {
+            OutputFormat::Parquet => unreachable!("refused by Writer::new"),
+            OutputFormat::Pgbinary => {
+                if let Some(pg) = self.pg.as_mut() {
+                    pg.write_row(fields)?;
+                }
+            }
+            OutputFormat::Native => {
+                for (i, (column, field)) in self.block.iter_mut().zip(fields).enumerate() {
+                    match i {
+                        2 => column.extend_from_slice(&row_id(field)?.to_le_bytes()),
+                        _ => rowbinary::write_string(column, field),
+                    }
+                }
+                self.block_rows += 1;
+                if self.block_rows == NATIVE_BLOCK_ROWS {
+                    self.flush_block()?;
+                }
+            }
+            OutputFormat::Csv => {
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.buf.push(b',');
+                    }
+                    Encoding::Hex.encode(field, &mut self.buf);
+                }
+                self.buf.push(b'\n');
+            }
+            OutputFormat::Kvbin => {
+                let value = fields.get(1).copied().unwrap_or_default();
+                for field in [fields[0], value] {
+                    let len = u32::try_from(field.len())
+                        .map_err(|_| format!("{}-byte field is too long for kvbin", field.len()))?;
+                    self.buf.extend_from_slice(&len.to_le_bytes());
+                    self.buf.extend_from_slice(field);
+                }
+            }
+            OutputFormat::Gensort => {
+                let layout = FixedLayout::GENSORT;
+                let payload = fields.get(1).copied().unwrap_or_default();
+                if fields[0].len() != layout.key_size || payload.len() != layout.payload_size {
+                    return Err(format!(
+                        "A {}-byte key and {}-byte payload don't make a gensort record",
+                        fields[0].len(),
+                        payload.len()
+                    )
+                    .into());
+                }
+                self.buf.extend_from_slice(fields[0]);
+                self.buf.extend_from_slice(payload);
+            }
+        }
+        if let Some(inner) = self.inner.as_mut() {
+            inner.write_all(&self.buf)?;
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Writes the buffered Native rows as one block
+    fn flush_block(&mut self) -> io::Result<()> {
+        let Some(inner) = self.inner.as_mut() else {
+            return Ok(());
+        };
+        let mut header = Vec::new();
+        rowbinary::write_varint(&mut header, self.columns.len() as u64);
+        rowbinary::write_varint(&mut header, self.block_rows as u64);
+        inner.write_all(&header)?;
+        for (name, values) in self.columns.iter().zip(self.block.iter_mut()) {
+            header.clear();
+            rowbinary::write_string(&mut header, name.as_bytes());
+            let type_name: &[u8] = match name == ROW_ID_COLUMN {
+                true => b"UInt64",
+                false => b"String",
+            };
+            rowbinary::write_string(&mut header, type_name);
+            inner.write_all(&header)?;
+            inner.write_all(values)?;
+            values.clear();
+        }
+        self.block_rows = 0;
+        Ok(())
+    }
+
+    /// Writes what is still buffered, and the trailer of a binary COPY file;
+    /// returns the rows written
+    pub fn finish(mut self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        if self.block_rows > 0 {
+            self.flush_block()?;
+        }
+        if let Some(pg) = self.pg.take() {
+            pg.finish()?;
+        }
+        if let Some(inner) = self.inner.as_mut() {
+            inner.flush()?;
+        }
+        Ok(self.rows)
+    }
+}
+
+/// Reads the (key, payload) rows of an output file in any format but Parquet.
+/// A row without a payload (a `--keys-only` sort) has an empty one.
+pub struct Reader<R: BufRead> {
+    format: OutputFormat,
+    inner: Option<R>,
+    pg: Option<pgcopy::Reader<R>>,
+    /// Native: the rest of the current block
+    block: std::vec::IntoIter<RowWithId>,
+    line: Vec<u8>,
+}
+
+impl Reader<BufReader<File>> {
+    pub fn open(path: &Path, format: OutputFormat) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+        Self::new(BufReader::with_capacity(8 * 1024 * 1024, file), format)
+            .map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(inner: R, format: OutputFormat) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if format == OutputFormat::Parquet {
+            return Err("Parquet output is read by an engine, not row by row".into());
+        }
+        let (inner, pg) = match format {
+            OutputFormat::Pgbinary => (None, Some(pgcopy::Reader::new(inner)?)),
+            _ => (Some(inner), None),
+        };
+        Ok(Self {
+            format,
+            inner,
+            pg,
+            block: Vec::new().into_iter(),
+            line: Vec::new(),
+        })
+    }
+
+    /// The next row, or None at the end of the file
+    pub fn next_row(&mut self) -> Result<Option<Row>, Box<dyn Error + Send + Sync>> {
+        Ok(self.next_row_with_id()?.map(|(row, _)| row))
+    }
+
+    /// The next row and its row id, if the file carries them
+    pub fn next_row_with_id(&mut self) -> Result<Option<RowWithId>, Box<dyn Error + Send + Sync>> {
+        if let Some(pg) = self.pg.as_mut() {
+            let Some(fields) = pg.next_row()? else {
+                return Ok(None);
+            };
+            return key_and_payload(fields).map(Some);
+        }
+        let Some(inner) = self.inner.as_mut() else {
+            return Ok(None);
+        };
+        match self.format {
+            OutputFormat::Parquet | OutputFormat::Pgbinary => unreachable!("handled above"),
+            OutputFormat::Csv => {
+                self.line.clear();
+                if inner.read_until(b'\n', &mut self.line)? == 0 {
+                    return Ok(None);
+                }
+                let line = self.line.strip_suffix(b"\n").unwrap_or(&self.line);
+                let fields = line
+                    .split(|&c| c == b',')
+                    .map(|field| Encoding::Hex.decode(field))
+                    .collect::<Result<Vec<_>, _>>()?;
+                key_and_payload(fields).map(Some)
+            }
+            OutputFormat::Kvbin => {
+                let Some(key) = read_kvbin_field(inner, true)? else {
+                    return Ok(None);
+                };
+                let value = read_kvbin_field(inner, false)?.unwrap_or_default();
+                Ok(Some(((key, value), None)))
+            }
+            OutputFormat::Gensort => {
+                let layout = FixedLayout::GENSORT;
+                let mut record = vec![0u8; layout.record_size()];
+                if !read_exact_or_eof(inner, &mut record)? {
+                    return Ok(None);
+                }
+                let payload = record.split_off(layout.key_size);
+                Ok(Some(((record, payload), None)))
+            }
+            OutputFormat::Native => loop {
+                if let Some(row) = self.block.next() {
+                    return Ok(Some(row));
+                }
+                let Some(rows) = read_native_block(inner)? else {
+                    return Ok(None);
+                };
+                self.block = rows.into_iter();
+            },
+        }
+    }
+}
+
+/// The key, the payload (or nothing) and the row id (or None) of a row's fields
+fn key_and_payload(mut fields: Vec<Vec<u8>>) -> Result<RowWithId, Box<dyn Error + Send + Sync>> {
+    let id = match fields.len() {
+        1 | 2 => None,
+        3 => Some(row_id(&fields.pop().unwrap())?),
+        n => return Err(format!("Expected 1 to 3 fields per row, found {}", n).into()),
+    };
+    let payload = if fields.len() == 2 {
+        fields.pop().unwrap()
+    } else {
+        Vec::new()
+    };
+    Ok(((fields.pop().unwrap(), payload), id))
+}
+
+/// A row id field: 8 bytes big-endian
+fn row_id(field: &[u8]) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    let bytes: [u8; 8] = field
+        .try_into()
+        .map_err(|_| format!("A {}-byte row id is not 8 bytes", field.len()))?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Fills `buf`; false when the input ended before its first byte
+fn read_exact_or_eof(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 if filled == 0 => return Ok(false),
+            0 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("the file ends inside a {}-byte record", buf.len()),
+                ));
+            }
+            n => filled += n,
+        }
+    }
+    Ok(true)
+}
+
+/// One length-prefixed kvbin field; None at the end of the file, where only a
+/// key may start
+fn read_kvbin_field(reader: &mut impl Read, first: bool) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    if !read_exact_or_eof(reader, &mut len)? {
+        return match first {
+            true => Ok(None),
+            false => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the file ends before a value length",
+            )),
+        };
+    }
+    let len = u32::from_le_bytes(len) as u64;
+    if len > kvbin_index::MAX_PLAUSIBLE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("kvbin field length {} is implausible", len),
+        ));
+    }
+    let mut field = vec![0u8; len as usize];
+    reader.read_exact(&mut field)?;
+    Ok(Some(field))
+}
+
+/// The rows of the next Native block, from its sort_key and payload columns
+/// (String or FixedString) and any row_id column (UInt64 or Int64); None at
+/// the end of the file
+fn read_native_block(
+    reader: &mut impl Read,
+) -> Result<Option<Vec<RowWithId>>, Box<dyn Error + Send + Sync>> {
+    // Each block: column count, row count, then (name, type, data) per column
+    let num_columns = match read_varint(reader) {
+        Ok(n) => n,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let num_rows = read_varint(reader)? as usize;
+
+    let mut keys = None;
+    let mut payloads = None;
+    let mut ids = None;
+    for _ in 0..num_columns {
+        let name = read_native_string(reader)?;
+        let type_name = String::from_utf8(read_native_string(reader)?)?;
+        if name == ROW_ID_COLUMN.as_bytes() && matches!(type_name.as_str(), "UInt64" | "Int64") {
+            let mut id = [0u8; 8];
+            let mut values = Vec::with_capacity(num_rows);
+            for _ in 0..num_rows {
+                reader.read_exact(&mut id)?;
+                values.push(Some(u64::from_le_bytes(id)));
+            }
+            ids = Some(values);
+            continue;
+        }
+        let values = read_native_column(reader, &type_name, num_rows)?;
+
+        match name.as_slice() {
+            b"sort_key" => keys = Some(values),
+            b"payload" => payloads = Some(values),
+            _ => {}
+        }
+    }
+
+    if num_rows == 0 {
+        return Ok(Some(Vec::new()));
+    }
+    let Some(keys) = keys else {
+        return Err("Native block is missing the sort_key column".into());
+    };
+    let payloads = payloads.unwrap_or_else(|| vec![Vec::new(); num_rows]);
+    let ids = ids.unwrap_or_else(|| vec![None; num_rows]);
+    Ok(Some(keys.into_iter().zip(payloads).zip(ids).collect()))
+}
+
+fn read_native_column(
+    reader: &mut impl Read,
+    type_name: &str,
+    num_rows: usize,
+) -> Result<Vec<Vec<u8>>, Box<dyn Error + Send + Sync>> {
+    if type_name == "UInt64" {
+        // An integer key, as the 8 big-endian bytes it stands for
+        let mut value = [0u8; 8];
+        let mut values = Vec::with_capacity(num_rows);
+        for _ in 0..num_rows {
+            reader.read_exact(&mut value)?;
+            values.push(u64::from_le_bytes(value).to_be_bytes().to_vec());
+        }
+        return Ok(values);
+    }
+    let fixed_len = if type_name == "String" {
+        None
+    } else if let Some(n) = type_name
+        .strip_prefix("FixedString(")
+        .and_then(|s| s.strip_suffix(')'))
+    {
+        Some(n.parse::<usize>()?)
+    } else {
+        return Err(format!("Unsupported Native column type: {}", type_name).into());
+    };
+
+    let mut values = Vec::with_capacity(num_rows);
+    for _ in 0..num_rows {
+        let value = match fixed_len {
+            Some(len) => {
+                let mut buf = vec![0u8; len];
+                reader.read_exact(&mut buf)?;
+                buf
+            }
+            None => read_native_string(reader)?,
+        };
+        values.push(value);
+    }
+    Ok(values)
+}
+
+fn read_native_string(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_varint(reader)? as usize;
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Read variable-length integer (LEB128 encoding used by ClickHouse)
+fn read_varint(reader: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0u64;
+    let mut byte = [0u8; 1];
+    for shift in (0..64).step_by(7) {
+        reader.read_exact(&mut byte)?;
+        value |= ((byte[0] & 0x7F) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint too long",
+    ))
+}
@@ -0,0 +1,257 @@
+//! Sorting input files where they lie, without a load.
+//!
+//! Engines that sort external files in place skip the load phase the other
+//! sorters pay for before the clock starts. `--input-files` makes that
+//! comparison fair: the sorter sorts a scan of the files (a glob such as
+//! `data/*.parquet`) instead of a table, so the timed sort reads the files
+//! itself.
+//!
+//! - sort-duckdb: `read_parquet()` or `read_csv()` in an in-memory database
+//!   (`duckdb_scan`)
+//! - sort-clickhouse: the `file()` table function over the server's
+//!   user_files, or `s3()` for a URL, in any format (`clickhouse_scan`)
+//! - sort-postgres: a file_fdw foreign table over a CSV file on the server,
+//!   or over `cat` of a glob (`COPY FROM PROGRAM` underneath), made before the
+//!   sort and dropped after it (`postgres_foreign_table`, `postgres_scan`)
+//!
+//! Parquet files are read as they are, key and payload as BLOBs like those the
+//! generators write. RowBinary and Native files hold them as `String` columns,
+//! as the ClickHouse loader sends them. CSV files are the generators'
+//! `sort_key,payload` lines without a header, and their fields are decoded
+//! back to bytes in the scan: base64 text doesn't sort like the bytes it
+//! encodes.
+
+use crate::csv_file::Encoding;
+use crate::schema::ColumnNames;
+use clap::ValueEnum;
+use std::path::Path;
+
+/// Format of the files a scan reads
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ScanFormat {
+    /// Parquet with BLOB key and payload columns
+    Parquet,
+    /// Header-less `sort_key,payload` lines, fields encoded per --csv-encoding
+    Csv,
+    /// ClickHouse RowBinary rows of two `String` columns
+    #[value(name = "rowbinary")]
+    RowBinary,
+    /// ClickHouse Native blocks of two `String` columns
+    Native,
+}
+
+impl ScanFormat {
+    /// The format's name as `--input-format` takes it
+    pub fn name(self) -> &'static str {
+        match self {
+            ScanFormat::Parquet => "parquet",
+            ScanFormat::Csv => "csv",
+            ScanFormat::RowBinary => "rowbinary",
+            ScanFormat::Native => "native",
+        }
+    }
+
+    /// The format the extension of `pattern` names, if any
+    pub fn from_pattern(pattern: &str) -> Option<Self> {
+        let ext = Path::new(pattern).extension()?.to_str()?;
+        match ext.to_ascii_lowercase().as_str() {
+            "parquet" => Some(ScanFormat::Parquet),
+            "csv" => Some(ScanFormat::Csv),
+            "rowbinary" => Some(ScanFormat::RowBinary),
+            "native" => Some(ScanFormat::Native),
+            _ => None,
+        }
+    }
+
+    /// `format` if given, else the one the extension of `pattern` names
+    pub fn resolve(format: Option<Self>, pattern: &str) -> Result<Self, String> {
+        format
+            .or_else(|| Self::from_pattern(pattern))
+            .ok_or_else(|| {
+                format!(
+                    "can't tell the format of '{}' from its extension: pass --input-format",
+                    pattern
+                )
+            })
+    }
+
+    /// Refuses a format `sorter` can't read
+    pub fn check(self, sorter: &str, supported: &[Self]) -> Result<(), String> {
+        if supported.contains(&self) {
+            return Ok(());
+        }
+        let names: Vec<&str> = supported.iter().map(|f| f.name()).collect();
+        Err(format!(
+            "{} can't read {} input files (it reads {})",
+            sorter,
+            self.name(),
+            names.join(", ")
+        ))
+    }
+}
+
+/// The formats `duckdb_scan` reads
+pub const DUCKDB_FORMATS: &[ScanFormat] = &[ScanFormat::Parquet, ScanFormat::Csv];
+
+/// The formats `postgres_scan` reads: file_fdw reads what COPY does
+pub const POSTGRES_FORMATS: &[ScanFormat] = &[ScanFormat::Csv];
+
+/// The function decoding CSV fields of `encoding` back to bytes, the same in
+/// DuckDB and ClickHouse but for base64
+fn decode_function(encoding: Encoding, base64: &'static str) -> &'static str {
+    match encoding {
+        Encoding::Hex => "unhex",
+        Encoding::Base64 => base64,
+    }
+}
+
+/// A DuckDB scan of the files matching `pattern` with the key and payload
+/// columns `columns` names, to sort in place of a table. A format not in
+/// `DUCKDB_FORMATS` is refused.
+pub fn duckdb_scan(
+    pattern: &str,
+    format: ScanFormat,
+    encoding: Encoding,
+    columns: &ColumnNames,
+) -> Result<String, String> {
+    format.check("sort-duckdb", DUCKDB_FORMATS)?;
+    let pattern = pattern.replace('\'', "''");
+    match format {
+        ScanFormat::Csv => {
+            let decode = decode_function(encoding, "from_base64");
+            Ok(format!(
+                "(SELECT {0}(column0) AS {1}, {0}(column1) AS {2} FROM read_csv('{3}', \
+                 header = false, columns = {{'column0': 'VARCHAR', 'column1': 'VARCHAR'}})) \
+                 AS input_files",
+                decode, columns.key, columns.payload, pattern
+            ))
+        }
+        ScanFormat::Parquet => Ok(format!("read_parquet('{}')", pattern)),
+        ScanFormat::RowBinary | ScanFormat::Native => unreachable!("refused by check"),
+    }
+}
+
+/// The ClickHouse table function reading the files matching `pattern` in
+/// `format`, with the columns `structure` names if given: `file()` over the
+/// server's user_files_path, or `s3()` for an `s3://` or `http(s)://` URL
+fn clickhouse_function(pattern: &str, format: &str, structure: Option<&str>) -> String {
+    let function = match ["s3://", "http://", "https://"]
+        .iter()
+        .any(|scheme| pattern.starts_with(scheme))
+    {
+        true => "s3",
+        false => "file",
+    };
+    let structure = structure
+        .map(|structure| format!(", '{}'", structure))
+        .unwrap_or_default();
+    format!(
+        "{}('{}', '{}'{})",
+        function,
+        pattern.replace('\'', "''"),
+        format,
+        structure
+    )
+}
+
+/// Counts the files matching `pattern` and their bytes, without reading them
+/// (the `One` format makes a single row per file)
+pub fn clickhouse_file_sizes(pattern: &str) -> String {
+    format!(
+        "SELECT count(), toUInt64(ifNull(sum(_size), 0)) FROM {}",
+        clickhouse_function(pattern, "One", None)
+    )
+}
+
+/// A ClickHouse scan of the files matching `pattern` (see
+/// `clickhouse_function`) with the key and payload columns `columns` names
+pub fn clickhouse_scan(
+    pattern: &str,
+    format: ScanFormat,
+    encoding: Encoding,
+    columns: &ColumnNames,
+) -> String {
+    let structure = |key: &str, payload: &str| format!("{} String, {} String", key, payload);
+    let scan =
+        |format: &str, structure: String| clickhouse_function(pattern, format, Some(&structure));
+    match format {
+        ScanFormat::Parquet => scan("Parquet", structure(&columns.key, &columns.payload)),
+        ScanFormat::RowBinary => scan("RowBinary", structure(&columns.key, &columns.payload)),
+        ScanFormat::Native => scan("Native", structure(&columns.key, &columns.payload)),
+        ScanFormat::Csv => format!(
+            "(SELECT {0}(column0) AS {1}, {0}(column1) AS {2} FROM {3}) AS input_files",
+            decode_function(encoding, "base64Decode"),
+            columns.key,
+            columns.payload,
+            scan("CSV", structure("column0", "column1"))
+        ),
+    }
+}
+
+/// Whether `pattern` holds glob characters, so it names files a shell finds
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// `pattern` as one word of a shell command that still expands its globs:
+/// characters the shell would take for anything else are quoted
+fn shell_glob(pattern: &str) -> String {
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in pattern.chars() {
+        let bare = c.is_ascii_alphanumeric() || "*?[]!^-_./,:=+@%".contains(c);
+        if quoted && (bare || c == '\'') {
+            word.push('\'');
+            quoted = false;
+        }
+        if c == '\'' {
+            word.push_str("\\'");
+            continue;
+        }
+        if !quoted && !bare {
+            word.push('\'');
+            quoted = true;
+        }
+        word.push(c);
+    }
+    if quoted {
+        word.push('\'');
+    }
+    word
+}
+
+/// Statements that (re)create the file_fdw foreign table `table` over the CSV
+/// file `pattern` on the server, or when it holds glob characters over what
+/// `cat` makes of it in the server's shell. Reading a file takes the
+/// pg_read_server_files role, running a program pg_execute_server_program.
+pub fn postgres_foreign_table(table: &str, pattern: &str) -> String {
+    let source = match is_glob(pattern) {
+        true => format!(
+            "program '{}'",
+            format!("cat -- {}", shell_glob(pattern)).replace('\'', "''")
+        ),
+        false => format!("filename '{}'", pattern.replace('\'', "''")),
+    };
+    format!(
+        "CREATE EXTENSION IF NOT EXISTS file_fdw; \
+         CREATE SERVER IF NOT EXISTS es_duck_files FOREIGN DATA WRAPPER file_fdw; \
+         DROP FOREIGN TABLE IF EXISTS {0}; \
+         CREATE FOREIGN TABLE {0} (column0 text, column1 text) SERVER es_duck_files \
+         OPTIONS (format 'csv', {1})",
+        table, source
+    )
+}
+
+/// A PostgreSQL scan of the foreign table `table` from `postgres_foreign_table`,
+/// its fields decoded to bytea under the names `columns` gives them
+pub fn postgres_scan(table: &str, encoding: Encoding, columns: &ColumnNames) -> String {
+    let encoding = match encoding {
+        Encoding::Hex => "hex",
+        Encoding::Base64 => "base64",
+    };
+    format!(
+        "(SELECT decode(column0, '{0}') AS {1}, decode(column1, '{0}') AS {2} FROM {3}) \
+         AS input_files",
+        encoding, columns.key, columns.payload, table
+    )
+}
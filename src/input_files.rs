@@ -0,0 +1,157 @@
+//! Loading several input files in one run.
+//!
+//! A sharded dataset (e.g. 64 gensort parts) loads without being concatenated
+//! first: `InputFiles` splits every file into units (see `checkpoint::units`)
+//! and deals the files out to the reader threads, which work through their own
+//! files front to back. A thread that runs out steals units from the back of
+//! the busiest thread's queue, so the threads stay busy however unevenly the
+//! files are sized. Each file's rows and load time are printed when its last
+//! unit is done.
+
+use crate::checkpoint;
+use crate::records::{self, FixedLayout, InputFormat};
+use std::collections::VecDeque;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Input bytes per unit: small enough for stealing to even out the end of a
+/// load, large enough that a unit's setup cost doesn't show
+pub const UNIT_BYTES: u64 = 64 << 20;
+
+/// Part of one input file: records `range` of a gensort file, or the kvbin
+/// records in bytes `range`, as for checkpointed units
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Unit {
+    pub file: usize,
+    pub range: Range<u64>,
+}
+
+#[derive(Default)]
+struct FileProgress {
+    units_left: usize,
+    rows: u64,
+    started: Option<Instant>,
+}
+
+/// The units of several input files, queued per reader thread
+pub struct InputFiles {
+    paths: Vec<PathBuf>,
+    bytes: u64,
+    queues: Vec<Mutex<VecDeque<Unit>>>,
+    progress: Mutex<Vec<FileProgress>>,
+}
+
+impl InputFiles {
+    /// Splits the files at `paths` (of records of `layout`, for gensort) into
+    /// units of about `unit_bytes`, and deals them out to `threads` queues a
+    /// whole file at a time
+    pub fn new(
+        format: InputFormat,
+        layout: FixedLayout,
+        paths: &[PathBuf],
+        threads: usize,
+        unit_bytes: u64,
+    ) -> io::Result<Self> {
+        let threads = threads.max(1);
+        let mut queues = vec![VecDeque::new(); threads];
+        let mut progress = Vec::with_capacity(paths.len());
+        let mut bytes = 0;
+        for (file, path) in paths.iter().enumerate() {
+            let range = records::select(format, layout, path, 0, None)?;
+            bytes += range.end - range.start;
+            let units = checkpoint::units(format, layout, path, range, unit_bytes)?;
+            progress.push(FileProgress {
+                units_left: units.len(),
+                ..FileProgress::default()
+            });
+            queues[file % threads].extend(units.into_iter().map(|range| Unit { file, range }));
+        }
+        Ok(Self {
+            paths: paths.to_vec(),
+            bytes,
+            queues: queues.into_iter().map(Mutex::new).collect(),
+            progress: Mutex::new(progress),
+        })
+    }
+
+    /// Total input bytes of all the files
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    pub fn path(&self, unit: &Unit) -> &Path {
+        &self.paths[unit.file]
+    }
+
+    /// The next unit for reader thread `thread`: the front of its own queue,
+    /// or else one stolen from the back of the longest other queue. None once
+    /// every queue is empty.
+    pub fn next(&self, thread: usize) -> Option<Unit> {
+        // Release the own queue before stealing, which locks every queue
+        let own = self.queues[thread % self.queues.len()]
+            .lock()
+            .unwrap()
+            .pop_front();
+        let unit = own.or_else(|| self.steal())?;
+        self.progress.lock().unwrap()[unit.file]
+            .started
+            .get_or_insert_with(Instant::now);
+        Some(unit)
+    }
+
+    fn steal(&self) -> Option<Unit> {
+        loop {
+            let victim = self
+                .queues
+                .iter()
+                .max_by_key(|queue| queue.lock().unwrap().len())?;
+            let mut queue = victim.lock().unwrap();
+            if let Some(unit) = queue.pop_back() {
+                return Some(unit);
+            }
+            // Emptied since it was picked: try again, unless all are empty
+            drop(queue);
+            if self.queues.iter().all(|q| q.lock().unwrap().is_empty()) {
+                return None;
+            }
+        }
+    }
+
+    /// Records that `unit` is loaded with `rows` rows, and reports its file
+    /// once that was the file's last unit
+    pub fn finish(&self, unit: &Unit, rows: u64) {
+        let mut progress = self.progress.lock().unwrap();
+        let file = &mut progress[unit.file];
+        file.rows += rows;
+        file.units_left -= 1;
+        if file.units_left == 0 {
+            let secs = file.started.map_or(0.0, |t| t.elapsed().as_secs_f64());
+            println!(
+                "Loaded {} rows from {} in {:.2}s",
+                file.rows,
+                self.paths[unit.file].display(),
+                secs
+            );
+        }
+    }
+}
+
+/// Checks that several input files can be loaded together: they must be
+/// regular files, read whole
+pub fn check(paths: &[PathBuf], skip: u64, limit: Option<u64>) -> Result<(), String> {
+    if skip > 0 || limit.is_some() {
+        return Err("--skip and --limit need a single --input".into());
+    }
+    for path in paths {
+        if records::is_stream(path).map_err(|e| format!("{}: {}", path.display(), e))? {
+            return Err(format!(
+                "{} is a stream; several --input files must be regular files",
+                path.display()
+            ));
+        }
+    }
+    Ok(())
+}
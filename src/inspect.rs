@@ -0,0 +1,239 @@
+//! Checking an input without loading it (`--dry-run`).
+//!
+//! `inspect` reads the bytes a load would read and checks their framing: a
+//! gensort input must hold whole records, and every kvbin record's lengths
+//! must fit in the input. It counts the records and tracks the smallest and
+//! largest key, so a bad input shows up before a database is involved.
+
+use crate::kvbin_index;
+use crate::records::{self, FixedLayout, InputFormat, Window};
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// At most this many malformed records are listed
+const MAX_REPORTED: usize = 20;
+
+/// What `inspect` found in an input
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub records: u64,
+    pub bytes: u64,
+    pub min_key: Option<Vec<u8>>,
+    pub max_key: Option<Vec<u8>>,
+    /// Input offsets of malformed records, with what is wrong there
+    pub malformed: Vec<(u64, String)>,
+    pub elapsed: Duration,
+}
+
+impl Summary {
+    fn add_key(&mut self, key: &[u8]) {
+        if self.min_key.as_deref().is_none_or(|min| key < min) {
+            self.min_key = Some(key.to_vec());
+        }
+        if self.max_key.as_deref().is_none_or(|max| key > max) {
+            self.max_key = Some(key.to_vec());
+        }
+    }
+
+    fn add_malformed(&mut self, offset: u64, problem: String) {
+        self.malformed.push((offset, problem));
+    }
+
+    /// Prints the summary; an error when the input has malformed records
+    pub fn report(&self) -> Result<(), String> {
+        let hex = |key: &Option<Vec<u8>>| match key {
+            Some(key) => key.iter().map(|b| format!("{:02x}", b)).collect(),
+            None => "-".to_string(),
+        };
+        let secs = self.elapsed.as_secs_f64().max(1e-3);
+        println!("Dry run: the input was read, no database was contacted");
+        println!("Records: {}", self.records);
+        println!(
+            "Bytes: {} ({:.1} MB/s)",
+            self.bytes,
+            self.bytes as f64 / (1024.0 * 1024.0) / secs
+        );
+        println!("Min key: {}", hex(&self.min_key));
+        println!("Max key: {}", hex(&self.max_key));
+        if self.malformed.is_empty() {
+            println!("Malformed records: none");
+            return Ok(());
+        }
+        println!("Malformed records: {}", self.malformed.len());
+        for (offset, problem) in self.malformed.iter().take(MAX_REPORTED) {
+            println!("  offset {}: {}", offset, problem);
+        }
+        if self.malformed.len() > MAX_REPORTED {
+            println!("  ... and {} more", self.malformed.len() - MAX_REPORTED);
+        }
+        Err(format!(
+            "The input has {} malformed records",
+            self.malformed.len()
+        ))
+    }
+}
+
+/// Reads records `skip..skip + limit` of the input at `path`, as a load
+/// would, and checks them. Unlike `records::select`, a kvbin input with
+/// broken framing is read up to the broken record and reported, not refused.
+/// Bytes past the last whole gensort record count as a malformed record when
+/// the selection runs to the end of the input.
+pub fn inspect(
+    format: InputFormat,
+    layout: FixedLayout,
+    path: &Path,
+    skip: u64,
+    limit: Option<u64>,
+) -> io::Result<Summary> {
+    let start = Instant::now();
+    let mut summary = match format {
+        InputFormat::Gensort => inspect_gensort(layout, path, skip, limit)?,
+        InputFormat::Kvbin => inspect_kvbin(path, skip, limit)?,
+    };
+    summary.elapsed = start.elapsed();
+    Ok(summary)
+}
+
+fn inspect_gensort(
+    layout: FixedLayout,
+    path: &Path,
+    skip: u64,
+    limit: Option<u64>,
+) -> io::Result<Summary> {
+    let range = records::select(InputFormat::Gensort, layout, path, skip, limit)?;
+    let mut reader = BufReader::with_capacity(8 * 1024 * 1024, Window::open(path, range.clone())?);
+    let record_size = layout.record_size();
+    let partial = |len: u64| format!("{} bytes of a {}-byte record at the end", len, record_size);
+    let mut summary = Summary::default();
+    let mut record = vec![0u8; record_size];
+    loop {
+        let n = read_full(&mut reader, &mut record)?;
+        if n < record_size {
+            // Only a stream gets here with part of a record
+            if n > 0 {
+                summary.add_malformed(range.start + summary.bytes, partial(n as u64));
+                summary.bytes += n as u64;
+            }
+            break;
+        }
+        summary.add_key(&record[..layout.key_size]);
+        summary.records += 1;
+        summary.bytes += record_size as u64;
+    }
+    if range != records::STREAM {
+        // `select` leaves out a partial record at the end of a file
+        let size = std::fs::metadata(path)?.len();
+        let trailing = size % record_size as u64;
+        if trailing > 0 && range.end == size - trailing {
+            summary.add_malformed(range.end, partial(trailing));
+        }
+    }
+    Ok(summary)
+}
+
+fn inspect_kvbin(path: &Path, skip: u64, limit: Option<u64>) -> io::Result<Summary> {
+    let range = if records::is_stream(path)? {
+        // Let `select` refuse --skip and --limit
+        records::select(InputFormat::Kvbin, FixedLayout::GENSORT, path, skip, limit)?
+    } else {
+        records::STREAM
+    };
+    let mut reader = BufReader::with_capacity(8 * 1024 * 1024, Window::open(path, range)?);
+    let last = limit.map(|limit| skip.saturating_add(limit));
+    let mut summary = Summary::default();
+    let mut key = Vec::new();
+    let mut value = Vec::new();
+    let mut offset = 0u64;
+    let mut index = 0u64;
+    while Some(index) != last {
+        match read_kvbin_record(&mut reader, &mut key, &mut value)? {
+            Framing::Record(len) => {
+                if index >= skip {
+                    summary.add_key(&key);
+                    summary.records += 1;
+                    summary.bytes += len;
+                }
+                offset += len;
+                index += 1;
+            }
+            Framing::End => break,
+            Framing::Malformed(problem) => {
+                // Without the framing nothing after this can be read
+                summary.add_malformed(offset, format!("record {}: {}", index, problem));
+                break;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+enum Framing {
+    /// A record of this many bytes
+    Record(u64),
+    End,
+    /// Framing that cannot be followed: cut off, or an implausible length
+    Malformed(String),
+}
+
+fn read_kvbin_record(
+    reader: &mut impl Read,
+    key: &mut Vec<u8>,
+    value: &mut Vec<u8>,
+) -> io::Result<Framing> {
+    let mut len_buf = [0u8; 4];
+    match read_full(reader, &mut len_buf)? {
+        0 => return Ok(Framing::End),
+        4 => {}
+        n => return Ok(Framing::Malformed(format!("{} bytes of a key length", n))),
+    }
+    let key_len = u32::from_le_bytes(len_buf) as usize;
+    if key_len as u64 > kvbin_index::MAX_PLAUSIBLE_LEN {
+        return Ok(Framing::Malformed(format!(
+            "key length {} is implausible",
+            key_len
+        )));
+    }
+    key.resize(key_len, 0);
+    let n = read_full(reader, key)?;
+    if n < key_len {
+        return Ok(Framing::Malformed(format!(
+            "key of {} bytes, but only {} follow",
+            key_len, n
+        )));
+    }
+    match read_full(reader, &mut len_buf)? {
+        4 => {}
+        n => return Ok(Framing::Malformed(format!("{} bytes of a value length", n))),
+    }
+    let value_len = u32::from_le_bytes(len_buf) as usize;
+    if value_len as u64 > kvbin_index::MAX_PLAUSIBLE_LEN {
+        return Ok(Framing::Malformed(format!(
+            "value length {} is implausible",
+            value_len
+        )));
+    }
+    value.resize(value_len, 0);
+    let n = read_full(reader, value)?;
+    if n < value_len {
+        return Ok(Framing::Malformed(format!(
+            "value of {} bytes, but only {} follow",
+            value_len, n
+        )));
+    }
+    Ok(Framing::Record(8 + key_len as u64 + value_len as u64))
+}
+
+/// Reads until `buf` is full or the input ends; returns the bytes read
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
@@ -0,0 +1,82 @@
+//! Range-partitioned export (`sort-postgres --export-streams N`).
+//!
+//! A binary COPY is one backend encoding and sending every sorted row, which
+//! caps how fast a large sorted output can be written. With `--export-streams
+//! N` the sorter instead samples the key's quantiles (`sample_query`), splits
+//! the keys at them into N ranges (`range_predicate`) and sorts every range
+//! with a COPY of its own, all at once. Range i goes to
+//! `<output stem>.shard<i>.<ext>`, so the files in order are the whole sorted
+//! output, like those of a per-shard sort of key-range shards.
+//!
+//! The sample is `TABLESAMPLE SYSTEM`, whole pages, so it costs a fraction of
+//! a scan; a skewed sample only makes some ranges bigger than others. The
+//! boundaries come back in the key's text form and go into the ranges as
+//! untyped literals, which PostgreSQL reads as the key's own type: a bytea,
+//! text or BIGINT key is split in the order the sort uses, a text key's under
+//! its collation.
+
+/// About how many rows the quantiles are taken from
+pub const SAMPLE_ROWS: u64 = 100_000;
+
+/// Percent of a table of `rows` rows that samples about `SAMPLE_ROWS` of them
+pub fn sample_percent(rows: u64) -> f64 {
+    match rows {
+        0 => 100.0,
+        rows => (SAMPLE_ROWS as f64 * 100.0 / rows as f64).min(100.0),
+    }
+}
+
+/// Selects the N-1 keys that split `table` (of `rows` rows, those matching
+/// `filter` if given) into `streams` ranges, as one text array of their text
+/// form; NULL when the sample is empty
+pub fn sample_query(
+    table: &str,
+    key: &str,
+    filter: Option<&str>,
+    streams: usize,
+    rows: u64,
+) -> String {
+    let fractions: Vec<String> = (1..streams)
+        .map(|i| format!("{:.6}", i as f64 / streams as f64))
+        .collect();
+    let filter = filter
+        .map(|predicate| format!(" WHERE {}", predicate))
+        .unwrap_or_default();
+    format!(
+        "SELECT (percentile_disc(ARRAY[{}]::float8[]) WITHIN GROUP (ORDER BY {}))::text[] \
+         FROM {} TABLESAMPLE SYSTEM ({:.6}){}",
+        fractions.join(", "),
+        key,
+        table,
+        sample_percent(rows),
+        filter
+    )
+}
+
+/// The condition on `key` for range `range` of those `boundaries` split the
+/// keys into: from the boundary before it, up to but without the one after.
+/// Equal boundaries leave the ranges between them empty, as do missing ones
+/// (an empty sample) the ranges past the last. The boundaries are the key's
+/// text form, as `sample_query` selects them.
+pub fn range_predicate(key: &str, boundaries: &[String], range: usize) -> String {
+    if range > boundaries.len() {
+        return "FALSE".to_string();
+    }
+    let mut conditions = Vec::new();
+    if let Some(lower) = range.checked_sub(1).and_then(|i| boundaries.get(i)) {
+        conditions.push(format!("{} >= {}", key, literal(lower)));
+    }
+    if let Some(upper) = boundaries.get(range) {
+        conditions.push(format!("{} < {}", key, literal(upper)));
+    }
+    match conditions.is_empty() {
+        true => "TRUE".to_string(),
+        false => conditions.join(" AND "),
+    }
+}
+
+/// `value` as an untyped literal, which takes the type of the key it is
+/// compared with; COPY takes no bind parameters
+fn literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
@@ -0,0 +1,349 @@
+//! Offset index (`.idx`) files for kvbin inputs.
+//!
+//! kvbin records have variable length, so a file cannot be split at arbitrary
+//! byte offsets. The parallel loaders look for `<input>.idx` next to the input:
+//! a flat array of u64 LE byte offsets, each the start of a record, and hand out
+//! the ranges between them to their reader threads.
+//!
+//! Without an index, `find_boundaries` looks for record starts near evenly
+//! spaced offsets instead. A candidate offset counts as a record start when
+//! the length headers from there on chain together into plausible records
+//! (up to `MAX_PLAUSIBLE_LEN` each) for `CHAIN_RECORDS` records or up to
+//! exactly the end of the file. Random data practically never passes this
+//! check. Long runs of zero bytes do: they look like empty records. So
+//! `verify_boundaries` can confirm the boundaries with one sequential pass.
+//! The same check finds where records resume after a corrupt one
+//! (`next_record_start`, for `--skip-corrupt`).
+
+use crate::records::Window;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::{ControlFlow, Range};
+use std::path::{Path, PathBuf};
+
+/// How far apart index entries are
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Spacing {
+    /// An entry every this many records
+    Records(u64),
+    /// An entry at the first record starting at or after every multiple of this many bytes
+    Bytes(u64),
+}
+
+/// What a scan found
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Index {
+    pub offsets: Vec<u64>,
+    pub records: u64,
+    pub bytes: u64,
+}
+
+/// Where the loaders look for the index of `input`: its path with ".idx" appended
+pub fn path_for(input: &Path) -> PathBuf {
+    let mut path = input.as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+/// Tracks record starts and decides which of them become index entries
+pub struct Builder {
+    spacing: Spacing,
+    index: Index,
+    next_mark: u64,
+}
+
+impl Builder {
+    pub fn new(spacing: Spacing) -> Self {
+        let next_mark = match spacing {
+            Spacing::Records(n) | Spacing::Bytes(n) => n.max(1),
+        };
+        Self {
+            spacing,
+            index: Index::default(),
+            next_mark,
+        }
+    }
+
+    /// Records that a record of `len` bytes starts at the current end of the data
+    pub fn push(&mut self, len: u64) {
+        let (position, step) = match self.spacing {
+            Spacing::Records(n) => (self.index.records, n.max(1)),
+            Spacing::Bytes(n) => (self.index.bytes, n.max(1)),
+        };
+        if self.index.records > 0 && position >= self.next_mark {
+            self.index.offsets.push(self.index.bytes);
+            self.next_mark = (position / step + 1) * step;
+        }
+        self.index.records += 1;
+        self.index.bytes += len;
+    }
+
+    pub fn finish(self) -> Index {
+        self.index
+    }
+}
+
+/// Scans a kvbin stream, skipping over keys and values without reading them
+pub fn scan<R: Read + Seek>(input: R, spacing: Spacing) -> io::Result<Index> {
+    let mut builder = Builder::new(spacing);
+    walk(input, |_, record_len| {
+        builder.push(record_len);
+        Ok(ControlFlow::Continue(()))
+    })?;
+    Ok(builder.finish())
+}
+
+/// Byte range of records `skip..skip + limit` of a kvbin stream (through the
+/// last record without a limit), walking the headers only as far as needed.
+/// Records past the end of the input are left out.
+pub fn record_range<R: Read + Seek>(
+    mut input: R,
+    skip: u64,
+    limit: Option<u64>,
+) -> io::Result<Range<u64>> {
+    let len = input.seek(SeekFrom::End(0))?;
+    let last = limit.map(|limit| skip.saturating_add(limit));
+    let (mut start, mut end) = (None, len);
+    let mut records = 0u64;
+    walk(input, |offset, _| {
+        if records == skip {
+            start = Some(offset);
+        }
+        if Some(records) == last {
+            end = offset;
+            return Ok(ControlFlow::Break(()));
+        }
+        records += 1;
+        Ok(ControlFlow::Continue(()))
+    })?;
+    Ok(start.unwrap_or(end)..end)
+}
+
+/// Checks that every offset in `boundaries` (sorted) is the start of a record,
+/// so ranges between them hold whole records and none is skipped or read twice.
+/// The end of the input counts as a record start. Returns the number of records.
+pub fn verify_boundaries<R: Read + Seek>(input: R, boundaries: &[u64]) -> io::Result<u64> {
+    let mut next = boundaries.iter().peekable();
+    let mut records = 0u64;
+    let mut check = |offset: u64| -> io::Result<()> {
+        while let Some(&&boundary) = next.peek() {
+            if boundary > offset {
+                break;
+            }
+            if boundary < offset {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Boundary {} falls inside a record ending at {}",
+                        boundary, offset
+                    ),
+                ));
+            }
+            next.next();
+        }
+        Ok(())
+    };
+    let mut end = 0;
+    walk(input, |offset, record_len| {
+        records += 1;
+        end = offset + record_len;
+        check(offset).map(ControlFlow::Continue)
+    })?;
+    check(end)?;
+    match next.next() {
+        Some(boundary) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Boundary {} lies past the end of the input at {}",
+                boundary, end
+            ),
+        )),
+        None => Ok(records),
+    }
+}
+
+/// Calls `f(offset, len)` for every record of a kvbin stream, in order, until
+/// it breaks
+fn walk<R: Read + Seek>(
+    input: R,
+    mut f: impl FnMut(u64, u64) -> io::Result<ControlFlow<()>>,
+) -> io::Result<()> {
+    let mut input = BufReader::with_capacity(8 * 1024 * 1024, input);
+    let len = input.seek(SeekFrom::End(0))?;
+    input.seek(SeekFrom::Start(0))?;
+
+    let mut offset = 0u64;
+    let mut len_buf = [0u8; 4];
+    while offset < len {
+        if len - offset < 4 {
+            return Err(truncated(offset));
+        }
+        input.read_exact(&mut len_buf)?;
+        let key_len = u32::from_le_bytes(len_buf) as u64;
+        if len - offset < 8 + key_len {
+            return Err(truncated(offset));
+        }
+        input.seek_relative(key_len as i64)?;
+        input.read_exact(&mut len_buf)?;
+        let value_len = u32::from_le_bytes(len_buf) as u64;
+        let record_len = 8 + key_len + value_len;
+        if len - offset < record_len {
+            return Err(truncated(offset));
+        }
+        input.seek_relative(value_len as i64)?;
+
+        if f(offset, record_len)?.is_break() {
+            break;
+        }
+        offset += record_len;
+    }
+    Ok(())
+}
+
+/// Longest key or value `find_boundaries` believes in, and the default limit
+/// of the loaders' `--max-kvbin-len`
+pub const MAX_PLAUSIBLE_LEN: u64 = 64 << 20;
+
+/// Records that must chain together behind a candidate record start
+pub const CHAIN_RECORDS: usize = 64;
+
+/// Finds record starts that split a kvbin stream into about `parts` ranges,
+/// without an index: from each of the `parts - 1` evenly spaced offsets it
+/// moves forward to the first offset that looks like a record start (see the
+/// module docs). Returns them sorted and without duplicates; a part whose
+/// range has no record start merges into the previous one.
+pub fn find_boundaries<R: Read + Seek>(mut input: R, parts: usize) -> io::Result<Vec<u64>> {
+    let len = input.seek(SeekFrom::End(0))?;
+    let mut boundaries = Vec::new();
+    for part in 1..parts as u64 {
+        let probe = (len as u128 * part as u128 / parts as u128) as u64;
+        let from = probe.max(boundaries.last().map_or(1, |&b| b + 1));
+        let until = (len as u128 * (part + 1) as u128 / parts as u128) as u64;
+        if let Some(start) = next_record_start(&mut input, from..until, MAX_PLAUSIBLE_LEN)? {
+            boundaries.push(start);
+        }
+    }
+    Ok(boundaries)
+}
+
+/// Record starts splitting bytes `range` of an unindexed kvbin file across
+/// `threads` threads, for the loaders: `find_boundaries` with several parts
+/// per thread, checked with `verify_boundaries` when `validate` is set.
+/// Returns absolute offsets with both ends of `range`, like a loaded index.
+pub fn scan_boundaries(
+    input: &Path,
+    range: &Range<u64>,
+    threads: usize,
+    validate: bool,
+) -> io::Result<Vec<u64>> {
+    // Several parts per thread, like an index, so the partitions come out even
+    const PARTS_PER_THREAD: usize = 16;
+
+    println!("No index file found, scanning for record boundaries...");
+    let found = find_boundaries(
+        Window::open(input, range.clone())?,
+        threads * PARTS_PER_THREAD,
+    )?;
+    if validate {
+        let records = verify_boundaries(Window::open(input, range.clone())?, &found)
+            .map_err(|e| io::Error::new(e.kind(), format!("Boundary validation failed: {}", e)))?;
+        println!(
+            "Validated {} boundaries against all {} records",
+            found.len(),
+            records
+        );
+    }
+
+    let mut offsets = vec![range.start];
+    offsets.extend(found.iter().map(|offset| range.start + offset));
+    offsets.push(range.end);
+    offsets.dedup();
+    println!(
+        "Boundaries found: {} offset points, using {} threads",
+        offsets.len(),
+        threads
+    );
+    Ok(offsets)
+}
+
+/// The first offset in `candidates` that looks like a record start (see the
+/// module docs), with keys and values of up to `max_len` bytes
+pub fn next_record_start<R: Read + Seek>(
+    mut input: R,
+    candidates: Range<u64>,
+    max_len: u64,
+) -> io::Result<Option<u64>> {
+    let len = input.seek(SeekFrom::End(0))?;
+    for candidate in candidates.start..candidates.end.min(len) {
+        if is_record_start(&mut input, candidate, len, max_len)? {
+            return Ok(Some(candidate));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether the length headers at `offset` chain into plausible records
+fn is_record_start<R: Read + Seek>(
+    input: &mut R,
+    offset: u64,
+    len: u64,
+    max_len: u64,
+) -> io::Result<bool> {
+    let mut len_buf = [0u8; 4];
+    let mut read_len = |input: &mut R, at: u64| -> io::Result<u64> {
+        input.seek(SeekFrom::Start(at))?;
+        input.read_exact(&mut len_buf)?;
+        Ok(u32::from_le_bytes(len_buf) as u64)
+    };
+    let mut at = offset;
+    for _ in 0..CHAIN_RECORDS {
+        if at == len {
+            return Ok(true);
+        }
+        if len - at < 8 {
+            return Ok(false);
+        }
+        let key_len = read_len(input, at)?;
+        if key_len > max_len || len - at < 8 + key_len {
+            return Ok(false);
+        }
+        let value_len = read_len(input, at + 4 + key_len)?;
+        if value_len > max_len || len - at < 8 + key_len + value_len {
+            return Ok(false);
+        }
+        at += 8 + key_len + value_len;
+    }
+    Ok(true)
+}
+
+fn truncated(offset: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        format!("kvbin input ends inside the record at offset {}", offset),
+    )
+}
+
+/// Writes offsets in the `.idx` layout
+pub fn write(path: &Path, offsets: &[u64]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for offset in offsets {
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+/// Reads an `.idx` file
+pub fn read(path: &Path) -> io::Result<Vec<u64>> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+    if !data.len().is_multiple_of(8) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{:?} is not a whole number of u64 offsets", path),
+        ));
+    }
+    Ok(data
+        .chunks_exact(8)
+        .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+        .collect())
+}
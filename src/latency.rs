@@ -0,0 +1,68 @@
+//! Per-batch ingest latency.
+//!
+//! Loaders record how long each unit of work handed to the database took
+//! (an appender flush, a COPY batch, an HTTP chunk) and print percentiles at
+//! the end, so tail stalls from server-side merges or checkpoints show up even
+//! when the average throughput looks fine. Samples are process-wide, like the
+//! counters in [`crate::metrics`].
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+static SAMPLES: Mutex<Vec<Duration>> = Mutex::new(Vec::new());
+
+pub fn record(latency: Duration) {
+    SAMPLES.lock().unwrap().push(latency);
+}
+
+/// Runs `f` and records how long it took
+pub fn time<T>(f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(start.elapsed());
+    result
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Nearest-rank percentiles of `samples`, or None if there are none
+pub fn summarize(samples: &[Duration]) -> Option<Summary> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    let rank = |p: f64| sorted[((p / 100.0 * sorted.len() as f64).ceil() as usize).max(1) - 1];
+    Some(Summary {
+        count: sorted.len(),
+        p50: rank(50.0),
+        p95: rank(95.0),
+        p99: rank(99.0),
+        max: sorted[sorted.len() - 1],
+    })
+}
+
+/// Prints the BATCH_LATENCY line for everything recorded so far
+pub fn report(what: &str) {
+    let summary = summarize(&SAMPLES.lock().unwrap());
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    match summary {
+        Some(s) => println!(
+            "BATCH_LATENCY ({}): n={} p50={:.2}ms p95={:.2}ms p99={:.2}ms max={:.2}ms",
+            what,
+            s.count,
+            ms(s.p50),
+            ms(s.p95),
+            ms(s.p99),
+            ms(s.max)
+        ),
+        None => println!("BATCH_LATENCY ({}): no samples", what),
+    }
+}
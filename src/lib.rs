@@ -0,0 +1,48 @@
+//! Shared helpers for the es-duck load/sort binaries.
+
+pub mod affinity;
+pub mod buffer_pool;
+pub mod cgroup;
+pub mod ch_query_log;
+pub mod checkpoint;
+pub mod checksum;
+pub mod collation;
+pub mod csv_file;
+pub mod direct;
+pub mod duckdb_profile;
+pub mod export;
+pub mod file_scan;
+pub mod input_files;
+pub mod inspect;
+pub mod key_ranges;
+pub mod kvbin_index;
+pub mod latency;
+pub mod loader;
+pub mod metrics;
+pub mod mmap;
+#[cfg(feature = "util-parquet")]
+pub mod parquet_file;
+pub mod pg_plan;
+pub mod pgcopy;
+pub mod phases;
+pub mod plot;
+pub mod profile;
+pub mod progress;
+pub mod progress_bar;
+pub mod records;
+pub mod results;
+pub mod rowbinary;
+pub mod rss;
+pub mod runs;
+pub mod scale;
+pub mod schema;
+pub mod shards;
+pub mod sorter;
+pub mod spill;
+#[cfg(feature = "util-async")]
+pub mod stream;
+pub mod telemetry;
+pub mod thread_rates;
+pub mod timeout;
+pub mod uring;
+pub mod verify;
@@ -0,0 +1,194 @@
+//! Backend-agnostic loading through the `Loader` trait.
+//!
+//! Every backend creates the benchmark table, takes record batches and reports
+//! how many rows the database ended up with. `Registry` maps backend names to
+//! constructors, so `es-duck load --backend <name>` works for anything that is
+//! registered, including backends that live outside this crate. The table has
+//! the columns of `LoaderConfig::schema`, with the key and payload named by
+//! `LoaderConfig::columns`; what happens to a table that already exists is
+//! `LoaderConfig::if_exists`, the policy the loader binaries share.
+
+use crate::metrics::{self, Phase};
+use crate::records::{Record, RecordReader};
+use crate::schema::{ColumnNames, ExtraColumn, Schema};
+use clap::ValueEnum;
+use std::error::Error;
+use std::io::Read;
+
+#[cfg(feature = "db-clickhouse")]
+mod clickhouse;
+#[cfg(feature = "db-duckdb")]
+mod duckdb;
+#[cfg(feature = "db-postgres")]
+mod postgres;
+
+#[cfg(feature = "db-clickhouse")]
+pub use clickhouse::ClickHouseLoader;
+#[cfg(feature = "db-duckdb")]
+pub use duckdb::DuckDbLoader;
+#[cfg(feature = "db-postgres")]
+pub use postgres::PostgresLoader;
+
+/// Loads (sort_key, payload) records into one table of one database; with
+/// `Schema::Wide` the payload is split into the wide columns
+pub trait Loader {
+    /// Creates the table, first dealing with an existing one as
+    /// `LoaderConfig::if_exists` says
+    fn create_schema(&mut self) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Writes one batch of records
+    fn ingest(&mut self, batch: &[Record]) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Adds `LoaderConfig::extra_columns`, makes everything written so far
+    /// durable and returns the table's row count
+    fn finalize(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>>;
+}
+
+/// Where to load: interpreted by each backend
+#[derive(Clone, Debug)]
+pub struct LoaderConfig {
+    /// Database file (DuckDB), connection string (PostgreSQL) or HTTP URL (ClickHouse)
+    pub connection: String,
+    pub table: String,
+    /// ClickHouse database name
+    pub database: String,
+    pub schema: Schema,
+    pub columns: ColumnNames,
+    /// Columns added once the rows are in (see `ExtraColumn::add_sql`)
+    pub extra_columns: Vec<ExtraColumn>,
+    pub if_exists: IfExists,
+}
+
+/// What a loader does when its table already exists
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum IfExists {
+    /// Stop with an error before loading anything
+    #[default]
+    Fail,
+    /// Drop the table and create it again
+    Replace,
+    /// Load into it, keeping its rows
+    Append,
+}
+
+impl IfExists {
+    /// Applies the policy to `table`, given whether it exists: true when it
+    /// has to be dropped before the load, an error for `Fail`
+    pub fn must_drop(
+        self,
+        table: &str,
+        exists: bool,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        match (self, exists) {
+            (_, false) | (IfExists::Append, true) => Ok(false),
+            (IfExists::Replace, true) => Ok(true),
+            (IfExists::Fail, true) => Err(format!(
+                "Table {} already exists (--if-exists replace drops it, append loads into it)",
+                table
+            )
+            .into()),
+        }
+    }
+}
+
+pub type LoaderFactory = fn(&LoaderConfig) -> Result<Box<dyn Loader>, Box<dyn Error + Send + Sync>>;
+
+/// Backend names and their constructors
+pub struct Registry {
+    backends: Vec<(String, LoaderFactory)>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self {
+            backends: Vec::new(),
+        }
+    }
+
+    /// The backends compiled into this build (see the `db-*` features)
+    pub fn builtin() -> Self {
+        #[allow(unused_mut)]
+        let mut registry = Self::new();
+        #[cfg(feature = "db-duckdb")]
+        registry.register("duckdb", |config| Ok(Box::new(DuckDbLoader::new(config)?)));
+        #[cfg(feature = "db-postgres")]
+        registry.register("postgres", |config| {
+            Ok(Box::new(PostgresLoader::new(config)?))
+        });
+        #[cfg(feature = "db-clickhouse")]
+        registry.register("clickhouse", |config| {
+            Ok(Box::new(ClickHouseLoader::new(config)?))
+        });
+        registry
+    }
+
+    /// Adds a backend, replacing any earlier one with the same name
+    pub fn register(&mut self, name: &str, factory: LoaderFactory) {
+        self.backends.retain(|(existing, _)| existing != name);
+        self.backends.push((name.to_string(), factory));
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.backends
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    pub fn create(
+        &self,
+        name: &str,
+        config: &LoaderConfig,
+    ) -> Result<Box<dyn Loader>, Box<dyn Error + Send + Sync>> {
+        match self.backends.iter().find(|(existing, _)| existing == name) {
+            Some((_, factory)) => factory(config),
+            None => Err(format!(
+                "Unknown backend '{}' (available: {})",
+                name,
+                self.names().join(", ")
+            )
+            .into()),
+        }
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Loads everything `reader` yields in batches of `batch_size` rows. Returns the
+/// number of rows read; fails if the table ends up with fewer than that.
+pub fn run(
+    loader: &mut dyn Loader,
+    reader: &mut RecordReader<impl Read>,
+    batch_size: usize,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    metrics::set_phase(Phase::Setup);
+    loader.create_schema()?;
+
+    metrics::set_phase(Phase::Load);
+    let mut rows = 0u64;
+    while let Some(batch) = reader.next_batch(batch_size)? {
+        loader.ingest(&batch)?;
+        let bytes: usize = batch
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .sum();
+        rows += batch.len() as u64;
+        metrics::add_rows_loaded(batch.len() as u64);
+        metrics::add_bytes_uploaded(bytes as u64);
+    }
+
+    let table_rows = loader.finalize()?;
+    metrics::set_phase(Phase::Done);
+    if table_rows < rows {
+        return Err(format!(
+            "Loaded {} rows, but the table only has {} rows",
+            rows, table_rows
+        )
+        .into());
+    }
+    Ok(rows)
+}
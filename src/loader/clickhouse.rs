@@ -0,0 +1,133 @@
+use super::{IfExists, Loader, LoaderConfig};
+use crate::records::Record;
+use crate::rowbinary::{ColumnType, Encoder, Value};
+use crate::schema::{ColumnNames, ExtraColumn, Schema, WideRow};
+use std::error::Error;
+use tokio::runtime::Runtime;
+
+/// Sends every batch as one RowBinary INSERT over HTTP
+pub struct ClickHouseLoader {
+    runtime: Runtime,
+    http: reqwest::Client,
+    url: String,
+    database: String,
+    table: String,
+    schema: Schema,
+    columns: ColumnNames,
+    extra_columns: Vec<ExtraColumn>,
+    if_exists: IfExists,
+}
+
+impl ClickHouseLoader {
+    pub fn new(config: &LoaderConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            runtime,
+            http: reqwest::Client::new(),
+            url: config.connection.trim_end_matches('/').to_string(),
+            database: config.database.clone(),
+            table: config.table.clone(),
+            schema: config.schema,
+            columns: config.columns.clone(),
+            extra_columns: config.extra_columns.clone(),
+            if_exists: config.if_exists,
+        })
+    }
+
+    /// Runs `query` with `body` appended (the data of an INSERT) and returns the response text
+    fn execute(&self, query: &str, body: Vec<u8>) -> Result<String, Box<dyn Error + Send + Sync>> {
+        self.runtime.block_on(async {
+            let resp = self
+                .http
+                .post(&self.url)
+                .query(&[("database", self.database.as_str()), ("query", query)])
+                .body(body)
+                .send()
+                .await?;
+            let status = resp.status();
+            let text = resp.text().await?;
+            if !status.is_success() {
+                return Err(format!("ClickHouse error: {}", text).into());
+            }
+            Ok(text)
+        })
+    }
+}
+
+impl Loader for ClickHouseLoader {
+    fn create_schema(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (key, payload) = (&self.columns.key, &self.columns.payload);
+        let columns = match self.schema {
+            Schema::KeyPayload => format!("{} String, {} String", key, payload),
+            Schema::Wide => format!(
+                "{} String, c_int Int32, c_bigint Int64, c_double Float64, \
+                 c_text String, {} String",
+                key, payload
+            ),
+        };
+        let exists = self.execute(&format!("EXISTS TABLE {}", self.table), Vec::new())?;
+        if self
+            .if_exists
+            .must_drop(&self.table, exists.trim() == "1")?
+        {
+            self.execute(&format!("DROP TABLE {} SYNC", self.table), Vec::new())?;
+        }
+        let ddl = format!(
+            "CREATE TABLE IF NOT EXISTS {} ({}) ENGINE = MergeTree() ORDER BY tuple()",
+            self.table, columns
+        );
+        self.execute(&ddl, Vec::new())?;
+        Ok(())
+    }
+
+    fn ingest(&mut self, batch: &[Record]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let bytes: usize = batch.iter().map(|(k, v)| k.len() + v.len() + 2).sum();
+        let columns = match self.schema {
+            Schema::KeyPayload => vec![ColumnType::String; 2],
+            Schema::Wide => vec![
+                ColumnType::String,
+                ColumnType::Int32,
+                ColumnType::Int64,
+                ColumnType::Float64,
+                ColumnType::String,
+                ColumnType::String,
+            ],
+        };
+        let mut encoder = Encoder::with_capacity(columns, bytes);
+        for (key, payload) in batch {
+            match self.schema {
+                Schema::KeyPayload => {
+                    encoder.encode_row(&[Value::Bytes(key), Value::Bytes(payload)])?
+                }
+                Schema::Wide => {
+                    let row = WideRow::decode(payload)?;
+                    encoder.encode_row(&[
+                        Value::Bytes(key),
+                        Value::Int32(row.c_int),
+                        Value::Int64(row.c_bigint),
+                        Value::Float64(row.c_double),
+                        Value::Bytes(row.c_text.as_bytes()),
+                        Value::Bytes(row.payload),
+                    ])?
+                }
+            }
+        }
+        let insert = format!(
+            "INSERT INTO {} ({}) FORMAT RowBinary",
+            self.table,
+            self.columns.list(self.schema)
+        );
+        self.execute(&insert, encoder.take())?;
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        for column in &self.extra_columns {
+            self.execute(&column.add_sql(&self.table), Vec::new())?;
+        }
+        let count = self.execute(&format!("SELECT count() FROM {}", self.table), Vec::new())?;
+        Ok(count.trim().parse()?)
+    }
+}
@@ -0,0 +1,92 @@
+use super::{IfExists, Loader, LoaderConfig};
+use crate::records::Record;
+use crate::schema::{ColumnNames, ExtraColumn, Schema, WideRow};
+use duckdb::{Connection, params};
+use std::error::Error;
+
+/// Appends batches to a DuckDB database file
+pub struct DuckDbLoader {
+    conn: Connection,
+    table: String,
+    schema: Schema,
+    columns: ColumnNames,
+    extra_columns: Vec<ExtraColumn>,
+    if_exists: IfExists,
+}
+
+impl DuckDbLoader {
+    pub fn new(config: &LoaderConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self {
+            conn: Connection::open(&config.connection)?,
+            table: config.table.clone(),
+            schema: config.schema,
+            columns: config.columns.clone(),
+            extra_columns: config.extra_columns.clone(),
+            if_exists: config.if_exists,
+        })
+    }
+}
+
+impl Loader for DuckDbLoader {
+    fn create_schema(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (key, payload) = (&self.columns.key, &self.columns.payload);
+        let columns = match self.schema {
+            Schema::KeyPayload => format!("{} BLOB, {} BLOB", key, payload),
+            Schema::Wide => format!(
+                "{} BLOB, c_int INTEGER, c_bigint BIGINT, c_double DOUBLE, \
+                 c_text VARCHAR, {} BLOB",
+                key, payload
+            ),
+        };
+        let exists: i64 = self.conn.query_row(
+            "SELECT count(*) FROM information_schema.tables WHERE table_name = ?",
+            params![self.table],
+            |row| row.get(0),
+        )?;
+        if self.if_exists.must_drop(&self.table, exists > 0)? {
+            self.conn
+                .execute_batch(&format!("DROP TABLE {};", self.table))?;
+        }
+        self.conn.execute(
+            &format!("CREATE TABLE IF NOT EXISTS {} ({});", self.table, columns),
+            [],
+        )?;
+        Ok(())
+    }
+
+    fn ingest(&mut self, batch: &[Record]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // The appender borrows the connection, so it lives for one batch
+        let mut appender = self.conn.appender(&self.table)?;
+        for (key, payload) in batch {
+            match self.schema {
+                Schema::KeyPayload => appender.append_row(params![key, payload])?,
+                Schema::Wide => {
+                    let row = WideRow::decode(payload)?;
+                    appender.append_row(params![
+                        key,
+                        row.c_int,
+                        row.c_bigint,
+                        row.c_double,
+                        row.c_text,
+                        row.payload
+                    ])?
+                }
+            }
+        }
+        appender.flush()?;
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        for column in &self.extra_columns {
+            self.conn.execute_batch(&column.add_sql(&self.table))?;
+        }
+        self.conn.execute_batch("CHECKPOINT;")?;
+        let rows: i64 =
+            self.conn
+                .query_row(&format!("SELECT COUNT(*) FROM {}", self.table), [], |row| {
+                    row.get(0)
+                })?;
+        Ok(rows as u64)
+    }
+}
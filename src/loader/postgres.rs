@@ -0,0 +1,109 @@
+use super::{IfExists, Loader, LoaderConfig};
+use crate::records::Record;
+use crate::schema::{ColumnNames, ExtraColumn, Schema, WideRow};
+use postgres::binary_copy::BinaryCopyInWriter;
+use postgres::types::Type;
+use postgres::{Client, NoTls};
+use std::error::Error;
+
+/// Writes every batch with its own binary COPY
+pub struct PostgresLoader {
+    client: Client,
+    table: String,
+    schema: Schema,
+    columns: ColumnNames,
+    extra_columns: Vec<ExtraColumn>,
+    if_exists: IfExists,
+}
+
+impl PostgresLoader {
+    pub fn new(config: &LoaderConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut client = Client::connect(&config.connection, NoTls)?;
+        client.batch_execute("SET synchronous_commit = off;")?;
+        Ok(Self {
+            client,
+            table: config.table.clone(),
+            schema: config.schema,
+            columns: config.columns.clone(),
+            extra_columns: config.extra_columns.clone(),
+            if_exists: config.if_exists,
+        })
+    }
+}
+
+impl Loader for PostgresLoader {
+    fn create_schema(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (key, payload) = (&self.columns.key, &self.columns.payload);
+        let columns = match self.schema {
+            Schema::KeyPayload => format!("{} BYTEA, {} BYTEA", key, payload),
+            Schema::Wide => format!(
+                "{} BYTEA, c_int INTEGER, c_bigint BIGINT, c_double DOUBLE PRECISION, \
+                 c_text TEXT, {} BYTEA",
+                key, payload
+            ),
+        };
+        let exists: bool = self
+            .client
+            .query_one("SELECT to_regclass($1) IS NOT NULL", &[&self.table])?
+            .get(0);
+        if self.if_exists.must_drop(&self.table, exists)? {
+            self.client
+                .batch_execute(&format!("DROP TABLE {};", self.table))?;
+        }
+        self.client.batch_execute(&format!(
+            "CREATE UNLOGGED TABLE IF NOT EXISTS {} ({});",
+            self.table, columns
+        ))?;
+        Ok(())
+    }
+
+    fn ingest(&mut self, batch: &[Record]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let types: &[Type] = match self.schema {
+            Schema::KeyPayload => &[Type::BYTEA, Type::BYTEA],
+            Schema::Wide => &[
+                Type::BYTEA,
+                Type::INT4,
+                Type::INT8,
+                Type::FLOAT8,
+                Type::TEXT,
+                Type::BYTEA,
+            ],
+        };
+        let copy_stmt = format!(
+            "COPY {} ({}) FROM STDIN BINARY",
+            self.table,
+            self.columns.list(self.schema)
+        );
+        let sink = self.client.copy_in(&copy_stmt)?;
+        let mut writer = BinaryCopyInWriter::new(sink, types);
+        for (key, payload) in batch {
+            match self.schema {
+                Schema::KeyPayload => writer.write(&[key, payload])?,
+                Schema::Wide => {
+                    let row = WideRow::decode(payload)?;
+                    writer.write(&[
+                        key,
+                        &row.c_int,
+                        &row.c_bigint,
+                        &row.c_double,
+                        &row.c_text,
+                        &row.payload,
+                    ])?
+                }
+            }
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        for column in &self.extra_columns {
+            self.client.batch_execute(&column.add_sql(&self.table))?;
+        }
+        let rows: i64 = self
+            .client
+            .query_one(&format!("SELECT COUNT(*) FROM {}", self.table), &[])?
+            .get(0);
+        Ok(rows as u64)
+    }
+}
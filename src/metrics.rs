@@ -0,0 +1,280 @@
+//! Prometheus metrics for long-running loads and sorts.
+//!
+//! The counters are process-wide atomics so the hot loops can bump them without
+//! passing a handle around. They are always updated; `serve` only decides whether
+//! anyone can scrape them, and `record_throughput` whether the per-second samples
+//! are also written to a CSV file.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Setup,
+    Load,
+    Sort,
+    Done,
+}
+
+impl Phase {
+    const ALL: [Phase; 4] = [Phase::Setup, Phase::Load, Phase::Sort, Phase::Done];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Setup => "setup",
+            Phase::Load => "load",
+            Phase::Sort => "sort",
+            Phase::Done => "done",
+        }
+    }
+}
+
+static PHASE: AtomicU8 = AtomicU8::new(0);
+static ROWS_LOADED: AtomicU64 = AtomicU64::new(0);
+static ROWS_SORTED: AtomicU64 = AtomicU64::new(0);
+static BYTES_UPLOADED: AtomicU64 = AtomicU64::new(0);
+static SPILL_BYTES: AtomicU64 = AtomicU64::new(0);
+static SPILL_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+static THROUGHPUT_LOG: Mutex<Option<(BufWriter<File>, Instant)>> = Mutex::new(None);
+
+/// Header of the file written by `record_throughput`
+pub const THROUGHPUT_HEADER: &str = "elapsed_secs,phase,rows_loaded_per_second,rows_sorted_per_second,bytes_uploaded_per_second,spill_bytes";
+
+// Per-second rates maintained by the sampler thread, stored as f64 bits
+static ROWS_LOADED_RATE: AtomicU64 = AtomicU64::new(0);
+static ROWS_SORTED_RATE: AtomicU64 = AtomicU64::new(0);
+static BYTES_UPLOADED_RATE: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_phase(phase: Phase) {
+    if PHASE.swap(phase as u8, Ordering::Relaxed) != phase as u8 {
+        crate::progress::phase_changed();
+    }
+}
+
+pub fn add_rows_loaded(rows: u64) {
+    ROWS_LOADED.fetch_add(rows, Ordering::Relaxed);
+    crate::thread_rates::add_rows(rows);
+}
+
+pub fn add_bytes_uploaded(bytes: u64) {
+    BYTES_UPLOADED.fetch_add(bytes, Ordering::Relaxed);
+    crate::thread_rates::add_bytes(bytes);
+}
+
+/// Records sort progress. Engines report a running total, so this never moves backwards.
+pub fn set_rows_sorted(rows: u64) {
+    ROWS_SORTED.fetch_max(rows, Ordering::Relaxed);
+}
+
+/// Sets the spill usage for engines that report it themselves (PostgreSQL, ClickHouse)
+pub fn set_spill_bytes(bytes: u64) {
+    SPILL_BYTES.store(bytes, Ordering::Relaxed);
+}
+
+/// Measures spill usage as the size of a local temp directory (DuckDB)
+pub fn watch_spill_dir(dir: &Path) {
+    *SPILL_DIR.lock().unwrap() = Some(dir.to_path_buf());
+}
+
+/// Point-in-time view of all metrics
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub phase: Phase,
+    pub rows_loaded: u64,
+    pub rows_sorted: u64,
+    pub bytes_uploaded: u64,
+    pub spill_bytes: u64,
+    pub rows_loaded_per_second: f64,
+    pub rows_sorted_per_second: f64,
+    pub bytes_uploaded_per_second: f64,
+}
+
+pub fn snapshot() -> Snapshot {
+    let rate = |value: &AtomicU64| f64::from_bits(value.load(Ordering::Relaxed));
+    Snapshot {
+        phase: Phase::ALL[PHASE.load(Ordering::Relaxed) as usize],
+        rows_loaded: ROWS_LOADED.load(Ordering::Relaxed),
+        rows_sorted: ROWS_SORTED.load(Ordering::Relaxed),
+        bytes_uploaded: BYTES_UPLOADED.load(Ordering::Relaxed),
+        spill_bytes: SPILL_BYTES.load(Ordering::Relaxed),
+        rows_loaded_per_second: rate(&ROWS_LOADED_RATE),
+        rows_sorted_per_second: rate(&ROWS_SORTED_RATE),
+        bytes_uploaded_per_second: rate(&BYTES_UPLOADED_RATE),
+    }
+}
+
+/// Starts serving the metrics over HTTP on `0.0.0.0:port` from a background thread
+pub fn serve(port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    start_sampler();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // A failed scrape is not worth interrupting the benchmark for
+            let _ = respond(stream);
+        }
+    });
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream) -> io::Result<()> {
+    // Every path returns the metrics, so the request itself is not parsed
+    let mut request = [0u8; 1024];
+    let _ = stream.read(&mut request)?;
+    let body = render();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+/// Renders all metrics in the Prometheus text exposition format
+pub fn render() -> String {
+    let mut out = String::new();
+    let current = PHASE.load(Ordering::Relaxed);
+
+    out.push_str("# HELP es_duck_phase Current benchmark phase (1 for the active phase)\n");
+    out.push_str("# TYPE es_duck_phase gauge\n");
+    for phase in Phase::ALL {
+        out.push_str(&format!(
+            "es_duck_phase{{phase=\"{}\"}} {}\n",
+            phase.name(),
+            (phase as u8 == current) as u8
+        ));
+    }
+
+    let counters = [
+        (
+            "es_duck_rows_loaded_total",
+            "Rows handed to the database",
+            &ROWS_LOADED,
+        ),
+        (
+            "es_duck_rows_sorted_total",
+            "Rows sorted so far",
+            &ROWS_SORTED,
+        ),
+        (
+            "es_duck_bytes_uploaded_total",
+            "Key and payload bytes sent to the database",
+            &BYTES_UPLOADED,
+        ),
+    ];
+    for (name, help, value) in counters {
+        out.push_str(&format!(
+            "# HELP {} {}\n# TYPE {} counter\n",
+            name, help, name
+        ));
+        out.push_str(&format!("{} {}\n", name, value.load(Ordering::Relaxed)));
+    }
+
+    let rates = [
+        (
+            "es_duck_rows_loaded_per_second",
+            "Load throughput over the last second",
+            &ROWS_LOADED_RATE,
+        ),
+        (
+            "es_duck_rows_sorted_per_second",
+            "Sort throughput over the last second",
+            &ROWS_SORTED_RATE,
+        ),
+        (
+            "es_duck_bytes_uploaded_per_second",
+            "Upload throughput over the last second",
+            &BYTES_UPLOADED_RATE,
+        ),
+    ];
+    for (name, help, value) in rates {
+        out.push_str(&format!(
+            "# HELP {} {}\n# TYPE {} gauge\n",
+            name, help, name
+        ));
+        out.push_str(&format!(
+            "{} {:.1}\n",
+            name,
+            f64::from_bits(value.load(Ordering::Relaxed))
+        ));
+    }
+
+    out.push_str("# HELP es_duck_spill_bytes Bytes currently spilled to disk by the engine\n");
+    out.push_str("# TYPE es_duck_spill_bytes gauge\n");
+    out.push_str(&format!(
+        "es_duck_spill_bytes {}\n",
+        SPILL_BYTES.load(Ordering::Relaxed)
+    ));
+
+    out
+}
+
+/// Appends one CSV row per second with the current rates to `path`, for plotting
+/// with `plot-throughput`
+pub fn record_throughput(path: &Path) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "{}", THROUGHPUT_HEADER)?;
+    *THROUGHPUT_LOG.lock().unwrap() = Some((file, Instant::now()));
+    start_sampler();
+    Ok(())
+}
+
+/// Starts the thread behind the rates and the spill directory size. Safe to call more than once.
+pub(crate) fn start_sampler() {
+    static STARTED: Once = Once::new();
+    STARTED.call_once(|| {
+        thread::spawn(sample);
+    });
+}
+
+/// Once a second, turns the counters into rates and re-measures the spill directory
+fn sample() {
+    let tracked = [
+        (&ROWS_LOADED, &ROWS_LOADED_RATE),
+        (&ROWS_SORTED, &ROWS_SORTED_RATE),
+        (&BYTES_UPLOADED, &BYTES_UPLOADED_RATE),
+    ];
+    let mut last = tracked.map(|(counter, _)| counter.load(Ordering::Relaxed));
+    let mut last_time = Instant::now();
+
+    loop {
+        thread::sleep(Duration::from_secs(1));
+        let elapsed = last_time.elapsed().as_secs_f64();
+        last_time = Instant::now();
+
+        for (i, (counter, rate)) in tracked.iter().enumerate() {
+            let value = counter.load(Ordering::Relaxed);
+            let per_second = value.saturating_sub(last[i]) as f64 / elapsed;
+            rate.store(per_second.to_bits(), Ordering::Relaxed);
+            last[i] = value;
+        }
+
+        let spill_dir = SPILL_DIR.lock().unwrap().clone();
+        if let Some(dir) = spill_dir {
+            SPILL_BYTES.store(crate::spill::dir_usage(&dir).bytes, Ordering::Relaxed);
+        }
+
+        if let Some((file, started)) = THROUGHPUT_LOG.lock().unwrap().as_mut() {
+            let m = snapshot();
+            let written = writeln!(
+                file,
+                "{:.3},{},{:.1},{:.1},{:.1},{}",
+                started.elapsed().as_secs_f64(),
+                m.phase.name(),
+                m.rows_loaded_per_second,
+                m.rows_sorted_per_second,
+                m.bytes_uploaded_per_second,
+                m.spill_bytes
+            )
+            .and_then(|_| file.flush());
+            if let Err(e) = written {
+                eprintln!("Warning: failed to write throughput sample: {}", e);
+            }
+        }
+    }
+}
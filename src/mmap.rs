@@ -0,0 +1,109 @@
+//! Read-only memory maps of input files.
+//!
+//! With `--io mmap` the loaders take fixed-width records straight out of the
+//! page cache instead of copying them through a read buffer first (see
+//! `records::FixedRecords`). The file must not shrink while it is mapped:
+//! touching a page past its new end kills the process with SIGBUS.
+
+use std::fs::File;
+use std::io;
+use std::ops::{Deref, Range};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::ptr;
+
+/// Bytes `range` of a file, mapped read-only
+pub struct Mmap {
+    /// Start of the mapping, which begins at the page holding `range.start`
+    base: *mut libc::c_void,
+    mapped: usize,
+    /// Where `range.start` falls in the mapping
+    offset: usize,
+    len: usize,
+}
+
+// The mapping is read-only and owned by this value
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// Maps bytes `range` of the file at `path`, which must hold all of them
+    pub fn open(path: &Path, range: Range<u64>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        if range.end > size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "cannot map bytes {}..{} of a {}-byte file",
+                    range.start, range.end, size
+                ),
+            ));
+        }
+        let len = range.end.saturating_sub(range.start) as usize;
+        if len == 0 {
+            // mmap refuses empty mappings
+            return Ok(Self {
+                base: ptr::null_mut(),
+                mapped: 0,
+                offset: 0,
+                len: 0,
+            });
+        }
+
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        let start = range.start - range.start % page;
+        let offset = (range.start - start) as usize;
+        let base = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                offset + len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                start as libc::off_t,
+            )
+        };
+        if base == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            base,
+            mapped: offset + len,
+            offset,
+            len,
+        })
+    }
+
+    /// Tells the kernel the mapping is read front to back, so it reads ahead
+    /// aggressively and drops pages soon after they were used
+    pub fn advise_sequential(&self) -> io::Result<()> {
+        if self.mapped > 0
+            && unsafe { libc::madvise(self.base, self.mapped, libc::MADV_SEQUENTIAL) } != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts((self.base as *const u8).add(self.offset), self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.mapped > 0 {
+            unsafe {
+                libc::munmap(self.base, self.mapped);
+            }
+        }
+    }
+}
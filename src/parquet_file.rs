@@ -0,0 +1,85 @@
+//! Parquet output for fixed-width records.
+//!
+//! `RecordWriter` stores each record as a row of two BYTE_ARRAY columns,
+//! `sort_key` and `payload`, the same layout the loaders create their tables
+//! with, so a generated file can be queried in place (e.g. DuckDB's
+//! `read_parquet`) or loaded without a conversion step. Columns are
+//! Snappy-compressed; rows are grouped into row groups of a fixed size.
+
+use crate::records::FixedLayout;
+use arrow_array::{ArrayRef, BinaryArray, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::error::Error;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Writes whole records, in order, to a Parquet file
+pub struct RecordWriter {
+    writer: ArrowWriter<File>,
+    schema: SchemaRef,
+    layout: FixedLayout,
+    rows: u64,
+}
+
+impl RecordWriter {
+    /// Creates `path` with row groups of at most `row_group_size` rows
+    pub fn create(
+        path: &Path,
+        layout: FixedLayout,
+        row_group_size: usize,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if row_group_size == 0 {
+            return Err("Parquet row groups must hold at least 1 row".into());
+        }
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("sort_key", DataType::Binary, false),
+            Field::new("payload", DataType::Binary, false),
+        ]));
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(row_group_size)
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let file = File::create(path).map_err(|e| format!("Failed to create {:?}: {}", path, e))?;
+        let writer = ArrowWriter::try_new(file, schema.clone(), Some(properties))?;
+        Ok(Self {
+            writer,
+            schema,
+            layout,
+            rows: 0,
+        })
+    }
+
+    /// Appends every record in `data`, which must hold whole records
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let record_size = self.layout.record_size();
+        if !data.len().is_multiple_of(record_size) {
+            return Err(format!(
+                "{} bytes are not a whole number of {}-byte records",
+                data.len(),
+                record_size
+            )
+            .into());
+        }
+        let key_size = self.layout.key_size;
+        let records = data.chunks_exact(record_size);
+        let keys = BinaryArray::from_iter_values(records.clone().map(|r| &r[..key_size]));
+        let payloads = BinaryArray::from_iter_values(records.map(|r| &r[key_size..]));
+        let batch = RecordBatch::try_new(
+            self.schema.clone(),
+            vec![Arc::new(keys) as ArrayRef, Arc::new(payloads) as ArrayRef],
+        )?;
+        self.writer.write(&batch)?;
+        self.rows += batch.num_rows() as u64;
+        Ok(())
+    }
+
+    /// Flushes the last row group and the footer; returns the rows written
+    pub fn finish(self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        self.writer.close()?;
+        Ok(self.rows)
+    }
+}
@@ -0,0 +1,141 @@
+//! PostgreSQL's `EXPLAIN (ANALYZE, BUFFERS, FORMAT JSON)` output, boiled down
+//! to what a sort run reports.
+//!
+//! `parse` walks the plan tree for the Sort nodes, with the sort method and
+//! space of the leader and of every parallel worker, and for the Gather nodes'
+//! planned and launched workers. The buffer counts are the top node's, which
+//! include everything below it. `planning_time` reads the Planning Time of
+//! that output, or of a text EXPLAIN with SUMMARY.
+
+use serde_json::Value;
+use std::error::Error;
+use std::time::Duration;
+
+/// The metrics of one or more analyzed sorts
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlanStats {
+    /// Distinct sort methods used, e.g. "external merge" or "quicksort", in the
+    /// order they first appear
+    pub sort_methods: Vec<String>,
+    /// Sort space on disk in kB, summed over the leader and the workers
+    pub disk_kb: u64,
+    /// Largest in-memory sort space of one process in kB
+    pub memory_kb: u64,
+    pub workers_planned: u64,
+    pub workers_launched: u64,
+    pub shared_hit_blocks: u64,
+    pub shared_read_blocks: u64,
+    pub temp_read_blocks: u64,
+    pub temp_written_blocks: u64,
+}
+
+/// Parses the single row of `EXPLAIN (ANALYZE, BUFFERS, FORMAT JSON)`
+pub fn parse(json: &str) -> Result<PlanStats, Box<dyn Error + Send + Sync>> {
+    let explained: Value =
+        serde_json::from_str(json).map_err(|e| format!("Invalid EXPLAIN output: {}", e))?;
+    let plan = &explained[0]["Plan"];
+    if !plan.is_object() {
+        return Err("EXPLAIN output has no plan".into());
+    }
+    let blocks = |field: &str| plan[field].as_u64().unwrap_or(0);
+    let mut stats = PlanStats {
+        shared_hit_blocks: blocks("Shared Hit Blocks"),
+        shared_read_blocks: blocks("Shared Read Blocks"),
+        temp_read_blocks: blocks("Temp Read Blocks"),
+        temp_written_blocks: blocks("Temp Written Blocks"),
+        ..PlanStats::default()
+    };
+    add_node(plan, &mut stats);
+    Ok(stats)
+}
+
+/// The Planning Time of EXPLAIN output in JSON, or in text as a
+/// `Planning Time: <ms> ms` line
+pub fn planning_time(explained: &str) -> Option<Duration> {
+    let ms = match serde_json::from_str::<Value>(explained) {
+        Ok(json) => json[0]["Planning Time"].as_f64(),
+        Err(_) => explained.lines().find_map(|line| {
+            line.trim()
+                .strip_prefix("Planning Time:")?
+                .trim()
+                .strip_suffix("ms")?
+                .trim()
+                .parse()
+                .ok()
+        }),
+    }?;
+    Duration::try_from_secs_f64(ms / 1000.0).ok()
+}
+
+/// Adds the sorts and workers of `node` and the nodes below it to `stats`
+fn add_node(node: &Value, stats: &mut PlanStats) {
+    stats.workers_planned += node["Workers Planned"].as_u64().unwrap_or(0);
+    stats.workers_launched += node["Workers Launched"].as_u64().unwrap_or(0);
+    if node["Node Type"] == "Sort" {
+        add_sort(node, stats);
+        for worker in node["Workers"].as_array().into_iter().flatten() {
+            add_sort(worker, stats);
+        }
+    }
+    for child in node["Plans"].as_array().into_iter().flatten() {
+        add_node(child, stats);
+    }
+}
+
+/// Adds one process's share of a Sort node
+fn add_sort(sort: &Value, stats: &mut PlanStats) {
+    if let Some(method) = sort["Sort Method"].as_str()
+        && !stats.sort_methods.iter().any(|m| m == method)
+    {
+        stats.sort_methods.push(method.to_string());
+    }
+    let space = sort["Sort Space Used"].as_u64().unwrap_or(0);
+    match sort["Sort Space Type"].as_str() {
+        Some("Disk") => stats.disk_kb += space,
+        Some("Memory") => stats.memory_kb = stats.memory_kb.max(space),
+        _ => {}
+    }
+}
+
+impl PlanStats {
+    /// Adds the stats of a further sort, e.g. the next shard's: counts add
+    /// up, the memory peak keeps the higher one
+    pub fn merge(&mut self, other: PlanStats) {
+        for method in other.sort_methods {
+            if !self.sort_methods.contains(&method) {
+                self.sort_methods.push(method);
+            }
+        }
+        self.disk_kb += other.disk_kb;
+        self.memory_kb = self.memory_kb.max(other.memory_kb);
+        self.workers_planned += other.workers_planned;
+        self.workers_launched += other.workers_launched;
+        self.shared_hit_blocks += other.shared_hit_blocks;
+        self.shared_read_blocks += other.shared_read_blocks;
+        self.temp_read_blocks += other.temp_read_blocks;
+        self.temp_written_blocks += other.temp_written_blocks;
+    }
+
+    /// `KEY: value` lines that go next to TIMING
+    pub fn report(&self) -> String {
+        let methods = match self.sort_methods.is_empty() {
+            true => "none".to_string(),
+            false => self.sort_methods.join(", "),
+        };
+        format!(
+            "SORT_METHOD: {}\nSORT_DISK_KB: {}\nSORT_MEMORY_KB: {}\n\
+             WORKERS_PLANNED: {}\nWORKERS_LAUNCHED: {}\n\
+             SHARED_HIT_BLOCKS: {}\nSHARED_READ_BLOCKS: {}\n\
+             TEMP_READ_BLOCKS: {}\nTEMP_WRITTEN_BLOCKS: {}",
+            methods,
+            self.disk_kb,
+            self.memory_kb,
+            self.workers_planned,
+            self.workers_launched,
+            self.shared_hit_blocks,
+            self.shared_read_blocks,
+            self.temp_read_blocks,
+            self.temp_written_blocks
+        )
+    }
+}
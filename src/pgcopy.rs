@@ -0,0 +1,115 @@
+//! PostgreSQL binary COPY format (`COPY ... (FORMAT BINARY)`).
+//!
+//! A file starts with an 11-byte signature, a flags word and a header extension
+//! area, followed by one tuple per row (a field count, then a length-prefixed
+//! value per field) and a trailer of -1. All integers are big-endian.
+//! NULL fields (length -1) are rejected: the benchmark tables have none.
+
+use std::io::{self, Read, Write};
+
+/// Signature at the start of every binary COPY file
+pub const SIGNATURE: &[u8; 11] = b"PGCOPY\n\xff\r\n\0";
+
+/// Reads tuples from a binary COPY stream
+pub struct Reader<R: Read> {
+    inner: R,
+    done: bool,
+}
+
+impl<R: Read> Reader<R> {
+    /// Checks the signature and skips the header
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut signature = [0u8; 11];
+        inner.read_exact(&mut signature)?;
+        if &signature != SIGNATURE {
+            return Err(invalid("not a binary COPY stream"));
+        }
+
+        // Flags field, then the header extension area which we skip
+        let mut word = [0u8; 4];
+        inner.read_exact(&mut word)?;
+        inner.read_exact(&mut word)?;
+        let extension_len = u32::from_be_bytes(word) as u64;
+        io::copy(&mut (&mut inner).take(extension_len), &mut io::sink())?;
+
+        Ok(Self { inner, done: false })
+    }
+
+    /// Reads the next tuple into `fields`, reusing its buffers. Returns false at the trailer.
+    pub fn read_row(&mut self, fields: &mut Vec<Vec<u8>>) -> io::Result<bool> {
+        if self.done {
+            return Ok(false);
+        }
+        let mut field_count = [0u8; 2];
+        self.inner.read_exact(&mut field_count)?;
+        let count = i16::from_be_bytes(field_count);
+        if count == -1 {
+            self.done = true;
+            return Ok(false);
+        }
+        if count < 0 {
+            return Err(invalid(&format!("invalid field count {}", count)));
+        }
+
+        fields.resize_with(count as usize, Vec::new);
+        for field in fields.iter_mut() {
+            let mut len_buf = [0u8; 4];
+            self.inner.read_exact(&mut len_buf)?;
+            let len = i32::from_be_bytes(len_buf);
+            if len < 0 {
+                return Err(invalid("unexpected NULL field"));
+            }
+            field.resize(len as usize, 0);
+            self.inner.read_exact(field)?;
+        }
+        Ok(true)
+    }
+
+    /// Reads the next tuple, or None at the trailer
+    pub fn next_row(&mut self) -> io::Result<Option<Vec<Vec<u8>>>> {
+        let mut fields = Vec::new();
+        Ok(self.read_row(&mut fields)?.then_some(fields))
+    }
+}
+
+/// Writes tuples as a binary COPY stream that `COPY ... FROM` accepts
+pub struct Writer<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Writes the signature and an empty header
+    pub fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(SIGNATURE)?;
+        inner.write_all(&0i32.to_be_bytes())?; // flags
+        inner.write_all(&0i32.to_be_bytes())?; // header extension length
+        Ok(Self { inner })
+    }
+
+    pub fn write_row(&mut self, fields: &[&[u8]]) -> io::Result<()> {
+        let count = i16::try_from(fields.len()).map_err(|_| invalid("too many fields"))?;
+        self.inner.write_all(&count.to_be_bytes())?;
+        for field in fields {
+            let len = i32::try_from(field.len()).map_err(|_| invalid("field too large"))?;
+            self.inner.write_all(&len.to_be_bytes())?;
+            self.inner.write_all(field)?;
+        }
+        Ok(())
+    }
+
+    /// The underlying writer, e.g. to drain a `Vec` into chunks between rows
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Writes the trailer and returns the underlying writer, flushed
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.write_all(&(-1i16).to_be_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
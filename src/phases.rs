@@ -0,0 +1,70 @@
+//! Where a sort's time goes: planning, execution and writing the output.
+//!
+//! TIMING is one number per sort, so a slow export and a slow merge look the
+//! same in it. The sorters split it into `PHASE_PLAN`, `PHASE_EXECUTE` and
+//! `PHASE_EXPORT` lines, from what each engine measures itself:
+//!
+//! - DuckDB: the planner, optimizer and physical planner metrics of the JSON
+//!   profile, and the COPY_TO_FILE operator's share of the CPU time applied
+//!   to the execution's wall time (its own time is summed over its threads),
+//!   or the time spent writing rows that come back to the client.
+//! - PostgreSQL: the Planning Time of EXPLAIN ANALYZE, or of an EXPLAIN
+//!   (SUMMARY) of the SELECT before a COPY, and the time spent writing rows
+//!   that come back to the client. A server-side COPY writes as it sorts, so
+//!   its export is part of execution.
+//! - ClickHouse: the query_log duration, with the output format's or the
+//!   table sink's time from processors_profile_log as the export. ClickHouse
+//!   logs no planning time; the EXPLAIN before the runs stands in for it.
+//!
+//! Execution is what is left of the sort's time after the other two. The
+//! phases are those of the last run, like the other statistics.
+
+use std::time::Duration;
+
+/// A sort's time by phase; None where the engine doesn't tell
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PhaseTimes {
+    pub plan: Option<Duration>,
+    pub execute: Option<Duration>,
+    pub export: Option<Duration>,
+}
+
+impl PhaseTimes {
+    /// Splits `total` into `plan`, `export` and the execution in between
+    pub fn split(total: Duration, plan: Option<Duration>, export: Option<Duration>) -> Self {
+        let execute = total
+            .saturating_sub(plan.unwrap_or_default())
+            .saturating_sub(export.unwrap_or_default());
+        Self {
+            plan,
+            execute: Some(execute),
+            export,
+        }
+    }
+
+    /// Adds the phases of a further query, e.g. the next shard's sort
+    pub fn merge(&mut self, other: PhaseTimes) {
+        let add = |a: Option<Duration>, b: Option<Duration>| match (a, b) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        self.plan = add(self.plan, other.plan);
+        self.execute = add(self.execute, other.execute);
+        self.export = add(self.export, other.export);
+    }
+
+    /// `PHASE_<name>:` lines in seconds that go next to TIMING, "unavailable"
+    /// for a phase the engine doesn't tell
+    pub fn report(&self) -> String {
+        let secs = |phase: Option<Duration>| match phase {
+            Some(time) => format!("{:.3}", time.as_secs_f64()),
+            None => "unavailable".to_string(),
+        };
+        format!(
+            "PHASE_PLAN: {}\nPHASE_EXECUTE: {}\nPHASE_EXPORT: {}",
+            secs(self.plan),
+            secs(self.execute),
+            secs(self.export)
+        )
+    }
+}
@@ -0,0 +1,190 @@
+//! SVG plots of the per-second samples written by `metrics::record_throughput`.
+//!
+//! Every non-empty series gets its own panel on a shared time axis, and phase
+//! changes are marked with a dashed line across all panels, so stalls such as
+//! appender flushes or spill waves stand out without any external tooling.
+
+use std::error::Error;
+use std::fmt::Write;
+
+const WIDTH: f64 = 900.0;
+const PANEL_HEIGHT: f64 = 160.0;
+const PANEL_GAP: f64 = 50.0;
+const LEFT: f64 = 80.0;
+const RIGHT: f64 = 20.0;
+const TOP: f64 = 50.0;
+const X_TICKS: usize = 5;
+
+pub struct Samples {
+    pub elapsed_secs: Vec<f64>,
+    pub phases: Vec<String>,
+    /// Column name and one value per sample
+    pub series: Vec<(String, Vec<f64>)>,
+}
+
+/// Parses a throughput CSV: `elapsed_secs`, `phase`, then any number of numeric columns
+pub fn parse_samples(csv: &str) -> Result<Samples, Box<dyn Error + Send + Sync>> {
+    let mut lines = csv.lines().filter(|l| !l.trim().is_empty());
+    let header: Vec<&str> = lines
+        .next()
+        .ok_or("Empty throughput file")?
+        .split(',')
+        .collect();
+    if header.len() < 3 || header[0] != "elapsed_secs" || header[1] != "phase" {
+        return Err(format!("Unexpected throughput header: {}", header.join(",")).into());
+    }
+
+    let mut samples = Samples {
+        elapsed_secs: Vec::new(),
+        phases: Vec::new(),
+        series: header[2..]
+            .iter()
+            .map(|name| (name.to_string(), Vec::new()))
+            .collect(),
+    };
+    for (i, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() != header.len() {
+            return Err(format!("Line {}: expected {} fields", i + 2, header.len()).into());
+        }
+        samples.elapsed_secs.push(fields[0].parse()?);
+        samples.phases.push(fields[1].to_string());
+        for (column, field) in samples.series.iter_mut().zip(&fields[2..]) {
+            column.1.push(field.parse()?);
+        }
+    }
+    Ok(samples)
+}
+
+/// Renders one panel per series that has any non-zero value
+pub fn render_svg(samples: &Samples, title: &str) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let panels: Vec<&(String, Vec<f64>)> = samples
+        .series
+        .iter()
+        .filter(|(_, values)| values.iter().any(|&v| v > 0.0))
+        .collect();
+    if panels.is_empty() {
+        return Err("Nothing to plot: every series is zero".into());
+    }
+
+    let height = TOP + panels.len() as f64 * (PANEL_HEIGHT + PANEL_GAP);
+    let plot_width = WIDTH - LEFT - RIGHT;
+    let max_x = samples
+        .elapsed_secs
+        .iter()
+        .cloned()
+        .fold(0.0, f64::max)
+        .max(1.0);
+    let x = |secs: f64| LEFT + secs / max_x * plot_width;
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="11">"#,
+        w = WIDTH,
+        h = height
+    )?;
+    writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+    writeln!(
+        svg,
+        r#"<text x="{}" y="24" font-size="15" text-anchor="middle">{}</text>"#,
+        WIDTH / 2.0,
+        escape(title)
+    )?;
+
+    for (p, (name, values)) in panels.iter().enumerate() {
+        let top = TOP + p as f64 * (PANEL_HEIGHT + PANEL_GAP);
+        let bottom = top + PANEL_HEIGHT;
+        let max_y = values.iter().cloned().fold(0.0, f64::max);
+        let y = |v: f64| bottom - v / max_y * PANEL_HEIGHT;
+
+        writeln!(
+            svg,
+            r#"<text x="{}" y="{}" font-weight="bold">{}</text>"#,
+            LEFT,
+            top - 8.0,
+            escape(name)
+        )?;
+        writeln!(
+            svg,
+            r##"<rect x="{}" y="{}" width="{}" height="{}" fill="none" stroke="#999"/>"##,
+            LEFT, top, plot_width, PANEL_HEIGHT
+        )?;
+        for (v, ypos) in [
+            (max_y, top),
+            (max_y / 2.0, top + PANEL_HEIGHT / 2.0),
+            (0.0, bottom),
+        ] {
+            writeln!(
+                svg,
+                r#"<text x="{}" y="{}" text-anchor="end" dominant-baseline="middle">{}</text>"#,
+                LEFT - 6.0,
+                ypos,
+                si(v)
+            )?;
+        }
+        for t in 0..=X_TICKS {
+            let secs = max_x * t as f64 / X_TICKS as f64;
+            writeln!(
+                svg,
+                r#"<text x="{}" y="{}" text-anchor="middle">{:.0}s</text>"#,
+                x(secs),
+                bottom + 14.0,
+                secs
+            )?;
+        }
+
+        let points: Vec<String> = samples
+            .elapsed_secs
+            .iter()
+            .zip(values.iter())
+            .map(|(&secs, &v)| format!("{:.1},{:.1}", x(secs), y(v)))
+            .collect();
+        writeln!(
+            svg,
+            r##"<polyline fill="none" stroke="#1f77b4" stroke-width="1.5" points="{}"/>"##,
+            points.join(" ")
+        )?;
+    }
+
+    // Phase boundaries across all panels
+    let last_bottom = height - PANEL_GAP;
+    for i in 1..samples.phases.len() {
+        if samples.phases[i] != samples.phases[i - 1] {
+            let xpos = x(samples.elapsed_secs[i]);
+            writeln!(
+                svg,
+                r##"<line x1="{x}" y1="{}" x2="{x}" y2="{}" stroke="#d62728" stroke-dasharray="4,3"/>"##,
+                TOP,
+                last_bottom,
+                x = xpos
+            )?;
+            writeln!(
+                svg,
+                r##"<text x="{}" y="{}" fill="#d62728">{}</text>"##,
+                xpos + 3.0,
+                TOP + 12.0,
+                escape(&samples.phases[i])
+            )?;
+        }
+    }
+
+    writeln!(svg, "</svg>")?;
+    Ok(svg)
+}
+
+/// Formats a value with a k/M/G suffix for axis labels
+fn si(v: f64) -> String {
+    match v {
+        v if v >= 1e9 => format!("{:.1}G", v / 1e9),
+        v if v >= 1e6 => format!("{:.1}M", v / 1e6),
+        v if v >= 1e3 => format!("{:.1}k", v / 1e3),
+        v => format!("{:.0}", v),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
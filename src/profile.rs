@@ -0,0 +1,65 @@
+//! Optional CPU profiling of the client-side data path.
+//!
+//! Built with `util-profile`, `--profile <file.svg>` samples every thread of the
+//! process with pprof and writes a flamegraph when the run finishes. This covers
+//! the loaders' readers and encoders and DuckDB's in-process sort; the
+//! PostgreSQL and ClickHouse sorts run inside the server and need `perf` there.
+
+use std::error::Error;
+use std::path::Path;
+
+/// Writes the flamegraph when dropped. Keep it alive for the part of `main` to profile.
+pub struct ProfileGuard {
+    #[cfg(feature = "util-profile")]
+    active: Option<(pprof::ProfilerGuard<'static>, std::path::PathBuf)>,
+}
+
+#[cfg(not(feature = "util-profile"))]
+pub fn start(output: Option<&Path>) -> Result<ProfileGuard, Box<dyn Error + Send + Sync>> {
+    match output {
+        Some(_) => Err("--profile requires building with --features util-profile".into()),
+        None => Ok(ProfileGuard {}),
+    }
+}
+
+#[cfg(feature = "util-profile")]
+pub fn start(output: Option<&Path>) -> Result<ProfileGuard, Box<dyn Error + Send + Sync>> {
+    // Odd frequency so sampling does not line up with periodic work
+    const FREQUENCY_HZ: i32 = 997;
+
+    let Some(output) = output else {
+        return Ok(ProfileGuard { active: None });
+    };
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(FREQUENCY_HZ)
+        .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+        .build()?;
+    println!(
+        "Profiling at {} Hz, flamegraph will be written to {:?}",
+        FREQUENCY_HZ, output
+    );
+    Ok(ProfileGuard {
+        active: Some((guard, output.to_path_buf())),
+    })
+}
+
+#[cfg(feature = "util-profile")]
+impl Drop for ProfileGuard {
+    fn drop(&mut self) {
+        let Some((guard, output)) = self.active.take() else {
+            return;
+        };
+        let written = guard
+            .report()
+            .build()
+            .map_err(|e| e.to_string())
+            .and_then(|report| {
+                let file = std::fs::File::create(&output).map_err(|e| e.to_string())?;
+                report.flamegraph(file).map_err(|e| e.to_string())
+            });
+        match written {
+            Ok(()) => println!("Wrote flamegraph to {:?}", output),
+            Err(e) => eprintln!("Warning: failed to write flamegraph: {}", e),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Periodic progress events as newline-delimited JSON on stderr.
+//!
+//! Each line is one self-contained JSON object built from the counters in
+//! [`crate::metrics`], so wrapping tools can follow a run without parsing the
+//! human-readable output on stdout. Besides the periodic events, one is emitted
+//! on every phase change, which guarantees a final `"phase":"done"` event.
+
+use crate::metrics;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+static STARTED_AT: OnceLock<Instant> = OnceLock::new();
+
+/// Emits an event every `interval` from a background thread until the process exits
+pub fn start(interval: Duration) {
+    if STARTED_AT.set(Instant::now()).is_err() {
+        return;
+    }
+    metrics::start_sampler();
+    thread::spawn(move || {
+        loop {
+            thread::sleep(interval);
+            emit();
+        }
+    });
+}
+
+pub(crate) fn phase_changed() {
+    if STARTED_AT.get().is_some() {
+        emit();
+    }
+}
+
+fn emit() {
+    eprintln!("{}", event());
+}
+
+/// Renders the current progress as a single-line JSON object
+pub fn event() -> String {
+    let elapsed = STARTED_AT.get().map_or(0.0, |t| t.elapsed().as_secs_f64());
+    let m = metrics::snapshot();
+    format!(
+        concat!(
+            "{{\"event\":\"progress\",\"phase\":\"{}\",\"elapsed_secs\":{:.3},",
+            "\"rows_loaded\":{},\"rows_sorted\":{},\"bytes_uploaded\":{},\"spill_bytes\":{},",
+            "\"rows_loaded_per_second\":{:.1},\"rows_sorted_per_second\":{:.1},",
+            "\"bytes_uploaded_per_second\":{:.1}}}"
+        ),
+        m.phase.name(),
+        elapsed,
+        m.rows_loaded,
+        m.rows_sorted,
+        m.bytes_uploaded,
+        m.spill_bytes,
+        m.rows_loaded_per_second,
+        m.rows_sorted_per_second,
+        m.bytes_uploaded_per_second,
+    )
+}
@@ -0,0 +1,123 @@
+//! Terminal progress bars for the loaders and generators.
+//!
+//! A bar shows records done (out of the total when it is known), bytes, MB/s
+//! and an ETA on stderr. It is only drawn when stderr is a terminal, and the
+//! binaries' `--no-progress` turns it off entirely, so scripted runs and the
+//! JSON events from [`crate::progress`] stay clean. Loaders don't update the
+//! bar from their hot loops: `follow_rows_loaded` polls the counters in
+//! [`crate::metrics`] that those loops already bump. Generators call `set`.
+
+use crate::metrics;
+use crate::records::{self, FixedLayout, InputFormat};
+use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use std::fmt::Write;
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+const MB: f64 = 1024.0 * 1024.0;
+
+/// A progress bar counting records, with a byte count alongside
+#[derive(Clone)]
+pub struct Bar {
+    bar: ProgressBar,
+    bytes: Arc<AtomicU64>,
+}
+
+impl Bar {
+    /// A bar towards `records` records, or an open-ended counter when the total
+    /// is unknown; hidden when `enabled` is false
+    pub fn new(records: Option<u64>, enabled: bool) -> Self {
+        let bar = match (enabled, records) {
+            (false, _) => ProgressBar::hidden(),
+            (true, Some(records)) => ProgressBar::new(records),
+            (true, None) => ProgressBar::no_length(),
+        };
+        let bytes = Arc::new(AtomicU64::new(0));
+        let template = if records.is_some() {
+            "{spinner} [{elapsed_precise}] [{wide_bar}] {human_pos}/{human_len} records, {bytes}, {throughput}, ETA {eta}"
+        } else {
+            "{spinner} [{elapsed_precise}] {human_pos} records, {bytes}, {throughput}"
+        };
+        let done = bytes.clone();
+        let rate = bytes.clone();
+        let style = ProgressStyle::with_template(template)
+            .expect("valid template")
+            .progress_chars("=> ")
+            .with_key("bytes", move |_: &ProgressState, w: &mut dyn Write| {
+                let _ = write!(w, "{:.1} MB", done.load(Ordering::Relaxed) as f64 / MB);
+            })
+            .with_key(
+                "throughput",
+                move |state: &ProgressState, w: &mut dyn Write| {
+                    let secs = state.elapsed().as_secs_f64().max(1e-3);
+                    let _ = write!(
+                        w,
+                        "{:.1} MB/s",
+                        rate.load(Ordering::Relaxed) as f64 / MB / secs
+                    );
+                },
+            );
+        bar.set_style(style);
+        Self { bar, bytes }
+    }
+
+    /// Sets the records and bytes done so far
+    pub fn set(&self, records: u64, bytes: u64) {
+        self.bytes.store(bytes, Ordering::Relaxed);
+        self.bar.set_position(records);
+    }
+
+    /// Records done so far
+    pub fn records(&self) -> u64 {
+        self.bar.position()
+    }
+
+    /// Bytes done so far
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Follows the rows loaded according to [`crate::metrics`] from a
+    /// background thread until `finish`. Bytes are `record_bytes` per row for
+    /// fixed-size records, otherwise the loader's uploaded bytes.
+    pub fn follow_rows_loaded(&self, record_bytes: Option<u64>) {
+        let bar = self.clone();
+        thread::spawn(move || {
+            // Not drawn right away, so messages printed as the load starts
+            // come out before the bar
+            loop {
+                thread::sleep(Duration::from_millis(200));
+                if bar.bar.is_finished() {
+                    break;
+                }
+                let m = metrics::snapshot();
+                let bytes = record_bytes.map_or(m.bytes_uploaded, |size| m.rows_loaded * size);
+                bar.set(m.rows_loaded, bytes);
+            }
+        });
+    }
+
+    /// Stops the bar, leaving its last state on screen
+    pub fn finish(&self) {
+        self.bar.finish();
+    }
+}
+
+/// A bar following the load of bytes `range` of an input (see
+/// `records::select`). Fixed-size records give the total, and so an ETA; for
+/// kvbin or a stream input the count is unknown until the end.
+pub fn load(format: InputFormat, layout: FixedLayout, range: &Range<u64>, enabled: bool) -> Bar {
+    let record_bytes = match format {
+        InputFormat::Gensort => Some(layout.record_size() as u64),
+        InputFormat::Kvbin => None,
+    };
+    let total = record_bytes
+        .filter(|_| *range != records::STREAM)
+        .map(|size| (range.end - range.start) / size);
+    let bar = Bar::new(total, enabled);
+    bar.follow_rows_loaded(record_bytes);
+    bar
+}
@@ -0,0 +1,743 @@
+//! Reading key/payload records from the benchmark input formats.
+//!
+//! The per-engine loaders parse their input inline for speed; this reader is the
+//! plain sequential version used by the backend-agnostic `Loader` path.
+//!
+//! `--skip` and `--limit` select a run of records, which `select` turns into
+//! the bytes holding them. A `Window` over those bytes looks like a file of its
+//! own, so the loaders read a selection the way they read a whole input.
+//!
+//! An input can also be a FIFO or another stream (`is_stream`), e.g. fed by
+//! `generate-gensort --output`. It has no size to split between threads, so
+//! `select` gives `STREAM` for it and the loaders read it to its end once.
+//!
+//! `FixedRecords` is the loaders' inline gensort reader. It hands out records
+//! in place, from its read buffer or, with `Io::Mmap`, from the mapped file.
+//! `Io::Uring` fills the read buffer from `uring::UringReader` instead of
+//! plain reads, and `--direct-io` from a `direct::DirectReader`. `KvbinReader`
+//! is their kvbin reader: it checks each record's lengths before trusting
+//! them, and with `--skip-corrupt` resumes after a corrupt record.
+
+use crate::direct::DirectReader;
+use crate::kvbin_index;
+use crate::mmap::Mmap;
+use crate::uring::{self, UringReader};
+use clap::{Args, ValueEnum};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+/// A single (sort_key, payload) record
+pub type Record = (Vec<u8>, Vec<u8>);
+pub type RecordBatch = Vec<Record>;
+/// A borrowed record with the input offset it starts at
+pub type RecordAt<'a> = (u64, &'a [u8], &'a [u8]);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    /// Fixed-width records: 10-byte key, 90-byte payload unless set with `with_layout`
+    Gensort,
+    /// Length-prefixed records: u32 LE key length, key, u32 LE value length, value
+    Kvbin,
+}
+
+/// Key and payload sizes of fixed-width (gensort) records; the loaders take
+/// them as `--key-size` and `--payload-size`, the sizes given to generate-gensort
+#[derive(Args, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FixedLayout {
+    /// Key bytes per gensort record, as given to generate-gensort
+    #[arg(long, default_value_t = 10)]
+    pub key_size: usize,
+
+    /// Payload bytes per gensort record, as given to generate-gensort
+    #[arg(long, default_value_t = 90)]
+    pub payload_size: usize,
+}
+
+impl FixedLayout {
+    /// Standard gensort records: 10-byte key, 90-byte payload
+    pub const GENSORT: FixedLayout = FixedLayout {
+        key_size: 10,
+        payload_size: 90,
+    };
+
+    pub fn record_size(&self) -> usize {
+        self.key_size + self.payload_size
+    }
+
+    /// Refuses records of no bytes, which no input can be split into
+    pub fn check(&self) -> Result<(), String> {
+        match self.record_size() {
+            0 => Err("--key-size + --payload-size must be at least 1".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for FixedLayout {
+    fn default() -> Self {
+        Self::GENSORT
+    }
+}
+
+/// Reads records one batch at a time from any `Read`
+pub struct RecordReader<R: Read> {
+    format: InputFormat,
+    layout: FixedLayout,
+    inner: R,
+}
+
+impl RecordReader<BufReader<File>> {
+    pub fn open(format: InputFormat, path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self::new(
+            format,
+            BufReader::with_capacity(8 * 1024 * 1024, file),
+        ))
+    }
+}
+
+impl<R: Read> RecordReader<R> {
+    pub fn new(format: InputFormat, inner: R) -> Self {
+        Self {
+            format,
+            layout: FixedLayout::GENSORT,
+            inner,
+        }
+    }
+
+    /// Record sizes for `InputFormat::Gensort`; ignored for kvbin
+    pub fn with_layout(mut self, layout: FixedLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn format(&self) -> InputFormat {
+        self.format
+    }
+
+    pub fn layout(&self) -> FixedLayout {
+        self.layout
+    }
+
+    /// Reads the next record, or None at a clean end of input
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        match self.format {
+            InputFormat::Gensort => {
+                let mut key = vec![0u8; self.layout.record_size()];
+                if !self.read_first(&mut key)? {
+                    return Ok(None);
+                }
+                let payload = key.split_off(self.layout.key_size);
+                Ok(Some((key, payload)))
+            }
+            InputFormat::Kvbin => {
+                let mut len_buf = [0u8; 4];
+                if !self.read_first(&mut len_buf)? {
+                    return Ok(None);
+                }
+                let mut key = vec![0u8; u32::from_le_bytes(len_buf) as usize];
+                self.inner.read_exact(&mut key)?;
+                self.inner.read_exact(&mut len_buf)?;
+                let mut value = vec![0u8; u32::from_le_bytes(len_buf) as usize];
+                self.inner.read_exact(&mut value)?;
+                Ok(Some((key, value)))
+            }
+        }
+    }
+
+    /// Reads up to `max_rows` records, or None once the input is exhausted
+    pub fn next_batch(&mut self, max_rows: usize) -> io::Result<Option<RecordBatch>> {
+        let mut batch = Vec::with_capacity(max_rows);
+        while batch.len() < max_rows {
+            match self.next_record()? {
+                Some(record) => batch.push(record),
+                None => break,
+            }
+        }
+        Ok((!batch.is_empty()).then_some(batch))
+    }
+
+    /// Fills `buf` with the start of a record. Returns false if the input ended
+    /// exactly at a record boundary, and errors if it ended inside the record.
+    fn read_first(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match self.inner.read(&mut buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(false),
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "input ends inside a record",
+                    ));
+                }
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// How the loaders read fixed-width input
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Io {
+    /// Buffered reads, copying every record out of the file
+    #[default]
+    Read,
+    /// Map the input and take records straight from the page cache
+    Mmap,
+    /// Buffered reads, with the file read ahead by io_uring
+    Uring,
+}
+
+/// How a loader reads its input (`--io` and its settings)
+#[derive(Args, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IoOptions {
+    /// How to read gensort input: read copies each record through a buffer,
+    /// mmap maps the file and uses records in place, uring reads ahead with
+    /// io_uring (needs the util-uring feature, Linux only)
+    #[arg(long = "io", value_enum, default_value_t = Io::Read)]
+    pub mode: Io,
+
+    /// Reads in flight per reader thread with --io uring
+    #[arg(long, default_value_t = 32)]
+    pub queue_depth: u32,
+
+    /// Read the input with O_DIRECT, bypassing the page cache, so an input
+    /// still cached from generating it doesn't flatter the load (--io read only)
+    #[arg(long)]
+    pub direct_io: bool,
+
+    /// Longest key or value a kvbin record may have; a longer length header
+    /// is taken for corrupt input
+    #[arg(long, default_value_t = kvbin_index::MAX_PLAUSIBLE_LEN)]
+    pub max_kvbin_len: u64,
+
+    /// Skip corrupt kvbin records instead of failing: resume at the next offset
+    /// where records chain together again, and log the bytes skipped
+    #[arg(long)]
+    pub skip_corrupt: bool,
+}
+
+impl Default for IoOptions {
+    fn default() -> Self {
+        Self {
+            mode: Io::Read,
+            queue_depth: 32,
+            direct_io: false,
+            max_kvbin_len: kvbin_index::MAX_PLAUSIBLE_LEN,
+            skip_corrupt: false,
+        }
+    }
+}
+
+impl IoOptions {
+    /// Plain buffered reads, the only kind kvbin input and the non-appender
+    /// paths support
+    pub fn is_plain(&self) -> bool {
+        self.mode == Io::Read && !self.direct_io
+    }
+
+    /// Checks that these options apply to `format` input, which is a stream
+    /// when `stream` is set (see `is_stream`)
+    pub fn check(&self, format: InputFormat, stream: bool) -> Result<(), String> {
+        if self.direct_io && self.mode != Io::Read {
+            return Err("--direct-io only applies to --io read".into());
+        }
+        if !self.is_plain() && format == InputFormat::Kvbin {
+            return Err(
+                "--io mmap, --io uring and --direct-io support --format gensort only".into(),
+            );
+        }
+        if !self.is_plain() && stream {
+            return Err(
+                "--io mmap, --io uring and --direct-io need a regular input file, not a stream"
+                    .into(),
+            );
+        }
+        if self.skip_corrupt && format != InputFormat::Kvbin {
+            return Err("--skip-corrupt supports --format kvbin only".into());
+        }
+        if self.skip_corrupt && stream {
+            return Err("--skip-corrupt needs a regular input file, not a stream".into());
+        }
+        Ok(())
+    }
+
+    /// Reads the kvbin records in bytes `range` of the input at `path` from
+    /// `inner`, which is at `range.start`
+    pub fn kvbin_reader<R: Read + Seek>(
+        &self,
+        inner: R,
+        path: &Path,
+        range: Range<u64>,
+    ) -> io::Result<KvbinReader<R>> {
+        let reader = KvbinReader::new(inner, range, self.max_kvbin_len);
+        if self.skip_corrupt {
+            reader.skip_corrupt(path)
+        } else {
+            Ok(reader)
+        }
+    }
+}
+
+/// Fixed-width records of a file, borrowed one or a run at a time
+pub struct FixedRecords {
+    source: Source,
+    record_size: usize,
+    left: u64,
+    /// Reading a stream, which ends where its writer stops
+    stream: bool,
+}
+
+enum Source {
+    Read {
+        reader: Box<dyn Read + Send>,
+        buf: Vec<u8>,
+    },
+    Mapped {
+        map: Mmap,
+        pos: usize,
+    },
+}
+
+impl FixedRecords {
+    /// Opens records `records` (record numbers) of the input at `path`.
+    /// `Io::Read` reads through a buffer of `buffer_bytes`; `Io::Mmap` maps
+    /// the records and advises the kernel they are read sequentially;
+    /// `Io::Uring` reads ahead `io.queue_depth` blocks at a time.
+    /// `io.direct_io` makes `Io::Read` read with O_DIRECT.
+    pub fn open(
+        io: IoOptions,
+        path: &Path,
+        layout: FixedLayout,
+        records: Range<u64>,
+        buffer_bytes: usize,
+    ) -> io::Result<Self> {
+        let record_size = layout.record_size();
+        let bytes = records.start * record_size as u64..records.end * record_size as u64;
+        let source = match io.mode {
+            Io::Read if io.direct_io => Source::Read {
+                reader: Box::new(DirectReader::open(path, bytes, buffer_bytes)?),
+                buf: Vec::new(),
+            },
+            Io::Read => Source::Read {
+                reader: Box::new(BufReader::with_capacity(
+                    buffer_bytes,
+                    Window::open(path, bytes)?,
+                )),
+                buf: Vec::new(),
+            },
+            Io::Mmap => {
+                let map = Mmap::open(path, bytes)?;
+                map.advise_sequential()?;
+                Source::Mapped { map, pos: 0 }
+            }
+            Io::Uring => Source::Read {
+                reader: Box::new(BufReader::with_capacity(
+                    buffer_bytes,
+                    UringReader::open(path, bytes, io.queue_depth, uring::BLOCK_SIZE)?,
+                )),
+                buf: Vec::new(),
+            },
+        };
+        Ok(Self {
+            source,
+            record_size,
+            left: records.end.saturating_sub(records.start),
+            stream: is_stream(path)?,
+        })
+    }
+
+    /// Records not handed out yet; for a stream, an upper bound until it ends
+    pub fn remaining(&self) -> u64 {
+        self.left
+    }
+
+    /// The next record, or None after the last one
+    pub fn next_record(&mut self) -> io::Result<Option<&[u8]>> {
+        let record = self.next_records(1)?;
+        Ok((!record.is_empty()).then_some(record))
+    }
+
+    /// Up to `n` next records back to back; empty after the last one
+    pub fn next_records(&mut self, n: usize) -> io::Result<&[u8]> {
+        let n = (n as u64).min(self.left);
+        self.left -= n;
+        let len = n as usize * self.record_size;
+        match &mut self.source {
+            Source::Read { reader, buf } => {
+                buf.resize(len, 0);
+                if !self.stream {
+                    reader.read_exact(buf)?;
+                } else if read_stream(reader, buf, self.record_size)? < len {
+                    // The writer is done
+                    self.left = 0;
+                }
+                Ok(buf)
+            }
+            Source::Mapped { map, pos } => {
+                let records = &map[*pos..*pos + len];
+                *pos += len;
+                Ok(records)
+            }
+        }
+    }
+}
+
+/// Reads whole records into `buf` until it is full or the stream ends, which
+/// it shrinks to the records read. Returns their length.
+fn read_stream(reader: &mut impl Read, buf: &mut Vec<u8>, record_size: usize) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    if filled % record_size != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "input stream ended in the middle of a record",
+        ));
+    }
+    buf.truncate(filled);
+    Ok(filled)
+}
+
+/// kvbin records of bytes `range` of an input, with their lengths checked
+/// before anything is allocated for them. A length over the limit or running
+/// past the end of the range is an error naming the record's offset, unless
+/// corrupt records are skipped (`skip_corrupt`).
+///
+/// The input is read in blocks of `KVBIN_BLOCK` bytes, and the records are
+/// parsed out of the block and handed out in place, so a small record costs
+/// no read calls and no copies of its own. A record that doesn't fit in what
+/// is left of the block moves to its start with one `copy_within` before the
+/// next read; one bigger than the block grows it.
+pub struct KvbinReader<R> {
+    inner: R,
+    /// Input offset of the next record, `head` in `block`
+    pos: u64,
+    end: u64,
+    max_len: u64,
+    /// Second handle on the input, to look for a record start after a
+    /// corrupt record without moving `inner`
+    probe: Option<File>,
+    block: Vec<u8>,
+    /// Bytes of `block` parsed so far, and read so far
+    head: usize,
+    filled: usize,
+    /// Where in `block` the last record's key and value are
+    key: Range<usize>,
+    value: Range<usize>,
+    skipped: u64,
+}
+
+/// Bytes `KvbinReader` reads at a time
+pub const KVBIN_BLOCK: usize = 1 << 20;
+
+impl<R: Read + Seek> KvbinReader<R> {
+    /// Reads records from `inner`, which is at `range.start`
+    pub fn new(inner: R, range: Range<u64>, max_len: u64) -> Self {
+        Self {
+            inner,
+            pos: range.start,
+            end: range.end,
+            max_len,
+            probe: None,
+            block: Vec::new(),
+            head: 0,
+            filled: 0,
+            key: 0..0,
+            value: 0..0,
+            skipped: 0,
+        }
+    }
+
+    /// Skips corrupt records of the input at `path`, a regular file, instead
+    /// of failing on them
+    pub fn skip_corrupt(mut self, path: &Path) -> io::Result<Self> {
+        self.probe = Some(File::open(path)?);
+        Ok(self)
+    }
+
+    /// Input offset of the next record, or of the end of the last one
+    pub fn offset(&self) -> u64 {
+        self.pos
+    }
+
+    /// Bytes left out as corrupt so far
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// The next record's key and value, or None at the end of the range
+    pub fn next_record(&mut self) -> io::Result<Option<(&[u8], &[u8])>> {
+        Ok(self.next_record_at()?.map(|(_, key, value)| (key, value)))
+    }
+
+    /// Like `next_record`, with the input offset the record starts at
+    pub fn next_record_at(&mut self) -> io::Result<Option<RecordAt<'_>>> {
+        loop {
+            let start = self.pos;
+            let problem = match self.read_record()? {
+                Ok(true) => {
+                    let (key, value) = (self.key.clone(), self.value.clone());
+                    return Ok(Some((start, &self.block[key], &self.block[value])));
+                }
+                Ok(false) => return Ok(None),
+                Err(problem) => problem,
+            };
+            let Some(probe) = self.probe.as_mut() else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("corrupt kvbin record at offset {}: {}", start, problem),
+                ));
+            };
+            let resume = kvbin_index::next_record_start(probe, start + 1..self.end, self.max_len)?
+                .unwrap_or(self.end);
+            self.seek_to(resume)?;
+            self.skipped += resume - start;
+            eprintln!(
+                "Skipped {} bytes of corrupt kvbin input at offset {} ({})",
+                resume - start,
+                start,
+                problem
+            );
+        }
+    }
+
+    /// Parses the record at `pos` into `key` and `value`: false at the end of
+    /// the range, or what is wrong with the record
+    fn read_record(&mut self) -> io::Result<Result<bool, String>> {
+        let start = self.pos;
+        if start >= self.end {
+            return Ok(Ok(false));
+        }
+        if !self.fill(4)? {
+            // The end of a stream, whose length wasn't known
+            return Ok(if self.end == STREAM.end {
+                Ok(false)
+            } else {
+                Err(format!("the input ends before {}", self.end))
+            });
+        }
+        let key_len = self.len_at(0);
+        if let Err(problem) = self.check_len("key", key_len, start, 8) {
+            return Ok(Err(problem));
+        }
+        let key_end = 4 + key_len as usize;
+        if !self.fill(key_end + 4)? {
+            return Ok(Err(if self.filled - self.head < key_end {
+                "the input ends inside the key".into()
+            } else {
+                "the input ends inside the value length".into()
+            }));
+        }
+        let value_len = self.len_at(key_end);
+        if let Err(problem) = self.check_len("value", value_len, start, 8 + key_len) {
+            return Ok(Err(problem));
+        }
+        let record_end = key_end + 4 + value_len as usize;
+        if !self.fill(record_end)? {
+            return Ok(Err("the input ends inside the value".into()));
+        }
+        self.key = self.head + 4..self.head + key_end;
+        self.value = self.head + key_end + 4..self.head + record_end;
+        self.head += record_end;
+        self.pos += record_end as u64;
+        Ok(Ok(true))
+    }
+
+    /// Checks the `what` length `len` of the record at `start`, of which
+    /// `before` bytes come before the `what` itself
+    fn check_len(&self, what: &str, len: u64, start: u64, before: u64) -> Result<(), String> {
+        if len > self.max_len {
+            return Err(format!(
+                "{} length {} is more than --max-kvbin-len ({})",
+                what, len, self.max_len
+            ));
+        }
+        if start.saturating_add(before + len) > self.end {
+            return Err(format!(
+                "{} length {} runs past the end of the input at {}",
+                what, len, self.end
+            ));
+        }
+        Ok(())
+    }
+
+    /// The length header `at` bytes into the record at `pos`
+    fn len_at(&self, at: usize) -> u64 {
+        let at = self.head + at;
+        u32::from_le_bytes(self.block[at..at + 4].try_into().unwrap()) as u64
+    }
+
+    /// Reads until `block` holds the `n` bytes from `pos` on; false if the
+    /// input ends first. Reads stop at the end of the range.
+    fn fill(&mut self, n: usize) -> io::Result<bool> {
+        if self.filled - self.head >= n {
+            return Ok(true);
+        }
+        if self.head > 0 {
+            self.block.copy_within(self.head..self.filled, 0);
+            self.filled -= self.head;
+            self.head = 0;
+        }
+        if self.block.len() < n.max(KVBIN_BLOCK) {
+            self.block.resize(n.max(KVBIN_BLOCK), 0);
+        }
+        while self.filled < n {
+            // Input offset `inner` is at
+            let read_to = self.pos + self.filled as u64;
+            let left = self.end.saturating_sub(read_to);
+            let max = (self.block.len() - self.filled).min(left.try_into().unwrap_or(usize::MAX));
+            match self
+                .inner
+                .read(&mut self.block[self.filled..self.filled + max])
+            {
+                Ok(0) => return Ok(false),
+                Ok(read) => self.filled += read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Moves on to input offset `resume`, past `pos`: within the block if it
+    /// is read already, else by seeking `inner` and dropping the block
+    fn seek_to(&mut self, resume: u64) -> io::Result<()> {
+        let ahead = resume - self.pos;
+        if ahead <= (self.filled - self.head) as u64 {
+            self.head += ahead as usize;
+        } else {
+            let read_to = self.pos + (self.filled - self.head) as u64;
+            self.inner
+                .seek(SeekFrom::Current((resume - read_to) as i64))?;
+            (self.head, self.filled) = (0, 0);
+        }
+        self.pos = resume;
+        Ok(())
+    }
+}
+
+/// The bytes `select` gives for a stream input: all of them, however many
+/// that turns out to be
+pub const STREAM: Range<u64> = 0..u64::MAX;
+
+/// Whether the input at `path` is a FIFO or another stream rather than a
+/// regular file. Only its metadata is read: opening a FIFO connects to its
+/// writer, which must happen once, when the loader reads it.
+pub fn is_stream(path: &Path) -> io::Result<bool> {
+    Ok(!std::fs::metadata(path)?.is_file())
+}
+
+/// Byte range of records `skip..skip + limit` of the input at `path` (through
+/// its last record without a limit). Records past the end of the input are
+/// left out; a kvbin input is walked as far as the last selected record. A
+/// stream input gives `STREAM`, and can't skip or limit records.
+pub fn select(
+    format: InputFormat,
+    layout: FixedLayout,
+    path: &Path,
+    skip: u64,
+    limit: Option<u64>,
+) -> io::Result<Range<u64>> {
+    if is_stream(path)? {
+        if skip > 0 || limit.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--skip and --limit need a regular input file, not a stream",
+            ));
+        }
+        return Ok(STREAM);
+    }
+    let file = File::open(path)?;
+    match format {
+        InputFormat::Gensort => {
+            let record_size = layout.record_size() as u64;
+            let records = file.metadata()?.len() / record_size;
+            let start = skip.min(records);
+            let end = limit.map_or(records, |limit| start.saturating_add(limit).min(records));
+            Ok(start * record_size..end * record_size)
+        }
+        // The loaders check the records' framing as they read them
+        InputFormat::Kvbin if skip == 0 && limit.is_none() => Ok(0..file.metadata()?.len()),
+        InputFormat::Kvbin => kvbin_index::record_range(file, skip, limit),
+    }
+}
+
+/// A view of bytes `range` of `inner` as a stream of its own: offsets count
+/// from `range.start`, and it ends at `range.end`
+pub struct Window<R> {
+    inner: R,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl Window<File> {
+    /// Opens bytes `range` of the file at `path`, which may be a stream when
+    /// the range starts at 0
+    pub fn open(path: &Path, range: Range<u64>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        if range.start == 0 {
+            // A fresh file is already there, and a stream could not seek
+            return Ok(Self {
+                inner: file,
+                start: 0,
+                len: range.end,
+                pos: 0,
+            });
+        }
+        Self::new(file, range)
+    }
+}
+
+impl<R: Seek> Window<R> {
+    pub fn new(mut inner: R, range: Range<u64>) -> io::Result<Self> {
+        inner.seek(SeekFrom::Start(range.start))?;
+        Ok(Self {
+            inner,
+            start: range.start,
+            len: range.end.saturating_sub(range.start),
+            pos: 0,
+        })
+    }
+}
+
+impl<R: Read> Read for Window<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.len.saturating_sub(self.pos);
+        let max = buf.len().min(left.try_into().unwrap_or(usize::MAX));
+        let n = self.inner.read(&mut buf[..max])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for Window<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(d) => self.len.checked_add_signed(d),
+            SeekFrom::Current(d) => self.pos.checked_add_signed(d),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek before the start of the window",
+            )
+        })?;
+        self.inner.seek(SeekFrom::Start(self.start + target))?;
+        self.pos = target;
+        Ok(target)
+    }
+}
@@ -0,0 +1,143 @@
+//! Harness results files and regression checks between two of them.
+//!
+//! `bench_harness.sh` writes every run to `results.json` (and `results.csv`);
+//! `compare-results` lines two such files up by engine, memory limit and thread
+//! count and flags configurations whose sort time grew by more than a threshold.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// One sort run of the harness
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RunResult {
+    pub engine: String,
+    pub memory_limit: String,
+    pub threads: u32,
+    /// "ok", "failed" or "timeout"
+    pub status: String,
+    pub seconds: Option<f64>,
+    #[serde(default)]
+    pub peak_rss_mb: Option<f64>,
+}
+
+impl RunResult {
+    fn same_config(&self, other: &RunResult) -> bool {
+        self.engine == other.engine
+            && self.memory_limit == other.memory_limit
+            && self.threads == other.threads
+    }
+
+    /// Sort time of a successful run
+    fn ok_seconds(&self) -> Option<f64> {
+        self.seconds.filter(|_| self.status == "ok")
+    }
+}
+
+/// Loads a results.json, or a results.csv from older harness runs
+pub fn load(path: &Path) -> Result<Vec<RunResult>, Box<dyn Error + Send + Sync>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    if path.extension().is_some_and(|ext| ext == "csv") {
+        parse_csv(&text)
+    } else {
+        Ok(
+            serde_json::from_str(&text)
+                .map_err(|e| format!("Failed to parse {:?}: {}", path, e))?,
+        )
+    }
+}
+
+/// Parses `engine,memory_limit,threads,seconds[,peak_rss_mb]` where seconds may be FAILED/TIMEOUT
+pub fn parse_csv(text: &str) -> Result<Vec<RunResult>, Box<dyn Error + Send + Sync>> {
+    let mut results = Vec::new();
+    for line in text.lines().skip(1).filter(|l| !l.trim().is_empty()) {
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() < 4 {
+            return Err(format!("Malformed results line '{}'", line).into());
+        }
+        let (status, seconds) = match fields[3].parse::<f64>() {
+            Ok(seconds) => ("ok".to_string(), Some(seconds)),
+            Err(_) => (fields[3].to_lowercase(), None),
+        };
+        results.push(RunResult {
+            engine: fields[0].to_string(),
+            memory_limit: fields[1].to_string(),
+            threads: fields[2].parse()?,
+            status,
+            seconds,
+            peak_rss_mb: fields.get(4).and_then(|v| v.parse().ok()),
+        });
+    }
+    Ok(results)
+}
+
+/// Parses "5%" or "5" into a percentage
+pub fn parse_threshold(threshold: &str) -> Result<f64, Box<dyn Error + Send + Sync>> {
+    let value: f64 = threshold
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .map_err(|_| format!("Invalid threshold '{}', expected e.g. '5%'", threshold))?;
+    if value < 0.0 {
+        return Err(format!("Threshold must not be negative, got '{}'", threshold).into());
+    }
+    Ok(value)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Within the threshold (or faster)
+    Ok,
+    /// Slower by more than the threshold, or no longer completes
+    Regression,
+    /// The baseline run did not complete, so there is nothing to compare against
+    NoBaseline,
+    /// The configuration is absent from the current results
+    Missing,
+}
+
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    pub baseline: RunResult,
+    pub current: Option<RunResult>,
+    /// Relative change of the sort time in percent (positive = slower)
+    pub change_pct: Option<f64>,
+    pub verdict: Verdict,
+}
+
+/// Compares every baseline configuration with the same configuration in `current`
+pub fn compare(
+    baseline: &[RunResult],
+    current: &[RunResult],
+    threshold_pct: f64,
+) -> Vec<Comparison> {
+    baseline
+        .iter()
+        .map(|base| {
+            let cur = current.iter().find(|c| c.same_config(base)).cloned();
+            let (change_pct, verdict) = match (base.ok_seconds(), &cur) {
+                (None, _) => (None, Verdict::NoBaseline),
+                (Some(_), None) => (None, Verdict::Missing),
+                (Some(base_secs), Some(cur)) => match cur.ok_seconds() {
+                    None => (None, Verdict::Regression),
+                    Some(cur_secs) => {
+                        let change = (cur_secs - base_secs) / base_secs * 100.0;
+                        let verdict = if change > threshold_pct {
+                            Verdict::Regression
+                        } else {
+                            Verdict::Ok
+                        };
+                        (Some(change), verdict)
+                    }
+                },
+            };
+            Comparison {
+                baseline: base.clone(),
+                current: cur,
+                change_pct,
+                verdict,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,197 @@
+//! ClickHouse RowBinary encoding.
+//!
+//! RowBinary is the format behind `INSERT ... FORMAT RowBinary`: rows are written
+//! one after another, each column in its binary representation without any
+//! framing. Only the column types the benchmark tables use are supported.
+//! The same format is spoken by chDB and accepted by several other engines.
+
+use std::error::Error;
+use std::fmt;
+
+/// Column types the encoder can write
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColumnType {
+    /// varint length followed by the bytes
+    String,
+    /// Exactly N bytes, zero-padded when the value is shorter
+    FixedString(usize),
+    /// 8 bytes, little-endian
+    UInt64,
+    /// 4 bytes, little-endian two's complement
+    Int32,
+    /// 8 bytes, little-endian two's complement
+    Int64,
+    /// 8 bytes, IEEE 754 little-endian
+    Float64,
+}
+
+impl fmt::Display for ColumnType {
+    /// The type as written in ClickHouse DDL
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColumnType::String => write!(f, "String"),
+            ColumnType::FixedString(n) => write!(f, "FixedString({})", n),
+            ColumnType::UInt64 => write!(f, "UInt64"),
+            ColumnType::Int32 => write!(f, "Int32"),
+            ColumnType::Int64 => write!(f, "Int64"),
+            ColumnType::Float64 => write!(f, "Float64"),
+        }
+    }
+}
+
+/// A single column value of a row passed to `Encoder::encode_row`
+#[derive(Copy, Clone, Debug)]
+pub enum Value<'a> {
+    Bytes(&'a [u8]),
+    UInt64(u64),
+    Int32(i32),
+    Int64(i64),
+    Float64(f64),
+}
+
+/// Writes a variable-length integer (unsigned LEB128, as used by ClickHouse)
+pub fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let mut byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value != 0 {
+            byte |= 0x80;
+        }
+        buf.push(byte);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+/// Writes a `String` value: varint length + bytes
+pub fn write_string(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Writes a `FixedString(n)` value, zero-padding it to `n` bytes
+pub fn write_fixed_string(
+    buf: &mut Vec<u8>,
+    bytes: &[u8],
+    n: usize,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if bytes.len() > n {
+        return Err(format!(
+            "Value of {} bytes does not fit into FixedString({})",
+            bytes.len(),
+            n
+        )
+        .into());
+    }
+    buf.extend_from_slice(bytes);
+    buf.resize(buf.len() + n - bytes.len(), 0);
+    Ok(())
+}
+
+/// Writes a `UInt64` value
+pub fn write_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Encodes rows of a fixed schema into a buffer that is handed off in batches
+pub struct Encoder {
+    columns: Vec<ColumnType>,
+    buf: Vec<u8>,
+    rows: u64,
+}
+
+impl Encoder {
+    pub fn new(columns: Vec<ColumnType>) -> Self {
+        Self::with_capacity(columns, 0)
+    }
+
+    /// Creates an encoder whose buffer starts with room for `bytes` bytes
+    pub fn with_capacity(columns: Vec<ColumnType>, bytes: usize) -> Self {
+        Self {
+            columns,
+            buf: Vec::with_capacity(bytes),
+            rows: 0,
+        }
+    }
+
+    pub fn columns(&self) -> &[ColumnType] {
+        &self.columns
+    }
+
+    /// Appends one row. On error the buffer is left as it was before the call.
+    pub fn encode_row(&mut self, values: &[Value]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if values.len() != self.columns.len() {
+            return Err(format!(
+                "Row has {} values, but the schema has {} columns",
+                values.len(),
+                self.columns.len()
+            )
+            .into());
+        }
+        let start = self.buf.len();
+        for (i, (column, value)) in self.columns.iter().zip(values).enumerate() {
+            let written = match (*column, *value) {
+                (ColumnType::String, Value::Bytes(bytes)) => {
+                    write_string(&mut self.buf, bytes);
+                    Ok(())
+                }
+                (ColumnType::FixedString(n), Value::Bytes(bytes)) => {
+                    write_fixed_string(&mut self.buf, bytes, n)
+                }
+                (ColumnType::UInt64, Value::UInt64(v)) => {
+                    write_u64(&mut self.buf, v);
+                    Ok(())
+                }
+                (ColumnType::Int32, Value::Int32(v)) => {
+                    self.buf.extend_from_slice(&v.to_le_bytes());
+                    Ok(())
+                }
+                (ColumnType::Int64, Value::Int64(v)) => {
+                    self.buf.extend_from_slice(&v.to_le_bytes());
+                    Ok(())
+                }
+                (ColumnType::Float64, Value::Float64(v)) => {
+                    self.buf.extend_from_slice(&v.to_le_bytes());
+                    Ok(())
+                }
+                (column, value) => Err(format!("Cannot write {:?} as {}", value, column).into()),
+            };
+            if let Err(e) = written {
+                self.buf.truncate(start);
+                return Err(format!("Column {}: {}", i, e).into());
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Bytes encoded since the last `take`
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Rows encoded since the last `take`
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    /// Returns the encoded bytes and starts a new buffer with the same capacity
+    pub fn take(&mut self) -> Vec<u8> {
+        self.take_into(Vec::new())
+    }
+
+    /// Like `take`, continuing in `buf` (emptied, and grown to the current
+    /// capacity if smaller) instead of a new buffer, e.g. one from a
+    /// `BufferPool`
+    pub fn take_into(&mut self, mut buf: Vec<u8>) -> Vec<u8> {
+        buf.clear();
+        buf.reserve(self.buf.capacity());
+        self.rows = 0;
+        std::mem::replace(&mut self.buf, buf)
+    }
+}
@@ -0,0 +1,86 @@
+//! Peak resident memory of whatever process is doing the sort.
+//!
+//! DuckDB sorts inside our own process, so the kernel's high-water mark (VmHWM)
+//! is exact. PostgreSQL and ClickHouse sort inside the server, which can only be
+//! sampled while the sort runs, so their peaks are approximate.
+
+use std::fs;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Reads a "<Field>:   1234 kB" line out of /proc/<pid>/status, in bytes
+pub fn parse_status_kb(status: &str, field: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(field)?.strip_prefix(':'))
+        .and_then(|rest| rest.trim().strip_suffix("kB"))
+        .and_then(|kb| kb.trim().parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+}
+
+/// Highest RSS this process has reached so far
+pub fn self_peak_rss() -> Option<u64> {
+    parse_status_kb(&fs::read_to_string("/proc/self/status").ok()?, "VmHWM")
+}
+
+/// Private (anonymous) resident memory of `pid`, or None if no process with that
+/// PID and command name is visible. Shared memory is left out so that summing it
+/// over processes attached to the same segment (PostgreSQL's shared_buffers)
+/// does not count the segment once per process.
+pub fn private_rss(pid: u32, comm: &str) -> Option<u64> {
+    let name = fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    if name.trim() != comm {
+        return None;
+    }
+    parse_status_kb(
+        &fs::read_to_string(format!("/proc/{}/status", pid)).ok()?,
+        "RssAnon",
+    )
+}
+
+/// Calls a sampling function on a background thread and keeps the largest value
+pub struct PeakSampler {
+    peak: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl PeakSampler {
+    pub fn start<F>(interval: Duration, mut sample: F) -> Self
+    where
+        F: FnMut() -> Option<u64> + Send + 'static,
+    {
+        let peak = Arc::new(AtomicU64::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let peak = peak.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    if let Some(bytes) = sample() {
+                        peak.fetch_max(bytes, Ordering::Relaxed);
+                    }
+                    thread::sleep(interval);
+                }
+            })
+        };
+        PeakSampler { peak, stop, handle }
+    }
+
+    /// Stops sampling and returns the peak, or None if no sample succeeded
+    pub fn finish(self) -> Option<u64> {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+        Some(self.peak.load(Ordering::Relaxed)).filter(|&bytes| bytes > 0)
+    }
+}
+
+/// Prints the PEAK_RSS line that goes next to TIMING
+pub fn report(peak: Option<u64>) {
+    match peak {
+        Some(bytes) => println!("PEAK_RSS: {:.1} MB", bytes as f64 / (1024.0 * 1024.0)),
+        None => println!("PEAK_RSS: unavailable"),
+    }
+}
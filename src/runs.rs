@@ -0,0 +1,90 @@
+//! Repeated sorts (`--runs N`), warm or cold.
+//!
+//! The sorters can run their query several times in one process and time
+//! every run on its own. Without `--cold` the later runs find whatever the
+//! earlier ones left in the engine's caches. With it each sorter drops what it
+//! can from the client side before every run: DuckDB gets a fresh connection,
+//! and so an empty buffer pool, and ClickHouse drops its mark and uncompressed
+//! caches. PostgreSQL's shared buffers only go with a server restart, so that
+//! sorter just says so. The OS page cache is kept in every case.
+//!
+//! TIMING is the median run, so the harness still reads a single number; the
+//! per-run times follow it as `RUN <i>:` lines.
+
+use clap::Args;
+use std::time::Duration;
+
+/// How often to run the sort, and whether to drop caches in between
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+pub struct Runs {
+    /// Run the sort this many times, reporting each run and the median as TIMING
+    #[arg(long = "runs", default_value_t = 1)]
+    pub count: usize,
+
+    /// Drop the engine's caches before every run
+    #[arg(long)]
+    pub cold: bool,
+}
+
+impl Default for Runs {
+    fn default() -> Self {
+        Self {
+            count: 1,
+            cold: false,
+        }
+    }
+}
+
+impl Runs {
+    pub fn check(&self) -> Result<(), String> {
+        match self.count {
+            0 => Err("--runs must be at least 1".into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// What run `run` (counting from 0) finds in the caches
+    pub fn label(&self, run: usize) -> &'static str {
+        match (self.cold, run) {
+            (true, _) => "cold",
+            (false, 0) => "first",
+            (false, _) => "warm",
+        }
+    }
+
+    /// `RUN <i>:` lines for every run and the spread over them, or nothing
+    /// for a single run
+    pub fn report(&self, times: &[Duration]) -> Option<String> {
+        if times.len() < 2 {
+            return None;
+        }
+        let mut lines: Vec<String> = times
+            .iter()
+            .enumerate()
+            .map(|(i, time)| {
+                format!(
+                    "RUN {}: {:.2} seconds ({})",
+                    i + 1,
+                    time.as_secs_f64(),
+                    self.label(i)
+                )
+            })
+            .collect();
+        let min = times.iter().min().copied().unwrap_or_default();
+        let max = times.iter().max().copied().unwrap_or_default();
+        lines.push(format!("RUNS: {}", times.len()));
+        lines.push(format!("RUN_MIN: {:.2}", min.as_secs_f64()));
+        lines.push(format!("RUN_MAX: {:.2}", max.as_secs_f64()));
+        Some(lines.join("\n"))
+    }
+}
+
+/// The middle run time; the lower of the two middle ones for an even count
+pub fn median(times: &[Duration]) -> Duration {
+    let mut sorted = times.to_vec();
+    sorted.sort();
+    match sorted.len() {
+        0 => Duration::ZERO,
+        n => sorted[(n - 1) / 2],
+    }
+}
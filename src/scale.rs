@@ -0,0 +1,104 @@
+//! Loading every input record several times (`--scale-factor`).
+//!
+//! A loader with `--scale-factor K` writes each record it reads K times, so a
+//! table K times the size of the input needs no K times larger input file. The
+//! copies are exact duplicates, which gives the sort K-way ties, unless
+//! `--perturb-keys` makes the copies of a key differ by scrambling its last
+//! `PERTURBED_BYTES` bytes differently in each copy.
+
+use clap::Args;
+
+/// Bytes at the end of a key that `--perturb-keys` changes
+pub const PERTURBED_BYTES: usize = 4;
+
+/// How many times a loader writes each record (`--scale-factor` and its settings)
+#[derive(Args, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ScaleOptions {
+    /// Load every input record K times, for a table K times the size of the input
+    #[arg(long, value_name = "K", default_value_t = 1)]
+    pub scale_factor: u32,
+
+    /// Scramble the last 4 key bytes of every copy but the first, so the
+    /// copies of a record have different keys instead of tying
+    #[arg(long)]
+    pub perturb_keys: bool,
+}
+
+impl Default for ScaleOptions {
+    fn default() -> Self {
+        Self {
+            scale_factor: 1,
+            perturb_keys: false,
+        }
+    }
+}
+
+impl ScaleOptions {
+    pub fn check(&self) -> Result<(), String> {
+        if self.scale_factor == 0 {
+            return Err("--scale-factor must be at least 1".into());
+        }
+        Ok(())
+    }
+
+    /// Whether records are written more than once
+    pub fn is_scaled(&self) -> bool {
+        self.scale_factor > 1
+    }
+
+    /// Rows written for `records` input records
+    pub fn rows(&self, records: u64) -> u64 {
+        records * self.scale_factor as u64
+    }
+
+    /// Row id of copy `copy` of the record with id `record_id`, so the copies
+    /// of a record follow it and come before the next record's. Fails when
+    /// the id does not fit the BIGINT row_id columns.
+    pub fn row_id(&self, record_id: u64, copy: u32) -> Result<u64, String> {
+        record_id
+            .checked_mul(self.scale_factor as u64)
+            .and_then(|id| id.checked_add(copy as u64))
+            .filter(|&id| id <= i64::MAX as u64)
+            .ok_or_else(|| {
+                format!(
+                    "The row id of copy {} of record {} overflows a BIGINT",
+                    copy, record_id
+                )
+            })
+    }
+
+    /// Calls `f(copy, key, payload)` for every copy of one record, copy 0 first
+    pub fn each_copy<E>(
+        &self,
+        key: &[u8],
+        payload: &[u8],
+        mut f: impl FnMut(u32, &[u8], &[u8]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        if !self.perturb_keys {
+            for i in 0..self.scale_factor {
+                f(i, key, payload)?;
+            }
+            return Ok(());
+        }
+        let mut copy = key.to_vec();
+        for i in 0..self.scale_factor {
+            copy.copy_from_slice(key);
+            perturb_key(&mut copy, i);
+            f(i, &copy, payload)?;
+        }
+        Ok(())
+    }
+}
+
+/// Turns a key into its copy `copy`: XORs the key's last `PERTURBED_BYTES`
+/// bytes (all of a shorter key) with a mask spread from `copy`. Copy 0 is the
+/// key itself. Adding the copy number instead would turn a sequential key into
+/// the next one; the spread mask makes copies unlikely to meet other keys.
+pub fn perturb_key(key: &mut [u8], copy: u32) {
+    let mask = copy.wrapping_mul(0x9E37_79B9).to_be_bytes();
+    let n = key.len().min(PERTURBED_BYTES);
+    let start = key.len() - n;
+    for (b, m) in key[start..].iter_mut().zip(mask) {
+        *b ^= m;
+    }
+}
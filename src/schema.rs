@@ -0,0 +1,330 @@
+//! Table schemas the loaders can create.
+//!
+//! `KeyPayload` is the benchmark's usual two-column table. `Wide` adds typed
+//! columns for multi-column ORDER BY benchmarks. Their values travel in the
+//! first `WIDE_COLUMNS_SIZE` bytes of each record's payload, so wide data still
+//! moves through the fixed-width (gensort) format unchanged:
+//!
+//! ```text
+//! 0..4    c_int     i32 LE (0..=999 when generated, so it has many ties)
+//! 4..12   c_bigint  i64 LE
+//! 12..20  c_double  f64 LE in [0, 1)
+//! 20..36  c_text    length byte (at most 15), then that many bytes of UTF-8, zero-padded
+//! 36..    payload   the rest of the payload, loaded as a blob
+//! ```
+//!
+//! The sorters order by the key column, or by the columns of `--order-by`
+//! (`OrderBy`), e.g. `c_int,c_text,sort_key` for a compound key on a wide table.
+//! With `--mode distinct` (`SortMode`) they keep each key once instead.
+//!
+//! The key and payload columns are named by `ColumnNames`, so the tools can
+//! work on existing tables that call them something else; the wide columns
+//! keep their names. Loaders can also add `ExtraColumn`s the benchmark itself
+//! never fills.
+//!
+//! With `--key-type uint64` the key column is an integer instead: `key_u64`
+//! reads the first 8 key bytes big-endian, so integer order is key order up to
+//! those bytes (gensort's last two key bytes are dropped).
+//!
+//! `--with-row-id` adds `ROW_ID_COLUMN`, numbering the rows in input order: a
+//! gensort record's id is its record number and a kvbin record's its byte
+//! offset, so the ids grow with the input, but kvbin ones leave gaps. A sort
+//! that keeps equal keys in input order keeps their row ids ascending.
+
+use clap::{Args, ValueEnum};
+use std::error::Error;
+use std::str::FromStr;
+
+/// Columns of the benchmark table
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Schema {
+    /// sort_key and payload blobs
+    #[default]
+    KeyPayload,
+    /// sort_key, c_int, c_bigint, c_double, c_text and payload
+    Wide,
+}
+
+/// Names of the key and payload columns
+#[derive(Args, Clone, Debug, PartialEq, Eq)]
+pub struct ColumnNames {
+    /// Name of the sort key column
+    #[arg(long = "key-column", default_value = "sort_key")]
+    pub key: String,
+
+    /// Name of the payload column
+    #[arg(long = "payload-column", default_value = "payload")]
+    pub payload: String,
+}
+
+impl Default for ColumnNames {
+    fn default() -> Self {
+        Self {
+            key: "sort_key".to_string(),
+            payload: "payload".to_string(),
+        }
+    }
+}
+
+impl ColumnNames {
+    /// The loaded columns in load order, for COPY and INSERT column lists
+    pub fn list(&self, schema: Schema) -> String {
+        match schema {
+            Schema::KeyPayload => format!("{}, {}", self.key, self.payload),
+            Schema::Wide => format!(
+                "{}, c_int, c_bigint, c_double, c_text, {}",
+                self.key, self.payload
+            ),
+        }
+    }
+
+    /// The key and payload as a SELECT list that names them `sort_key` and
+    /// `payload`, so sorted output files look the same whatever the table calls
+    /// its columns
+    pub fn select(&self) -> String {
+        format!(
+            "{}, {}",
+            self.select_key(),
+            aliased(&self.payload, "payload")
+        )
+    }
+
+    /// Just the key, named `sort_key`, for sorts that leave the payload behind
+    pub fn select_key(&self) -> String {
+        aliased(&self.key, "sort_key")
+    }
+}
+
+/// `name AS alias`, or just `name` if they are the same
+fn aliased(name: &str, alias: &str) -> String {
+    if name == alias {
+        name.to_string()
+    } else {
+        format!("{} AS {}", name, alias)
+    }
+}
+
+/// What the sorters make of the rows
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SortMode {
+    /// Every row, in order
+    #[default]
+    Sort,
+    /// Every key once, in order (SELECT DISTINCT sort_key ... ORDER BY
+    /// sort_key): dedup during the sort, which duplicate keys (e.g.
+    /// generate-gensort --duplicate-ratio) make cheaper. Implies --keys-only.
+    Distinct,
+}
+
+impl SortMode {
+    /// What follows SELECT in a sort query ordered by `key`: nothing, or
+    /// DISTINCT ON the key. That keeps the rows SELECT DISTINCT would, but
+    /// lets DuckDB and PostgreSQL select the key encoded (hex text for CSV)
+    /// and still order by the bytes.
+    pub fn select(self, key: &str) -> String {
+        match self {
+            SortMode::Sort => String::new(),
+            SortMode::Distinct => format!("DISTINCT ON ({}) ", key),
+        }
+    }
+
+    /// Refuses an ORDER BY other than the key, which DISTINCT leaves behind
+    pub fn check(self, order_by: &OrderBy) -> Result<(), String> {
+        match (self, order_by.columns.is_empty()) {
+            (SortMode::Distinct, false) => {
+                Err("--mode distinct keeps only the key: drop --order-by".into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The sorters' ORDER BY
+#[derive(Args, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrderBy {
+    /// Sort by these columns, in order, instead of the key column (e.g.
+    /// "c_int,c_text,sort_key" on a --schema wide table); each may end in ASC or DESC
+    #[arg(long = "order-by", value_delimiter = ',')]
+    pub columns: Vec<String>,
+}
+
+impl OrderBy {
+    /// Refuses empty entries, as in "c_int,,sort_key"
+    pub fn check(&self) -> Result<(), String> {
+        match self.columns.iter().any(|column| column.trim().is_empty()) {
+            true => Err(format!(
+                "--order-by has an empty column: '{}'",
+                self.columns.join(",")
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// The ORDER BY list: the `--order-by` columns, or else the key column
+    pub fn clause(&self, names: &ColumnNames) -> String {
+        if self.columns.is_empty() {
+            return names.key.clone();
+        }
+        let columns: Vec<&str> = self.columns.iter().map(|c| c.trim()).collect();
+        columns.join(", ")
+    }
+
+    /// The columns a UNION ALL of shards has to carry: the key and payload, the
+    /// `ROW_ID_COLUMN` of tables that have one, then any other column the ORDER
+    /// BY names
+    pub fn union_columns(&self, names: &ColumnNames, row_ids: bool) -> String {
+        let mut columns = vec![names.key.as_str(), names.payload.as_str()];
+        if row_ids {
+            columns.push(ROW_ID_COLUMN);
+        }
+        for column in &self.columns {
+            let name = column.split_whitespace().next().unwrap_or_default();
+            if !columns.contains(&name) {
+                columns.push(name);
+            }
+        }
+        columns.join(", ")
+    }
+}
+
+/// A column the loaders add to the table without filling it, written `name TYPE`
+/// (e.g. `tenant_id INTEGER`); it is NULL, or the type's default in ClickHouse
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtraColumn {
+    pub name: String,
+    /// The column type in the target engine's SQL, with any modifiers
+    pub sql_type: String,
+}
+
+impl ExtraColumn {
+    /// Adds the column to `table` unless it already has it. The loaders run
+    /// this once the rows are in, so their load paths only ever see the key
+    /// and payload columns.
+    pub fn add_sql(&self, table: &str) -> String {
+        format!(
+            "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} {}",
+            table, self.name, self.sql_type
+        )
+    }
+}
+
+impl FromStr for ExtraColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().split_once(char::is_whitespace) {
+            Some((name, sql_type)) if !sql_type.trim().is_empty() => Ok(Self {
+                name: name.to_string(),
+                sql_type: sql_type.trim().to_string(),
+            }),
+            _ => Err(format!("Expected 'name TYPE', got '{}'", s)),
+        }
+    }
+}
+
+/// Name of the `--with-row-id` column, a 64-bit integer after the payload
+pub const ROW_ID_COLUMN: &str = "row_id";
+
+/// Bytes at the start of a payload taken by the wide schema's typed columns
+pub const WIDE_COLUMNS_SIZE: usize = 36;
+
+/// Distinct c_int values `fill_wide` produces
+pub const WIDE_INT_VALUES: u32 = 1000;
+
+const TEXT_CAPACITY: usize = 15;
+
+/// The typed columns of one wide record, borrowed from its payload
+#[derive(Clone, Debug, PartialEq)]
+pub struct WideRow<'a> {
+    pub c_int: i32,
+    pub c_bigint: i64,
+    pub c_double: f64,
+    pub c_text: &'a str,
+    pub payload: &'a [u8],
+}
+
+impl<'a> WideRow<'a> {
+    /// Splits a payload into the wide columns
+    pub fn decode(payload: &'a [u8]) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        if payload.len() < WIDE_COLUMNS_SIZE {
+            return Err(format!(
+                "Wide records need payloads of at least {} bytes, got {}",
+                WIDE_COLUMNS_SIZE,
+                payload.len()
+            )
+            .into());
+        }
+        let text_len = payload[20] as usize;
+        if text_len > TEXT_CAPACITY {
+            return Err(format!("c_text length {} exceeds {}", text_len, TEXT_CAPACITY).into());
+        }
+        Ok(Self {
+            c_int: i32::from_le_bytes(payload[0..4].try_into().unwrap()),
+            c_bigint: i64::from_le_bytes(payload[4..12].try_into().unwrap()),
+            c_double: f64::from_le_bytes(payload[12..20].try_into().unwrap()),
+            c_text: std::str::from_utf8(&payload[21..21 + text_len])
+                .map_err(|e| format!("c_text is not UTF-8: {}", e))?,
+            payload: &payload[WIDE_COLUMNS_SIZE..],
+        })
+    }
+
+    /// Writes the columns into the first `WIDE_COLUMNS_SIZE` bytes of `payload`
+    pub fn encode_columns(&self, payload: &mut [u8]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if payload.len() < WIDE_COLUMNS_SIZE {
+            return Err(format!(
+                "Wide records need payloads of at least {} bytes, got {}",
+                WIDE_COLUMNS_SIZE,
+                payload.len()
+            )
+            .into());
+        }
+        if self.c_text.len() > TEXT_CAPACITY {
+            return Err(format!(
+                "c_text '{}' is longer than {} bytes",
+                self.c_text, TEXT_CAPACITY
+            )
+            .into());
+        }
+        payload[0..4].copy_from_slice(&self.c_int.to_le_bytes());
+        payload[4..12].copy_from_slice(&self.c_bigint.to_le_bytes());
+        payload[12..20].copy_from_slice(&self.c_double.to_le_bytes());
+        payload[20] = self.c_text.len() as u8;
+        payload[21..21 + self.c_text.len()].copy_from_slice(self.c_text.as_bytes());
+        payload[21 + self.c_text.len()..WIDE_COLUMNS_SIZE].fill(0);
+        Ok(())
+    }
+}
+
+/// A key as an unsigned integer: its first 8 bytes big-endian, zero-padded when
+/// the key is shorter
+pub fn key_u64(key: &[u8]) -> u64 {
+    let mut bytes = [0u8; 8];
+    let n = key.len().min(8);
+    bytes[..n].copy_from_slice(&key[..n]);
+    u64::from_be_bytes(bytes)
+}
+
+/// `key_u64` for a signed 64-bit column, as PostgreSQL has no unsigned one: the
+/// top bit is flipped, so signed order is still key order
+pub fn key_i64(key: &[u8]) -> i64 {
+    (key_u64(key) ^ (1 << 63)) as i64
+}
+
+/// Turns the random bytes at the start of a payload into wide columns, in place:
+/// c_int in 0..WIDE_INT_VALUES, any c_bigint, c_double in [0, 1) and 4 to 15
+/// lowercase letters of c_text. Panics if the payload is shorter than
+/// `WIDE_COLUMNS_SIZE`.
+pub fn fill_wide(payload: &mut [u8]) {
+    let c_int = (u32::from_le_bytes(payload[0..4].try_into().unwrap()) % WIDE_INT_VALUES) as i32;
+    let bits = u64::from_le_bytes(payload[12..20].try_into().unwrap());
+    let c_double = (bits >> 11) as f64 / (1u64 << 53) as f64;
+    let text_len = 4 + payload[20] as usize % (TEXT_CAPACITY - 3);
+
+    payload[0..4].copy_from_slice(&c_int.to_le_bytes());
+    payload[12..20].copy_from_slice(&c_double.to_le_bytes());
+    payload[20] = text_len as u8;
+    for b in &mut payload[21..21 + text_len] {
+        *b = b'a' + *b % 26;
+    }
+    payload[21 + text_len..WIDE_COLUMNS_SIZE].fill(0);
+}
@@ -0,0 +1,180 @@
+//! Loading into several tables (`--shards`) and sorting them as one.
+//!
+//! With `--shards N` a loader creates the tables `<table>_shard0` ..
+//! `<table>_shard<N-1>` instead of `<table>`, and its reader threads send
+//! every row to one of them: round-robin, so the shards are the same size, or
+//! by ranges of the first key byte, so each shard holds a key range and the
+//! shards sorted one by one, in order, are the whole table sorted.
+//!
+//! The sorters read the shards back either as one `UNION ALL`, sorted by a
+//! single ORDER BY, or one shard at a time (`ShardSort::PerShard`), each with
+//! an output file of its own.
+
+use crate::records::{FixedLayout, FixedRecords, InputFormat, IoOptions, Window};
+use clap::ValueEnum;
+use std::error::Error;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// How a loader spreads the rows over the shards
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ShardBy {
+    /// Each reader thread deals its rows out in turn
+    #[default]
+    RoundRobin,
+    /// Ranges of the first key byte, shard 0 holding the smallest keys
+    KeyRange,
+}
+
+/// How a sorter reads the shards
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ShardSort {
+    /// One ORDER BY over the UNION ALL of the shards
+    #[default]
+    UnionAll,
+    /// One ORDER BY per shard, in shard order; in total order for key-range shards
+    PerShard,
+}
+
+/// Checks a `--shards` count for the given routing
+pub fn check(shards: usize, by: ShardBy) -> Result<(), String> {
+    match (shards, by) {
+        (0, _) => Err("--shards must be at least 1".into()),
+        (n, ShardBy::KeyRange) if n > 256 => {
+            Err("Key-range shards split on the first key byte: at most 256 shards".into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Name of shard `shard` of `table`
+pub fn table_name(table: &str, shard: usize) -> String {
+    format!("{}_shard{}", table, shard)
+}
+
+/// Names of all `shards` shards of `table`, in order
+pub fn table_names(table: &str, shards: usize) -> Vec<String> {
+    (0..shards).map(|shard| table_name(table, shard)).collect()
+}
+
+/// Key-range shard a key belongs to; the empty key sorts first
+pub fn key_range_shard(key: &[u8], shards: usize) -> usize {
+    match key.first() {
+        Some(&b) => ((b as usize + 1) * shards - 1) / 256,
+        None => 0,
+    }
+}
+
+/// Picks the shard of each row one reader thread reads
+#[derive(Clone, Debug)]
+pub struct Router {
+    by: ShardBy,
+    shards: usize,
+    next: usize,
+}
+
+impl Router {
+    /// A router for reader thread `reader`; round-robin readers start at
+    /// different shards, so short inputs still spread out
+    pub fn new(by: ShardBy, shards: usize, reader: usize) -> Self {
+        let shards = shards.max(1);
+        Self {
+            by,
+            shards,
+            next: reader % shards,
+        }
+    }
+
+    pub fn route(&mut self, key: &[u8]) -> usize {
+        match self.by {
+            ShardBy::RoundRobin => {
+                let shard = self.next;
+                self.next = (self.next + 1) % self.shards;
+                shard
+            }
+            ShardBy::KeyRange => key_range_shard(key, self.shards),
+        }
+    }
+}
+
+/// Byte ranges of whole records (of `layout`) of `range` for `threads`
+/// readers. A kvbin input has no record boundaries to split on here, so one
+/// reader takes it.
+pub fn reader_ranges(
+    format: InputFormat,
+    layout: FixedLayout,
+    range: Range<u64>,
+    threads: usize,
+) -> Vec<Range<u64>> {
+    let record_size = layout.record_size() as u64;
+    if format == InputFormat::Kvbin || threads <= 1 {
+        return vec![range];
+    }
+    let total_records = (range.end - range.start) / record_size;
+    let per_thread = total_records.div_ceil(threads as u64);
+    (0..threads as u64)
+        .map(|t| (t * per_thread, ((t + 1) * per_thread).min(total_records)))
+        .take_while(|&(start, _)| start < total_records)
+        .map(|(start, end)| range.start + start * record_size..range.start + end * record_size)
+        .collect()
+}
+
+/// Reads the records in bytes `range` of `input` (of `layout`, for gensort)
+/// and calls `f(shard, id, key, payload)` for each, with the shard `router`
+/// picks and the record's id (`schema::ROW_ID_COLUMN`). Returns the rows read.
+pub fn route(
+    input: &Path,
+    format: InputFormat,
+    layout: FixedLayout,
+    io: IoOptions,
+    range: Range<u64>,
+    router: &mut Router,
+    mut f: impl FnMut(usize, u64, &[u8], &[u8]) -> Result<(), Box<dyn Error + Send + Sync>>,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    const READ_BUFFER: usize = 8 * 1024 * 1024;
+
+    let mut rows = 0u64;
+    match format {
+        InputFormat::Gensort => {
+            let record_size = layout.record_size() as u64;
+            let records = range.start / record_size..range.end / record_size;
+            let first = records.start;
+            let mut reader = FixedRecords::open(io, input, layout, records, READ_BUFFER)?;
+            while let Some(record) = reader.next_record()? {
+                let (key, payload) = record.split_at(layout.key_size);
+                f(router.route(key), first + rows, key, payload)?;
+                rows += 1;
+            }
+        }
+        InputFormat::Kvbin => {
+            let reader = Window::open(input, range.clone())?;
+            let mut records = io.kvbin_reader(reader, input, range)?;
+            while let Some((id, key, payload)) = records.next_record_at()? {
+                f(router.route(key), id, key, payload)?;
+                rows += 1;
+            }
+        }
+    }
+    Ok(rows)
+}
+
+/// The FROM source that reads all `shards` shards of `table` as one:
+/// `(SELECT <columns> FROM t_shard0 UNION ALL ...) AS shards`
+pub fn union_all(table: &str, shards: usize, columns: &str) -> String {
+    let selects: Vec<String> = table_names(table, shards)
+        .iter()
+        .map(|name| format!("SELECT {} FROM {}", columns, name))
+        .collect();
+    format!("({}) AS shards", selects.join(" UNION ALL "))
+}
+
+/// Output file of shard `shard` in a per-shard sort: `sorted.parquet` becomes
+/// `sorted.shard0.parquet`
+pub fn output_path(output: &Path, shard: usize) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(ext) => format!("{}.shard{}.{}", stem, shard, ext.to_string_lossy()),
+        None => format!("{}.shard{}", stem, shard),
+    };
+    output.with_file_name(name)
+}
@@ -0,0 +1,164 @@
+//! Backend-agnostic sorting through the `Sorter` trait.
+//!
+//! `SortOptions` carries what every engine understands — a memory budget, a
+//! thread count, an optional output file and raw engine settings — and each
+//! backend maps it onto its own knobs. `es-duck sort --backend <name>` drives
+//! any registered backend the same way. The settings each engine sorts under
+//! live in `session`, which the sort-* binaries apply too.
+
+use crate::metrics::{self, Phase};
+use crate::schema::ColumnNames;
+use std::error::Error;
+use std::path::PathBuf;
+
+#[cfg(feature = "db-clickhouse")]
+mod clickhouse;
+#[cfg(feature = "db-duckdb")]
+mod duckdb;
+#[cfg(feature = "db-postgres")]
+mod postgres;
+pub mod session;
+
+#[cfg(feature = "db-clickhouse")]
+pub use clickhouse::ClickHouseSorter;
+#[cfg(feature = "db-duckdb")]
+pub use duckdb::DuckDbSorter;
+#[cfg(feature = "db-postgres")]
+pub use postgres::PostgresSorter;
+
+/// What to sort and under which limits
+#[derive(Clone, Debug)]
+pub struct SortOptions {
+    /// Database file (DuckDB), connection string (PostgreSQL) or HTTP URL (ClickHouse)
+    pub connection: String,
+    /// ClickHouse database name
+    pub database: String,
+    pub table: String,
+    /// The key and payload columns; output files always call them sort_key and payload
+    pub columns: ColumnNames,
+    /// Memory budget for the whole sort (e.g. "4GB")
+    pub memory_limit: String,
+    /// Engine threads (PostgreSQL: parallel workers); None keeps the engine default
+    pub threads: Option<usize>,
+    /// File the sorted rows are written to; None sorts without writing output
+    pub output: Option<PathBuf>,
+    /// Spill directory (DuckDB only)
+    pub temp_dir: Option<PathBuf>,
+    /// Extra engine settings, applied after the ones derived from the options above
+    pub settings: Vec<(String, String)>,
+}
+
+/// What a finished sort measured
+#[derive(Clone, Debug, PartialEq)]
+pub struct SortResult {
+    pub rows: u64,
+    pub seconds: f64,
+    pub peak_rss_bytes: Option<u64>,
+}
+
+/// Runs the benchmark sort on one engine
+pub trait Sorter {
+    /// Applies the options to the session and returns the number of rows to sort
+    fn prepare(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>>;
+
+    /// Sorts the table (writing the output file, if any), timing only the sort itself
+    fn sort(&mut self) -> Result<SortResult, Box<dyn Error + Send + Sync>>;
+}
+
+pub type SorterFactory = fn(&SortOptions) -> Result<Box<dyn Sorter>, Box<dyn Error + Send + Sync>>;
+
+/// Backend names and their constructors
+pub struct Registry {
+    backends: Vec<(String, SorterFactory)>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self {
+            backends: Vec::new(),
+        }
+    }
+
+    /// The backends compiled into this build (see the `db-*` features)
+    pub fn builtin() -> Self {
+        #[allow(unused_mut)]
+        let mut registry = Self::new();
+        #[cfg(feature = "db-duckdb")]
+        registry.register("duckdb", |options| {
+            Ok(Box::new(DuckDbSorter::new(options)?))
+        });
+        #[cfg(feature = "db-postgres")]
+        registry.register("postgres", |options| {
+            Ok(Box::new(PostgresSorter::new(options)?))
+        });
+        #[cfg(feature = "db-clickhouse")]
+        registry.register("clickhouse", |options| {
+            Ok(Box::new(ClickHouseSorter::new(options)?))
+        });
+        registry
+    }
+
+    /// Adds a backend, replacing any earlier one with the same name
+    pub fn register(&mut self, name: &str, factory: SorterFactory) {
+        self.backends.retain(|(existing, _)| existing != name);
+        self.backends.push((name.to_string(), factory));
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.backends
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    pub fn create(
+        &self,
+        name: &str,
+        options: &SortOptions,
+    ) -> Result<Box<dyn Sorter>, Box<dyn Error + Send + Sync>> {
+        match self.backends.iter().find(|(existing, _)| existing == name) {
+            Some((_, factory)) => factory(options),
+            None => Err(format!(
+                "Unknown backend '{}' (available: {})",
+                name,
+                self.names().join(", ")
+            )
+            .into()),
+        }
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parses a `--set key=value` argument
+pub fn parse_setting(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("Expected key=value, got '{}'", arg)),
+    }
+}
+
+/// A `--set` value as a SQL string literal, which the engines cast to the
+/// setting's type, so a value with spaces or quotes can't end the statement
+pub fn setting_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Prepares and runs a sort, keeping the metrics phases and sorted-row count up to date
+pub fn run(sorter: &mut dyn Sorter) -> Result<SortResult, Box<dyn Error + Send + Sync>> {
+    metrics::set_phase(Phase::Setup);
+    let rows = sorter.prepare()?;
+    println!("Rows to sort: {}", rows);
+
+    metrics::set_phase(Phase::Sort);
+    let result = sorter.sort()?;
+    metrics::set_rows_sorted(result.rows);
+    metrics::set_phase(Phase::Done);
+    Ok(result)
+}
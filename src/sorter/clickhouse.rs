@@ -0,0 +1,77 @@
+use super::{SortOptions, SortResult, Sorter, session};
+use crate::cgroup;
+use clickhouse::Client;
+use std::error::Error;
+use std::time::Instant;
+use tokio::runtime::Runtime;
+
+/// Sorts with a single query; the server handles spilling and the output file
+pub struct ClickHouseSorter {
+    runtime: Runtime,
+    client: Client,
+    options: SortOptions,
+    settings_clause: String,
+    rows: u64,
+}
+
+impl ClickHouseSorter {
+    pub fn new(options: &SortOptions) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let client = Client::default()
+            .with_url(&options.connection)
+            .with_database(&options.database);
+        Ok(Self {
+            runtime,
+            client,
+            options: options.clone(),
+            settings_clause: String::new(),
+            rows: 0,
+        })
+    }
+}
+
+impl Sorter for ClickHouseSorter {
+    fn prepare(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let options = &self.options;
+        let max_bytes = cgroup::parse_memory_to_bytes(&options.memory_limit)?;
+        let settings = session::clickhouse(max_bytes, options.threads, &options.settings);
+        self.settings_clause = format!("SETTINGS {}", settings.join(", "));
+
+        let count_query = format!("SELECT COUNT(*) FROM {}", options.table);
+        self.rows = self
+            .runtime
+            .block_on(self.client.query(&count_query).fetch_one::<u64>())?;
+        Ok(self.rows)
+    }
+
+    fn sort(&mut self) -> Result<SortResult, Box<dyn Error + Send + Sync>> {
+        let columns = &self.options.columns;
+        let select_query = format!(
+            "SELECT {} FROM {} ORDER BY {} {}",
+            columns.select(),
+            self.options.table,
+            columns.key,
+            self.settings_clause
+        );
+        let query = match &self.options.output {
+            Some(output) => format!(
+                "{} INTO OUTFILE '{}' FORMAT Native",
+                select_query,
+                output.display()
+            ),
+            None => format!("{} FORMAT Null", select_query),
+        };
+
+        let start = Instant::now();
+        self.runtime.block_on(self.client.query(&query).execute())?;
+
+        Ok(SortResult {
+            rows: self.rows,
+            seconds: start.elapsed().as_secs_f64(),
+            // The server's memory is not visible from here; sort-clickhouse polls it
+            peak_rss_bytes: None,
+        })
+    }
+}
@@ -0,0 +1,92 @@
+use super::{SortOptions, SortResult, Sorter, session};
+use crate::metrics;
+use crate::rss;
+use duckdb::Connection;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Sorts inside this process; the output is a Parquet file
+pub struct DuckDbSorter {
+    conn: Connection,
+    options: SortOptions,
+    rows: u64,
+}
+
+impl DuckDbSorter {
+    pub fn new(options: &SortOptions) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self {
+            conn: Connection::open(&options.connection)?,
+            options: options.clone(),
+            rows: 0,
+        })
+    }
+}
+
+impl Sorter for DuckDbSorter {
+    fn prepare(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let options = &self.options;
+        for statement in session::duckdb(
+            &options.memory_limit,
+            options.threads,
+            options.temp_dir.as_deref(),
+            &options.settings,
+        ) {
+            self.conn.execute(&statement, [])?;
+        }
+
+        // DuckDB spills next to the database file unless a temp directory is given
+        let spill_dir = match &options.temp_dir {
+            Some(dir) => dir.clone(),
+            None => PathBuf::from(format!("{}.tmp", options.connection)),
+        };
+        metrics::watch_spill_dir(&spill_dir);
+
+        let rows: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM {}", options.table),
+            [],
+            |row| row.get(0),
+        )?;
+        self.rows = rows as u64;
+        Ok(self.rows)
+    }
+
+    fn sort(&mut self) -> Result<SortResult, Box<dyn Error + Send + Sync>> {
+        // Quote table name as an identifier: "foo""bar"
+        let table = format!("\"{}\"", self.options.table.replace('"', "\"\""));
+        let columns = &self.options.columns;
+        let select_query = format!(
+            "SELECT {} FROM {} ORDER BY {}",
+            columns.select(),
+            table,
+            columns.key
+        );
+
+        let start = Instant::now();
+        match &self.options.output {
+            Some(output) => {
+                let path = output.display().to_string().replace('\'', "''");
+                self.conn.execute(
+                    &format!(
+                        "COPY ({}) TO '{}' (FORMAT PARQUET, PRESERVE_ORDER true)",
+                        select_query, path
+                    ),
+                    [],
+                )?;
+            }
+            None => {
+                let mut stmt = self
+                    .conn
+                    .prepare(&format!("EXPLAIN ANALYZE {}", select_query))?;
+                let mut rows = stmt.query([])?;
+                while rows.next()?.is_some() {}
+            }
+        }
+
+        Ok(SortResult {
+            rows: self.rows,
+            seconds: start.elapsed().as_secs_f64(),
+            peak_rss_bytes: rss::self_peak_rss(),
+        })
+    }
+}
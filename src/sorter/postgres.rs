@@ -0,0 +1,89 @@
+use super::{SortOptions, SortResult, Sorter, session};
+use crate::cgroup;
+use crate::rss::{self, PeakSampler};
+use postgres::{Client, NoTls};
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+/// PostgreSQL's default max_parallel_workers_per_gather is too low for the benchmark
+const DEFAULT_WORKERS: usize = 7;
+
+/// Sorts in one read-only transaction; the server writes the output as binary COPY
+pub struct PostgresSorter {
+    client: Client,
+    options: SortOptions,
+    rows: u64,
+}
+
+impl PostgresSorter {
+    pub fn new(options: &SortOptions) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self {
+            client: Client::connect(&options.connection, NoTls)?,
+            options: options.clone(),
+            rows: 0,
+        })
+    }
+}
+
+impl Sorter for PostgresSorter {
+    fn prepare(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        let options = &self.options;
+        // The leader also sorts, but the budget is split over the workers like sort-postgres does
+        let workers = options.threads.unwrap_or(DEFAULT_WORKERS).max(1);
+        let work_mem_kb =
+            cgroup::parse_memory_to_bytes(&options.memory_limit)? / 1024 / workers as u64;
+
+        self.client.batch_execute("BEGIN")?;
+        self.client
+            .batch_execute("SET LOCAL transaction_read_only = on")?;
+        for statement in session::postgres(work_mem_kb, workers, &options.settings) {
+            self.client.batch_execute(&statement)?;
+        }
+
+        let rows: i64 = self
+            .client
+            .query_one(&format!("SELECT COUNT(*) FROM {}", options.table), &[])?
+            .get(0);
+        self.rows = rows as u64;
+        Ok(self.rows)
+    }
+
+    fn sort(&mut self) -> Result<SortResult, Box<dyn Error + Send + Sync>> {
+        let columns = &self.options.columns;
+        let select_query = format!(
+            "SELECT {} FROM {} ORDER BY {}",
+            columns.select(),
+            self.options.table,
+            columns.key
+        );
+        let query = match &self.options.output {
+            Some(output) => {
+                // PostgreSQL requires absolute paths for COPY TO FILE
+                let path = std::path::absolute(output)?;
+                let path = path.display().to_string().replace('\'', "''");
+                format!("COPY ({}) TO '{}' (FORMAT BINARY)", select_query, path)
+            }
+            None => format!("EXPLAIN ANALYZE {}", select_query),
+        };
+
+        // Only the leader backend is sampled; sort-postgres also follows the workers
+        let backend_pid: i32 = self
+            .client
+            .query_one("SELECT pg_backend_pid()", &[])?
+            .get(0);
+        let sampler = PeakSampler::start(Duration::from_millis(100), move || {
+            rss::private_rss(backend_pid as u32, "postgres")
+        });
+        let start = Instant::now();
+        self.client.batch_execute(&query)?;
+        let seconds = start.elapsed().as_secs_f64();
+        let peak_rss_bytes = sampler.finish();
+        self.client.batch_execute("COMMIT")?;
+
+        Ok(SortResult {
+            rows: self.rows,
+            seconds,
+            peak_rss_bytes,
+        })
+    }
+}
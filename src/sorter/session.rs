@@ -0,0 +1,80 @@
+//! The settings each engine sorts under, built as SQL so the sort-* binaries
+//! and the `Sorter` impls apply the same ones without needing the database crates.
+
+use super::setting_literal;
+use std::path::Path;
+
+/// Nudge PostgreSQL's optimizer to actually use the workers, and let it spill without limit
+pub const POSTGRES_PARALLEL: &[&str] = &[
+    "SET LOCAL parallel_tuple_cost = 0",
+    "SET LOCAL parallel_setup_cost = 0",
+    "SET LOCAL min_parallel_table_scan_size = '0'",
+    "SET LOCAL enable_parallel_append = on",
+    "SET LOCAL temp_file_limit = -1",
+];
+
+/// PostgreSQL's work_mem is per sort node, so callers pass their budget
+/// already split over the workers that sort
+pub fn postgres(work_mem_kb: u64, workers: usize, settings: &[(String, String)]) -> Vec<String> {
+    let mut statements = vec![
+        format!("SET LOCAL work_mem = '{}kB'", work_mem_kb),
+        format!("SET LOCAL max_parallel_workers_per_gather = {}", workers),
+    ];
+    statements.extend(POSTGRES_PARALLEL.iter().map(|s| s.to_string()));
+    statements.extend(
+        settings
+            .iter()
+            .map(|(key, value)| format!("SET LOCAL {} = {}", key, setting_literal(value))),
+    );
+    statements
+}
+
+/// DuckDB's SET statements; `memory_limit` is passed through in DuckDB's own syntax
+pub fn duckdb(
+    memory_limit: &str,
+    threads: Option<usize>,
+    temp_dir: Option<&Path>,
+    settings: &[(String, String)],
+) -> Vec<String> {
+    let mut statements = Vec::new();
+    if let Some(threads) = threads {
+        statements.push(format!("SET threads = {}", threads));
+    }
+    if let Some(dir) = temp_dir {
+        statements.push(format!(
+            "SET temp_directory = {}",
+            setting_literal(&dir.display().to_string())
+        ));
+    }
+    statements.push(format!(
+        "SET memory_limit = {}",
+        setting_literal(memory_limit)
+    ));
+    statements.extend(
+        settings
+            .iter()
+            .map(|(key, value)| format!("SET {} = {}", key, setting_literal(value))),
+    );
+    statements
+}
+
+/// The entries of a ClickHouse SETTINGS clause. The ratio setting is zeroed
+/// so `max_bytes` alone decides when the sort spills.
+pub fn clickhouse(
+    max_bytes: u64,
+    threads: Option<usize>,
+    settings: &[(String, String)],
+) -> Vec<String> {
+    let mut entries = Vec::new();
+    if let Some(threads) = threads {
+        entries.push(format!("max_threads = {}", threads));
+    }
+    entries.push(format!("max_bytes_before_external_sort = {}", max_bytes));
+    entries.push("max_bytes_ratio_before_external_sort = 0".to_string());
+    entries.extend(
+        settings
+            .iter()
+            .map(|(key, value)| format!("{} = {}", key, setting_literal(value))),
+    );
+    entries
+}
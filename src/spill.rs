@@ -0,0 +1,100 @@
+//! How much a sort spilled to a local temp directory (DuckDB).
+//!
+//! A sort that fits in its memory limit never touches the temp directory, so
+//! its size over time is the plain way to tell whether a run really was an
+//! external sort. `SpillWatcher` samples the directory on a background thread
+//! while the sort runs and keeps the largest size and file count it saw.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Size and number of the files under a directory
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DirUsage {
+    pub bytes: u64,
+    pub files: u64,
+}
+
+/// What is under `dir` right now. Spill files come and go while we walk, so
+/// anything that cannot be read is left out.
+pub fn dir_usage(dir: &Path) -> DirUsage {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return DirUsage::default();
+    };
+    let mut usage = DirUsage::default();
+    for entry in entries.flatten() {
+        match entry.metadata() {
+            Ok(meta) if meta.is_dir() => {
+                let below = dir_usage(&entry.path());
+                usage.bytes += below.bytes;
+                usage.files += below.files;
+            }
+            Ok(meta) => {
+                usage.bytes += meta.len();
+                usage.files += 1;
+            }
+            Err(_) => {}
+        }
+    }
+    usage
+}
+
+/// Samples a spill directory until `finish`
+pub struct SpillWatcher {
+    peak_bytes: Arc<AtomicU64>,
+    peak_files: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl SpillWatcher {
+    pub fn start(dir: PathBuf, interval: Duration) -> Self {
+        let peak_bytes = Arc::new(AtomicU64::new(0));
+        let peak_files = Arc::new(AtomicU64::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = {
+            let (peak_bytes, peak_files) = (peak_bytes.clone(), peak_files.clone());
+            let stop = stop.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let usage = dir_usage(&dir);
+                    peak_bytes.fetch_max(usage.bytes, Ordering::Relaxed);
+                    peak_files.fetch_max(usage.files, Ordering::Relaxed);
+                    thread::sleep(interval);
+                }
+            })
+        };
+        SpillWatcher {
+            peak_bytes,
+            peak_files,
+            stop,
+            handle,
+        }
+    }
+
+    /// Stops sampling; the most bytes and files the directory held at once
+    pub fn finish(self) -> DirUsage {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = self.handle.join();
+        DirUsage {
+            bytes: self.peak_bytes.load(Ordering::Relaxed),
+            files: self.peak_files.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The SPILL_* lines that go next to TIMING: the peak `watched` while the
+/// sort ran, and the temporary files the engine still `held` after it
+pub fn report(watched: DirUsage, held: DirUsage) -> String {
+    format!(
+        "SPILL_PEAK_MB: {:.1}\nSPILL_PEAK_FILES: {}\nSPILL_HELD_MB: {:.1}\nSPILL_HELD_FILES: {}",
+        watched.bytes as f64 / (1024.0 * 1024.0),
+        watched.files,
+        held.bytes as f64 / (1024.0 * 1024.0),
+        held.files
+    )
+}
@@ -0,0 +1,347 @@
+//! Async access to input records.
+//!
+//! `RecordStream` reads batches on tokio's blocking pool and hands them out as a
+//! `futures_core::Stream`. The channel in between holds a fixed number of
+//! batches, so a slow consumer (an HTTP upload, say) stalls the reader instead
+//! of letting the whole input pile up in memory.
+//!
+//! `ChannelReader` goes the other way: it turns a channel of already encoded
+//! chunks back into an `AsyncRead`, e.g. for a streaming HTTP request body.
+//! With an `UploadProgress` attached it also reports the bytes that body has
+//! taken and how much of the input they were encoded from.
+//!
+//! A channel bounded by a chunk count holds more bytes the bigger the chunks
+//! are. `ByteBudget` bounds the bytes instead, across any number of channels:
+//! a sender takes a `BudgetPermit` for a chunk's bytes before sending it, and
+//! the bytes are given back when the receiver drops the permit.
+
+use crate::buffer_pool::BufferPool;
+use crate::latency;
+use crate::metrics;
+use crate::records::{InputFormat, RecordBatch, RecordReader};
+use futures_core::Stream;
+use std::future::poll_fn;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio::sync::mpsc::{Receiver, channel};
+use tokio::task;
+
+/// Batches of records read in the background. A read error ends the stream.
+pub struct RecordStream {
+    rx: Receiver<io::Result<RecordBatch>>,
+}
+
+impl RecordStream {
+    /// Streams `path` in batches of `batch_size` rows, buffering at most
+    /// `capacity` batches ahead of the consumer. Must be called inside a tokio runtime.
+    pub fn open(format: InputFormat, path: PathBuf, batch_size: usize, capacity: usize) -> Self {
+        Self::spawn(capacity, move |tx| {
+            match RecordReader::open(format, &path) {
+                Ok(reader) => forward(reader, batch_size, tx),
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(e));
+                }
+            }
+        })
+    }
+
+    /// Like `open`, for records coming from any reader
+    pub fn from_reader<R: Read + Send + 'static>(
+        reader: RecordReader<R>,
+        batch_size: usize,
+        capacity: usize,
+    ) -> Self {
+        Self::spawn(capacity, move |tx| forward(reader, batch_size, tx))
+    }
+
+    fn spawn<F>(capacity: usize, read: F) -> Self
+    where
+        F: FnOnce(tokio::sync::mpsc::Sender<io::Result<RecordBatch>>) + Send + 'static,
+    {
+        let (tx, rx) = channel(capacity.max(1));
+        task::spawn_blocking(move || read(tx));
+        Self { rx }
+    }
+
+    /// The next batch, for callers that do not use `StreamExt`
+    pub async fn next_batch(&mut self) -> Option<io::Result<RecordBatch>> {
+        poll_fn(|cx| self.rx.poll_recv(cx)).await
+    }
+}
+
+impl Stream for RecordStream {
+    type Item = io::Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// Sends batches until the input ends, a read fails or the stream is dropped
+fn forward<R: Read>(
+    mut reader: RecordReader<R>,
+    batch_size: usize,
+    tx: tokio::sync::mpsc::Sender<io::Result<RecordBatch>>,
+) {
+    loop {
+        let item = match reader.next_batch(batch_size) {
+            Ok(Some(batch)) => Ok(batch),
+            Ok(None) => return,
+            Err(e) => Err(e),
+        };
+        let failed = item.is_err();
+        if tx.blocking_send(item).is_err() || failed {
+            return;
+        }
+    }
+}
+
+/// Bytes that may wait in the channels between senders and receivers at once
+#[derive(Debug)]
+pub struct ByteBudget {
+    max: u64,
+    /// Bytes taken, and the most ever taken at once
+    state: Mutex<(u64, u64)>,
+    freed: Condvar,
+}
+
+impl ByteBudget {
+    pub fn new(max: u64) -> Arc<Self> {
+        Arc::new(Self {
+            max,
+            state: Mutex::new((0, 0)),
+            freed: Condvar::new(),
+        })
+    }
+
+    /// Blocks until `bytes` more fit in the budget and takes them. A chunk
+    /// larger than the whole budget waits until nothing else is taken and
+    /// then goes alone, rather than waiting forever.
+    pub fn acquire(self: &Arc<Self>, bytes: u64) -> BudgetPermit {
+        let mut state = self.state.lock().unwrap();
+        while state.0 > 0 && state.0 + bytes > self.max {
+            state = self.freed.wait(state).unwrap();
+        }
+        state.0 += bytes;
+        state.1 = state.1.max(state.0);
+        BudgetPermit {
+            budget: self.clone(),
+            bytes,
+        }
+    }
+
+    /// Bytes taken now
+    pub fn used(&self) -> u64 {
+        self.state.lock().unwrap().0
+    }
+
+    /// The most bytes ever taken at once
+    pub fn peak(&self) -> u64 {
+        self.state.lock().unwrap().1
+    }
+}
+
+/// Bytes taken from a `ByteBudget`, given back when the permit is dropped
+#[derive(Debug)]
+pub struct BudgetPermit {
+    budget: Arc<ByteBudget>,
+    bytes: u64,
+}
+
+impl Drop for BudgetPermit {
+    fn drop(&mut self) {
+        self.budget.state.lock().unwrap().0 -= self.bytes;
+        self.budget.freed.notify_all();
+    }
+}
+
+/// Bytes an upload has sent, shared by all its streams: the encoded data, and
+/// the input bytes it was encoded from. Prints a line every 5% of the input,
+/// or every GB sent when the input size is unknown.
+#[derive(Debug)]
+pub struct UploadProgress {
+    input_total: Option<u64>,
+    started: Instant,
+    sent: AtomicU64,
+    input: AtomicU64,
+    /// The last 5% of the input (or GB sent) a line was printed for
+    printed: AtomicU64,
+}
+
+impl UploadProgress {
+    /// Progress of an upload of `input_total` input bytes, None for a stream
+    pub fn new(input_total: Option<u64>) -> Arc<Self> {
+        Arc::new(Self {
+            input_total,
+            started: Instant::now(),
+            sent: AtomicU64::new(0),
+            input: AtomicU64::new(0),
+            printed: AtomicU64::new(0),
+        })
+    }
+
+    /// Counts `sent` more bytes sent, encoded from `input` bytes of the input
+    pub fn add(&self, sent: u64, input: u64) {
+        let sent = self.sent.fetch_add(sent, Ordering::Relaxed) + sent;
+        let input = self.input.fetch_add(input, Ordering::Relaxed) + input;
+        let step = match self.input_total {
+            Some(total) if total > 0 => input.min(total) * 20 / total,
+            Some(_) => 0,
+            None => sent >> 30,
+        };
+        if self.printed.fetch_max(step, Ordering::Relaxed) < step {
+            println!("{}", self.line());
+        }
+    }
+
+    /// Bytes sent so far, and the input bytes they were encoded from
+    pub fn sent(&self) -> (u64, u64) {
+        (
+            self.sent.load(Ordering::Relaxed),
+            self.input.load(Ordering::Relaxed),
+        )
+    }
+
+    /// "Uploaded 512.0 MB at 85.3 MB/s, 45% of the input"
+    pub fn line(&self) -> String {
+        let (sent, input) = self.sent();
+        let secs = self.started.elapsed().as_secs_f64().max(1e-9);
+        let mut line = format!(
+            "Uploaded {:.1} MB at {:.1} MB/s",
+            sent as f64 / 1_000_000.0,
+            sent as f64 / 1_000_000.0 / secs
+        );
+        if let Some(total) = self.input_total.filter(|&total| total > 0) {
+            line.push_str(&format!(
+                ", {:.0}% of the input",
+                input.min(total) as f64 * 100.0 / total as f64
+            ));
+        }
+        line
+    }
+}
+
+/// `AsyncRead` over a channel of encoded chunks, each sent together with the
+/// number of rows it holds, used as a streaming HTTP request body. Counts
+/// uploaded bytes and rows and records how long each chunk took to drain.
+/// Items may be any type that converts into such a `(bytes, rows)` pair.
+pub struct ChannelReader<T = (Vec<u8>, u64)> {
+    rx: Receiver<T>,
+    current_chunk: Option<Vec<u8>>,
+    pos: usize,
+    /// When the HTTP body started consuming the current chunk
+    chunk_started: Instant,
+    total_rows: Arc<AtomicU64>,
+    progress: Option<Arc<UploadProgress>>,
+    /// Input bytes an item was encoded from, for `progress`
+    input_bytes: fn(&T) -> u64,
+    /// Input bytes of the current chunk, and those not reported yet
+    chunk_input: (u64, u64),
+    /// Where drained chunks go back to, if the senders recycle them
+    pool: Option<Arc<BufferPool<u8>>>,
+}
+
+impl<T> ChannelReader<T> {
+    pub fn new(rx: Receiver<T>, total_rows: Arc<AtomicU64>) -> Self {
+        Self {
+            rx,
+            current_chunk: None,
+            pos: 0,
+            chunk_started: Instant::now(),
+            total_rows,
+            progress: None,
+            input_bytes: |_| 0,
+            chunk_input: (0, 0),
+            pool: None,
+        }
+    }
+
+    /// Gives every chunk back to `pool` once it has been read
+    pub fn with_pool(mut self, pool: Arc<BufferPool<u8>>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Reports the bytes read to `progress`; `input_bytes` tells how many
+    /// input bytes an item was encoded from
+    pub fn with_progress(
+        mut self,
+        progress: Arc<UploadProgress>,
+        input_bytes: fn(&T) -> u64,
+    ) -> Self {
+        self.progress = Some(progress);
+        self.input_bytes = input_bytes;
+        self
+    }
+}
+
+impl<T: Into<(Vec<u8>, u64)>> AsyncRead for ChannelReader<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            // Try to read from current chunk
+            if let Some(ref chunk) = self.current_chunk {
+                let pos = self.pos;
+                let chunk_len = chunk.len();
+                if pos < chunk_len {
+                    let remaining = chunk_len - pos;
+                    let to_copy = remaining.min(buf.remaining());
+                    buf.put_slice(&chunk[pos..pos + to_copy]);
+                    self.pos += to_copy;
+                    metrics::add_bytes_uploaded(to_copy as u64);
+                    if let Some(ref progress) = self.progress {
+                        // The chunk's input bytes in proportion, the rest with its last byte
+                        let (input, left) = self.chunk_input;
+                        let part = match self.pos >= chunk_len {
+                            true => left,
+                            false => (input * to_copy as u64 / chunk_len as u64).min(left),
+                        };
+                        progress.add(to_copy as u64, part);
+                        self.chunk_input.1 -= part;
+                    }
+
+                    // Clear chunk if fully consumed
+                    if self.pos >= chunk_len {
+                        let chunk = self.current_chunk.take();
+                        if let (Some(pool), Some(chunk)) = (&self.pool, chunk) {
+                            pool.give(chunk);
+                        }
+                        self.pos = 0;
+                        latency::record(self.chunk_started.elapsed());
+                    }
+
+                    return Poll::Ready(Ok(()));
+                }
+            }
+
+            // Need new chunk from channel; poll_recv wakes us when one arrives
+            match self.rx.poll_recv(cx) {
+                Poll::Ready(Some(item)) => {
+                    let input = (self.input_bytes)(&item);
+                    self.chunk_input = (input, input);
+                    let (chunk, rows) = item.into();
+                    self.total_rows.fetch_add(rows, Ordering::Relaxed);
+                    metrics::add_rows_loaded(rows);
+
+                    self.current_chunk = Some(chunk);
+                    self.pos = 0;
+                    self.chunk_started = Instant::now();
+                }
+                Poll::Ready(None) => {
+                    // Channel closed, EOF
+                    return Poll::Ready(Ok(()));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Optional OpenTelemetry tracing.
+//!
+//! The binaries always create `tracing` spans for file reads, encoding, ingest,
+//! sorting and export; they cost next to nothing while no subscriber is
+//! installed. Built with `util-otel` and run with `OTEL_EXPORTER_OTLP_ENDPOINT`
+//! set, `init` exports those spans over OTLP/HTTP.
+
+/// Flushes pending spans when dropped. Keep it alive for the whole of `main`.
+pub struct TelemetryGuard {
+    #[cfg(feature = "util-otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+#[cfg(not(feature = "util-otel"))]
+pub fn init(_service_name: &str) -> TelemetryGuard {
+    TelemetryGuard {}
+}
+
+#[cfg(feature = "util-otel")]
+pub fn init(service_name: &str) -> TelemetryGuard {
+    use opentelemetry::trace::TracerProvider as _;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_none() {
+        return TelemetryGuard { provider: None };
+    }
+
+    // The exporter picks up the endpoint and headers from the standard OTEL_* variables
+    let exporter = match opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()
+    {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            eprintln!("Warning: OTLP tracing disabled: {}", e);
+            return TelemetryGuard { provider: None };
+        }
+    };
+
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            opentelemetry_sdk::Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        )
+        .build();
+    let tracer = provider.tracer(service_name.to_string());
+    let subscriber =
+        tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+    if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
+        eprintln!("Warning: OTLP tracing disabled: {}", e);
+        return TelemetryGuard { provider: None };
+    }
+
+    TelemetryGuard {
+        provider: Some(provider),
+    }
+}
+
+#[cfg(feature = "util-otel")]
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("Warning: failed to flush traces: {}", e);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Per-thread load rates, printed while a load runs.
+//!
+//! The process-wide counters in [`crate::metrics`] show a stalled connection
+//! only as a lower total rate. A loader thread that calls `register` gets
+//! counters of its own: the same `metrics::add_rows_loaded` and
+//! `metrics::add_bytes_uploaded` calls also bump them, so the hot loops need no
+//! handle. `start` prints every registered thread's rows/s and MB/s, and the
+//! combined rate, every interval.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default)]
+struct Counters {
+    rows: AtomicU64,
+    bytes: AtomicU64,
+    finished: AtomicBool,
+}
+
+static THREADS: Mutex<Vec<(String, Arc<Counters>)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Counters>>> = const { RefCell::new(None) };
+}
+
+/// Counts the calling thread's rows and bytes under `name` until dropped
+pub struct Registration {
+    counters: Arc<Counters>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.counters.finished.store(true, Ordering::Relaxed);
+        CURRENT.with(|current| current.borrow_mut().take());
+    }
+}
+
+/// Gives the calling thread its own counters, reported as `name`
+pub fn register(name: impl Into<String>) -> Registration {
+    let counters = Arc::new(Counters::default());
+    THREADS
+        .lock()
+        .unwrap()
+        .push((name.into(), counters.clone()));
+    CURRENT.with(|current| *current.borrow_mut() = Some(counters.clone()));
+    Registration { counters }
+}
+
+pub(crate) fn add_rows(rows: u64) {
+    CURRENT.with(|current| {
+        if let Some(counters) = current.borrow().as_ref() {
+            counters.rows.fetch_add(rows, Ordering::Relaxed);
+        }
+    });
+}
+
+pub(crate) fn add_bytes(bytes: u64) {
+    CURRENT.with(|current| {
+        if let Some(counters) = current.borrow().as_ref() {
+            counters.bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+    });
+}
+
+/// One registered thread's totals at some point
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThreadTotals {
+    pub name: String,
+    pub rows: u64,
+    pub bytes: u64,
+    pub finished: bool,
+}
+
+/// Totals of every thread registered so far, in registration order
+pub fn snapshot() -> Vec<ThreadTotals> {
+    THREADS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, counters)| ThreadTotals {
+            name: name.clone(),
+            rows: counters.rows.load(Ordering::Relaxed),
+            bytes: counters.bytes.load(Ordering::Relaxed),
+            finished: counters.finished.load(Ordering::Relaxed),
+        })
+        .collect()
+}
+
+/// The rates between two snapshots `secs` apart, one line per thread and a
+/// last one for all of them. A running thread that loaded nothing is marked
+/// stalled; finished threads are left out.
+pub fn report(before: &[ThreadTotals], now: &[ThreadTotals], secs: f64) -> String {
+    let mut out = format!("Rates over the last {:.1}s:\n", secs);
+    let (mut all_rows, mut all_bytes) = (0u64, 0u64);
+    // Threads are only ever added, so `before` lines up with the start of `now`
+    for (i, thread) in now.iter().enumerate() {
+        let (rows, bytes) = match before.get(i) {
+            Some(b) => (thread.rows - b.rows, thread.bytes - b.bytes),
+            None => (thread.rows, thread.bytes),
+        };
+        all_rows += rows;
+        all_bytes += bytes;
+        if thread.finished && rows == 0 {
+            continue;
+        }
+        let stalled = if rows == 0 && !thread.finished {
+            ", stalled"
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "  {}: {:.0} rows/s, {:.1} MB/s{}\n",
+            thread.name,
+            rows as f64 / secs,
+            bytes as f64 / secs / 1_000_000.0,
+            stalled
+        ));
+    }
+    out.push_str(&format!(
+        "  All threads: {:.0} rows/s, {:.1} MB/s",
+        all_rows as f64 / secs,
+        all_bytes as f64 / secs / 1_000_000.0
+    ));
+    out
+}
+
+/// Prints a `report` every `interval` from a background thread until the
+/// process exits. Safe to call more than once.
+pub fn start(interval: Duration) {
+    static STARTED: Once = Once::new();
+    STARTED.call_once(|| {
+        thread::spawn(move || {
+            let mut before = snapshot();
+            let mut last = Instant::now();
+            loop {
+                thread::sleep(interval);
+                let now = snapshot();
+                if now.iter().any(|thread| !thread.finished) {
+                    println!("{}", report(&before, &now, last.elapsed().as_secs_f64()));
+                }
+                before = now;
+                last = Instant::now();
+            }
+        });
+    });
+}
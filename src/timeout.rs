@@ -0,0 +1,126 @@
+//! Giving up on a sort that runs too long (`--timeout`).
+//!
+//! The budget covers the sort queries only, every run and shard together, not
+//! the statistics and EXPLAIN before them nor what the sorter does between
+//! queries (reading the query log, dropping caches, reopening a database): a
+//! `Budget` is charged the time the sorter measures for each query, and a
+//! query may run for what is left of it. When it runs out, the sorter cancels
+//! the query on the server (DuckDB's interrupt, PostgreSQL's
+//! `pg_cancel_backend`, ClickHouse's `KILL QUERY`) instead of leaving it
+//! behind, prints `TIMEOUT:` where TIMING would go and exits with
+//! `EXIT_CODE`, the code of `timeout(1)`, so the harness counts it the same.
+//!
+//! `Watchdog` does the cancelling for the blocking clients from a thread of its
+//! own. It keeps cancelling until it is disarmed, so a cancel that lands just
+//! before the query starts doesn't let it run on.
+
+use clap::Args;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Exit code of a sort that timed out
+pub const EXIT_CODE: i32 = 124;
+
+/// How often a fired watchdog cancels again
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The longest a sort may run
+#[derive(Args, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Timeout {
+    /// Cancel the sort on the server once its queries have run this many
+    /// seconds (every run and shard together, nothing in between them),
+    /// report TIMEOUT and exit with code 124
+    #[arg(long = "timeout", value_name = "SECS")]
+    pub secs: Option<u64>,
+}
+
+impl Timeout {
+    pub fn check(&self) -> Result<(), String> {
+        match self.secs {
+            Some(0) => Err("--timeout must be at least 1 second".into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The whole budget, before any query
+    pub fn start(&self) -> Budget {
+        Budget {
+            limit: self.secs.map(Duration::from_secs),
+            used: Duration::ZERO,
+        }
+    }
+
+    /// The line that stands in for TIMING
+    pub fn report(&self) -> String {
+        format!("TIMEOUT: {}", self.secs.unwrap_or_default())
+    }
+}
+
+/// What the sort queries have used of a started `Timeout`
+#[derive(Copy, Clone, Debug)]
+pub struct Budget {
+    limit: Option<Duration>,
+    used: Duration,
+}
+
+impl Budget {
+    /// What is left: None without a timeout, zero once it is used up
+    pub fn remaining(&self) -> Option<Duration> {
+        self.limit.map(|limit| limit.saturating_sub(self.used))
+    }
+
+    /// Charges the time a sort query ran
+    pub fn charge(&mut self, elapsed: Duration) {
+        self.used += elapsed;
+    }
+}
+
+/// Calls a cancel function if a query outlives its deadline
+pub struct Watchdog {
+    disarm: Option<Sender<()>>,
+    fired: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Calls `cancel` once what is left of `budget` has passed, and every
+    /// `RETRY_INTERVAL` after that until disarmed; does nothing without a timeout
+    pub fn arm(budget: Budget, cancel: impl Fn() + Send + 'static) -> Self {
+        let fired = Arc::new(AtomicBool::new(false));
+        let Some(remaining) = budget.remaining() else {
+            return Self {
+                disarm: None,
+                fired,
+                thread: None,
+            };
+        };
+        let (tx, rx) = mpsc::channel::<()>();
+        let flag = fired.clone();
+        let thread = thread::spawn(move || {
+            let mut wait = remaining;
+            // Anything but a timeout means the watchdog was disarmed
+            while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(wait) {
+                flag.store(true, Ordering::SeqCst);
+                cancel();
+                wait = RETRY_INTERVAL;
+            }
+        });
+        Self {
+            disarm: Some(tx),
+            fired,
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops the watchdog; true if it cancelled the query
+    pub fn disarm(mut self) -> bool {
+        drop(self.disarm.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.fired.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,237 @@
+//! Read-ahead of input files with io_uring.
+//!
+//! With `--io uring` the loaders keep `--queue-depth` block reads in flight
+//! per reader thread, so a fast NVMe device sees a deep queue even though
+//! each thread consumes its records in order. Blocks are handed out in file
+//! order as they complete. Built with `util-uring` (Linux only); otherwise
+//! opening a reader fails with a pointer to the feature.
+
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::Path;
+
+/// Bytes per read; large enough that a deep queue covers a few MB
+pub const BLOCK_SIZE: usize = 256 * 1024;
+
+/// Sequential reader of bytes `range` of a file, reading ahead with io_uring
+pub struct UringReader {
+    // Boxed: the ring is a few hundred bytes, and readers sit in enums
+    #[cfg(all(feature = "util-uring", target_os = "linux"))]
+    inner: Box<imp::Reader>,
+    #[cfg(not(all(feature = "util-uring", target_os = "linux")))]
+    never: std::convert::Infallible,
+}
+
+#[cfg(not(all(feature = "util-uring", target_os = "linux")))]
+impl UringReader {
+    /// Opens bytes `range` of the file at `path`, with up to `queue_depth`
+    /// reads of `block_size` bytes in flight
+    pub fn open(
+        _path: &Path,
+        _range: Range<u64>,
+        _queue_depth: u32,
+        _block_size: usize,
+    ) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--io uring requires building with --features util-uring (Linux only)",
+        ))
+    }
+}
+
+#[cfg(not(all(feature = "util-uring", target_os = "linux")))]
+impl Read for UringReader {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        match self.never {}
+    }
+}
+
+#[cfg(all(feature = "util-uring", target_os = "linux"))]
+impl UringReader {
+    /// Opens bytes `range` of the file at `path`, with up to `queue_depth`
+    /// reads of `block_size` bytes in flight
+    pub fn open(
+        path: &Path,
+        range: Range<u64>,
+        queue_depth: u32,
+        block_size: usize,
+    ) -> io::Result<Self> {
+        if queue_depth == 0 || block_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "io_uring queue depth and block size must be at least 1",
+            ));
+        }
+        Ok(Self {
+            inner: Box::new(imp::Reader::open(path, range, queue_depth, block_size)?),
+        })
+    }
+}
+
+#[cfg(all(feature = "util-uring", target_os = "linux"))]
+impl Read for UringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(all(feature = "util-uring", target_os = "linux"))]
+mod imp {
+    use io_uring::{IoUring, opcode, types};
+    use std::collections::VecDeque;
+    use std::fs::File;
+    use std::io;
+    use std::ops::Range;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::FileExt;
+    use std::path::Path;
+
+    pub struct Reader {
+        ring: IoUring,
+        file: File,
+        /// One buffer per read in flight
+        buffers: Vec<Vec<u8>>,
+        /// Input offset and length of the read into each buffer
+        blocks: Vec<(u64, usize)>,
+        /// Raw result of each buffer's read, once it has completed
+        results: Vec<Option<i32>>,
+        /// Buffers with a read submitted, in file order
+        queue: VecDeque<usize>,
+        /// Read position in the buffer at the front of `queue`
+        pos: usize,
+        next_offset: u64,
+        end: u64,
+        in_flight: usize,
+    }
+
+    impl Reader {
+        pub fn open(
+            path: &Path,
+            range: Range<u64>,
+            queue_depth: u32,
+            block_size: usize,
+        ) -> io::Result<Self> {
+            let depth = queue_depth as usize;
+            let mut reader = Self {
+                ring: IoUring::new(queue_depth)?,
+                file: File::open(path)?,
+                buffers: vec![vec![0u8; block_size]; depth],
+                blocks: vec![(0, 0); depth],
+                results: vec![None; depth],
+                queue: VecDeque::with_capacity(depth),
+                pos: 0,
+                next_offset: range.start,
+                end: range.end.max(range.start),
+                in_flight: 0,
+            };
+            for slot in 0..depth {
+                if !reader.push(slot) {
+                    break;
+                }
+            }
+            reader.ring.submit()?;
+            Ok(reader)
+        }
+
+        /// Queues a read of the next block into buffer `slot`; false once the
+        /// whole range has been queued
+        fn push(&mut self, slot: usize) -> bool {
+            if self.next_offset >= self.end {
+                return false;
+            }
+            let len = (self.end - self.next_offset).min(self.buffers[slot].len() as u64) as usize;
+            let entry = opcode::Read::new(
+                types::Fd(self.file.as_raw_fd()),
+                self.buffers[slot].as_mut_ptr(),
+                len as u32,
+            )
+            .offset(self.next_offset)
+            .build()
+            .user_data(slot as u64);
+            // At most one entry per buffer is queued, and the ring has as many
+            // entries as there are buffers
+            unsafe {
+                self.ring
+                    .submission()
+                    .push(&entry)
+                    .expect("submission queue has room for every buffer");
+            }
+            self.blocks[slot] = (self.next_offset, len);
+            self.results[slot] = None;
+            self.next_offset += len as u64;
+            self.in_flight += 1;
+            self.queue.push_back(slot);
+            true
+        }
+
+        fn reap(&mut self) {
+            for cqe in self.ring.completion() {
+                self.results[cqe.user_data() as usize] = Some(cqe.result());
+                self.in_flight -= 1;
+            }
+        }
+
+        /// Waits for the read into buffer `slot` and returns its length.
+        /// A short read is completed with a plain positioned read.
+        fn wait(&mut self, slot: usize) -> io::Result<usize> {
+            loop {
+                self.reap();
+                if self.results[slot].is_some() {
+                    break;
+                }
+                match self.ring.submit_and_wait(1) {
+                    Ok(_) => {}
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            let result = self.results[slot].unwrap();
+            if result < 0 {
+                return Err(io::Error::from_raw_os_error(-result));
+            }
+            let (offset, len) = self.blocks[slot];
+            let done = result as usize;
+            if done < len {
+                self.file
+                    .read_exact_at(&mut self.buffers[slot][done..len], offset + done as u64)?;
+                self.results[slot] = Some(len as i32);
+            }
+            Ok(len)
+        }
+
+        pub fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while let Some(&slot) = self.queue.front() {
+                let len = self.wait(slot)?;
+                if self.pos < len {
+                    let n = buf.len().min(len - self.pos);
+                    buf[..n].copy_from_slice(&self.buffers[slot][self.pos..self.pos + n]);
+                    self.pos += n;
+                    return Ok(n);
+                }
+                // Buffer used up: reuse it for the next block
+                self.queue.pop_front();
+                self.pos = 0;
+                if self.push(slot) {
+                    self.ring.submit()?;
+                }
+            }
+            Ok(0)
+        }
+    }
+
+    impl Drop for Reader {
+        fn drop(&mut self) {
+            // The kernel may still write into the buffers
+            while self.in_flight > 0 {
+                match self.ring.submit_and_wait(1) {
+                    Ok(_) => self.reap(),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(_) => {
+                        std::mem::forget(std::mem::take(&mut self.buffers));
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,346 @@
+//! Checking a sorter's own output (`--verify`).
+//!
+//! compare-outputs checks the engines against each other once the harness has
+//! all their files. `--verify` is the check a single sorter can make on its own:
+//! after the last run, outside the timing, it reads back the file it wrote and
+//! checks that the keys never go down and that the file holds as many rows as
+//! the sort should have produced. Anything else fails the sorter before it
+//! prints TIMING, so a broken export can't pass as a benchmark result.
+//!
+//! Keys compare as bytes, the order of the key column (a blob in DuckDB,
+//! bytea in PostgreSQL, a String in ClickHouse, or an integer the file holds
+//! as 8 big-endian bytes). With `--order-by` the file's sort_key is not the
+//! sort order, and under a collation other than byte order (`--collation`, or
+//! else the text key's own) not its byte order, so only the rows are counted.
+//! Each file of a per-shard sort is sorted on its own and their rows add up.
+//!
+//! `Verifier` takes the keys in file order, so a sorter that reads its output
+//! some other way (sort-duckdb reads Parquet through DuckDB) checks it the same;
+//! `verify_file` reads every format `export::Reader` does. The output of
+//! `--mode distinct` must hold every key once, so there a key may not repeat
+//! (`Verifier::distinct`); how many keys there should be is not known.
+//!
+//! Sorting a `--with-row-id` table the file also carries the row ids. Their
+//! count and sum must be those of the rows the sort saw (`RowIds`), so a lost
+//! row is caught even when a doubled one makes up the count, and within a run
+//! of equal keys ids going down count as ties the sort did not keep in input
+//! order (`Verifier::unstable_ties`). An engine need not sort stably, so that
+//! is reported rather than failed, but an id repeating under one key is a
+//! doubled row.
+//!
+//! The sorters each check their files with a `Check`, built from what the
+//! sort saw, and print the lines its `run` returns.
+
+use crate::collation::Collation;
+use crate::export::{OutputFormat, Reader};
+use crate::schema::{OrderBy, ROW_ID_COLUMN, SortMode};
+use std::error::Error;
+use std::path::Path;
+
+/// Count and sum of row ids, which don't depend on the order of the rows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RowIds {
+    pub rows: u64,
+    pub sum: u128,
+}
+
+impl RowIds {
+    pub fn add(&mut self, id: u64) {
+        self.rows += 1;
+        self.sum += id as u128;
+    }
+
+    /// Combines the ids of two disjoint sets of rows
+    pub fn merge(&mut self, other: &RowIds) {
+        self.rows += other.rows;
+        self.sum += other.sum;
+    }
+
+    /// The PostgreSQL and DuckDB query for the sum of the row ids of the rows
+    /// of `source` matching `where_clause`, as text so it can't overflow
+    pub fn sum_query(source: &str, where_clause: &str) -> String {
+        format!(
+            "SELECT coalesce(sum({}), 0)::text FROM {}{}",
+            ROW_ID_COLUMN, source, where_clause
+        )
+    }
+
+    /// `sum_query` for ClickHouse, whose sum of UInt64s would wrap
+    pub fn sum_query_clickhouse(source: &str, where_clause: &str) -> String {
+        format!(
+            "SELECT toString(sum(toUInt128({}))) FROM {}{}",
+            ROW_ID_COLUMN, source, where_clause
+        )
+    }
+}
+
+/// Checks keys for order as they come, counting them and their row ids
+#[derive(Clone, Debug, Default)]
+pub struct Verifier {
+    check_order: bool,
+    distinct: bool,
+    last_key: Option<Vec<u8>>,
+    last_id: Option<u64>,
+    ids: Option<RowIds>,
+    unstable_ties: u64,
+    rows: u64,
+}
+
+impl Verifier {
+    /// Checks the order of the keys, or with `check_order` false just counts them
+    pub fn new(check_order: bool) -> Self {
+        Self {
+            check_order,
+            ..Self::default()
+        }
+    }
+
+    /// Also refuses a key equal to the one before it, for `--mode distinct`
+    pub fn distinct(self) -> Self {
+        Self {
+            distinct: true,
+            ..self
+        }
+    }
+
+    /// Takes the key of the next row
+    pub fn add(&mut self, key: &[u8]) -> Result<(), String> {
+        self.add_row(key, None)
+    }
+
+    /// Takes the key and the row id, if the file carries them, of the next row
+    pub fn add_row(&mut self, key: &[u8], id: Option<u64>) -> Result<(), String> {
+        if let Some(id) = id {
+            if self.check_order
+                && self.last_key.as_deref() == Some(key)
+                && let Some(last) = self.last_id
+            {
+                if id == last {
+                    return Err(format!(
+                        "row id {} repeats at row {} under key {}",
+                        id,
+                        self.rows,
+                        hex(key)
+                    ));
+                }
+                if id < last {
+                    self.unstable_ties += 1;
+                }
+            }
+            self.ids.get_or_insert_default().add(id);
+        }
+        self.last_id = id;
+        if self.check_order {
+            if let Some(last) = self.last_key.as_deref()
+                && last > key
+            {
+                return Err(format!(
+                    "not sorted at row {}: key {} follows {}",
+                    self.rows,
+                    hex(key),
+                    hex(last)
+                ));
+            }
+            if self.distinct && self.last_key.as_deref() == Some(key) {
+                return Err(format!(
+                    "not distinct at row {}: key {} repeats",
+                    self.rows,
+                    hex(key)
+                ));
+            }
+            self.last_key = Some(key.to_vec());
+        }
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Rows seen so far
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    /// The row ids seen so far; None when the rows carry none
+    pub fn row_ids(&self) -> Option<RowIds> {
+        self.ids
+    }
+
+    /// Rows whose id is lower than that of the row before with an equal key
+    pub fn unstable_ties(&self) -> u64 {
+        self.unstable_ties
+    }
+
+    /// Adds what another verifier saw in a file of its own, for the files of a
+    /// per-shard sort
+    pub fn merge(&mut self, other: &Verifier) {
+        self.rows += other.rows;
+        self.unstable_ties += other.unstable_ties;
+        if let Some(ids) = other.ids {
+            self.ids.get_or_insert_default().merge(&ids);
+        }
+    }
+}
+
+/// What `--verify` checks the output files of a sort against
+#[derive(Clone, Debug)]
+pub struct Check {
+    /// Whether the files' sort_key is in the order to check
+    pub check_order: bool,
+    pub mode: SortMode,
+    /// Rows the sort saw: all of them, or those matching --where
+    pub sorted_rows: u64,
+    pub limit: Option<u64>,
+    /// The ids of those rows, for a table that has them; None when the output
+    /// needn't hold all of them
+    pub ids: Option<RowIds>,
+}
+
+impl Check {
+    /// Whether the files' sort_key is the sort order, as it is only without
+    /// --order-by, and its byte order, as it is only under a binary
+    /// `collation`, or without one when the key's own order is
+    /// (`key_in_byte_order`)
+    pub fn checks_order(
+        order_by: &OrderBy,
+        collation: Option<&Collation>,
+        key_in_byte_order: bool,
+    ) -> bool {
+        order_by.columns.is_empty() && collation.map_or(key_in_byte_order, Collation::is_binary)
+    }
+
+    /// A verifier for one output file
+    pub fn verifier(&self) -> Verifier {
+        match self.mode {
+            SortMode::Sort => Verifier::new(self.check_order),
+            SortMode::Distinct => Verifier::new(self.check_order).distinct(),
+        }
+    }
+
+    /// Checks the output `files` of `format`, each read by `read` (usually
+    /// `verify_file_with`), returning the VERIFY lines for them together
+    pub fn run<P: AsRef<Path>>(
+        &self,
+        files: &[P],
+        format: OutputFormat,
+        mut read: impl FnMut(&Path, Verifier) -> Result<Verifier, Box<dyn Error + Send + Sync>>,
+    ) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        let mut seen = Verifier::default();
+        for path in files {
+            seen.merge(&read(path.as_ref(), self.verifier())?);
+        }
+        // How many distinct keys there are is not counted
+        let expected = match self.mode {
+            SortMode::Sort => expected_rows(self.sorted_rows, self.limit, files.len()),
+            SortMode::Distinct => None,
+        };
+        let mut lines = vec![report(seen.rows(), expected, self.check_order)?];
+        // kvbin and gensort files leave the ids out
+        let ids = self.ids.filter(|_| format.carries_row_ids());
+        if let Some(line) = report_row_ids(&seen, ids, self.check_order)? {
+            lines.push(line);
+        }
+        Ok(lines)
+    }
+}
+
+/// Reads the output file `path` of `format` and checks it, returning its rows
+pub fn verify_file(
+    path: &Path,
+    format: OutputFormat,
+    check_order: bool,
+) -> Result<u64, Box<dyn Error + Send + Sync>> {
+    Ok(verify_file_with(path, format, Verifier::new(check_order))?.rows())
+}
+
+/// `verify_file` with the checks of `verifier`, returning it with what it saw
+pub fn verify_file_with(
+    path: &Path,
+    format: OutputFormat,
+    mut verifier: Verifier,
+) -> Result<Verifier, Box<dyn Error + Send + Sync>> {
+    let mut reader = Reader::open(path, format)?;
+    while let Some(((key, _), id)) = reader.next_row_with_id()? {
+        verifier
+            .add_row(&key, id)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(verifier)
+}
+
+/// Rows the output files should hold: those the sort saw, or at most `limit`
+/// of them. None when each of several files keeps `limit` rows, which depends
+/// on how the rows fall into the shards.
+pub fn expected_rows(sorted_rows: u64, limit: Option<u64>, files: usize) -> Option<u64> {
+    match limit {
+        None => Some(sorted_rows),
+        Some(limit) if files == 1 => Some(sorted_rows.min(limit)),
+        Some(_) => None,
+    }
+}
+
+/// The VERIFY line for `rows` output rows, or the error when they are not the
+/// `expected` ones
+pub fn report(rows: u64, expected: Option<u64>, check_order: bool) -> Result<String, String> {
+    if let Some(expected) = expected
+        && rows != expected
+    {
+        return Err(format!(
+            "--verify: the output holds {} rows, the sort had {}",
+            rows, expected
+        ));
+    }
+    let checked = match (check_order, expected.is_some()) {
+        (true, true) => "sorted, row count matches",
+        (true, false) => "sorted, row count not checked",
+        (false, true) => "row count matches, order not checked",
+        (false, false) => "order and row count not checked",
+    };
+    Ok(format!("VERIFY: OK {} rows ({})", rows, checked))
+}
+
+/// The VERIFY_ROW_IDS line for the row ids `verifier` saw, or the error when
+/// they are not the `expected` ones: those of the rows the sort saw, when the
+/// output should hold all of them. None when the table has no row ids.
+pub fn report_row_ids(
+    verifier: &Verifier,
+    expected: Option<RowIds>,
+    check_order: bool,
+) -> Result<Option<String>, String> {
+    let ids = match (verifier.row_ids(), expected) {
+        (Some(ids), _) => ids,
+        (None, None) => return Ok(None),
+        (None, Some(_)) => return Err("--verify: the output lost the row_id column".into()),
+    };
+    if let Some(expected) = expected {
+        if ids.rows != expected.rows {
+            return Err(format!(
+                "--verify: the output holds {} row ids, the sort had {}",
+                ids.rows, expected.rows
+            ));
+        }
+        if ids.sum != expected.sum {
+            return Err(format!(
+                "--verify: the output's row ids sum to {}, the sort's to {}: rows were lost \
+                 and others doubled",
+                ids.sum, expected.sum
+            ));
+        }
+    }
+    let sum = match expected {
+        Some(_) => "sum matches",
+        None => "sum not checked",
+    };
+    let stability = match (check_order, verifier.unstable_ties()) {
+        (false, _) => "stability not checked".to_string(),
+        (true, 0) => "equal keys in input order".to_string(),
+        (true, n) => format!("{} equal keys out of input order", n),
+    };
+    Ok(Some(format!(
+        "VERIFY_ROW_IDS: OK {} ids ({}, {})",
+        ids.rows, sum, stability
+    )))
+}
+
+/// `bytes` as lowercase hex, the way the checks print keys
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
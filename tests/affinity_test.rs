@@ -0,0 +1,23 @@
+use es_duck::affinity::parse_cpu_list;
+
+#[test]
+fn test_parse_cpu_list() {
+    assert_eq!(parse_cpu_list("3").unwrap(), vec![3]);
+    assert_eq!(parse_cpu_list("0-3").unwrap(), vec![0, 1, 2, 3]);
+    assert_eq!(
+        parse_cpu_list("0-2,8,10-11").unwrap(),
+        vec![0, 1, 2, 8, 10, 11]
+    );
+    // sysfs cpulist files end with a newline
+    assert_eq!(parse_cpu_list("0-1\n").unwrap(), vec![0, 1]);
+    // Overlapping ranges are merged
+    assert_eq!(parse_cpu_list("2-4,3,0-2").unwrap(), vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn test_parse_cpu_list_errors() {
+    assert!(parse_cpu_list("").is_err());
+    assert!(parse_cpu_list("5-3").is_err());
+    assert!(parse_cpu_list("a-b").is_err());
+    assert!(parse_cpu_list("1,x").is_err());
+}
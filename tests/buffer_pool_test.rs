@@ -0,0 +1,65 @@
+use es_duck::buffer_pool::{BufferPool, PoolStats};
+use es_duck::rowbinary::{ColumnType, Encoder, Value};
+
+#[test]
+fn test_pool_recycles_buffers() {
+    let pool = BufferPool::<u64>::new(2);
+    let mut first = pool.take(100);
+    assert!(first.capacity() >= 100);
+    first.extend(0..50);
+    let addr = first.as_ptr();
+    pool.give(first);
+
+    // The same allocation comes back, emptied, and grows if asked for more
+    let again = pool.take(10);
+    assert_eq!((again.as_ptr(), again.len()), (addr, 0));
+    assert!(again.capacity() >= 100);
+    pool.give(again);
+    assert!(pool.take(1000).capacity() >= 1000);
+    assert_eq!(
+        pool.stats(),
+        PoolStats {
+            created: 1,
+            reused: 2
+        }
+    );
+
+    // Buffers beyond `max_free` are freed
+    for _ in 0..3 {
+        pool.give(Vec::with_capacity(8));
+    }
+    let _ = (pool.take(0), pool.take(0), pool.take(0));
+    assert_eq!(
+        pool.stats(),
+        PoolStats {
+            created: 2,
+            reused: 4
+        }
+    );
+}
+
+#[test]
+fn test_encoder_takes_into_pooled_buffers() {
+    let pool = BufferPool::<u8>::new(4);
+    let mut encoder = Encoder::with_capacity(vec![ColumnType::String], 64);
+    let mut sent = Vec::new();
+    for row in [&b"one"[..], b"two", b"three"] {
+        encoder.encode_row(&[Value::Bytes(row)]).unwrap();
+        let chunk = encoder.take_into(pool.take(0));
+        assert!(encoder.is_empty());
+        sent.push(chunk[1..].to_vec());
+        pool.give(chunk);
+    }
+    assert_eq!(
+        sent,
+        vec![b"one".to_vec(), b"two".to_vec(), b"three".to_vec()]
+    );
+    // One buffer for the encoder's second chunk, then the two swap places
+    assert_eq!(
+        pool.stats(),
+        PoolStats {
+            created: 1,
+            reused: 2
+        }
+    );
+}
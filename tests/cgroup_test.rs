@@ -0,0 +1,14 @@
+use es_duck::cgroup::parse_memory_to_bytes;
+
+#[test]
+fn test_parse_memory_to_bytes() {
+    assert_eq!(parse_memory_to_bytes("4GB").unwrap(), 4 << 30);
+    assert_eq!(parse_memory_to_bytes("512m").unwrap(), 512 << 20);
+    assert_eq!(parse_memory_to_bytes("0.5MB").unwrap(), 512 << 10);
+    assert_eq!(parse_memory_to_bytes("1KB").unwrap(), 1024);
+    assert_eq!(parse_memory_to_bytes("64k").unwrap(), 64 << 10);
+    assert_eq!(parse_memory_to_bytes("10000").unwrap(), 10000);
+    assert!(parse_memory_to_bytes("lots").is_err());
+    assert!(parse_memory_to_bytes("1.5").is_err());
+    assert!(parse_memory_to_bytes("KB").is_err());
+}
@@ -0,0 +1,35 @@
+use es_duck::ch_query_log::QueryLogStats;
+
+#[test]
+fn test_from_row_keeps_query_order() {
+    let stats = QueryLogStats::from_row((1 << 20, 1000, 100_000, 3, 2 << 20, 5 << 20));
+    assert_eq!(
+        stats,
+        QueryLogStats {
+            memory_usage: 1 << 20,
+            read_rows: 1000,
+            read_bytes: 100_000,
+            spill_parts: 3,
+            spill_compressed_bytes: 2 << 20,
+            spill_uncompressed_bytes: 5 << 20,
+        }
+    );
+}
+
+#[test]
+fn test_merge_sums_shards_and_keeps_the_larger_peak() {
+    let mut total = QueryLogStats::from_row((3 << 20, 500, 50_000, 2, 1 << 20, 2 << 20));
+    total.merge(&QueryLogStats::from_row((
+        2 << 20,
+        700,
+        70_000,
+        1,
+        1 << 20,
+        3 << 20,
+    )));
+    assert_eq!(
+        total.report(),
+        "QUERY_PEAK_MEMORY_MB: 3.0\nQUERY_READ_ROWS: 1200\nQUERY_READ_MB: 0.1\n\
+         SPILL_PARTS: 3\nSPILL_COMPRESSED_MB: 2.0\nSPILL_UNCOMPRESSED_MB: 5.0"
+    );
+}
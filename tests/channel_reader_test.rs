@@ -0,0 +1,60 @@
+#![cfg(feature = "util-async")]
+
+use es_duck::stream::ChannelReader;
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc::channel;
+
+/// CPU time used by this process so far
+fn process_cpu_time() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: ts is a valid, writable timespec
+    let ret = unsafe { libc::clock_gettime(libc::CLOCK_PROCESS_CPUTIME_ID, &mut ts) };
+    assert_eq!(ret, 0);
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+// Alone in this file so no other test adds to the process CPU time
+#[tokio::test]
+async fn test_slow_producer_does_not_spin_reader() {
+    const CHUNKS: usize = 20;
+    const CHUNK_SIZE: usize = 102 * 1000;
+
+    let (tx, rx) = channel::<(Vec<u8>, u64)>(4);
+    let producer = tokio::spawn(async move {
+        for _ in 0..CHUNKS {
+            tokio::time::sleep(Duration::from_millis(25)).await;
+            tx.send((vec![7u8; CHUNK_SIZE], 1000)).await.unwrap();
+        }
+    });
+
+    let cpu_before = process_cpu_time();
+    let start = Instant::now();
+    let mut reader = ChannelReader::new(rx, Arc::new(AtomicU64::new(0)));
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body).await.unwrap();
+    let wall = start.elapsed();
+    let cpu = process_cpu_time() - cpu_before;
+    producer.await.unwrap();
+
+    assert_eq!(body.len(), CHUNKS * CHUNK_SIZE);
+    println!(
+        "{:.1} MB in {:?} ({:.1} MB/s), CPU {:?}",
+        body.len() as f64 / 1e6,
+        wall,
+        body.len() as f64 / 1e6 / wall.as_secs_f64(),
+        cpu
+    );
+    // Busy-polling the channel keeps a core at 100% for the whole wait
+    assert!(
+        cpu < wall / 4,
+        "reader used {:?} of CPU over {:?} waiting for a slow producer",
+        cpu,
+        wall
+    );
+}
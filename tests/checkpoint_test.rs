@@ -0,0 +1,143 @@
+use es_duck::checkpoint::{self, Checkpoint};
+use es_duck::records::{FixedLayout, InputFormat};
+use std::fs;
+use std::io::Write;
+
+#[test]
+fn test_gensort_units() {
+    let path = "/tmp/test_checkpoint_units.dat";
+    fs::write(path, vec![0u8; 2_500]).unwrap();
+    let units = checkpoint::units(
+        InputFormat::Gensort,
+        FixedLayout::GENSORT,
+        path.as_ref(),
+        0..2_500,
+        1_000,
+    )
+    .unwrap();
+    assert_eq!(units, vec![0..10, 10..20, 20..25]);
+
+    // Records 5..20 selected with --skip/--limit
+    let units = checkpoint::units(
+        InputFormat::Gensort,
+        FixedLayout::GENSORT,
+        path.as_ref(),
+        500..2_000,
+        1_000,
+    )
+    .unwrap();
+    assert_eq!(units, vec![5..15, 15..20]);
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn test_gensort_units_follow_the_layout() {
+    let path = "/tmp/test_checkpoint_units_layout.dat";
+    fs::write(path, vec![0u8; 640]).unwrap();
+    let layout = FixedLayout {
+        key_size: 16,
+        payload_size: 48,
+    };
+    let units =
+        checkpoint::units(InputFormat::Gensort, layout, path.as_ref(), 0..640, 256).unwrap();
+    assert_eq!(units, vec![0..4, 4..8, 8..10]);
+
+    // A checkpoint taken with another layout is not resumed
+    let default = checkpoint::describe(
+        InputFormat::Gensort,
+        FixedLayout::GENSORT,
+        path.as_ref(),
+        &(0..640),
+        "t",
+        256,
+    )
+    .unwrap();
+    let other = checkpoint::describe(
+        InputFormat::Gensort,
+        layout,
+        path.as_ref(),
+        &(0..640),
+        "t",
+        256,
+    )
+    .unwrap();
+    assert_ne!(default, other);
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn test_kvbin_units_start_at_records() {
+    let path = "/tmp/test_checkpoint_kvbin_units.dat";
+    let mut data = Vec::new();
+    let mut offsets = Vec::new();
+    for i in 0..10u32 {
+        offsets.push(data.len() as u64);
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&i.to_be_bytes());
+        data.extend_from_slice(&(i * 3).to_le_bytes());
+        data.extend(std::iter::repeat_n(b'v', (i * 3) as usize));
+    }
+    fs::write(path, &data).unwrap();
+
+    let units = checkpoint::units(
+        InputFormat::Kvbin,
+        FixedLayout::GENSORT,
+        path.as_ref(),
+        0..data.len() as u64,
+        50,
+    )
+    .unwrap();
+    assert_eq!(units.first().unwrap().start, 0);
+    assert_eq!(units.last().unwrap().end, data.len() as u64);
+    for pair in units.windows(2) {
+        assert_eq!(pair[0].end, pair[1].start);
+        assert!(offsets.contains(&pair[1].start));
+    }
+    assert!(units.len() > 1);
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn test_checkpoint_resumes() {
+    let path = "/tmp/test_checkpoint_resumes.ckpt";
+    let _ = fs::remove_file(path);
+
+    let checkpoint = Checkpoint::open(path.as_ref(), "load a").unwrap();
+    assert!(!checkpoint.is_done(&(0..10)));
+    checkpoint.mark_staged(&(0..10)).unwrap();
+    checkpoint.mark_done(&(0..10)).unwrap();
+    checkpoint.mark_staged(&(10..20)).unwrap();
+    drop(checkpoint);
+
+    // A crash in the middle of a line leaves a torn line behind
+    let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(b"done 10 2").unwrap();
+    drop(file);
+
+    let checkpoint = Checkpoint::open(path.as_ref(), "load a").unwrap();
+    assert!(checkpoint.is_done(&(0..10)));
+    assert!(!checkpoint.is_staged(&(0..10)));
+    assert!(checkpoint.is_staged(&(10..20)));
+    assert!(!checkpoint.is_done(&(10..20)));
+    assert_eq!(checkpoint.done_units(), 1);
+    checkpoint.mark_done(&(10..20)).unwrap();
+    drop(checkpoint);
+
+    let checkpoint = Checkpoint::open(path.as_ref(), "load a").unwrap();
+    assert!(checkpoint.is_done(&(10..20)));
+    assert_eq!(checkpoint.done_units(), 2);
+
+    // Another load's checkpoint is refused
+    assert!(Checkpoint::open(path.as_ref(), "load b").is_err());
+
+    checkpoint.remove().unwrap();
+    assert!(!std::path::Path::new(path).exists());
+}
+
+#[test]
+fn test_checkpoint_rejects_garbage() {
+    let path = "/tmp/test_checkpoint_garbage.ckpt";
+    fs::write(path, "load a\nhalf done 1 2\n").unwrap();
+    assert!(Checkpoint::open(path.as_ref(), "load a").is_err());
+    let _ = fs::remove_file(path);
+}
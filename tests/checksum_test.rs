@@ -0,0 +1,61 @@
+use es_duck::checksum::{self, Checksum};
+use std::path::Path;
+
+#[test]
+fn test_checksum_is_order_independent() {
+    let records: Vec<[u8; 100]> = (0..50u8).map(|i| [i; 100]).collect();
+
+    let mut forward = Checksum::new();
+    for record in &records {
+        forward.add(record);
+    }
+    let mut backward = Checksum::new();
+    for record in records.iter().rev() {
+        backward.add(record);
+    }
+    assert_eq!(forward, backward);
+    assert_eq!(forward.records, 50);
+
+    // Merging the checksums of two halves gives the whole
+    let flat: Vec<u8> = records.concat();
+    let mut first = Checksum::new();
+    first.add_records(&flat[..2000], 100);
+    let mut second = Checksum::new();
+    second.add_records(&flat[2000..], 100);
+    first.merge(&second);
+    assert_eq!(first, forward);
+
+    // A changed byte changes the sum
+    let mut changed = flat.clone();
+    changed[4321] ^= 1;
+    let mut other = Checksum::new();
+    other.add_records(&changed, 100);
+    assert_ne!(other, forward);
+}
+
+#[test]
+fn test_crc32_matches_zlib() {
+    let mut sum = Checksum::new();
+    sum.add(b"123456789");
+    assert_eq!(sum.sum, 0xcbf43926);
+}
+
+#[test]
+fn test_sum_file_roundtrip() {
+    let sum = Checksum {
+        records: 1000,
+        sum: 0x1f3a3b1c7e44,
+    };
+    assert_eq!(sum.to_string(), "Records: 1000\nChecksum: 1f3a3b1c7e44\n");
+    assert_eq!(checksum::parse(&sum.to_string()).unwrap(), sum);
+
+    let path = checksum::sum_path(Path::new("/tmp/test_checksum_roundtrip.dat"));
+    assert_eq!(path, Path::new("/tmp/test_checksum_roundtrip.sum"));
+    sum.write(&path).unwrap();
+    assert_eq!(Checksum::read(&path).unwrap(), sum);
+    let _ = std::fs::remove_file(&path);
+
+    assert!(checksum::parse("Records: 10\n").is_err());
+    assert!(checksum::parse("Records: 10\nChecksum: xyz\n").is_err());
+    assert!(checksum::parse("Records: 10\nChecksum: ff\nExtra: 1\n").is_err());
+}
@@ -206,6 +206,51 @@ async fn test_clickhouse_kvbin_format() {
     drop_table(&client, table).await;
 }
 
+#[tokio::test]
+async fn test_clickhouse_multiple_upload_streams() {
+    setup_env();
+
+    let url = clickhouse_url().unwrap();
+    let database = clickhouse_database();
+    let table = "clickhouse_upload_streams_test";
+
+    let client = clickhouse_client();
+    drop_table(&client, table).await;
+
+    let output = Command::new(load_clickhouse_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            "testdata/test_gensort.dat",
+            "--url",
+            &url,
+            "--database",
+            &database,
+            "--table",
+            table,
+            "--threads",
+            "3",
+            "--upload-streams",
+            "2",
+        ])
+        .output()
+        .expect("Failed to execute load-clickhouse");
+    assert!(
+        output.status.success(),
+        "Loader failed: stdout: {}, stderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Successfully loaded 3 rows"));
+
+    let rows = fetch_rows(&client, table).await;
+    let keys: Vec<&[u8]> = rows.iter().map(|r| r.sort_key.as_bytes()).collect();
+    assert_eq!(keys, [b"AAAAAAAAAA", b"BBBBBBBBBB", b"CCCCCCCCCC"]);
+
+    drop_table(&client, table).await;
+}
+
 #[tokio::test]
 async fn test_clickhouse_external_sort() {
     setup_env();
@@ -259,6 +304,11 @@ async fn test_clickhouse_external_sort() {
         "Expected TIMING output, got: {}",
         stdout
     );
+    assert!(
+        stdout.contains(&format!("QUERY_READ_ROWS: {}", record_count)),
+        "Expected query_log stats, got: {}",
+        stdout
+    );
 
     // Verify the data is correctly sorted by fetching from database
     let rows = fetch_rows(&client, table).await;
@@ -275,6 +325,840 @@ async fn test_clickhouse_external_sort() {
         );
     }
 
+    // Re-sorting into a MergeTree table ordered by the key
+    let sorted = "clickhouse_sort_test_sorted";
+    let output = Command::new(sort_clickhouse_binary())
+        .args(["--url", &url, "--database", &database, "--table", table])
+        .args(["--output-table", sorted])
+        .output()
+        .expect("Failed to execute sort-clickhouse");
+    assert!(
+        output.status.success(),
+        "Sorter failed: stdout: {}, stderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let (engine, sorting_key) = client
+        .query("SELECT engine, sorting_key FROM system.tables WHERE database = ? AND name = ?")
+        .bind(&database)
+        .bind(sorted)
+        .fetch_one::<(String, String)>()
+        .await
+        .expect("Failed to describe the sorted table");
+    assert_eq!(
+        (engine.as_str(), sorting_key.as_str()),
+        ("MergeTree", "sort_key")
+    );
+    assert_eq!(fetch_rows(&client, sorted).await.len(), record_count);
+    drop_table(&client, sorted).await;
+
     let _ = fs::remove_file(input_path);
     drop_table(&client, table).await;
 }
+
+#[test]
+fn test_clickhouse_loader_rejects_missing_ca_cert() {
+    let output = Command::new(load_clickhouse_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            "testdata/test_gensort.dat",
+            "--url",
+            "https://localhost:8443",
+            "--ca-cert",
+            "/tmp/test_missing_ca.pem",
+        ])
+        .output()
+        .expect("Failed to execute load-clickhouse");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Failed to read CA certificate"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+/// One request a `FakeClickhouse` answered: its URL parameters and body length
+struct FakeRequest {
+    params: Vec<(String, String)>,
+    body: usize,
+}
+
+impl FakeRequest {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// An HTTP server standing in for ClickHouse: `EXISTS TABLE` says the table
+/// exists, as a plain MergeTree table, and the first `failed_inserts` INSERTs
+/// fail with a server error. Records every request it answers.
+struct FakeClickhouse {
+    url: String,
+    requests: std::sync::Arc<std::sync::Mutex<Vec<FakeRequest>>>,
+}
+
+impl FakeClickhouse {
+    fn start(failed_inserts: usize) -> Self {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::sync::{Arc, Mutex};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let failures = Arc::new(Mutex::new(failed_inserts));
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let (requests, failures) = (recorded.clone(), failures.clone());
+                std::thread::spawn(move || {
+                    let mut stream = BufReader::new(stream.unwrap());
+                    // Requests on one kept-alive connection
+                    loop {
+                        let mut line = String::new();
+                        if stream.read_line(&mut line).unwrap_or(0) == 0 {
+                            return;
+                        }
+                        let target = line.split(' ').nth(1).unwrap_or("").to_string();
+                        let mut length = 0;
+                        loop {
+                            let mut header = String::new();
+                            stream.read_line(&mut header).unwrap();
+                            let header = header.trim_end();
+                            if header.is_empty() {
+                                break;
+                            }
+                            if let Some((name, value)) = header.split_once(':')
+                                && name.eq_ignore_ascii_case("content-length")
+                            {
+                                length = value.trim().parse().unwrap();
+                            }
+                        }
+                        let mut body = vec![0u8; length];
+                        stream.read_exact(&mut body).unwrap();
+
+                        let params = target
+                            .split_once('?')
+                            .map_or("", |(_, query)| query)
+                            .split('&')
+                            .filter_map(|pair| pair.split_once('='))
+                            .map(|(key, value)| (url_decode(key), url_decode(value)))
+                            .collect();
+                        let request = FakeRequest {
+                            params,
+                            body: length,
+                        };
+                        let query = request.param("query").unwrap_or("").to_string();
+                        requests.lock().unwrap().push(request);
+
+                        let (status, text) = if query.starts_with("EXISTS TABLE") {
+                            ("200 OK", "1\n")
+                        } else if query.starts_with("SELECT engine_full") {
+                            ("200 OK", "MergeTree ORDER BY tuple()\n")
+                        } else if query.starts_with("INSERT") && {
+                            let mut failures = failures.lock().unwrap();
+                            let fail = *failures > 0;
+                            *failures = failures.saturating_sub(1);
+                            fail
+                        } {
+                            (
+                                "500 Internal Server Error",
+                                "Code: 242. DB::Exception: Table is in readonly mode\n",
+                            )
+                        } else {
+                            ("200 OK", "")
+                        };
+                        let response = format!(
+                            "HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}",
+                            status,
+                            text.len(),
+                            text
+                        );
+                        stream.get_mut().write_all(response.as_bytes()).unwrap();
+                    }
+                });
+            }
+        });
+        Self { url, requests }
+    }
+}
+
+/// Decodes a URL query component, as reqwest encodes them
+fn url_decode(s: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut input = s.bytes();
+    while let Some(b) = input.next() {
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [input.next().unwrap(), input.next().unwrap()];
+                let hex = std::str::from_utf8(&hex).unwrap();
+                bytes.push(u8::from_str_radix(hex, 16).unwrap());
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).unwrap()
+}
+
+#[test]
+fn test_clickhouse_insert_retries() {
+    let server = FakeClickhouse::start(2);
+    let input_path = format!(
+        "{}/clickhouse_insert_retries.dat",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    let records = 3000;
+    let mut data = Vec::new();
+    for i in 0..records as u32 {
+        data.extend_from_slice(&[b'k'; 6]);
+        data.extend_from_slice(&i.to_be_bytes());
+        data.extend_from_slice(&[b'p'; 90]);
+    }
+    fs::write(&input_path, &data).unwrap();
+
+    let output = Command::new(load_clickhouse_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            &input_path,
+            "--url",
+            &server.url,
+            "--table",
+            "insert_retries_test",
+            "--if-exists",
+            "append",
+            "--threads",
+            "1",
+            "--upload-streams",
+            "1",
+            "--batch-size",
+            "100",
+            "--insert-bytes",
+            "0.1MB",
+            "--insert-retries",
+            "3",
+            "--retry-backoff-ms",
+            "10",
+        ])
+        .output()
+        .expect("Failed to execute load-clickhouse");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Loader failed: stdout: {}, stderr: {}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("failed, retry 2 of 3 in 20ms"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Retried inserts covered input bytes 0.."),
+        "stdout: {}",
+        stdout
+    );
+
+    let requests = server.requests.lock().unwrap();
+    // The existing table gets the window that makes the tokens count
+    assert!(requests.iter().any(|r| r.param("query")
+        == Some(
+            "ALTER TABLE insert_retries_test MODIFY SETTING non_replicated_deduplication_window = 1000"
+        )));
+
+    // Each INSERT keeps its token and body across its attempts
+    let mut inserts: Vec<(&str, Vec<usize>)> = Vec::new();
+    for request in requests.iter() {
+        if !request.param("query").unwrap().starts_with("INSERT") {
+            continue;
+        }
+        let token = request.param("insert_deduplication_token").unwrap();
+        match inserts.iter_mut().find(|(t, _)| *t == token) {
+            Some((_, bodies)) => bodies.push(request.body),
+            None => inserts.push((token, vec![request.body])),
+        }
+    }
+    assert_eq!(inserts[0].1.len(), 3);
+    assert!(
+        inserts
+            .iter()
+            .all(|(_, bodies)| bodies.iter().all(|&b| b == bodies[0]))
+    );
+    assert!(inserts[1..].iter().all(|(_, bodies)| bodies.len() == 1));
+
+    // Chunks are merged up to --insert-bytes, and every row is sent once
+    let max_bytes = (0.1 * 1024.0 * 1024.0) as usize;
+    let chunk_bytes = 100 * (1 + 10 + 1 + 90);
+    let sizes: Vec<usize> = inserts.iter().map(|(_, bodies)| bodies[0]).collect();
+    assert!(sizes.len() > 1, "sizes: {:?}", sizes);
+    assert!(
+        sizes[..sizes.len() - 1]
+            .iter()
+            .all(|&s| s >= max_bytes && s < max_bytes + chunk_bytes)
+    );
+    assert_eq!(sizes.iter().sum::<usize>(), records * (1 + 10 + 1 + 90));
+    let _ = fs::remove_file(&input_path);
+}
+
+#[test]
+fn test_clickhouse_shard_progress() {
+    let server = FakeClickhouse::start(0);
+    let input_path = format!(
+        "{}/clickhouse_shard_progress.dat",
+        env!("CARGO_TARGET_TMPDIR")
+    );
+    let records = 6000;
+    let mut data = Vec::new();
+    for i in 0..records as u32 {
+        data.extend_from_slice(&[b'k'; 6]);
+        data.extend_from_slice(&i.to_be_bytes());
+        data.extend_from_slice(&[b'p'; 90]);
+    }
+    fs::write(&input_path, &data).unwrap();
+
+    let output = Command::new(load_clickhouse_binary())
+        .args(["--format", "gensort", "--input", &input_path])
+        .args(["--url", &server.url, "--table", "shard_progress_test"])
+        .args(["--if-exists", "append", "--shards", "2", "--threads", "2"])
+        .args(["--batch-size", "100", "--insert-bytes", "0.05MB"])
+        .output()
+        .expect("Failed to execute load-clickhouse");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Loader failed: stdout: {}, stderr: {}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Each shard counts only the input bytes of its own rows, so the share of
+    // the input goes with the share of the 6000 rows of 1 + 10 + 1 + 90 bytes
+    // sent, reaching 100% with the last of them rather than half of them
+    let sent_mb = (records * (1 + 10 + 1 + 90)) as f64 / 1_000_000.0;
+    let mut lines = 0;
+    for line in stdout.lines().filter(|l| l.ends_with("% of the input")) {
+        let (uploaded, rest) = line
+            .strip_prefix("Uploaded ")
+            .and_then(|l| l.split_once(" MB at "))
+            .unwrap();
+        let percent = rest.rsplit_once(", ").unwrap().1;
+        let percent: f64 = percent
+            .strip_suffix("% of the input")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let uploaded: f64 = uploaded.parse().unwrap();
+        assert!(
+            (uploaded - sent_mb * percent / 100.0).abs() < 0.06,
+            "stdout: {}",
+            stdout
+        );
+        lines += 1;
+    }
+    assert!(lines > 2, "stdout: {}", stdout);
+
+    let requests = server.requests.lock().unwrap();
+    let inserted: Vec<usize> = requests
+        .iter()
+        .filter(|r| {
+            r.param("query")
+                .unwrap()
+                .starts_with("INSERT INTO shard_progress_test_")
+        })
+        .map(|r| r.body)
+        .collect();
+    assert_eq!(inserted.iter().sum::<usize>(), records * (1 + 10 + 1 + 90));
+    let _ = fs::remove_file(&input_path);
+}
+
+#[tokio::test]
+async fn test_clickhouse_async_insert() {
+    setup_env();
+
+    let url = clickhouse_url().unwrap();
+    let database = clickhouse_database();
+    let client = clickhouse_client();
+
+    for mode in ["wait", "no-wait"] {
+        let table = format!("clickhouse_async_insert_{}_test", mode.replace('-', "_"));
+        drop_table(&client, &table).await;
+
+        // --verify-count only passes once no-wait's buffered rows are flushed
+        let output = Command::new(load_clickhouse_binary())
+            .args([
+                "--format",
+                "kvbin",
+                "--input",
+                "testdata/test_kvbin.dat",
+                "--url",
+                &url,
+                "--database",
+                &database,
+                "--table",
+                &table,
+                "--async-insert",
+                mode,
+                "--upload-streams",
+                "2",
+                "--verify-count",
+            ])
+            .output()
+            .expect("Failed to execute load-clickhouse");
+        assert!(
+            output.status.success(),
+            "Loader failed ({}): stdout: {}, stderr: {}",
+            mode,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(fetch_rows(&client, &table).await.len(), 3);
+
+        drop_table(&client, &table).await;
+    }
+}
+
+#[tokio::test]
+async fn test_clickhouse_engine_and_order_by() {
+    setup_env();
+
+    let url = clickhouse_url().unwrap();
+    let database = clickhouse_database();
+    let table = "clickhouse_order_by_test";
+
+    let client = clickhouse_client();
+    drop_table(&client, table).await;
+
+    let output = Command::new(load_clickhouse_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            "testdata/test_gensort.dat",
+            "--url",
+            &url,
+            "--database",
+            &database,
+            "--table",
+            table,
+            "--engine",
+            "ReplacingMergeTree()",
+            "--order-by",
+            "sort_key",
+            "--table-settings",
+            "index_granularity = 1024",
+        ])
+        .output()
+        .expect("Failed to execute load-clickhouse");
+    assert!(
+        output.status.success(),
+        "Loader failed: stdout: {}, stderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let table_property = |column: &str| {
+        client
+            .query(&format!(
+                "SELECT {} FROM system.tables WHERE database = ? AND name = ?",
+                column
+            ))
+            .bind(&database)
+            .bind(table)
+            .fetch_one::<String>()
+    };
+    let engine = table_property("engine")
+        .await
+        .expect("Failed to query engine");
+    let sorting_key = table_property("sorting_key")
+        .await
+        .expect("Failed to query sorting_key");
+    assert_eq!(engine, "ReplacingMergeTree");
+    assert_eq!(sorting_key, "sort_key");
+    assert_eq!(fetch_rows(&client, table).await.len(), 3);
+
+    drop_table(&client, table).await;
+}
+
+#[test]
+fn test_clickhouse_order_by_needs_merge_tree() {
+    let output = Command::new(load_clickhouse_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            "testdata/test_gensort.dat",
+            "--engine",
+            "Log",
+            "--order-by",
+            "sort_key",
+        ])
+        .output()
+        .expect("Failed to execute load-clickhouse");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("MergeTree-family engine"),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[tokio::test]
+async fn test_clickhouse_checkpoint_resume() {
+    setup_env();
+
+    let url = clickhouse_url().unwrap();
+    let database = clickhouse_database();
+    let client = clickhouse_client();
+    let table = "clickhouse_checkpoint_test";
+    let input_path = "/tmp/test_clickhouse_checkpoint_resume.dat";
+    let checkpoint_path = "/tmp/test_clickhouse_checkpoint_resume.ckpt";
+    drop_table(&client, table).await;
+    let _ = fs::remove_file(checkpoint_path);
+    let data: Vec<u8> = (0..1000)
+        .flat_map(|i| [format!("{:010}", i).into_bytes(), vec![b'p'; 90]].concat())
+        .collect();
+    fs::write(input_path, &data).unwrap();
+
+    // A view that throws on keys from record 350 on interrupts the first run
+    // once a few units are in
+    let interrupt = format!("{}_interrupt", table);
+    client
+        .query(&format!("DROP VIEW IF EXISTS {}", interrupt))
+        .execute()
+        .await
+        .unwrap();
+    client
+        .query(&format!(
+            "CREATE TABLE {} (sort_key String, payload String) ENGINE = MergeTree ORDER BY tuple()",
+            table
+        ))
+        .execute()
+        .await
+        .unwrap();
+    client
+        .query(&format!(
+            "CREATE MATERIALIZED VIEW {} ENGINE = Memory AS \
+             SELECT throwIf(sort_key >= '0000000350', 'interrupted') AS stop FROM {}",
+            interrupt, table
+        ))
+        .execute()
+        .await
+        .unwrap();
+
+    // 100 records per unit
+    let run = || {
+        Command::new(load_clickhouse_binary())
+            .args([
+                "--format",
+                "gensort",
+                "--input",
+                input_path,
+                "--url",
+                &url,
+                "--database",
+                &database,
+                "--table",
+                table,
+                "--if-exists",
+                "append",
+                "--threads",
+                "2",
+                "--checkpoint-file",
+                checkpoint_path,
+                "--checkpoint-unit",
+                "10000",
+            ])
+            .output()
+            .expect("Failed to execute load-clickhouse")
+    };
+    let counts = || async {
+        client
+            .query(&format!(
+                "SELECT count(), uniqExact(sort_key) FROM {}",
+                table
+            ))
+            .fetch_one::<(u64, u64)>()
+            .await
+            .unwrap()
+    };
+    let output = run();
+    assert!(
+        !output.status.success(),
+        "the first run was not interrupted"
+    );
+    let checkpoint = fs::read_to_string(checkpoint_path).unwrap();
+    let done = checkpoint
+        .lines()
+        .filter(|l| l.starts_with("done "))
+        .count();
+    assert!((1..=3).contains(&done), "checkpoint: {}", checkpoint);
+
+    client
+        .query(&format!("DROP VIEW {}", interrupt))
+        .execute()
+        .await
+        .unwrap();
+    let output = run();
+    assert!(
+        output.status.success(),
+        "Resumed loader failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains(&format!("({} already loaded)", done))
+    );
+    assert!(!std::path::Path::new(checkpoint_path).exists());
+    // An interrupted INSERT may have reached the table before the view threw;
+    // its retry carries the same token and is dropped
+    assert_eq!(
+        counts().await,
+        (1000, 1000),
+        "rows were lost or loaded twice"
+    );
+
+    // A crash after inserting every unit but marking only the first one done:
+    // the other units are sent again with the same tokens and dropped
+    let description = es_duck::checkpoint::describe(
+        es_duck::records::InputFormat::Gensort,
+        es_duck::records::FixedLayout::GENSORT,
+        input_path.as_ref(),
+        &(0..data.len() as u64),
+        table,
+        10_000,
+    )
+    .unwrap();
+    fs::write(checkpoint_path, format!("{}\ndone 0 100\n", description)).unwrap();
+    let output = run();
+    assert!(
+        output.status.success(),
+        "Resumed loader failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("(1 already loaded)"));
+    assert_eq!(counts().await, (1000, 1000), "rows were loaded twice");
+
+    drop_table(&client, table).await;
+    let _ = fs::remove_file(input_path);
+}
+
+#[tokio::test]
+async fn test_clickhouse_client_insert() {
+    setup_env();
+
+    let url = clickhouse_url().unwrap();
+    let database = clickhouse_database();
+    let client = clickhouse_client();
+
+    for (format, input) in [
+        ("gensort", "testdata/test_gensort.dat"),
+        ("kvbin", "testdata/test_kvbin.dat"),
+    ] {
+        let table = format!("clickhouse_client_insert_{}_test", format);
+        drop_table(&client, &table).await;
+
+        let output = Command::new(load_clickhouse_binary())
+            .args([
+                "--format",
+                format,
+                "--input",
+                input,
+                "--url",
+                &url,
+                "--database",
+                &database,
+                "--table",
+                &table,
+                "--via",
+                "client-insert",
+                "--threads",
+                "2",
+                "--with-row-id",
+                "--verify-count",
+            ])
+            .output()
+            .expect("Failed to execute load-clickhouse");
+        assert!(
+            output.status.success(),
+            "Loader failed ({}): stdout: {}, stderr: {}",
+            format,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(fetch_rows(&client, &table).await.len(), 3);
+
+        let ids = client
+            .query(&format!("SELECT row_id FROM {} ORDER BY row_id", table))
+            .fetch_all::<u64>()
+            .await
+            .expect("Failed to query row ids");
+        assert_eq!(ids.len(), 3);
+        assert!(ids.windows(2).all(|w| w[0] < w[1]), "{:?}", ids);
+
+        drop_table(&client, &table).await;
+    }
+
+    // The typed rows can't name other columns
+    let output = Command::new(load_clickhouse_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            "testdata/test_gensort.dat",
+            "--via",
+            "client-insert",
+            "--key-column",
+            "k",
+        ])
+        .output()
+        .expect("Failed to execute load-clickhouse");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--via client-insert"), "stderr: {}", stderr);
+}
+
+#[tokio::test]
+async fn test_clickhouse_sort_input_files() {
+    setup_env();
+
+    let url = clickhouse_url().unwrap();
+    let database = clickhouse_database();
+    let client = clickhouse_client();
+
+    // Written by the server into its user_files, so the test needn't share a
+    // file system with it
+    for (part, range) in [("a", "100, 200"), ("b", "0, 100")] {
+        client
+            .query(&format!(
+                "INSERT INTO FUNCTION file('es_duck_input_files_test/{}.rowbinary', \
+                 'RowBinary', 'sort_key String, payload String') \
+                 SELECT leftPad(toString(number), 10, '0'), 'x' FROM numbers({}) \
+                 SETTINGS engine_file_truncate_on_insert = 1",
+                part, range
+            ))
+            .execute()
+            .await
+            .expect("Failed to write the input files");
+    }
+
+    let output = Command::new(sort_clickhouse_binary())
+        .args(["--url", &url, "--database", &database])
+        .args(["--input-files", "es_duck_input_files_test/*.rowbinary"])
+        .output()
+        .expect("Failed to execute sort-clickhouse");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: stdout: {}, stderr: {}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("Input files: es_duck_input_files_test/*.rowbinary (2 files, rowbinary)"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(stdout.contains("Row count: 300"), "stdout: {}", stdout);
+
+    // Without an extension naming it the format has to be given
+    let output = Command::new(sort_clickhouse_binary())
+        .args(["--url", &url, "--database", &database])
+        .args(["--input-files", "es_duck_input_files_test/*"])
+        .output()
+        .expect("Failed to execute sort-clickhouse");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--input-format"), "stderr: {}", stderr);
+}
+
+#[tokio::test]
+async fn test_clickhouse_read_in_order() {
+    setup_env();
+
+    let url = clickhouse_url().unwrap();
+    let database = clickhouse_database();
+    let client = clickhouse_client();
+
+    for (table, order_by) in [
+        ("clickhouse_read_in_order_test", "sort_key"),
+        ("clickhouse_unsorted_test", "tuple()"),
+    ] {
+        drop_table(&client, table).await;
+        client
+            .query(&format!(
+                "CREATE TABLE {} (sort_key String, payload String) \
+                 ENGINE = MergeTree ORDER BY {}",
+                table, order_by
+            ))
+            .execute()
+            .await
+            .expect("Failed to create table");
+        client
+            .query(&format!(
+                "INSERT INTO {} SELECT leftPad(toString((number * 37) % 500), 10, '0'), 'x' \
+                 FROM numbers(500)",
+                table
+            ))
+            .execute()
+            .await
+            .expect("Failed to insert rows");
+    }
+
+    // The sorted table's parts are merged, not sorted
+    let output = Command::new(sort_clickhouse_binary())
+        .args(["--url", &url, "--database", &database])
+        .args(["--table", "clickhouse_read_in_order_test"])
+        .args(["--read-in-order", "on"])
+        .output()
+        .expect("Failed to execute sort-clickhouse");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: stdout: {}, stderr: {}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("Sorting key of clickhouse_read_in_order_test: sort_key"),
+        "stdout: {}",
+        stdout
+    );
+    assert!(stdout.contains("Read in order"), "stdout: {}", stdout);
+
+    // ORDER BY tuple() can't be read in order
+    let output = Command::new(sort_clickhouse_binary())
+        .args(["--url", &url, "--database", &database])
+        .args(["--table", "clickhouse_unsorted_test"])
+        .args(["--read-in-order", "on"])
+        .output()
+        .expect("Failed to execute sort-clickhouse");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("doesn't read"), "stderr: {}", stderr);
+
+    // Turned off, the sorted table is sorted in full
+    let output = Command::new(sort_clickhouse_binary())
+        .args(["--url", &url, "--database", &database])
+        .args(["--table", "clickhouse_read_in_order_test"])
+        .args(["--read-in-order", "off"])
+        .output()
+        .expect("Failed to execute sort-clickhouse");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {}", stdout);
+    assert!(
+        stdout.contains("Setting optimize_read_in_order to 0"),
+        "stdout: {}",
+        stdout
+    );
+
+    drop_table(&client, "clickhouse_read_in_order_test").await;
+    drop_table(&client, "clickhouse_unsorted_test").await;
+}
@@ -0,0 +1,33 @@
+use es_duck::collation::Collation;
+
+#[test]
+fn test_parse_collation() {
+    assert_eq!("binary".parse(), Ok(Collation::Binary));
+    assert_eq!("icu:de-DE".parse(), Ok(Collation::Icu("de-DE".to_string())));
+    for bad in ["", "icu", "icu:", "icu:de'DE", "posix", "binary:de"] {
+        assert!(bad.parse::<Collation>().is_err(), "parsed '{}'", bad);
+    }
+    assert_eq!(Collation::Icu("en_US".to_string()).to_string(), "icu:en_US");
+    assert!(Collation::Binary.is_binary());
+    assert!(!Collation::Icu("de".to_string()).is_binary());
+}
+
+#[test]
+fn test_collate_clauses() {
+    let icu = Collation::Icu("de-DE".to_string());
+    assert_eq!(icu.duckdb(), " COLLATE de_de");
+    assert_eq!(icu.duckdb_setup(), "INSTALL icu; LOAD icu;");
+    assert_eq!(icu.postgres(), " COLLATE \"de-DE-x-icu\"");
+    assert_eq!(icu.clickhouse(), " COLLATE 'de-DE'");
+    // PostgreSQL names its ICU collations by BCP 47 tag
+    assert_eq!(
+        Collation::Icu("en_US".to_string()).postgres(),
+        " COLLATE \"en-US-x-icu\""
+    );
+
+    assert_eq!(Collation::Binary.duckdb(), " COLLATE binary");
+    assert_eq!(Collation::Binary.duckdb_setup(), "");
+    assert_eq!(Collation::Binary.postgres(), " COLLATE \"C\"");
+    // ClickHouse Strings compare as bytes already
+    assert_eq!(Collation::Binary.clickhouse(), "");
+}
@@ -0,0 +1,351 @@
+#![cfg(feature = "db-duckdb")]
+
+use duckdb::Connection;
+use es_duck::checksum::Checksum;
+use es_duck::export::{OutputFormat, Writer};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn compare_outputs_binary() -> String {
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    format!("target/{}/compare-outputs", profile)
+}
+
+fn run_compare(
+    duckdb: &str,
+    postgres: &str,
+    clickhouse: &str,
+    extra: &[&str],
+) -> std::process::Output {
+    Command::new(compare_outputs_binary())
+        .args([
+            "--duckdb",
+            duckdb,
+            "--postgres",
+            postgres,
+            "--clickhouse",
+            clickhouse,
+        ])
+        .args(extra)
+        .output()
+        .expect("Failed to execute compare-outputs")
+}
+
+fn write_parquet(path: &str, rows: &[(&[u8], &[u8])]) {
+    let _ = fs::remove_file(path);
+    let conn = Connection::open_in_memory().expect("Failed to open DuckDB");
+    conn.execute(
+        "CREATE TABLE rows (id INTEGER, sort_key BLOB, payload BLOB)",
+        [],
+    )
+    .unwrap();
+    for (i, (key, payload)) in rows.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO rows VALUES (?, ?, ?)",
+            duckdb::params![i as i32, key, payload],
+        )
+        .unwrap();
+    }
+    conn.execute(
+        &format!(
+            "COPY (SELECT sort_key, payload FROM rows ORDER BY id) TO '{}' (FORMAT PARQUET)",
+            path
+        ),
+        [],
+    )
+    .unwrap();
+}
+
+fn write_pgcopy(path: &str, rows: &[(&[u8], &[u8])]) {
+    let mut data = b"PGCOPY\n\xff\r\n\0".to_vec();
+    data.extend_from_slice(&0i32.to_be_bytes()); // flags
+    data.extend_from_slice(&0i32.to_be_bytes()); // header extension length
+    for (key, payload) in rows {
+        data.extend_from_slice(&2i16.to_be_bytes());
+        data.extend_from_slice(&(key.len() as i32).to_be_bytes());
+        data.extend_from_slice(key);
+        data.extend_from_slice(&(payload.len() as i32).to_be_bytes());
+        data.extend_from_slice(payload);
+    }
+    data.extend_from_slice(&(-1i16).to_be_bytes());
+    fs::write(path, data).expect("Failed to write binary COPY file");
+}
+
+fn write_native(path: &str, rows: &[(&[u8], &[u8])]) {
+    // Single block with two String columns; all lengths here fit in one varint byte
+    let mut data = vec![2u8, rows.len() as u8];
+    for (name, column) in [("sort_key", 0), ("payload", 1)] {
+        data.push(name.len() as u8);
+        data.extend_from_slice(name.as_bytes());
+        data.push(6);
+        data.extend_from_slice(b"String");
+        for row in rows {
+            let value = if column == 0 { row.0 } else { row.1 };
+            data.push(value.len() as u8);
+            data.extend_from_slice(value);
+        }
+    }
+    fs::write(path, data).expect("Failed to write Native file");
+}
+
+fn write_rows(path: &str, format: OutputFormat, rows: &[(&[u8], &[u8])]) {
+    let mut writer = Writer::create(Path::new(path), format, &["sort_key", "payload"]).unwrap();
+    for (key, payload) in rows {
+        writer.write_row(&[key, payload]).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+/// Writes rows with their row ids as the sorters do for a --with-row-id table
+fn write_rows_with_ids(path: &str, format: OutputFormat, rows: &[(&[u8], &[u8], u64)]) {
+    let columns = ["sort_key", "payload", "row_id"];
+    let mut writer = Writer::create(Path::new(path), format, &columns).unwrap();
+    for (key, payload, id) in rows {
+        writer
+            .write_row(&[key, payload, &id.to_be_bytes()])
+            .unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+const SORTED: [(&[u8], &[u8]); 4] = [
+    (b"aaaa", b"payload-1"),
+    (b"bbbb", b"payload-2"),
+    (b"bbbb", b"payload-3"),
+    (b"cccc", b"payload-4"),
+];
+
+#[test]
+fn test_equivalent_outputs() {
+    let duckdb = "/tmp/test_compare_equal.parquet";
+    let postgres = "/tmp/test_compare_equal.bin";
+    let clickhouse = "/tmp/test_compare_equal.native";
+
+    write_parquet(duckdb, &SORTED);
+    write_pgcopy(postgres, &SORTED);
+    write_native(clickhouse, &SORTED);
+
+    let output = run_compare(duckdb, postgres, clickhouse, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "compare-outputs failed: stdout: {}, stderr: {}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("postgres: 4 rows, OK"), "got: {}", stdout);
+    assert!(stdout.contains("clickhouse: 4 rows, OK"), "got: {}", stdout);
+
+    let _ = fs::remove_file(duckdb);
+    let _ = fs::remove_file(postgres);
+    let _ = fs::remove_file(clickhouse);
+}
+
+#[test]
+fn test_dropped_row_is_reported() {
+    let duckdb = "/tmp/test_compare_dropped.parquet";
+    let postgres = "/tmp/test_compare_dropped.bin";
+    let clickhouse = "/tmp/test_compare_dropped.native";
+
+    write_parquet(duckdb, &SORTED);
+    write_pgcopy(postgres, &SORTED);
+    write_native(clickhouse, &[SORTED[0], SORTED[1], SORTED[3]]);
+
+    let output = run_compare(duckdb, postgres, clickhouse, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        !output.status.success(),
+        "Expected failure, got: {}",
+        stdout
+    );
+    assert!(stdout.contains("postgres: 4 rows, OK"), "got: {}", stdout);
+    assert!(stdout.contains("clickhouse: MISMATCH"), "got: {}", stdout);
+
+    let _ = fs::remove_file(duckdb);
+    let _ = fs::remove_file(postgres);
+    let _ = fs::remove_file(clickhouse);
+}
+
+#[test]
+fn test_reordered_ties() {
+    let duckdb = "/tmp/test_compare_ties.parquet";
+    let postgres = "/tmp/test_compare_ties.bin";
+    let clickhouse = "/tmp/test_compare_ties.native";
+
+    write_parquet(duckdb, &SORTED);
+    write_pgcopy(postgres, &[SORTED[0], SORTED[2], SORTED[1], SORTED[3]]);
+    write_native(clickhouse, &SORTED);
+
+    // Different tie order is only a warning by default
+    let output = run_compare(duckdb, postgres, clickhouse, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "Expected success, got: {}", stdout);
+    assert!(
+        stdout.contains("postgres: 4 rows, 1 equal-key runs in a different order than duckdb"),
+        "got: {}",
+        stdout
+    );
+
+    let output = run_compare(duckdb, postgres, clickhouse, &["--strict-ties"]);
+    assert!(
+        !output.status.success(),
+        "Expected failure with --strict-ties"
+    );
+
+    let _ = fs::remove_file(duckdb);
+    let _ = fs::remove_file(postgres);
+    let _ = fs::remove_file(clickhouse);
+}
+
+#[test]
+fn test_outputs_in_any_format() {
+    let duckdb = "/tmp/test_compare_formats.csv";
+    let postgres = "/tmp/test_compare_formats.kvbin";
+    let clickhouse = "/tmp/test_compare_formats.out";
+
+    // Each file's extension names its format; .out is sort-clickhouse's default, Native
+    write_rows(duckdb, OutputFormat::Csv, &SORTED);
+    write_rows(postgres, OutputFormat::Kvbin, &SORTED);
+    write_rows(clickhouse, OutputFormat::Native, &SORTED);
+
+    let output = run_compare(duckdb, postgres, clickhouse, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "compare-outputs failed: stdout: {}, stderr: {}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("postgres: 4 rows, OK"), "got: {}", stdout);
+    assert!(stdout.contains("clickhouse: 4 rows, OK"), "got: {}", stdout);
+
+    // Read as one format, the CSV and kvbin files are not Native
+    let output = run_compare(duckdb, postgres, clickhouse, &["--format", "native"]);
+    assert!(
+        !output.status.success(),
+        "Expected failure with --format native"
+    );
+
+    let _ = fs::remove_file(duckdb);
+    let _ = fs::remove_file(postgres);
+    let _ = fs::remove_file(clickhouse);
+}
+
+#[test]
+fn test_row_ids() {
+    let duckdb = "/tmp/test_compare_row_ids.native";
+    let postgres = "/tmp/test_compare_row_ids.bin";
+    let clickhouse = "/tmp/test_compare_row_ids.csv";
+
+    let row = |i: usize| (SORTED[i].0, SORTED[i].1, i as u64);
+    write_rows_with_ids(
+        duckdb,
+        OutputFormat::Native,
+        &[row(0), row(1), row(2), row(3)],
+    );
+    // Equal keys out of input order
+    write_rows_with_ids(
+        postgres,
+        OutputFormat::Pgbinary,
+        &[row(0), row(2), row(1), row(3)],
+    );
+    // Row 2 lost, and row 1 doubled so the count still matches
+    write_rows_with_ids(
+        clickhouse,
+        OutputFormat::Csv,
+        &[row(0), row(1), row(1), row(3)],
+    );
+
+    let output = run_compare(duckdb, postgres, clickhouse, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        !output.status.success(),
+        "Expected failure, got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("duckdb: equal keys in row id order (stable)"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("postgres: 1 equal keys out of row id order (unstable)"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains(
+            "clickhouse: MISMATCH (4 rows) - row id 1 repeats at row 2 under key 62626262"
+        ),
+        "got: {}",
+        stdout
+    );
+
+    let _ = fs::remove_file(duckdb);
+    let _ = fs::remove_file(postgres);
+    let _ = fs::remove_file(clickhouse);
+}
+
+#[test]
+fn test_input_checksum() {
+    let duckdb = "/tmp/test_compare_checksum.parquet";
+    let postgres = "/tmp/test_compare_checksum.bin";
+    let clickhouse = "/tmp/test_compare_checksum.native";
+    let sum_file = "/tmp/test_compare_checksum.sum";
+
+    let mut sum = Checksum::new();
+    for (key, payload) in SORTED {
+        sum.add(&[key, payload].concat());
+    }
+    sum.write(Path::new(sum_file)).unwrap();
+
+    write_parquet(duckdb, &SORTED);
+    write_pgcopy(postgres, &SORTED);
+    write_native(clickhouse, &SORTED);
+    let output = run_compare(duckdb, postgres, clickhouse, &["--checksum", sum_file]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "compare-outputs failed: stdout: {}, stderr: {}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("duckdb: 4 rows, OK (checksum matches)"),
+        "got: {}",
+        stdout
+    );
+
+    // Every engine lost a row and doubled another alike, which only the
+    // input's checksum can tell
+    let rows = [SORTED[0], SORTED[1], SORTED[1], SORTED[3]];
+    write_parquet(duckdb, &rows);
+    write_pgcopy(postgres, &rows);
+    write_native(clickhouse, &rows);
+    let output = run_compare(duckdb, postgres, clickhouse, &[]);
+    assert!(output.status.success());
+    let output = run_compare(duckdb, postgres, clickhouse, &["--checksum", sum_file]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        !output.status.success(),
+        "Expected failure, got: {}",
+        stdout
+    );
+    for engine in ["duckdb", "postgres", "clickhouse"] {
+        assert!(
+            stdout.contains(&format!("{}: MISMATCH (4 rows) - checksum", engine)),
+            "got: {}",
+            stdout
+        );
+    }
+
+    let _ = fs::remove_file(duckdb);
+    let _ = fs::remove_file(postgres);
+    let _ = fs::remove_file(clickhouse);
+    let _ = fs::remove_file(sum_file);
+}
@@ -0,0 +1,61 @@
+use es_duck::csv_file::{self, Encoding};
+use es_duck::records::FixedLayout;
+
+#[test]
+fn test_encodings_match_reference_vectors() {
+    // RFC 4648 test vectors
+    for (data, base64) in [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ] {
+        let mut out = Vec::new();
+        Encoding::Base64.encode(data.as_bytes(), &mut out);
+        assert_eq!(out, base64.as_bytes());
+        assert_eq!(Encoding::Base64.encoded_len(data.len()), base64.len());
+        assert_eq!(
+            Encoding::Base64.decode(base64.as_bytes()).unwrap(),
+            data.as_bytes()
+        );
+    }
+
+    let mut out = Vec::new();
+    Encoding::Hex.encode(&[0x00, 0x9f, 0xff], &mut out);
+    assert_eq!(out, b"009fff");
+    assert_eq!(Encoding::Hex.decode(b"009FfF").unwrap(), [0x00, 0x9f, 0xff]);
+
+    assert!(Encoding::Hex.decode(b"abc").is_err());
+    assert!(Encoding::Hex.decode(b"zz").is_err());
+    assert!(Encoding::Base64.decode(b"Zg=").is_err());
+    assert!(Encoding::Base64.decode(b"Z===").is_err());
+}
+
+#[test]
+fn test_records_round_trip() {
+    let layout = FixedLayout {
+        key_size: 3,
+        payload_size: 7,
+    };
+    let data: Vec<u8> = (0..=255u8).cycle().take(50).collect();
+    for encoding in [Encoding::Hex, Encoding::Base64] {
+        let mut text = Vec::new();
+        csv_file::encode_records(&data, layout, encoding, &mut text);
+        let row_size = csv_file::row_size(layout, encoding);
+        assert_eq!(text.len(), 5 * row_size);
+
+        let mut decoded = Vec::new();
+        for line in text.chunks_exact(row_size) {
+            assert_eq!(line.last(), Some(&b'\n'));
+            let (key, payload) = csv_file::decode_row(line, encoding).unwrap();
+            assert_eq!(key.len(), 3);
+            decoded.extend_from_slice(&key);
+            decoded.extend_from_slice(&payload);
+        }
+        assert_eq!(decoded, data);
+    }
+    assert!(csv_file::decode_row(b"00ff\n", Encoding::Hex).is_err());
+}
@@ -1,6 +1,9 @@
 #![cfg(feature = "db-duckdb")]
 
 use duckdb::Connection;
+use es_duck::csv_file::{self, Encoding};
+use es_duck::export::{OutputFormat, Reader};
+use es_duck::records::FixedLayout;
 use std::fs;
 use std::process::Command;
 
@@ -244,7 +247,7 @@ fn test_external_sort() {
         String::from_utf8_lossy(&output.stderr)
     );
 
-    // Run external sort (now just executes count query)
+    // Run external sort
     let output = run_sorter(db_path, table, "128MB");
     assert!(
         output.status.success(),
@@ -261,16 +264,20 @@ fn test_external_sort() {
         stdout
     );
     assert!(
-        stdout.contains("Count result:"),
-        "Expected count result, got: {}",
+        stdout.contains("PROFILE_LATENCY:") && stdout.contains("OPERATOR: ORDER_BY"),
+        "Expected the sort's profile, got: {}",
         stdout
     );
-
-    // Parse the count from output and verify it's correct (100 records - 1 due to OFFSET 1)
+    assert!(
+        stdout.contains("SPILL_PEAK_MB:") && stdout.contains("SPILL_HELD_FILES:"),
+        "Expected spill statistics, got: {}",
+        stdout
+    );
+    // The row count comes from the table statistics gathered before the sort
     let count_line = stdout
         .lines()
-        .find(|line| line.contains("Count result:"))
-        .expect("Could not find count result line");
+        .find(|line| line.starts_with("Row count:"))
+        .expect("Could not find row count line");
     let count: i64 = count_line
         .split(':')
         .nth(1)
@@ -278,7 +285,7 @@ fn test_external_sort() {
         .trim()
         .parse()
         .expect("Failed to parse count");
-    assert_eq!(count, 99, "Expected count of 99 (100 records - 1 offset)");
+    assert_eq!(count, 100, "Expected all 100 records to be counted");
 
     // Verify data is actually sorted by reading directly from database
     let conn = Connection::open(db_path).expect("Failed to open database");
@@ -308,3 +315,1696 @@ fn test_external_sort() {
     let _ = fs::remove_file(input_path);
     let _ = fs::remove_file(db_path);
 }
+
+/// Writes `count` gensort records with increasing keys, so input order is key
+/// order; returns the file's contents
+fn write_sequential_gensort(path: &str, count: u32) -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..count {
+        data.extend_from_slice(format!("{:010}", i).as_bytes());
+        data.extend(std::iter::repeat_n((i % 256) as u8, 90));
+    }
+    fs::write(path, &data).unwrap();
+    data
+}
+
+/// Writes `count` sequential gensort records to `input` and loads them into
+/// `table` of a new database at `db`, with the loader options `extra`,
+/// returning the records
+fn load_fixture(input: &str, count: u32, db: &str, table: &str, extra: &[&str]) -> Vec<u8> {
+    let data = write_sequential_gensort(input, count);
+    let _ = fs::remove_file(db);
+    let output = Command::new(load_duckdb_binary())
+        .args([
+            "--format", "gensort", "--input", input, "--db", db, "--table", table,
+        ])
+        .args(extra)
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "Loader failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    data
+}
+
+/// The table's rows in table order, concatenated back into gensort records
+fn table_as_gensort(conn: &Connection, table: &str) -> Vec<u8> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT sort_key, payload FROM {}", table))
+        .unwrap();
+    let rows: Vec<(Vec<u8>, Vec<u8>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    rows.into_iter()
+        .flat_map(|(k, p)| k.into_iter().chain(p))
+        .collect()
+}
+
+#[test]
+fn test_staging_tables() {
+    let input_path = "/tmp/test_staging_tables.dat";
+    let db_path = "/tmp/test_staging_tables.duckdb";
+    let table = "staging_test";
+
+    let data = load_fixture(
+        input_path,
+        1000,
+        db_path,
+        table,
+        &["--threads", "4", "--staging-tables"],
+    );
+
+    let conn = Connection::open(db_path).expect("Failed to open database");
+    assert!(
+        table_as_gensort(&conn, table) == data,
+        "rows differ from the input or its order"
+    );
+
+    let staging: i64 = conn
+        .query_row(
+            "SELECT count(*) FROM information_schema.tables WHERE table_name LIKE '%_staging_%'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(staging, 0, "staging tables were not dropped");
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(input_path);
+}
+
+#[test]
+fn test_arrow_ingest() {
+    let input_path = "/tmp/test_arrow_ingest.dat";
+    let db_path = "/tmp/test_arrow_ingest.duckdb";
+    let table = "arrow_test";
+
+    // More than one 50k-record batch, plus a partial one
+    let data = write_sequential_gensort(input_path, 120_001);
+
+    for extra in [
+        &[][..],
+        &["--threads", "3"],
+        &["--threads", "3", "--staging-tables"],
+    ] {
+        let _ = fs::remove_file(db_path);
+        let output = Command::new(load_duckdb_binary())
+            .args([
+                "--format", "gensort", "--input", input_path, "--db", db_path, "--table", table,
+                "--arrow",
+            ])
+            .args(extra)
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "Loader failed with {:?}: {:?}",
+            extra,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let conn = Connection::open(db_path).expect("Failed to open database");
+        let loaded = table_as_gensort(&conn, table);
+        if extra.len() == 2 {
+            // Batches go in as they arrive from the reader threads
+            let mut records: Vec<&[u8]> = loaded.chunks(100).collect();
+            records.sort();
+            assert!(records.concat() == data, "rows differ with {:?}", extra);
+        } else {
+            assert!(loaded == data, "rows differ with {:?}", extra);
+        }
+    }
+
+    let _ = fs::remove_file(db_path);
+    let output = Command::new(load_duckdb_binary())
+        .args([
+            "--format",
+            "kvbin",
+            "--input",
+            "testdata/test_kvbin.dat",
+            "--db",
+            db_path,
+            "--arrow",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--arrow supports"));
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(input_path);
+}
+
+#[test]
+fn test_copy_via_csv() {
+    let input_path = "/tmp/test_copy_via_csv.dat";
+    let db_path = "/tmp/test_copy_via_csv.duckdb";
+    let table = "copy_test";
+
+    let data = write_sequential_gensort(input_path, 120_001);
+
+    let _ = fs::remove_file(db_path);
+    let output = Command::new(load_duckdb_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            input_path,
+            "--db",
+            db_path,
+            "--table",
+            table,
+            "--via",
+            "copy",
+            "--threads",
+            "2",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "Loader failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("DuckDB load:"));
+
+    // DuckDB's CSV reader may run in parallel, so compare as a set
+    let conn = Connection::open(db_path).expect("Failed to open database");
+    let loaded = table_as_gensort(&conn, table);
+    let mut records: Vec<&[u8]> = loaded.chunks(100).collect();
+    records.sort();
+    assert!(records.concat() == data, "rows differ");
+
+    // kvbin goes through the same CSV file
+    let _ = fs::remove_file(db_path);
+    let output = Command::new(load_duckdb_binary())
+        .args([
+            "--format",
+            "kvbin",
+            "--input",
+            "testdata/test_kvbin.dat",
+            "--db",
+            db_path,
+            "--table",
+            table,
+            "--via",
+            "copy",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "Loader failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let conn = Connection::open(db_path).expect("Failed to open database");
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT sort_key, payload FROM {} ORDER BY sort_key",
+            table
+        ))
+        .unwrap();
+    let rows: Vec<(Vec<u8>, Vec<u8>)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        rows,
+        vec![
+            (b"hello".to_vec(), b"world".to_vec()),
+            (b"key1".to_vec(), b"value1".to_vec()),
+            (b"key2".to_vec(), b"value2".to_vec()),
+        ]
+    );
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(input_path);
+}
+
+#[test]
+fn test_varchar_key_type() {
+    let input_path = "/tmp/test_varchar_key_type.dat";
+    let db_path = "/tmp/test_varchar_key_type.duckdb";
+    let table = "varchar_test";
+
+    // ASCII keys; payload bytes run past 0x7F, so they are not UTF-8
+    write_sequential_gensort(input_path, 1_000);
+
+    for extra in [&[][..], &["--threads", "2"], &["--via", "copy"]] {
+        let _ = fs::remove_file(db_path);
+        let output = Command::new(load_duckdb_binary())
+            .args([
+                "--format",
+                "gensort",
+                "--input",
+                input_path,
+                "--db",
+                db_path,
+                "--table",
+                table,
+                "--key-type",
+                "varchar",
+            ])
+            .args(extra)
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "Loader failed with {:?}: {:?}",
+            extra,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let conn = Connection::open(db_path).expect("Failed to open database");
+        let key_type: String = conn
+            .query_row(
+                &format!(
+                    "SELECT data_type FROM information_schema.columns \
+                     WHERE table_name = '{}' AND column_name = 'sort_key'",
+                    table
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(key_type, "VARCHAR");
+        let max_key: String = conn
+            .query_row(&format!("SELECT max(sort_key) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(max_key, "0000000999", "with {:?}", extra);
+    }
+
+    let output = run_sorter(db_path, table, "100MB");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("sort_key VARCHAR"));
+
+    // Binary payloads cannot go into a VARCHAR column
+    let _ = fs::remove_file(db_path);
+    let output = Command::new(load_duckdb_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            input_path,
+            "--db",
+            db_path,
+            "--payload-type",
+            "varchar",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not valid UTF-8"));
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(input_path);
+}
+
+#[test]
+fn test_scale_factor() {
+    let input_path = "/tmp/test_scale_factor.dat";
+    let db_path = "/tmp/test_scale_factor.duckdb";
+    let table = "scale_test";
+
+    write_sequential_gensort(input_path, 100);
+
+    // Tied copies, then copies with their own keys
+    for (extra, distinct) in [(&[][..], 100), (&["--perturb-keys"][..], 300)] {
+        let _ = fs::remove_file(db_path);
+        let output = Command::new(load_duckdb_binary())
+            .args([
+                "--format",
+                "gensort",
+                "--input",
+                input_path,
+                "--db",
+                db_path,
+                "--table",
+                table,
+                "--threads",
+                "2",
+                "--scale-factor",
+                "3",
+            ])
+            .args(extra)
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "Loader failed with {:?}: {:?}",
+            extra,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("appended 300 rows"));
+
+        let conn = Connection::open(db_path).expect("Failed to open database");
+        let counts: (i64, i64) = conn
+            .query_row(
+                &format!("SELECT count(*), count(DISTINCT sort_key) FROM {}", table),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(counts, (300, distinct), "with {:?}", extra);
+    }
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(input_path);
+}
+
+#[test]
+fn test_with_row_id() {
+    let input_path = "/tmp/test_with_row_id.dat";
+    let db_path = "/tmp/test_with_row_id.duckdb";
+    let table = "row_id_test";
+
+    load_fixture(
+        input_path,
+        100,
+        db_path,
+        table,
+        &["--threads", "2", "--scale-factor", "2", "--with-row-id"],
+    );
+
+    // Every row has its own id, numbered in input order across the threads
+    let conn = Connection::open(db_path).expect("Failed to open database");
+    let ids: (i64, i64, i64, i64) = conn
+        .query_row(
+            &format!(
+                "SELECT count(*), count(DISTINCT row_id), min(row_id), max(row_id) FROM {}",
+                table
+            ),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .unwrap();
+    assert_eq!(ids, (200, 200, 0, 199));
+    // Record i's copies are rows 2i and 2i + 1
+    let misplaced: i64 = conn
+        .query_row(
+            &format!(
+                "SELECT count(*) FROM {} WHERE CAST(sort_key AS VARCHAR) <> lpad(CAST(row_id // 2 AS VARCHAR), 10, '0')",
+                table
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(misplaced, 0);
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(input_path);
+}
+
+#[test]
+fn test_uint64_key_type() {
+    let input_path = "/tmp/test_uint64_key_type.dat";
+    let db_path = "/tmp/test_uint64_key_type.duckdb";
+    let table = "uint64_test";
+
+    write_sequential_gensort(input_path, 1_000);
+
+    for extra in [&[][..], &["--threads", "2"]] {
+        let _ = fs::remove_file(db_path);
+        let output = Command::new(load_duckdb_binary())
+            .args([
+                "--format",
+                "gensort",
+                "--input",
+                input_path,
+                "--db",
+                db_path,
+                "--table",
+                table,
+                "--key-type",
+                "uint64",
+            ])
+            .args(extra)
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "Loader failed with {:?}: {:?}",
+            extra,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let conn = Connection::open(db_path).expect("Failed to open database");
+        let (key_type, rows): (String, i64) = conn
+            .query_row(
+                &format!(
+                    "SELECT data_type, (SELECT count(*) FROM {}) FROM information_schema.columns \
+                     WHERE table_name = '{}' AND column_name = 'sort_key'",
+                    table, table
+                ),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((key_type.as_str(), rows), ("UBIGINT", 1_000));
+        // Only the first 8 of the 10 key bytes make it into the integer
+        let max_key: u64 = conn
+            .query_row(&format!("SELECT max(sort_key) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(
+            max_key,
+            es_duck::schema::key_u64(b"00000009"),
+            "with {:?}",
+            extra
+        );
+    }
+
+    let output = run_sorter(db_path, table, "100MB");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("sort_key UBIGINT"));
+
+    // DuckDB's own reader has no integer conversion for the key
+    let output = Command::new(load_duckdb_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            input_path,
+            "--db",
+            db_path,
+            "--key-type",
+            "uint64",
+            "--via",
+            "copy",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--via appender"));
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(input_path);
+}
+
+#[test]
+fn test_checkpoint_resume() {
+    let input_path = "/tmp/test_checkpoint_resume.dat";
+    let db_path = "/tmp/test_checkpoint_resume.duckdb";
+    let checkpoint_path = "/tmp/test_checkpoint_resume.ckpt";
+    let table = "checkpoint_test";
+
+    // 10 units of 100 records
+    let data = write_sequential_gensort(input_path, 1000);
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(checkpoint_path);
+
+    // A table that refuses keys from record 350 on interrupts the first run
+    // once its threads move their second unit
+    {
+        let conn = Connection::open(db_path).unwrap();
+        conn.execute_batch(&format!(
+            "CREATE TABLE {} (sort_key BLOB CHECK (sort_key < '0000000350'::BLOB), payload BLOB);",
+            table
+        ))
+        .unwrap();
+    }
+    let load = || {
+        Command::new(load_duckdb_binary())
+            .args([
+                "--format",
+                "gensort",
+                "--input",
+                input_path,
+                "--db",
+                db_path,
+                "--table",
+                table,
+                "--if-exists",
+                "append",
+                "--threads",
+                "3",
+                "--checkpoint-file",
+                checkpoint_path,
+                "--checkpoint-unit",
+                "10000",
+            ])
+            .output()
+            .expect("Failed to execute command")
+    };
+    let output = load();
+    assert!(
+        !output.status.success(),
+        "the first run was not interrupted"
+    );
+    let checkpoint = fs::read_to_string(checkpoint_path).unwrap();
+    let done = checkpoint
+        .lines()
+        .filter(|l| l.starts_with("done "))
+        .count();
+    assert!((1..=3).contains(&done), "checkpoint: {}", checkpoint);
+
+    // Lift the constraint, keeping the rows and the staging tables
+    {
+        let conn = Connection::open(db_path).unwrap();
+        let loaded: i64 = conn
+            .query_row(&format!("SELECT count(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert!(
+            loaded >= done as i64 * 100 && loaded <= 300,
+            "{} rows",
+            loaded
+        );
+        conn.execute_batch(&format!(
+            "ALTER TABLE {table} RENAME TO {table}_constrained;
+             CREATE TABLE {table} AS SELECT * FROM {table}_constrained;
+             DROP TABLE {table}_constrained;",
+            table = table
+        ))
+        .unwrap();
+    }
+
+    let output = load();
+    assert!(
+        output.status.success(),
+        "Loader failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains(&format!("({} already loaded)", done))
+    );
+    assert!(
+        !std::path::Path::new(checkpoint_path).exists(),
+        "checkpoint was not removed"
+    );
+
+    let conn = Connection::open(db_path).expect("Failed to open database");
+    conn.execute_batch(&format!(
+        "CREATE TABLE sorted AS SELECT * FROM {} ORDER BY sort_key",
+        table
+    ))
+    .unwrap();
+    assert!(
+        table_as_gensort(&conn, "sorted") == data,
+        "rows were lost or loaded twice"
+    );
+    let staging: i64 = conn
+        .query_row(
+            "SELECT count(*) FROM information_schema.tables WHERE table_name LIKE '%_unit_%'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(staging, 0, "staging tables were not dropped");
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(input_path);
+}
+
+#[test]
+fn test_skip_and_limit() {
+    let input_path = "/tmp/test_skip_and_limit.dat";
+    let db_path = "/tmp/test_skip_and_limit.duckdb";
+    let table = "skip_limit_test";
+
+    let data = write_sequential_gensort(input_path, 1000);
+
+    for extra in [
+        &["--threads", "1"][..],
+        &["--threads", "3"],
+        &["--via", "copy"],
+    ] {
+        let _ = fs::remove_file(db_path);
+        let output = Command::new(load_duckdb_binary())
+            .args([
+                "--format", "gensort", "--input", input_path, "--db", db_path, "--table", table,
+                "--skip", "100", "--limit", "250",
+            ])
+            .args(extra)
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "Loader failed: {:?}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let conn = Connection::open(db_path).expect("Failed to open database");
+        conn.execute_batch(&format!(
+            "CREATE TABLE sorted AS SELECT * FROM {} ORDER BY sort_key",
+            table
+        ))
+        .unwrap();
+        assert!(
+            table_as_gensort(&conn, "sorted") == data[100 * 100..350 * 100],
+            "wrong records loaded with {:?}",
+            extra
+        );
+    }
+
+    // A limit running past the end loads up to the end
+    let _ = fs::remove_file(db_path);
+    let output = Command::new(load_duckdb_binary())
+        .args([
+            "--format",
+            "kvbin",
+            "--input",
+            "testdata/test_kvbin.dat",
+            "--db",
+            db_path,
+            "--table",
+            table,
+            "--skip",
+            "1",
+            "--limit",
+            "10",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "Loader failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let conn = Connection::open(db_path).expect("Failed to open database");
+    let count: i64 = conn
+        .query_row(&format!("SELECT count(*) FROM {}", table), [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(count, 2);
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(input_path);
+}
+
+#[test]
+fn test_if_exists() {
+    let db_path = "/tmp/test_if_exists.duckdb";
+    let table = "if_exists_test";
+    let input_path = "testdata/test_gensort.dat";
+
+    let _ = fs::remove_file(db_path);
+    let output = run_loader("gensort", input_path, db_path, table);
+    assert!(output.status.success());
+
+    // Another table in the same file is fine
+    let output = run_loader("gensort", input_path, db_path, "other_table");
+    assert!(
+        output.status.success(),
+        "Loader failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The default refuses to touch the table
+    let output = run_loader("gensort", input_path, db_path, table);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+
+    let count = |expected: i64| {
+        let conn = Connection::open(db_path).expect("Failed to open database");
+        let count: i64 = conn
+            .query_row(&format!("SELECT count(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count, expected);
+    };
+    count(3);
+
+    for (policy, expected) in [("append", 6), ("replace", 3)] {
+        let output = Command::new(load_duckdb_binary())
+            .args([
+                "--format",
+                "gensort",
+                "--input",
+                input_path,
+                "--db",
+                db_path,
+                "--table",
+                table,
+                "--if-exists",
+                policy,
+            ])
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "Loader failed: {:?}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        count(expected);
+    }
+
+    let _ = fs::remove_file(db_path);
+}
+
+#[test]
+fn test_custom_column_names() {
+    let db_path = "/tmp/test_custom_columns.duckdb";
+    let table = "custom_columns_test";
+    let output_path = "/tmp/test_custom_columns.parquet";
+
+    let _ = fs::remove_file(db_path);
+    let output = Command::new(load_duckdb_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            "testdata/test_gensort.dat",
+            "--db",
+            db_path,
+            "--table",
+            table,
+            "--key-column",
+            "k",
+            "--payload-column",
+            "v",
+            "--extra-column",
+            "tenant_id INTEGER",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "Loader failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Closed before the sorter opens the file
+    {
+        let conn = Connection::open(db_path).expect("Failed to open database");
+        let columns: Vec<String> = conn
+            .prepare(&format!(
+                "SELECT column_name FROM information_schema.columns \
+                 WHERE table_name = '{}' ORDER BY ordinal_position",
+                table
+            ))
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(columns, ["k", "v", "tenant_id"]);
+        let nulls: i64 = conn
+            .query_row(
+                &format!("SELECT count(*) FROM {} WHERE tenant_id IS NULL", table),
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(nulls, 3);
+    }
+
+    // The sorter reads the same columns and writes the usual names
+    let output = Command::new(sort_duckdb_binary())
+        .args([
+            "--db",
+            db_path,
+            "--table",
+            table,
+            "--key-column",
+            "k",
+            "--payload-column",
+            "v",
+            "--output",
+            output_path,
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "Sorter failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let conn = Connection::open_in_memory().unwrap();
+    let sorted: i64 = conn
+        .query_row(
+            &format!(
+                "SELECT count(*) FROM read_parquet('{}') \
+                 WHERE sort_key IS NOT NULL AND payload IS NOT NULL",
+                output_path
+            ),
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(sorted, 3);
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(output_path);
+}
+
+#[test]
+fn test_mmap_input() {
+    let input_path = "/tmp/test_mmap_input.dat";
+    let db_path = "/tmp/test_mmap_input.duckdb";
+    let table = "mmap_test";
+
+    let data = write_sequential_gensort(input_path, 60_001);
+    for extra in [
+        &[][..],
+        &["--threads", "3", "--staging-tables"],
+        &["--arrow"],
+        &["--skip", "7", "--limit", "50000"],
+    ] {
+        let _ = fs::remove_file(db_path);
+        let output = Command::new(load_duckdb_binary())
+            .args([
+                "--format", "gensort", "--input", input_path, "--db", db_path, "--table", table,
+                "--io", "mmap",
+            ])
+            .args(extra)
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "Loader failed with {:?}: {:?}",
+            extra,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let conn = Connection::open(db_path).expect("Failed to open database");
+        let expected = if extra.first() == Some(&"--skip") {
+            &data[700..5_000_700]
+        } else {
+            &data[..]
+        };
+        assert!(
+            table_as_gensort(&conn, table) == expected,
+            "rows differ with {:?}",
+            extra
+        );
+    }
+
+    let _ = fs::remove_file(db_path);
+    let output = Command::new(load_duckdb_binary())
+        .args([
+            "--format",
+            "kvbin",
+            "--input",
+            "testdata/test_kvbin.dat",
+            "--db",
+            db_path,
+            "--io",
+            "mmap",
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("support --format gensort only"));
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(input_path);
+}
+
+#[test]
+fn test_sort_limit() {
+    let input_path = "/tmp/test_sort_limit.dat";
+    let db_path = "/tmp/test_sort_limit.duckdb";
+    let output_path = "/tmp/test_sort_limit.parquet";
+    let table = "limit_test";
+
+    load_fixture(input_path, 1000, db_path, table, &[]);
+
+    let expected: Vec<Vec<u8>> = (0..10).map(|i| format!("{:010}", i).into_bytes()).collect();
+    for extra in [&[][..], &["--keys-only"]] {
+        let output = Command::new(sort_duckdb_binary())
+            .args([
+                "--db",
+                db_path,
+                "--table",
+                table,
+                "--limit",
+                "10",
+                "--output",
+                output_path,
+            ])
+            .args(extra)
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "Sorter failed with {:?}: {:?}",
+            extra,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        // Only the 10 smallest keys, in order; --keys-only leaves out the payload
+        let conn = Connection::open_in_memory().unwrap();
+        let keys: Vec<Vec<u8>> = conn
+            .prepare(&format!(
+                "SELECT sort_key FROM read_parquet('{}')",
+                output_path
+            ))
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(keys, expected, "rows differ with {:?}", extra);
+        let columns: i64 = conn
+            .query_row(
+                &format!(
+                    "SELECT count(*) FROM (DESCRIBE SELECT * FROM read_parquet('{}'))",
+                    output_path
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(columns, 2 - extra.len() as i64, "columns with {:?}", extra);
+    }
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(input_path);
+    let _ = fs::remove_file(output_path);
+}
+
+#[test]
+fn test_sort_order_by_columns() {
+    let db_path = "/tmp/test_sort_order_by.duckdb";
+    let output_path = "/tmp/test_sort_order_by.parquet";
+    let table = "order_by_test";
+
+    // Few distinct c_int values, so the key breaks most ties
+    let _ = fs::remove_file(db_path);
+    Connection::open(db_path)
+        .expect("Failed to open database")
+        .execute_batch(&format!(
+            "CREATE TABLE {0} (sort_key BLOB, c_int INTEGER, payload BLOB); \
+             INSERT INTO {0} SELECT printf('%010d', i)::BLOB, i * 7 % 5, 'X'::BLOB \
+             FROM range(200) t(i);",
+            table
+        ))
+        .unwrap();
+    let mut expected: Vec<(i64, Vec<u8>)> = (0..200i64)
+        .map(|i| (i * 7 % 5, format!("{:010}", i).into_bytes()))
+        .collect();
+    // c_int ascending, then the key descending
+    expected.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+    let output = Command::new(sort_duckdb_binary())
+        .args([
+            "--db",
+            db_path,
+            "--table",
+            table,
+            "--order-by",
+            "c_int,sort_key DESC",
+            "--output",
+            output_path,
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "Sorter failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let conn = Connection::open_in_memory().unwrap();
+    let keys: Vec<Vec<u8>> = conn
+        .prepare(&format!(
+            "SELECT sort_key FROM read_parquet('{}')",
+            output_path
+        ))
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let expected: Vec<Vec<u8>> = expected.into_iter().map(|(_, key)| key).collect();
+    assert_eq!(keys, expected);
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(output_path);
+}
+
+#[test]
+fn test_sort_where() {
+    let db_path = "/tmp/test_sort_where.duckdb";
+    let output_path = "/tmp/test_sort_where.parquet";
+    let table = "where_test";
+
+    let _ = fs::remove_file(db_path);
+    Connection::open(db_path)
+        .expect("Failed to open database")
+        .execute_batch(&format!(
+            "CREATE TABLE {0} (sort_key BLOB, c_int INTEGER, payload BLOB); \
+             INSERT INTO {0} SELECT printf('%010d', 199 - i)::BLOB, i % 5, 'X'::BLOB \
+             FROM range(200) t(i);",
+            table
+        ))
+        .unwrap();
+
+    let output = Command::new(sort_duckdb_binary())
+        .args([
+            "--db",
+            db_path,
+            "--table",
+            table,
+            "--where",
+            "c_int = 3",
+            "--output",
+            output_path,
+        ])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "Sorter failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("WHERE: c_int = 3\nROWS_MATCHED: 40"),
+        "Expected the predicate in the results, got: {}",
+        stdout
+    );
+    // DuckDB shows a FILTER operator, or the predicate pushed into the scan as Filters
+    let plan = stdout
+        .split("===== SORT-ONLY EXPLAIN PLAN =====")
+        .nth(1)
+        .and_then(|rest| rest.split("=====").next())
+        .expect("Expected the sort plan");
+    assert!(
+        plan.to_lowercase().contains("filter"),
+        "Expected the filter in the plan, got: {}",
+        plan
+    );
+
+    // Only the matching rows, in key order
+    let conn = Connection::open_in_memory().unwrap();
+    let keys: Vec<Vec<u8>> = conn
+        .prepare(&format!(
+            "SELECT sort_key FROM read_parquet('{}')",
+            output_path
+        ))
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let mut expected: Vec<Vec<u8>> = (0..200)
+        .filter(|i| i % 5 == 3)
+        .map(|i| format!("{:010}", 199 - i).into_bytes())
+        .collect();
+    expected.sort();
+    assert_eq!(keys, expected);
+
+    let _ = fs::remove_file(db_path);
+    let _ = fs::remove_file(output_path);
+}
+
+#[test]
+fn test_sort_runs() {
+    let db_path = "/tmp/test_sort_runs.duckdb";
+    let table = "runs_test";
+
+    let _ = fs::remove_file(db_path);
+    Connection::open(db_path)
+        .expect("Failed to open database")
+        .execute_batch(&format!(
+            "CREATE TABLE {0} AS SELECT printf('%010d', 999 - i)::BLOB AS sort_key, \
+             'X'::BLOB AS payload FROM range(1000) t(i);",
+            table
+        ))
+        .unwrap();
+
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--db", db_path, "--table", table, "--runs", "3", "--cold"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "Sorter failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("TIMING:").count(), 1, "{}", stdout);
+    for line in ["RUN 1:", "RUN 3:", "(cold)", "RUNS: 3", "RUN_MAX:"] {
+        assert!(stdout.contains(line), "Expected {}, got: {}", line, stdout);
+    }
+
+    let _ = fs::remove_file(db_path);
+}
+
+#[test]
+fn test_sort_output_table() {
+    let db_path = "/tmp/test_sort_output_table.duckdb";
+    let table = "unsorted";
+
+    let _ = fs::remove_file(db_path);
+    Connection::open(db_path)
+        .expect("Failed to open database")
+        .execute_batch(&format!(
+            "CREATE TABLE {0} AS SELECT printf('%010d', (i * 37) % 500)::BLOB AS sort_key, \
+             'X'::BLOB AS payload FROM range(500) t(i);",
+            table
+        ))
+        .unwrap();
+
+    // The second sort replaces the table the first one made
+    for _ in 0..2 {
+        let output = Command::new(sort_duckdb_binary())
+            .args([
+                "--db",
+                db_path,
+                "--table",
+                table,
+                "--output-table",
+                "sorted",
+            ])
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "Sorter failed: {:?}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains("materializing table 'sorted'"),
+            "{}",
+            stdout
+        );
+    }
+
+    let conn = Connection::open(db_path).unwrap();
+    let keys: Vec<Vec<u8>> = conn
+        .prepare("SELECT sort_key FROM sorted")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let expected: Vec<Vec<u8>> = (0..500)
+        .map(|i| format!("{:010}", i).into_bytes())
+        .collect();
+    assert_eq!(keys, expected);
+
+    // Only one place to put the rows
+    let output = Command::new(sort_duckdb_binary())
+        .args([
+            "--db",
+            db_path,
+            "--table",
+            table,
+            "--output-table",
+            "sorted",
+        ])
+        .args(["--output", "/tmp/test_sort_output_table.parquet"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    let _ = fs::remove_file(db_path);
+}
+
+#[test]
+fn test_sort_uint64_key() {
+    let input_path = "/tmp/test_sort_uint64_key.dat";
+    let db_path = "/tmp/test_sort_uint64_key.duckdb";
+    let table = "uint64_sort_test";
+
+    // 200 records whose integer keys start at 0, which hex() writes as "0"
+    let key = |i: u64| i * (u64::MAX / 200);
+    let mut data = Vec::new();
+    for i in 0..200u64 {
+        data.extend_from_slice(&key(i * 37 % 200).to_be_bytes());
+        data.extend_from_slice(&[b'k'; 2]);
+        data.extend_from_slice(&[b'p'; 90]);
+    }
+    fs::write(input_path, &data).unwrap();
+    let _ = fs::remove_file(db_path);
+    let output = Command::new(load_duckdb_binary())
+        .args([
+            "--format", "gensort", "--input", input_path, "--db", db_path,
+        ])
+        .args(["--table", table, "--key-type", "uint64"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(
+        output.status.success(),
+        "Loader failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Every format has the keys as 8 big-endian bytes, but Parquet's UBIGINT
+    let expected: Vec<Vec<u8>> = (0..200).map(|i| key(i).to_be_bytes().to_vec()).collect();
+    for format in ["parquet", "csv", "native", "pgbinary", "kvbin"] {
+        let output_path = format!("/tmp/test_sort_uint64_key.{}", format);
+        let output = Command::new(sort_duckdb_binary())
+            .args(["--db", db_path, "--table", table, "--output", &output_path])
+            .arg("--verify")
+            .output()
+            .expect("Failed to execute command");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "Sorter failed for {}: {:?}",
+            format,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(
+            stdout.contains("VERIFY: OK 200 rows (sorted, row count matches)"),
+            "got: {}",
+            stdout
+        );
+        let keys: Vec<Vec<u8>> = match OutputFormat::from_path(output_path.as_ref()).unwrap() {
+            OutputFormat::Parquet => {
+                let conn = Connection::open_in_memory().unwrap();
+                let mut stmt = conn
+                    .prepare(&format!(
+                        "SELECT sort_key FROM read_parquet('{}')",
+                        output_path
+                    ))
+                    .unwrap();
+                stmt.query_map([], |row| row.get::<_, u64>(0))
+                    .unwrap()
+                    .map(|key| key.unwrap().to_be_bytes().to_vec())
+                    .collect()
+            }
+            format => {
+                let mut reader = Reader::open(output_path.as_ref(), format).unwrap();
+                let mut keys = Vec::new();
+                while let Some((key, _)) = reader.next_row().unwrap() {
+                    keys.push(key);
+                }
+                keys
+            }
+        };
+        assert_eq!(keys, expected, "{} keys", format);
+        let _ = fs::remove_file(&output_path);
+    }
+
+    // gensort's keys are 10 bytes
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--db", db_path, "--table", table])
+        .args(["--output", "/tmp/test_sort_uint64_key.gensort"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("a uint64 key can't write gensort"));
+
+    let _ = fs::remove_file(input_path);
+    let _ = fs::remove_file(db_path);
+}
+
+#[test]
+fn test_sort_output_formats() {
+    let input_path = "/tmp/test_sort_output_formats.dat";
+    let db_path = "/tmp/test_sort_output_formats.duckdb";
+    let table = "formats_test";
+
+    // Sequential keys: the sorted rows are the input records again
+    let data = load_fixture(input_path, 1000, db_path, table, &[]);
+
+    let mut csv = Vec::new();
+    csv_file::encode_records(&data, FixedLayout::GENSORT, Encoding::Hex, &mut csv);
+    // CSV comes from COPY, gensort from the rows read back; the extension
+    // picks the format unless --output-format is given
+    for (output_path, extra, expected) in [
+        ("/tmp/test_sort_output_formats.csv", &[][..], &csv),
+        (
+            "/tmp/test_sort_output_formats.out",
+            &["--output-format", "gensort"][..],
+            &data,
+        ),
+    ] {
+        let output = Command::new(sort_duckdb_binary())
+            .args(["--db", db_path, "--table", table, "--output", output_path])
+            .args(extra)
+            .output()
+            .expect("Failed to execute command");
+        assert!(
+            output.status.success(),
+            "Sorter failed for {}: {:?}",
+            output_path,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert_eq!(&fs::read(output_path).unwrap(), expected, "{}", output_path);
+        let _ = fs::remove_file(output_path);
+    }
+
+    // Gensort records need the payload
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--db", db_path, "--table", table, "--keys-only"])
+        .args(["--output", "/tmp/test_sort_output_formats.gensort"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    let _ = fs::remove_file(input_path);
+    let _ = fs::remove_file(db_path);
+}
+
+#[test]
+fn test_sort_settings() {
+    let input_path = "/tmp/test_sort_settings.dat";
+    let db_path = "/tmp/test_sort_settings.duckdb";
+    let table = "settings_test";
+
+    load_fixture(input_path, 100, db_path, table, &[]);
+
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--db", db_path, "--table", table])
+        .args(["--set", "preserve_insertion_order=false"])
+        .args(["--set", "checkpoint_threshold=64MB"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("Setting preserve_insertion_order = false"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Setting checkpoint_threshold = 64MB"),
+        "got: {}",
+        stdout
+    );
+
+    // An unknown setting fails the sort instead of being ignored
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--db", db_path, "--table", table])
+        .args(["--set", "no_such_setting=1"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    let _ = fs::remove_file(input_path);
+    let _ = fs::remove_file(db_path);
+}
+
+#[test]
+fn test_sort_timeout() {
+    let input_path = "/tmp/test_sort_timeout.dat";
+    let db_path = "/tmp/test_sort_timeout.duckdb";
+    let table = "timeout_test";
+
+    load_fixture(input_path, 100, db_path, table, &[]);
+
+    // A sort well within its budget reports TIMING as usual
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--db", db_path, "--table", table, "--timeout", "600"])
+        .args(["--runs", "2"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("TIMING:"), "got: {}", stdout);
+    assert!(!stdout.contains("TIMEOUT:"), "got: {}", stdout);
+
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--db", db_path, "--table", table, "--timeout", "0"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("--timeout must be at least 1 second")
+    );
+
+    let _ = fs::remove_file(input_path);
+    let _ = fs::remove_file(db_path);
+}
+
+#[test]
+fn test_sort_verify() {
+    let input_path = "/tmp/test_sort_verify.dat";
+    let db_path = "/tmp/test_sort_verify.duckdb";
+    let table = "verify_test";
+
+    load_fixture(input_path, 500, db_path, table, &[]);
+
+    // Parquet is read back through DuckDB, kvbin through export::Reader
+    for (output_path, extra, expected) in [
+        (
+            "/tmp/test_sort_verify.parquet",
+            &[][..],
+            "VERIFY: OK 500 rows (sorted, row count matches)",
+        ),
+        (
+            "/tmp/test_sort_verify.kvbin",
+            &["--limit", "100"][..],
+            "VERIFY: OK 100 rows (sorted, row count matches)",
+        ),
+    ] {
+        let output = Command::new(sort_duckdb_binary())
+            .args(["--db", db_path, "--table", table, "--output", output_path])
+            .arg("--verify")
+            .args(extra)
+            .output()
+            .expect("Failed to execute command");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "Sorter failed for {}: {:?}",
+            output_path,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(stdout.contains(expected), "got: {}", stdout);
+        let _ = fs::remove_file(output_path);
+    }
+
+    // Nothing to read back without an output file
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--db", db_path, "--table", table, "--verify"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    let _ = fs::remove_file(input_path);
+    let _ = fs::remove_file(db_path);
+}
+
+#[test]
+fn test_sort_phases() {
+    let input_path = "/tmp/test_sort_phases.dat";
+    let db_path = "/tmp/test_sort_phases.duckdb";
+    let output_path = "/tmp/test_sort_phases.kvbin";
+    let table = "phases_test";
+
+    load_fixture(input_path, 200, db_path, table, &[]);
+
+    // kvbin is written here, so every phase has a time
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--db", db_path, "--table", table, "--output", output_path])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    for phase in ["PHASE_PLAN:", "PHASE_EXECUTE:", "PHASE_EXPORT:"] {
+        let line = stdout
+            .lines()
+            .find(|line| line.starts_with(phase))
+            .unwrap_or_else(|| panic!("no {} in: {}", phase, stdout));
+        assert!(
+            line[phase.len()..].trim().parse::<f64>().is_ok(),
+            "got: {}",
+            line
+        );
+    }
+
+    let _ = fs::remove_file(output_path);
+    let _ = fs::remove_file(input_path);
+    let _ = fs::remove_file(db_path);
+}
+
+#[test]
+fn test_sort_input_files() {
+    let dir = "/tmp/test_sort_input_files";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let data = write_sequential_gensort(&format!("{}/input.dat", dir), 400);
+    let record = FixedLayout::GENSORT.record_size();
+
+    // The second half of the keys in the first file, so the glob's order isn't
+    // the sorted one
+    for encoding in [Encoding::Hex, Encoding::Base64] {
+        for (name, half) in [
+            ("a.csv", &data[200 * record..]),
+            ("b.csv", &data[..200 * record]),
+        ] {
+            let mut csv = Vec::new();
+            csv_file::encode_records(half, FixedLayout::GENSORT, encoding, &mut csv);
+            fs::write(format!("{}/{}", dir, name), csv).unwrap();
+        }
+
+        let output_path = format!("{}/sorted.kvbin", dir);
+        let encoding_name = match encoding {
+            Encoding::Hex => "hex",
+            Encoding::Base64 => "base64",
+        };
+        let output = Command::new(sort_duckdb_binary())
+            .args(["--input-files", &format!("{}/*.csv", dir)])
+            .args(["--csv-encoding", encoding_name])
+            .args(["--output", &output_path, "--verify"])
+            .output()
+            .expect("Failed to execute command");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "Sorter failed for {}: {:?}",
+            encoding_name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(stdout.contains("Input files:"), "got: {}", stdout);
+        assert!(stdout.contains("(2 files, csv)"), "got: {}", stdout);
+        // Decoded back to the bytes, whichever way they were encoded
+        assert!(
+            stdout.contains("VERIFY: OK 400 rows (sorted, row count matches)"),
+            "got: {}",
+            stdout
+        );
+    }
+
+    // Neither --db nor --input-files
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--table", "bench_data"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    // No file matches
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--input-files", &format!("{}/*.parquet", dir)])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_sort_distinct() {
+    let dir = "/tmp/test_sort_distinct";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let data = write_sequential_gensort(&format!("{}/input.dat", dir), 400);
+    let record = FixedLayout::GENSORT.record_size();
+
+    // Rows 100..300 are in both files: 600 rows, 400 keys
+    for (name, part) in [
+        ("a.csv", &data[..300 * record]),
+        ("b.csv", &data[100 * record..]),
+    ] {
+        let mut csv = Vec::new();
+        csv_file::encode_records(part, FixedLayout::GENSORT, Encoding::Hex, &mut csv);
+        fs::write(format!("{}/{}", dir, name), csv).unwrap();
+    }
+
+    // CSV selects the key as hex text, which must still come in key order
+    for output_name in ["distinct.kvbin", "distinct.csv"] {
+        let output_path = format!("{}/{}", dir, output_name);
+        let output = Command::new(sort_duckdb_binary())
+            .args(["--input-files", &format!("{}/*.csv", dir)])
+            .args(["--mode", "distinct"])
+            .args(["--output", &output_path, "--verify"])
+            .output()
+            .expect("Failed to execute command");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "Sorter failed for {}: {:?}",
+            output_name,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(stdout.contains("Row count: 600"), "got: {}", stdout);
+        assert!(
+            stdout.contains("Mode: distinct (each key once)"),
+            "got: {}",
+            stdout
+        );
+        assert!(
+            stdout.contains("VERIFY: OK 400 rows (sorted, row count not checked)"),
+            "got: {}",
+            stdout
+        );
+    }
+
+    // DISTINCT leaves only the key to order by
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--input-files", &format!("{}/*.csv", dir)])
+        .args(["--mode", "distinct", "--order-by", "payload"])
+        .output()
+        .expect("Failed to execute command");
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("drop --order-by"),
+        "got: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let _ = fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_sort_explain_only() {
+    let dir = "/tmp/test_sort_explain_only";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir_all(dir).unwrap();
+    let data = write_sequential_gensort(&format!("{}/input.dat", dir), 100);
+    let mut csv = Vec::new();
+    csv_file::encode_records(&data, FixedLayout::GENSORT, Encoding::Hex, &mut csv);
+    fs::write(format!("{}/input.csv", dir), csv).unwrap();
+
+    let output_path = format!("{}/sorted.parquet", dir);
+    let output = Command::new(sort_duckdb_binary())
+        .args(["--input-files", &format!("{}/input.csv", dir)])
+        .args(["--output", &output_path, "--memory-limit", "512MB"])
+        .args(["--set", "preserve_insertion_order=false", "--explain-only"])
+        .output()
+        .expect("Failed to execute command");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: {:?}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("Row count: not counted (--explain-only)"),
+        "got: {}",
+        stdout
+    );
+    assert!(stdout.contains("SORT-ONLY EXPLAIN PLAN"), "got: {}", stdout);
+    assert!(stdout.contains("===== SETTINGS ====="), "got: {}", stdout);
+    assert!(
+        stdout.contains("preserve_insertion_order = false"),
+        "got: {}",
+        stdout
+    );
+    assert!(stdout.contains("===== SQL ====="), "got: {}", stdout);
+    assert!(stdout.contains("COPY (SELECT"), "got: {}", stdout);
+    assert!(stdout.contains(&output_path), "got: {}", stdout);
+    assert!(
+        stdout.contains("Explain only: nothing was run"),
+        "got: {}",
+        stdout
+    );
+    // Nothing ran, so nothing was timed or written
+    assert!(!stdout.contains("TIMING:"), "got: {}", stdout);
+    assert!(!std::path::Path::new(&output_path).exists());
+
+    let _ = fs::remove_dir_all(dir);
+}
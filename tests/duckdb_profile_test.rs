@@ -0,0 +1,115 @@
+use es_duck::duckdb_profile::{self, OperatorTiming, Profile};
+
+const PROFILE: &str = r#"{
+    "latency": 2.5,
+    "cpu_time": 7.25,
+    "system_peak_buffer_memory": 104857600,
+    "system_peak_temp_dir_size": 314572800,
+    "children": [
+        {
+            "operator_type": "ORDER_BY",
+            "operator_timing": 1.5,
+            "operator_cardinality": 1000,
+            "children": [
+                {
+                    "operator_type": "TABLE_SCAN",
+                    "operator_timing": 0.25,
+                    "operator_cardinality": 1000,
+                    "children": []
+                }
+            ]
+        }
+    ]
+}"#;
+
+fn timing(operator: &str, seconds: f64, rows: u64) -> OperatorTiming {
+    OperatorTiming {
+        operator: operator.to_string(),
+        seconds,
+        rows,
+    }
+}
+
+#[test]
+fn test_parse_profile() {
+    let profile = duckdb_profile::parse(PROFILE).unwrap();
+    assert_eq!(
+        profile,
+        Profile {
+            latency: 2.5,
+            planning: 0.0,
+            cpu_time: 7.25,
+            peak_buffer_memory: 100 << 20,
+            peak_temp_dir_size: 300 << 20,
+            operators: vec![
+                timing("ORDER_BY", 1.5, 1000),
+                timing("TABLE_SCAN", 0.25, 1000)
+            ],
+        }
+    );
+    assert_eq!(
+        profile.report(),
+        "PROFILE_LATENCY: 2.500\nPROFILE_CPU_TIME: 7.250\n\
+         PROFILE_PEAK_BUFFER_MB: 100.0\nPROFILE_PEAK_TEMP_MB: 300.0\n\
+         OPERATOR: ORDER_BY 1.500s 1000 rows\nOPERATOR: TABLE_SCAN 0.250s 1000 rows"
+    );
+
+    // Metrics the settings left out count as 0
+    let bare = duckdb_profile::parse(r#"{"children": [{"operator_type": "ORDER_BY"}]}"#).unwrap();
+    assert_eq!(bare.peak_temp_dir_size, 0);
+    assert_eq!(bare.operators, vec![timing("ORDER_BY", 0.0, 0)]);
+    assert!(duckdb_profile::parse("ORDER_BY 1.5s").is_err());
+}
+
+#[test]
+fn test_merge_profiles_of_several_sorts() {
+    let mut profile = Profile::default();
+    profile.merge(duckdb_profile::parse(PROFILE).unwrap());
+    let mut second = duckdb_profile::parse(PROFILE).unwrap();
+    second.peak_temp_dir_size = 0;
+    second.operators.push(timing("UNION", 0.5, 2000));
+    profile.merge(second);
+
+    assert_eq!(profile.latency, 5.0);
+    assert_eq!(profile.peak_buffer_memory, 100 << 20);
+    assert_eq!(profile.peak_temp_dir_size, 300 << 20);
+    assert_eq!(
+        profile.operators,
+        vec![
+            timing("ORDER_BY", 3.0, 2000),
+            timing("TABLE_SCAN", 0.5, 2000),
+            timing("UNION", 0.5, 2000)
+        ]
+    );
+}
+
+#[test]
+fn test_planning_and_operator_seconds() {
+    let profile = duckdb_profile::parse(
+        r#"{"planner": 0.01, "all_optimizers": 0.02, "physical_planner": 0.005,
+            "children": [{"operator_type": "BATCH_COPY_TO_FILE", "operator_timing": 0.75}]}"#,
+    )
+    .unwrap();
+    assert!((profile.planning - 0.035).abs() < 1e-9);
+    assert_eq!(profile.operator_seconds("BATCH_COPY_TO_FILE"), Some(0.75));
+    assert_eq!(profile.operator_seconds("ORDER_BY"), None);
+}
+
+#[test]
+fn test_cpu_share() {
+    // Eight threads spent 4s writing out of 10s of CPU time
+    let profile = duckdb_profile::parse(
+        r#"{"cpu_time": 10.0, "children": [
+            {"operator_type": "ORDER_BY", "operator_timing": 6.0},
+            {"operator_type": "COPY_TO_FILE", "operator_timing": 4.0}]}"#,
+    )
+    .unwrap();
+    let share = profile
+        .cpu_share(&["COPY_TO_FILE", "BATCH_COPY_TO_FILE"])
+        .unwrap();
+    assert!((share - 0.4).abs() < 1e-9);
+    assert_eq!(profile.cpu_share(&["BATCH_COPY_TO_FILE"]), Some(0.0));
+
+    let profile = duckdb_profile::parse(r#"{"children": []}"#).unwrap();
+    assert_eq!(profile.cpu_share(&["COPY_TO_FILE"]), None);
+}
@@ -0,0 +1,241 @@
+use es_duck::export::{KeyType, OutputFormat, Reader, Writer};
+use es_duck::rowbinary;
+use std::path::Path;
+
+const ROWS: [(&[u8], &[u8]); 3] = [
+    (b"aaaaaaaaaa", &[1; 90]),
+    (b"bbbbbbbbbb", &[0; 90]),
+    (b"cccccccccc", &[0xff; 90]),
+];
+
+/// The file `Writer` makes of `ROWS`, with or without their payloads
+fn write_rows(format: OutputFormat, keys_only: bool) -> Vec<u8> {
+    let columns: &[&str] = match keys_only {
+        true => &["sort_key"],
+        false => &["sort_key", "payload"],
+    };
+    let mut data = Vec::new();
+    let mut writer = Writer::new(&mut data, format, columns).unwrap();
+    for (key, payload) in ROWS {
+        match keys_only {
+            true => writer.write_row(&[key]).unwrap(),
+            false => writer.write_row(&[key, payload]).unwrap(),
+        }
+    }
+    assert_eq!(writer.finish().unwrap(), ROWS.len() as u64);
+    data
+}
+
+fn read_rows(format: OutputFormat, data: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut reader = Reader::new(data, format).unwrap();
+    let mut rows = Vec::new();
+    while let Some(row) = reader.next_row().unwrap() {
+        rows.push(row);
+    }
+    rows
+}
+
+const ROW_FORMATS: [OutputFormat; 5] = [
+    OutputFormat::Csv,
+    OutputFormat::Native,
+    OutputFormat::Pgbinary,
+    OutputFormat::Kvbin,
+    OutputFormat::Gensort,
+];
+
+#[test]
+fn test_rows_read_back_in_every_format() {
+    let expected: Vec<(Vec<u8>, Vec<u8>)> = ROWS
+        .iter()
+        .map(|(key, payload)| (key.to_vec(), payload.to_vec()))
+        .collect();
+    for format in ROW_FORMATS {
+        let data = write_rows(format, false);
+        assert_eq!(read_rows(format, &data), expected, "{:?}", format);
+    }
+}
+
+#[test]
+fn test_keys_only_rows_have_empty_payloads() {
+    let expected: Vec<(Vec<u8>, Vec<u8>)> = ROWS
+        .iter()
+        .map(|(key, _)| (key.to_vec(), Vec::new()))
+        .collect();
+    for format in [
+        OutputFormat::Csv,
+        OutputFormat::Native,
+        OutputFormat::Pgbinary,
+        OutputFormat::Kvbin,
+    ] {
+        let data = write_rows(format, true);
+        assert_eq!(read_rows(format, &data), expected, "{:?}", format);
+    }
+}
+
+#[test]
+fn test_encodings() {
+    // Lowercase hex lines, as the loaders' CSV input
+    let csv = write_rows(OutputFormat::Csv, true);
+    assert_eq!(
+        csv,
+        b"61616161616161616161\n62626262626262626262\n63636363636363636363\n"
+    );
+    // Length-prefixed records and bare 100-byte records
+    let kvbin = write_rows(OutputFormat::Kvbin, false);
+    assert_eq!(kvbin.len(), 3 * (4 + 10 + 4 + 90));
+    assert_eq!(&kvbin[..4], &10u32.to_le_bytes());
+    assert_eq!(&kvbin[14..18], &90u32.to_le_bytes());
+    let gensort = write_rows(OutputFormat::Gensort, false);
+    assert_eq!(gensort.len(), 300);
+    assert_eq!(&gensort[100..110], b"bbbbbbbbbb");
+}
+
+#[test]
+fn test_gensort_needs_fixed_sizes() {
+    let mut data = Vec::new();
+    let mut writer =
+        Writer::new(&mut data, OutputFormat::Gensort, &["sort_key", "payload"]).unwrap();
+    let err = writer.write_row(&[b"short", b"payload"]).unwrap_err();
+    assert!(err.to_string().contains("gensort record"), "got: {}", err);
+}
+
+#[test]
+fn test_parquet_is_not_written_row_by_row() {
+    assert!(Writer::new(Vec::new(), OutputFormat::Parquet, &["sort_key"]).is_err());
+    assert!(Reader::new(&b""[..], OutputFormat::Parquet).is_err());
+}
+
+#[test]
+fn test_row_ids_read_back() {
+    let ids = [7u64, 3, u64::MAX];
+    for format in ROW_FORMATS {
+        let mut data = Vec::new();
+        let columns = ["sort_key", "payload", "row_id"];
+        let mut writer = Writer::new(&mut data, format, &columns).unwrap();
+        for ((key, payload), id) in ROWS.iter().zip(ids) {
+            writer
+                .write_row(&[key, payload, &id.to_be_bytes()])
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = Reader::new(&data[..], format).unwrap();
+        for ((key, payload), id) in ROWS.iter().zip(ids) {
+            let ((k, p), read_id) = reader.next_row_with_id().unwrap().unwrap();
+            assert_eq!(
+                (k.as_slice(), p.as_slice()),
+                (*key, *payload),
+                "{:?}",
+                format
+            );
+            // kvbin and gensort have no room for the id
+            let expected = format.carries_row_ids().then_some(id);
+            assert_eq!(read_id, expected, "{:?}", format);
+        }
+        assert!(reader.next_row_with_id().unwrap().is_none());
+    }
+
+    // The third column can only be the row id, of 8 bytes
+    let columns = ["sort_key", "payload", "c_int"];
+    assert!(Writer::new(Vec::new(), OutputFormat::Csv, &columns).is_err());
+    let columns = ["sort_key", "payload", "row_id"];
+    let mut writer = Writer::new(Vec::new(), OutputFormat::Native, &columns).unwrap();
+    assert!(writer.write_row(&[b"k", b"v", b"id"]).is_err());
+}
+
+#[test]
+fn test_native_splits_blocks() {
+    let rows = 65_536 + 10;
+    let mut data = Vec::new();
+    let mut writer = Writer::new(&mut data, OutputFormat::Native, &["sort_key"]).unwrap();
+    for i in 0..rows as u32 {
+        writer.write_row(&[&i.to_be_bytes()]).unwrap();
+    }
+    writer.finish().unwrap();
+    let read = read_rows(OutputFormat::Native, &data);
+    assert_eq!(read.len(), rows);
+    assert_eq!(read[65_536].0, 65_536u32.to_be_bytes());
+}
+
+#[test]
+fn test_integer_keys() {
+    // A Native block as ClickHouse writes a UInt64 sort_key
+    let mut data = Vec::new();
+    rowbinary::write_varint(&mut data, 1);
+    rowbinary::write_varint(&mut data, 2);
+    rowbinary::write_string(&mut data, b"sort_key");
+    rowbinary::write_string(&mut data, b"UInt64");
+    for key in [5u64, 1 << 63] {
+        data.extend_from_slice(&key.to_le_bytes());
+    }
+    let keys: Vec<Vec<u8>> = read_rows(OutputFormat::Native, &data)
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    assert_eq!(
+        keys,
+        [5u64.to_be_bytes(), (1u64 << 63).to_be_bytes()].map(|key| key.to_vec())
+    );
+
+    assert_eq!(KeyType::from_sql("bigint"), KeyType::Uint64);
+    assert_eq!(KeyType::from_sql("UBIGINT"), KeyType::Uint64);
+    assert_eq!(KeyType::from_sql("VARCHAR"), KeyType::Text);
+    assert_eq!(KeyType::from_sql("FixedString(10)"), KeyType::Bytes);
+    assert!(KeyType::Uint64.check(OutputFormat::Gensort).is_err());
+    assert!(KeyType::Uint64.check(OutputFormat::Kvbin).is_ok());
+}
+
+#[test]
+fn test_truncated_files_are_errors() {
+    for format in [OutputFormat::Kvbin, OutputFormat::Gensort] {
+        let data = write_rows(format, false);
+        let mut reader = Reader::new(&data[..data.len() - 1], format).unwrap();
+        assert!(reader.next_row().unwrap().is_some());
+        assert!(reader.next_row().unwrap().is_some());
+        assert!(reader.next_row().is_err(), "{:?}", format);
+    }
+}
+
+#[test]
+fn test_format_from_path() {
+    let format = |path: &str| OutputFormat::from_path(Path::new(path));
+    assert_eq!(format("sorted.parquet"), Some(OutputFormat::Parquet));
+    assert_eq!(format("out/sorted.shard0.CSV"), Some(OutputFormat::Csv));
+    assert_eq!(format("postgres.bin"), Some(OutputFormat::Pgbinary));
+    assert_eq!(format("sorted.dat"), Some(OutputFormat::Gensort));
+    assert_eq!(format("sorted"), None);
+
+    // An explicit format wins over the extension, which wins over the default
+    let path = Path::new("sorted.kvbin");
+    assert_eq!(
+        OutputFormat::resolve(Some(OutputFormat::Csv), path, OutputFormat::Native),
+        OutputFormat::Csv
+    );
+    assert_eq!(
+        OutputFormat::resolve(None, path, OutputFormat::Native),
+        OutputFormat::Kvbin
+    );
+    assert_eq!(
+        OutputFormat::resolve(None, Path::new("sorted.out"), OutputFormat::Native),
+        OutputFormat::Native
+    );
+}
+
+#[test]
+fn test_check_names_what_a_sorter_writes() {
+    let supported = [OutputFormat::Pgbinary, OutputFormat::Csv];
+    assert_eq!(
+        OutputFormat::Parquet.check("sort-postgres", &supported, false),
+        Err("sort-postgres can't write parquet output (it writes pgbinary, csv)".to_string())
+    );
+    assert!(
+        OutputFormat::Csv
+            .check("sort-postgres", &supported, true)
+            .is_ok()
+    );
+    assert!(
+        OutputFormat::Gensort
+            .check("sort-duckdb", &[OutputFormat::Gensort], true)
+            .is_err()
+    );
+}
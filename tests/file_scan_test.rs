@@ -0,0 +1,150 @@
+use es_duck::csv_file::Encoding;
+use es_duck::file_scan::{self, ScanFormat};
+use es_duck::schema::ColumnNames;
+
+#[test]
+fn test_scan_format() {
+    assert_eq!(
+        ScanFormat::from_pattern("data/*.parquet"),
+        Some(ScanFormat::Parquet)
+    );
+    assert_eq!(
+        ScanFormat::from_pattern("part-*.CSV"),
+        Some(ScanFormat::Csv)
+    );
+    assert_eq!(ScanFormat::from_pattern("data/*"), None);
+
+    assert_eq!(
+        ScanFormat::resolve(Some(ScanFormat::Csv), "data/*.txt"),
+        Ok(ScanFormat::Csv)
+    );
+    assert_eq!(
+        ScanFormat::resolve(None, "data/*.txt"),
+        Err(
+            "can't tell the format of 'data/*.txt' from its extension: pass --input-format"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn test_duckdb_scan() {
+    let columns = ColumnNames::default();
+    assert_eq!(
+        file_scan::duckdb_scan(
+            "it's/*.parquet",
+            ScanFormat::Parquet,
+            Encoding::Hex,
+            &columns
+        )
+        .unwrap(),
+        "read_parquet('it''s/*.parquet')"
+    );
+
+    // CSV fields are decoded back to bytes under the --key-column names
+    let columns = ColumnNames {
+        key: "k".to_string(),
+        payload: "v".to_string(),
+    };
+    assert_eq!(
+        file_scan::duckdb_scan("*.csv", ScanFormat::Csv, Encoding::Base64, &columns).unwrap(),
+        "(SELECT from_base64(column0) AS k, from_base64(column1) AS v FROM read_csv('*.csv', \
+         header = false, columns = {'column0': 'VARCHAR', 'column1': 'VARCHAR'})) AS input_files"
+    );
+
+    // DuckDB reads no ClickHouse formats
+    for format in [ScanFormat::RowBinary, ScanFormat::Native] {
+        assert!(
+            file_scan::duckdb_scan("*.bin", format, Encoding::Hex, &columns)
+                .unwrap_err()
+                .contains("sort-duckdb can't read")
+        );
+    }
+}
+
+#[test]
+fn test_scan_format_check() {
+    assert!(
+        ScanFormat::Csv
+            .check("sort-duckdb", file_scan::DUCKDB_FORMATS)
+            .is_ok()
+    );
+    assert_eq!(
+        ScanFormat::RowBinary.check("sort-duckdb", file_scan::DUCKDB_FORMATS),
+        Err("sort-duckdb can't read rowbinary input files (it reads parquet, csv)".to_string())
+    );
+    assert_eq!(
+        ScanFormat::from_pattern("parts/*.rowbinary"),
+        Some(ScanFormat::RowBinary)
+    );
+    assert_eq!(
+        ScanFormat::from_pattern("parts/*.native"),
+        Some(ScanFormat::Native)
+    );
+}
+
+#[test]
+fn test_clickhouse_scan() {
+    let columns = ColumnNames::default();
+    assert_eq!(
+        file_scan::clickhouse_scan(
+            "parts/*.rowbinary",
+            ScanFormat::RowBinary,
+            Encoding::Hex,
+            &columns
+        ),
+        "file('parts/*.rowbinary', 'RowBinary', 'sort_key String, payload String')"
+    );
+    // A URL is read through s3()
+    assert_eq!(
+        file_scan::clickhouse_scan(
+            "s3://bucket/it's/*.native",
+            ScanFormat::Native,
+            Encoding::Hex,
+            &columns
+        ),
+        "s3('s3://bucket/it''s/*.native', 'Native', 'sort_key String, payload String')"
+    );
+    assert_eq!(
+        file_scan::clickhouse_scan("*.csv", ScanFormat::Csv, Encoding::Base64, &columns),
+        "(SELECT base64Decode(column0) AS sort_key, base64Decode(column1) AS payload \
+         FROM file('*.csv', 'CSV', 'column0 String, column1 String')) AS input_files"
+    );
+
+    assert_eq!(
+        file_scan::clickhouse_file_sizes("https://host/parts/*.parquet"),
+        "SELECT count(), toUInt64(ifNull(sum(_size), 0)) \
+         FROM s3('https://host/parts/*.parquet', 'One')"
+    );
+}
+
+#[test]
+fn test_postgres_foreign_table() {
+    assert!(file_scan::is_glob("/data/part-?.csv"));
+    assert!(!file_scan::is_glob("/data/sorted.csv"));
+
+    assert_eq!(
+        file_scan::postgres_foreign_table("files", "/data/it's.csv"),
+        "CREATE EXTENSION IF NOT EXISTS file_fdw; \
+         CREATE SERVER IF NOT EXISTS es_duck_files FOREIGN DATA WRAPPER file_fdw; \
+         DROP FOREIGN TABLE IF EXISTS files; \
+         CREATE FOREIGN TABLE files (column0 text, column1 text) SERVER es_duck_files \
+         OPTIONS (format 'csv', filename '/data/it''s.csv')"
+    );
+    // A glob is left to the server's shell, anything else in it quoted
+    assert!(
+        file_scan::postgres_foreign_table("files", "/data/*.csv")
+            .ends_with("OPTIONS (format 'csv', program 'cat -- /data/*.csv')")
+    );
+    assert!(
+        file_scan::postgres_foreign_table("files", "/my data/it's;rm/[0-9]*.csv").ends_with(
+            "OPTIONS (format 'csv', program 'cat -- /my'' ''data/it\\''s'';''rm/[0-9]*.csv')"
+        )
+    );
+
+    assert_eq!(
+        file_scan::postgres_scan("files", Encoding::Base64, &ColumnNames::default()),
+        "(SELECT decode(column0, 'base64') AS sort_key, decode(column1, 'base64') AS payload \
+         FROM files) AS input_files"
+    );
+}
@@ -0,0 +1,709 @@
+#![cfg(feature = "util-rand")]
+
+use es_duck::checksum::Checksum;
+use es_duck::csv_file::{self, Encoding};
+use es_duck::schema::WideRow;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn generate_gensort_binary() -> String {
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    format!("target/{}/generate-gensort", profile)
+}
+
+fn generate(output: &str, num_records: u64, seed: Option<u64>) -> Vec<u8> {
+    generate_with(output, num_records, seed, &[])
+}
+
+fn generate_with(output: &str, num_records: u64, seed: Option<u64>, extra: &[&str]) -> Vec<u8> {
+    let mut cmd = Command::new(generate_gensort_binary());
+    cmd.args([
+        "--output",
+        output,
+        "--num-records",
+        &num_records.to_string(),
+    ]);
+    cmd.args(extra);
+    if let Some(seed) = seed {
+        cmd.args(["--seed", &seed.to_string()]);
+    }
+    let status = cmd.status().expect("Failed to run generate-gensort");
+    assert!(status.success());
+    fs::read(output).unwrap()
+}
+
+#[test]
+fn test_seed_is_reproducible() {
+    let first = generate("/tmp/test_generate_seed_a.dat", 1000, Some(42));
+    let second = generate("/tmp/test_generate_seed_b.dat", 1000, Some(42));
+    assert_eq!(first.len(), 1000 * 100);
+    assert_eq!(first, second);
+
+    let other_seed = generate("/tmp/test_generate_seed_c.dat", 1000, Some(43));
+    assert_ne!(first, other_seed);
+
+    // A shorter run with the same seed is a prefix of the longer one
+    let shorter = generate("/tmp/test_generate_seed_d.dat", 10, Some(42));
+    assert_eq!(shorter[..], first[..shorter.len()]);
+
+    let unseeded = generate("/tmp/test_generate_seed_e.dat", 1000, None);
+    assert_ne!(first, unseeded);
+
+    for name in ["a", "b", "c", "d", "e"] {
+        let _ = fs::remove_file(format!("/tmp/test_generate_seed_{}.dat", name));
+    }
+}
+
+#[test]
+fn test_threads_do_not_change_output() {
+    let single = generate("/tmp/test_generate_threads_1.dat", 1001, Some(9));
+    for threads in ["4", "7"] {
+        let parallel = generate_with(
+            "/tmp/test_generate_threads_n.dat",
+            1001,
+            Some(9),
+            &["--threads", threads],
+        );
+        assert_eq!(single, parallel, "--threads {} changed the output", threads);
+    }
+
+    // More threads than records leaves some threads without work
+    let tiny = generate_with(
+        "/tmp/test_generate_threads_n.dat",
+        3,
+        Some(9),
+        &["--threads", "8"],
+    );
+    assert_eq!(tiny[..], single[..300]);
+
+    let _ = fs::remove_file("/tmp/test_generate_threads_1.dat");
+    let _ = fs::remove_file("/tmp/test_generate_threads_n.dat");
+}
+
+#[test]
+fn test_zipf_keys_are_skewed() {
+    let count_keys = |data: &[u8]| {
+        let mut counts = std::collections::HashMap::new();
+        for record in data.chunks_exact(100) {
+            *counts.entry(record[..10].to_vec()).or_insert(0usize) += 1;
+        }
+        let mut counts: Vec<usize> = counts.into_values().collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        counts
+    };
+
+    let uniform = generate("/tmp/test_generate_zipf_u.dat", 10_000, Some(5));
+    let zipf = generate_with(
+        "/tmp/test_generate_zipf_z.dat",
+        10_000,
+        Some(5),
+        &["--distribution", "zipf", "--zipf-s", "1.2"],
+    );
+    assert_eq!(count_keys(&uniform)[0], 1);
+    let counts = count_keys(&zipf);
+    assert!(
+        counts[0] > 1_000,
+        "most frequent key only appears {} times",
+        counts[0]
+    );
+    assert!(counts[0] > counts[1] && counts[1] > counts[10]);
+
+    // Payloads stay those of the uniform file; only keys change
+    for (u, z) in uniform.chunks_exact(100).zip(zipf.chunks_exact(100)) {
+        assert_eq!(u[10..], z[10..]);
+    }
+
+    let parallel = generate_with(
+        "/tmp/test_generate_zipf_z.dat",
+        10_000,
+        Some(5),
+        &[
+            "--distribution",
+            "zipf",
+            "--zipf-s",
+            "1.2",
+            "--threads",
+            "3",
+        ],
+    );
+    assert_eq!(zipf, parallel);
+
+    let _ = fs::remove_file("/tmp/test_generate_zipf_u.dat");
+    let _ = fs::remove_file("/tmp/test_generate_zipf_z.dat");
+}
+
+#[test]
+fn test_distinct_keys_are_exact() {
+    let keys = |data: &[u8]| {
+        let mut counts = std::collections::HashMap::new();
+        for record in data.chunks_exact(100) {
+            *counts.entry(record[..10].to_vec()).or_insert(0usize) += 1;
+        }
+        counts
+    };
+
+    let data = generate_with(
+        "/tmp/test_generate_distinct.dat",
+        1000,
+        Some(3),
+        &["--distinct-keys", "7"],
+    );
+    let counts = keys(&data);
+    assert_eq!(counts.len(), 7);
+    assert!(counts.values().all(|&c| c == 142 || c == 143));
+    // Equal keys are spread over the file, not grouped or strictly cyclic
+    let records: Vec<&[u8]> = data.chunks_exact(100).map(|r| &r[..10]).collect();
+    for period in [1, 7] {
+        let repeats = (period..records.len())
+            .filter(|&i| records[i] == records[i - period])
+            .count();
+        assert!(
+            repeats < 300,
+            "{} keys repeat after {} records",
+            repeats,
+            period
+        );
+    }
+
+    let data = generate_with(
+        "/tmp/test_generate_distinct.dat",
+        1000,
+        Some(3),
+        &["--duplicate-ratio", "0.25", "--threads", "3"],
+    );
+    assert_eq!(keys(&data).len(), 750);
+
+    let status = Command::new(generate_gensort_binary())
+        .args(["--output", "/tmp/test_generate_distinct.dat"])
+        .args(["--num-records", "10", "--duplicate-ratio", "1.0"])
+        .status()
+        .unwrap();
+    assert!(!status.success());
+
+    let _ = fs::remove_file("/tmp/test_generate_distinct.dat");
+}
+
+#[test]
+fn test_ascii_records_follow_gensort_layout() {
+    let data = generate_with(
+        "/tmp/test_generate_ascii.dat",
+        1000,
+        Some(11),
+        &["--ascii", "--threads", "2"],
+    );
+    assert_eq!(data.len(), 1000 * 100);
+    for (i, record) in data.chunks_exact(100).enumerate() {
+        assert!(record[..10].iter().all(|c| (b' '..=b'~').contains(c)));
+        assert_eq!(&record[10..12], b"  ");
+        assert_eq!(&record[12..44], format!("{:032X}", i).as_bytes());
+        assert_eq!(&record[44..46], b"  ");
+        for group in record[46..98].chunks_exact(4) {
+            assert!(group[0].is_ascii_hexdigit() && !group[0].is_ascii_lowercase());
+            assert!(group.iter().all(|&c| c == group[0]));
+        }
+        assert_eq!(&record[98..], b"\r\n");
+    }
+
+    // Rank-based keys stay distinct after the conversion
+    let data = generate_with(
+        "/tmp/test_generate_ascii.dat",
+        1000,
+        Some(11),
+        &["--ascii", "--distinct-keys", "1000"],
+    );
+    let keys: std::collections::HashSet<&[u8]> = data.chunks_exact(100).map(|r| &r[..10]).collect();
+    assert_eq!(keys.len(), 1000);
+
+    let _ = fs::remove_file("/tmp/test_generate_ascii.dat");
+}
+
+#[test]
+fn test_checksum_file_matches_data() {
+    let data = generate_with(
+        "/tmp/test_generate_checksum.dat",
+        1000,
+        Some(13),
+        &["--checksum", "--threads", "3", "--ascii"],
+    );
+    let written = Checksum::read(Path::new("/tmp/test_generate_checksum.sum")).unwrap();
+    let mut expected = Checksum::new();
+    expected.add_records(&data, 100);
+    assert_eq!(written, expected);
+    assert_eq!(written.records, 1000);
+
+    let _ = fs::remove_file("/tmp/test_generate_checksum.dat");
+    let _ = fs::remove_file("/tmp/test_generate_checksum.sum");
+}
+
+#[cfg(feature = "db-duckdb")]
+#[test]
+fn test_generate_straight_into_duckdb() {
+    let db = "/tmp/test_generate_load.db";
+    let _ = fs::remove_file(db);
+    let status = Command::new(generate_gensort_binary())
+        .args(["--num-records", "1000", "--seed", "21", "--threads", "3"])
+        .args([
+            "--load",
+            "duckdb",
+            "--connection",
+            db,
+            "--batch-size",
+            "128",
+        ])
+        .status()
+        .expect("Failed to run generate-gensort");
+    assert!(status.success());
+
+    // The table holds exactly the records the file would have
+    let data = generate("/tmp/test_generate_load.dat", 1000, Some(21));
+    let mut expected: Vec<Vec<u8>> = data.chunks_exact(100).map(|r| r[..10].to_vec()).collect();
+    expected.sort();
+
+    let conn = duckdb::Connection::open(db).unwrap();
+    let mut stmt = conn
+        .prepare("SELECT sort_key FROM bench_data ORDER BY sort_key")
+        .unwrap();
+    let keys: Vec<Vec<u8>> = stmt
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .map(|k| k.unwrap())
+        .collect();
+    assert_eq!(keys, expected);
+
+    let _ = fs::remove_file(db);
+    let _ = fs::remove_file("/tmp/test_generate_load.dat");
+}
+
+#[cfg(feature = "db-duckdb")]
+#[test]
+fn test_generate_wide_schema_into_duckdb() {
+    let db = "/tmp/test_generate_wide.db";
+    let _ = fs::remove_file(db);
+    let status = Command::new(generate_gensort_binary())
+        .args(["--num-records", "500", "--seed", "43", "--schema", "wide"])
+        .args(["--load", "duckdb", "--connection", db])
+        .status()
+        .expect("Failed to run generate-gensort");
+    assert!(status.success());
+
+    let data = generate_with(
+        "/tmp/test_generate_wide.dat",
+        500,
+        Some(43),
+        &["--schema", "wide"],
+    );
+    let mut expected: Vec<(i32, String)> = data
+        .chunks_exact(100)
+        .map(|r| {
+            let row = WideRow::decode(&r[10..]).unwrap();
+            (row.c_int, row.c_text.to_string())
+        })
+        .collect();
+    expected.sort();
+
+    let conn = duckdb::Connection::open(db).unwrap();
+    let mut stmt = conn
+        .prepare("SELECT c_int, c_text FROM bench_data ORDER BY c_int, c_text")
+        .unwrap();
+    let rows: Vec<(i32, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+    assert_eq!(rows, expected);
+
+    let _ = fs::remove_file(db);
+    let _ = fs::remove_file("/tmp/test_generate_wide.dat");
+}
+
+#[test]
+fn test_slices_concatenate_to_whole_dataset() {
+    for extra in [
+        &["--ascii"][..],
+        &["--distinct-keys", "37"][..],
+        &["--distribution", "zipf"][..],
+    ] {
+        let whole = generate_with("/tmp/test_generate_slice_all.dat", 1000, Some(17), extra);
+
+        let mut sliced = Vec::new();
+        for (start, count) in [(0, 400), (400, 250), (650, 350)] {
+            let mut args = vec![
+                "--start-record".to_string(),
+                start.to_string(),
+                "--total-records".to_string(),
+                "1000".to_string(),
+                "--threads".to_string(),
+                "2".to_string(),
+            ];
+            args.extend(extra.iter().map(|a| a.to_string()));
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            sliced.extend(generate_with(
+                "/tmp/test_generate_slice_part.dat",
+                count,
+                Some(17),
+                &args,
+            ));
+        }
+        assert_eq!(whole, sliced, "slices differ with {:?}", extra);
+    }
+
+    let result = Command::new(generate_gensort_binary())
+        .args(["--output", "/tmp/test_generate_slice_part.dat"])
+        .args(["--num-records", "10", "--start-record", "995"])
+        .args(["--total-records", "1000"])
+        .output()
+        .unwrap();
+    assert!(!result.status.success());
+
+    let _ = fs::remove_file("/tmp/test_generate_slice_all.dat");
+    let _ = fs::remove_file("/tmp/test_generate_slice_part.dat");
+}
+
+#[test]
+fn test_payload_entropy() {
+    let high = generate("/tmp/test_generate_entropy.dat", 500, Some(23));
+    let low = generate_with(
+        "/tmp/test_generate_entropy.dat",
+        500,
+        Some(23),
+        &["--payload-entropy", "low"],
+    );
+    let medium = generate_with(
+        "/tmp/test_generate_entropy.dat",
+        500,
+        Some(23),
+        &["--payload-entropy", "medium", "--threads", "2"],
+    );
+
+    let distinct = |payload: &[u8]| {
+        let mut seen = [false; 256];
+        payload.iter().for_each(|&b| seen[b as usize] = true);
+        seen.iter().filter(|&&s| s).count()
+    };
+    for ((h, l), m) in high
+        .chunks_exact(100)
+        .zip(low.chunks_exact(100))
+        .zip(medium.chunks_exact(100))
+    {
+        // Keys do not change
+        assert_eq!(h[..10], l[..10]);
+        assert_eq!(h[..10], m[..10]);
+
+        assert_eq!(distinct(&l[10..]), 1);
+        assert!(m[10..].iter().all(|b| (b'a'..=b'p').contains(b)));
+        assert!(distinct(&m[10..]) > 8);
+    }
+    assert!(high.chunks_exact(100).all(|h| distinct(&h[10..]) > 40));
+
+    let _ = fs::remove_file("/tmp/test_generate_entropy.dat");
+}
+
+#[test]
+fn test_custom_record_layout() {
+    let layout = ["--key-size", "16", "--payload-size", "1001"];
+    let single = generate_with("/tmp/test_generate_layout_a.dat", 300, Some(29), &layout);
+    assert_eq!(single.len(), 300 * 1017);
+
+    // Odd record sizes start records mid-word; threads must still agree
+    let threaded = generate_with(
+        "/tmp/test_generate_layout_b.dat",
+        300,
+        Some(29),
+        &[&layout[..], &["--threads", "4"]].concat(),
+    );
+    assert_eq!(single, threaded);
+
+    // Keys from ranks fill every key byte, and stay distinct
+    let distinct = generate_with(
+        "/tmp/test_generate_layout_b.dat",
+        300,
+        Some(29),
+        &[&layout[..], &["--distinct-keys", "5"]].concat(),
+    );
+    let keys: std::collections::HashSet<&[u8]> =
+        distinct.chunks_exact(1017).map(|r| &r[..16]).collect();
+    assert_eq!(keys.len(), 5);
+    assert!(keys.iter().all(|k| k[10..] != [0u8; 6]));
+
+    // The default layout is gensort's
+    let explicit = generate_with(
+        "/tmp/test_generate_layout_b.dat",
+        300,
+        Some(29),
+        &["--key-size", "10", "--payload-size", "90"],
+    );
+    assert_eq!(
+        explicit,
+        generate("/tmp/test_generate_layout_a.dat", 300, Some(29))
+    );
+
+    let status = Command::new(generate_gensort_binary())
+        .args(["--output", "/tmp/test_generate_layout_a.dat"])
+        .args(["--num-records", "10", "--ascii", "--key-size", "16"])
+        .status()
+        .unwrap();
+    assert!(!status.success());
+
+    for name in ["a", "b"] {
+        let _ = fs::remove_file(format!("/tmp/test_generate_layout_{}.dat", name));
+    }
+}
+
+#[cfg(feature = "util-parquet")]
+#[test]
+fn test_parquet_output_matches_binary() {
+    use arrow_array::{Array, BinaryArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    // Large records make several 16MB chunks, so all threads contribute to the file
+    let layout = ["--key-size", "10", "--payload-size", "500000"];
+    let binary = generate_with("/tmp/test_generate_parquet.dat", 100, Some(31), &layout);
+
+    let path = "/tmp/test_generate_parquet.parquet";
+    let status = Command::new(generate_gensort_binary())
+        .args(["--output", path, "--num-records", "100", "--seed", "31"])
+        .args(layout)
+        .args(["--output-format", "parquet", "--row-group-size", "30"])
+        .args(["--threads", "3"])
+        .status()
+        .expect("Failed to run generate-gensort");
+    assert!(status.success());
+
+    let builder = ParquetRecordBatchReaderBuilder::try_new(fs::File::open(path).unwrap()).unwrap();
+    assert_eq!(builder.metadata().num_row_groups(), 4);
+    let mut rows = Vec::new();
+    for batch in builder.build().unwrap() {
+        let batch = batch.unwrap();
+        let column = |i: usize| {
+            batch
+                .column(i)
+                .as_any()
+                .downcast_ref::<BinaryArray>()
+                .unwrap()
+                .clone()
+        };
+        let (keys, payloads) = (column(0), column(1));
+        for i in 0..keys.len() {
+            rows.extend_from_slice(keys.value(i));
+            rows.extend_from_slice(payloads.value(i));
+        }
+    }
+    // Same records, in file order
+    assert!(rows == binary);
+
+    let _ = fs::remove_file("/tmp/test_generate_parquet.dat");
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn test_csv_output_matches_binary() {
+    let binary = generate("/tmp/test_generate_csv.dat", 500, Some(37));
+    for (encoding, row_size) in [("hex", 2 * 100 + 2), ("base64", 16 + 120 + 2)] {
+        let text = generate_with(
+            "/tmp/test_generate_csv.csv",
+            500,
+            Some(37),
+            &["--output-format", "csv", "--csv-encoding", encoding],
+        );
+        assert_eq!(text.len(), 500 * row_size);
+        let threaded = generate_with(
+            "/tmp/test_generate_csv.csv",
+            500,
+            Some(37),
+            &[
+                "--output-format",
+                "csv",
+                "--csv-encoding",
+                encoding,
+                "--threads",
+                "3",
+            ],
+        );
+        assert_eq!(text, threaded);
+
+        let encoding = match encoding {
+            "hex" => Encoding::Hex,
+            _ => Encoding::Base64,
+        };
+        let mut decoded = Vec::new();
+        for line in text.split_inclusive(|&c| c == b'\n') {
+            let (key, payload) = csv_file::decode_row(line, encoding).unwrap();
+            decoded.extend_from_slice(&key);
+            decoded.extend_from_slice(&payload);
+        }
+        assert_eq!(decoded, binary);
+    }
+
+    let _ = fs::remove_file("/tmp/test_generate_csv.dat");
+    let _ = fs::remove_file("/tmp/test_generate_csv.csv");
+}
+
+#[test]
+fn test_wide_schema_columns() {
+    let wide = ["--schema", "wide", "--payload-entropy", "low"];
+    let data = generate_with("/tmp/test_generate_wide_a.dat", 2000, Some(41), &wide);
+    let threaded = generate_with(
+        "/tmp/test_generate_wide_b.dat",
+        2000,
+        Some(41),
+        &[&wide[..], &["--threads", "3"]].concat(),
+    );
+    assert_eq!(data, threaded);
+
+    let mut ints = std::collections::HashSet::new();
+    for record in data.chunks_exact(100) {
+        let row = WideRow::decode(&record[10..]).unwrap();
+        assert!((0.0..1.0).contains(&row.c_double));
+        assert!(!row.c_text.is_empty());
+        ints.insert(row.c_int);
+        // Payload entropy only reshapes the blob after the typed columns
+        assert_eq!(row.payload.len(), 54);
+        assert!(row.payload.iter().all(|&b| b == row.payload[0]));
+    }
+    // c_int has few distinct values, so it ties often
+    assert!(ints.len() > 500 && ints.len() <= 1000);
+
+    // Keys are the same as without --schema
+    let plain = generate_with(
+        "/tmp/test_generate_wide_b.dat",
+        2000,
+        Some(41),
+        &["--payload-entropy", "low"],
+    );
+    for (w, p) in data.chunks_exact(100).zip(plain.chunks_exact(100)) {
+        assert_eq!(w[..10], p[..10]);
+    }
+
+    let status = Command::new(generate_gensort_binary())
+        .args(["--output", "/tmp/test_generate_wide_a.dat"])
+        .args([
+            "--num-records",
+            "10",
+            "--schema",
+            "wide",
+            "--payload-size",
+            "20",
+        ])
+        .status()
+        .unwrap();
+    assert!(!status.success());
+
+    for name in ["a", "b"] {
+        let _ = fs::remove_file(format!("/tmp/test_generate_wide_{}.dat", name));
+    }
+}
+
+#[test]
+fn test_heavy_hitters_take_their_fraction() {
+    let key_counts = |data: &[u8]| {
+        let mut counts = std::collections::HashMap::new();
+        for record in data.chunks_exact(100) {
+            *counts.entry(record[..10].to_vec()).or_insert(0usize) += 1;
+        }
+        let mut counts: Vec<usize> = counts.into_values().collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        counts
+    };
+
+    let heavy = ["--heavy-hitters", "3", "--heavy-fraction", "0.6"];
+    let data = generate_with("/tmp/test_generate_heavy.dat", 10_000, Some(47), &heavy);
+    let counts = key_counts(&data);
+    let top: usize = counts[..3].iter().sum();
+    assert!(
+        (5700..6300).contains(&top),
+        "top 3 keys hold {} records",
+        top
+    );
+    // The other records keep unique random keys
+    assert_eq!(counts[3], 1);
+
+    let threaded = generate_with(
+        "/tmp/test_generate_heavy.dat",
+        10_000,
+        Some(47),
+        &[&heavy[..], &["--threads", "4"]].concat(),
+    );
+    assert_eq!(data, threaded);
+
+    // On top of other distributions too
+    let data = generate_with(
+        "/tmp/test_generate_heavy.dat",
+        10_000,
+        Some(47),
+        &[&heavy[..], &["--distinct-keys", "100"]].concat(),
+    );
+    let counts = key_counts(&data);
+    assert_eq!(counts.len(), 103);
+    assert!(counts[..3].iter().all(|&c| c > 1800));
+
+    for (keys, fraction) in [("0", "0.5"), ("2", "1.5"), ("2", "0")] {
+        let output = Command::new(generate_gensort_binary())
+            .args(["--output", "/tmp/test_generate_heavy.dat"])
+            .args(["--num-records", "10", "--heavy-hitters", keys])
+            .args(["--heavy-fraction", fraction])
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--heavy-"));
+    }
+
+    let _ = fs::remove_file("/tmp/test_generate_heavy.dat");
+}
+
+#[test]
+fn test_fifo_output_streams_records_in_order() {
+    use es_duck::records::{self, FixedLayout, FixedRecords, InputFormat, IoOptions};
+
+    // Three 16MB chunks from two threads, which the writer has to put in order
+    let expected = generate("/tmp/test_generate_fifo.dat", 400_001, Some(4));
+    let fifo = "/tmp/test_generate_fifo.pipe";
+    let _ = fs::remove_file(fifo);
+    let status = Command::new("mkfifo").arg(fifo).status().unwrap();
+    assert!(status.success());
+
+    let mut child = Command::new(generate_gensort_binary())
+        .args(["--output", fifo, "--num-records", "400001", "--seed", "4"])
+        .args(["--threads", "2"])
+        .spawn()
+        .expect("Failed to run generate-gensort");
+
+    // Read the way the loaders do: as a stream of unknown length
+    let range = records::select(
+        InputFormat::Gensort,
+        FixedLayout::GENSORT,
+        fifo.as_ref(),
+        0,
+        None,
+    )
+    .unwrap();
+    assert_eq!(range, records::STREAM);
+    let mut reader = FixedRecords::open(
+        IoOptions::default(),
+        fifo.as_ref(),
+        FixedLayout::GENSORT,
+        range.start / 100..range.end / 100,
+        1 << 20,
+    )
+    .unwrap();
+    let mut streamed = Vec::new();
+    loop {
+        let records = reader.next_records(7_000).unwrap();
+        if records.is_empty() {
+            break;
+        }
+        streamed.extend_from_slice(records);
+    }
+    assert!(child.wait().unwrap().success());
+    assert!(
+        streamed == expected,
+        "streamed records differ from the file"
+    );
+
+    let _ = fs::remove_file(fifo);
+    let _ = fs::remove_file("/tmp/test_generate_fifo.dat");
+}
@@ -0,0 +1,135 @@
+#![cfg(feature = "util-rand")]
+
+use es_duck::records::{InputFormat, Record, RecordReader};
+use std::fs;
+use std::process::Command;
+
+fn generate_kvbin_binary() -> String {
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    format!("target/{}/generate-kvbin", profile)
+}
+
+fn generate(output: &str, args: &[&str]) -> Vec<u8> {
+    let status = Command::new(generate_kvbin_binary())
+        .args(["--output", output])
+        .args(args)
+        .status()
+        .expect("Failed to run generate-kvbin");
+    assert!(status.success());
+    fs::read(output).unwrap()
+}
+
+fn records(data: &[u8]) -> Vec<Record> {
+    let mut reader = RecordReader::new(InputFormat::Kvbin, data);
+    let mut records = Vec::new();
+    while let Some(record) = reader.next_record().unwrap() {
+        records.push(record);
+    }
+    records
+}
+
+#[test]
+fn test_length_distributions() {
+    let output = "/tmp/test_generate_kvbin_lengths.kvbin";
+
+    let data = generate(output, &["--num-records", "500", "--seed", "1"]);
+    let fixed = records(&data);
+    assert_eq!(fixed.len(), 500);
+    assert!(fixed.iter().all(|(k, v)| k.len() == 10 && v.len() == 90));
+
+    let data = generate(
+        output,
+        &[
+            "--num-records",
+            "2000",
+            "--key-len",
+            "uniform:4:20",
+            "--value-len",
+            "lognormal:200:0.5",
+            "--seed",
+            "1",
+        ],
+    );
+    let varied = records(&data);
+    assert_eq!(varied.len(), 2000);
+    assert!(varied.iter().all(|(k, _)| (4..=20).contains(&k.len())));
+    assert!(varied.iter().any(|(k, _)| k.len() == 4));
+    assert!(varied.iter().any(|(k, _)| k.len() == 20));
+    let mean = varied.iter().map(|(_, v)| v.len()).sum::<usize>() as f64 / 2000.0;
+    assert!((180.0..220.0).contains(&mean), "mean value length {}", mean);
+
+    // Same seed, same file
+    let again = generate(
+        output,
+        &[
+            "--num-records",
+            "2000",
+            "--key-len",
+            "uniform:4:20",
+            "--value-len",
+            "lognormal:200:0.5",
+            "--seed",
+            "1",
+        ],
+    );
+    assert_eq!(data, again);
+
+    for bad in ["uniform:9:3", "lognormal:100", "normal:1:2", "abc"] {
+        let result = Command::new(generate_kvbin_binary())
+            .args(["--output", output, "--num-records", "1", "--key-len", bad])
+            .output()
+            .unwrap();
+        assert!(!result.status.success(), "--key-len {} was accepted", bad);
+    }
+
+    let _ = fs::remove_file(output);
+}
+
+#[test]
+fn test_target_bytes_and_index() {
+    let output = "/tmp/test_generate_kvbin_index.kvbin";
+    let data = generate(
+        output,
+        &[
+            "--target-bytes",
+            "1MB",
+            "--value-len",
+            "uniform:50:150",
+            "--index",
+            "--index-interval",
+            "1000",
+        ],
+    );
+    // Stops at the first record boundary at or past the target
+    assert!(data.len() >= 1 << 20);
+    assert!(data.len() < (1 << 20) + 4 + 10 + 4 + 150);
+
+    let count = records(&data).len();
+    let index: Vec<u64> = fs::read(format!("{}.idx", output))
+        .unwrap()
+        .chunks_exact(8)
+        .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    assert_eq!(index.len(), (count - 1) / 1000);
+
+    // Every entry is the offset of record 1000 * (i + 1)
+    let mut offset = 0usize;
+    let mut boundaries = Vec::new();
+    for i in 0..count {
+        if i > 0 && i % 1000 == 0 {
+            boundaries.push(offset as u64);
+        }
+        let key_len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        offset += 4 + key_len;
+        let value_len = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize;
+        offset += 4 + value_len;
+    }
+    assert_eq!(index, boundaries);
+
+    let _ = fs::remove_file(output);
+    let _ = fs::remove_file(format!("{}.idx", output));
+}
@@ -0,0 +1,60 @@
+use es_duck::input_files::{self, InputFiles, Unit};
+use es_duck::records::{FixedLayout, InputFormat};
+use std::fs;
+use std::path::PathBuf;
+
+fn gensort_file(name: &str, records: usize) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, vec![b'r'; records * 100]).unwrap();
+    path
+}
+
+#[test]
+fn test_input_files_hand_out_every_unit_once() {
+    let paths = vec![
+        gensort_file("input_files_a.gensort", 50),
+        gensort_file("input_files_b.gensort", 7),
+        gensort_file("input_files_c.gensort", 23),
+    ];
+    // Units of 10 records: 5 + 1 + 3 of them
+    let files =
+        InputFiles::new(InputFormat::Gensort, FixedLayout::GENSORT, &paths, 2, 1000).unwrap();
+    assert_eq!(files.bytes(), 80 * 100);
+
+    // Thread 0 alone drains every queue, stealing thread 1's file b
+    let mut units: Vec<Unit> = std::iter::from_fn(|| files.next(0)).collect();
+    assert_eq!(units.len(), 9);
+    assert!(files.next(1).is_none());
+    assert_eq!(files.path(&units[0]), paths[0]);
+
+    let mut covered = [0u64; 3];
+    units.sort_by_key(|unit| (unit.file, unit.range.start));
+    for unit in &units {
+        // Ranges of each file follow on from each other
+        assert_eq!(unit.range.start, covered[unit.file]);
+        covered[unit.file] = unit.range.end;
+        files.finish(unit, unit.range.end - unit.range.start);
+    }
+    assert_eq!(covered, [50, 7, 23]);
+    for path in paths {
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn test_input_files_check() {
+    let paths = vec![
+        gensort_file("input_files_check_a.gensort", 1),
+        gensort_file("input_files_check_b.gensort", 1),
+    ];
+    assert!(input_files::check(&paths, 0, None).is_ok());
+    assert!(input_files::check(&paths, 1, None).is_err());
+    assert!(input_files::check(&paths, 0, Some(1)).is_err());
+
+    let device = vec![paths[0].clone(), PathBuf::from("/dev/null")];
+    let err = input_files::check(&device, 0, None).unwrap_err();
+    assert!(err.contains("stream"));
+    for path in paths {
+        fs::remove_file(path).unwrap();
+    }
+}
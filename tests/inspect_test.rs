@@ -0,0 +1,127 @@
+use es_duck::inspect;
+use es_duck::records::{FixedLayout, InputFormat};
+use std::fs;
+
+/// gensort records whose keys count down from `count - 1`
+fn gensort(count: u8) -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in (0..count).rev() {
+        data.extend_from_slice(&[i; 10]);
+        data.extend_from_slice(&[b'p'; 90]);
+    }
+    data
+}
+
+fn kvbin_record(data: &mut Vec<u8>, key: &[u8], value: &[u8]) {
+    data.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    data.extend_from_slice(key);
+    data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    data.extend_from_slice(value);
+}
+
+#[test]
+fn test_inspect_gensort() {
+    let path = "/tmp/test_inspect.gensort";
+    fs::write(path, gensort(5)).unwrap();
+    let run = |skip, limit| {
+        inspect::inspect(
+            InputFormat::Gensort,
+            FixedLayout::GENSORT,
+            path.as_ref(),
+            skip,
+            limit,
+        )
+        .unwrap()
+    };
+    let summary = run(0, None);
+    assert_eq!((summary.records, summary.bytes), (5, 500));
+    assert_eq!(summary.min_key, Some(vec![0u8; 10]));
+    assert_eq!(summary.max_key, Some(vec![4u8; 10]));
+    assert!(summary.malformed.is_empty());
+    assert!(summary.report().is_ok());
+
+    let summary = run(1, Some(2));
+    assert_eq!((summary.records, summary.bytes), (2, 200));
+    assert_eq!(summary.min_key, Some(vec![2u8; 10]));
+    assert_eq!(summary.max_key, Some(vec![3u8; 10]));
+
+    // A cut-off last record is reported where it starts, once the selection reaches it
+    let mut data = gensort(5);
+    data.extend_from_slice(&[9u8; 30]);
+    fs::write(path, data).unwrap();
+    let summary = run(0, None);
+    assert_eq!(summary.records, 5);
+    assert_eq!(summary.malformed.len(), 1);
+    assert_eq!(summary.malformed[0].0, 500);
+    assert!(summary.malformed[0].1.contains("30 bytes"));
+    assert!(summary.report().is_err());
+    assert!(run(0, Some(3)).malformed.is_empty());
+
+    let summary = run(9, None);
+    assert_eq!(summary.records, 0);
+    assert_eq!((summary.min_key, summary.max_key), (None, None));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_inspect_kvbin_framing() {
+    let path = "/tmp/test_inspect.kvbin";
+    let mut data = Vec::new();
+    kvbin_record(&mut data, b"mm", b"value");
+    kvbin_record(&mut data, b"a", b"");
+    kvbin_record(&mut data, b"zzz", b"v");
+    fs::write(path, &data).unwrap();
+    let run = |skip, limit| {
+        inspect::inspect(
+            InputFormat::Kvbin,
+            FixedLayout::GENSORT,
+            path.as_ref(),
+            skip,
+            limit,
+        )
+        .unwrap()
+    };
+    let summary = run(0, None);
+    assert_eq!(summary.records, 3);
+    assert_eq!(summary.bytes, data.len() as u64);
+    assert_eq!(summary.min_key, Some(b"a".to_vec()));
+    assert_eq!(summary.max_key, Some(b"zzz".to_vec()));
+    assert!(summary.malformed.is_empty());
+
+    let summary = run(1, Some(1));
+    assert_eq!((summary.records, summary.bytes), (1, 9));
+    assert_eq!(summary.max_key, Some(b"a".to_vec()));
+
+    // A key length running past the end of the input: the records before it
+    // still count, and the broken one is located
+    let good = data.len() as u64;
+    data.extend_from_slice(&1_000u32.to_le_bytes());
+    data.extend_from_slice(b"short");
+    fs::write(path, &data).unwrap();
+    let summary = run(0, None);
+    assert_eq!(summary.records, 3);
+    assert_eq!(summary.malformed.len(), 1);
+    assert_eq!(summary.malformed[0].0, good);
+    assert!(summary.malformed[0].1.contains("record 3"));
+    assert!(summary.report().is_err());
+
+    // Stopping before the broken record leaves nothing to report
+    assert!(run(0, Some(3)).malformed.is_empty());
+
+    // A value length no record has is reported without reading (or
+    // allocating) that much
+    data.truncate(good as usize);
+    data.extend_from_slice(&1u32.to_le_bytes());
+    data.push(b'k');
+    data.extend_from_slice(&u32::MAX.to_le_bytes());
+    fs::write(path, &data).unwrap();
+    let summary = run(0, None);
+    assert_eq!(summary.records, 3);
+    assert_eq!(summary.malformed.len(), 1);
+    assert!(
+        summary.malformed[0]
+            .1
+            .contains("value length 4294967295 is implausible")
+    );
+    fs::remove_file(path).unwrap();
+}
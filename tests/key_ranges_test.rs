@@ -0,0 +1,51 @@
+use es_duck::key_ranges::{self, SAMPLE_ROWS};
+
+#[test]
+fn test_sample_percent() {
+    assert_eq!(key_ranges::sample_percent(0), 100.0);
+    assert_eq!(key_ranges::sample_percent(SAMPLE_ROWS / 2), 100.0);
+    assert_eq!(key_ranges::sample_percent(SAMPLE_ROWS * 100), 1.0);
+}
+
+#[test]
+fn test_sample_query() {
+    assert_eq!(
+        key_ranges::sample_query("t", "sort_key", None, 4, SAMPLE_ROWS * 10),
+        "SELECT (percentile_disc(ARRAY[0.250000, 0.500000, 0.750000]::float8[]) \
+         WITHIN GROUP (ORDER BY sort_key))::text[] FROM t TABLESAMPLE SYSTEM (10.000000)"
+    );
+    assert_eq!(
+        key_ranges::sample_query("t", "k", Some("k > '\\x00'"), 2, 10),
+        "SELECT (percentile_disc(ARRAY[0.500000]::float8[]) WITHIN GROUP (ORDER BY k))::text[] \
+         FROM t TABLESAMPLE SYSTEM (100.000000) WHERE k > '\\x00'"
+    );
+}
+
+#[test]
+fn test_range_predicate() {
+    // A bytea key's boundaries as PostgreSQL prints them
+    let boundaries = vec!["\\x10".to_string(), "\\x80ff".to_string()];
+    assert_eq!(
+        key_ranges::range_predicate("k", &boundaries, 0),
+        "k < '\\x10'"
+    );
+    assert_eq!(
+        key_ranges::range_predicate("k", &boundaries, 1),
+        "k >= '\\x10' AND k < '\\x80ff'"
+    );
+    assert_eq!(
+        key_ranges::range_predicate("k", &boundaries, 2),
+        "k >= '\\x80ff'"
+    );
+
+    // One stream takes every row; with an empty sample the first range does
+    assert_eq!(key_ranges::range_predicate("k", &[], 0), "TRUE");
+    assert_eq!(key_ranges::range_predicate("k", &[], 1), "FALSE");
+
+    // Text and integer keys compare in their own type
+    let boundaries = vec!["it's".to_string(), "-42".to_string()];
+    assert_eq!(
+        key_ranges::range_predicate("k", &boundaries, 1),
+        "k >= 'it''s' AND k < '-42'"
+    );
+}
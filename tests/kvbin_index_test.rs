@@ -0,0 +1,183 @@
+use es_duck::kvbin_index::{self, Builder, Spacing};
+use std::io::Cursor;
+use std::path::Path;
+
+/// kvbin records with value lengths 0, 1, 2, ...; returns the data and each record's offset
+fn kvbin(count: usize) -> (Vec<u8>, Vec<u64>) {
+    let mut data = Vec::new();
+    let mut offsets = Vec::new();
+    for i in 0..count {
+        offsets.push(data.len() as u64);
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&(i as u32).to_be_bytes());
+        data.extend_from_slice(&(i as u32).to_le_bytes());
+        data.extend(std::iter::repeat_n(b'v', i));
+    }
+    (data, offsets)
+}
+
+#[test]
+fn test_scan_every_n_records() {
+    let (data, offsets) = kvbin(25);
+    let index = kvbin_index::scan(Cursor::new(&data), Spacing::Records(10)).unwrap();
+    assert_eq!(index.records, 25);
+    assert_eq!(index.bytes, data.len() as u64);
+    assert_eq!(index.offsets, vec![offsets[10], offsets[20]]);
+
+    // Exactly on a multiple: no entry pointing at the end of the file
+    let (data, offsets) = kvbin(20);
+    let index = kvbin_index::scan(Cursor::new(&data), Spacing::Records(10)).unwrap();
+    assert_eq!(index.offsets, vec![offsets[10]]);
+
+    let index = kvbin_index::scan(Cursor::new(&[][..]), Spacing::Records(10)).unwrap();
+    assert_eq!(index, Default::default());
+}
+
+#[test]
+fn test_scan_every_n_bytes() {
+    let (data, offsets) = kvbin(40);
+    let index = kvbin_index::scan(Cursor::new(&data), Spacing::Bytes(200)).unwrap();
+
+    // Each entry is the first record starting at or after a multiple of 200
+    let mut expected = Vec::new();
+    let mut mark = 200;
+    for &offset in &offsets[1..] {
+        if offset >= mark {
+            expected.push(offset);
+            mark = (offset / 200 + 1) * 200;
+        }
+    }
+    assert!(expected.len() > 3);
+    assert_eq!(index.offsets, expected);
+}
+
+#[test]
+fn test_scan_rejects_truncated_input() {
+    let (data, offsets) = kvbin(5);
+    for cut in [offsets[4] + 2, offsets[4] + 6, data.len() as u64 - 1] {
+        let err =
+            kvbin_index::scan(Cursor::new(&data[..cut as usize]), Spacing::Records(1)).unwrap_err();
+        assert!(err.to_string().contains(&format!("offset {}", offsets[4])));
+    }
+}
+
+#[test]
+fn test_builder_matches_scan() {
+    let (data, offsets) = kvbin(30);
+    let mut builder = Builder::new(Spacing::Records(7));
+    for pair in offsets.windows(2) {
+        builder.push(pair[1] - pair[0]);
+    }
+    builder.push(data.len() as u64 - offsets[29]);
+    assert_eq!(
+        builder.finish(),
+        kvbin_index::scan(Cursor::new(&data), Spacing::Records(7)).unwrap()
+    );
+}
+
+#[test]
+fn test_index_file_roundtrip() {
+    let path = kvbin_index::path_for(Path::new("/tmp/test_kvbin_index.kvbin"));
+    assert_eq!(path, Path::new("/tmp/test_kvbin_index.kvbin.idx"));
+
+    kvbin_index::write(&path, &[100, 2000, 30000]).unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), 24);
+    assert_eq!(kvbin_index::read(&path).unwrap(), vec![100, 2000, 30000]);
+
+    std::fs::write(&path, [0u8; 12]).unwrap();
+    assert!(kvbin_index::read(&path).is_err());
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_find_boundaries_without_index() {
+    let (data, offsets) = kvbin(2000);
+    let boundaries = kvbin_index::find_boundaries(Cursor::new(&data), 8).unwrap();
+    assert_eq!(boundaries.len(), 7);
+    assert!(boundaries.windows(2).all(|w| w[0] < w[1]));
+    for (part, boundary) in (1..).zip(&boundaries) {
+        assert!(
+            offsets.contains(boundary),
+            "{} is not a record start",
+            boundary
+        );
+        // The first record start at or after the probe
+        let probe = data.len() as u64 * part / 8;
+        assert_eq!(*boundary, *offsets.iter().find(|&&o| o >= probe).unwrap());
+    }
+    let records = kvbin_index::verify_boundaries(Cursor::new(&data), &boundaries).unwrap();
+    assert_eq!(records, 2000);
+
+    // More parts than bytes: probes share candidates, but no offset repeats
+    // and the start of the input is never a boundary
+    let (data, _) = kvbin(3);
+    let boundaries = kvbin_index::find_boundaries(Cursor::new(&data), 100).unwrap();
+    assert!(!boundaries.is_empty());
+    assert!(boundaries.windows(2).all(|w| w[0] < w[1]));
+    assert!(boundaries.iter().all(|&b| b > 0 && b < data.len() as u64));
+
+    assert!(
+        kvbin_index::find_boundaries(Cursor::new(&[][..]), 4)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_scan_boundaries_of_a_range() {
+    let (data, offsets) = kvbin(2000);
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("scan_boundaries.kvbin");
+    std::fs::write(&path, &data).unwrap();
+
+    // Records 100..1900, as --skip/--limit select them
+    let range = offsets[100]..offsets[1900];
+    let boundaries = kvbin_index::scan_boundaries(&path, &range, 1, true).unwrap();
+    assert_eq!(boundaries.first(), Some(&range.start));
+    assert_eq!(boundaries.last(), Some(&range.end));
+    assert!(boundaries.len() > 2);
+    assert!(boundaries.windows(2).all(|w| w[0] < w[1]));
+    assert!(boundaries.iter().all(|b| offsets.contains(b)));
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_verify_boundaries_catches_false_starts() {
+    // Zeros look like a chain of empty records, so scanning finds a "boundary"
+    // inside the value
+    let mut data = Vec::new();
+    data.extend_from_slice(&1u32.to_le_bytes());
+    data.push(b'k');
+    data.extend_from_slice(&4096u32.to_le_bytes());
+    data.extend(std::iter::repeat_n(0u8, 4096));
+    let boundaries = kvbin_index::find_boundaries(Cursor::new(&data), 2).unwrap();
+    assert_eq!(boundaries.len(), 1);
+    let err = kvbin_index::verify_boundaries(Cursor::new(&data), &boundaries).unwrap_err();
+    assert!(err.to_string().contains("inside a record"), "got: {}", err);
+
+    let (data, offsets) = kvbin(10);
+    assert!(kvbin_index::verify_boundaries(Cursor::new(&data), &[offsets[3] + 1]).is_err());
+    assert!(kvbin_index::verify_boundaries(Cursor::new(&data), &[data.len() as u64 + 8]).is_err());
+    // The end of the input is a valid boundary
+    assert_eq!(
+        kvbin_index::verify_boundaries(Cursor::new(&data), &[offsets[3], data.len() as u64])
+            .unwrap(),
+        10
+    );
+}
+
+#[test]
+fn test_record_range() {
+    let (data, offsets) = kvbin(10);
+    let end = data.len() as u64;
+    let range = |skip, limit| kvbin_index::record_range(Cursor::new(&data), skip, limit).unwrap();
+    assert_eq!(range(0, None), 0..end);
+    assert_eq!(range(3, Some(4)), offsets[3]..offsets[7]);
+    assert_eq!(range(7, Some(100)), offsets[7]..end);
+    assert_eq!(range(2, Some(0)), offsets[2]..offsets[2]);
+    // Skipping past the end selects nothing
+    assert_eq!(range(20, None), end..end);
+
+    let mut truncated = data.clone();
+    truncated.truncate(data.len() - 1);
+    assert!(kvbin_index::record_range(Cursor::new(&truncated), 0, None).is_err());
+}
@@ -0,0 +1,27 @@
+use es_duck::latency;
+use std::time::Duration;
+
+#[test]
+fn test_latency_percentiles() {
+    assert_eq!(latency::summarize(&[]), None);
+
+    let samples: Vec<Duration> = (1..=100).rev().map(Duration::from_millis).collect();
+    let summary = latency::summarize(&samples).unwrap();
+    assert_eq!(summary.count, 100);
+    assert_eq!(summary.p50, Duration::from_millis(50));
+    assert_eq!(summary.p95, Duration::from_millis(95));
+    assert_eq!(summary.p99, Duration::from_millis(99));
+    assert_eq!(summary.max, Duration::from_millis(100));
+
+    // A single stall dominates the tail but not the median
+    let mut samples = vec![Duration::from_millis(2); 99];
+    samples.push(Duration::from_secs(3));
+    let summary = latency::summarize(&samples).unwrap();
+    assert_eq!(summary.p50, Duration::from_millis(2));
+    assert_eq!(summary.p99, Duration::from_millis(2));
+    assert_eq!(summary.max, Duration::from_secs(3));
+
+    let single = latency::summarize(&[Duration::from_millis(7)]).unwrap();
+    assert_eq!(single.p50, Duration::from_millis(7));
+    assert_eq!(single.p99, Duration::from_millis(7));
+}
@@ -0,0 +1,119 @@
+use es_duck::loader::{self, IfExists, Loader, LoaderConfig, Registry};
+use es_duck::records::{InputFormat, Record, RecordReader};
+use es_duck::schema::{ColumnNames, Schema};
+use std::error::Error;
+
+/// Keeps rows in memory; `lose` drops that many rows to simulate a lossy backend
+struct MemoryLoader {
+    created: bool,
+    rows: Vec<Record>,
+    batches: usize,
+    lose: usize,
+}
+
+impl Loader for MemoryLoader {
+    fn create_schema(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.created = true;
+        Ok(())
+    }
+
+    fn ingest(&mut self, batch: &[Record]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        assert!(self.created, "ingest before create_schema");
+        self.rows.extend_from_slice(batch);
+        self.batches += 1;
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        Ok((self.rows.len() - self.lose) as u64)
+    }
+}
+
+fn memory_loader(lose: usize) -> MemoryLoader {
+    MemoryLoader {
+        created: false,
+        rows: Vec::new(),
+        batches: 0,
+        lose,
+    }
+}
+
+fn gensort_input(records: u8) -> Vec<u8> {
+    let mut data = Vec::new();
+    for i in 0..records {
+        data.extend_from_slice(&[i; 10]);
+        data.extend_from_slice(&[b'P'; 90]);
+    }
+    data
+}
+
+fn config() -> LoaderConfig {
+    LoaderConfig {
+        connection: "memory".to_string(),
+        table: "bench_data".to_string(),
+        database: "default".to_string(),
+        schema: Schema::KeyPayload,
+        columns: ColumnNames::default(),
+        extra_columns: Vec::new(),
+        if_exists: IfExists::Fail,
+    }
+}
+
+#[test]
+fn test_run_loads_all_batches() {
+    let data = gensort_input(5);
+    let mut reader = RecordReader::new(InputFormat::Gensort, data.as_slice());
+    let mut backend = memory_loader(0);
+
+    let rows = loader::run(&mut backend, &mut reader, 2).unwrap();
+    assert_eq!(rows, 5);
+    assert_eq!(backend.batches, 3);
+    assert_eq!(backend.rows[4].0, vec![4u8; 10]);
+}
+
+#[test]
+fn test_run_detects_lost_rows() {
+    let data = gensort_input(3);
+    let mut reader = RecordReader::new(InputFormat::Gensort, data.as_slice());
+    let mut backend = memory_loader(1);
+
+    let err = loader::run(&mut backend, &mut reader, 10).unwrap_err();
+    assert!(err.to_string().contains("only has 2 rows"), "got: {}", err);
+}
+
+#[test]
+fn test_registry_dispatch() {
+    let mut registry = Registry::new();
+    assert!(registry.create("memory", &config()).is_err());
+
+    registry.register("memory", |_| Ok(Box::new(memory_loader(0))));
+    assert_eq!(registry.names(), vec!["memory"]);
+
+    let mut backend = registry.create("memory", &config()).unwrap();
+    let data = gensort_input(2);
+    let mut reader = RecordReader::new(InputFormat::Gensort, data.as_slice());
+    assert_eq!(loader::run(backend.as_mut(), &mut reader, 10).unwrap(), 2);
+
+    let err = registry.create("nope", &config()).err().unwrap();
+    assert!(
+        err.to_string().contains("available: memory"),
+        "got: {}",
+        err
+    );
+}
+
+#[test]
+fn test_if_exists_policy() {
+    for policy in [IfExists::Fail, IfExists::Replace, IfExists::Append] {
+        assert!(!policy.must_drop("t", false).unwrap());
+    }
+    assert!(IfExists::Replace.must_drop("t", true).unwrap());
+    assert!(!IfExists::Append.must_drop("t", true).unwrap());
+
+    let err = IfExists::Fail.must_drop("bench_data", true).unwrap_err();
+    assert!(
+        err.to_string().contains("bench_data already exists"),
+        "got: {}",
+        err
+    );
+}
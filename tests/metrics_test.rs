@@ -0,0 +1,42 @@
+use es_duck::metrics::{self, Phase};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+fn scrape(port: u16) -> String {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).expect("Failed to connect");
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+// The metrics are process-wide, so everything is checked in a single test
+#[test]
+fn test_metrics_endpoint() {
+    let port = 19187;
+    metrics::serve(port).expect("Failed to start metrics server");
+
+    metrics::set_phase(Phase::Load);
+    metrics::add_rows_loaded(1000);
+    metrics::add_bytes_uploaded(100_000);
+    metrics::set_spill_bytes(4096);
+
+    let response = scrape(port);
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "got: {}", response);
+    assert!(response.contains("es_duck_phase{phase=\"load\"} 1"));
+    assert!(response.contains("es_duck_phase{phase=\"sort\"} 0"));
+    assert!(response.contains("es_duck_rows_loaded_total 1000"));
+    assert!(response.contains("es_duck_bytes_uploaded_total 100000"));
+    assert!(response.contains("es_duck_spill_bytes 4096"));
+
+    // Sort progress never moves backwards
+    metrics::set_phase(Phase::Sort);
+    metrics::set_rows_sorted(500);
+    metrics::set_rows_sorted(200);
+
+    let response = scrape(port);
+    assert!(response.contains("es_duck_phase{phase=\"sort\"} 1"));
+    assert!(response.contains("es_duck_rows_sorted_total 500"));
+}
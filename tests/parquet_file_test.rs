@@ -0,0 +1,56 @@
+#![cfg(feature = "util-parquet")]
+
+use arrow_array::{Array, BinaryArray};
+use es_duck::parquet_file::RecordWriter;
+use es_duck::records::FixedLayout;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::fs::{self, File};
+use std::path::Path;
+
+#[test]
+fn test_records_round_trip_in_row_groups() {
+    let path = Path::new("/tmp/test_parquet_file_round_trip.parquet");
+    let layout = FixedLayout {
+        key_size: 4,
+        payload_size: 6,
+    };
+    let data: Vec<u8> = (0..100u8).collect();
+
+    let mut writer = RecordWriter::create(path, layout, 4).unwrap();
+    writer.write(&data[..30]).unwrap();
+    writer.write(&data[30..]).unwrap();
+    // Partial records are rejected
+    assert!(writer.write(&data[..15]).is_err());
+    assert_eq!(writer.finish().unwrap(), 10);
+
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap();
+    assert_eq!(builder.metadata().num_row_groups(), 3);
+    let fields: Vec<&str> = builder
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().as_str())
+        .collect();
+    assert_eq!(fields, ["sort_key", "payload"]);
+
+    let mut rows = Vec::new();
+    for batch in builder.build().unwrap() {
+        let batch = batch.unwrap();
+        let column = |i: usize| {
+            batch
+                .column(i)
+                .as_any()
+                .downcast_ref::<BinaryArray>()
+                .unwrap()
+                .clone()
+        };
+        let (keys, payloads) = (column(0), column(1));
+        for i in 0..keys.len() {
+            rows.extend_from_slice(keys.value(i));
+            rows.extend_from_slice(payloads.value(i));
+        }
+    }
+    assert_eq!(rows, data);
+
+    let _ = fs::remove_file(path);
+}
@@ -0,0 +1,111 @@
+use es_duck::pg_plan::{self, PlanStats};
+use std::time::Duration;
+
+/// A parallel external sort, trimmed to the fields `parse` reads
+const PLAN: &str = r#"[
+  {
+    "Plan": {
+      "Node Type": "Gather Merge",
+      "Workers Planned": 4,
+      "Workers Launched": 2,
+      "Shared Hit Blocks": 10,
+      "Shared Read Blocks": 1200,
+      "Temp Read Blocks": 3000,
+      "Temp Written Blocks": 3100,
+      "Plans": [
+        {
+          "Node Type": "Sort",
+          "Sort Key": ["sort_key"],
+          "Sort Method": "external merge",
+          "Sort Space Used": 8000,
+          "Sort Space Type": "Disk",
+          "Workers": [
+            {
+              "Worker Number": 0,
+              "Sort Method": "external merge",
+              "Sort Space Used": 7000,
+              "Sort Space Type": "Disk"
+            },
+            {
+              "Worker Number": 1,
+              "Sort Method": "quicksort",
+              "Sort Space Used": 900,
+              "Sort Space Type": "Memory"
+            }
+          ],
+          "Plans": [
+            {"Node Type": "Seq Scan", "Parallel Aware": true}
+          ]
+        }
+      ]
+    },
+    "Planning Time": 0.1,
+    "Execution Time": 2500.0
+  }
+]"#;
+
+#[test]
+fn test_parse_parallel_external_sort() {
+    let stats = pg_plan::parse(PLAN).unwrap();
+    assert_eq!(
+        stats,
+        PlanStats {
+            sort_methods: vec!["external merge".to_string(), "quicksort".to_string()],
+            disk_kb: 15_000,
+            memory_kb: 900,
+            workers_planned: 4,
+            workers_launched: 2,
+            shared_hit_blocks: 10,
+            shared_read_blocks: 1200,
+            temp_read_blocks: 3000,
+            temp_written_blocks: 3100,
+        }
+    );
+    assert_eq!(
+        stats.report(),
+        "SORT_METHOD: external merge, quicksort\nSORT_DISK_KB: 15000\nSORT_MEMORY_KB: 900\n\
+         WORKERS_PLANNED: 4\nWORKERS_LAUNCHED: 2\n\
+         SHARED_HIT_BLOCKS: 10\nSHARED_READ_BLOCKS: 1200\n\
+         TEMP_READ_BLOCKS: 3000\nTEMP_WRITTEN_BLOCKS: 3100"
+    );
+
+    assert!(pg_plan::parse("Sort  (cost=0.00..1.00)").is_err());
+    assert!(pg_plan::parse("[{}]").is_err());
+}
+
+#[test]
+fn test_merge_per_shard_plans() {
+    let in_memory = r#"[{"Plan": {"Node Type": "Sort", "Sort Method": "quicksort",
+        "Sort Space Used": 500, "Sort Space Type": "Memory", "Shared Hit Blocks": 5}}]"#;
+    let mut stats = pg_plan::parse(in_memory).unwrap();
+    assert_eq!(
+        stats.report().lines().next(),
+        Some("SORT_METHOD: quicksort")
+    );
+    stats.merge(pg_plan::parse(PLAN).unwrap());
+
+    assert_eq!(stats.sort_methods, ["quicksort", "external merge"]);
+    assert_eq!(stats.disk_kb, 15_000);
+    assert_eq!(stats.memory_kb, 900);
+    assert_eq!(stats.shared_hit_blocks, 15);
+    assert_eq!(
+        PlanStats::default().report().lines().next(),
+        Some("SORT_METHOD: none")
+    );
+}
+
+#[test]
+fn test_planning_time() {
+    assert_eq!(
+        pg_plan::planning_time(PLAN),
+        Some(Duration::from_micros(100))
+    );
+    // A text EXPLAIN with SUMMARY
+    let text =
+        "Sort  (cost=0.00..1.00 rows=1 width=8)\n  Sort Key: sort_key\nPlanning Time: 2.500 ms";
+    assert_eq!(
+        pg_plan::planning_time(text),
+        Some(Duration::from_micros(2500))
+    );
+    assert_eq!(pg_plan::planning_time("Sort  (cost=0.00..1.00)"), None);
+}
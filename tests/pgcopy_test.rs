@@ -0,0 +1,85 @@
+use es_duck::pgcopy::{self, Reader, Writer};
+
+#[test]
+fn test_pgcopy_round_trip() {
+    let mut writer = Writer::new(Vec::new()).unwrap();
+    writer.write_row(&[b"key1", b"value1"]).unwrap();
+    writer.write_row(&[b"", b"empty key"]).unwrap();
+    let data = writer.finish().unwrap();
+
+    // Signature, flags, extension length, then the first tuple
+    assert_eq!(&data[..11], pgcopy::SIGNATURE);
+    assert_eq!(&data[11..19], &[0u8; 8]);
+    assert_eq!(&data[19..21], &2i16.to_be_bytes());
+    assert_eq!(&data[data.len() - 2..], &(-1i16).to_be_bytes());
+
+    let mut reader = Reader::new(data.as_slice()).unwrap();
+    assert_eq!(
+        reader.next_row().unwrap(),
+        Some(vec![b"key1".to_vec(), b"value1".to_vec()])
+    );
+    let mut fields = Vec::new();
+    assert!(reader.read_row(&mut fields).unwrap());
+    assert_eq!(fields, vec![b"".to_vec(), b"empty key".to_vec()]);
+    assert_eq!(reader.next_row().unwrap(), None);
+    // Stays at the end after the trailer
+    assert_eq!(reader.next_row().unwrap(), None);
+}
+
+#[test]
+fn test_pgcopy_skips_header_extension() {
+    let mut data = pgcopy::SIGNATURE.to_vec();
+    data.extend_from_slice(&0i32.to_be_bytes());
+    data.extend_from_slice(&3i32.to_be_bytes());
+    data.extend_from_slice(b"ext");
+    data.extend_from_slice(&1i16.to_be_bytes());
+    data.extend_from_slice(&2i32.to_be_bytes());
+    data.extend_from_slice(b"ok");
+    data.extend_from_slice(&(-1i16).to_be_bytes());
+
+    let mut reader = Reader::new(data.as_slice()).unwrap();
+    assert_eq!(reader.next_row().unwrap(), Some(vec![b"ok".to_vec()]));
+    assert_eq!(reader.next_row().unwrap(), None);
+}
+
+#[test]
+fn test_pgcopy_drained_chunks_concatenate() {
+    let mut writer = Writer::new(Vec::new()).unwrap();
+    let mut stream = Vec::new();
+    for i in 0..10u8 {
+        writer.write_row(&[&[i], b"payload"]).unwrap();
+        if i % 3 == 0 {
+            stream.append(writer.get_mut());
+        }
+    }
+    stream.extend(writer.finish().unwrap());
+
+    let mut reader = Reader::new(stream.as_slice()).unwrap();
+    for i in 0..10u8 {
+        assert_eq!(
+            reader.next_row().unwrap(),
+            Some(vec![vec![i], b"payload".to_vec()])
+        );
+    }
+    assert_eq!(reader.next_row().unwrap(), None);
+}
+
+#[test]
+fn test_pgcopy_rejects_bad_input() {
+    assert!(Reader::new(&b"not a copy file"[..]).is_err());
+
+    // NULL field
+    let mut data = pgcopy::SIGNATURE.to_vec();
+    data.extend_from_slice(&[0u8; 8]);
+    data.extend_from_slice(&1i16.to_be_bytes());
+    data.extend_from_slice(&(-1i32).to_be_bytes());
+    let mut reader = Reader::new(data.as_slice()).unwrap();
+    assert!(reader.next_row().is_err());
+
+    // Truncated before the trailer
+    let mut writer = Writer::new(Vec::new()).unwrap();
+    writer.write_row(&[b"key", b"value"]).unwrap();
+    let data = writer.finish().unwrap();
+    let mut reader = Reader::new(&data[..data.len() - 4]).unwrap();
+    assert!(reader.next_row().is_err());
+}
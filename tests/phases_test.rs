@@ -0,0 +1,43 @@
+use es_duck::phases::PhaseTimes;
+use std::time::Duration;
+
+#[test]
+fn test_split_leaves_execution() {
+    let ms = Duration::from_millis;
+    let phases = PhaseTimes::split(ms(1000), Some(ms(50)), Some(ms(300)));
+    assert_eq!(phases.execute, Some(ms(650)));
+    assert_eq!(
+        phases.report(),
+        "PHASE_PLAN: 0.050\nPHASE_EXECUTE: 0.650\nPHASE_EXPORT: 0.300"
+    );
+
+    // Phases an engine doesn't tell stay in execution
+    let phases = PhaseTimes::split(ms(1000), None, None);
+    assert_eq!(phases.execute, Some(ms(1000)));
+    assert_eq!(
+        phases.report(),
+        "PHASE_PLAN: unavailable\nPHASE_EXECUTE: 1.000\nPHASE_EXPORT: unavailable"
+    );
+
+    // Operator times summed over threads can add up to more than the sort
+    assert_eq!(
+        PhaseTimes::split(ms(100), Some(ms(10)), Some(ms(400))).execute,
+        Some(Duration::ZERO)
+    );
+}
+
+#[test]
+fn test_merge_phases_of_several_sorts() {
+    let ms = Duration::from_millis;
+    let mut phases = PhaseTimes::default();
+    phases.merge(PhaseTimes::split(ms(1000), Some(ms(50)), None));
+    phases.merge(PhaseTimes::split(ms(500), Some(ms(25)), Some(ms(100))));
+    assert_eq!(
+        phases,
+        PhaseTimes {
+            plan: Some(ms(75)),
+            execute: Some(ms(1325)),
+            export: Some(ms(100)),
+        }
+    );
+}
@@ -0,0 +1,37 @@
+use es_duck::metrics::THROUGHPUT_HEADER;
+use es_duck::plot;
+
+#[test]
+fn test_plot_throughput_samples() {
+    let csv = format!(
+        "{}\n0.000,setup,0.0,0.0,0.0,0\n1.000,load,5000.0,0.0,500000.0,0\n2.000,load,0.0,0.0,0.0,0\n3.000,sort,0.0,0.0,0.0,1048576\n",
+        THROUGHPUT_HEADER
+    );
+    let samples = plot::parse_samples(&csv).unwrap();
+    assert_eq!(samples.elapsed_secs, vec![0.0, 1.0, 2.0, 3.0]);
+    assert_eq!(samples.phases[3], "sort");
+    assert_eq!(samples.series.len(), 4);
+    assert_eq!(samples.series[0].0, "rows_loaded_per_second");
+    assert_eq!(samples.series[0].1[1], 5000.0);
+
+    let svg = plot::render_svg(&samples, "load <test>").unwrap();
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.trim_end().ends_with("</svg>"));
+    assert!(svg.contains("load &lt;test&gt;"));
+    // One panel per non-zero series: rows loaded, bytes uploaded and spill
+    assert_eq!(svg.matches("<polyline").count(), 3);
+    assert!(!svg.contains("rows_sorted_per_second"));
+    // Setup -> load and load -> sort
+    assert_eq!(svg.matches("stroke-dasharray").count(), 2);
+}
+
+#[test]
+fn test_plot_rejects_bad_input() {
+    assert!(plot::parse_samples("").is_err());
+    assert!(plot::parse_samples("a,b,c\n1,2,3\n").is_err());
+    assert!(plot::parse_samples("elapsed_secs,phase,x\n1.0,load\n").is_err());
+    assert!(plot::parse_samples("elapsed_secs,phase,x\n1.0,load,abc\n").is_err());
+
+    let zeros = plot::parse_samples("elapsed_secs,phase,x\n1.0,load,0\n").unwrap();
+    assert!(plot::render_svg(&zeros, "zeros").is_err());
+}
@@ -1,6 +1,8 @@
 #![cfg(feature = "db-postgres")]
 
+use es_duck::export::{OutputFormat, Reader, Writer};
 use postgres::{Client, NoTls};
+use std::fs::File;
 use std::process::Command;
 
 fn postgres_url() -> Option<String> {
@@ -16,6 +18,15 @@ fn load_postgres_binary() -> String {
     format!("target/{}/load-postgres", profile)
 }
 
+fn load_postgres_async_binary() -> String {
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    format!("target/{}/load-postgres-async", profile)
+}
+
 fn sort_postgres_binary() -> String {
     let profile = if cfg!(debug_assertions) {
         "debug"
@@ -46,6 +57,19 @@ fn run_postgres_sorter(db_url: &str, table: &str, work_mem: &str) -> std::proces
         .expect("Failed to execute sort-postgres")
 }
 
+/// Creates `table` with the 500 keys 0000000000..0000000499 in scrambled order
+/// and a one-byte payload
+fn create_sort_fixture(client: &mut Client, table: &str) {
+    client
+        .batch_execute(&format!(
+            "DROP TABLE IF EXISTS {0}; \
+             CREATE TABLE {0} AS SELECT convert_to(lpad(((i * 37) % 500)::text, 10, '0'), 'UTF8') \
+             AS sort_key, '\\x58'::bytea AS payload FROM generate_series(0, 499) AS i",
+            table
+        ))
+        .unwrap();
+}
+
 #[test]
 fn test_postgres_gensort_format() {
     let Some(db_url) = postgres_url() else {
@@ -70,6 +94,13 @@ fn test_postgres_gensort_format() {
         String::from_utf8_lossy(&output.stdout),
         String::from_utf8_lossy(&output.stderr)
     );
+    // Far fewer rows than a batch still time the batch, the COPY's end and the commit
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let latency = stdout
+        .lines()
+        .find(|line| line.starts_with("BATCH_LATENCY"))
+        .expect("no BATCH_LATENCY line");
+    assert!(!latency.ends_with("no samples"), "got: {}", latency);
 
     // Verify the data
     let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
@@ -146,6 +177,75 @@ fn test_postgres_kvbin_format() {
     assert_eq!(&val2, b"world");
 }
 
+#[test]
+fn test_postgres_custom_layout() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_custom_layout; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_custom_layout_test";
+    let input_path = format!("{}/custom_layout.dat", env!("CARGO_TARGET_TMPDIR"));
+
+    // 16-byte keys and 48-byte payloads, as generate-gensort --key-size 16 --payload-size 48 writes
+    let mut data = Vec::new();
+    for i in 0..1000u32 {
+        data.extend_from_slice(&[b'k'; 12]);
+        data.extend_from_slice(&(999 - i).to_be_bytes());
+        data.extend_from_slice(&[b'p'; 44]);
+        data.extend_from_slice(&i.to_be_bytes());
+    }
+    std::fs::write(&input_path, &data).unwrap();
+
+    {
+        let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+        let _ = client.batch_execute(&format!("DROP TABLE IF EXISTS {}", table));
+    }
+
+    let output = Command::new(load_postgres_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            &input_path,
+            "--db",
+            &db_url,
+            "--table",
+            table,
+            "--key-size",
+            "16",
+            "--payload-size",
+            "48",
+            "--threads",
+            "3",
+        ])
+        .output()
+        .expect("Failed to execute load-postgres");
+    assert!(
+        output.status.success(),
+        "Loader failed: stdout: {}, stderr: {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    let rows = client
+        .query(
+            &format!("SELECT sort_key, payload FROM {} ORDER BY sort_key", table),
+            &[],
+        )
+        .expect("Failed to query rows");
+    assert_eq!(rows.len(), 1000);
+    for (i, row) in rows.iter().enumerate() {
+        let key: Vec<u8> = row.get(0);
+        let payload: Vec<u8> = row.get(1);
+        assert_eq!((key.len(), payload.len()), (16, 48));
+        // Key i belongs with the payload of record 999 - i
+        assert_eq!(key[12..], (i as u32).to_be_bytes());
+        assert_eq!(payload[44..], (999 - i as u32).to_be_bytes());
+    }
+}
+
 #[test]
 fn test_postgres_external_sort() {
     use rand::Rng;
@@ -196,7 +296,7 @@ fn test_postgres_external_sort() {
         String::from_utf8_lossy(&output.stderr)
     );
 
-    // Run external sort (now just executes count query)
+    // Run external sort
     let output = run_postgres_sorter(&db_url, table, "64MB");
     assert!(
         output.status.success(),
@@ -213,16 +313,15 @@ fn test_postgres_external_sort() {
         stdout
     );
     assert!(
-        stdout.contains("Count result:"),
-        "Expected count result, got: {}",
+        stdout.contains("SORT_METHOD:") && stdout.contains("WORKERS_LAUNCHED:"),
+        "Expected the plan's sort statistics, got: {}",
         stdout
     );
-
-    // Parse the count from output and verify it's correct (100 records - 1 due to OFFSET 1)
+    // The row count comes from the table statistics gathered before the sort
     let count_line = stdout
         .lines()
-        .find(|line| line.contains("Count result:"))
-        .expect("Could not find count result line");
+        .find(|line| line.starts_with("Row count:"))
+        .expect("Could not find row count line");
     let count: i64 = count_line
         .split(':')
         .nth(1)
@@ -230,7 +329,7 @@ fn test_postgres_external_sort() {
         .trim()
         .parse()
         .expect("Failed to parse count");
-    assert_eq!(count, 99, "Expected count of 99 (100 records - 1 offset)");
+    assert_eq!(count, 100, "Expected all 100 records to be counted");
 
     // Verify data is actually sorted by reading directly from database
     let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
@@ -263,3 +362,1288 @@ fn test_postgres_external_sort() {
         let _ = client.batch_execute(&format!("DROP TABLE IF EXISTS {}", table));
     }
 }
+
+#[test]
+fn test_postgres_async_loader() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_async_loader; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_async_test";
+    let input_path = &format!("{}/async_loader.dat", env!("CARGO_TARGET_TMPDIR"));
+    let expected: Vec<u8> = (0..2000u32)
+        .flat_map(|i| {
+            [
+                &(i * 7919 % 2000).to_be_bytes()[..],
+                &[b'k'; 6],
+                &[b'p'; 90],
+            ]
+            .concat()
+        })
+        .collect();
+    std::fs::write(input_path, &expected).unwrap();
+
+    // 1KB chunks of about ten rows and a short queue, so the reader has to
+    // wait on the network
+    for extra in [
+        &["--threads", "1"][..],
+        &["--threads", "3", "--chunk-bytes", "1KB", "--in-flight", "2"],
+    ] {
+        let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+        let _ = client.batch_execute(&format!("DROP TABLE IF EXISTS {}", table));
+
+        let output = Command::new(load_postgres_async_binary())
+            .args([
+                "--format", "gensort", "--input", input_path, "--db", &db_url, "--table", table,
+            ])
+            .args(extra)
+            .output()
+            .expect("Failed to execute load-postgres-async");
+        assert!(
+            output.status.success(),
+            "Loader failed with {:?}: {}",
+            extra,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let rows = client
+            .query(
+                &format!(
+                    "SELECT sort_key, payload FROM {} ORDER BY sort_key, payload",
+                    table
+                ),
+                &[],
+            )
+            .expect("Failed to query rows");
+        let mut loaded: Vec<Vec<u8>> = rows
+            .iter()
+            .map(|row| [row.get::<_, Vec<u8>>(0), row.get(1)].concat())
+            .collect();
+        let mut records: Vec<Vec<u8>> = expected.chunks(100).map(|r| r.to_vec()).collect();
+        records.sort();
+        loaded.sort();
+        assert!(loaded == records, "rows differ with {:?}", extra);
+    }
+
+    // kvbin goes over one connection
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    let _ = client.batch_execute(&format!("DROP TABLE IF EXISTS {}", table));
+    let output = Command::new(load_postgres_async_binary())
+        .args([
+            "--format",
+            "kvbin",
+            "--input",
+            "testdata/test_kvbin.dat",
+            "--db",
+            &db_url,
+            "--table",
+            table,
+        ])
+        .output()
+        .expect("Failed to execute load-postgres-async");
+    assert!(output.status.success());
+    let count: i64 = client
+        .query_one(&format!("SELECT COUNT(*) FROM {}", table), &[])
+        .unwrap()
+        .get(0);
+    assert_eq!(count, 3);
+}
+
+#[test]
+fn test_postgres_partitioned_load() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_partitioned_load; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_partitioned_test";
+    let input_path = "testdata/test_gensort.dat";
+    let expected = std::fs::metadata(input_path).unwrap().len() as i64 / 100;
+
+    for extra in [
+        &["--partition-by", "range"][..],
+        &["--partition-by", "range", "--attach"],
+        &["--partition-by", "hash"],
+        &["--partition-by", "range", "--merge"],
+    ] {
+        let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+        let _ = client.batch_execute(&format!("DROP TABLE IF EXISTS {} CASCADE", table));
+
+        let output = Command::new(load_postgres_binary())
+            .args([
+                "--format",
+                "gensort",
+                "--input",
+                input_path,
+                "--db",
+                &db_url,
+                "--table",
+                table,
+                "--threads",
+                "3",
+                "--partitions",
+                "4",
+            ])
+            .args(extra)
+            .output()
+            .expect("Failed to execute load-postgres");
+        assert!(
+            output.status.success(),
+            "Loader failed with {:?}: {}",
+            extra,
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let count: i64 = client
+            .query_one(&format!("SELECT COUNT(*) FROM {}", table), &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(count, expected, "with {:?}", extra);
+
+        // Every partition is attached, or merged away into a plain table
+        let partitions: i64 = client
+            .query_one(
+                "SELECT COUNT(*) FROM pg_inherits WHERE inhparent = $1::text::regclass",
+                &[&table],
+            )
+            .unwrap()
+            .get(0);
+        let merged = extra.contains(&"--merge");
+        assert_eq!(partitions, if merged { 0 } else { 4 }, "with {:?}", extra);
+    }
+
+    // Hash partitions cannot be attached: rows are routed by the server
+    let output = Command::new(load_postgres_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            input_path,
+            "--db",
+            &db_url,
+            "--table",
+            table,
+            "--partitions",
+            "4",
+            "--partition-by",
+            "hash",
+            "--attach",
+        ])
+        .output()
+        .expect("Failed to execute load-postgres");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--attach needs"));
+}
+
+#[test]
+fn test_postgres_post_load_maintenance() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_post_load_maintenance; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_maintenance_test";
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    let _ = client.batch_execute(&format!("DROP TABLE IF EXISTS {} CASCADE", table));
+
+    let output = Command::new(load_postgres_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            "testdata/test_gensort.dat",
+            "--db",
+            &db_url,
+            "--table",
+            table,
+            "--storage-params",
+            "fillfactor=90",
+            "--logged",
+            "--index",
+            "--analyze",
+        ])
+        .output()
+        .expect("Failed to execute load-postgres");
+    assert!(
+        output.status.success(),
+        "Loader failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Post-load ANALYZE"));
+
+    let row = client
+        .query_one(
+            "SELECT relpersistence::text, array_to_string(reloptions, ','), \
+             (SELECT COUNT(*) FROM pg_indexes WHERE tablename = $1), \
+             (SELECT COUNT(*) FROM pg_stats WHERE tablename = $1) \
+             FROM pg_class WHERE relname = $1",
+            &[&table],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, String>(0), "p");
+    assert_eq!(row.get::<_, String>(1), "fillfactor=90");
+    assert_eq!(row.get::<_, i64>(2), 1);
+    assert!(row.get::<_, i64>(3) > 0);
+}
+
+#[test]
+fn test_postgres_checkpoint_resume() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_checkpoint_resume; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_checkpoint_test";
+    let input_path = &format!("{}/postgres_checkpoint.dat", env!("CARGO_TARGET_TMPDIR"));
+    let checkpoint_path = "/tmp/test_postgres_checkpoint_resume.ckpt";
+    let _ = std::fs::remove_file(checkpoint_path);
+    let records = 2000;
+    let data: Vec<u8> = (0..records as u32)
+        .flat_map(|i| [&i.to_be_bytes()[..], &[b'k'; 6], &[b'p'; 90]].concat())
+        .collect();
+    std::fs::write(input_path, &data).unwrap();
+
+    // A table that refuses rows once it holds three units' worth interrupts
+    // the first run after a few units are done
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    client
+        .batch_execute(&format!(
+            "DROP TABLE IF EXISTS {table};
+             DROP FUNCTION IF EXISTS {table}_interrupt();
+             DO $$ DECLARE staging text; BEGIN
+                 FOR staging IN SELECT tablename FROM pg_tables WHERE tablename LIKE '{table}_unit_%' LOOP
+                     EXECUTE 'DROP TABLE ' || staging;
+                 END LOOP;
+             END $$;
+             CREATE UNLOGGED TABLE {table} (sort_key BYTEA, payload BYTEA);
+             CREATE FUNCTION {table}_interrupt() RETURNS trigger AS $$
+             BEGIN
+                 IF (SELECT COUNT(*) FROM {table}) >= 300 THEN
+                     RAISE EXCEPTION 'interrupted';
+                 END IF;
+                 RETURN NULL;
+             END $$ LANGUAGE plpgsql;
+             CREATE TRIGGER interrupt BEFORE INSERT ON {table}
+                 FOR EACH STATEMENT EXECUTE FUNCTION {table}_interrupt();",
+            table = table
+        ))
+        .unwrap();
+
+    // 100 records per unit
+    let load = || {
+        Command::new(load_postgres_binary())
+            .args([
+                "--format",
+                "gensort",
+                "--input",
+                input_path,
+                "--db",
+                &db_url,
+                "--table",
+                table,
+                "--if-exists",
+                "append",
+                "--threads",
+                "2",
+                "--checkpoint-file",
+                checkpoint_path,
+                "--checkpoint-unit",
+                "10000",
+            ])
+            .output()
+            .expect("Failed to execute load-postgres")
+    };
+    let output = load();
+    assert!(
+        !output.status.success(),
+        "the first run was not interrupted"
+    );
+    let checkpoint = std::fs::read_to_string(checkpoint_path).unwrap();
+    let done = checkpoint
+        .lines()
+        .filter(|l| l.starts_with("done "))
+        .count() as i64;
+    let loaded: i64 = client
+        .query_one(&format!("SELECT COUNT(*) FROM {}", table), &[])
+        .unwrap()
+        .get(0);
+    assert!(done >= 3, "checkpoint: {}", checkpoint);
+    // A unit moved just before the process exited may not be marked done yet
+    assert!(loaded >= done * 100 && loaded < records, "{} rows", loaded);
+
+    client
+        .batch_execute(&format!("DROP TRIGGER interrupt ON {}", table))
+        .unwrap();
+    let output = load();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Loader failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains(&format!("({} already loaded)", done)),
+        "stdout: {}",
+        stdout
+    );
+    assert!(!std::path::Path::new(checkpoint_path).exists());
+
+    let row = client
+        .query_one(
+            &format!(
+                "SELECT COUNT(*), COUNT(DISTINCT sort_key), \
+                 (SELECT COUNT(*) FROM pg_tables WHERE tablename LIKE '{}_unit_%') FROM {}",
+                table, table
+            ),
+            &[],
+        )
+        .unwrap();
+    assert_eq!(
+        row.get::<_, i64>(0),
+        records,
+        "rows were lost or loaded twice"
+    );
+    assert_eq!(row.get::<_, i64>(1), records);
+    assert_eq!(row.get::<_, i64>(2), 0, "staging tables were not dropped");
+    let _ = client.batch_execute(&format!(
+        "DROP TABLE {table}; DROP FUNCTION {table}_interrupt();",
+        table = table
+    ));
+}
+
+#[test]
+fn test_postgres_if_exists() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_if_exists; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_if_exists_test";
+    let input_path = "testdata/test_gensort.dat";
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    let _ = client.batch_execute(&format!("DROP TABLE IF EXISTS {}", table));
+
+    let output = run_postgres_loader("gensort", input_path, &db_url, table);
+    assert!(output.status.success());
+
+    // Loading again used to append silently; now the default refuses
+    let output = run_postgres_loader("gensort", input_path, &db_url, table);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+
+    for (policy, expected) in [("append", 6i64), ("replace", 3)] {
+        let output = Command::new(load_postgres_binary())
+            .args([
+                "--format",
+                "gensort",
+                "--input",
+                input_path,
+                "--db",
+                &db_url,
+                "--table",
+                table,
+                "--if-exists",
+                policy,
+            ])
+            .output()
+            .expect("Failed to execute load-postgres");
+        assert!(
+            output.status.success(),
+            "Loader failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let count: i64 = client
+            .query_one(&format!("SELECT count(*) FROM {}", table), &[])
+            .unwrap()
+            .get(0);
+        assert_eq!(count, expected, "after --if-exists {}", policy);
+    }
+
+    let _ = client.batch_execute(&format!("DROP TABLE IF EXISTS {}", table));
+}
+
+#[test]
+fn test_postgres_custom_column_names() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_custom_column_names; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_custom_columns_test";
+    let input_path = "testdata/test_gensort.dat";
+    let expected = std::fs::metadata(input_path).unwrap().len() as i64 / 100;
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    let _ = client.batch_execute(&format!("DROP TABLE IF EXISTS {} CASCADE", table));
+
+    // Merging partitions rewrites the table, so the extra column comes after it
+    let output = Command::new(load_postgres_binary())
+        .args([
+            "--format",
+            "gensort",
+            "--input",
+            input_path,
+            "--db",
+            &db_url,
+            "--table",
+            table,
+            "--key-column",
+            "k",
+            "--payload-column",
+            "v",
+            "--extra-column",
+            "tenant_id INTEGER",
+            "--partitions",
+            "4",
+            "--merge",
+            "--index",
+        ])
+        .output()
+        .expect("Failed to execute load-postgres");
+    assert!(
+        output.status.success(),
+        "Loader failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let count: i64 = client
+        .query_one(
+            &format!(
+                "SELECT COUNT(k) FROM {} WHERE v IS NOT NULL AND tenant_id IS NULL",
+                table
+            ),
+            &[],
+        )
+        .unwrap()
+        .get(0);
+    assert_eq!(count, expected);
+    let index: bool = client
+        .query_one(
+            "SELECT to_regclass($1) IS NOT NULL",
+            &[&format!("{}_k_idx", table)],
+        )
+        .unwrap()
+        .get(0);
+    assert!(index);
+
+    let output = Command::new(sort_postgres_binary())
+        .args([
+            "--db",
+            &db_url,
+            "--table",
+            table,
+            "--key-column",
+            "k",
+            "--payload-column",
+            "v",
+        ])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    assert!(
+        output.status.success(),
+        "Sorter failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let _ = client.batch_execute(&format!("DROP TABLE IF EXISTS {}", table));
+}
+
+#[test]
+fn test_postgres_sort_into_table_and_cluster() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_sort_into_table_and_cluster; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_materialize_test";
+    let sorted = "postgres_materialize_sorted";
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    create_sort_fixture(&mut client, table);
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", sorted))
+        .unwrap();
+    let expected: Vec<Vec<u8>> = (0..500)
+        .map(|i| format!("{:010}", i).into_bytes())
+        .collect();
+
+    // CREATE UNLOGGED TABLE AS, analyzed like a sort without output
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", table, "--output-table", sorted])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    assert!(
+        output.status.success(),
+        "Sorter failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("SORT_METHOD:"));
+    let keys: Vec<Vec<u8>> = client
+        .query(&format!("SELECT sort_key FROM {}", sorted), &[])
+        .unwrap()
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    assert_eq!(keys, expected);
+    let persistence: i8 = client
+        .query_one(
+            "SELECT relpersistence::\"char\" FROM pg_class WHERE relname = $1",
+            &[&sorted],
+        )
+        .unwrap()
+        .get(0);
+    assert_eq!(persistence as u8, b'u');
+
+    // CLUSTER rewrites the table itself in key order
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", table, "--cluster"])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    assert!(
+        output.status.success(),
+        "Sorter failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let keys: Vec<Vec<u8>> = client
+        .query(&format!("SELECT sort_key FROM {}", table), &[])
+        .unwrap()
+        .iter()
+        .map(|row| row.get(0))
+        .collect();
+    assert_eq!(keys, expected);
+
+    let _ = client.batch_execute(&format!(
+        "DROP TABLE IF EXISTS {}; DROP TABLE IF EXISTS {}",
+        table, sorted
+    ));
+}
+
+#[test]
+fn test_postgres_sort_settings() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_sort_settings; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_settings_test";
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    create_sort_fixture(&mut client, table);
+
+    // Applied after the sorter's own settings, so work_mem can be overridden too
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", table])
+        .args([
+            "--set",
+            "enable_incremental_sort=off",
+            "--set",
+            "work_mem=64kB",
+            "--set",
+            "application_name=sort's settings",
+        ])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("Setting enable_incremental_sort = off"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Setting work_mem = 64kB"),
+        "got: {}",
+        stdout
+    );
+
+    // An unknown setting fails the sort instead of being ignored
+    let output = Command::new(sort_postgres_binary())
+        .args([
+            "--db",
+            &db_url,
+            "--table",
+            table,
+            "--set",
+            "no_such_setting=1",
+        ])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    assert!(!output.status.success());
+
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", table))
+        .unwrap();
+}
+
+#[test]
+fn test_postgres_sort_timeout() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_sort_timeout; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    // Every key of the view takes a second, so sorting its 100 rows runs well
+    // past the budget; counting them doesn't read the key, so it stays fast
+    let table = "postgres_timeout_test";
+    let view = "postgres_timeout_view";
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    client
+        .batch_execute(&format!(
+            "DROP VIEW IF EXISTS {1}; DROP TABLE IF EXISTS {0}; \
+             CREATE TABLE {0} AS SELECT convert_to(lpad(i::text, 10, '0'), 'UTF8') AS sort_key, \
+             '\\x58'::bytea AS payload FROM generate_series(0, 99) AS i; \
+             CREATE OR REPLACE FUNCTION es_duck_slow_key(k bytea) RETURNS bytea \
+             LANGUAGE plpgsql STABLE AS 'BEGIN PERFORM pg_sleep(1); RETURN k; END'; \
+             CREATE VIEW {1} AS SELECT es_duck_slow_key(sort_key) AS sort_key, payload FROM {0}",
+            table, view
+        ))
+        .unwrap();
+
+    let start = std::time::Instant::now();
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", view, "--timeout", "1"])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        output.status.code(),
+        Some(124),
+        "stdout: {}, stderr: {}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("TIMEOUT: 1"), "got: {}", stdout);
+    assert!(!stdout.contains("TIMING:"), "got: {}", stdout);
+    assert!(
+        start.elapsed().as_secs() < 30,
+        "The sort wasn't stopped at its timeout"
+    );
+
+    // The server stops too: a query the client merely walked away from would
+    // go on sleeping through the rest of the rows
+    let running: i64 = client
+        .query_one(
+            "SELECT count(*) FROM pg_stat_activity WHERE state = 'active' \
+             AND query LIKE '%' || $1 || '%' AND pid <> pg_backend_pid()",
+            &[&view],
+        )
+        .unwrap()
+        .get(0);
+    assert_eq!(running, 0, "The sort is still running on the server");
+
+    let _ = client.batch_execute(&format!(
+        "DROP VIEW IF EXISTS {}; DROP TABLE IF EXISTS {}; \
+         DROP FUNCTION IF EXISTS es_duck_slow_key(bytea)",
+        view, table
+    ));
+}
+
+#[test]
+fn test_postgres_export_streams() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_export_streams; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_export_streams_test";
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    create_sort_fixture(&mut client, table);
+
+    // Written here, so the test needn't share a file system with the server
+    let output_path = "/tmp/es_duck_pg_export_streams.kvbin";
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", table])
+        .args(["--output", output_path, "--output-format", "kvbin"])
+        .args(["--client-side", "--export-streams", "3", "--verify"])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // The ranges in order are the whole sorted output
+    assert!(
+        stdout.contains("VERIFY: OK 500 rows (sorted, row count matches)"),
+        "got: {}",
+        stdout
+    );
+    for i in 0..3 {
+        let part = format!("/tmp/es_duck_pg_export_streams.shard{}.kvbin", i);
+        assert!(
+            stdout.contains(&format!("Stream {}:", i)),
+            "got: {}",
+            stdout
+        );
+        std::fs::remove_file(&part).unwrap();
+    }
+
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", table))
+        .unwrap();
+}
+
+#[test]
+fn test_postgres_text_key() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_text_key; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    // The fixture's keys as text, like a load-postgres --key-type text table
+    let table = "postgres_text_key_test";
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    client
+        .batch_execute(&format!(
+            "DROP TABLE IF EXISTS {0}; \
+             CREATE TABLE {0} AS SELECT lpad(((i * 37) % 500)::text, 10, '0') AS sort_key, \
+             '\\x58'::bytea AS payload FROM generate_series(0, 499) AS i",
+            table
+        ))
+        .unwrap();
+
+    // The ranges are sampled and split as text
+    let output_path = "/tmp/es_duck_pg_text_key.kvbin";
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", table])
+        .args(["--output", output_path, "--output-format", "kvbin"])
+        .args(["--client-side", "--export-streams", "3", "--verify"])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("VERIFY: OK 500 rows (sorted, row count matches)"),
+        "got: {}",
+        stdout
+    );
+    for i in 0..3 {
+        std::fs::remove_file(format!("/tmp/es_duck_pg_text_key.shard{}.kvbin", i)).unwrap();
+    }
+
+    // The server hex-encodes the key's UTF-8 bytes for CSV; the file stays on
+    // the server's side
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", table])
+        .args(["--output", "/tmp/es_duck_pg_text_key.csv"])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    assert!(
+        output.status.success(),
+        "Sorter failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", table))
+        .unwrap();
+}
+
+#[test]
+fn test_postgres_uint64_key() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_uint64_key; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_uint64_key_test";
+    let input_path = format!("{}/uint64_key.dat", env!("CARGO_TARGET_TMPDIR"));
+    // 200 gensort records whose integer keys span both halves of the BIGINT
+    // range, so the flipped top bit matters
+    let key = |i: u64| i * (u64::MAX / 200);
+    let mut data = Vec::new();
+    for i in 0..200u64 {
+        data.extend_from_slice(&key(i * 37 % 200).to_be_bytes());
+        data.extend_from_slice(&[b'k'; 2]);
+        data.extend_from_slice(&[b'p'; 90]);
+    }
+    std::fs::write(&input_path, &data).unwrap();
+
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", table))
+        .unwrap();
+    let output = Command::new(load_postgres_binary())
+        .args(["--format", "gensort", "--input", &input_path])
+        .args(["--db", &db_url, "--table", table, "--key-type", "uint64"])
+        .output()
+        .expect("Failed to execute load-postgres");
+    assert!(
+        output.status.success(),
+        "Loader failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Every format goes out with the keys as 8 big-endian bytes
+    for (format, client_side) in [
+        ("pgbinary", false),
+        ("csv", false),
+        ("pgbinary", true),
+        ("csv", true),
+        ("native", true),
+        ("kvbin", true),
+    ] {
+        let output_path = format!("/tmp/es_duck_pg_uint64_key_{}.{}", client_side, format);
+        let mut command = Command::new(sort_postgres_binary());
+        command
+            .args(["--db", &db_url, "--table", table])
+            .args(["--output", &output_path, "--output-format", format])
+            .arg("--verify");
+        if client_side {
+            command.arg("--client-side");
+        }
+        let output = command.output().expect("Failed to execute sort-postgres");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "Sorter failed on {}: {}",
+            format,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(
+            stdout.contains("VERIFY: OK 200 rows (sorted, row count matches)"),
+            "got: {}",
+            stdout
+        );
+
+        let format = OutputFormat::from_path(output_path.as_ref()).unwrap();
+        let mut reader = Reader::open(output_path.as_ref(), format).unwrap();
+        let mut keys = Vec::new();
+        while let Some((key, _)) = reader.next_row().unwrap() {
+            keys.push(key);
+        }
+        let expected: Vec<Vec<u8>> = (0..200).map(|i| key(i).to_be_bytes().to_vec()).collect();
+        assert_eq!(keys, expected, "{} keys", format.name());
+        std::fs::remove_file(&output_path).unwrap();
+    }
+
+    // The ranges are sampled and split as integers; the shards in order are
+    // the whole sorted output
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", table])
+        .args(["--output", "/tmp/es_duck_pg_uint64_key.kvbin"])
+        .args(["--client-side", "--export-streams", "3", "--verify"])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("VERIFY: OK 200 rows (sorted, row count matches)"),
+        "got: {}",
+        stdout
+    );
+    let mut keys = Vec::new();
+    for i in 0..3 {
+        let part = format!("/tmp/es_duck_pg_uint64_key.shard{}.kvbin", i);
+        let mut reader = Reader::open(part.as_ref(), OutputFormat::Kvbin).unwrap();
+        while let Some((key, _)) = reader.next_row().unwrap() {
+            keys.push(key);
+        }
+        std::fs::remove_file(&part).unwrap();
+    }
+    let expected: Vec<Vec<u8>> = (0..200).map(|i| key(i).to_be_bytes().to_vec()).collect();
+    assert_eq!(keys, expected);
+
+    // gensort's keys are 10 bytes
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", table])
+        .args(["--output", "/tmp/es_duck_pg_uint64_key.gensort"])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("a uint64 key can't write gensort"),
+        "got: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", table))
+        .unwrap();
+}
+
+#[test]
+fn test_postgres_sort_row_ids() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_sort_row_ids; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_sort_row_ids_test";
+    let input_path = format!("{}/sort_row_ids.dat", env!("CARGO_TARGET_TMPDIR"));
+    // 200 gensort records over 20 keys, loaded 3 times: runs of 30 equal keys
+    let mut data = Vec::new();
+    for i in 0..200u32 {
+        data.extend_from_slice(format!("{:010}", i % 20).as_bytes());
+        data.extend_from_slice(&[b'p'; 86]);
+        data.extend_from_slice(&i.to_be_bytes());
+    }
+    std::fs::write(&input_path, &data).unwrap();
+
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", table))
+        .unwrap();
+    let output = Command::new(load_postgres_binary())
+        .args(["--format", "gensort", "--input", &input_path])
+        .args(["--db", &db_url, "--table", table])
+        .args(["--with-row-id", "--scale-factor", "3"])
+        .output()
+        .expect("Failed to execute load-postgres");
+    assert!(
+        output.status.success(),
+        "Loader failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The server writes binary COPY and CSV, the rest are written here
+    for (format, client_side) in [
+        ("pgbinary", false),
+        ("csv", false),
+        ("native", true),
+        ("csv", true),
+    ] {
+        let output_path = format!("/tmp/es_duck_pg_sort_row_ids_{}.{}", client_side, format);
+        let mut command = Command::new(sort_postgres_binary());
+        command
+            .args(["--db", &db_url, "--table", table])
+            .args(["--output", &output_path, "--output-format", format])
+            .arg("--verify");
+        if client_side {
+            command.arg("--client-side");
+        }
+        let output = command.output().expect("Failed to execute sort-postgres");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            output.status.success(),
+            "Sorter failed on {}: {}",
+            format,
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(
+            stdout.contains("VERIFY_ROW_IDS: OK 600 ids (sum matches"),
+            "got: {}",
+            stdout
+        );
+
+        // Every id once, and each key's rows from its records
+        let format = OutputFormat::from_path(output_path.as_ref()).unwrap();
+        let mut reader = Reader::open(output_path.as_ref(), format).unwrap();
+        let mut ids = Vec::new();
+        while let Some(((key, payload), id)) = reader.next_row_with_id().unwrap() {
+            let id = id.expect("the output carries row ids");
+            let record = u32::from_be_bytes(payload[86..].try_into().unwrap());
+            assert_eq!(id / 3, record as u64);
+            assert_eq!(key, format!("{:010}", record % 20).as_bytes());
+            ids.push(id);
+        }
+        ids.sort_unstable();
+        assert_eq!(ids, (0..600).collect::<Vec<u64>>());
+        std::fs::remove_file(&output_path).unwrap();
+    }
+
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", table))
+        .unwrap();
+}
+
+#[test]
+fn test_postgres_sort_input_files() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_sort_input_files; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    // Written by the server, which has to read it
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    let input_path = "/tmp/es_duck_pg_input_files.csv";
+    client
+        .batch_execute(&format!(
+            "COPY (SELECT encode(convert_to(lpad(((i * 37) % 500)::text, 10, '0'), 'UTF8'), \
+             'hex'), encode('\\x58'::bytea, 'hex') FROM generate_series(0, 499) AS i) \
+             TO '{}' (FORMAT csv)",
+            input_path
+        ))
+        .unwrap();
+
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--input-files", input_path])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains(&format!("Input files: {} (file_fdw)", input_path)),
+        "got: {}",
+        stdout
+    );
+    assert!(stdout.contains("Row count: 500"), "got: {}", stdout);
+
+    // The foreign table is gone with the sort
+    let mut foreign_tables_left = || -> i64 {
+        client
+            .query_one(
+                "SELECT count(*) FROM pg_foreign_table t JOIN pg_class c ON c.oid = t.ftrelid \
+                 WHERE c.relname LIKE 'es_duck_input_files_%'",
+                &[],
+            )
+            .unwrap()
+            .get(0)
+    };
+    assert_eq!(foreign_tables_left(), 0);
+
+    // Also when reading the files fails in the middle of the sort
+    let output = Command::new(sort_postgres_binary())
+        .args([
+            "--db",
+            &db_url,
+            "--input-files",
+            "/tmp/es_duck_no_such_file.csv",
+        ])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    assert!(!output.status.success());
+    assert_eq!(foreign_tables_left(), 0);
+
+    // file_fdw reads only what COPY does
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--input-files", "/tmp/parts/*.parquet"])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_postgres_index_strategy() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_index_strategy; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_index_strategy_test";
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    create_sort_fixture(&mut client, table);
+
+    // Both runs build the index again, then scan it in order
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", table])
+        .args(["--strategy", "index", "--runs", "2"])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(stdout.matches("Index build:").count(), 2, "got: {}", stdout);
+    assert!(stdout.contains("Index Scan"), "got: {}", stdout);
+    assert!(
+        !stdout.contains("\"Node Type\": \"Sort\""),
+        "got: {}",
+        stdout
+    );
+
+    // The table is left without the index
+    let indexes: i64 = client
+        .query_one(
+            "SELECT count(*) FROM pg_indexes WHERE tablename = $1",
+            &[&table],
+        )
+        .unwrap()
+        .get(0);
+    assert_eq!(indexes, 0);
+
+    // CLUSTER has an index of its own
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", table])
+        .args(["--strategy", "index", "--cluster"])
+        .output()
+        .expect("Failed to execute sort-postgres");
+    assert!(!output.status.success());
+
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", table))
+        .unwrap();
+}
+
+#[test]
+fn test_postgres_collation() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_collation; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_collation_test";
+    let dir = "/tmp/test_postgres_collation";
+    let _ = std::fs::remove_dir_all(dir);
+    std::fs::create_dir_all(dir).unwrap();
+    let input_path = format!("{}/input.kvbin", dir);
+    let mut writer = Writer::new(
+        File::create(&input_path).unwrap(),
+        OutputFormat::Kvbin,
+        &["sort_key", "payload"],
+    )
+    .unwrap();
+    for key in ["b", "A", "a", "B"] {
+        writer.write_row(&[key.as_bytes(), b"x"]).unwrap();
+    }
+    writer.finish().unwrap();
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", table))
+        .unwrap();
+
+    // Only a text key has a collation
+    let output = Command::new(load_postgres_binary())
+        .args(["--format", "kvbin", "--input", &input_path])
+        .args([
+            "--db",
+            &db_url,
+            "--table",
+            table,
+            "--collation",
+            "icu:en-US",
+        ])
+        .output()
+        .expect("Failed to execute load-postgres");
+    assert!(!output.status.success());
+
+    let output = Command::new(load_postgres_binary())
+        .args(["--format", "kvbin", "--input", &input_path])
+        .args(["--db", &db_url, "--table", table])
+        .args(["--key-type", "text", "--collation", "icu:en-US"])
+        .output()
+        .expect("Failed to execute load-postgres");
+    assert!(
+        output.status.success(),
+        "Loader failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The column's ICU order, ICU's, then the bytes'
+    let sorted_keys = |collation: Option<&str>| {
+        let output_path = format!("{}/sorted.kvbin", dir);
+        let mut command = Command::new(sort_postgres_binary());
+        command.args(["--db", &db_url, "--table", table]).args([
+            "--output",
+            &output_path,
+            "--client-side",
+            "--verify",
+        ]);
+        if let Some(collation) = collation {
+            command.args(["--collation", collation]);
+        }
+        let output = command.output().expect("Failed to execute sort-postgres");
+        assert!(
+            output.status.success(),
+            "Sorter failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let mut reader = Reader::open(output_path.as_ref(), OutputFormat::Kvbin).unwrap();
+        let mut keys = Vec::new();
+        while let Some((key, _)) = reader.next_row().unwrap() {
+            keys.push(String::from_utf8(key).unwrap());
+        }
+        (String::from_utf8_lossy(&output.stdout).into_owned(), keys)
+    };
+    let (stdout, keys) = sorted_keys(None);
+    assert_eq!(keys, ["a", "A", "b", "B"]);
+    assert!(
+        stdout.contains("VERIFY: OK 4 rows (row count matches, order not checked)"),
+        "got: {}",
+        stdout
+    );
+    let (stdout, keys) = sorted_keys(Some("icu:en-US"));
+    assert_eq!(keys, ["a", "A", "b", "B"]);
+    assert!(
+        stdout.contains("Order by: sort_key COLLATE \"en-US-x-icu\""),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("VERIFY: OK 4 rows (row count matches, order not checked)"),
+        "got: {}",
+        stdout
+    );
+    let (stdout, keys) = sorted_keys(Some("binary"));
+    assert_eq!(keys, ["A", "B", "a", "b"]);
+    assert!(
+        stdout.contains("VERIFY: OK 4 rows (sorted, row count matches)"),
+        "got: {}",
+        stdout
+    );
+
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", table))
+        .unwrap();
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn test_postgres_explain_only() {
+    let Some(db_url) = postgres_url() else {
+        eprintln!("skipping test_postgres_explain_only; POSTGRES_TEST_URL not set");
+        return;
+    };
+
+    let table = "postgres_explain_only_test";
+    let mut client = Client::connect(&db_url, NoTls).expect("Failed to connect to Postgres");
+    create_sort_fixture(&mut client, table);
+
+    let output_path = "/tmp/test_postgres_explain_only.bin";
+    let _ = std::fs::remove_file(output_path);
+    let output = Command::new(sort_postgres_binary())
+        .args(["--db", &db_url, "--table", table])
+        .args(["--output", output_path, "--client-side"])
+        .args(["--total-memory", "64MB", "--parallel-workers", "1"])
+        .arg("--explain-only")
+        .output()
+        .expect("Failed to execute sort-postgres");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "Sorter failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains("Row count: not counted (--explain-only)"),
+        "got: {}",
+        stdout
+    );
+    assert!(stdout.contains("===== SETTINGS ====="), "got: {}", stdout);
+    assert!(stdout.contains("work_mem = 65536kB"), "got: {}", stdout);
+    assert!(stdout.contains("===== QUERY PLAN ====="), "got: {}", stdout);
+    assert!(stdout.contains("Sort Key:"), "got: {}", stdout);
+    assert!(
+        stdout.contains("TO STDOUT (FORMAT BINARY);"),
+        "got: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Explain only: nothing was run"),
+        "got: {}",
+        stdout
+    );
+    assert!(!stdout.contains("TIMING:"), "got: {}", stdout);
+    assert!(!std::path::Path::new(output_path).exists());
+
+    client
+        .batch_execute(&format!("DROP TABLE IF EXISTS {}", table))
+        .unwrap();
+}
@@ -0,0 +1,32 @@
+use es_duck::profile;
+
+#[test]
+fn test_profile_disabled_without_output() {
+    assert!(profile::start(None).is_ok());
+}
+
+#[cfg(not(feature = "util-profile"))]
+#[test]
+fn test_profile_requires_feature() {
+    match profile::start(Some(std::path::Path::new("flamegraph.svg"))) {
+        Ok(_) => panic!("--profile should fail without util-profile"),
+        Err(e) => assert!(e.to_string().contains("util-profile")),
+    }
+}
+
+#[cfg(feature = "util-profile")]
+#[test]
+fn test_profile_writes_flamegraph() {
+    let output = std::env::temp_dir().join(format!("es_duck_profile_{}.svg", std::process::id()));
+    {
+        let _profile = profile::start(Some(&output)).unwrap();
+        // Burn enough CPU to collect a few samples
+        let mut x = 0u64;
+        for i in 0..50_000_000u64 {
+            x = std::hint::black_box(x.wrapping_mul(31).wrapping_add(i));
+        }
+    }
+    let svg = std::fs::read_to_string(&output).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    assert!(svg.contains("<svg"));
+}
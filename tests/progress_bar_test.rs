@@ -0,0 +1,34 @@
+use es_duck::metrics;
+use es_duck::progress_bar::{self, Bar};
+use es_duck::records::{FixedLayout, InputFormat};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_bar_counts_records_and_bytes() {
+    let bar = Bar::new(Some(1_000), false);
+    bar.set(10, 1_000);
+    assert_eq!((bar.records(), bar.bytes()), (10, 1_000));
+
+    // Without a total the bar still counts
+    let bar = Bar::new(None, false);
+    bar.set(7, 123);
+    assert_eq!((bar.records(), bar.bytes()), (7, 123));
+    bar.finish();
+}
+
+#[test]
+fn test_load_bar_follows_metrics() {
+    let layout = FixedLayout {
+        key_size: 10,
+        payload_size: 30,
+    };
+    let bar = progress_bar::load(InputFormat::Gensort, layout, &(400..4_400), false);
+    metrics::add_rows_loaded(25);
+    thread::sleep(Duration::from_millis(600));
+    bar.finish();
+
+    // Other tests in this process may bump the counters too
+    assert!(bar.records() >= 25);
+    assert_eq!(bar.bytes(), bar.records() * 40);
+}
@@ -0,0 +1,26 @@
+use es_duck::metrics::{self, Phase};
+use es_duck::progress;
+
+#[test]
+fn test_progress_event() {
+    metrics::set_phase(Phase::Load);
+    metrics::add_rows_loaded(250);
+    metrics::add_bytes_uploaded(25_000);
+
+    let event = progress::event();
+    assert!(!event.contains('\n'), "got: {}", event);
+    assert!(
+        event.starts_with("{\"event\":\"progress\","),
+        "got: {}",
+        event
+    );
+    assert!(event.ends_with('}'), "got: {}", event);
+    assert!(event.contains("\"phase\":\"load\""));
+    assert!(event.contains("\"rows_loaded\":250,"));
+    assert!(event.contains("\"bytes_uploaded\":25000,"));
+    assert!(event.contains("\"elapsed_secs\":"));
+    assert!(event.contains("\"rows_loaded_per_second\":"));
+
+    metrics::set_phase(Phase::Done);
+    assert!(progress::event().contains("\"phase\":\"done\""));
+}
@@ -0,0 +1,499 @@
+use es_duck::direct::DirectReader;
+use es_duck::mmap::Mmap;
+use es_duck::records::{
+    self, FixedLayout, FixedRecords, InputFormat, Io, IoOptions, KvbinReader, RecordReader, Window,
+};
+use std::fs;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+#[test]
+fn test_read_gensort_records() {
+    let mut data = Vec::new();
+    for i in 0..3u8 {
+        data.extend_from_slice(&[i; 10]);
+        data.extend_from_slice(&[b'P'; 90]);
+    }
+
+    let mut reader = RecordReader::new(InputFormat::Gensort, data.as_slice());
+    let batch = reader.next_batch(2).unwrap().unwrap();
+    assert_eq!(batch.len(), 2);
+    assert_eq!(batch[1].0, vec![1u8; 10]);
+    assert_eq!(batch[1].1.len(), 90);
+
+    let batch = reader.next_batch(2).unwrap().unwrap();
+    assert_eq!(batch.len(), 1);
+    assert_eq!(batch[0].0, vec![2u8; 10]);
+    assert!(reader.next_batch(2).unwrap().is_none());
+}
+
+#[test]
+fn test_read_gensort_records_with_layout() {
+    let layout = FixedLayout {
+        key_size: 16,
+        payload_size: 3,
+    };
+    let mut data = Vec::new();
+    for i in 0..2u8 {
+        data.extend_from_slice(&[i; 16]);
+        data.extend_from_slice(b"abc");
+    }
+
+    let mut reader = RecordReader::new(InputFormat::Gensort, data.as_slice()).with_layout(layout);
+    assert_eq!(
+        reader.next_record().unwrap(),
+        Some((vec![0u8; 16], b"abc".to_vec()))
+    );
+    assert_eq!(reader.next_record().unwrap().unwrap().0, vec![1u8; 16]);
+    assert_eq!(reader.next_record().unwrap(), None);
+
+    // A partial record at the end is still an error
+    let mut reader = RecordReader::new(InputFormat::Gensort, &data[..30]).with_layout(layout);
+    assert!(reader.next_record().unwrap().is_some());
+    assert!(reader.next_record().is_err());
+}
+
+#[test]
+fn test_read_kvbin_records() {
+    let mut data = Vec::new();
+    for (key, value) in [(&b"key1"[..], &b"value1"[..]), (b"", b"no key")] {
+        data.extend_from_slice(&(key.len() as u32).to_le_bytes());
+        data.extend_from_slice(key);
+        data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        data.extend_from_slice(value);
+    }
+
+    let mut reader = RecordReader::new(InputFormat::Kvbin, data.as_slice());
+    assert_eq!(
+        reader.next_record().unwrap(),
+        Some((b"key1".to_vec(), b"value1".to_vec()))
+    );
+    assert_eq!(
+        reader.next_record().unwrap(),
+        Some((Vec::new(), b"no key".to_vec()))
+    );
+    assert_eq!(reader.next_record().unwrap(), None);
+}
+
+#[test]
+fn test_truncated_records_are_errors() {
+    let mut reader = RecordReader::new(InputFormat::Gensort, &[0u8; 150][..]);
+    assert!(reader.next_record().unwrap().is_some());
+    assert!(reader.next_record().is_err());
+
+    // Key length says 8 bytes, only 3 follow
+    let mut data = 8u32.to_le_bytes().to_vec();
+    data.extend_from_slice(b"abc");
+    let mut reader = RecordReader::new(InputFormat::Kvbin, data.as_slice());
+    assert!(reader.next_record().is_err());
+}
+
+#[test]
+fn test_select_gensort_records() {
+    let path = "/tmp/test_records_select.dat";
+    fs::write(path, vec![0u8; 1_000]).unwrap();
+    let select = |skip, limit| {
+        records::select(
+            InputFormat::Gensort,
+            FixedLayout::GENSORT,
+            path.as_ref(),
+            skip,
+            limit,
+        )
+        .unwrap()
+    };
+    assert_eq!(select(0, None), 0..1_000);
+    assert_eq!(select(2, Some(5)), 200..700);
+    assert_eq!(select(8, Some(5)), 800..1_000);
+    assert_eq!(select(20, None), 1_000..1_000);
+
+    let layout = FixedLayout {
+        key_size: 16,
+        payload_size: 4,
+    };
+    let range = records::select(InputFormat::Gensort, layout, path.as_ref(), 10, Some(3)).unwrap();
+    assert_eq!(range, 200..260);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_window_reads_and_seeks_within_range() {
+    let data: Vec<u8> = (0..100).collect();
+    let mut window = Window::new(Cursor::new(&data), 10..30).unwrap();
+    let mut buf = Vec::new();
+    window.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, (10..30).collect::<Vec<u8>>());
+
+    assert_eq!(window.seek(SeekFrom::Start(5)).unwrap(), 5);
+    let mut byte = [0u8];
+    window.read_exact(&mut byte).unwrap();
+    assert_eq!(byte[0], 15);
+    assert_eq!(window.seek(SeekFrom::End(-2)).unwrap(), 18);
+    window.read_exact(&mut byte).unwrap();
+    assert_eq!(byte[0], 28);
+    assert!(window.seek(SeekFrom::Current(-100)).is_err());
+
+    // The whole range sits past the data: reads see nothing
+    let mut window = Window::new(Cursor::new(&data), 100..100).unwrap();
+    assert_eq!(window.read(&mut byte).unwrap(), 0);
+}
+
+#[test]
+fn test_fixed_records_read_and_mmap_agree() {
+    let path = "/tmp/test_records_fixed.dat";
+    let data: Vec<u8> = (0..10u8)
+        .flat_map(|i| [[i; 10].as_slice(), [b'P'; 90].as_slice()].concat())
+        .collect();
+    fs::write(path, &data).unwrap();
+
+    for mode in [Io::Read, Io::Mmap] {
+        let io = IoOptions {
+            mode,
+            ..IoOptions::default()
+        };
+        let mut reader =
+            FixedRecords::open(io, path.as_ref(), FixedLayout::GENSORT, 3..8, 1024).unwrap();
+        assert_eq!(reader.remaining(), 5);
+        assert_eq!(reader.next_record().unwrap().unwrap(), &data[300..400]);
+        assert_eq!(reader.next_records(3).unwrap(), &data[400..700]);
+        // Batches stop at the end of the range
+        assert_eq!(reader.next_records(3).unwrap(), &data[700..800]);
+        assert!(reader.next_records(3).unwrap().is_empty());
+        assert!(reader.next_record().unwrap().is_none());
+
+        let mut empty =
+            FixedRecords::open(io, path.as_ref(), FixedLayout::GENSORT, 10..10, 1024).unwrap();
+        assert!(empty.next_record().unwrap().is_none());
+    }
+
+    // Records past the end of the file are errors, not short reads
+    let mut reader = FixedRecords::open(
+        IoOptions::default(),
+        path.as_ref(),
+        FixedLayout::GENSORT,
+        8..12,
+        1024,
+    )
+    .unwrap();
+    reader.next_records(2).unwrap();
+    assert!(reader.next_record().is_err());
+    assert!(Mmap::open(path.as_ref(), 800..1_200).is_err());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_mmap_unaligned_range() {
+    let path = "/tmp/test_records_mmap.dat";
+    let data: Vec<u8> = (0..10_000u32).map(|i| i as u8).collect();
+    fs::write(path, &data).unwrap();
+    let map = Mmap::open(path.as_ref(), 4_099..9_001).unwrap();
+    map.advise_sequential().unwrap();
+    assert_eq!(&map[..], &data[4_099..9_001]);
+    fs::remove_file(path).unwrap();
+}
+
+#[cfg(all(feature = "util-uring", target_os = "linux"))]
+#[test]
+fn test_uring_reader_reads_range_in_order() {
+    use es_duck::uring::UringReader;
+
+    let path = "/tmp/test_records_uring.dat";
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(path, &data).unwrap();
+
+    // Blocks smaller than the range and a queue shallower than its blocks,
+    // so buffers are reused and the last block is short
+    let mut reader = UringReader::open(path.as_ref(), 1_234..98_765, 4, 4_096).unwrap();
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).unwrap();
+    assert!(buf == data[1_234..98_765]);
+
+    let io = IoOptions {
+        mode: Io::Uring,
+        queue_depth: 2,
+        ..IoOptions::default()
+    };
+    let mut records =
+        FixedRecords::open(io, path.as_ref(), FixedLayout::GENSORT, 5..995, 1024).unwrap();
+    assert_eq!(records.next_record().unwrap().unwrap(), &data[500..600]);
+    assert_eq!(records.next_records(989).unwrap(), &data[600..99_500]);
+    assert!(records.next_record().unwrap().is_none());
+
+    // A range past the end of the file fails once the reads get there
+    let mut reader = UringReader::open(path.as_ref(), 90_000..110_000, 4, 4_096).unwrap();
+    assert!(reader.read_to_end(&mut Vec::new()).is_err());
+    fs::remove_file(path).unwrap();
+}
+
+#[cfg(not(all(feature = "util-uring", target_os = "linux")))]
+#[test]
+fn test_uring_needs_feature() {
+    let path = "/tmp/test_records_uring_missing.dat";
+    fs::write(path, [0u8; 100]).unwrap();
+    let io = IoOptions {
+        mode: Io::Uring,
+        ..IoOptions::default()
+    };
+    let err = FixedRecords::open(io, path.as_ref(), FixedLayout::GENSORT, 0..1, 1024)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("util-uring"));
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_direct_reader_unaligned_range() {
+    // Under target/ rather than /tmp, which may be a tmpfs without O_DIRECT
+    let path = concat!(env!("CARGO_TARGET_TMPDIR"), "/test_records_direct.dat");
+    let data: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+    fs::write(path, &data).unwrap();
+
+    // Reads of 4KB, so the range spans several of them and ends mid-block
+    let mut reader = DirectReader::open(path.as_ref(), 4_095..17_001, 1).unwrap();
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).unwrap();
+    assert!(buf == data[4_095..17_001]);
+
+    let io = IoOptions {
+        direct_io: true,
+        ..IoOptions::default()
+    };
+    let mut records =
+        FixedRecords::open(io, path.as_ref(), FixedLayout::GENSORT, 3..200, 1 << 20).unwrap();
+    assert_eq!(records.next_records(197).unwrap(), &data[300..20_000]);
+    assert!(records.next_record().unwrap().is_none());
+
+    // Past the end of the file
+    let mut records =
+        FixedRecords::open(io, path.as_ref(), FixedLayout::GENSORT, 199..201, 1 << 20).unwrap();
+    assert!(records.next_records(2).is_err());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_io_options_check() {
+    let direct = IoOptions {
+        direct_io: true,
+        ..IoOptions::default()
+    };
+    assert!(direct.check(InputFormat::Gensort, false).is_ok());
+    assert!(direct.check(InputFormat::Kvbin, false).is_err());
+    let mmap = IoOptions {
+        mode: Io::Mmap,
+        ..direct
+    };
+    assert!(mmap.check(InputFormat::Gensort, false).is_err());
+    assert!(
+        IoOptions::default()
+            .check(InputFormat::Kvbin, false)
+            .is_ok()
+    );
+    assert!(direct.check(InputFormat::Gensort, true).is_err());
+    assert!(IoOptions::default().check(InputFormat::Kvbin, true).is_ok());
+
+    let skip_corrupt = IoOptions {
+        skip_corrupt: true,
+        ..IoOptions::default()
+    };
+    assert!(skip_corrupt.check(InputFormat::Kvbin, false).is_ok());
+    assert!(skip_corrupt.check(InputFormat::Gensort, false).is_err());
+    assert!(skip_corrupt.check(InputFormat::Kvbin, true).is_err());
+}
+
+/// kvbin records `first..first + count`: 4-byte keys, values as long as the record's number
+fn kvbin_records(data: &mut Vec<u8>, first: u32, count: u32) {
+    for i in first..first + count {
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&i.to_be_bytes());
+        data.extend_from_slice(&(i % 7).to_le_bytes());
+        data.extend(std::iter::repeat_n(b'v', (i % 7) as usize));
+    }
+}
+
+fn keys<R: Read + Seek>(records: &mut KvbinReader<R>) -> std::io::Result<Vec<u32>> {
+    let mut keys = Vec::new();
+    while let Some((key, _)) = records.next_record()? {
+        keys.push(u32::from_be_bytes(key.try_into().unwrap()));
+    }
+    Ok(keys)
+}
+
+#[test]
+fn test_kvbin_reader_checks_lengths() {
+    let mut data = Vec::new();
+    kvbin_records(&mut data, 0, 3);
+    let good = data.len() as u64;
+    let mut records = KvbinReader::new(Cursor::new(&data), 0..good, 1_000);
+    assert_eq!(keys(&mut records).unwrap(), vec![0, 1, 2]);
+    assert_eq!(records.offset(), good);
+
+    // A length over the limit fails before anything is allocated for it
+    data.extend_from_slice(&u32::MAX.to_le_bytes());
+    kvbin_records(&mut data, 3, 2);
+    let mut records = KvbinReader::new(Cursor::new(&data), 0..data.len() as u64, 1_000);
+    let err = keys(&mut records).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    let message = err.to_string();
+    assert!(message.contains(&format!("offset {}", good)), "{}", message);
+    assert!(message.contains(&u32::MAX.to_string()), "{}", message);
+
+    // So does a value running past the end of the range
+    let mut records = KvbinReader::new(Cursor::new(&data), 0..good - 1, 1_000);
+    let message = keys(&mut records).unwrap_err().to_string();
+    assert!(message.contains("value length"), "{}", message);
+}
+
+/// Hands out at most 1000 bytes per read, like a pipe
+struct Trickle<R>(R);
+
+impl<R: Read> Read for Trickle<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let max = buf.len().min(1000);
+        self.0.read(&mut buf[..max])
+    }
+}
+
+impl<R: Seek> Seek for Trickle<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.0.seek(pos)
+    }
+}
+
+#[test]
+fn test_kvbin_reader_parses_across_blocks() {
+    // Records straddling block boundaries, and one bigger than a block
+    let mut data = Vec::new();
+    kvbin_records(&mut data, 0, 200_000);
+    let big = data.len() as u64;
+    data.extend_from_slice(&4u32.to_le_bytes());
+    data.extend_from_slice(&200_000u32.to_be_bytes());
+    data.extend_from_slice(&(3 * records::KVBIN_BLOCK as u32).to_le_bytes());
+    data.extend(std::iter::repeat_n(b'b', 3 * records::KVBIN_BLOCK));
+    kvbin_records(&mut data, 200_001, 1_000);
+    assert!(big > 2 * records::KVBIN_BLOCK as u64);
+
+    let range = 0..data.len() as u64;
+    let mut records = KvbinReader::new(Trickle(Cursor::new(&data)), range, 1 << 30);
+    let mut count = 0u32;
+    while let Some((offset, key, value)) = records.next_record_at().unwrap() {
+        assert_eq!(key, count.to_be_bytes(), "record {}", count);
+        let len = if count == 200_000 {
+            assert_eq!(offset, big);
+            3 * records::KVBIN_BLOCK
+        } else {
+            (count % 7) as usize
+        };
+        assert_eq!(value.len(), len, "record {}", count);
+        assert!(value.iter().all(|&b| b == value[0]));
+        count += 1;
+    }
+    assert_eq!(count, 201_001);
+    assert_eq!(records.offset(), data.len() as u64);
+}
+
+#[test]
+fn test_kvbin_reader_skips_corrupt_records() {
+    let path = concat!(env!("CARGO_TARGET_TMPDIR"), "/test_records_corrupt.kvbin");
+    let mut data = Vec::new();
+    kvbin_records(&mut data, 0, 3);
+    data.extend_from_slice(&[0xff; 13]);
+    kvbin_records(&mut data, 3, 100);
+    // A record cut off at the end of the file
+    data.extend_from_slice(&[9, 0, 0, 0, 1]);
+    fs::write(path, &data).unwrap();
+
+    // Reading the whole file leaves the framing to the reader
+    let range = records::select(
+        InputFormat::Kvbin,
+        FixedLayout::GENSORT,
+        path.as_ref(),
+        0,
+        None,
+    )
+    .unwrap();
+    assert_eq!(range, 0..data.len() as u64);
+
+    let open = |options: IoOptions| {
+        options
+            .kvbin_reader(
+                Window::open(path.as_ref(), range.clone()).unwrap(),
+                path.as_ref(),
+                range.clone(),
+            )
+            .unwrap()
+    };
+    assert!(keys(&mut open(IoOptions::default())).is_err());
+
+    let mut records = open(IoOptions {
+        skip_corrupt: true,
+        ..IoOptions::default()
+    });
+    assert_eq!(keys(&mut records).unwrap(), (0..103).collect::<Vec<_>>());
+    assert_eq!(records.skipped(), 13 + 5);
+    assert_eq!(records.offset(), data.len() as u64);
+
+    // Record 3 starts after the corrupt bytes
+    let mut records = open(IoOptions {
+        skip_corrupt: true,
+        ..IoOptions::default()
+    });
+    let mut first = Vec::new();
+    kvbin_records(&mut first, 0, 3);
+    let offsets: Vec<u64> = (0..4)
+        .map(|_| records.next_record_at().unwrap().unwrap().0)
+        .collect();
+    assert_eq!(offsets, vec![0, 12, 25, first.len() as u64 + 13]);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_stream_input_reads_to_its_end() {
+    let fifo = "/tmp/test_records_stream.pipe";
+    let _ = fs::remove_file(fifo);
+    assert!(
+        std::process::Command::new("mkfifo")
+            .arg(fifo)
+            .status()
+            .unwrap()
+            .success()
+    );
+    assert!(records::is_stream(fifo.as_ref()).unwrap());
+    let select = |skip, limit| {
+        records::select(
+            InputFormat::Gensort,
+            FixedLayout::GENSORT,
+            fifo.as_ref(),
+            skip,
+            limit,
+        )
+    };
+    assert_eq!(select(0, None).unwrap(), records::STREAM);
+    assert!(select(1, None).is_err());
+    assert!(select(0, Some(1)).is_err());
+
+    // Two and a half records: the whole ones come out, then the cut is an error
+    let writer = std::thread::spawn(move || fs::write(fifo, [7u8; 250]).unwrap());
+    let records = records::STREAM.start / 100..records::STREAM.end / 100;
+    let mut reader = FixedRecords::open(
+        IoOptions::default(),
+        fifo.as_ref(),
+        FixedLayout::GENSORT,
+        records,
+        64,
+    )
+    .unwrap();
+    assert_eq!(reader.next_records(2).unwrap(), &[7u8; 200][..]);
+    assert!(reader.next_records(2).is_err());
+    drop(reader);
+    writer.join().unwrap();
+
+    // A stream read through a window, as the kvbin loaders do
+    let writer = std::thread::spawn(move || fs::write(fifo, b"abc").unwrap());
+    let mut buf = Vec::new();
+    Window::open(fifo.as_ref(), records::STREAM)
+        .unwrap()
+        .read_to_end(&mut buf)
+        .unwrap();
+    assert_eq!(buf, b"abc");
+    writer.join().unwrap();
+    fs::remove_file(fifo).unwrap();
+}
@@ -0,0 +1,73 @@
+use es_duck::results::{self, RunResult, Verdict};
+
+fn run(engine: &str, threads: u32, status: &str, seconds: Option<f64>) -> RunResult {
+    RunResult {
+        engine: engine.to_string(),
+        memory_limit: "2GB".to_string(),
+        threads,
+        status: status.to_string(),
+        seconds,
+        peak_rss_mb: None,
+    }
+}
+
+#[test]
+fn test_parse_threshold() {
+    assert_eq!(results::parse_threshold("5%").unwrap(), 5.0);
+    assert_eq!(results::parse_threshold("2.5").unwrap(), 2.5);
+    assert!(results::parse_threshold("-1%").is_err());
+    assert!(results::parse_threshold("five").is_err());
+}
+
+#[test]
+fn test_parse_harness_outputs() {
+    let csv = "engine,memory_limit,threads,seconds,peak_rss_mb\nduckdb,2GB,8,12.50,1024.0\npostgres,2GB,8,TIMEOUT,\n";
+    let parsed = results::parse_csv(csv).unwrap();
+    assert_eq!(parsed[0].seconds, Some(12.5));
+    assert_eq!(parsed[0].peak_rss_mb, Some(1024.0));
+    assert_eq!(parsed[1].status, "timeout");
+    assert_eq!(parsed[1].seconds, None);
+
+    let json = r#"[
+  {"engine": "duckdb", "memory_limit": "2GB", "threads": 8, "status": "ok", "seconds": 12.50, "peak_rss_mb": 1024.0},
+  {"engine": "postgres", "memory_limit": "2GB", "threads": 8, "status": "timeout", "seconds": null, "peak_rss_mb": null}
+]"#;
+    let from_json: Vec<RunResult> = serde_json::from_str(json).unwrap();
+    assert_eq!(from_json, parsed);
+}
+
+#[test]
+fn test_compare_results() {
+    let baseline = vec![
+        run("duckdb", 8, "ok", Some(10.0)),
+        run("postgres", 8, "ok", Some(20.0)),
+        run("clickhouse", 8, "ok", Some(5.0)),
+        run("duckdb", 16, "ok", Some(8.0)),
+        run("postgres", 16, "failed", None),
+    ];
+    let current = vec![
+        run("duckdb", 8, "ok", Some(10.4)),
+        run("postgres", 8, "ok", Some(22.0)),
+        run("clickhouse", 8, "timeout", None),
+        run("postgres", 16, "ok", Some(30.0)),
+    ];
+
+    let verdicts: Vec<Verdict> = results::compare(&baseline, &current, 5.0)
+        .iter()
+        .map(|c| c.verdict)
+        .collect();
+    assert_eq!(
+        verdicts,
+        vec![
+            Verdict::Ok,
+            Verdict::Regression,
+            Verdict::Regression,
+            Verdict::Missing,
+            Verdict::NoBaseline,
+        ]
+    );
+
+    let comparisons = results::compare(&baseline, &current, 15.0);
+    assert!((comparisons[1].change_pct.unwrap() - 10.0).abs() < 1e-9);
+    assert_eq!(comparisons[1].verdict, Verdict::Ok);
+}
@@ -0,0 +1,105 @@
+use es_duck::rowbinary::{self, ColumnType, Encoder, Value};
+
+fn varint(value: u64) -> Vec<u8> {
+    let mut buf = Vec::new();
+    rowbinary::write_varint(&mut buf, value);
+    buf
+}
+
+#[test]
+fn test_varint_encoding() {
+    assert_eq!(varint(0), vec![0x00]);
+    assert_eq!(varint(10), vec![0x0A]);
+    assert_eq!(varint(127), vec![0x7F]);
+    assert_eq!(varint(128), vec![0x80, 0x01]);
+    assert_eq!(varint(300), vec![0xAC, 0x02]);
+    assert_eq!(varint(16_384), vec![0x80, 0x80, 0x01]);
+    let max = varint(u64::MAX);
+    assert_eq!(max.len(), 10);
+    assert_eq!(max[9], 0x01);
+}
+
+#[test]
+fn test_encode_rows() {
+    let mut encoder = Encoder::new(vec![
+        ColumnType::String,
+        ColumnType::FixedString(4),
+        ColumnType::UInt64,
+    ]);
+    encoder
+        .encode_row(&[Value::Bytes(b"ab"), Value::Bytes(b"xy"), Value::UInt64(258)])
+        .unwrap();
+    assert_eq!(encoder.rows(), 1);
+
+    let mut expected = vec![2, b'a', b'b', b'x', b'y', 0, 0];
+    expected.extend_from_slice(&[2, 1, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(encoder.len(), expected.len());
+    assert_eq!(encoder.take(), expected);
+    assert!(encoder.is_empty());
+    assert_eq!(encoder.rows(), 0);
+
+    // Long strings get a multi-byte length prefix
+    let long = vec![7u8; 200];
+    encoder
+        .encode_row(&[Value::Bytes(&long), Value::Bytes(b"abcd"), Value::UInt64(0)])
+        .unwrap();
+    let encoded = encoder.take();
+    assert_eq!(&encoded[..2], &[0xC8, 0x01]);
+    assert_eq!(encoded.len(), 2 + 200 + 4 + 8);
+}
+
+#[test]
+fn test_encode_rejects_bad_rows() {
+    let mut encoder = Encoder::new(vec![ColumnType::String, ColumnType::FixedString(2)]);
+    encoder
+        .encode_row(&[Value::Bytes(b"ok"), Value::Bytes(b"ok")])
+        .unwrap();
+    let before = encoder.len();
+
+    // Wrong arity, wrong type and a value longer than the FixedString
+    assert!(encoder.encode_row(&[Value::Bytes(b"a")]).is_err());
+    assert!(
+        encoder
+            .encode_row(&[Value::UInt64(1), Value::Bytes(b"a")])
+            .is_err()
+    );
+    assert!(
+        encoder
+            .encode_row(&[Value::Bytes(b"a"), Value::Bytes(b"abc")])
+            .is_err()
+    );
+
+    // Failed rows leave nothing behind
+    assert_eq!(encoder.len(), before);
+    assert_eq!(encoder.rows(), 1);
+}
+
+#[test]
+fn test_column_type_names() {
+    assert_eq!(ColumnType::String.to_string(), "String");
+    assert_eq!(ColumnType::FixedString(10).to_string(), "FixedString(10)");
+    assert_eq!(ColumnType::UInt64.to_string(), "UInt64");
+}
+
+#[test]
+fn test_encode_signed_and_float_columns() {
+    let mut encoder = Encoder::new(vec![
+        ColumnType::Int32,
+        ColumnType::Int64,
+        ColumnType::Float64,
+    ]);
+    encoder
+        .encode_row(&[Value::Int32(-2), Value::Int64(3), Value::Float64(0.5)])
+        .unwrap();
+    let mut expected = vec![0xFE, 0xFF, 0xFF, 0xFF];
+    expected.extend_from_slice(&[3, 0, 0, 0, 0, 0, 0, 0]);
+    expected.extend_from_slice(&0.5f64.to_le_bytes());
+    assert_eq!(encoder.take(), expected);
+
+    assert!(
+        encoder
+            .encode_row(&[Value::Int64(1), Value::Int64(1), Value::Float64(1.0)])
+            .is_err()
+    );
+    assert_eq!(ColumnType::Float64.to_string(), "Float64");
+}
@@ -0,0 +1,35 @@
+use es_duck::rss::{self, PeakSampler};
+use std::time::Duration;
+
+#[test]
+fn test_parse_status_kb() {
+    let status =
+        "Name:\tpostgres\nVmHWM:\t  204800 kB\nVmRSS:\t  102400 kB\nRssAnon:\t    4096 kB\n";
+    assert_eq!(rss::parse_status_kb(status, "VmHWM"), Some(204800 * 1024));
+    assert_eq!(rss::parse_status_kb(status, "RssAnon"), Some(4096 * 1024));
+    // Field names must match exactly, not just as a prefix
+    assert_eq!(rss::parse_status_kb(status, "VmR"), None);
+    assert_eq!(rss::parse_status_kb(status, "Name"), None);
+    assert_eq!(rss::parse_status_kb(status, "VmSwap"), None);
+}
+
+#[test]
+fn test_self_peak_rss() {
+    let peak = rss::self_peak_rss().expect("VmHWM should be readable on Linux");
+    assert!(peak > 0);
+    assert_eq!(
+        rss::private_rss(std::process::id(), "no-such-command"),
+        None
+    );
+}
+
+#[test]
+fn test_peak_sampler_keeps_maximum() {
+    let mut samples = vec![Some(10), None, Some(30), Some(20)].into_iter();
+    let sampler = PeakSampler::start(Duration::from_millis(1), move || samples.next().flatten());
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(sampler.finish(), Some(30));
+
+    let sampler = PeakSampler::start(Duration::from_millis(1), || None);
+    assert_eq!(sampler.finish(), None);
+}
@@ -0,0 +1,46 @@
+use es_duck::runs::{self, Runs};
+use std::time::Duration;
+
+#[test]
+fn test_median_run() {
+    let secs = |s: &[u64]| -> Vec<Duration> { s.iter().map(|&s| Duration::from_secs(s)).collect() };
+    assert_eq!(runs::median(&secs(&[5])), Duration::from_secs(5));
+    assert_eq!(runs::median(&secs(&[9, 1, 4])), Duration::from_secs(4));
+    // The lower middle run of an even count
+    assert_eq!(runs::median(&secs(&[8, 2, 6, 4])), Duration::from_secs(4));
+    assert_eq!(runs::median(&[]), Duration::ZERO);
+}
+
+#[test]
+fn test_report_labels_each_run() {
+    let times = [Duration::from_millis(2500), Duration::from_millis(1250)];
+    let warm = Runs {
+        count: 2,
+        cold: false,
+    };
+    assert_eq!(
+        warm.report(&times).unwrap(),
+        "RUN 1: 2.50 seconds (first)\nRUN 2: 1.25 seconds (warm)\n\
+         RUNS: 2\nRUN_MIN: 1.25\nRUN_MAX: 2.50"
+    );
+    let cold = Runs {
+        count: 2,
+        cold: true,
+    };
+    assert!(
+        cold.report(&times)
+            .unwrap()
+            .contains("RUN 2: 1.25 seconds (cold)")
+    );
+
+    // A single run prints just TIMING, as before
+    assert_eq!(Runs::default().report(&times[..1]), None);
+    assert!(
+        Runs {
+            count: 0,
+            cold: false
+        }
+        .check()
+        .is_err()
+    );
+}
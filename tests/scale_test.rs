@@ -0,0 +1,68 @@
+use es_duck::scale::{self, ScaleOptions};
+
+fn copies(scale: ScaleOptions, key: &[u8]) -> Vec<Vec<u8>> {
+    let mut keys = Vec::new();
+    scale
+        .each_copy(key, b"payload", |copy, key, payload| {
+            assert_eq!(payload, b"payload");
+            assert_eq!(copy as usize, keys.len());
+            keys.push(key.to_vec());
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+    keys
+}
+
+#[test]
+fn test_scale_copies() {
+    let tied = ScaleOptions {
+        scale_factor: 3,
+        perturb_keys: false,
+    };
+    assert_eq!(copies(tied, b"key"), vec![b"key".to_vec(); 3]);
+    assert_eq!(tied.rows(10), 30);
+    assert_eq!((tied.row_id(0, 2), tied.row_id(1, 0)), (Ok(2), Ok(3)));
+    assert!(tied.is_scaled() && tied.check().is_ok());
+    // Ids past a BIGINT are refused rather than wrapped
+    assert!(tied.row_id(u64::MAX / 2, 0).is_err());
+    assert!(tied.row_id(i64::MAX as u64 / 3, 2).is_err());
+    assert_eq!(tied.row_id(i64::MAX as u64 / 3, 1), Ok(i64::MAX as u64));
+    assert!(!ScaleOptions::default().is_scaled());
+    assert!(
+        ScaleOptions {
+            scale_factor: 0,
+            ..tied
+        }
+        .check()
+        .is_err()
+    );
+
+    // Copy 0 is the record itself; the others differ in the last 4 bytes
+    let perturbed = ScaleOptions {
+        perturb_keys: true,
+        ..tied
+    };
+    let keys = copies(perturbed, b"0000000042");
+    assert_eq!(keys[0], b"0000000042");
+    assert!(keys.iter().all(|key| key.starts_with(b"000000")));
+    assert!(keys[1] != keys[0] && keys[2] != keys[0] && keys[1] != keys[2]);
+}
+
+#[test]
+fn test_perturbed_sequential_keys_stay_unique() {
+    let mut keys = std::collections::HashSet::new();
+    for i in 0..1000u32 {
+        for copy in 0..8 {
+            let mut key = format!("{:010}", i).into_bytes();
+            scale::perturb_key(&mut key, copy);
+            assert!(keys.insert(key));
+        }
+    }
+
+    // A short key is perturbed as a whole
+    let mut key = [0x00];
+    scale::perturb_key(&mut key, 1);
+    assert_eq!(key, [0x9e]);
+    let mut key: [u8; 0] = [];
+    scale::perturb_key(&mut key, 5);
+}
@@ -0,0 +1,154 @@
+use es_duck::schema::{self, ColumnNames, ExtraColumn, OrderBy, Schema, SortMode, WideRow};
+
+#[test]
+fn test_wide_row_round_trip() {
+    let mut payload = vec![0xAAu8; 50];
+    let row = WideRow {
+        c_int: -7,
+        c_bigint: 1 << 40,
+        c_double: 0.25,
+        c_text: "sort",
+        payload: &[],
+    };
+    row.encode_columns(&mut payload).unwrap();
+
+    let decoded = WideRow::decode(&payload).unwrap();
+    assert_eq!(decoded.c_int, -7);
+    assert_eq!(decoded.c_bigint, 1 << 40);
+    assert_eq!(decoded.c_double, 0.25);
+    assert_eq!(decoded.c_text, "sort");
+    assert_eq!(decoded.payload, &[0xAA; 14]);
+
+    assert!(WideRow::decode(&payload[..35]).is_err());
+    let long = WideRow {
+        c_text: "sixteen letters!",
+        ..row
+    };
+    assert!(long.encode_columns(&mut payload).is_err());
+    // A length byte past the text field
+    payload[20] = 16;
+    assert!(WideRow::decode(&payload).is_err());
+}
+
+#[test]
+fn test_fill_wide_columns_are_in_range() {
+    let mut payload = [0u8; 40];
+    for seed in 0..1000u64 {
+        // Arbitrary bytes, as the generator's ChaCha8 output would be
+        let mut state = seed.wrapping_mul(0x9e3779b97f4a7c15);
+        for b in payload.iter_mut() {
+            state = state.rotate_left(7).wrapping_mul(0xbf58476d1ce4e5b9) ^ seed;
+            *b = (state >> 56) as u8;
+        }
+        let tail = payload[36..].to_vec();
+        schema::fill_wide(&mut payload);
+
+        let row = WideRow::decode(&payload).unwrap();
+        assert!((0..schema::WIDE_INT_VALUES as i32).contains(&row.c_int));
+        assert!((0.0..1.0).contains(&row.c_double));
+        assert!((4..=15).contains(&row.c_text.len()));
+        assert!(row.c_text.bytes().all(|c| c.is_ascii_lowercase()));
+        assert_eq!(row.payload, tail);
+    }
+}
+
+#[test]
+fn test_column_names() {
+    let defaults = ColumnNames::default();
+    assert_eq!(defaults.list(Schema::KeyPayload), "sort_key, payload");
+    assert_eq!(defaults.select(), "sort_key, payload");
+
+    let custom = ColumnNames {
+        key: "k".to_string(),
+        payload: "v".to_string(),
+    };
+    assert_eq!(
+        custom.list(Schema::Wide),
+        "k, c_int, c_bigint, c_double, c_text, v"
+    );
+    // Output files keep the usual names
+    assert_eq!(custom.select(), "k AS sort_key, v AS payload");
+    assert_eq!(custom.select_key(), "k AS sort_key");
+    assert_eq!(defaults.select_key(), "sort_key");
+}
+
+#[test]
+fn test_order_by() {
+    let names = ColumnNames::default();
+    let key_only = OrderBy::default();
+    assert_eq!(key_only.clause(&names), "sort_key");
+    assert_eq!(key_only.union_columns(&names, false), "sort_key, payload");
+    assert_eq!(
+        key_only.union_columns(&names, true),
+        "sort_key, payload, row_id"
+    );
+
+    let compound = OrderBy {
+        columns: vec![
+            "c_int".to_string(),
+            " c_text DESC".to_string(),
+            "sort_key".to_string(),
+        ],
+    };
+    assert!(compound.check().is_ok());
+    assert_eq!(compound.clause(&names), "c_int, c_text DESC, sort_key");
+    // Shards carry the ordering columns, once each
+    assert_eq!(
+        compound.union_columns(&names, false),
+        "sort_key, payload, c_int, c_text"
+    );
+
+    let empty = OrderBy {
+        columns: vec!["c_int".to_string(), "".to_string()],
+    };
+    assert!(empty.check().is_err());
+}
+
+#[test]
+fn test_sort_mode() {
+    assert_eq!(SortMode::Sort.select("sort_key"), "");
+    assert_eq!(SortMode::Distinct.select("k"), "DISTINCT ON (k) ");
+
+    let compound = OrderBy {
+        columns: vec!["c_int".to_string(), "sort_key".to_string()],
+    };
+    assert!(SortMode::Sort.check(&compound).is_ok());
+    assert!(SortMode::Distinct.check(&OrderBy::default()).is_ok());
+    assert_eq!(
+        SortMode::Distinct.check(&compound),
+        Err("--mode distinct keeps only the key: drop --order-by".to_string())
+    );
+}
+
+#[test]
+fn test_extra_column() {
+    let column: ExtraColumn = " tenant_id  DECIMAL(10, 2) ".parse().unwrap();
+    assert_eq!(column.name, "tenant_id");
+    assert_eq!(column.sql_type, "DECIMAL(10, 2)");
+    assert_eq!(
+        column.add_sql("bench_data"),
+        "ALTER TABLE bench_data ADD COLUMN IF NOT EXISTS tenant_id DECIMAL(10, 2)"
+    );
+
+    assert!("tenant_id".parse::<ExtraColumn>().is_err());
+    assert!("".parse::<ExtraColumn>().is_err());
+}
+
+#[test]
+fn test_numeric_keys_keep_key_order() {
+    assert_eq!(schema::key_u64(&[0, 0, 0, 0, 0, 0, 1, 0, 0xff, 0xff]), 256);
+    assert_eq!(schema::key_u64(&[1]), 1 << 56);
+    assert_eq!(schema::key_u64(&[]), 0);
+    assert_eq!(schema::key_i64(&[]), i64::MIN);
+    assert_eq!(schema::key_i64(&[0xff; 10]), i64::MAX);
+
+    let keys: [&[u8]; 5] = [b"", &[0x00, 0x01], &[0x7f, 0xff], &[0x80], &[0xff, 0x00]];
+    assert!(
+        keys.windows(2)
+            .all(|w| schema::key_u64(w[0]) < schema::key_u64(w[1]))
+    );
+    assert!(
+        keys.windows(2)
+            .all(|w| schema::key_i64(w[0]) < schema::key_i64(w[1]))
+    );
+}
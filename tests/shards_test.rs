@@ -0,0 +1,93 @@
+use es_duck::records::{FixedLayout, InputFormat, IoOptions};
+use es_duck::shards::{self, Router, ShardBy};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[test]
+fn test_shard_routing() {
+    let mut router = Router::new(ShardBy::RoundRobin, 3, 1);
+    let picks: Vec<usize> = (0..6).map(|_| router.route(b"any")).collect();
+    assert_eq!(picks, vec![1, 2, 0, 1, 2, 0]);
+
+    // Key ranges split on the first byte and keep shards in key order
+    let mut router = Router::new(ShardBy::KeyRange, 4, 0);
+    assert_eq!(router.route(b""), 0);
+    assert_eq!(router.route(&[0x00, 0xff]), 0);
+    assert_eq!(router.route(&[0x3f]), 0);
+    assert_eq!(router.route(&[0x40]), 1);
+    assert_eq!(router.route(&[0xbf]), 2);
+    assert_eq!(router.route(&[0xff]), 3);
+    let by_byte: Vec<usize> = (0..=255u8)
+        .map(|b| shards::key_range_shard(&[b], 7))
+        .collect();
+    assert!(by_byte.windows(2).all(|w| w[0] <= w[1]));
+    assert_eq!((by_byte[0], by_byte[255]), (0, 6));
+
+    assert!(shards::check(0, ShardBy::RoundRobin).is_err());
+    assert!(shards::check(1000, ShardBy::RoundRobin).is_ok());
+    assert!(shards::check(256, ShardBy::KeyRange).is_ok());
+    assert!(shards::check(257, ShardBy::KeyRange).is_err());
+}
+
+#[test]
+fn test_shard_route_reads_every_record() {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("shards_route.gensort");
+    let mut data = Vec::new();
+    for i in 0..10u8 {
+        data.extend_from_slice(&[i * 25; 10]);
+        data.extend_from_slice(&[b'p'; 90]);
+    }
+    fs::write(&path, &data).unwrap();
+
+    let ranges = shards::reader_ranges(InputFormat::Gensort, FixedLayout::GENSORT, 0..1000, 3);
+    assert_eq!(ranges, vec![0..400, 400..800, 800..1000]);
+    assert_eq!(
+        shards::reader_ranges(InputFormat::Kvbin, FixedLayout::GENSORT, 0..1000, 3),
+        vec![0..1000]
+    );
+
+    let mut counts = [0u64; 2];
+    let mut keys = Vec::new();
+    for (reader, range) in ranges.into_iter().enumerate() {
+        let mut router = Router::new(ShardBy::KeyRange, 2, reader);
+        let rows = shards::route(
+            &path,
+            InputFormat::Gensort,
+            FixedLayout::GENSORT,
+            IoOptions::default(),
+            range,
+            &mut router,
+            |shard, id, key, payload| {
+                assert_eq!((key.len(), payload.len()), (10, 90));
+                assert_eq!(id, keys.len() as u64);
+                assert_eq!(shard, shards::key_range_shard(key, 2));
+                counts[shard] += 1;
+                keys.push(key[0]);
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert!(rows > 0);
+    }
+    // Keys 0, 25, .., 125 go to shard 0, 150 .. 225 to shard 1
+    assert_eq!(counts, [6, 4]);
+    assert_eq!(keys, (0..10u8).map(|i| i * 25).collect::<Vec<_>>());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_shard_sql_and_outputs() {
+    assert_eq!(shards::table_name("bench", 2), "bench_shard2");
+    assert_eq!(
+        shards::union_all("bench", 2, "k, v"),
+        "(SELECT k, v FROM bench_shard0 UNION ALL SELECT k, v FROM bench_shard1) AS shards"
+    );
+    assert_eq!(
+        shards::output_path(Path::new("/out/sorted.parquet"), 3),
+        Path::new("/out/sorted.shard3.parquet")
+    );
+    assert_eq!(
+        shards::output_path(Path::new("sorted"), 0),
+        Path::new("sorted.shard0")
+    );
+}
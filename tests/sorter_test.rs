@@ -0,0 +1,120 @@
+use es_duck::schema::ColumnNames;
+use es_duck::sorter::{self, Registry, SortOptions, SortResult, Sorter, session};
+use std::error::Error;
+use std::path::Path;
+
+/// Pretends to sort `rows` rows; `prepared` guards against sorting unprepared sessions
+struct FakeSorter {
+    rows: u64,
+    prepared: bool,
+}
+
+impl Sorter for FakeSorter {
+    fn prepare(&mut self) -> Result<u64, Box<dyn Error + Send + Sync>> {
+        self.prepared = true;
+        Ok(self.rows)
+    }
+
+    fn sort(&mut self) -> Result<SortResult, Box<dyn Error + Send + Sync>> {
+        if !self.prepared {
+            return Err("sort before prepare".into());
+        }
+        Ok(SortResult {
+            rows: self.rows,
+            seconds: 1.5,
+            peak_rss_bytes: Some(1024),
+        })
+    }
+}
+
+fn options() -> SortOptions {
+    SortOptions {
+        connection: "fake".to_string(),
+        database: "default".to_string(),
+        table: "bench_data".to_string(),
+        columns: ColumnNames::default(),
+        memory_limit: "1GB".to_string(),
+        threads: Some(4),
+        output: None,
+        temp_dir: None,
+        settings: vec![("a".to_string(), "1".to_string())],
+    }
+}
+
+#[test]
+fn test_registry_runs_sorter() {
+    let mut registry = Registry::new();
+    registry.register("fake", |options| {
+        Ok(Box::new(FakeSorter {
+            rows: options.threads.unwrap_or(1) as u64 * 100,
+            prepared: false,
+        }))
+    });
+    assert_eq!(registry.names(), vec!["fake"]);
+
+    let mut backend = registry.create("fake", &options()).unwrap();
+    let result = sorter::run(backend.as_mut()).unwrap();
+    assert_eq!(
+        result,
+        SortResult {
+            rows: 400,
+            seconds: 1.5,
+            peak_rss_bytes: Some(1024),
+        }
+    );
+
+    let err = registry.create("other", &options()).err().unwrap();
+    assert!(err.to_string().contains("available: fake"), "got: {}", err);
+}
+
+#[test]
+fn test_parse_setting() {
+    assert_eq!(
+        sorter::parse_setting("work_mem='64MB'").unwrap(),
+        ("work_mem".to_string(), "'64MB'".to_string())
+    );
+    assert_eq!(
+        sorter::parse_setting(" threads = 4 ").unwrap(),
+        ("threads".to_string(), "4".to_string())
+    );
+    assert!(sorter::parse_setting("novalue").is_err());
+    assert!(sorter::parse_setting("=1").is_err());
+}
+
+#[test]
+fn test_setting_literal() {
+    assert_eq!(sorter::setting_literal("64kB"), "'64kB'");
+    assert_eq!(sorter::setting_literal("it's; DROP"), "'it''s; DROP'");
+}
+
+#[test]
+fn test_session_settings() {
+    let settings = vec![("jit".to_string(), "off".to_string())];
+
+    let postgres = session::postgres(1024, 4, &settings);
+    assert_eq!(postgres[0], "SET LOCAL work_mem = '1024kB'");
+    assert_eq!(postgres[1], "SET LOCAL max_parallel_workers_per_gather = 4");
+    assert!(postgres.contains(&"SET LOCAL enable_parallel_append = on".to_string()));
+    assert_eq!(postgres.last().unwrap(), "SET LOCAL jit = 'off'");
+
+    let duckdb = session::duckdb("4GB", Some(2), Some(Path::new("/tmp/it's")), &settings);
+    assert_eq!(
+        duckdb,
+        [
+            "SET threads = 2",
+            "SET temp_directory = '/tmp/it''s'",
+            "SET memory_limit = '4GB'",
+            "SET jit = 'off'",
+        ]
+    );
+
+    // --set values are quoted here too, like the other engines
+    assert_eq!(
+        session::clickhouse(1 << 30, None, &settings),
+        [
+            "max_bytes_before_external_sort = 1073741824",
+            "max_bytes_ratio_before_external_sort = 0",
+            "jit = 'off'",
+        ]
+    );
+}
@@ -0,0 +1,54 @@
+use es_duck::spill::{self, DirUsage, SpillWatcher};
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_dir_usage_counts_nested_files() {
+    let dir = PathBuf::from(concat!(env!("CARGO_TARGET_TMPDIR"), "/test_spill_usage"));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    fs::write(dir.join("a.tmp"), [0u8; 1000]).unwrap();
+    fs::write(dir.join("nested/b.tmp"), [0u8; 24]).unwrap();
+
+    assert_eq!(
+        spill::dir_usage(&dir),
+        DirUsage {
+            bytes: 1024,
+            files: 2
+        }
+    );
+    // A directory that is not there (yet) holds nothing
+    assert_eq!(spill::dir_usage(&dir.join("missing")), DirUsage::default());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_watcher_keeps_the_peak() {
+    let dir = PathBuf::from(concat!(env!("CARGO_TARGET_TMPDIR"), "/test_spill_watcher"));
+    let _ = fs::remove_dir_all(&dir);
+    let watcher = SpillWatcher::start(dir.clone(), Duration::from_millis(5));
+
+    fs::create_dir_all(&dir).unwrap();
+    for i in 0..3 {
+        fs::write(dir.join(format!("{}.tmp", i)), vec![0u8; 1 << 20]).unwrap();
+    }
+    thread::sleep(Duration::from_millis(100));
+    // Spill files are gone by the time the sort finishes
+    fs::remove_dir_all(&dir).unwrap();
+    thread::sleep(Duration::from_millis(20));
+
+    let peak = watcher.finish();
+    assert_eq!(
+        peak,
+        DirUsage {
+            bytes: 3 << 20,
+            files: 3
+        }
+    );
+    assert_eq!(
+        spill::report(peak, DirUsage::default()),
+        "SPILL_PEAK_MB: 3.0\nSPILL_PEAK_FILES: 3\nSPILL_HELD_MB: 0.0\nSPILL_HELD_FILES: 0"
+    );
+}
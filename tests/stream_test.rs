@@ -0,0 +1,213 @@
+#![cfg(feature = "util-async")]
+
+use es_duck::records::{InputFormat, RecordReader};
+use es_duck::stream::{ByteBudget, ChannelReader, RecordStream, UploadProgress};
+use std::io::{self, Read};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+
+/// Gensort input that counts how many bytes have been read from it
+struct CountingInput {
+    remaining: usize,
+    read: Arc<AtomicUsize>,
+}
+
+impl Read for CountingInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.remaining);
+        buf[..n].fill(b'x');
+        self.remaining -= n;
+        self.read.fetch_add(n, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+#[tokio::test]
+async fn test_stream_yields_all_batches() {
+    let read = Arc::new(AtomicUsize::new(0));
+    let input = CountingInput {
+        remaining: 25 * 100,
+        read: read.clone(),
+    };
+    let mut stream =
+        RecordStream::from_reader(RecordReader::new(InputFormat::Gensort, input), 10, 2);
+
+    let mut sizes = Vec::new();
+    while let Some(batch) = stream.next_batch().await {
+        sizes.push(batch.unwrap().len());
+    }
+    assert_eq!(sizes, vec![10, 10, 5]);
+    assert_eq!(read.load(Ordering::Relaxed), 25 * 100);
+}
+
+#[tokio::test]
+async fn test_stream_applies_backpressure() {
+    let read = Arc::new(AtomicUsize::new(0));
+    let input = CountingInput {
+        remaining: 1000 * 100,
+        read: read.clone(),
+    };
+    let mut stream =
+        RecordStream::from_reader(RecordReader::new(InputFormat::Gensort, input), 1, 2);
+
+    // Nothing is consumed: the reader stops after filling the channel plus the batch it is sending
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(
+        read.load(Ordering::Relaxed) <= 3 * 100,
+        "read {} bytes",
+        read.load(Ordering::Relaxed)
+    );
+
+    let mut rows = 0;
+    while let Some(batch) = stream.next_batch().await {
+        rows += batch.unwrap().len();
+    }
+    assert_eq!(rows, 1000);
+}
+
+#[tokio::test]
+async fn test_stream_reports_errors() {
+    let mut stream =
+        RecordStream::open(InputFormat::Kvbin, "/nonexistent/input.kvbin".into(), 10, 2);
+    assert!(stream.next_batch().await.unwrap().is_err());
+    assert!(stream.next_batch().await.is_none());
+
+    // Truncated record after one good batch
+    let mut stream = RecordStream::from_reader(
+        RecordReader::new(InputFormat::Gensort, &[0u8; 150][..]),
+        1,
+        2,
+    );
+    assert_eq!(stream.next_batch().await.unwrap().unwrap().len(), 1);
+    assert!(stream.next_batch().await.unwrap().is_err());
+    assert!(stream.next_batch().await.is_none());
+}
+
+#[tokio::test]
+async fn test_channel_reader_counts_sent_rows() {
+    // Row sizes vary (kvbin), so the byte count says nothing about the row count
+    let (tx, rx) = tokio::sync::mpsc::channel(2);
+    let producer = tokio::spawn(async move {
+        for rows in [3u64, 250, 1] {
+            tx.send((vec![0u8; 17 * rows as usize], rows))
+                .await
+                .unwrap();
+        }
+    });
+
+    let total_rows = Arc::new(AtomicU64::new(0));
+    let mut reader = ChannelReader::new(rx, total_rows.clone());
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body).await.unwrap();
+    producer.await.unwrap();
+
+    assert_eq!(body.len(), 17 * 254);
+    assert_eq!(total_rows.load(Ordering::Relaxed), 254);
+}
+
+/// A chunk that carries more than its bytes and row count
+struct TaggedChunk {
+    data: Vec<u8>,
+    rows: u64,
+    _tag: &'static str,
+}
+
+impl From<TaggedChunk> for (Vec<u8>, u64) {
+    fn from(chunk: TaggedChunk) -> Self {
+        (chunk.data, chunk.rows)
+    }
+}
+
+#[tokio::test]
+async fn test_channel_reader_takes_convertible_chunks() {
+    let (tx, rx) = tokio::sync::mpsc::channel(2);
+    let producer = tokio::spawn(async move {
+        for (byte, tag) in [(1u8, "a"), (2, "b")] {
+            let chunk = TaggedChunk {
+                data: vec![byte; 4],
+                rows: 2,
+                _tag: tag,
+            };
+            tx.send(chunk).await.unwrap();
+        }
+    });
+
+    let total_rows = Arc::new(AtomicU64::new(0));
+    let mut reader = ChannelReader::new(rx, total_rows.clone());
+    let mut body = Vec::new();
+    reader.read_to_end(&mut body).await.unwrap();
+    producer.await.unwrap();
+
+    assert_eq!(body, [1, 1, 1, 1, 2, 2, 2, 2]);
+    assert_eq!(total_rows.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn test_byte_budget_blocks_until_bytes_are_freed() {
+    let budget = ByteBudget::new(100);
+    let first = budget.acquire(60);
+    let second = budget.acquire(40);
+    assert_eq!(budget.used(), 100);
+
+    // A third chunk waits for room, whatever the chunk count
+    let acquired = Arc::new(AtomicUsize::new(0));
+    let waiter = {
+        let (budget, acquired) = (budget.clone(), acquired.clone());
+        std::thread::spawn(move || {
+            let permit = budget.acquire(50);
+            acquired.store(1, Ordering::Relaxed);
+            permit
+        })
+    };
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(acquired.load(Ordering::Relaxed), 0);
+    drop(first);
+    let third = waiter.join().unwrap();
+    assert_eq!(budget.used(), 90);
+    drop((second, third));
+    assert_eq!((budget.used(), budget.peak()), (0, 100));
+
+    // A chunk larger than the whole budget goes alone instead of waiting forever
+    let oversized = budget.acquire(250);
+    assert_eq!(budget.peak(), 250);
+    drop(oversized);
+    assert_eq!(budget.used(), 0);
+}
+
+#[tokio::test]
+async fn test_channel_reader_reports_upload_progress() {
+    // Chunks of 7 and 5 bytes, encoded from 100 and 33 input bytes
+    let (tx, rx) = tokio::sync::mpsc::channel(2);
+    let producer = tokio::spawn(async move {
+        for (len, input) in [(7usize, 100u64), (5, 33)] {
+            let chunk = TaggedChunk {
+                data: vec![0; len],
+                rows: input,
+                _tag: "",
+            };
+            tx.send(chunk).await.unwrap();
+        }
+    });
+
+    let progress = UploadProgress::new(Some(266));
+    let mut reader = ChannelReader::new(rx, Arc::new(AtomicU64::new(0)))
+        .with_progress(progress.clone(), |chunk: &TaggedChunk| chunk.rows);
+    // Tiny reads, so each chunk's input is handed out in parts
+    let mut buf = [0u8; 3];
+    while reader.read(&mut buf).await.unwrap() > 0 {
+        let (sent, input) = progress.sent();
+        assert!(input <= 133 && sent <= 12, "{} {}", sent, input);
+    }
+    producer.await.unwrap();
+
+    assert_eq!(progress.sent(), (12, 133));
+    let line = progress.line();
+    assert!(
+        line.starts_with("Uploaded 0.0 MB at ") && line.ends_with(", 50% of the input"),
+        "{}",
+        line
+    );
+    assert!(!UploadProgress::new(None).line().contains('%'));
+}
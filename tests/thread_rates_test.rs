@@ -0,0 +1,66 @@
+use es_duck::metrics;
+use es_duck::thread_rates::{self, ThreadTotals};
+use std::thread;
+
+#[test]
+fn test_registered_threads_count_their_own_rows() {
+    let workers: Vec<_> = (0..2u64)
+        .map(|i| {
+            thread::spawn(move || {
+                let _rates = thread_rates::register(format!("worker {}", i));
+                metrics::add_rows_loaded(10 * (i + 1));
+                metrics::add_bytes_uploaded(1000 * (i + 1));
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    // Unregistered threads only count towards the process-wide totals
+    metrics::add_rows_loaded(5);
+
+    let mut totals: Vec<ThreadTotals> = thread_rates::snapshot()
+        .into_iter()
+        .filter(|thread| thread.name.starts_with("worker"))
+        .collect();
+    totals.sort_by(|a, b| a.name.cmp(&b.name));
+    let totals: Vec<_> = totals
+        .iter()
+        .map(|t| (t.name.as_str(), t.rows, t.bytes, t.finished))
+        .collect();
+    assert_eq!(
+        totals,
+        vec![("worker 0", 10, 1000, true), ("worker 1", 20, 2000, true)]
+    );
+}
+
+#[test]
+fn test_report_marks_stalled_threads() {
+    let totals = |rows: [u64; 3], finished: [bool; 3]| -> Vec<ThreadTotals> {
+        (0..3)
+            .map(|i| ThreadTotals {
+                name: format!("Thread {}", i),
+                rows: rows[i],
+                bytes: rows[i] * 100,
+                finished: finished[i],
+            })
+            .collect()
+    };
+    let before = totals([100, 50, 70], [false; 3]);
+    let now = totals([300, 50, 70], [false, false, true]);
+    assert_eq!(
+        thread_rates::report(&before, &now, 2.0),
+        "Rates over the last 2.0s:\n  \
+         Thread 0: 100 rows/s, 0.0 MB/s\n  \
+         Thread 1: 0 rows/s, 0.0 MB/s, stalled\n  \
+         All threads: 100 rows/s, 0.0 MB/s"
+    );
+
+    // A thread registered since the last report counts from zero
+    let report = thread_rates::report(&before[..2], &now, 1.0);
+    assert!(
+        report.contains("All threads: 270 rows/s, 0.0 MB/s"),
+        "{}",
+        report
+    );
+}
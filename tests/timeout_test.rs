@@ -0,0 +1,67 @@
+use es_duck::timeout::{EXIT_CODE, Timeout, Watchdog};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// A watchdog over `timeout` that counts its cancels
+fn counting_watchdog(timeout: &Timeout) -> (Watchdog, Arc<AtomicUsize>) {
+    let cancels = Arc::new(AtomicUsize::new(0));
+    let counter = cancels.clone();
+    let watchdog = Watchdog::arm(timeout.start(), move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+    (watchdog, cancels)
+}
+
+#[test]
+fn test_check_and_report() {
+    assert!(Timeout::default().check().is_ok());
+    assert!(Timeout { secs: Some(5) }.check().is_ok());
+    assert_eq!(
+        Timeout { secs: Some(0) }.check(),
+        Err("--timeout must be at least 1 second".to_string())
+    );
+    // Where TIMING would go, with the exit code of timeout(1)
+    assert_eq!(Timeout { secs: Some(30) }.report(), "TIMEOUT: 30");
+    assert_eq!(EXIT_CODE, 124);
+}
+
+#[test]
+fn test_budget_remaining() {
+    assert_eq!(Timeout::default().start().remaining(), None);
+
+    // Only what the queries are charged uses it up, not the time in between
+    let mut budget = Timeout { secs: Some(60) }.start();
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(budget.remaining(), Some(Duration::from_secs(60)));
+    budget.charge(Duration::from_secs(45));
+    assert_eq!(budget.remaining(), Some(Duration::from_secs(15)));
+    budget.charge(Duration::from_secs(20));
+    assert_eq!(budget.remaining(), Some(Duration::ZERO));
+}
+
+#[test]
+fn test_watchdog_quiet_before_deadline() {
+    let (watchdog, cancels) = counting_watchdog(&Timeout { secs: Some(60) });
+    thread::sleep(Duration::from_millis(50));
+    assert!(!watchdog.disarm());
+    assert_eq!(cancels.load(Ordering::SeqCst), 0);
+
+    // Without --timeout nothing is ever cancelled
+    let (watchdog, cancels) = counting_watchdog(&Timeout::default());
+    assert!(!watchdog.disarm());
+    assert_eq!(cancels.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn test_watchdog_cancels_until_disarmed() {
+    let (watchdog, cancels) = counting_watchdog(&Timeout { secs: Some(1) });
+    // Past the deadline and a few retries
+    thread::sleep(Duration::from_millis(1350));
+    assert!(watchdog.disarm());
+    let fired = cancels.load(Ordering::SeqCst);
+    assert!(fired >= 2, "cancelled {} times", fired);
+    thread::sleep(Duration::from_millis(250));
+    assert_eq!(cancels.load(Ordering::SeqCst), fired);
+}
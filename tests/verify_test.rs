@@ -0,0 +1,222 @@
+use es_duck::collation::Collation;
+use es_duck::export::{OutputFormat, Writer};
+use es_duck::schema::{OrderBy, SortMode};
+use es_duck::verify::{self, RowIds, Verifier};
+use std::fs::{self, File};
+
+/// Writes `keys` (with a one-byte payload) to a file of `format`
+fn write_file(name: &str, format: OutputFormat, keys: &[&[u8]]) -> String {
+    let path = format!("/tmp/es_duck_verify_test_{}", name);
+    let mut writer = Writer::new(
+        File::create(&path).unwrap(),
+        format,
+        &["sort_key", "payload"],
+    )
+    .unwrap();
+    for key in keys {
+        writer.write_row(&[key, b"x"]).unwrap();
+    }
+    writer.finish().unwrap();
+    path
+}
+
+#[test]
+fn test_verifier_checks_order() {
+    let mut verifier = Verifier::new(true);
+    for key in [&b"a"[..], b"b", b"b", b"ba"] {
+        verifier.add(key).unwrap();
+    }
+    assert_eq!(verifier.rows(), 4);
+    assert_eq!(
+        verifier.add(b"az"),
+        Err("not sorted at row 4: key 617a follows 6261".to_string())
+    );
+
+    // Without the order check any keys count
+    let mut verifier = Verifier::new(false);
+    verifier.add(b"b").unwrap();
+    verifier.add(b"a").unwrap();
+    assert_eq!(verifier.rows(), 2);
+}
+
+#[test]
+fn test_verifier_checks_distinct() {
+    let mut verifier = Verifier::new(true).distinct();
+    for key in [&b"a"[..], b"b", b"ba"] {
+        verifier.add(key).unwrap();
+    }
+    assert_eq!(
+        verifier.add(b"ba"),
+        Err("not distinct at row 3: key 6261 repeats".to_string())
+    );
+    assert_eq!(
+        verifier.add(b"a"),
+        Err("not sorted at row 3: key 61 follows 6261".to_string())
+    );
+
+    let path = write_file("distinct.kvbin", OutputFormat::Kvbin, &[b"a", b"b", b"b"]);
+    let err = verify::verify_file_with(
+        path.as_ref(),
+        OutputFormat::Kvbin,
+        Verifier::new(true).distinct(),
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("distinct.kvbin: not distinct at row 2"),
+        "got: {}",
+        err
+    );
+    // Repeats are fine in a plain sort
+    assert_eq!(
+        verify::verify_file(path.as_ref(), OutputFormat::Kvbin, true).unwrap(),
+        3
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_verify_file() {
+    let path = write_file("sorted.kvbin", OutputFormat::Kvbin, &[b"a", b"b", b"c"]);
+    assert_eq!(
+        verify::verify_file(path.as_ref(), OutputFormat::Kvbin, true).unwrap(),
+        3
+    );
+    fs::remove_file(&path).unwrap();
+
+    let path = write_file("unsorted.csv", OutputFormat::Csv, &[b"a", b"c", b"b"]);
+    let err = verify::verify_file(path.as_ref(), OutputFormat::Csv, true).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("unsorted.csv: not sorted at row 2"),
+        "got: {}",
+        err
+    );
+    // Only counted, as with --order-by
+    assert_eq!(
+        verify::verify_file(path.as_ref(), OutputFormat::Csv, false).unwrap(),
+        3
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_expected_rows_and_report() {
+    assert_eq!(verify::expected_rows(100, None, 4), Some(100));
+    assert_eq!(verify::expected_rows(100, Some(10), 1), Some(10));
+    assert_eq!(verify::expected_rows(5, Some(10), 1), Some(5));
+    // A per-shard top-K keeps up to K rows of every shard
+    assert_eq!(verify::expected_rows(100, Some(10), 4), None);
+
+    assert_eq!(
+        verify::report(100, Some(100), true),
+        Ok("VERIFY: OK 100 rows (sorted, row count matches)".to_string())
+    );
+    assert_eq!(
+        verify::report(40, None, false),
+        Ok("VERIFY: OK 40 rows (order and row count not checked)".to_string())
+    );
+    assert_eq!(
+        verify::report(99, Some(100), true),
+        Err("--verify: the output holds 99 rows, the sort had 100".to_string())
+    );
+}
+
+#[test]
+fn test_check_runs_over_the_files_of_a_sort() {
+    let shards = [
+        write_file("check_0.csv", OutputFormat::Csv, &[b"a", b"c"]),
+        write_file("check_1.csv", OutputFormat::Csv, &[b"b", b"b"]),
+    ];
+    let read = |path: &std::path::Path, verifier| {
+        verify::verify_file_with(path, OutputFormat::Csv, verifier)
+    };
+    let mut check = verify::Check {
+        check_order: verify::Check::checks_order(&OrderBy::default(), None, true),
+        mode: SortMode::Sort,
+        sorted_rows: 4,
+        limit: None,
+        ids: None,
+    };
+    assert_eq!(
+        check.run(&shards, OutputFormat::Csv, read).unwrap(),
+        vec!["VERIFY: OK 4 rows (sorted, row count matches)".to_string()]
+    );
+    // The second file repeats its key
+    check.mode = SortMode::Distinct;
+    let err = check.run(&shards, OutputFormat::Csv, read).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("check_1.csv: not distinct at row 1"),
+        "got: {}",
+        err
+    );
+    // Under an ICU collation the files are only counted, as they are under
+    // a text key's own collation unless --collation binary overrides it
+    let icu = Collation::Icu("de".into());
+    let order_by = OrderBy::default();
+    assert!(!verify::Check::checks_order(&order_by, Some(&icu), true));
+    assert!(!verify::Check::checks_order(&order_by, None, false));
+    assert!(verify::Check::checks_order(
+        &order_by,
+        Some(&Collation::Binary),
+        false
+    ));
+    for path in &shards {
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn test_verifier_checks_row_ids() {
+    let rows: [(&[u8], u64); 5] = [(b"a", 4), (b"b", 1), (b"b", 3), (b"b", 2), (b"c", 0)];
+    let mut verifier = Verifier::new(true);
+    for (key, id) in rows {
+        verifier.add_row(key, Some(id)).unwrap();
+    }
+    // Id 2 follows id 3 under key b
+    assert_eq!(verifier.unstable_ties(), 1);
+    assert_eq!(verifier.row_ids(), Some(RowIds { rows: 5, sum: 10 }));
+    assert_eq!(
+        verifier.add_row(b"c", Some(0)),
+        Err("row id 0 repeats at row 5 under key 63".to_string())
+    );
+
+    // Per-shard files add up
+    let mut total = Verifier::default();
+    total.merge(&verifier);
+    total.merge(&verifier);
+    assert_eq!(total.rows(), 10);
+    assert_eq!(total.unstable_ties(), 2);
+    assert_eq!(total.row_ids(), Some(RowIds { rows: 10, sum: 20 }));
+
+    let expected = RowIds { rows: 5, sum: 10 };
+    assert_eq!(
+        verify::report_row_ids(&verifier, Some(expected), true),
+        Ok(Some(
+            "VERIFY_ROW_IDS: OK 5 ids (sum matches, 1 equal keys out of input order)".to_string()
+        ))
+    );
+    // Id 4 lost and id 0 doubled keep the count
+    let expected = RowIds { rows: 5, sum: 14 };
+    let err = verify::report_row_ids(&verifier, Some(expected), true).unwrap_err();
+    assert!(err.contains("sum to 10, the sort's to 14"), "got: {}", err);
+    // Without ids there is nothing to report, unless the sort had them
+    let plain = Verifier::new(true);
+    assert_eq!(verify::report_row_ids(&plain, None, true), Ok(None));
+    assert!(verify::report_row_ids(&plain, Some(expected), true).is_err());
+
+    // Read from a file that carries them
+    let path = "/tmp/es_duck_verify_test_row_ids.csv";
+    let columns = ["sort_key", "payload", "row_id"];
+    let mut writer = Writer::new(File::create(path).unwrap(), OutputFormat::Csv, &columns).unwrap();
+    for (key, id) in rows {
+        writer.write_row(&[key, b"x", &id.to_be_bytes()]).unwrap();
+    }
+    writer.finish().unwrap();
+    let read =
+        verify::verify_file_with(path.as_ref(), OutputFormat::Csv, Verifier::new(true)).unwrap();
+    assert_eq!(read.row_ids(), Some(RowIds { rows: 5, sum: 10 }));
+    assert_eq!(read.unstable_ties(), 1);
+    fs::remove_file(path).unwrap();
+}